@@ -33,6 +33,7 @@ pub fn derive_params(input: TokenStream) -> TokenStream {
     //       about the fields on the nested structs
     let mut params: Vec<Param> = Vec::new();
     let mut persistent_fields: Vec<PersistentField> = Vec::new();
+    let mut streamed_fields: Vec<PersistentField> = Vec::new();
     for field in fields.named {
         let field_name = match &field.ident {
             Some(ident) => ident,
@@ -131,6 +132,47 @@ pub fn derive_params(input: TokenStream) -> TokenStream {
                         .into()
                     }
                 };
+            } else if attr.path.is_ident("persist_streamed") {
+                match attr.parse_meta() {
+                    Ok(syn::Meta::NameValue(syn::MetaNameValue {
+                        lit: syn::Lit::Str(s),
+                        ..
+                    })) => {
+                        if processed_attribute {
+                            return syn::Error::new(
+                                attr.span(),
+                                "Duplicate or incompatible attribute found",
+                            )
+                            .to_compile_error()
+                            .into();
+                        }
+
+                        if streamed_fields.iter().any(|p| p.key == s) {
+                            return syn::Error::new(
+                                field.span(),
+                                "Multiple streamed persistent fields with the same key found",
+                            )
+                            .to_compile_error()
+                            .into();
+                        }
+
+                        streamed_fields.push(PersistentField {
+                            key: s,
+                            field: field_name.clone(),
+                        });
+
+                        processed_attribute = true;
+                    }
+                    _ => {
+                        return syn::Error::new(
+                            attr.span(),
+                            "The persist_streamed attribute should be a key-value pair with a \
+                             string argument: #[persist_streamed = \"foo_bar\"]",
+                        )
+                        .to_compile_error()
+                        .into()
+                    }
+                };
             } else if attr.path.is_ident("nested") {
                 // This one is more complicated. Supports an `array` attribute, an `id_prefix =
                 // "foo"` attribute, and a `group = "group name"` attribute. All are optional, and
@@ -412,6 +454,66 @@ pub fn derive_params(input: TokenStream) -> TokenStream {
         (serialize_fields_tokens, deserialize_fields_tokens)
     };
 
+    let streamed_fields_tokens = {
+        let streamed_fields_self_tokens = streamed_fields.into_iter().map(
+            |PersistentField { field, key }| {
+                quote! {
+                    streamed.push((
+                        String::from(#key),
+                        &self.#field as &dyn ::nih_plug::params::persist::StreamedPersistentField,
+                    ));
+                }
+            },
+        );
+
+        // The keys for nested objects get the same prefixes and suffixes as the regular persistent
+        // fields
+        let streamed_fields_nested_tokens = params
+            .iter()
+            .filter_map(|p| match p {
+                Param::Single { .. } => None,
+                Param::Nested(nested) => Some(nested),
+            })
+            .map(|nested| match nested {
+                NestedParams::Inline { field, .. } => {
+                    quote! { streamed.extend(self.#field.streamed_fields()); }
+                }
+                NestedParams::Prefixed {
+                    field, id_prefix, ..
+                } => quote! {
+                    let prefixed = self
+                        .#field
+                        .streamed_fields()
+                        .into_iter()
+                        .map(|(key, field)| (format!("{}_{}", #id_prefix, key), field));
+
+                    streamed.extend(prefixed);
+                },
+                NestedParams::Array { field, .. } => quote! {
+                    for (field_idx, field) in self.#field.iter().enumerate() {
+                        let idx = field_idx + 1;
+                        let suffixed = field
+                            .streamed_fields()
+                            .into_iter()
+                            .map(|(key, field)| (format!("{}_{}", key, idx), field));
+
+                        streamed.extend(suffixed);
+                    }
+                },
+            });
+
+        quote! {
+            #[allow(unused_mut)]
+            let mut streamed: Vec<(String, &dyn ::nih_plug::params::persist::StreamedPersistentField)> =
+                Vec::new();
+            #(#streamed_fields_self_tokens)*
+
+            #(#streamed_fields_nested_tokens)*
+
+            streamed
+        }
+    };
+
     quote! {
         unsafe impl #impl_generics Params for #struct_name #ty_generics #where_clause {
             fn param_map(&self) -> Vec<(String, nih_plug::prelude::ParamPtr, String)> {
@@ -425,6 +527,10 @@ pub fn derive_params(input: TokenStream) -> TokenStream {
             fn deserialize_fields(&self, serialized: &::std::collections::BTreeMap<String, String>) {
                 #deserialize_fields_tokens
             }
+
+            fn streamed_fields(&self) -> Vec<(String, &dyn ::nih_plug::params::persist::StreamedPersistentField)> {
+                #streamed_fields_tokens
+            }
         }
     }
     .into()
@@ -1,6 +1,7 @@
 use std::collections::BTreeMap;
 use std::sync::Mutex;
 
+use nih_plug::params::persist::{StreamedBytes, StreamedPersistentField};
 use nih_plug::prelude::*;
 
 #[derive(Params, Default)]
@@ -15,6 +16,20 @@ struct ArrayWrapperParams {
     pub inners: [InnerParams; 3],
 }
 
+#[derive(Params, Default)]
+struct StreamedParams {
+    #[persist_streamed = "samples"]
+    pub samples: StreamedBytes,
+}
+
+#[derive(Params, Default)]
+struct StreamedWrapperParams {
+    #[nested(id_prefix = "foo")]
+    pub inner: StreamedParams,
+    #[nested(array)]
+    pub inners: [StreamedParams; 2],
+}
+
 #[derive(Default)]
 struct InnerParams {
     /// The value `deserialize()` has been called with so we can check that the prefix has been
@@ -135,4 +150,36 @@ mod persist {
             }
         }
     }
+
+    mod streamed {
+        use super::super::*;
+
+        #[test]
+        fn keys() {
+            let params = StreamedWrapperParams::default();
+
+            let keys: Vec<String> = params
+                .streamed_fields()
+                .into_iter()
+                .map(|(key, _)| key)
+                .collect();
+            assert_eq!(keys, ["foo_samples", "samples_1", "samples_2"]);
+        }
+
+        #[test]
+        fn save_load_bytes() {
+            let params = StreamedParams::default();
+            params.samples.set(vec![1, 2, 3, 4]);
+
+            let mut saved = Vec::new();
+            params.samples.save(&mut saved).unwrap();
+
+            let restored = StreamedParams::default();
+            restored.samples.begin_load();
+            assert!(restored.samples.is_loading());
+            restored.samples.load(&mut saved.as_slice()).unwrap();
+            assert!(!restored.samples.is_loading());
+            assert_eq!(*restored.samples.read(), [1, 2, 3, 4]);
+        }
+    }
 }
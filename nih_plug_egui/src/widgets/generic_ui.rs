@@ -6,7 +6,7 @@ use std::sync::Arc;
 use egui::{TextStyle, Ui, Vec2};
 use nih_plug::prelude::{Param, ParamFlags, ParamPtr, ParamSetter, Params};
 
-use super::ParamSlider;
+use super::{ParamKnob, ParamSlider};
 
 /// A widget that can be used to create a generic UI with. This is used in conjuction with empty
 /// structs to emulate existential types.
@@ -31,6 +31,9 @@ pub trait ParamWidget {
 /// Create a generic UI using [`ParamSlider`]s.
 pub struct GenericSlider;
 
+/// Create a generic UI using [`ParamKnob`]s.
+pub struct GenericKnob;
+
 /// Create a scrollable generic UI using the specified widget. Takes up all the remaining vertical
 /// space.
 pub fn create(
@@ -70,3 +73,9 @@ impl ParamWidget for GenericSlider {
         ui.add(ParamSlider::for_param(param, setter).with_width(100.0));
     }
 }
+
+impl ParamWidget for GenericKnob {
+    fn add_widget<P: Param>(&self, ui: &mut Ui, param: &P, setter: &ParamSetter) {
+        ui.add(ParamKnob::for_param(param, setter));
+    }
+}
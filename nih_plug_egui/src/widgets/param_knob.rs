@@ -0,0 +1,205 @@
+use std::f32::consts::PI;
+
+use egui::{vec2, Pos2, Response, Sense, Shape, Stroke, Ui, Vec2, Widget};
+use lazy_static::lazy_static;
+use nih_plug::prelude::{Param, ParamSetter};
+
+use super::util;
+
+/// When dragging a knob, one pixel dragged vertically corresponds to this much change in the
+/// normalized parameter.
+const DRAG_MULTIPLIER: f32 = 0.005;
+/// When shift+dragging a knob, one pixel dragged corresponds to this much change in the normalized
+/// parameter.
+const GRANULAR_DRAG_MULTIPLIER: f32 = 0.0005;
+
+/// The angle of the knob's minimum value. Zero radians points to the right, and since the y-axis
+/// points down this is at the bottom left.
+const START_ANGLE: f32 = 0.75 * PI;
+/// The knob's range of motion in radians. The maximum value is at the bottom right.
+const SWEEP_ANGLE: f32 = 1.5 * PI;
+
+lazy_static! {
+    static ref DRAG_NORMALIZED_VALUE_MEMORY_ID: egui::Id = egui::Id::new((file!(), 0));
+}
+
+/// A rotary knob that knows about NIH-plug parameters. Dragging the knob up or down changes the
+/// value, shift+dragging allows for more granular changes, and double clicking or control clicking
+/// resets the parameter to its default value. Every change is surrounded by the begin and end
+/// gesture calls the host needs for automation recording.
+#[must_use = "You should put this widget in an ui with `ui.add(widget);`"]
+pub struct ParamKnob<'a, P: Param> {
+    param: &'a P,
+    setter: &'a ParamSetter<'a>,
+
+    draw_value: bool,
+    diameter: Option<f32>,
+}
+
+impl<'a, P: Param> ParamKnob<'a, P> {
+    /// Create a new knob for a parameter. Use the other methods to modify the knob before passing
+    /// it to [`Ui::add()`].
+    pub fn for_param(param: &'a P, setter: &'a ParamSetter<'a>) -> Self {
+        Self {
+            param,
+            setter,
+
+            draw_value: true,
+            diameter: None,
+        }
+    }
+
+    /// Don't draw the parameter's current value below the knob.
+    pub fn without_value(mut self) -> Self {
+        self.draw_value = false;
+        self
+    }
+
+    /// Set a custom diameter for the knob.
+    pub fn with_diameter(mut self, diameter: f32) -> Self {
+        self.diameter = Some(diameter);
+        self
+    }
+
+    fn normalized_value(&self) -> f32 {
+        self.param.modulated_normalized_value()
+    }
+
+    fn set_normalized_value(&self, normalized: f32) {
+        // This snaps to the nearest plain value if the parameter is stepped in some way
+        let value = self.param.preview_plain(normalized);
+        if value != self.param.modulated_plain_value() {
+            self.setter.set_parameter(self.param, value);
+        }
+    }
+
+    /// Reset the parameter to its default value. This starts and ends its own gesture when the knob
+    /// is not already being dragged.
+    fn reset_param(&self, dragging: bool) {
+        if !dragging {
+            self.setter.begin_set_parameter(self.param);
+        }
+        self.setter
+            .set_parameter(self.param, self.param.default_plain_value());
+        if !dragging {
+            self.setter.end_set_parameter(self.param);
+        }
+    }
+
+    fn knob_ui(&self, ui: &Ui, response: &mut Response) {
+        if response.drag_started() {
+            self.setter.begin_set_parameter(self.param);
+            ui.memory_mut(|mem| {
+                mem.data
+                    .insert_temp(*DRAG_NORMALIZED_VALUE_MEMORY_ID, self.normalized_value())
+            });
+        }
+        if response.dragged() {
+            // The unsnapped value is kept track of separately so stepped parameters can still be
+            // dragged through slowly
+            let multiplier = if ui.input(|i| i.modifiers.shift) {
+                GRANULAR_DRAG_MULTIPLIER
+            } else {
+                DRAG_MULTIPLIER
+            };
+            let drag_value = ui.memory(|mem| {
+                mem.data
+                    .get_temp(*DRAG_NORMALIZED_VALUE_MEMORY_ID)
+                    .unwrap_or_else(|| self.normalized_value())
+            });
+            let drag_value = (drag_value - (response.drag_delta().y * multiplier)).clamp(0.0, 1.0);
+            ui.memory_mut(|mem| {
+                mem.data
+                    .insert_temp(*DRAG_NORMALIZED_VALUE_MEMORY_ID, drag_value)
+            });
+
+            self.set_normalized_value(drag_value);
+            response.mark_changed();
+        }
+        if response.double_clicked() || (response.clicked() && ui.input(|i| i.modifiers.command)) {
+            self.reset_param(response.dragged());
+            response.mark_changed();
+        }
+        if response.drag_released() {
+            self.setter.end_set_parameter(self.param);
+        }
+
+        // And finally draw the thing
+        if ui.is_rect_visible(response.rect) {
+            let center = response.rect.center();
+            let stroke_width = (response.rect.width() * 0.1).max(2.0);
+            let radius = (response.rect.width() - stroke_width) / 2.0;
+
+            let track_color = ui.visuals().widgets.inactive.bg_fill;
+            let value_color = if response.dragged() {
+                util::add_hsv(ui.visuals().selection.bg_fill, 0.0, -0.1, 0.1)
+            } else {
+                ui.visuals().selection.bg_fill
+            };
+
+            let value_angle = START_ANGLE + (self.normalized_value() * SWEEP_ANGLE);
+            ui.painter().add(Shape::line(
+                arc_points(center, radius, START_ANGLE, START_ANGLE + SWEEP_ANGLE),
+                Stroke::new(stroke_width, track_color),
+            ));
+            if value_angle > START_ANGLE {
+                ui.painter().add(Shape::line(
+                    arc_points(center, radius, START_ANGLE, value_angle),
+                    Stroke::new(stroke_width, value_color),
+                ));
+            }
+
+            // A line pointing at the current value makes the knob readable without the arc
+            ui.painter().line_segment(
+                [
+                    center + (angle_to_vec(value_angle) * radius * 0.3),
+                    center + (angle_to_vec(value_angle) * radius),
+                ],
+                Stroke::new(
+                    stroke_width * 0.5,
+                    ui.visuals().widgets.active.fg_stroke.color,
+                ),
+            );
+        }
+    }
+}
+
+impl<P: Param> Widget for ParamKnob<'_, P> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let diameter = self
+            .diameter
+            .unwrap_or_else(|| ui.spacing().interact_size.y * 2.0);
+
+        ui.vertical_centered(|ui| {
+            let mut response = ui.allocate_response(Vec2::splat(diameter), Sense::click_and_drag());
+            self.knob_ui(ui, &mut response);
+            if self.draw_value {
+                ui.label(self.param.to_string());
+            }
+
+            response
+        })
+        .inner
+    }
+}
+
+/// A unit vector pointing in the direction of `angle`.
+fn angle_to_vec(angle: f32) -> Vec2 {
+    vec2(angle.cos(), angle.sin())
+}
+
+/// Points on an arc between two angles, for drawing with [`Shape::line()`].
+fn arc_points(center: Pos2, radius: f32, start_angle: f32, end_angle: f32) -> Vec<Pos2> {
+    // A full sweep is drawn using 48 line segments, which looks smooth at any reasonable size
+    let num_segments = (((end_angle - start_angle) / SWEEP_ANGLE) * 48.0)
+        .ceil()
+        .max(1.0) as usize;
+
+    (0..=num_segments)
+        .map(|i| {
+            let angle =
+                start_angle + ((end_angle - start_angle) * (i as f32 / num_segments as f32));
+            center + (angle_to_vec(angle) * radius)
+        })
+        .collect()
+}
@@ -8,10 +8,13 @@
 use nih_plug::prelude::ParamPtr;
 
 pub mod generic_ui;
+pub mod param_decibel_meter;
 pub mod param_slider;
 pub mod peak_meter;
 pub mod util;
 
+pub use generic_ui::GenericUi;
+pub use param_decibel_meter::ParamDecibelMeter;
 pub use param_slider::ParamSlider;
 pub use peak_meter::PeakMeter;
 
@@ -0,0 +1,110 @@
+//! A meter that displays a gain parameter's current value in decibels.
+
+use nih_plug::prelude::{util, Param};
+use std::time::Duration;
+
+use super::peak_meter::{self, PeakMeter};
+use crate::backend::Renderer;
+use crate::{layout, renderer, Element, Font, Layout, Length, Point, Rectangle, Widget};
+
+/// A read-only horizontal meter for a parameter containing a linear gain value, like an output
+/// parameter that reports a compressor's gain reduction, or a gain parameter created with
+/// [`FloatRange::Skewed`][nih_plug::prelude::FloatRange::Skewed] and
+/// [`formatters::v2s_f32_gain_to_db()`][nih_plug::prelude::formatters::v2s_f32_gain_to_db()]. The
+/// parameter's modulated value is converted to decibels and drawn the same way as a
+/// [`PeakMeter`]. This widget never changes the parameter's value.
+pub struct ParamDecibelMeter<'a, P: Param<Plain = f32>, Message> {
+    meter: PeakMeter<'a, Message>,
+
+    /// The parameter is only read when creating the meter, but it's kept around to tie the
+    /// widget's lifetime to the parameter.
+    _param: &'a P,
+}
+
+impl<'a, P: Param<Plain = f32>, Message> ParamDecibelMeter<'a, P, Message> {
+    /// Creates a new [`ParamDecibelMeter`] for the given parameter. The meter shares its state with
+    /// [`PeakMeter`].
+    pub fn new(state: &'a mut peak_meter::State, param: &'a P) -> Self {
+        Self {
+            meter: PeakMeter::new(state, util::gain_to_db(param.modulated_plain_value())),
+
+            _param: param,
+        }
+    }
+
+    /// Keep showing the peak value for a certain amount of time.
+    pub fn hold_time(mut self, time: Duration) -> Self {
+        self.meter = self.meter.hold_time(time);
+        self
+    }
+
+    /// Sets the width of the [`ParamDecibelMeter`].
+    pub fn width(mut self, width: Length) -> Self {
+        self.meter = self.meter.width(width);
+        self
+    }
+
+    /// Sets the height of the [`ParamDecibelMeter`].
+    pub fn height(mut self, height: Length) -> Self {
+        self.meter = self.meter.height(height);
+        self
+    }
+
+    /// Sets the text size of the [`ParamDecibelMeter`]'s ticks bar.
+    pub fn text_size(mut self, size: u16) -> Self {
+        self.meter = self.meter.text_size(size);
+        self
+    }
+
+    /// Sets the font of the [`ParamDecibelMeter`]'s ticks bar.
+    pub fn font(mut self, font: Font) -> Self {
+        self.meter = self.meter.font(font);
+        self
+    }
+}
+
+impl<'a, P: Param<Plain = f32>, Message> Widget<Message, Renderer>
+    for ParamDecibelMeter<'a, P, Message>
+where
+    Message: Clone,
+{
+    fn width(&self) -> Length {
+        Widget::<Message, Renderer>::width(&self.meter)
+    }
+
+    fn height(&self) -> Length {
+        Widget::<Message, Renderer>::height(&self.meter)
+    }
+
+    fn layout(&self, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        Widget::<Message, Renderer>::layout(&self.meter, renderer, limits)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+    ) {
+        Widget::<Message, Renderer>::draw(
+            &self.meter,
+            renderer,
+            style,
+            layout,
+            cursor_position,
+            viewport,
+        )
+    }
+}
+
+impl<'a, P: Param<Plain = f32>, Message> From<ParamDecibelMeter<'a, P, Message>>
+    for Element<'a, Message>
+where
+    Message: 'a + Clone,
+{
+    fn from(widget: ParamDecibelMeter<'a, P, Message>) -> Self {
+        Element::new(widget)
+    }
+}
@@ -26,12 +26,14 @@ const BORDER_WIDTH: f32 = 1.0;
 /// A slider that integrates with NIH-plug's [`Param`] types.
 ///
 /// TODO: There are currently no styling options at all
-/// TODO: Handle scrolling for steps (and shift+scroll for smaller steps?)
 pub struct ParamSlider<'a, P: Param> {
     state: &'a mut State,
 
     param: &'a P,
 
+    /// Whether the slider should respond to scroll wheel events.
+    use_scroll_wheel: bool,
+
     height: Length,
     width: Length,
     text_size: Option<u16>,
@@ -50,6 +52,9 @@ pub struct State {
     granular_drag_start_x_value: Option<(f32, f32)>,
     /// Track clicks for double clicks.
     last_click: Option<mouse::Click>,
+    /// The number of (fractional) scrolled lines that have not yet been turned into parameter
+    /// change events. This is needed to support trackpads with smooth scrolling.
+    scrolled_lines: f32,
 
     /// State for the text input overlay that will be shown when this widget is alt+clicked.
     text_input_state: AtomicRefCell<widget::text_input::State>,
@@ -105,6 +110,8 @@ impl<'a, P: Param> ParamSlider<'a, P> {
 
             param,
 
+            use_scroll_wheel: true,
+
             width: Length::Units(180),
             height: Length::Units(30),
             text_size: None,
@@ -112,6 +119,13 @@ impl<'a, P: Param> ParamSlider<'a, P> {
         }
     }
 
+    /// Don't respond to scroll wheel events. Useful when this slider is used as part of a scrolling
+    /// view.
+    pub fn disable_scroll_wheel(mut self) -> Self {
+        self.use_scroll_wheel = false;
+        self
+    }
+
     /// Sets the width of the [`ParamSlider`].
     pub fn width(mut self, width: Length) -> Self {
         self.width = width;
@@ -390,6 +404,51 @@ impl<'a, P: Param> Widget<ParamMessage, Renderer> for ParamSlider<'a, P> {
                     return event::Status::Captured;
                 }
             }
+            Event::Mouse(mouse::Event::WheelScrolled { delta })
+                if self.use_scroll_wheel && bounds.contains(cursor_position) =>
+            {
+                // With a regular scroll wheel the delta will only ever be -1 or 1 lines, but with
+                // smooth scrolling trackpads being a thing this could be anything. Pixel deltas are
+                // converted to lines using the slider's height.
+                self.state.scrolled_lines += match delta {
+                    mouse::ScrollDelta::Lines { y, .. } => y,
+                    mouse::ScrollDelta::Pixels { y, .. } => y / bounds.height.max(1.0),
+                };
+
+                if self.state.scrolled_lines.abs() >= 1.0 {
+                    let use_finer_steps = self.state.keyboard_modifiers.shift();
+
+                    // Scrolling while dragging needs to be taken into account here
+                    if !self.state.drag_active {
+                        shell.publish(ParamMessage::BeginSetParameter(self.param.as_ptr()));
+                    }
+
+                    // The messages are only handled after this event, so the new value needs to be
+                    // tracked here
+                    let mut current_value = self.param.unmodulated_normalized_value();
+
+                    while self.state.scrolled_lines >= 1.0 {
+                        current_value = self
+                            .param
+                            .next_normalized_step(current_value, use_finer_steps);
+                        self.state.scrolled_lines -= 1.0;
+                    }
+
+                    while self.state.scrolled_lines <= -1.0 {
+                        current_value = self
+                            .param
+                            .previous_normalized_step(current_value, use_finer_steps);
+                        self.state.scrolled_lines += 1.0;
+                    }
+
+                    self.set_normalized_value(shell, current_value);
+                    if !self.state.drag_active {
+                        shell.publish(ParamMessage::EndSetParameter(self.param.as_ptr()));
+                    }
+                }
+
+                return event::Status::Captured;
+            }
             Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
                 self.state.keyboard_modifiers = modifiers;
 
@@ -0,0 +1,214 @@
+//! An [`Editor`] implementation for software rendered framebuffers.
+
+use baseview::{
+    Event, EventStatus, Size, Window, WindowEvent, WindowHandle, WindowHandler, WindowOpenOptions,
+    WindowScalePolicy,
+};
+use crossbeam::atomic::AtomicCell;
+use nih_plug::nih_error;
+use nih_plug::prelude::{Editor, GuiContext, ParamSetter, ParentWindowHandle};
+use parking_lot::RwLock;
+use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+use softbuffer::GraphicsContext;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::{Framebuffer, SoftbufferState};
+
+type DrawFn<T> = dyn Fn(&mut Framebuffer, &ParamSetter, &mut T) + 'static + Send + Sync;
+type EventFn<T> = dyn Fn(&Event, &ParamSetter, &mut T) -> EventStatus + 'static + Send + Sync;
+
+/// An [`Editor`] implementation that blits a pixel framebuffer to a baseview window.
+pub(crate) struct SoftbufferEditor<T> {
+    pub(crate) softbuffer_state: Arc<SoftbufferState>,
+    /// The plugin's state. This is kept in between editor openenings.
+    pub(crate) user_state: Arc<RwLock<T>>,
+
+    /// The user's draw function. Called on every frame.
+    pub(crate) draw: Arc<DrawFn<T>>,
+    /// The user's event handler.
+    pub(crate) on_event: Arc<EventFn<T>>,
+
+    /// The scaling factor reported by the host, if any. On macOS this will never be set and we
+    /// should use the system scaling factor instead.
+    pub(crate) scaling_factor: AtomicCell<Option<f32>>,
+}
+
+impl<T> Editor for SoftbufferEditor<T>
+where
+    T: 'static + Send + Sync,
+{
+    fn spawn(
+        &self,
+        parent: ParentWindowHandle,
+        context: Arc<dyn GuiContext>,
+    ) -> Box<dyn std::any::Any + Send> {
+        let draw = self.draw.clone();
+        let on_event = self.on_event.clone();
+        let user_state = self.user_state.clone();
+
+        let (unscaled_width, unscaled_height) = self.softbuffer_state.size();
+        let scaling_factor = self.scaling_factor.load();
+        let window = Window::open_parented(
+            &parent,
+            WindowOpenOptions {
+                title: String::from("softbuffer window"),
+                // Baseview should be doing the DPI scaling for us
+                size: Size::new(unscaled_width as f64, unscaled_height as f64),
+                scale: scaling_factor
+                    .map(|factor| WindowScalePolicy::ScaleFactor(factor as f64))
+                    .unwrap_or(WindowScalePolicy::SystemScaleFactor),
+
+                gl_config: None,
+            },
+            move |window| {
+                // The graphics context needs to outlive this borrow of the window, so it's created
+                // from the window's raw handle instead
+                let graphics_context =
+                    match unsafe { GraphicsContext::new(RawHandle(window.raw_window_handle())) } {
+                        Ok(graphics_context) => Some(graphics_context),
+                        Err(_) => {
+                            nih_error!("Could not create the softbuffer context, not drawing");
+                            None
+                        }
+                    };
+
+                // This is updated when the window sends its first resize event
+                let scale_factor = scaling_factor.unwrap_or(1.0) as f64;
+                SoftbufferWindowHandler {
+                    context,
+                    draw,
+                    on_event,
+                    user_state,
+
+                    graphics_context,
+                    pixels: Vec::new(),
+                    physical_size: (
+                        (unscaled_width as f64 * scale_factor).round() as usize,
+                        (unscaled_height as f64 * scale_factor).round() as usize,
+                    ),
+                    scale_factor,
+                }
+            },
+        );
+
+        self.softbuffer_state.open.store(true, Ordering::Release);
+        Box::new(SoftbufferEditorHandle {
+            softbuffer_state: self.softbuffer_state.clone(),
+            window,
+        })
+    }
+
+    fn size(&self) -> (u32, u32) {
+        self.softbuffer_state.size()
+    }
+
+    fn set_scale_factor(&self, factor: f32) -> bool {
+        // If the editor is currently open then the host must not change the current HiDPI scale as
+        // we don't have a way to handle that. Ableton Live does this.
+        if self.softbuffer_state.is_open() {
+            return false;
+        }
+
+        self.scaling_factor.store(Some(factor));
+        true
+    }
+
+    fn param_value_changed(&self, _id: &str, _normalized_value: f32) {
+        // The framebuffer is redrawn on every frame, so there's nothing to do here
+    }
+
+    fn param_modulation_changed(&self, _id: &str, _modulation_offset: f32) {}
+
+    fn param_values_changed(&self) {
+        // Same
+    }
+}
+
+/// Allows creating a softbuffer context from a window handle that outlives baseview's borrowed
+/// window. The handle stays valid for as long as the window is open, and the context is dropped
+/// together with the window handler.
+struct RawHandle(RawWindowHandle);
+
+unsafe impl HasRawWindowHandle for RawHandle {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        self.0
+    }
+}
+
+/// The baseview window handler that draws the framebuffer and forwards events to the plugin.
+struct SoftbufferWindowHandler<T> {
+    context: Arc<dyn GuiContext>,
+    draw: Arc<DrawFn<T>>,
+    on_event: Arc<EventFn<T>>,
+    user_state: Arc<RwLock<T>>,
+
+    /// The softbuffer context used to blit the framebuffer, if it could be created.
+    graphics_context: Option<GraphicsContext<RawHandle>>,
+    /// The framebuffer's pixels. Resized whenever the window's physical size changes.
+    pixels: Vec<u32>,
+    /// The window's current size in physical pixels.
+    physical_size: (usize, usize),
+    /// The ratio between physical and logical pixels.
+    scale_factor: f64,
+}
+
+impl<T> WindowHandler for SoftbufferWindowHandler<T> {
+    fn on_frame(&mut self, _window: &mut Window) {
+        let graphics_context = match &mut self.graphics_context {
+            Some(graphics_context) => graphics_context,
+            None => return,
+        };
+
+        // softbuffer's sizes are limited to 16-bit integers
+        let (width, height) = self.physical_size;
+        let (width, height) = (width.min(u16::MAX as usize), height.min(u16::MAX as usize));
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        self.pixels.resize(width * height, 0);
+        let setter = ParamSetter::new(self.context.as_ref());
+        (self.draw)(
+            &mut Framebuffer {
+                pixels: &mut self.pixels,
+                width,
+                height,
+                scale_factor: self.scale_factor,
+            },
+            &setter,
+            &mut self.user_state.write(),
+        );
+
+        graphics_context.set_buffer(&self.pixels, width as u16, height as u16);
+    }
+
+    fn on_event(&mut self, _window: &mut Window, event: Event) -> EventStatus {
+        if let Event::Window(WindowEvent::Resized(window_info)) = &event {
+            let physical_size = window_info.physical_size();
+            self.physical_size = (physical_size.width as usize, physical_size.height as usize);
+            self.scale_factor = window_info.scale();
+        }
+
+        let setter = ParamSetter::new(self.context.as_ref());
+        (self.on_event)(&event, &setter, &mut self.user_state.write())
+    }
+}
+
+/// The window handle used for [`SoftbufferEditor`].
+struct SoftbufferEditorHandle {
+    softbuffer_state: Arc<SoftbufferState>,
+    window: WindowHandle,
+}
+
+/// The window handle enum stored within 'WindowHandle' contains raw pointers. Is there a way around
+/// having this requirement?
+unsafe impl Send for SoftbufferEditorHandle {}
+
+impl Drop for SoftbufferEditorHandle {
+    fn drop(&mut self) {
+        self.softbuffer_state.open.store(false, Ordering::Release);
+        // XXX: This should automatically happen when the handle gets dropped, but apparently not
+        self.window.close();
+    }
+}
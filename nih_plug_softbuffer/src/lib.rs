@@ -0,0 +1,147 @@
+//! A minimal software rendered editor for NIH-plug. The plugin draws into a pixel framebuffer,
+//! which is blitted to the editor's window using [softbuffer](https://docs.rs/softbuffer). Input
+//! events are passed to the plugin as raw [`baseview`] events.
+//!
+//! This is useful for plugins that want to do fully custom rendering without pulling in a GUI
+//! framework.
+
+use crossbeam::atomic::AtomicCell;
+use nih_plug::params::persist::PersistentField;
+use nih_plug::prelude::{Editor, ParamSetter};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Re-export for convenience, the input events are passed to the plugin as [`baseview::Event`]s.
+pub use baseview;
+pub use baseview::EventStatus;
+
+mod editor;
+
+/// Create an [`Editor`] instance backed by a pixel framebuffer. `draw` is called on every frame to
+/// fill the [`Framebuffer`], and `on_event` is called for every mouse, keyboard, and window event
+/// the editor's window receives. Both functions receive a [`ParamSetter`] for changing parameter
+/// values and the user state, which can be used to store GUI-only state like the current mouse
+/// position or the parameter that's being dragged.
+///
+/// The [`SoftbufferState`] passed to this function contains the GUI's size. You can also use this
+/// to know if the GUI is open, so you can avoid performing potentially expensive calculations
+/// while the GUI is not open. If you want this size to be persisted when restoring a plugin
+/// instance, then you can store it in a `#[persist = "key"]` field on your parameters struct.
+pub fn create_softbuffer_editor<T, D, E>(
+    softbuffer_state: Arc<SoftbufferState>,
+    user_state: T,
+    draw: D,
+    on_event: E,
+) -> Option<Box<dyn Editor>>
+where
+    T: 'static + Send + Sync,
+    D: Fn(&mut Framebuffer, &ParamSetter, &mut T) + 'static + Send + Sync,
+    E: Fn(&baseview::Event, &ParamSetter, &mut T) -> EventStatus + 'static + Send + Sync,
+{
+    Some(Box::new(editor::SoftbufferEditor {
+        softbuffer_state,
+        user_state: Arc::new(RwLock::new(user_state)),
+        draw: Arc::new(draw),
+        on_event: Arc::new(on_event),
+
+        // TODO: We can't get the size of the window when baseview does its own scaling, so if the
+        //       host does not set a scale factor on Windows or Linux we should just use a factor of
+        //       1. That may make the GUI tiny but it also prevents it from getting cut off.
+        #[cfg(target_os = "macos")]
+        scaling_factor: AtomicCell::new(None),
+        #[cfg(not(target_os = "macos"))]
+        scaling_factor: AtomicCell::new(Some(1.0)),
+    }))
+}
+
+/// The pixels for the current frame. Pixels are stored row by row, starting at the top left
+/// corner. Every pixel is a `u32` in the `0RGB` format, so the upper eight bits are ignored, and
+/// the red, green, and blue components are stored in the following three bytes. Use [`rgb()`] to
+/// create a pixel value.
+pub struct Framebuffer<'a> {
+    /// The pixels, `width * height` in total.
+    pub pixels: &'a mut [u32],
+    /// The framebuffer's width in physical pixels.
+    pub width: usize,
+    /// The framebuffer's height in physical pixels.
+    pub height: usize,
+    /// The ratio between physical and logical pixels. The framebuffer's size is the editor's
+    /// logical size multiplied by this value.
+    pub scale_factor: f64,
+}
+
+impl Framebuffer<'_> {
+    /// Fill the entire framebuffer with a single color.
+    pub fn clear(&mut self, color: u32) {
+        self.pixels.fill(color);
+    }
+
+    /// Set a single pixel. Coordinates outside of the framebuffer are ignored.
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: u32) {
+        if x < self.width && y < self.height {
+            self.pixels[(y * self.width) + x] = color;
+        }
+    }
+
+    /// Get a single row of pixels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `y` is out of bounds.
+    pub fn row_mut(&mut self, y: usize) -> &mut [u32] {
+        &mut self.pixels[y * self.width..(y + 1) * self.width]
+    }
+}
+
+/// Create a pixel value for a [`Framebuffer`] from its red, green, and blue components.
+pub const fn rgb(red: u8, green: u8, blue: u8) -> u32 {
+    ((red as u32) << 16) | ((green as u32) << 8) | blue as u32
+}
+
+/// State for an `nih_plug_softbuffer` editor.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SoftbufferState {
+    /// The window's size in logical pixels before applying the DPI scaling factor.
+    #[serde(with = "nih_plug::params::persist::serialize_atomic_cell")]
+    size: AtomicCell<(u32, u32)>,
+    /// Whether the editor's window is currently open.
+    #[serde(skip)]
+    open: AtomicBool,
+}
+
+impl<'a> PersistentField<'a, SoftbufferState> for Arc<SoftbufferState> {
+    fn set(&self, new_value: SoftbufferState) {
+        self.size.store(new_value.size.load());
+    }
+
+    fn map<F, R>(&self, f: F) -> R
+    where
+        F: Fn(&SoftbufferState) -> R,
+    {
+        f(self)
+    }
+}
+
+impl SoftbufferState {
+    /// Initialize the GUI's state. This value can be passed to [`create_softbuffer_editor()`]. The
+    /// window size is in logical pixels, so before it is multiplied by the DPI scaling factor.
+    pub fn from_size(width: u32, height: u32) -> Arc<SoftbufferState> {
+        Arc::new(SoftbufferState {
+            size: AtomicCell::new((width, height)),
+            open: AtomicBool::new(false),
+        })
+    }
+
+    /// Returns a `(width, height)` pair for the current size of the GUI in logical pixels.
+    pub fn size(&self) -> (u32, u32) {
+        self.size.load()
+    }
+
+    /// Whether the GUI is currently visible.
+    // Called `is_open()` instead of `open()` to avoid the ambiguity.
+    pub fn is_open(&self) -> bool {
+        self.open.load(Ordering::Acquire)
+    }
+}
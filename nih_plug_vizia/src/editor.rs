@@ -8,8 +8,9 @@ use std::sync::Arc;
 use vizia::context::backend::TextConfig;
 use vizia::prelude::*;
 
+use crate::hot_reload::StylesheetWatcherEvent;
 use crate::widgets::RawParamEvent;
-use crate::{assets, widgets, ViziaState, ViziaTheming};
+use crate::{assets, widgets, ViziaPalette, ViziaState, ViziaTheming};
 
 /// An [`Editor`] implementation that calls an vizia draw loop.
 pub(crate) struct ViziaEditor {
@@ -56,6 +57,10 @@ impl Editor for ViziaEditor {
                 // There doesn't seem to be any way to bundle styles with a widget, so we'll always
                 // include the style sheet for our custom widgets at context creation
                 widgets::register_theme(cx);
+
+                // The colors for both style sheets are defined separately so they can be swapped
+                // out by the plugin
+                ViziaPalette::LIGHT.apply(cx);
             }
 
             // Any widget can change the parameters by emitting `ParamEvent` events. This model will
@@ -75,6 +80,7 @@ impl Editor for ViziaEditor {
                     current_inner_window_size.width,
                     current_inner_window_size.height,
                 )),
+                scrolled_lines: 0.0,
             }
             .build(cx);
 
@@ -103,6 +109,15 @@ impl Editor for ViziaEditor {
                             .propagate(Propagation::Subtree),
                     );
                 }
+
+                // Style sheets added through `watch_stylesheet()` are only reloaded in debug builds
+                if cfg!(debug_assertions) {
+                    cx.emit_custom(
+                        Event::new(StylesheetWatcherEvent::Poll)
+                            .target(Entity::root())
+                            .propagate(Propagation::Direct),
+                    );
+                }
             }
         });
 
@@ -0,0 +1,100 @@
+//! Live reloading for style sheets loaded from disk, to speed up iterating on a GUI's styling.
+
+use nih_plug::nih_error;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use vizia::prelude::*;
+
+/// How often the watched style sheets are checked for modifications.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Load a CSS style sheet from disk, and in debug builds, reload all of the editor's style sheets
+/// whenever that file is modified. This lets you tweak a GUI's styling inside of a running host
+/// without having to rebuild and reload the plugin. Call this from your app function after
+/// registering any other themes:
+///
+/// ```ignore
+/// create_vizia_editor(editor_state, ViziaTheming::Custom, move |cx, _| {
+///     #[cfg(debug_assertions)]
+///     nih_plug_vizia::watch_stylesheet(cx, concat!(env!("CARGO_MANIFEST_DIR"), "/src/editor.css"));
+///     #[cfg(not(debug_assertions))]
+///     cx.add_theme(include_str!("editor.css"));
+///
+///     // ...
+/// })
+/// ```
+///
+/// The path is read at runtime, so it should be an absolute path that exists on the machine
+/// running the plugin. That makes this mostly useful during development, and release builds
+/// should embed their styles using `cx.add_theme(include_str!(...))` instead. In release builds
+/// the style sheet is still loaded, but it is not watched for changes.
+pub fn watch_stylesheet(cx: &mut Context, path: impl AsRef<Path>) {
+    let path = path.as_ref();
+    if let Err(err) = cx.add_stylesheet(path) {
+        nih_error!(
+            "Could not load the style sheet at '{}': {}",
+            path.display(),
+            err
+        );
+        return;
+    }
+
+    if cfg!(debug_assertions) {
+        StylesheetWatcher {
+            path: path.to_owned(),
+            last_modified: modified_time(path),
+            last_poll: Instant::now(),
+        }
+        .build(cx);
+    }
+}
+
+/// Polls a style sheet's modification time, and reloads all style sheets when it changes. These are
+/// polled from the editor's idle callback through [`StylesheetWatcherEvent::Poll`].
+struct StylesheetWatcher {
+    path: PathBuf,
+    /// The file's modification time when it was last loaded. `None` if that could not be
+    /// determined, for instance because the file is being written to.
+    last_modified: Option<SystemTime>,
+    /// When the file was last checked, to avoid hitting the file system every frame.
+    last_poll: Instant,
+}
+
+/// Sent to the root entity every idle tick in debug builds.
+pub(crate) enum StylesheetWatcherEvent {
+    /// Check whether any watched style sheets have changed.
+    Poll,
+}
+
+impl Model for StylesheetWatcher {
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|watcher_event, _| match watcher_event {
+            StylesheetWatcherEvent::Poll => {
+                let now = Instant::now();
+                if now < self.last_poll + POLL_INTERVAL {
+                    return;
+                }
+                self.last_poll = now;
+
+                let last_modified = modified_time(&self.path);
+                if last_modified.is_some() && last_modified != self.last_modified {
+                    self.last_modified = last_modified;
+                    if let Err(err) = cx.reload_styles() {
+                        nih_error!(
+                            "Could not reload the style sheet at '{}': {}",
+                            self.path.display(),
+                            err
+                        );
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// The modification time of a file, if it exists and the platform supports it.
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
@@ -17,9 +17,14 @@ pub use vizia;
 
 pub mod assets;
 mod editor;
+mod hot_reload;
+mod palette;
 pub mod vizia_assets;
 pub mod widgets;
 
+pub use hot_reload::watch_stylesheet;
+pub use palette::ViziaPalette;
+
 /// Create an [`Editor`] instance using a [`vizia`][::vizia] GUI. The [`ViziaState`] passed to this
 /// function contains the GUI's intitial size, and this is kept in sync whenever the GUI gets
 /// resized. You can also use this to know if the GUI is open, so you can avoid performing
@@ -78,13 +83,17 @@ pub enum ViziaTheming {
     Builtin,
     /// Apply `nih_plug_vizia`'s custom theming. This is the default. You **need** to call
     /// [`nih_plug_vizia::assets::register_noto_sans_light()`][assets::register_noto_sans_light()]
-    /// at the start of your app function for the font to work correctly.
+    /// at the start of your app function for the font to work correctly. This uses the
+    /// [`ViziaPalette::LIGHT`] color palette, which can be changed by applying another
+    /// [`ViziaPalette`].
     #[default]
     Custom,
 }
 
 /// State for an `nih_plug_vizia` editor. The scale factor can be manipulated at runtime using
-/// `cx.set_user_scale_factor()`.
+/// `cx.set_user_scale_factor()` or by emitting
+/// [`GuiContextEvent::SetUserScaleFactor`][widgets::GuiContextEvent::SetUserScaleFactor]. Users
+/// can also change it by scrolling while holding Ctrl (or Cmd on macOS).
 #[derive(Serialize, Deserialize)]
 pub struct ViziaState {
     /// A function that returns the window's current size in logical pixels, before any sort of
@@ -0,0 +1,168 @@
+//! Color palettes for `nih_plug_vizia`'s theme and widgets.
+
+use vizia::prelude::*;
+
+/// The colors used by `nih_plug_vizia`'s theme and by all of its widgets. The layout and sizing
+/// of the widgets are defined in regular style sheets, and since vizia does not support CSS
+/// variables, a palette turns these colors into a style sheet that's applied on top of that. When
+/// using [`ViziaTheming::Custom`][crate::ViziaTheming::Custom], [`ViziaPalette::LIGHT`] is applied
+/// automatically. Call [`apply()`][Self::apply()] at the start of your app function to use a
+/// different palette:
+///
+/// ```ignore
+/// create_vizia_editor(editor_state, ViziaTheming::Custom, move |cx, _| {
+///     ViziaPalette {
+///         modulation: 0xf5a623ff,
+///         ..ViziaPalette::DARK
+///     }
+///     .apply(cx);
+///
+///     // ...
+/// })
+/// ```
+///
+/// Every color is an `0xRRGGBBAA` value. Individual rules can still be overridden with a regular
+/// style sheet added after the palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ViziaPalette {
+    /// The window's background color.
+    pub background: u32,
+    /// The default text color. Also used for the caret in text inputs and for knobs' values.
+    pub text: u32,
+    /// Text selections in text inputs.
+    pub text_selection: u32,
+    /// Borders around sliders, buttons, and meters, and the peak meter's ticks.
+    pub border: u32,
+    /// The filled portion of sliders, and knobs' tracks.
+    pub fill: u32,
+    /// Host modulation on sliders and knobs. Sliders draw this at around 40% opacity.
+    pub modulation: u32,
+    /// The background for hovered and active sliders. This should be mostly transparent.
+    pub highlight: u32,
+    /// The background for active buttons. Hovered buttons use this color at half opacity.
+    pub button_checked: u32,
+    /// The background for active bypass buttons. Hovered bypass buttons use this color at low
+    /// opacity.
+    pub bypass: u32,
+    /// The resize handle in the bottom right corner.
+    pub resize_handle: u32,
+    /// The track for scroll bars.
+    pub scrollbar: u32,
+    /// A scroll bar's thumb.
+    pub scrollbar_thumb: u32,
+    /// A scroll bar's thumb when it's hovered.
+    pub scrollbar_thumb_hover: u32,
+}
+
+impl Default for ViziaPalette {
+    fn default() -> Self {
+        Self::LIGHT
+    }
+}
+
+impl ViziaPalette {
+    /// The default light palette. These are the colors `nih_plug_vizia` has always used.
+    pub const LIGHT: ViziaPalette = ViziaPalette {
+        background: 0xfafafaff,
+        text: 0x0a0a0aff,
+        text_selection: 0x0a0a0a30,
+        border: 0x0a0a0aff,
+        fill: 0xc4c4c4ff,
+        modulation: 0xa4eafcff,
+        highlight: 0x8080801a,
+        button_checked: 0xd0d0d0ff,
+        bypass: 0xffcfcbff,
+        resize_handle: 0x696969ff,
+        scrollbar: 0xdadadaff,
+        scrollbar_thumb: 0x5d5d5dff,
+        scrollbar_thumb_hover: 0x808080ff,
+    };
+
+    /// A dark palette with the same contrast as [`LIGHT`][Self::LIGHT].
+    pub const DARK: ViziaPalette = ViziaPalette {
+        background: 0x1e1e1eff,
+        text: 0xe6e6e6ff,
+        text_selection: 0xe6e6e640,
+        border: 0x8c8c8cff,
+        fill: 0x4f4f4fff,
+        modulation: 0x3d9cb8ff,
+        highlight: 0xffffff14,
+        button_checked: 0x5a5a5aff,
+        bypass: 0x8c3d37ff,
+        resize_handle: 0xa0a0a0ff,
+        scrollbar: 0x2e2e2eff,
+        scrollbar_thumb: 0x8a8a8aff,
+        scrollbar_thumb_hover: 0xa8a8a8ff,
+    };
+
+    /// Apply this palette to the editor. This adds a style sheet that overrides the colors set by
+    /// any palettes that were applied before it.
+    pub fn apply(&self, cx: &mut Context) {
+        cx.add_theme(&self.to_css());
+    }
+
+    /// Generate the style sheet for this palette.
+    pub fn to_css(&self) -> String {
+        let text = css_color(self.text);
+        let border = css_color(self.border);
+        let fill = css_color(self.fill);
+        let modulation = css_color(self.modulation);
+        let highlight = css_color(self.highlight);
+
+        format!(
+            ":root {{ background-color: {background}; color: {text}; }}
+
+scrollview scrollbar {{ background-color: {scrollbar}; }}
+scrollview scrollbar .thumb {{ background-color: {scrollbar_thumb}; }}
+scrollview scrollbar .thumb:hover {{ background-color: {scrollbar_thumb_hover}; }}
+
+param-button {{ border-color: {border}; background-color: {button_transparent}; }}
+param-button:hover {{ background-color: {button_hover}; }}
+param-button:checked {{ background-color: {button_checked}; }}
+param-button.bypass {{ background-color: {bypass_transparent}; }}
+param-button.bypass:hover {{ background-color: {bypass_hover}; }}
+param-button.bypass:checked {{ background-color: {bypass}; }}
+
+param-knob .knob__track {{ border-color: {fill}; }}
+param-knob .knob__value {{ border-color: {text}; }}
+param-knob .knob__modulation {{ border-color: {modulation}; }}
+
+param-slider {{ border-color: {border}; }}
+param-slider:active {{ background-color: {highlight}; }}
+param-slider:hover {{ background-color: {highlight}; }}
+param-slider .fill {{ background-color: {fill}; }}
+param-slider .fill--modulation {{ background-color: {slider_modulation}; }}
+param-slider .value-entry .caret {{ background-color: {text}; }}
+param-slider .value-entry .selection {{ background-color: {text_selection}; }}
+
+peak-meter .bar {{ border-color: {border}; }}
+peak-meter .ticks__tick {{ background-color: {border}; }}
+
+resize-handle {{ color: {resize_handle}; }}
+",
+            background = css_color(self.background),
+            scrollbar = css_color(self.scrollbar),
+            scrollbar_thumb = css_color(self.scrollbar_thumb),
+            scrollbar_thumb_hover = css_color(self.scrollbar_thumb_hover),
+            button_transparent = css_color(with_alpha(self.button_checked, 0x00)),
+            button_hover = css_color(with_alpha(self.button_checked, 0x80)),
+            button_checked = css_color(self.button_checked),
+            bypass_transparent = css_color(with_alpha(self.bypass, 0x00)),
+            bypass_hover = css_color(with_alpha(self.bypass, 0x20)),
+            bypass = css_color(self.bypass),
+            slider_modulation = css_color(with_alpha(self.modulation, 0x69)),
+            text_selection = css_color(self.text_selection),
+            resize_handle = css_color(self.resize_handle),
+        )
+    }
+}
+
+/// Format an `0xRRGGBBAA` color as a CSS hex color.
+fn css_color(color: u32) -> String {
+    format!("#{color:08x}")
+}
+
+/// Replace an `0xRRGGBBAA` color's alpha component.
+fn with_alpha(color: u32, alpha: u8) -> u32 {
+    (color & 0xffffff00) | alpha as u32
+}
@@ -6,29 +6,42 @@
 //! to copy the widgets and modify them to your personal taste.
 
 use crossbeam::atomic::AtomicCell;
-use nih_plug::nih_debug_assert_eq;
-use nih_plug::prelude::{GuiContext, Param, ParamPtr};
+use nih_plug::prelude::{GuiContext, Param, ParamPtr, PluginState};
+use nih_plug::{nih_debug_assert_eq, nih_error, nih_log};
 use std::sync::Arc;
 use vizia::prelude::*;
 
 use super::ViziaState;
 
+mod ab_toggle;
+mod custom_canvas;
 mod generic_ui;
+mod io_strip;
+mod loudness_meter;
+mod mod_matrix;
 pub mod param_base;
 mod param_button;
+mod param_knob;
 mod param_slider;
 mod peak_meter;
 mod resize_handle;
 pub mod util;
 
+pub use ab_toggle::AbToggle;
+pub use custom_canvas::{CanvasSize, CustomCanvas};
 pub use generic_ui::GenericUi;
+pub use io_strip::IoStrip;
+pub use loudness_meter::LoudnessMeter;
+pub use mod_matrix::ModMatrix;
 pub use param_button::{ParamButton, ParamButtonExt};
+pub use param_knob::{ParamKnob, ParamKnobExt};
 pub use param_slider::{ParamSlider, ParamSliderExt, ParamSliderStyle};
 pub use peak_meter::PeakMeter;
 pub use resize_handle::ResizeHandle;
 
 /// Register the default theme for the widgets exported by this module. This is automatically called
-/// for you when using [`create_vizia_editor()`][super::create_vizia_editor()].
+/// for you when using [`create_vizia_editor()`][super::create_vizia_editor()]. This only contains
+/// the widgets' layout, their colors are defined by a [`ViziaPalette`][super::ViziaPalette].
 pub fn register_theme(cx: &mut Context) {
     cx.add_theme(include_str!("../assets/widgets.css"));
 }
@@ -69,6 +82,16 @@ pub enum RawParamEvent {
     ParametersChanged,
 }
 
+/// The smallest user scale factor that can be set through [`GuiContextEvent::SetUserScaleFactor`]
+/// or by scrolling while holding Ctrl.
+pub const MIN_USER_SCALE_FACTOR: f64 = 0.5;
+/// The largest user scale factor that can be set through [`GuiContextEvent::SetUserScaleFactor`]
+/// or by scrolling while holding Ctrl.
+pub const MAX_USER_SCALE_FACTOR: f64 = 3.0;
+/// How much the user scale factor changes for every line scrolled while holding Ctrl (or Cmd on
+/// macOS).
+const USER_SCALE_FACTOR_SCROLL_STEP: f64 = 0.1;
+
 /// Events that directly interact with the [`GuiContext`]. Used to trigger resizes.
 pub enum GuiContextEvent {
     /// Resize the window to match the current size reported by the [`ViziaState`]'s size function.
@@ -104,6 +127,22 @@ pub enum GuiContextEvent {
     /// }
     /// ```
     Resize,
+    /// Change the user scale factor, which gets applied on top of the host's or the system's HiDPI
+    /// scaling factor. The value is clamped to [`MIN_USER_SCALE_FACTOR`] and
+    /// [`MAX_USER_SCALE_FACTOR`], and it's stored in the [`ViziaState`] so it's persisted along
+    /// with the rest of the GUI's state. This can be used to offer a GUI size option in a menu,
+    /// which is useful on Linux where many hosts don't report a scale factor at all. The scale
+    /// factor can also be changed by scrolling while holding Ctrl (or Cmd on macOS).
+    SetUserScaleFactor(f64),
+    /// Copy the plugin's entire state, including all parameter values and persistent fields, to
+    /// the clipboard as JSON. This can be used for a 'copy settings' button, and the settings can
+    /// be restored in another instance of the plugin, even in another host, using
+    /// [`PasteState`][Self::PasteState]. Plain text can be copied and pasted using
+    /// `cx.set_clipboard()` and `cx.get_clipboard()`.
+    CopyState,
+    /// Restore a state previously copied with [`CopyState`][Self::CopyState]. This does nothing if
+    /// the clipboard does not contain a plugin state.
+    PasteState,
 }
 
 /// Handles parameter updates for VIZIA GUIs. Registered in
@@ -122,6 +161,20 @@ pub(crate) struct WindowModel {
     /// The last known unscaled logical window size. Used to prevent sending duplicate resize
     /// requests.
     pub last_inner_window_size: AtomicCell<(u32, u32)>,
+    /// The number of lines scrolled while holding Ctrl that have not yet been applied to the user
+    /// scale factor. Smooth scrolling trackpads can report fractional lines.
+    pub scrolled_lines: f32,
+}
+
+impl WindowModel {
+    /// Clamp the new user scale factor and apply it. This will trigger a
+    /// `WindowEvent::GeometryChanged`, which requests the host to resize the window.
+    fn set_user_scale_factor(&self, cx: &mut EventContext, scale_factor: f64) {
+        let scale_factor = scale_factor.clamp(MIN_USER_SCALE_FACTOR, MAX_USER_SCALE_FACTOR);
+        if scale_factor != cx.user_scale_factor() {
+            cx.set_user_scale_factor(scale_factor);
+        }
+    }
 }
 
 impl Model for ParamModel {
@@ -153,6 +206,57 @@ impl Model for WindowModel {
 
                 meta.consume();
             }
+            GuiContextEvent::SetUserScaleFactor(scale_factor) => {
+                self.set_user_scale_factor(cx, *scale_factor);
+
+                meta.consume();
+            }
+            GuiContextEvent::CopyState => {
+                match self.context.get_state().to_json() {
+                    Ok(json) => {
+                        if let Err(err) = cx.set_clipboard(json) {
+                            nih_error!("Could not copy the plugin's state to the clipboard: {err}");
+                        }
+                    }
+                    Err(err) => nih_error!("{err:#}"),
+                }
+
+                meta.consume();
+            }
+            GuiContextEvent::PasteState => {
+                match cx.get_clipboard() {
+                    Ok(json) => match PluginState::from_json(&json) {
+                        Ok(state) => self.context.set_state(state),
+                        Err(err) => nih_log!("Not pasting the clipboard's contents: {err:#}"),
+                    },
+                    Err(err) => nih_error!("Could not read from the clipboard: {err}"),
+                }
+
+                meta.consume();
+            }
+        });
+
+        // Scrolling while holding Ctrl changes the user scale factor in fixed steps. The widgets in
+        // this crate ignore scroll events while Ctrl is held so this always works.
+        event.map(|window_event, meta| match *window_event {
+            WindowEvent::MouseScroll(_scroll_x, scroll_y) if cx.modifiers.command() => {
+                self.scrolled_lines += scroll_y;
+                let steps = self.scrolled_lines.trunc();
+                if steps != 0.0 {
+                    self.scrolled_lines -= steps;
+
+                    // Snapping to the step size prevents rounding errors from accumulating
+                    let current_step =
+                        (cx.user_scale_factor() / USER_SCALE_FACTOR_SCROLL_STEP).round();
+                    self.set_user_scale_factor(
+                        cx,
+                        (current_step + steps as f64) * USER_SCALE_FACTOR_SCROLL_STEP,
+                    );
+                }
+
+                meta.consume();
+            }
+            _ => (),
         });
 
         // This gets fired whenever the inner window gets resized
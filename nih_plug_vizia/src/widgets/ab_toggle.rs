@@ -0,0 +1,68 @@
+//! A button for switching between the plugin's A/B comparison state slots.
+
+use nih_plug::prelude::{GuiContext, StateSlot};
+use std::sync::Arc;
+use vizia::prelude::*;
+
+use super::WindowModel;
+
+/// A toggle button that switches between the two A/B comparison state slots managed by the
+/// wrapper. Left clicking switches to the other slot, and right clicking copies the current state
+/// to the other slot so the two slots can start out from the same settings.
+pub struct AbToggle {
+    context: Arc<dyn GuiContext>,
+}
+
+impl AbToggle {
+    /// Creates a new A/B toggle. The button always reflects the wrapper's active state slot, even
+    /// when the slot is switched from somewhere else.
+    pub fn new(cx: &mut Context, context: Arc<dyn GuiContext>) -> Handle<Self> {
+        // The active slot is read from the wrapper every time the bindings are updated, so this
+        // can't go out of sync with the host or with other editor code switching slots
+        let slot_b_active =
+            WindowModel::context.map(|context| context.active_state_slot() == StateSlot::B);
+
+        Self { context }
+            .build(cx, |cx| {
+                Label::new(
+                    cx,
+                    slot_b_active.map(|slot_b_active| {
+                        if *slot_b_active {
+                            StateSlot::B.to_string()
+                        } else {
+                            StateSlot::A.to_string()
+                        }
+                    }),
+                )
+                .hoverable(false);
+            })
+            .checked(slot_b_active)
+            .class("ab-toggle")
+    }
+}
+
+impl View for AbToggle {
+    fn element(&self) -> Option<&'static str> {
+        // Reuse the styling from param-button
+        Some("param-button")
+    }
+
+    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, meta| match window_event {
+            WindowEvent::MouseDown(MouseButton::Left)
+            | WindowEvent::MouseDoubleClick(MouseButton::Left)
+            | WindowEvent::MouseTripleClick(MouseButton::Left) => {
+                let new_slot = self.context.active_state_slot().other();
+                self.context.switch_state_slot(new_slot);
+
+                meta.consume();
+            }
+            WindowEvent::MouseDown(MouseButton::Right) => {
+                self.context.copy_state_to_other_slot();
+
+                meta.consume();
+            }
+            _ => {}
+        });
+    }
+}
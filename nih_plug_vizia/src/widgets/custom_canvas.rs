@@ -0,0 +1,105 @@
+//! An escape hatch for drawing directly to vizia's GPU-accelerated canvas.
+
+use vizia::prelude::*;
+
+/// The size of a [`CustomCanvas`] at the time it's being drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CanvasSize {
+    /// The canvas' width in physical pixels.
+    pub width: f32,
+    /// The canvas' height in physical pixels.
+    pub height: f32,
+    /// The ratio between physical and logical pixels. This includes both the HiDPI scaling factor
+    /// and the user scale factor, so line widths and font sizes should be multiplied by this value.
+    pub scale_factor: f32,
+}
+
+/// A widget that lets the plugin draw its own content using vizia's
+/// [femtovg](https://github.com/femtovg/femtovg) canvas, which is rendered using OpenGL. This can
+/// be used for things like oscilloscopes, spectrum analyzers, and other visualizations that would
+/// be too slow or too awkward to build out of regular widgets. The widget can be sized, styled, and
+/// positioned like any other element, and it coexists with the widgets around it.
+///
+/// The draw function is called with the canvas translated so `(0, 0)` is the widget's top left
+/// corner and with drawing clipped to the widget's bounds. All coordinates are in physical pixels,
+/// and [`CanvasSize`] contains the widget's current physical size and DPI scale factor so the
+/// drawing can adapt to resizes and HiDPI scaling. The draw function also receives the
+/// [`DrawContext`], which can be used to read lenses:
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use nih_plug_vizia::vizia::prelude::*;
+/// # use nih_plug_vizia::vizia::vg;
+/// # use nih_plug_vizia::widgets::CustomCanvas;
+/// # #[derive(Lens)]
+/// # struct Data { scope_samples: Arc<Vec<f32>> }
+/// # fn build(cx: &mut Context) {
+/// CustomCanvas::new(cx, |cx, canvas, size| {
+///     let samples = Data::scope_samples.get(cx);
+///
+///     let mut path = vg::Path::new();
+///     for (i, sample) in samples.iter().enumerate() {
+///         let x = i as f32 / samples.len() as f32 * size.width;
+///         let y = (0.5 - sample * 0.5) * size.height;
+///         if i == 0 {
+///             path.move_to(x, y);
+///         } else {
+///             path.line_to(x, y);
+///         }
+///     }
+///
+///     let mut paint = vg::Paint::color(vg::Color::white());
+///     paint.set_line_width(1.0 * size.scale_factor);
+///     canvas.stroke_path(&mut path, &paint);
+/// })
+/// .width(Stretch(1.0))
+/// .height(Pixels(200.0));
+/// # }
+/// ```
+///
+/// The widget's background color and border are not drawn automatically. Vizia only redraws the
+/// window when something changed, so visualizations that should update continuously need to be
+/// driven by a lens that changes whenever new data is available.
+pub struct CustomCanvas {
+    draw: Box<dyn Fn(&mut DrawContext, &mut Canvas, CanvasSize)>,
+}
+
+impl CustomCanvas {
+    /// Create a new canvas that calls `draw` whenever it needs to be redrawn.
+    pub fn new<F>(cx: &mut Context, draw: F) -> Handle<Self>
+    where
+        F: Fn(&mut DrawContext, &mut Canvas, CanvasSize) + 'static,
+    {
+        Self {
+            draw: Box::new(draw),
+        }
+        .build(cx, |_| {})
+    }
+}
+
+impl View for CustomCanvas {
+    fn element(&self) -> Option<&'static str> {
+        Some("custom-canvas")
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let size = CanvasSize {
+            width: bounds.w,
+            height: bounds.h,
+            scale_factor: cx.logical_to_physical(1.0),
+        };
+
+        // The draw function gets its own coordinate system, and it cannot draw over the other
+        // widgets
+        canvas.save();
+        canvas.translate(bounds.x, bounds.y);
+        canvas.scissor(0.0, 0.0, bounds.w, bounds.h);
+        (self.draw)(cx, canvas, size);
+        canvas.restore();
+    }
+}
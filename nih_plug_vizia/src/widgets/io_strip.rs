@@ -0,0 +1,77 @@
+//! Input and output trim controls with meters for use with
+//! [`util::IoStrip`][nih_plug::util::IoStrip].
+
+use nih_plug::prelude::util::{IoStripMeters, IoStripParams};
+use std::sync::Arc;
+use std::time::Duration;
+use vizia::prelude::*;
+
+use super::{ParamButton, ParamSlider, PeakMeter};
+
+/// How long the meters hold their peaks.
+const PEAK_HOLD_TIME: Duration = Duration::from_millis(600);
+
+/// The controls for an [`IoStripParams`] object: a slider and a peak meter for both the input and
+/// the output trims, and a toggle for the automatic gain compensation. The meters are read from
+/// the [`IoStripMeters`] of the plugin's [`util::IoStrip`][nih_plug::util::IoStrip].
+///
+/// ```ignore
+/// IoStrip::new(cx, Data::params, |params| &params.io, Data::io_meters);
+/// ```
+///
+/// The rows can be styled using the `.io-strip__row` class, and the labels using the
+/// `.io-strip__label` class.
+pub struct IoStrip;
+
+impl IoStrip {
+    /// Creates a new [`IoStrip`] for the IO strip parameters returned by `params_to_io`, using the
+    /// meters from `meters`.
+    pub fn new<L, Params, FMap, M>(
+        cx: &mut Context,
+        params: L,
+        params_to_io: FMap,
+        meters: M,
+    ) -> Handle<Self>
+    where
+        L: Lens<Target = Params> + Clone,
+        Params: 'static,
+        FMap: Fn(&Params) -> &IoStripParams + Copy + 'static,
+        M: Lens<Target = Arc<IoStripMeters>>,
+    {
+        Self.build(cx, |cx| {
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Input").class("io-strip__label");
+                ParamSlider::new(cx, params.clone(), move |params| {
+                    &params_to_io(params).input_trim
+                });
+                PeakMeter::new(
+                    cx,
+                    meters.clone().map(|meters| meters.input_peak_db()),
+                    Some(PEAK_HOLD_TIME),
+                );
+            })
+            .class("io-strip__row");
+
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Output").class("io-strip__label");
+                ParamSlider::new(cx, params.clone(), move |params| {
+                    &params_to_io(params).output_trim
+                });
+                PeakMeter::new(
+                    cx,
+                    meters.map(|meters| meters.output_peak_db()),
+                    Some(PEAK_HOLD_TIME),
+                );
+            })
+            .class("io-strip__row");
+
+            ParamButton::new(cx, params, move |params| &params_to_io(params).auto_gain);
+        })
+    }
+}
+
+impl View for IoStrip {
+    fn element(&self) -> Option<&'static str> {
+        Some("io-strip")
+    }
+}
@@ -0,0 +1,165 @@
+//! A loudness meter widget for use with [`util::LoudnessMeter`].
+
+use nih_plug::prelude::util;
+use vizia::prelude::*;
+use vizia::vg;
+
+/// The loudness corresponding to the very left of the bar, in LUFS.
+const MIN_LUFS: f32 = -60.0;
+/// The loudness corresponding to the very right of the bar, in LUFS.
+const MAX_LUFS: f32 = 0.0;
+/// The loudness values marked with a vertical line on the bar.
+const BAR_TICKS: [f32; 5] = [-50.0, -40.0, -30.0, -20.0, -10.0];
+
+/// A horizontal loudness meter. The bar shows the momentary loudness, the vertical line on top of
+/// it shows the short-term loudness, and the integrated loudness is shown as text underneath the
+/// bar. The values are usually measured using [`util::LoudnessMeter`] in the plugin's process
+/// function and then sent to the editor using atomics, just like for the
+/// [`PeakMeter`][super::PeakMeter].
+///
+/// The bar's background and border can be styled using the `.bar` class, and the readout can be
+/// styled using the `.readout` class.
+pub struct LoudnessMeter;
+
+/// The bar for the loudness meter.
+struct LoudnessMeterBar<M, S>
+where
+    M: Lens<Target = f32>,
+    S: Lens<Target = f32>,
+{
+    momentary_lufs: M,
+    short_term_lufs: S,
+}
+
+impl LoudnessMeter {
+    /// Creates a new [`LoudnessMeter`] for the given momentary, short-term, and integrated loudness
+    /// values in LUFS. Values at or below [`util::MINUS_INFINITY_DB`] are treated as silence.
+    pub fn new<M, S, I>(
+        cx: &mut Context,
+        momentary_lufs: M,
+        short_term_lufs: S,
+        integrated_lufs: I,
+    ) -> Handle<Self>
+    where
+        M: Lens<Target = f32>,
+        S: Lens<Target = f32>,
+        I: Lens<Target = f32>,
+    {
+        Self.build(cx, |cx| {
+            LoudnessMeterBar {
+                momentary_lufs,
+                short_term_lufs,
+            }
+            .build(cx, |_| {})
+            .class("bar");
+
+            Label::new(
+                cx,
+                integrated_lufs.map(|lufs| {
+                    if *lufs > util::MINUS_INFINITY_DB {
+                        format!("{lufs:.1} LUFS integrated")
+                    } else {
+                        String::from("-inf LUFS integrated")
+                    }
+                }),
+            )
+            .class("readout");
+        })
+    }
+}
+
+impl View for LoudnessMeter {
+    fn element(&self) -> Option<&'static str> {
+        Some("loudness-meter")
+    }
+}
+
+impl<M, S> View for LoudnessMeterBar<M, S>
+where
+    M: Lens<Target = f32>,
+    S: Lens<Target = f32>,
+{
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let momentary_lufs = self.momentary_lufs.get(cx);
+        let short_term_lufs = self.short_term_lufs.get(cx);
+
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let background_color = cx.background_color().cloned().unwrap_or_default();
+        let border_color = cx.border_color().cloned().unwrap_or_default();
+        let opacity = cx.opacity();
+        let mut background_color: vg::Color = background_color.into();
+        background_color.set_alphaf(background_color.a * opacity);
+        let mut border_color: vg::Color = border_color.into();
+        border_color.set_alphaf(border_color.a * opacity);
+
+        let border_width = match cx.border_width().unwrap_or_default() {
+            Units::Pixels(val) => val,
+            Units::Percentage(val) => bounds.w.min(bounds.h) * (val / 100.0),
+            _ => 0.0,
+        };
+
+        let mut path = vg::Path::new();
+        path.rect(
+            bounds.x + border_width / 2.0,
+            bounds.y + border_width / 2.0,
+            bounds.w - border_width,
+            bounds.h - border_width,
+        );
+        canvas.fill_path(&mut path, &vg::Paint::color(background_color));
+
+        let bar_bounds = bounds.shrink(border_width);
+        let lufs_to_x_coord = |lufs: f32| {
+            let fraction = ((lufs - MIN_LUFS) / (MAX_LUFS - MIN_LUFS)).clamp(0.0, 1.0);
+            bar_bounds.left() + (bar_bounds.w * fraction).round()
+        };
+        let dpi_scale = cx.logical_to_physical(1.0).floor().max(1.0);
+
+        // The momentary loudness is drawn as a solid bar
+        if momentary_lufs > MIN_LUFS {
+            let mut bar_path = vg::Path::new();
+            bar_path.rect(
+                bar_bounds.left(),
+                bar_bounds.top(),
+                lufs_to_x_coord(momentary_lufs) - bar_bounds.left(),
+                bar_bounds.h,
+            );
+            canvas.fill_path(
+                &mut bar_path,
+                &vg::Paint::color(vg::Color::rgbaf(0.55, 0.55, 0.55, opacity)),
+            );
+        }
+
+        for tick_lufs in BAR_TICKS {
+            // femtovg draws paths centered on these coordinates, so this is offset by half a pixel
+            // to keep the lines sharp
+            let tick_x = lufs_to_x_coord(tick_lufs) + (dpi_scale / 2.0);
+            let mut tick_path = vg::Path::new();
+            tick_path.move_to(tick_x, bar_bounds.bottom() - (bar_bounds.h * 0.3));
+            tick_path.line_to(tick_x, bar_bounds.bottom());
+
+            let mut paint = vg::Paint::color(vg::Color::rgbaf(0.3, 0.3, 0.3, opacity));
+            paint.set_line_width(dpi_scale);
+            canvas.stroke_path(&mut tick_path, &paint);
+        }
+
+        if (MIN_LUFS..MAX_LUFS).contains(&short_term_lufs) {
+            let short_term_x = lufs_to_x_coord(short_term_lufs) + (dpi_scale / 2.0);
+            let mut short_term_path = vg::Path::new();
+            short_term_path.move_to(short_term_x, bar_bounds.top());
+            short_term_path.line_to(short_term_x, bar_bounds.bottom());
+
+            let mut paint = vg::Paint::color(vg::Color::rgbaf(0.15, 0.15, 0.15, opacity));
+            paint.set_line_width(2.0 * dpi_scale);
+            canvas.stroke_path(&mut short_term_path, &paint);
+        }
+
+        // Draw border last
+        let mut paint = vg::Paint::color(border_color);
+        paint.set_line_width(border_width);
+        canvas.stroke_path(&mut path, &paint);
+    }
+}
@@ -0,0 +1,92 @@
+//! A modulation matrix for routing modulation sources to parameters.
+
+use nih_plug::prelude::Param;
+use vizia::prelude::*;
+
+use super::{ParamSlider, ParamSliderExt, ParamSliderStyle};
+
+/// A grid of modulation amounts. Every row corresponds to a modulation source, like an LFO or an
+/// envelope, and every column corresponds to a destination parameter. Each cell is a
+/// [`ParamSlider`] bound to the parameter that stores the amount for that source and destination
+/// pair, so the amounts can be dragged, reset, and automated like any other parameter. Continuous
+/// amount parameters with a centered default value, like a `[-1, 1]` range with a default of 0,
+/// are drawn as a bipolar offset from that center.
+///
+/// The amount parameters usually live in a nested array in the plugin's `Params` struct:
+///
+/// ```ignore
+/// ModMatrix::new(
+///     cx,
+///     Data::params,
+///     &["LFO 1", "LFO 2", "Envelope"],
+///     &["Cutoff", "Resonance", "Pitch"],
+///     |params, source_idx, destination_idx| {
+///         &params.mod_amounts[source_idx].destinations[destination_idx]
+///     },
+/// );
+/// ```
+///
+/// The rows, labels, and cells can be styled using the `.mod-matrix__row`,
+/// `.mod-matrix__source`, `.mod-matrix__destination`, and `.mod-matrix__cell` classes.
+pub struct ModMatrix;
+
+impl ModMatrix {
+    /// Creates a new [`ModMatrix`] with a row for every entry in `sources` and a column for every
+    /// entry in `destinations`. `amount_param` maps the `Params` object and a source and destination
+    /// index to the parameter containing that cell's modulation amount. See
+    /// [`ParamSlider::new()`] for more information on how the `params` lens is used.
+    pub fn new<L, Params, P, FMap>(
+        cx: &mut Context,
+        params: L,
+        sources: &[&str],
+        destinations: &[&str],
+        amount_param: FMap,
+    ) -> Handle<Self>
+    where
+        L: Lens<Target = Params> + Clone,
+        Params: 'static,
+        P: Param + 'static,
+        FMap: Fn(&Params, usize, usize) -> &P + Copy + 'static,
+    {
+        Self.build(cx, |cx| {
+            // The top left corner is left empty so the destination labels line up with the cells
+            HStack::new(cx, |cx| {
+                Element::new(cx).class("mod-matrix__source");
+                for destination in destinations {
+                    Label::new(cx, *destination).class("mod-matrix__destination");
+                }
+            })
+            .class("mod-matrix__row");
+
+            for (source_idx, source) in sources.iter().enumerate() {
+                let params = params.clone();
+                HStack::new(cx, move |cx| {
+                    Label::new(cx, *source).class("mod-matrix__source");
+
+                    for destination_idx in 0..destinations.len() {
+                        // Closures that return a reference borrowed from their argument can't be
+                        // inferred here, so like `GenericUi` this goes through a raw pointer. The
+                        // parameter outlives the editor.
+                        let param: *const P = params
+                            .clone()
+                            .map(move |params| {
+                                amount_param(params, source_idx, destination_idx) as *const P
+                            })
+                            .get(cx);
+
+                        ParamSlider::new(cx, params.clone(), move |_| unsafe { &*param })
+                            .set_style(ParamSliderStyle::Centered)
+                            .class("mod-matrix__cell");
+                    }
+                })
+                .class("mod-matrix__row");
+            }
+        })
+    }
+}
+
+impl View for ModMatrix {
+    fn element(&self) -> Option<&'static str> {
+        Some("mod-matrix")
+    }
+}
@@ -93,7 +93,10 @@ impl View for ParamButton {
                 self.toggle_value(cx);
                 meta.consume();
             }
-            WindowEvent::MouseScroll(_scroll_x, scroll_y) if self.use_scroll_wheel => {
+            // Scrolling while holding Ctrl changes the GUI's scale factor
+            WindowEvent::MouseScroll(_scroll_x, scroll_y)
+                if self.use_scroll_wheel && !cx.modifiers.command() =>
+            {
                 // With a regular scroll wheel `scroll_y` will only ever be -1 or 1, but with smooth
                 // scrolling trackpads being a thing `scroll_y` could be anything.
                 self.scrolled_lines += scroll_y;
@@ -0,0 +1,393 @@
+//! A rotary knob that integrates with NIH-plug's [`Param`] types.
+
+use nih_plug::prelude::Param;
+use std::f32::consts::PI;
+use vizia::prelude::*;
+use vizia::vg;
+
+use super::param_base::ParamWidgetBase;
+use super::util::ModifiersExt;
+
+/// The default amount the normalized value changes per logical pixel dragged vertically. Dragging
+/// across 200 pixels covers the knob's entire range.
+const DEFAULT_DRAG_SENSITIVITY: f32 = 0.005;
+/// When shift+dragging a knob, the drag sensitivity is multiplied by this value.
+const GRANULAR_DRAG_MULTIPLIER: f32 = 0.1;
+
+/// The angle of the knob's minimum value. Zero radians points to the right, and since the y-axis
+/// points down this is at the bottom left.
+const START_ANGLE: f32 = 0.75 * PI;
+/// The knob's range of motion in radians. The maximum value is at the bottom right.
+const SWEEP_ANGLE: f32 = 1.5 * PI;
+
+/// A rotary knob that integrates with NIH-plug's [`Param`] types. Dragging the knob up or down
+/// changes the value, shift+dragging allows for more granular changes, and double clicking, right
+/// clicking, or Ctrl+clicking resets the parameter to its default value. The knob's value is shown
+/// as an arc, and if the host is modulating the parameter then the modulated value is shown as a
+/// second, thinner ring inside of that arc. Use [`ParamKnobExt`] to change the drag sensitivity or
+/// to hide the value label.
+///
+/// The knob's arcs and pointer can be styled through the `.knob__track`, `.knob__value`, and
+/// `.knob__modulation` elements' `border-color` and `border-width` properties.
+#[derive(Lens)]
+pub struct ParamKnob {
+    param_base: ParamWidgetBase,
+
+    /// Will be set to `true` if we're dragging the parameter. Resetting the parameter should not
+    /// initiate a drag.
+    drag_active: bool,
+    /// The mouse's Y-coordinate and the knob's normalized value at the start of the current drag.
+    /// This is reset when shift is pressed or released during a drag so the knob doesn't jump
+    /// when switching between regular and granular dragging.
+    drag_start: Option<(f32, f32)>,
+
+    // These fields are set through modifiers:
+    /// The amount the normalized value changes per logical pixel dragged.
+    drag_sensitivity: f32,
+    /// Whether or not to listen to scroll events for changing the parameter's value in steps.
+    use_scroll_wheel: bool,
+    /// The number of (fractional) scrolled lines that have not yet been turned into parameter
+    /// change events. This is needed to support trackpads with smooth scrolling.
+    scrolled_lines: f32,
+    /// Whether to show the parameter's current value below the knob.
+    show_value: bool,
+}
+
+/// An arc between two normalized values, drawn inside of the element's bounds using its border
+/// color and width. Used for the knob's track, value, and modulation rings.
+struct KnobArc<S, E>
+where
+    S: Lens<Target = f32>,
+    E: Lens<Target = f32>,
+{
+    start: S,
+    end: E,
+    /// Whether to draw a line from the center of the knob towards `end`.
+    draw_pointer: bool,
+}
+
+impl ParamKnob {
+    /// Creates a new [`ParamKnob`] for the given parameter. To accommodate VIZIA's mapping system,
+    /// you'll need to provide a lens containing your `Params` implementation object (check out how
+    /// the `Data` struct is used in `gain_gui_vizia`) and a projection function that maps the
+    /// `Params` object to the parameter you want to display a widget for. Parameter changes are
+    /// handled by emitting [`ParamEvent`][super::ParamEvent]s which are automatically handled by
+    /// the VIZIA wrapper.
+    ///
+    /// See [`ParamKnobExt`] for additional options.
+    pub fn new<L, Params, P, FMap>(
+        cx: &mut Context,
+        params: L,
+        params_to_param: FMap,
+    ) -> Handle<Self>
+    where
+        L: Lens<Target = Params> + Clone,
+        Params: 'static,
+        P: Param + 'static,
+        FMap: Fn(&Params) -> &P + Copy + 'static,
+    {
+        Self {
+            param_base: ParamWidgetBase::new(cx, params.clone(), params_to_param),
+
+            drag_active: false,
+            drag_start: None,
+
+            drag_sensitivity: DEFAULT_DRAG_SENSITIVITY,
+            use_scroll_wheel: true,
+            scrolled_lines: 0.0,
+            show_value: true,
+        }
+        .build(
+            cx,
+            ParamWidgetBase::build_view(params, params_to_param, move |cx, param_data| {
+                // Continuous parameters with a default value at around the center of their range
+                // are drawn as an offset from that center, just like with `ParamSlider`
+                let default_value = param_data.param().default_normalized_value();
+                let draw_from_default = param_data.param().step_count().is_none()
+                    && (0.45..=0.55).contains(&default_value);
+                let value_start = if draw_from_default {
+                    default_value
+                } else {
+                    0.0
+                };
+
+                let unmodulated_normalized_value_lens =
+                    param_data.make_lens(|param| param.unmodulated_normalized_value());
+                let modulated_normalized_value_lens =
+                    param_data.make_lens(|param| param.modulated_normalized_value());
+                let display_value_lens = param_data.make_lens(|param| {
+                    param.normalized_value_to_string(param.unmodulated_normalized_value(), true)
+                });
+
+                ZStack::new(cx, move |cx| {
+                    KnobArc {
+                        start: param_data.make_lens(|_| 0.0),
+                        end: param_data.make_lens(|_| 1.0),
+                        draw_pointer: false,
+                    }
+                    .build(cx, |_| {})
+                    .class("knob__track")
+                    .hoverable(false);
+                    KnobArc {
+                        start: param_data.make_lens(move |_| value_start),
+                        end: unmodulated_normalized_value_lens.clone(),
+                        draw_pointer: true,
+                    }
+                    .build(cx, |_| {})
+                    .class("knob__value")
+                    .hoverable(false);
+                    KnobArc {
+                        start: unmodulated_normalized_value_lens,
+                        end: modulated_normalized_value_lens,
+                        draw_pointer: false,
+                    }
+                    .build(cx, |_| {})
+                    .class("knob__modulation")
+                    .hoverable(false);
+                })
+                .class("knob")
+                .hoverable(false);
+
+                Binding::new(cx, ParamKnob::show_value, move |cx, show_value| {
+                    if show_value.get(cx) {
+                        Label::new(cx, display_value_lens.clone())
+                            .class("value")
+                            .hoverable(false);
+                    }
+                });
+            }),
+        )
+    }
+
+    /// Start a new drag from the current mouse position and the parameter's current value.
+    fn start_drag(&mut self, cx: &EventContext) {
+        self.drag_start = Some((
+            cx.mouse.cursory,
+            self.param_base.unmodulated_normalized_value(),
+        ));
+    }
+}
+
+impl View for ParamKnob {
+    fn element(&self) -> Option<&'static str> {
+        Some("param-knob")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, meta| match window_event {
+            // See `ParamSlider` for why triple clicks are treated as regular clicks
+            WindowEvent::MouseDown(MouseButton::Left)
+            | WindowEvent::MouseTripleClick(MouseButton::Left) => {
+                if cx.modifiers.command() {
+                    // Ctrl+Click, double click, and right clicks should reset the parameter instead
+                    // of initiating a drag operation
+                    self.param_base.begin_set_parameter(cx);
+                    self.param_base
+                        .set_normalized_value(cx, self.param_base.default_normalized_value());
+                    self.param_base.end_set_parameter(cx);
+                } else {
+                    self.drag_active = true;
+                    cx.capture();
+                    // NOTE: Otherwise we don't get key up events
+                    cx.focus();
+                    cx.set_active(true);
+
+                    // Unlike the slider, clicking on a knob never jumps to a new value
+                    self.param_base.begin_set_parameter(cx);
+                    self.start_drag(cx);
+                }
+
+                meta.consume();
+            }
+            WindowEvent::MouseDoubleClick(MouseButton::Left)
+            | WindowEvent::MouseDown(MouseButton::Right)
+            | WindowEvent::MouseDoubleClick(MouseButton::Right)
+            | WindowEvent::MouseTripleClick(MouseButton::Right) => {
+                self.param_base.begin_set_parameter(cx);
+                self.param_base
+                    .set_normalized_value(cx, self.param_base.default_normalized_value());
+                self.param_base.end_set_parameter(cx);
+
+                meta.consume();
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if self.drag_active {
+                    self.drag_active = false;
+                    self.drag_start = None;
+                    cx.release();
+                    cx.set_active(false);
+
+                    self.param_base.end_set_parameter(cx);
+
+                    meta.consume();
+                }
+            }
+            WindowEvent::MouseMove(_x, y) => {
+                if let (true, Some((start_y, start_value))) = (self.drag_active, self.drag_start) {
+                    let sensitivity = if cx.modifiers.shift() {
+                        self.drag_sensitivity * GRANULAR_DRAG_MULTIPLIER
+                    } else {
+                        self.drag_sensitivity
+                    };
+
+                    // Dragging upwards increases the value. The mouse coordinates are in physical
+                    // pixels, so the DPI scale needs to be compensated for to keep the sensitivity
+                    // consistent.
+                    let delta_y = (start_y - *y) / cx.style.dpi_factor as f32;
+                    self.param_base.set_normalized_value(
+                        cx,
+                        (start_value + (delta_y * sensitivity)).clamp(0.0, 1.0),
+                    );
+                }
+            }
+            WindowEvent::KeyDown(_, Some(Key::Shift)) | WindowEvent::KeyUp(_, Some(Key::Shift)) => {
+                // Switching between regular and granular dragging continues the drag from the
+                // current value
+                if self.drag_active {
+                    self.start_drag(cx);
+                }
+            }
+            WindowEvent::MouseScroll(_scroll_x, scroll_y)
+                if self.use_scroll_wheel && !cx.modifiers.command() =>
+            {
+                // With a regular scroll wheel `scroll_y` will only ever be -1 or 1, but with smooth
+                // scrolling trackpads being a thing `scroll_y` could be anything.
+                self.scrolled_lines += scroll_y;
+
+                if self.scrolled_lines.abs() >= 1.0 {
+                    let use_finer_steps = cx.modifiers.shift();
+
+                    // Scrolling while dragging needs to be taken into account here
+                    if !self.drag_active {
+                        self.param_base.begin_set_parameter(cx);
+                    }
+
+                    let mut current_value = self.param_base.unmodulated_normalized_value();
+
+                    while self.scrolled_lines >= 1.0 {
+                        current_value = self
+                            .param_base
+                            .next_normalized_step(current_value, use_finer_steps);
+                        self.param_base.set_normalized_value(cx, current_value);
+                        self.scrolled_lines -= 1.0;
+                    }
+
+                    while self.scrolled_lines <= -1.0 {
+                        current_value = self
+                            .param_base
+                            .previous_normalized_step(current_value, use_finer_steps);
+                        self.param_base.set_normalized_value(cx, current_value);
+                        self.scrolled_lines += 1.0;
+                    }
+
+                    if !self.drag_active {
+                        self.param_base.end_set_parameter(cx);
+                    } else {
+                        // Otherwise the next mouse move would undo the scroll. The parameter change
+                        // events have not been handled yet, so the new value is used directly.
+                        self.drag_start = Some((cx.mouse.cursory, current_value));
+                    }
+                }
+
+                meta.consume();
+            }
+            _ => {}
+        });
+    }
+}
+
+impl<S, E> View for KnobArc<S, E>
+where
+    S: Lens<Target = f32>,
+    E: Lens<Target = f32>,
+{
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let start = self.start.get(cx).clamp(0.0, 1.0);
+        let end = self.end.get(cx).clamp(0.0, 1.0);
+
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let border_color = cx.border_color().copied().unwrap_or_default();
+        let opacity = cx.opacity();
+        let mut border_color: vg::Color = border_color.into();
+        border_color.set_alphaf(border_color.a * opacity);
+
+        let diameter = bounds.w.min(bounds.h);
+        let line_width = match cx.border_width().unwrap_or_default() {
+            Units::Pixels(val) => val,
+            Units::Percentage(val) => diameter * (val / 100.0),
+            _ => 0.0,
+        };
+        if line_width <= 0.0 {
+            return;
+        }
+
+        let (center_x, center_y) = (bounds.x + (bounds.w / 2.0), bounds.y + (bounds.h / 2.0));
+        let radius = (diameter - line_width) / 2.0;
+        let start_angle = START_ANGLE + (start.min(end) * SWEEP_ANGLE);
+        let end_angle = START_ANGLE + (start.max(end) * SWEEP_ANGLE);
+
+        let mut paint = vg::Paint::color(border_color);
+        paint.set_line_width(line_width);
+
+        // Zero-width arcs would otherwise still draw a dot with some renderers
+        if end_angle - start_angle > 1e-3 {
+            let mut path = vg::Path::new();
+            path.arc(
+                center_x,
+                center_y,
+                radius,
+                start_angle,
+                end_angle,
+                vg::Solidity::Hole,
+            );
+            canvas.stroke_path(&mut path, &paint);
+        }
+
+        if self.draw_pointer {
+            let pointer_angle = START_ANGLE + (end * SWEEP_ANGLE);
+            let (sin, cos) = pointer_angle.sin_cos();
+
+            let mut path = vg::Path::new();
+            path.move_to(
+                center_x + (cos * radius * 0.3),
+                center_y + (sin * radius * 0.3),
+            );
+            path.line_to(center_x + (cos * radius), center_y + (sin * radius));
+            paint.set_line_width(line_width / 2.0);
+            canvas.stroke_path(&mut path, &paint);
+        }
+    }
+}
+
+/// Extension methods for [`ParamKnob`] handles.
+pub trait ParamKnobExt {
+    /// Change how much the parameter's normalized value changes per logical pixel dragged. The
+    /// default value of `0.005` covers the entire range in 200 pixels. Shift+dragging always uses a
+    /// tenth of this value.
+    fn drag_sensitivity(self, sensitivity: f32) -> Self;
+
+    /// Don't respond to scroll wheel events. Useful when this knob is used as part of a scrolling
+    /// view.
+    fn disable_scroll_wheel(self) -> Self;
+
+    /// Don't show the parameter's current value below the knob.
+    fn without_value(self) -> Self;
+}
+
+impl ParamKnobExt for Handle<'_, ParamKnob> {
+    fn drag_sensitivity(self, sensitivity: f32) -> Self {
+        self.modify(|param_knob: &mut ParamKnob| param_knob.drag_sensitivity = sensitivity)
+    }
+
+    fn disable_scroll_wheel(self) -> Self {
+        self.modify(|param_knob: &mut ParamKnob| param_knob.use_scroll_wheel = false)
+    }
+
+    fn without_value(self) -> Self {
+        self.modify(|param_knob: &mut ParamKnob| param_knob.show_value = false)
+    }
+}
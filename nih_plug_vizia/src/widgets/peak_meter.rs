@@ -17,7 +17,7 @@ const MIN_TICK: f32 = -90.0;
 /// The decibel value corresponding to the very right of the bar.
 const MAX_TICK: f32 = 20.0;
 /// The ticks that will be shown beneath the peak meter's bar. The first value is shown as
-/// -infinity, and at the last position we'll draw the `dBFS` string.
+/// -infinity, and at the last position we'll draw the unit, either `dBFS` or `dBTP`.
 const TEXT_TICKS: [i32; 6] = [-80, -60, -40, -20, 0, 12];
 
 /// A simple horizontal peak meter.
@@ -40,6 +40,32 @@ impl PeakMeter {
     /// Creates a new [`PeakMeter`] for the given value in decibel, optionally holding the peak
     /// value for a certain amount of time.
     pub fn new<L>(cx: &mut Context, level_dbfs: L, hold_time: Option<Duration>) -> Handle<Self>
+    where
+        L: Lens<Target = f32>,
+    {
+        Self::build_meter(cx, level_dbfs, hold_time, "dBFS")
+    }
+
+    /// Creates a new [`PeakMeter`] for a true peak level in decibel, as measured by
+    /// [`util::TruePeakDetector`]. This works the same as [`new()`][Self::new()], but the scale is
+    /// labeled in dBTP.
+    pub fn new_true_peak<L>(
+        cx: &mut Context,
+        level_dbtp: L,
+        hold_time: Option<Duration>,
+    ) -> Handle<Self>
+    where
+        L: Lens<Target = f32>,
+    {
+        Self::build_meter(cx, level_dbtp, hold_time, "dBTP")
+    }
+
+    fn build_meter<L>(
+        cx: &mut Context,
+        level_dbfs: L,
+        hold_time: Option<Duration>,
+        unit: &'static str,
+    ) -> Handle<Self>
     where
         L: Lens<Target = f32>,
     {
@@ -107,7 +133,7 @@ impl PeakMeter {
                                 .class("ticks__label--inf")
                         } else if last_tick {
                             // This is only inclued in the array to make positioning this easier
-                            Label::new(cx, "dBFS")
+                            Label::new(cx, unit)
                                 .class("ticks__label")
                                 .class("ticks__label--dbfs")
                         } else {
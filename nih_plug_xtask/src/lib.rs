@@ -8,6 +8,7 @@ use std::process::Command;
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
 
+mod new;
 mod symbols;
 mod util;
 
@@ -23,10 +24,21 @@ fn build_usage_string(command_name: &str) -> String {
   {command_name} bundle <package> [--release]
   {command_name} bundle -p <package1> -p <package2> ... [--release]
 
-  {command_name} bundle-universal <package> [--release]  (macOS only)
-  {command_name} bundle-universal -p <package1> -p <package2> ... [--release]  (macOS only)
+  {command_name} bundle <package> --universal [--release]  (macOS only)
+  {command_name} bundle -p <package1> -p <package2> ... --universal [--release]  (macOS only)
 
-  All other 'cargo build' options are supported, including '--target' and '--profile'."
+  '{command_name} bundle-universal' is an alias for '{command_name} bundle --universal'.
+
+  {command_name} bundle <package> --target <target> --xwin [--release]
+    Cross compiles the package for a '*-pc-windows-msvc' target using 'cargo xwin build'.
+
+  {command_name} bundle <package> --install [--release]
+    Also copies the plugin bundles to the current user's CLAP, VST3, and VST2 directories.
+
+  All other 'cargo build' options are supported, including '--target' and '--profile'.
+
+  {command_name} new <package>
+    Creates a new plugin crate with a vizia editor in the workspace's root directory."
     )
 }
 
@@ -34,9 +46,16 @@ fn build_usage_string(command_name: &str) -> String {
 /// `bundler.toml` alongside the workspace's main `Cargo.toml` file.
 type BundlerConfig = HashMap<String, PackageConfig>;
 
-#[derive(Debug, Clone, Deserialize)]
-struct PackageConfig {
-    name: Option<String>,
+/// The options for a single package in `bundler.toml`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PackageConfig {
+    /// The human readable name for the bundles. Defaults to the package's name.
+    pub name: Option<String>,
+    /// The reverse domain name identifier used for macOS bundles. Defaults to
+    /// `com.nih-plug.<package>`.
+    pub bundle_id: Option<String>,
+    /// The version string used for macOS bundles. Defaults to `1.0.0`.
+    pub version: Option<String>,
 }
 
 /// The target we're generating a plugin for. This can be either the native target or a cross
@@ -90,56 +109,57 @@ pub fn main_with_args(command_name: &str, args: impl IntoIterator<Item = String>
             // cargo build, but you can also build a single package without specifying `-p`. Since
             // multiple packages can be built in parallel if we pass all of these flags to a single
             // `cargo build` we'll first build all of these packages and only then bundle them.
-            let (packages, other_args) = split_bundle_args(args, &usage_string)?;
+            let (packages, mut other_args) = split_bundle_args(args, &usage_string)?;
+
+            // These are not cargo options, so they need to be removed before building
+            let universal = take_flag(&mut other_args, "--universal");
+            let use_xwin = take_flag(&mut other_args, "--xwin");
+            let install_bundles = take_flag(&mut other_args, "--install");
+
+            if universal {
+                bundle_universal(command_name, &packages, &other_args)?;
+            } else {
+                // As explained above, for efficiency's sake this is a two step process
+                if use_xwin {
+                    build_xwin(&packages, &other_args)?;
+                } else {
+                    build(&packages, &other_args)?;
+                }
 
-            // As explained above, for efficiency's sake this is a two step process
-            build(&packages, &other_args)?;
+                for package in &packages {
+                    bundle(package, &other_args, false)?;
+                }
+            }
 
-            bundle(&packages[0], &other_args, false)?;
-            for package in packages.into_iter().skip(1) {
-                bundle(&package, &other_args, false)?;
+            if install_bundles {
+                for package in &packages {
+                    install(package, &other_args, universal)?;
+                }
             }
 
             Ok(())
         }
         "bundle-universal" => {
-            // The same as `--bundle`, but builds universal binaries for macOS Cargo will also error
-            // out on duplicate `--target` options, but it seems like a good idea to preemptively
-            // abort the bundling process if that happens
-            let (packages, other_args) = split_bundle_args(args, &usage_string)?;
-
-            for arg in &other_args {
-                if arg == "--target" || arg.starts_with("--target=") {
-                    anyhow::bail!(
-                        "'{command_name} xtask bundle-universal' is incompatible with the '{arg}' \
-                         option."
-                    )
+            // The same as `bundle --universal`
+            let (packages, mut other_args) = split_bundle_args(args, &usage_string)?;
+            let install_bundles = take_flag(&mut other_args, "--install");
+
+            bundle_universal(command_name, &packages, &other_args)?;
+            if install_bundles {
+                for package in &packages {
+                    install(package, &other_args, true)?;
                 }
             }
 
-            // We can just use the regular build function here. There's sadly no way to build both
-            // targets in parallel, so this will likely take twice as logn as a regular build.
-            // TODO: Explicitly specifying the target even on the native target causes a rebuild in
-            //       the target `target/<target_triple>` directory. This makes bundling much simpler
-            //       because there's no conditional logic required based on the current platform,
-            //       but it does waste some resources and requires a rebuild if the native target
-            //       was already built.
-            let mut x86_64_args = other_args.clone();
-            x86_64_args.push(String::from("--target=x86_64-apple-darwin"));
-            build(&packages, &x86_64_args)?;
-            let mut aarch64_args = other_args.clone();
-            aarch64_args.push(String::from("--target=aarch64-apple-darwin"));
-            build(&packages, &aarch64_args)?;
-
-            // This `true` indicates a universal build. This will cause the two sets of built
-            // binaries to beq lipo'd together into universal binaries before bundling
-            bundle(&packages[0], &other_args, true)?;
-            for package in packages.into_iter().skip(1) {
-                bundle(&package, &other_args, true)?;
-            }
-
             Ok(())
         }
+        "new" => {
+            let package = args
+                .next()
+                .with_context(|| format!("Missing package name\n\n{usage_string}"))?;
+
+            new::create_plugin(&package)
+        }
         // This is only meant to be used by the CI, since using awk for this can be a bit spotty on
         // macOS
         "known-packages" => list_known_packages(),
@@ -147,6 +167,42 @@ pub fn main_with_args(command_name: &str, args: impl IntoIterator<Item = String>
     }
 }
 
+/// Build and bundle one or more packages as universal macOS binaries. This builds both
+/// `x86_64-apple-darwin` and `aarch64-apple-darwin` and combines the two using lipo.
+fn bundle_universal(command_name: &str, packages: &[String], other_args: &[String]) -> Result<()> {
+    // Cargo will also error out on duplicate `--target` options, but it seems like a good idea to
+    // preemptively abort the bundling process if that happens
+    for arg in other_args {
+        if arg == "--target" || arg.starts_with("--target=") {
+            anyhow::bail!(
+                "Universal builds with '{command_name} bundle' are incompatible with the '{arg}' \
+                 option."
+            )
+        }
+    }
+
+    // We can just use the regular build function here. There's sadly no way to build both targets
+    // in parallel, so this will likely take twice as long as a regular build.
+    // TODO: Explicitly specifying the target even on the native target causes a rebuild in the
+    //       target `target/<target_triple>` directory. This makes bundling much simpler because
+    //       there's no conditional logic required based on the current platform, but it does waste
+    //       some resources and requires a rebuild if the native target was already built.
+    let mut x86_64_args = other_args.to_vec();
+    x86_64_args.push(String::from("--target=x86_64-apple-darwin"));
+    build(packages, &x86_64_args)?;
+    let mut aarch64_args = other_args.to_vec();
+    aarch64_args.push(String::from("--target=aarch64-apple-darwin"));
+    build(packages, &aarch64_args)?;
+
+    // This `true` indicates a universal build. This will cause the two sets of built binaries to
+    // be lipo'd together into universal binaries before bundling
+    for package in packages {
+        bundle(package, other_args, true)?;
+    }
+
+    Ok(())
+}
+
 /// Change the current directory into the Cargo workspace's root.
 ///
 /// This is using a heuristic to find the workspace root. It considers all ancestor directories of
@@ -184,10 +240,21 @@ pub fn chdir_workspace_root() -> Result<()> {
 /// before calling [`bundle()`]. This requires the current working directory to have been set to
 /// the workspace's root using [`chdir_workspace_root()`].
 pub fn build(packages: &[String], args: &[String]) -> Result<()> {
+    cargo_build(&["build"], packages, args)
+}
+
+/// The same as [`build()`], but builds the packages using `cargo xwin build` instead. This makes it
+/// possible to cross compile for the `*-pc-windows-msvc` targets from Linux and macOS using
+/// [cargo-xwin](https://github.com/rust-cross/cargo-xwin).
+pub fn build_xwin(packages: &[String], args: &[String]) -> Result<()> {
+    cargo_build(&["xwin", "build"], packages, args)
+}
+
+fn cargo_build(cargo_command: &[&str], packages: &[String], args: &[String]) -> Result<()> {
     let package_args = packages.iter().flat_map(|package| ["-p", package]);
 
     let status = Command::new("cargo")
-        .arg("build")
+        .args(cargo_command)
         .args(package_args)
         .args(args)
         .status()
@@ -214,39 +281,7 @@ pub fn build(packages: &[String], args: &[String]) -> Result<()> {
 /// specified instead, then this will assume both `x86_64-apple-darwin` and `aarch64-apple-darwin`
 /// have been built and it will try to lipo those together instead.
 pub fn bundle(package: &str, args: &[String], universal: bool) -> Result<()> {
-    let mut build_type_dir = "debug";
-    let mut cross_compile_target: Option<String> = None;
-    for arg_idx in (0..args.len()).rev() {
-        let arg = &args[arg_idx];
-        match arg.as_str() {
-            "--profile" => {
-                // Since Rust 1.57 you can have custom profiles
-                build_type_dir = args.get(arg_idx + 1).context("Missing profile name")?;
-            }
-            "--release" => build_type_dir = "release",
-            "--target" => {
-                // When cross compiling we should generate the correct bundle type
-                cross_compile_target = Some(
-                    args.get(arg_idx + 1)
-                        .context("Missing cross-compile target")?
-                        .to_owned(),
-                );
-            }
-            arg if arg.starts_with("--profile=") => {
-                build_type_dir = arg
-                    .strip_prefix("--profile=")
-                    .context("Missing profile name")?;
-            }
-            arg if arg.starts_with("--target=") => {
-                cross_compile_target = Some(
-                    arg.strip_prefix("--target=")
-                        .context("Missing cross-compile target")?
-                        .to_owned(),
-                );
-            }
-            _ => (),
-        }
-    }
+    let (build_type_dir, cross_compile_target) = parse_build_args(args)?;
 
     // We can bundle both library targets (for plugins) and binary targets (for standalone
     // applications)
@@ -323,6 +358,46 @@ to your Cargo.toml file?"#,
     Ok(())
 }
 
+/// Parse the build profile directory and the cross compilation target, if any, from a set of `cargo
+/// build` arguments.
+fn parse_build_args(args: &[String]) -> Result<(&str, Option<String>)> {
+    let mut build_type_dir = "debug";
+    let mut cross_compile_target: Option<String> = None;
+    for arg_idx in (0..args.len()).rev() {
+        let arg = &args[arg_idx];
+        match arg.as_str() {
+            "--profile" => {
+                // Since Rust 1.57 you can have custom profiles
+                build_type_dir = args.get(arg_idx + 1).context("Missing profile name")?;
+            }
+            "--release" => build_type_dir = "release",
+            "--target" => {
+                // When cross compiling we should generate the correct bundle type
+                cross_compile_target = Some(
+                    args.get(arg_idx + 1)
+                        .context("Missing cross-compile target")?
+                        .to_owned(),
+                );
+            }
+            arg if arg.starts_with("--profile=") => {
+                build_type_dir = arg
+                    .strip_prefix("--profile=")
+                    .context("Missing profile name")?;
+            }
+            arg if arg.starts_with("--target=") => {
+                cross_compile_target = Some(
+                    arg.strip_prefix("--target=")
+                        .context("Missing cross-compile target")?
+                        .to_owned(),
+                );
+            }
+            _ => (),
+        }
+    }
+
+    Ok((build_type_dir, cross_compile_target))
+}
+
 /// Bundle a standalone target. If `bin_path` contains more than one path, then the binaries will be
 /// combined into a single binary using a method that depends on the compilation target. For
 /// universal macOS builds this uses lipo.
@@ -331,10 +406,11 @@ fn bundle_binary(
     bin_paths: &[&Path],
     compilation_target: CompilationTarget,
 ) -> Result<()> {
-    let bundle_name = match load_bundler_config()?.and_then(|c| c.get(package).cloned()) {
-        Some(PackageConfig { name: Some(name) }) => name,
-        _ => package.to_string(),
-    };
+    let package_config = load_package_config(package)?;
+    let bundle_name = package_config
+        .name
+        .clone()
+        .unwrap_or_else(|| package.to_string());
 
     // On MacOS the standalone target needs to be in a bundle
     let standalone_bundle_binary_name =
@@ -393,10 +469,11 @@ fn bundle_plugin(
     lib_paths: &[&Path],
     compilation_target: CompilationTarget,
 ) -> Result<()> {
-    let bundle_name = match load_bundler_config()?.and_then(|c| c.get(package).cloned()) {
-        Some(PackageConfig { name: Some(name) }) => name,
-        _ => package.to_string(),
-    };
+    let package_config = load_package_config(package)?;
+    let bundle_name = package_config
+        .name
+        .clone()
+        .unwrap_or_else(|| package.to_string());
 
     // We'll detect the plugin formats supported by the plugin binary and create bundled accordingly.
     // If `lib_path` contains paths to multiple plugins that need to be combined into a macOS
@@ -436,6 +513,7 @@ fn bundle_plugin(
         maybe_create_macos_bundle_metadata(
             package,
             &bundle_name,
+            &package_config,
             &clap_bundle_home,
             compilation_target,
             BundleType::Plugin,
@@ -464,6 +542,7 @@ fn bundle_plugin(
         maybe_create_macos_bundle_metadata(
             package,
             &bundle_name,
+            &package_config,
             &vst2_bundle_home,
             compilation_target,
             BundleType::Plugin,
@@ -491,6 +570,7 @@ fn bundle_plugin(
         maybe_create_macos_bundle_metadata(
             package,
             &bundle_name,
+            &package_config,
             vst3_bundle_home,
             compilation_target,
             BundleType::Plugin,
@@ -506,6 +586,139 @@ fn bundle_plugin(
     Ok(())
 }
 
+/// Copy the bundles created by [`bundle()`] for a package to the current user's plugin
+/// directories. The arguments should be the same as those passed to [`bundle()`]. Bundles can only
+/// be installed when they were built for the current platform. Standalone binaries are not
+/// installed.
+///
+/// The bundles are installed to:
+///
+/// - Linux: `~/.clap`, `~/.vst3`, and `~/.vst`
+/// - macOS: `~/Library/Audio/Plug-Ins/{CLAP,VST3,VST}`
+/// - Windows: `%LOCALAPPDATA%\Programs\Common\{CLAP,VST3}`
+pub fn install(package: &str, args: &[String], universal: bool) -> Result<()> {
+    let target = if universal {
+        CompilationTarget::MacOSUniversal
+    } else {
+        let (_, cross_compile_target) = parse_build_args(args)?;
+        compilation_target(cross_compile_target.as_deref())?
+    };
+    let native_target = compilation_target(None)?;
+    let is_native_platform = matches!(
+        (target, native_target),
+        (CompilationTarget::Linux(_), CompilationTarget::Linux(_))
+            | (
+                CompilationTarget::MacOS(_) | CompilationTarget::MacOSUniversal,
+                CompilationTarget::MacOS(_)
+            )
+            | (CompilationTarget::Windows(_), CompilationTarget::Windows(_))
+    );
+    if !is_native_platform {
+        anyhow::bail!("Cannot install bundles built for {target:?} on {native_target:?}");
+    }
+
+    let bundle_name = load_package_config(package)?
+        .name
+        .unwrap_or_else(|| package.to_string());
+    let bundles = [
+        ("CLAP", clap_bundle_library_name(&bundle_name, target)),
+        ("VST3", vst3_bundle_library_name(&bundle_name, target)),
+        ("VST2", vst2_bundle_library_name(&bundle_name, target)),
+    ];
+
+    let mut installed_plugin = false;
+    for (format, bundle_library_name) in bundles {
+        // The bundle is either a single file or a directory, depending on the format and the
+        // platform. In both cases this is the first component of the library's path.
+        let bundle_file_name = Path::new(&bundle_library_name)
+            .components()
+            .next()
+            .expect("Malformed bundle library path");
+        let bundle_home = Path::new(BUNDLE_HOME).join(bundle_file_name);
+        if !bundle_home.exists() {
+            continue;
+        }
+
+        let install_dir = match plugin_install_dir(format, target)? {
+            Some(install_dir) => install_dir,
+            None => {
+                eprintln!(
+                    "WARNING: There is no standard {format} plugin directory on this platform, not \
+                     installing '{}'",
+                    bundle_home.display()
+                );
+                continue;
+            }
+        };
+        fs::create_dir_all(&install_dir).with_context(|| {
+            format!("Could not create the directory '{}'", install_dir.display())
+        })?;
+
+        let install_path = install_dir.join(bundle_file_name);
+        util::copy_recursively(&bundle_home, &install_path).with_context(|| {
+            format!(
+                "Could not install '{}' to '{}'",
+                bundle_home.display(),
+                install_path.display()
+            )
+        })?;
+        installed_plugin = true;
+
+        eprintln!(
+            "Installed the {format} bundle to '{}'",
+            install_path.display()
+        );
+    }
+    if !installed_plugin {
+        eprintln!("Could not find any plugin bundles to install for '{package}'")
+    }
+
+    Ok(())
+}
+
+/// The current user's plugin directory for a plugin format. Returns `None` if the platform does not
+/// have a standard directory for the format.
+fn plugin_install_dir(format: &str, target: CompilationTarget) -> Result<Option<PathBuf>> {
+    match target {
+        CompilationTarget::Linux(_) => {
+            let home_dir = env_path("HOME")?;
+            Ok(match format {
+                "CLAP" => Some(home_dir.join(".clap")),
+                "VST3" => Some(home_dir.join(".vst3")),
+                "VST2" => Some(home_dir.join(".vst")),
+                _ => None,
+            })
+        }
+        CompilationTarget::MacOS(_) | CompilationTarget::MacOSUniversal => {
+            let plugins_dir = env_path("HOME")?
+                .join("Library")
+                .join("Audio")
+                .join("Plug-Ins");
+            Ok(match format {
+                "CLAP" => Some(plugins_dir.join("CLAP")),
+                "VST3" => Some(plugins_dir.join("VST3")),
+                "VST2" => Some(plugins_dir.join("VST")),
+                _ => None,
+            })
+        }
+        CompilationTarget::Windows(_) => {
+            let common_dir = env_path("LOCALAPPDATA")?.join("Programs").join("Common");
+            Ok(match format {
+                "CLAP" => Some(common_dir.join("CLAP")),
+                "VST3" => Some(common_dir.join("VST3")),
+                _ => None,
+            })
+        }
+    }
+}
+
+/// Read a path from an environment variable.
+fn env_path(variable: &str) -> Result<PathBuf> {
+    std::env::var_os(variable)
+        .map(PathBuf::from)
+        .with_context(|| format!("'${variable}' is not set"))
+}
+
 /// This lists the packages configured in `bundler.toml`. This is only used as part of the CI when
 /// bundling plugins.
 pub fn list_known_packages() -> Result<()> {
@@ -536,6 +749,22 @@ fn load_bundler_config() -> Result<Option<BundlerConfig>> {
     Ok(Some(result))
 }
 
+/// Remove all occurrences of `flag` from `args`. Returns whether the flag was present.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let num_args = args.len();
+    args.retain(|arg| arg != flag);
+
+    args.len() != num_args
+}
+
+/// Load the `bundler.toml` options for a package. If the file does not exist or if it does not
+/// contain an entry for the package, then this returns the default options.
+fn load_package_config(package: &str) -> Result<PackageConfig> {
+    Ok(load_bundler_config()?
+        .and_then(|mut config| config.remove(package))
+        .unwrap_or_default())
+}
+
 /// Split the `xtask bundle` arguments into a list of packages and a list of other arguments. The
 /// package vector either contains just the first argument, or if the arguments iterator starts with
 /// one or more occurences of `-p <package>` then this will contain all those packages.
@@ -567,22 +796,24 @@ fn split_bundle_args(
 /// plugin bundles.
 fn compilation_target(cross_compile_target: Option<&str>) -> Result<CompilationTarget> {
     match cross_compile_target {
-        Some("i686-unknown-linux-gnu") => Ok(CompilationTarget::Linux(Architecture::X86)),
-        Some("i686-apple-darwin") => Ok(CompilationTarget::MacOS(Architecture::X86)),
-        Some("i686-pc-windows-gnu") | Some("i686-pc-windows-msvc") => {
-            Ok(CompilationTarget::Windows(Architecture::X86))
-        }
-        Some("x86_64-unknown-linux-gnu") => Ok(CompilationTarget::Linux(Architecture::X86_64)),
-        Some("x86_64-apple-darwin") => Ok(CompilationTarget::MacOS(Architecture::X86_64)),
-        Some("x86_64-pc-windows-gnu") | Some("x86_64-pc-windows-msvc") => {
-            Ok(CompilationTarget::Windows(Architecture::X86_64))
-        }
-        Some("aarch64-unknown-linux-gnu") => Ok(CompilationTarget::Linux(Architecture::AArch64)),
-        Some("aarch64-apple-darwin") => Ok(CompilationTarget::MacOS(Architecture::AArch64)),
-        Some("aarch64-pc-windows-gnu") | Some("aarch64-pc-windows-msvc") => {
-            Ok(CompilationTarget::Windows(Architecture::AArch64))
+        Some(target) => {
+            // Target triples are `<arch>-<vendor>-<os>[-<env>]`. The vendor and environment don't
+            // affect the bundle layout, so this also covers targets like `*-unknown-linux-musl`
+            let mut components = target.split('-');
+            let architecture = match components.next() {
+                Some("i586" | "i686") => Architecture::X86,
+                Some("x86_64") => Architecture::X86_64,
+                Some("aarch64") => Architecture::AArch64,
+                _ => anyhow::bail!("Unhandled cross-compilation target: {}", target),
+            };
+            let os = components.nth(1);
+            match os {
+                Some("linux") => Ok(CompilationTarget::Linux(architecture)),
+                Some("darwin") => Ok(CompilationTarget::MacOS(architecture)),
+                Some("windows") => Ok(CompilationTarget::Windows(architecture)),
+                _ => anyhow::bail!("Unhandled cross-compilation target: {}", target),
+            }
         }
-        Some(target) => anyhow::bail!("Unhandled cross-compilation target: {}", target),
         None => {
             #[cfg(target_arch = "x86")]
             let architecture = Architecture::X86;
@@ -705,10 +936,12 @@ fn vst3_bundle_library_name(package: &str, target: CompilationTarget) -> String
 /// If compiling for macOS, create all of the bundl-y stuff Steinberg and Apple require you to have.
 ///
 /// This still requires you to move the dylib file to `{bundle_home}/Contents/macOS/{package}`
-/// yourself first.
+/// yourself first. The bundle identifier and version are taken from `package_config` when they are
+/// set.
 pub fn maybe_create_macos_bundle_metadata(
     package: &str,
     display_name: &str,
+    package_config: &PackageConfig,
     bundle_home: &Path,
     target: CompilationTarget,
     bundle_type: BundleType,
@@ -725,8 +958,25 @@ pub fn maybe_create_macos_bundle_metadata(
         BundleType::Binary => "APPL",
     };
 
-    // TODO: May want to add bundler.toml fields for the identifier, version and signature at some
-    //       point.
+    let bundle_id = package_config
+        .bundle_id
+        .clone()
+        .unwrap_or_else(|| format!("com.nih-plug.{package}"));
+    let version = package_config.version.as_deref().unwrap_or("1.0.0");
+    // Universal binaries should prefer the native slice on Apple Silicon machines
+    let architecture_priority = match target {
+        CompilationTarget::MacOSUniversal => {
+            "
+    <key>LSArchitecturePriority</key>
+    <array>
+      <string>arm64</string>
+      <string>x86_64</string>
+    </array>"
+        }
+        _ => "",
+    };
+
+    // TODO: May want to add a bundler.toml field for the signature at some point
     fs::write(
         bundle_home.join("Contents").join("PkgInfo"),
         format!("{package_type}????"),
@@ -744,7 +994,9 @@ pub fn maybe_create_macos_bundle_metadata(
     <key>CFBundleIconFile</key>
     <string></string>
     <key>CFBundleIdentifier</key>
-    <string>com.nih-plug.{package}</string>
+    <string>{bundle_id}</string>
+    <key>CFBundleInfoDictionaryVersion</key>
+    <string>6.0</string>
     <key>CFBundleName</key>
     <string>{display_name}</string>
     <key>CFBundleDisplayName</key>
@@ -754,13 +1006,13 @@ pub fn maybe_create_macos_bundle_metadata(
     <key>CFBundleSignature</key>
     <string>????</string>
     <key>CFBundleShortVersionString</key>
-    <string>1.0.0</string>
+    <string>{version}</string>
     <key>CFBundleVersion</key>
-    <string>1.0.0</string>
+    <string>{version}</string>
     <key>NSHumanReadableCopyright</key>
     <string></string>
     <key>NSHighResolutionCapable</key>
-    <true/>
+    <true/>{architecture_priority}
   </dict>
 </plist>
 "#),
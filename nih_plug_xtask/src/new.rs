@@ -0,0 +1,98 @@
+//! Scaffolding for new plugin crates, used by `cargo xtask new`.
+
+use anyhow::{Context, Result};
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::path::Path;
+
+const CARGO_TOML_TEMPLATE: &str = include_str!("../templates/plugin/Cargo.toml.template");
+const LIB_RS_TEMPLATE: &str = include_str!("../templates/plugin/lib.rs.template");
+const EDITOR_RS_TEMPLATE: &str = include_str!("../templates/plugin/editor.rs.template");
+
+/// Create a new plugin crate called `package` in the directory of the same name. The crate contains
+/// a simple gain plugin with a parameters struct, a vizia editor, and CLAP and VST3 exports.
+pub fn create_plugin(package: &str) -> Result<()> {
+    let is_valid_name = package.starts_with(|c: char| c.is_ascii_lowercase())
+        && package
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-');
+    if !is_valid_name {
+        anyhow::bail!(
+            "'{package}' is not a valid package name. Package names should start with a letter \
+             and may only contain lowercase letters, digits, underscores, and dashes."
+        );
+    }
+
+    let package_dir = Path::new(package);
+    if package_dir.exists() {
+        anyhow::bail!("'{}' already exists", package_dir.display());
+    }
+
+    let words: Vec<&str> = package
+        .split(['_', '-'])
+        .filter(|w| !w.is_empty())
+        .collect();
+    let struct_name: String = words.iter().map(|word| capitalize(word)).collect();
+    let display_name = words
+        .iter()
+        .map(|word| capitalize(word))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let clap_id = format!("com.your-domain.{}", words.join("-"));
+    let vst3_class_id = random_class_id();
+
+    let render = |template: &str| {
+        template
+            .replace("{{package}}", package)
+            .replace("{{struct_name}}", &struct_name)
+            .replace("{{display_name}}", &display_name)
+            .replace("{{clap_id}}", &clap_id)
+            .replace("{{vst3_class_id}}", &vst3_class_id)
+    };
+
+    let src_dir = package_dir.join("src");
+    fs::create_dir_all(&src_dir)
+        .with_context(|| format!("Could not create '{}'", src_dir.display()))?;
+    for (path, template) in [
+        (package_dir.join("Cargo.toml"), CARGO_TOML_TEMPLATE),
+        (src_dir.join("lib.rs"), LIB_RS_TEMPLATE),
+        (src_dir.join("editor.rs"), EDITOR_RS_TEMPLATE),
+    ] {
+        fs::write(&path, render(template))
+            .with_context(|| format!("Could not write '{}'", path.display()))?;
+    }
+
+    eprintln!(
+        "Created a new plugin at '{}'. Add it to your workspace's members, and then run \
+         'cargo xtask bundle {package} --release' to build it.",
+        package_dir.display()
+    );
+
+    Ok(())
+}
+
+/// Uppercase the first character of a word.
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+/// Generate a random 16 character alphanumeric VST3 class ID. The standard library's hasher is
+/// randomly seeded, so this doesn't need a dedicated random number generator.
+fn random_class_id() -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+    let random_state = RandomState::new();
+    (0..16u64)
+        .map(|i| {
+            let mut hasher = random_state.build_hasher();
+            hasher.write_u64(i);
+
+            ALPHABET[(hasher.finish() % ALPHABET.len() as u64) as usize] as char
+        })
+        .collect()
+}
@@ -17,6 +17,31 @@ pub fn reflink<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> Result<Option<
     reflink::reflink_or_copy(from, to).context("Could not reflink or copy file")
 }
 
+/// Recursively copy a file or a directory from `from` to `to`, replacing `to` if it already exists.
+pub fn copy_recursively<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> Result<()> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    if to.is_dir() {
+        fs::remove_dir_all(to)
+            .with_context(|| format!("Could not remove '{}' before copying", to.display()))?;
+    }
+
+    if from.is_dir() {
+        fs::create_dir_all(to)
+            .with_context(|| format!("Could not create the directory '{}'", to.display()))?;
+        for entry in fs::read_dir(from)
+            .with_context(|| format!("Could not read the directory '{}'", from.display()))?
+        {
+            let entry = entry.context("Could not read directory entry")?;
+            copy_recursively(entry.path(), to.join(entry.file_name()))?;
+        }
+    } else {
+        reflink(from, to)
+            .with_context(|| format!("Could not copy {} to {}", from.display(), to.display()))?;
+    }
+
+    Ok(())
+}
+
 /// Either reflink `from` to `to` if `from` contains a single element, or combine multiple binaries
 /// into `to` depending on the compilation target
 pub fn reflink_or_combine<P: AsRef<Path>>(
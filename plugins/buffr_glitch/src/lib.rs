@@ -194,6 +194,8 @@ impl Plugin for BuffrGlitch {
 
     type SysExMessage = ();
     type BackgroundTask = ();
+    type GuiMessage = ();
+    type EditorEvent = ();
 
     fn params(&self) -> Arc<dyn Params> {
         self.params.clone()
@@ -312,6 +312,8 @@ impl Plugin for Crisp {
 
     type SysExMessage = ();
     type BackgroundTask = ();
+    type GuiMessage = ();
+    type EditorEvent = ();
 
     fn params(&self) -> Arc<dyn Params> {
         self.params.clone()
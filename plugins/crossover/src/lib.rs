@@ -183,10 +183,13 @@ impl Plugin for Crossover {
             aux_inputs: &[],
             aux_outputs: &["Band 1", "Band 2", "Band 3", "Band 4", "Band 5"],
         },
+        channel_layouts: ChannelLayouts::const_default(),
     }];
 
     type SysExMessage = ();
     type BackgroundTask = ();
+    type GuiMessage = ();
+    type EditorEvent = ();
 
     fn params(&self) -> Arc<dyn Params> {
         self.params.clone()
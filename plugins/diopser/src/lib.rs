@@ -121,6 +121,8 @@ impl Plugin for Diopser {
 
     type SysExMessage = ();
     type BackgroundTask = ();
+    type GuiMessage = ();
+    type EditorEvent = ();
 
     fn params(&self) -> Arc<dyn Params> {
         self.params.clone()
@@ -16,10 +16,8 @@
 
 use nih_plug::prelude::*;
 use nih_plug::util::window::multiply_with_window;
-use realfft::num_complex::Complex32;
-use realfft::{RealFftPlanner, RealToComplex};
+use nih_plug::util::{Complex32, FftBackend, RealFft};
 use std::f32;
-use std::sync::Arc;
 use triple_buffer::TripleBuffer;
 
 pub const SPECTRUM_WINDOW_SIZE: usize = 2048;
@@ -54,8 +52,8 @@ pub struct SpectrumInput {
     /// A scratch buffer to compute the resulting power amplitude spectrum.
     spectrum_result_buffer: Spectrum,
 
-    /// The algorithm for the FFT operation used for our spectrum analyzer.
-    plan: Arc<dyn RealToComplex<f32>>,
+    /// The FFT used for our spectrum analyzer.
+    fft: RealFft,
     /// A Hann window window, passed to the STFT helper. The gain compensation is already part of
     /// this window to save a multiplication step.
     compensated_window_function: Vec<f32>,
@@ -79,7 +77,7 @@ impl SpectrumInput {
             triple_buffer_input,
             spectrum_result_buffer: [0.0; SPECTRUM_WINDOW_SIZE / 2 + 1],
 
-            plan: RealFftPlanner::new().plan_fft_forward(SPECTRUM_WINDOW_SIZE),
+            fft: RealFft::new(SPECTRUM_WINDOW_SIZE),
             compensated_window_function: util::window::hann(SPECTRUM_WINDOW_SIZE)
                 .into_iter()
                 // Include the gain compensation in the window function to save some multiplications
@@ -112,14 +110,8 @@ impl SpectrumInput {
             |_channel_idx, real_fft_scratch_buffer| {
                 multiply_with_window(real_fft_scratch_buffer, &self.compensated_window_function);
 
-                self.plan
-                    .process_with_scratch(
-                        real_fft_scratch_buffer,
-                        &mut self.complex_fft_buffer,
-                        // We don't actually need a scratch buffer
-                        &mut [],
-                    )
-                    .unwrap();
+                self.fft
+                    .forward(real_fft_scratch_buffer, &mut self.complex_fft_buffer);
 
                 // We'll use peak meter-like behavior for the spectrum analyzer to make things
                 // easier to dial in. Values that are higher than the old value snap to the new
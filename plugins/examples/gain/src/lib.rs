@@ -134,6 +134,9 @@ impl Plugin for Gain {
             // are generated as needed. This layout will be called 'Stereo', while the other one is
             // given the name 'Mono' based no the number of input and output channels.
             names: PortNames::const_default(),
+            // Surround and ambisonic plugins can assign speaker arrangements to their ports here.
+            // Mono and stereo ports are presented to the host as such without setting anything.
+            channel_layouts: ChannelLayouts::const_default(),
         },
         AudioIOLayout {
             main_input_channels: NonZeroU32::new(1),
@@ -158,6 +161,8 @@ impl Plugin for Gain {
     // documentation for more information. `()` means that the plugin does not have any background
     // tasks.
     type BackgroundTask = ();
+    type GuiMessage = ();
+    type EditorEvent = ();
 
     fn params(&self) -> Arc<dyn Params> {
         self.params.clone()
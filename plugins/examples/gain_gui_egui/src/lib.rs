@@ -95,6 +95,8 @@ impl Plugin for Gain {
 
     type SysExMessage = ();
     type BackgroundTask = ();
+    type GuiMessage = ();
+    type EditorEvent = ();
 
     fn params(&self) -> Arc<dyn Params> {
         self.params.clone()
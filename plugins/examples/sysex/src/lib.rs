@@ -62,6 +62,8 @@ impl Plugin for SysEx {
 
     type SysExMessage = CoolSysExMessage;
     type BackgroundTask = ();
+    type GuiMessage = ();
+    type EditorEvent = ();
 
     fn params(&self) -> Arc<dyn Params> {
         self.params.clone()
@@ -19,9 +19,8 @@ use atomic_float::AtomicF32;
 use crossbeam::atomic::AtomicCell;
 use editor::EditorMode;
 use nih_plug::prelude::*;
+use nih_plug::util::{Complex32, FftBackend, RealFft};
 use nih_plug_vizia::ViziaState;
-use realfft::num_complex::Complex32;
-use realfft::{ComplexToReal, RealFftPlanner, RealToComplex};
 use std::sync::atomic::Ordering;
 use std::sync::{Arc, Mutex};
 use triple_buffer::TripleBuffer;
@@ -72,9 +71,9 @@ pub struct SpectralCompressor {
     /// the magic happens.
     compressor_bank: compressor_bank::CompressorBank,
 
-    /// The algorithms for the FFT and IFFT operations, for each supported order so we can switch
-    /// between them without replanning or allocations. Initialized during `initialize()`.
-    plan_for_order: Option<[Plan; MAX_WINDOW_ORDER - MIN_WINDOW_ORDER + 1]>,
+    /// The FFT and IFFT operations for each supported order so we can switch between them without
+    /// replanning or allocations. Initialized during `initialize()`.
+    fft_for_order: Option<[RealFft; MAX_WINDOW_ORDER - MIN_WINDOW_ORDER + 1]>,
     /// The output of our real->complex FFT.
     complex_fft_buffer: Vec<Complex32>,
 
@@ -83,14 +82,6 @@ pub struct SpectralCompressor {
     analyzer_output_data: Arc<Mutex<triple_buffer::Output<AnalyzerData>>>,
 }
 
-/// An FFT plan for a specific window size, all of which will be precomputed during initilaization.
-struct Plan {
-    /// The algorithm for the FFT operation.
-    r2c_plan: Arc<dyn RealToComplex<f32>>,
-    /// The algorithm for the IFFT operation.
-    c2r_plan: Arc<dyn ComplexToReal<f32>>,
-}
-
 #[derive(Params)]
 pub struct SpectralCompressorParams {
     /// The editor state, saved together with the parameter state so the custom scaling can be
@@ -182,7 +173,7 @@ impl Default for SpectralCompressor {
 
             // This is initialized later since we don't want to do non-trivial computations before
             // the plugin is initialized
-            plan_for_order: None,
+            fft_for_order: None,
             complex_fft_buffer: Vec::with_capacity(MAX_WINDOW_SIZE / 2 + 1),
 
             analyzer_output_data: Arc::new(Mutex::new(analyzer_output_data)),
@@ -312,6 +303,8 @@ impl Plugin for SpectralCompressor {
 
     type SysExMessage = ();
     type BackgroundTask = ();
+    type GuiMessage = ();
+    type EditorEvent = ();
 
     fn params(&self) -> Arc<dyn Params> {
         self.params.clone()
@@ -363,16 +356,12 @@ impl Plugin for SpectralCompressor {
 
         // Planning with RustFFT is very fast, but it will still allocate we we'll plan all of the
         // FFTs we might need in advance
-        if self.plan_for_order.is_none() {
-            let mut planner = RealFftPlanner::new();
-            let plan_for_order: Vec<Plan> = (MIN_WINDOW_ORDER..=MAX_WINDOW_ORDER)
-                .map(|order| Plan {
-                    r2c_plan: planner.plan_fft_forward(1 << order),
-                    c2r_plan: planner.plan_fft_inverse(1 << order),
-                })
+        if self.fft_for_order.is_none() {
+            let fft_for_order: Vec<RealFft> = (MIN_WINDOW_ORDER..=MAX_WINDOW_ORDER)
+                .map(|order| RealFft::new(1 << order))
                 .collect();
-            self.plan_for_order = Some(
-                plan_for_order
+            self.fft_for_order = Some(
+                fft_for_order
                     .try_into()
                     .unwrap_or_else(|_| panic!("Mismatched plan orders")),
             );
@@ -407,7 +396,7 @@ impl Plugin for SpectralCompressor {
 
         // These plans have already been made during initialization we can switch between versions
         // without reallocating
-        let fft_plan = &mut self.plan_for_order.as_mut().unwrap()
+        let fft = &mut self.fft_for_order.as_mut().unwrap()
             [self.params.global.window_size_order.value() as usize - MIN_WINDOW_ORDER];
         let num_bins = self.complex_fft_buffer.len();
         // The Hann window function spreads the DC signal out slightly, so we'll clear all 0-20 Hz
@@ -444,7 +433,7 @@ impl Plugin for SpectralCompressor {
                         channel_idx,
                         real_fft_buffer,
                         &mut self.complex_fft_buffer,
-                        fft_plan,
+                        fft,
                         &self.window_function,
                         &self.params,
                         &mut self.compressor_bank,
@@ -467,7 +456,7 @@ impl Plugin for SpectralCompressor {
                                 channel_idx,
                                 real_fft_buffer,
                                 &mut self.complex_fft_buffer,
-                                fft_plan,
+                                fft,
                                 &self.window_function,
                                 &mut self.compressor_bank,
                                 input_gain,
@@ -477,7 +466,7 @@ impl Plugin for SpectralCompressor {
                                 channel_idx,
                                 real_fft_buffer,
                                 &mut self.complex_fft_buffer,
-                                fft_plan,
+                                fft,
                                 &self.window_function,
                                 &self.params,
                                 &mut self.compressor_bank,
@@ -520,7 +509,7 @@ impl SpectralCompressor {
     /// `window_size` should not exceed `MAX_WINDOW_SIZE` or this will allocate.
     fn resize_for_window(&mut self, window_size: usize) {
         // The FFT algorithms for this window size have already been planned in
-        // `self.plan_for_order`, and all of these data structures already have enough capacity, so
+        // `self.fft_for_order`, and all of these data structures already have enough capacity, so
         // we just need to change some sizes.
         self.stft.set_block_size(window_size);
         self.window_function.resize(window_size, 0.0);
@@ -547,7 +536,7 @@ fn process_stft_main(
     channel_idx: usize,
     real_fft_buffer: &mut [f32],
     complex_fft_buffer: &mut [Complex32],
-    fft_plan: &Plan,
+    fft: &mut RealFft,
     window_function: &[f32],
     params: &SpectralCompressorParams,
     compressor_bank: &mut compressor_bank::CompressorBank,
@@ -563,12 +552,7 @@ fn process_stft_main(
         *sample *= window_sample * input_gain;
     }
 
-    // RustFFT doesn't actually need a scratch buffer here, so we'll pass an empty buffer
-    // instead
-    fft_plan
-        .r2c_plan
-        .process_with_scratch(real_fft_buffer, complex_fft_buffer, &mut [])
-        .unwrap();
+    fft.forward(real_fft_buffer, complex_fft_buffer);
 
     // This is where the magic happens
     compressor_bank.process(
@@ -581,10 +565,7 @@ fn process_stft_main(
 
     // Inverse FFT back into the scratch buffer. This will be added to a ring buffer
     // which gets written back to the host at a one block delay.
-    fft_plan
-        .c2r_plan
-        .process_with_scratch(complex_fft_buffer, real_fft_buffer, &mut [])
-        .unwrap();
+    fft.inverse(complex_fft_buffer, real_fft_buffer);
 
     // Apply the window function once more to reduce time domain aliasing. The gain
     // compensation compensates for the squared Hann window that would be applied if we
@@ -601,7 +582,7 @@ fn process_stft_sidechain(
     channel_idx: usize,
     real_fft_buffer: &mut [f32],
     complex_fft_buffer: &mut [Complex32],
-    fft_plan: &Plan,
+    fft: &mut RealFft,
     window_function: &[f32],
     compressor_bank: &mut compressor_bank::CompressorBank,
     input_gain: f32,
@@ -612,10 +593,7 @@ fn process_stft_sidechain(
         *sample *= window_sample * input_gain;
     }
 
-    fft_plan
-        .r2c_plan
-        .process_with_scratch(real_fft_buffer, complex_fft_buffer, &mut [])
-        .unwrap();
+    fft.forward(real_fft_buffer, complex_fft_buffer);
     compressor_bank.process_sidechain(complex_fft_buffer, channel_idx);
 }
 
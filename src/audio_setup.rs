@@ -30,6 +30,9 @@ pub struct AudioIOLayout {
     /// Optional names for the audio ports. Defining these can be useful for plugins with multiple
     /// output and input ports.
     pub names: PortNames,
+    /// Optional speaker arrangements for the audio ports, for surround and ambisonic plugins. Ports
+    /// without a channel layout are presented to the host based on their channel count.
+    pub channel_layouts: ChannelLayouts,
 }
 
 /// Construct a `NonZeroU32` value at compile time. Equivalent to `NonZeroU32::new(n).unwrap()`.
@@ -74,6 +77,70 @@ pub struct PortNames {
     pub aux_outputs: &'static [&'static str],
 }
 
+/// Contains speaker arrangements for the ports defined in an `AudioIOLayout`. Setting these is
+/// optional. A port's channel layout must have the same number of channels as the port.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChannelLayouts {
+    /// The channel layout for the main input port.
+    pub main_input: Option<ChannelLayout>,
+    /// The channel layout for the main output port.
+    pub main_output: Option<ChannelLayout>,
+    /// Channel layouts for the auxiliary (sidechain) input ports. Ports past the end of this slice
+    /// don't have a channel layout.
+    pub aux_inputs: &'static [ChannelLayout],
+    /// Channel layouts for the auxiliary output ports. Ports past the end of this slice don't have
+    /// a channel layout.
+    pub aux_outputs: &'static [ChannelLayout],
+}
+
+/// A named speaker arrangement for an audio port. The order of the channels in the plugin's
+/// buffers is listed for every layout. The wrappers translate these to the plugin API's speaker
+/// arrangements, so the channel order is the same regardless of the plugin API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChannelLayout {
+    /// A single channel.
+    Mono,
+    /// Left and right.
+    Stereo,
+    /// 5.0 surround: left, right, center, back left, and back right.
+    Surround50,
+    /// 5.1 surround: left, right, center, LFE, back left, and back right.
+    Surround51,
+    /// 7.0 surround: left, right, center, back left, back right, side left, and side right.
+    Surround70,
+    /// 7.1 surround: left, right, center, LFE, back left, back right, side left, and side right.
+    Surround71,
+    /// 7.1.4 surround: the 7.1 channels followed by top front left, top front right, top back
+    /// left, and top back right.
+    Surround714,
+    /// First order ambisonics with four channels, using ACN channel ordering and SN3D
+    /// normalization (AmbiX).
+    FirstOrderAmbisonics,
+    /// Second order ambisonics with nine channels, using ACN channel ordering and SN3D
+    /// normalization (AmbiX).
+    SecondOrderAmbisonics,
+    /// Third order ambisonics with sixteen channels, using ACN channel ordering and SN3D
+    /// normalization (AmbiX).
+    ThirdOrderAmbisonics,
+}
+
+/// A speaker position in a surround [`ChannelLayout`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Speaker {
+    FrontLeft,
+    FrontRight,
+    FrontCenter,
+    LowFrequency,
+    BackLeft,
+    BackRight,
+    SideLeft,
+    SideRight,
+    TopFrontLeft,
+    TopFrontRight,
+    TopBackLeft,
+    TopBackRight,
+}
+
 /// Configuration for (the host's) audio buffers.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct BufferConfig {
@@ -115,6 +182,7 @@ impl AudioIOLayout {
             aux_input_ports: &[],
             aux_output_ports: &[],
             names: PortNames::const_default(),
+            channel_layouts: ChannelLayouts::const_default(),
         }
     }
 
@@ -189,6 +257,166 @@ impl AudioIOLayout {
     }
 }
 
+impl AudioIOLayout {
+    /// The channel layout for the main input port, if the port exists and has a channel layout.
+    /// Mono and stereo ports without an explicit channel layout use [`ChannelLayout::Mono`] and
+    /// [`ChannelLayout::Stereo`].
+    pub fn main_input_channel_layout(&self) -> Option<ChannelLayout> {
+        let num_channels = self.main_input_channels?.get();
+        self.channel_layouts
+            .main_input
+            .or_else(|| ChannelLayout::from_channel_count(num_channels))
+    }
+
+    /// The channel layout for the main output port, if the port exists and has a channel layout.
+    /// Mono and stereo ports without an explicit channel layout use [`ChannelLayout::Mono`] and
+    /// [`ChannelLayout::Stereo`].
+    pub fn main_output_channel_layout(&self) -> Option<ChannelLayout> {
+        let num_channels = self.main_output_channels?.get();
+        self.channel_layouts
+            .main_output
+            .or_else(|| ChannelLayout::from_channel_count(num_channels))
+    }
+
+    /// The channel layout for the auxiliary input port with the given index, if the port exists
+    /// and has a channel layout.
+    pub fn aux_input_channel_layout(&self, idx: usize) -> Option<ChannelLayout> {
+        let num_channels = self.aux_input_ports.get(idx)?.get();
+        self.channel_layouts
+            .aux_inputs
+            .get(idx)
+            .copied()
+            .or_else(|| ChannelLayout::from_channel_count(num_channels))
+    }
+
+    /// The channel layout for the auxiliary output port with the given index, if the port exists
+    /// and has a channel layout.
+    pub fn aux_output_channel_layout(&self, idx: usize) -> Option<ChannelLayout> {
+        let num_channels = self.aux_output_ports.get(idx)?.get();
+        self.channel_layouts
+            .aux_outputs
+            .get(idx)
+            .copied()
+            .or_else(|| ChannelLayout::from_channel_count(num_channels))
+    }
+}
+
+impl ChannelLayouts {
+    /// [`ChannelLayouts::default()`], but as a const function. Used when initializing
+    /// `Plugin::AUDIO_IO_LAYOUTS`. (<https://github.com/rust-lang/rust/issues/67792>)
+    pub const fn const_default() -> Self {
+        Self {
+            main_input: None,
+            main_output: None,
+            aux_inputs: &[],
+            aux_outputs: &[],
+        }
+    }
+}
+
+impl ChannelLayout {
+    /// The number of channels in this layout.
+    pub const fn num_channels(&self) -> u32 {
+        match self {
+            ChannelLayout::Mono => 1,
+            ChannelLayout::Stereo => 2,
+            ChannelLayout::Surround50 => 5,
+            ChannelLayout::Surround51 => 6,
+            ChannelLayout::Surround70 => 7,
+            ChannelLayout::Surround71 => 8,
+            ChannelLayout::Surround714 => 12,
+            ChannelLayout::FirstOrderAmbisonics => 4,
+            ChannelLayout::SecondOrderAmbisonics => 9,
+            ChannelLayout::ThirdOrderAmbisonics => 16,
+        }
+    }
+
+    /// The speaker for each of the layout's channels, in channel order. Returns `None` for
+    /// ambisonic layouts, since those channels don't correspond to speakers.
+    pub const fn speakers(&self) -> Option<&'static [Speaker]> {
+        use Speaker::*;
+
+        match self {
+            ChannelLayout::Mono => Some(&[FrontCenter]),
+            ChannelLayout::Stereo => Some(&[FrontLeft, FrontRight]),
+            ChannelLayout::Surround50 => {
+                Some(&[FrontLeft, FrontRight, FrontCenter, BackLeft, BackRight])
+            }
+            ChannelLayout::Surround51 => Some(&[
+                FrontLeft,
+                FrontRight,
+                FrontCenter,
+                LowFrequency,
+                BackLeft,
+                BackRight,
+            ]),
+            ChannelLayout::Surround70 => Some(&[
+                FrontLeft,
+                FrontRight,
+                FrontCenter,
+                BackLeft,
+                BackRight,
+                SideLeft,
+                SideRight,
+            ]),
+            ChannelLayout::Surround71 => Some(&[
+                FrontLeft,
+                FrontRight,
+                FrontCenter,
+                LowFrequency,
+                BackLeft,
+                BackRight,
+                SideLeft,
+                SideRight,
+            ]),
+            ChannelLayout::Surround714 => Some(&[
+                FrontLeft,
+                FrontRight,
+                FrontCenter,
+                LowFrequency,
+                BackLeft,
+                BackRight,
+                SideLeft,
+                SideRight,
+                TopFrontLeft,
+                TopFrontRight,
+                TopBackLeft,
+                TopBackRight,
+            ]),
+            ChannelLayout::FirstOrderAmbisonics
+            | ChannelLayout::SecondOrderAmbisonics
+            | ChannelLayout::ThirdOrderAmbisonics => None,
+        }
+    }
+
+    /// The index of `speaker`'s channel in this layout, if the layout contains that speaker.
+    pub fn speaker_channel_idx(&self, speaker: Speaker) -> Option<usize> {
+        self.speakers()?
+            .iter()
+            .position(|candidate| *candidate == speaker)
+    }
+
+    /// The ambisonics order for ambisonic layouts, or `None` for speaker based layouts.
+    pub const fn ambisonic_order(&self) -> Option<u32> {
+        match self {
+            ChannelLayout::FirstOrderAmbisonics => Some(1),
+            ChannelLayout::SecondOrderAmbisonics => Some(2),
+            ChannelLayout::ThirdOrderAmbisonics => Some(3),
+            _ => None,
+        }
+    }
+
+    /// The channel layout used for ports with `num_channels` channels that don't specify one.
+    /// This is only defined for mono and stereo ports.
+    pub(crate) const fn from_channel_count(num_channels: u32) -> Option<Self> {
+        match num_channels {
+            1 => Some(ChannelLayout::Mono),
+            2 => Some(ChannelLayout::Stereo),
+            _ => None,
+        }
+    }
+}
+
 impl PortNames {
     /// [`PortNames::default()`], but as a const function. Used when initializing
     /// `Plugin::AUDIO_IO_LAYOUTS`. (<https://github.com/rust-lang/rust/issues/67792>)
@@ -202,3 +430,48 @@ impl PortNames {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_LAYOUTS: [ChannelLayout; 10] = [
+        ChannelLayout::Mono,
+        ChannelLayout::Stereo,
+        ChannelLayout::Surround50,
+        ChannelLayout::Surround51,
+        ChannelLayout::Surround70,
+        ChannelLayout::Surround71,
+        ChannelLayout::Surround714,
+        ChannelLayout::FirstOrderAmbisonics,
+        ChannelLayout::SecondOrderAmbisonics,
+        ChannelLayout::ThirdOrderAmbisonics,
+    ];
+
+    #[test]
+    fn layout_speaker_round_trip() {
+        for layout in ALL_LAYOUTS {
+            match (layout.speakers(), layout.ambisonic_order()) {
+                (Some(speakers), None) => {
+                    assert_eq!(speakers.len() as u32, layout.num_channels(), "{layout:?}");
+                    for (channel_idx, speaker) in speakers.iter().enumerate() {
+                        assert_eq!(
+                            layout.speaker_channel_idx(*speaker),
+                            Some(channel_idx),
+                            "{layout:?} contains {speaker:?} more than once"
+                        );
+                    }
+                }
+                (None, Some(order)) => {
+                    assert_eq!((order + 1).pow(2), layout.num_channels(), "{layout:?}");
+                    assert_eq!(layout.speaker_channel_idx(Speaker::FrontLeft), None);
+                }
+                _ => panic!("{layout:?} should either have speakers or an ambisonic order"),
+            }
+
+            if let Some(default_layout) = ChannelLayout::from_channel_count(layout.num_channels()) {
+                assert_eq!(default_layout, layout);
+            }
+        }
+    }
+}
@@ -3,11 +3,17 @@
 use std::marker::PhantomData;
 
 mod blocks;
+pub mod ffi;
 mod samples;
 
 pub use blocks::{Block, BlockChannelsIter, BlocksIter};
 pub use samples::{ChannelSamples, ChannelSamplesIter, SamplesIter};
 
+use crate::audio_setup::{ChannelLayout, Speaker};
+use crate::context::process::ProcessContext;
+use crate::midi::NoteEvent;
+use crate::plugin::Plugin;
+
 /// The audio buffers used during processing. This contains the output audio output buffers with the
 /// inputs already copied to the outputs. You can either use the iterator adapters to conveniently
 /// and efficiently iterate over the samples, or you can do your own thing using the raw audio
@@ -29,6 +35,9 @@ pub struct Buffer<'a> {
     /// buffers, and it also cannot be stored in a field next to it because that would mean
     /// containing mutable references to data stored in a mutex.
     output_slices: Vec<&'a mut [f32]>,
+    /// The speaker arrangement for the port this buffer belongs to, if it has one. Set by the
+    /// wrapper.
+    channel_layout: Option<ChannelLayout>,
 }
 
 impl<'a> Buffer<'a> {
@@ -50,6 +59,51 @@ impl<'a> Buffer<'a> {
         self.num_samples == 0
     }
 
+    /// The speaker arrangement of the port this buffer belongs to, as configured in the plugin's
+    /// [`AudioIOLayout`][crate::prelude::AudioIOLayout]. Mono and stereo ports without an explicit
+    /// channel layout use [`ChannelLayout::Mono`] and [`ChannelLayout::Stereo`]. `None` for other
+    /// ports without a channel layout.
+    #[inline]
+    pub fn channel_layout(&self) -> Option<ChannelLayout> {
+        self.channel_layout
+    }
+
+    /// The speaker for every channel in this buffer, in channel order. `None` if the buffer does not
+    /// have a speaker based [`channel_layout()`][Self::channel_layout()].
+    #[inline]
+    pub fn speakers(&self) -> Option<&'static [Speaker]> {
+        self.channel_layout?.speakers()
+    }
+
+    /// The index of the channel for `speaker`, if the buffer's channel layout contains it. Use this
+    /// instead of hardcoding channel indices, since those differ between channel layouts.
+    #[inline]
+    pub fn speaker_channel_idx(&self, speaker: Speaker) -> Option<usize> {
+        self.channel_layout?.speaker_channel_idx(speaker)
+    }
+
+    /// The samples for `speaker`'s channel, if the buffer's channel layout contains it. For
+    /// instance, `buffer.speaker_channel(Speaker::LowFrequency)` returns the LFE channel in a 5.1
+    /// or 7.1 buffer and `None` in a stereo buffer.
+    #[inline]
+    pub fn speaker_channel(&mut self, speaker: Speaker) -> Option<&mut [f32]> {
+        let channel_idx = self.speaker_channel_idx(speaker)?;
+        self.output_slices
+            .get_mut(channel_idx)
+            .map(|channel| &mut **channel)
+    }
+
+    /// Iterate over the channels along with their speakers. This yields nothing if the buffer does
+    /// not have a speaker based [`channel_layout()`][Self::channel_layout()].
+    #[inline]
+    pub fn iter_speaker_channels(&mut self) -> impl Iterator<Item = (Speaker, &mut [f32])> {
+        self.speakers()
+            .unwrap_or_default()
+            .iter()
+            .copied()
+            .zip(self.output_slices.iter_mut().map(|channel| &mut **channel))
+    }
+
     /// Obtain the raw audio buffers.
     #[inline]
     pub fn as_slice(&mut self) -> &mut [&'a mut [f32]] {
@@ -63,6 +117,183 @@ impl<'a> Buffer<'a> {
         &self.output_slices
     }
 
+    /// Copy `samples` to a channel. `samples` should have the same length as the buffer. If it is
+    /// shorter, then only the start of the channel is overwritten, and if it is longer then the
+    /// extra samples are ignored. Both cases trigger a debug assertion.
+    pub fn write_from_slice(&mut self, channel_idx: usize, samples: &[f32]) {
+        nih_debug_assert_eq!(samples.len(), self.num_samples);
+        match self.output_slices.get_mut(channel_idx) {
+            Some(channel) => {
+                let len = channel.len().min(samples.len());
+                channel[..len].copy_from_slice(&samples[..len]);
+            }
+            None => nih_debug_assert_failure!("Channel index {} out of bounds", channel_idx),
+        }
+    }
+
+    /// Copy a channel to `samples`. The same length rules as in
+    /// [`write_from_slice()`][Self::write_from_slice()] apply.
+    pub fn read_to_slice(&self, channel_idx: usize, samples: &mut [f32]) {
+        nih_debug_assert_eq!(samples.len(), self.num_samples);
+        match self.output_slices.get(channel_idx) {
+            Some(channel) => {
+                let len = channel.len().min(samples.len());
+                samples[..len].copy_from_slice(&channel[..len]);
+            }
+            None => nih_debug_assert_failure!("Channel index {} out of bounds", channel_idx),
+        }
+    }
+
+    /// Copy every channel from a slice of channels, like a `Vec<Vec<f32>>` the plugin uses to
+    /// stage audio. There should be as many source channels as there are channels in this buffer.
+    /// Excess channels on either side are ignored, triggering a debug assertion.
+    pub fn write_from_slices<S: AsRef<[f32]>>(&mut self, channels: &[S]) {
+        nih_debug_assert_eq!(channels.len(), self.output_slices.len());
+        for (channel_idx, samples) in channels.iter().take(self.output_slices.len()).enumerate() {
+            self.write_from_slice(channel_idx, samples.as_ref());
+        }
+    }
+
+    /// Copy every channel to a slice of channels. The counterpart to
+    /// [`write_from_slices()`][Self::write_from_slices()].
+    pub fn read_to_slices<S: AsMut<[f32]>>(&self, channels: &mut [S]) {
+        nih_debug_assert_eq!(channels.len(), self.output_slices.len());
+        for (channel_idx, samples) in channels
+            .iter_mut()
+            .take(self.output_slices.len())
+            .enumerate()
+        {
+            self.read_to_slice(channel_idx, samples.as_mut());
+        }
+    }
+
+    /// Swap the contents of two channels, for instance to swap the left and right channels.
+    pub fn swap_channels(&mut self, channel_idx_a: usize, channel_idx_b: usize) {
+        if channel_idx_a >= self.output_slices.len() || channel_idx_b >= self.output_slices.len() {
+            nih_debug_assert_failure!(
+                "Channel indices {} and {} out of bounds",
+                channel_idx_a,
+                channel_idx_b
+            );
+            return;
+        }
+
+        if channel_idx_a != channel_idx_b {
+            let (low, high) = (
+                channel_idx_a.min(channel_idx_b),
+                channel_idx_a.max(channel_idx_b),
+            );
+            let (start, end) = self.output_slices.split_at_mut(high);
+            start[low].swap_with_slice(end[0]);
+        }
+    }
+
+    /// Copy one channel's samples to another channel, for instance to turn a mono signal in the
+    /// first channel into a stereo signal.
+    pub fn duplicate_channel(&mut self, from_channel_idx: usize, to_channel_idx: usize) {
+        if from_channel_idx >= self.output_slices.len()
+            || to_channel_idx >= self.output_slices.len()
+        {
+            nih_debug_assert_failure!(
+                "Channel indices {} and {} out of bounds",
+                from_channel_idx,
+                to_channel_idx
+            );
+            return;
+        }
+
+        if from_channel_idx != to_channel_idx {
+            let (low, high) = (
+                from_channel_idx.min(to_channel_idx),
+                from_channel_idx.max(to_channel_idx),
+            );
+            let (start, end) = self.output_slices.split_at_mut(high);
+            if from_channel_idx < to_channel_idx {
+                end[0].copy_from_slice(start[low]);
+            } else {
+                start[low].copy_from_slice(end[0]);
+            }
+        }
+    }
+
+    /// Convert the left and right channels to mid and side channels in place, with
+    /// `mid = (L + R) / 2` and `side = (L - R) / 2`. A mono signal stays at the same level in the
+    /// mid channel, and [`decode_mid_side()`][Self::decode_mid_side()] restores the original
+    /// signal exactly. The left and right channels are looked up in the buffer's channel layout,
+    /// falling back to the first two channels. Other channels are not touched. Buffers with fewer
+    /// than two channels are left as is, triggering a debug assertion.
+    ///
+    /// ```ignore
+    /// buffer.encode_mid_side();
+    /// for (_mid, side) in buffer.iter_stereo_pairs() {
+    ///     *side *= width;
+    /// }
+    /// buffer.decode_mid_side();
+    /// ```
+    pub fn encode_mid_side(&mut self) {
+        let Some((left, right)) = self.stereo_channels_mut() else {
+            nih_debug_assert_failure!("The buffer does not have left and right channels");
+            return;
+        };
+
+        for (left, right) in left.iter_mut().zip(right.iter_mut()) {
+            let mid = (*left + *right) * 0.5;
+            let side = (*left - *right) * 0.5;
+            *left = mid;
+            *right = side;
+        }
+    }
+
+    /// Convert mid and side channels created by [`encode_mid_side()`][Self::encode_mid_side()]
+    /// back to left and right channels in place, with `L = mid + side` and `R = mid - side`. Like
+    /// with `encode_mid_side()`, buffers with fewer than two channels trigger a debug assertion.
+    pub fn decode_mid_side(&mut self) {
+        let Some((mid, side)) = self.stereo_channels_mut() else {
+            nih_debug_assert_failure!("The buffer does not have left and right channels");
+            return;
+        };
+
+        for (mid, side) in mid.iter_mut().zip(side.iter_mut()) {
+            let left = *mid + *side;
+            let right = *mid - *side;
+            *mid = left;
+            *side = right;
+        }
+    }
+
+    /// Iterate over the left and right channels' samples in pairs. After calling
+    /// [`encode_mid_side()`][Self::encode_mid_side()] these are the mid and side samples. The
+    /// channels are chosen the same way as in [`encode_mid_side()`][Self::encode_mid_side()], and
+    /// the iterator is empty if the buffer has fewer than two channels.
+    pub fn iter_stereo_pairs(&mut self) -> impl Iterator<Item = (&mut f32, &mut f32)> {
+        let (left, right): (&mut [f32], &mut [f32]) =
+            self.stereo_channels_mut().unwrap_or((&mut [], &mut []));
+
+        left.iter_mut().zip(right.iter_mut())
+    }
+
+    /// Process every channel with `f`, which receives the channel's index and its samples. If the
+    /// host provides a thread pool then the channels are processed in parallel using
+    /// [`ProcessContext::execute_parallel()`]. This is useful when channels are processed
+    /// independently and processing them is expensive, like with linear phase EQs. For cheap
+    /// processing the overhead of dispatching the work to other threads outweighs the gains.
+    pub fn for_each_channel_parallel<P: Plugin>(
+        &mut self,
+        context: &impl ProcessContext<P>,
+        f: impl Fn(usize, &mut [f32]) + Sync,
+    ) {
+        let num_channels = self.output_slices.len();
+        let channels = SharedChannels(self.output_slices.as_mut_ptr());
+        context.execute_parallel(num_channels as u32, &|channel_idx| {
+            // SAFETY: Every task index is used exactly once, so every task has exclusive access to
+            //         its channel. `execute_parallel()` blocks until all tasks are done, so the
+            //         channels don't outlive this mutable borrow.
+            f(channel_idx as usize, unsafe {
+                channels.channel(channel_idx as usize)
+            });
+        });
+    }
+
     /// Iterate over the samples, returning a channel iterator for each sample.
     #[inline]
     pub fn iter_samples<'slice>(&'slice mut self) -> SamplesIter<'slice, 'a> {
@@ -108,6 +339,102 @@ impl<'a> Buffer<'a> {
         }
     }
 
+    /// Process the buffer in blocks with the specified maximum size, splitting the blocks at the
+    /// events' timings. `f` is called with the block's offset from the start of the buffer, the
+    /// block, and the events that happen at the start of that block. Since every event starts a new
+    /// block, this makes note starts sample accurate for synths that render entire blocks at once,
+    /// without having to keep track of block boundaries by hand. Events that occur at the same
+    /// sample are passed to the same block.
+    ///
+    /// `events` needs to be sorted by timing, which is the order the host sends them in. The event
+    /// timings are not modified and are thus still relative to the start of the buffer. Events
+    /// with timings past the end of the buffer are passed to the last block.
+    ///
+    /// ```ignore
+    /// // `self.events` is a `Vec` with enough capacity allocated in `initialize()`
+    /// self.events.clear();
+    /// while let Some(event) = context.next_event() {
+    ///     self.events.push(event);
+    /// }
+    ///
+    /// buffer.process_blocks_with_events(&self.events, 64, |block_start, block, events| {
+    ///     for event in events {
+    ///         // Start or stop voices
+    ///     }
+    ///
+    ///     // Render the voices to `block`
+    /// });
+    /// ```
+    pub fn process_blocks_with_events<'slice, S>(
+        &'slice mut self,
+        events: &[NoteEvent<S>],
+        max_block_size: usize,
+        mut f: impl FnMut(usize, Block<'slice, 'a>, &[NoteEvent<S>]),
+    ) {
+        nih_debug_assert!(max_block_size > 0);
+        let max_block_size = max_block_size.max(1);
+
+        let num_samples = self.samples();
+        let buffers: *mut [&'a mut [f32]] = self.output_slices.as_mut_slice();
+        let mut block_start = 0;
+        let mut next_event_idx = 0;
+        while block_start < num_samples {
+            let block_events_start = next_event_idx;
+            while next_event_idx < events.len()
+                && events[next_event_idx].timing() as usize <= block_start
+            {
+                next_event_idx += 1;
+            }
+
+            let mut block_end = (block_start + max_block_size).min(num_samples);
+            if let Some(next_event) = events.get(next_event_idx) {
+                block_end = block_end.min(next_event.timing() as usize);
+            }
+            if block_end == num_samples {
+                next_event_idx = events.len();
+            }
+
+            // SAFETY: The block is in bounds, and the blocks never overlap
+            let block = unsafe { Block::new(buffers, block_start, block_end) };
+            f(
+                block_start,
+                block,
+                &events[block_events_start..next_event_idx],
+            );
+
+            block_start = block_end;
+        }
+    }
+
+    /// The left and right channels, or the first two channels if the channel layout does not
+    /// contain front left and right speakers. Returns `None` if the buffer has fewer than two
+    /// channels.
+    fn stereo_channels_mut(&mut self) -> Option<(&mut [f32], &mut [f32])> {
+        let left_idx = self.speaker_channel_idx(Speaker::FrontLeft).unwrap_or(0);
+        let right_idx = self.speaker_channel_idx(Speaker::FrontRight).unwrap_or(1);
+        if left_idx == right_idx || left_idx.max(right_idx) >= self.output_slices.len() {
+            return None;
+        }
+
+        let (start, end) = self.output_slices.split_at_mut(left_idx.max(right_idx));
+        let low_channel = &mut *start[left_idx.min(right_idx)];
+        let high_channel = &mut *end[0];
+        if left_idx < right_idx {
+            Some((low_channel, high_channel))
+        } else {
+            Some((high_channel, low_channel))
+        }
+    }
+
+    /// Set the speaker arrangement for the port this buffer belongs to. This is ignored if the
+    /// layout's channel count does not match the buffer's. Called by the wrappers after the buffer's
+    /// slices have been allocated.
+    pub(crate) fn set_channel_layout(&mut self, channel_layout: Option<ChannelLayout>) {
+        self.channel_layout = channel_layout.filter(|channel_layout| {
+            channel_layout.num_channels() as usize == self.output_slices.len()
+        });
+    }
+
     /// Set the slices in the raw output slice vector. This vector needs to be resized to match the
     /// number of output channels during the plugin's initialization. Then during audio processing,
     /// these slices should be updated to point to the plugin's audio buffers. The `num_samples`
@@ -133,6 +460,22 @@ impl<'a> Buffer<'a> {
     }
 }
 
+/// A pointer to a buffer's channel slices that can be shared between the tasks in
+/// [`Buffer::for_each_channel_parallel()`].
+struct SharedChannels<'a>(*mut &'a mut [f32]);
+
+// SAFETY: Every task only accesses a single distinct channel through this pointer
+unsafe impl Sync for SharedChannels<'_> {}
+
+impl SharedChannels<'_> {
+    /// Get a channel's samples. The caller needs to make sure there are no other references to
+    /// that channel.
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn channel(&self, channel_idx: usize) -> &mut [f32] {
+        &mut **self.0.add(channel_idx)
+    }
+}
+
 #[cfg(any(miri, test))]
 mod miri {
     use super::*;
@@ -165,6 +508,41 @@ mod miri {
         assert_eq!(real_buffers[0][0], 0.003);
     }
 
+    #[test]
+    fn mid_side_round_trip() {
+        let mut real_buffers = vec![vec![1.0, 0.5, -0.25], vec![1.0, -0.5, 0.75]];
+        let mut buffer = Buffer::default();
+        unsafe {
+            buffer.set_slices(3, |output_slices| {
+                let (first_channel, other_channels) = real_buffers.split_at_mut(1);
+                *output_slices = vec![&mut first_channel[0], &mut other_channels[0]];
+            })
+        };
+
+        buffer.encode_mid_side();
+        let pairs: Vec<(f32, f32)> = buffer
+            .iter_stereo_pairs()
+            .map(|(mid, side)| (*mid, *side))
+            .collect();
+        assert_eq!(pairs, [(1.0, 0.0), (0.0, 0.5), (0.25, -0.5)]);
+
+        buffer.decode_mid_side();
+        assert_eq!(real_buffers, [[1.0, 0.5, -0.25], [1.0, -0.5, 0.75]]);
+    }
+
+    #[test]
+    fn mono_stereo_pairs() {
+        let mut real_buffers = vec![vec![1.0, 0.5, -0.25]];
+        let mut buffer = Buffer::default();
+        unsafe {
+            buffer.set_slices(3, |output_slices| {
+                *output_slices = vec![&mut real_buffers[0]];
+            })
+        };
+
+        assert_eq!(buffer.iter_stereo_pairs().count(), 0);
+    }
+
     #[test]
     fn repeated_slices() {
         let mut real_buffers = vec![vec![0.0; 512]; 2];
@@ -198,4 +576,91 @@ mod miri {
             assert_eq!(real_buffers[0][i], 0.0);
         }
     }
+
+    #[test]
+    fn blocks_with_events() {
+        let mut real_buffers = vec![vec![0.0; 100]; 1];
+        let mut buffer = Buffer::default();
+        unsafe {
+            buffer.set_slices(100, |output_slices| {
+                *output_slices = real_buffers.iter_mut().map(|b| b.as_mut_slice()).collect();
+            })
+        };
+
+        let note_on = |timing| NoteEvent::<()>::NoteOn {
+            timing,
+            voice_id: None,
+            channel: 0,
+            note: 60,
+            velocity: 1.0,
+        };
+        let events = [
+            note_on(0),
+            note_on(10),
+            note_on(10),
+            note_on(70),
+            note_on(200),
+        ];
+
+        let mut blocks = Vec::new();
+        buffer.process_blocks_with_events(&events, 32, |block_start, mut block, block_events| {
+            for sample in block.get_mut(0).unwrap() {
+                *sample += 1.0;
+            }
+
+            blocks.push((block_start, block.samples(), block_events.len()));
+        });
+
+        assert_eq!(blocks, [(0, 10, 1), (10, 32, 2), (42, 28, 0), (70, 30, 2)]);
+        assert!(real_buffers[0].iter().all(|sample| *sample == 1.0));
+    }
+
+    #[test]
+    fn speaker_channels() {
+        let mut real_buffers = vec![vec![0.0; 16]; 6];
+        let mut buffer = Buffer::default();
+        unsafe {
+            buffer.set_slices(16, |output_slices| {
+                *output_slices = real_buffers.iter_mut().map(|s| s.as_mut_slice()).collect();
+            })
+        };
+
+        // The layout is ignored if the channel count doesn't match
+        buffer.set_channel_layout(Some(ChannelLayout::Stereo));
+        assert_eq!(buffer.channel_layout(), None);
+        assert!(buffer.speaker_channel(Speaker::FrontLeft).is_none());
+
+        buffer.set_channel_layout(Some(ChannelLayout::Surround51));
+        assert_eq!(buffer.speaker_channel_idx(Speaker::LowFrequency), Some(3));
+        assert!(buffer.speaker_channel(Speaker::SideLeft).is_none());
+        buffer
+            .speaker_channel(Speaker::LowFrequency)
+            .unwrap()
+            .fill(1.0);
+        for (speaker, channel) in buffer.iter_speaker_channels() {
+            assert_eq!(channel[0] == 1.0, speaker == Speaker::LowFrequency);
+        }
+    }
+
+    #[test]
+    fn copy_helpers() {
+        let mut real_buffers = vec![vec![0.0; 4]; 2];
+        let mut buffer = Buffer::default();
+        unsafe {
+            buffer.set_slices(4, |output_slices| {
+                *output_slices = real_buffers.iter_mut().map(|s| s.as_mut_slice()).collect();
+            })
+        };
+
+        buffer.write_from_slices(&[vec![1.0, 2.0, 3.0, 4.0], vec![5.0; 4]]);
+        buffer.swap_channels(1, 0);
+        let mut staged = vec![vec![0.0; 4]; 2];
+        buffer.read_to_slices(&mut staged);
+        assert_eq!(staged, [vec![5.0; 4], vec![1.0, 2.0, 3.0, 4.0]]);
+
+        buffer.duplicate_channel(1, 0);
+        let mut channel = [0.0; 4];
+        buffer.read_to_slice(0, &mut channel);
+        assert_eq!(channel, [1.0, 2.0, 3.0, 4.0]);
+    }
 }
@@ -125,6 +125,23 @@ impl ExactSizeIterator for BlocksIter<'_, '_> {}
 impl ExactSizeIterator for BlockChannelsIter<'_, '_> {}
 
 impl<'slice, 'sample> Block<'slice, 'sample> {
+    /// Create a block for the samples in `block_start..block_end`. The caller needs to make sure
+    /// that range is in bounds and that no other blocks for the same samples exist at the same
+    /// time.
+    #[inline]
+    pub(super) unsafe fn new(
+        buffers: *mut [&'sample mut [f32]],
+        block_start: usize,
+        block_end: usize,
+    ) -> Self {
+        Block {
+            buffers,
+            current_block_start: block_start,
+            current_block_end: block_end,
+            _marker: PhantomData,
+        }
+    }
+
     /// Get the number of samples per channel in the block.
     #[inline]
     pub fn samples(&self) -> usize {
@@ -0,0 +1,250 @@
+//! Passing audio buffers to C and C++ DSP code.
+//!
+//! Most C DSP libraries take audio as an array of channel pointers together with the number of
+//! channels and samples, like `float **channels`. [`ChannelPointerArray`] converts a [`Buffer`],
+//! or any other set of channel slices, to that representation without allocating. The returned
+//! [`ChannelPointers`] and [`ChannelPointersMut`] borrow both the buffer and the pointer array, so
+//! the pointers cannot outlive the audio data they point to.
+//!
+//! ```
+//! # use nih_plug::prelude::*;
+//! # use nih_plug::buffer::ffi::ChannelPointerArray;
+//! # unsafe extern "C" fn process_kernel(_: *mut *mut f32, _: u32, _: u32) {}
+//! # fn process(channel_pointers: &mut ChannelPointerArray, buffer: &mut Buffer) {
+//! // `channel_pointers` is created in `initialize()` with room for the plugin's channels
+//! let mut pointers = channel_pointers.borrow_buffer_mut(buffer);
+//! unsafe {
+//!     process_kernel(
+//!         pointers.as_mut_ptr(),
+//!         pointers.num_channels() as u32,
+//!         pointers.num_samples() as u32,
+//!     )
+//! };
+//! # }
+//! ```
+
+use std::marker::PhantomData;
+
+use super::Buffer;
+
+/// Preallocated storage for an array of channel pointers. Create this in your plugin's
+/// [`initialize()`][crate::prelude::Plugin::initialize()] function with enough capacity for the
+/// largest buffer you will pass to C code, and then use it to borrow buffers as raw channel pointer
+/// arrays in the process function. This does not allocate as long as the number of channels does
+/// not exceed the capacity.
+#[derive(Debug, Default)]
+pub struct ChannelPointerArray {
+    /// Scratch storage for the channel pointers. These are only valid while a [`ChannelPointers`]
+    /// or [`ChannelPointersMut`] borrowing this object is alive.
+    pointers: Vec<*mut f32>,
+}
+
+// SAFETY: The raw pointers are only used while the buffer they point to is borrowed through
+//         `ChannelPointers` or `ChannelPointersMut`, which are neither `Send` nor `Sync`
+unsafe impl Send for ChannelPointerArray {}
+unsafe impl Sync for ChannelPointerArray {}
+
+/// A buffer's channels as an array of immutable channel pointers, borrowed from a
+/// [`ChannelPointerArray`]. The pointers stay valid for as long as this object exists.
+#[derive(Debug)]
+pub struct ChannelPointers<'a> {
+    pointers: &'a [*mut f32],
+    num_samples: usize,
+    _audio: PhantomData<&'a [f32]>,
+}
+
+/// A buffer's channels as an array of mutable channel pointers, borrowed from a
+/// [`ChannelPointerArray`]. The pointers stay valid for as long as this object exists, and the
+/// buffer cannot be accessed through Rust in the meantime.
+#[derive(Debug)]
+pub struct ChannelPointersMut<'a> {
+    pointers: &'a mut [*mut f32],
+    num_samples: usize,
+    _audio: PhantomData<&'a mut [f32]>,
+}
+
+impl ChannelPointerArray {
+    /// Preallocate room for the pointers of up to `num_channels` channels.
+    pub fn with_capacity(num_channels: usize) -> Self {
+        Self {
+            pointers: Vec::with_capacity(num_channels),
+        }
+    }
+
+    /// Borrow a buffer's channels as mutable channel pointers.
+    pub fn borrow_buffer_mut<'a>(&'a mut self, buffer: &'a mut Buffer) -> ChannelPointersMut<'a> {
+        let num_samples = buffer.samples();
+        self.borrow_slices_mut(buffer.as_slice(), num_samples)
+    }
+
+    /// Borrow a buffer's channels as immutable channel pointers.
+    pub fn borrow_buffer<'a>(&'a mut self, buffer: &'a Buffer) -> ChannelPointers<'a> {
+        let num_samples = buffer.samples();
+        self.borrow_slices(buffer.as_slice_immutable(), num_samples)
+    }
+
+    /// Borrow a set of channel slices as mutable channel pointers. `num_samples` is stored for
+    /// convenience, and every channel must contain at least that many samples.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the channels contains fewer than `num_samples` samples.
+    pub fn borrow_slices_mut<'a>(
+        &'a mut self,
+        channels: &'a mut [&mut [f32]],
+        num_samples: usize,
+    ) -> ChannelPointersMut<'a> {
+        let num_channels = channels.len();
+        self.fill(
+            channels.iter_mut().map(|channel| {
+                assert!(channel.len() >= num_samples);
+                channel.as_mut_ptr()
+            }),
+            num_channels,
+        );
+
+        ChannelPointersMut {
+            pointers: &mut self.pointers,
+            num_samples,
+            _audio: PhantomData,
+        }
+    }
+
+    /// Borrow a set of channel slices as immutable channel pointers. `num_samples` is stored for
+    /// convenience, and every channel must contain at least that many samples.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the channels contains fewer than `num_samples` samples.
+    pub fn borrow_slices<'a, C: AsRef<[f32]>>(
+        &'a mut self,
+        channels: &'a [C],
+        num_samples: usize,
+    ) -> ChannelPointers<'a> {
+        let num_channels = channels.len();
+        self.fill(
+            channels.iter().map(|channel| {
+                let channel = channel.as_ref();
+                assert!(channel.len() >= num_samples);
+
+                // These are never written through, `ChannelPointers` only hands out const pointers
+                channel.as_ptr() as *mut f32
+            }),
+            num_channels,
+        );
+
+        ChannelPointers {
+            pointers: &self.pointers,
+            num_samples,
+            _audio: PhantomData,
+        }
+    }
+
+    fn fill(&mut self, pointers: impl Iterator<Item = *mut f32>, num_channels: usize) {
+        nih_debug_assert!(
+            num_channels <= self.pointers.capacity(),
+            "The channel pointer array needs to be reallocated, consider increasing its capacity"
+        );
+
+        self.pointers.clear();
+        self.pointers.extend(pointers);
+    }
+}
+
+impl ChannelPointers<'_> {
+    /// The array of channel pointers, for C functions that take a `const float* const*`.
+    pub fn as_ptr(&self) -> *const *const f32 {
+        self.pointers.as_ptr() as *const *const f32
+    }
+
+    /// The number of channels in the array.
+    pub fn num_channels(&self) -> usize {
+        self.pointers.len()
+    }
+
+    /// The number of samples in each channel.
+    pub fn num_samples(&self) -> usize {
+        self.num_samples
+    }
+}
+
+impl ChannelPointersMut<'_> {
+    /// The array of channel pointers, for C functions that take a `float**`.
+    pub fn as_mut_ptr(&mut self) -> *mut *mut f32 {
+        self.pointers.as_mut_ptr()
+    }
+
+    /// The array of channel pointers as immutable pointers, for C functions that take a
+    /// `const float* const*`.
+    pub fn as_ptr(&self) -> *const *const f32 {
+        self.pointers.as_ptr() as *const *const f32
+    }
+
+    /// The number of channels in the array.
+    pub fn num_channels(&self) -> usize {
+        self.pointers.len()
+    }
+
+    /// The number of samples in each channel.
+    pub fn num_samples(&self) -> usize {
+        self.num_samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stands in for a C DSP kernel that copies its input to its output with a gain.
+    unsafe extern "C" fn gain_kernel(
+        inputs: *const *const f32,
+        outputs: *mut *mut f32,
+        num_channels: u32,
+        num_samples: u32,
+        gain: f32,
+    ) {
+        for channel_idx in 0..num_channels as usize {
+            let input = std::slice::from_raw_parts(*inputs.add(channel_idx), num_samples as usize);
+            let output =
+                std::slice::from_raw_parts_mut(*outputs.add(channel_idx), num_samples as usize);
+            for (output_sample, input_sample) in output.iter_mut().zip(input) {
+                *output_sample = input_sample * gain;
+            }
+        }
+    }
+
+    #[test]
+    fn buffer_to_channel_pointers() {
+        let mut left = vec![0.0f32; 4];
+        let mut right = vec![0.0f32; 4];
+        let mut buffer = Buffer::default();
+        unsafe {
+            buffer.set_slices(4, |slices| {
+                *slices = vec![
+                    &mut *(left.as_mut_slice() as *mut [f32]),
+                    &mut *(right.as_mut_slice() as *mut [f32]),
+                ];
+            })
+        };
+
+        let sidechain = [vec![1.0f32, 2.0, 3.0, 4.0], vec![-1.0f32; 4]];
+        let mut input_pointers = ChannelPointerArray::with_capacity(2);
+        let mut output_pointers = ChannelPointerArray::with_capacity(2);
+        let inputs = input_pointers.borrow_slices(&sidechain, 4);
+        let mut outputs = output_pointers.borrow_buffer_mut(&mut buffer);
+        assert_eq!(inputs.num_channels(), 2);
+        assert_eq!(outputs.num_samples(), 4);
+        unsafe {
+            gain_kernel(
+                inputs.as_ptr(),
+                outputs.as_mut_ptr(),
+                outputs.num_channels() as u32,
+                outputs.num_samples() as u32,
+                0.5,
+            )
+        };
+
+        assert_eq!(buffer.as_slice()[0], [0.5, 1.0, 1.5, 2.0]);
+        assert_eq!(buffer.as_slice()[1], [-0.5; 4]);
+    }
+}
@@ -18,6 +18,41 @@ pub enum PluginApi {
     Vst3,
 }
 
+/// Information about the host the plugin is running in. This is available during
+/// [`Plugin::initialize()`][crate::prelude::Plugin::initialize()] through
+/// [`InitContext::host_info()`][init::InitContext::host_info()], so plugins can enable host-specific
+/// workarounds before they start processing audio.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostInfo {
+    /// The host's name, if the host reported one. This is always `None` for the standalone
+    /// target.
+    pub name: Option<String>,
+    /// The host's vendor. Only CLAP hosts report this.
+    pub vendor: Option<String>,
+    /// The host's version string. Only CLAP hosts report this.
+    pub version: Option<String>,
+    /// Whether the host responds to latency changes reported through
+    /// [`InitContext::set_latency_samples()`][init::InitContext::set_latency_samples()] and
+    /// [`ProcessContext::set_latency_samples()`][process::ProcessContext::set_latency_samples()].
+    pub supports_latency_changes: bool,
+    /// Whether [`ProcessContext::execute_parallel()`][process::ProcessContext::execute_parallel()]
+    /// runs tasks on the host's thread pool. If this is `false`, then the tasks are run one after
+    /// another on the audio thread.
+    pub supports_parallel_execution: bool,
+}
+
+impl HostInfo {
+    /// Whether the host's name contains `name`, ignoring case. Returns `false` if the host did not
+    /// report a name. Useful for host-specific workarounds, e.g. `host_info.name_contains("FL
+    /// Studio")`.
+    pub fn name_contains(&self, name: &str) -> bool {
+        match &self.name {
+            Some(host_name) => host_name.to_lowercase().contains(&name.to_lowercase()),
+            None => false,
+        }
+    }
+}
+
 impl Display for PluginApi {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -1,9 +1,14 @@
 //! A context passed to a plugin's editor.
 
+use std::fmt::Display;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use super::PluginApi;
-use crate::prelude::{Param, ParamPtr, Plugin, PluginState};
+use crate::midi::learn::MidiLearn;
+use crate::prelude::{
+    BufferConfig, FactoryPreset, Param, ParamPtr, Plugin, PluginState, ProcessStatus, Transport,
+};
 
 /// Callbacks the plugin can make when the user interacts with its GUI such as updating parameter
 /// values. This is passed to the plugin during [`Editor::spawn()`][crate::prelude::Editor::spawn()]. All of
@@ -54,6 +59,35 @@ pub trait GuiContext: Send + Sync + 'static {
     /// mostly marked as unsafe for API reasons.
     unsafe fn raw_end_set_parameter(&self, param: ParamPtr);
 
+    /// Inform the host that the following parameter gestures belong together, for instance because
+    /// they're all changed by a single macro knob. Hosts that support this will combine them into a
+    /// single undo step. Must be followed by a call to [`end_group_edit()`][Self::end_group_edit()].
+    /// Use [`ParamSetter::begin_set_parameters()`] for a more convenient API.
+    ///
+    /// This is only supported by VST3 hosts. The other wrappers don't do anything here.
+    fn begin_group_edit(&self) {}
+
+    /// End a group of parameter gestures started with
+    /// [`begin_group_edit()`][Self::begin_group_edit()].
+    fn end_group_edit(&self) {}
+
+    /// Start an edit to the plugin's non-parameter state, like loading a sample or editing a
+    /// wavetable, so it can participate in the host's undo history. `name` describes the edit and
+    /// may be shown in the host's undo history. Any parameter gestures made until the matching
+    /// [`end_undo_block()`][Self::end_undo_block()] call become part of the same edit.
+    ///
+    /// Neither plugin API has a dedicated undo interface, so this uses the closest available
+    /// mechanisms. VST3 hosts will group the edit using `IComponentHandler2`'s group edits and the
+    /// project will be marked as modified afterwards. CLAP hosts will be told that the plugin's
+    /// state has changed through the `clap.state` extension, which some hosts use to create an
+    /// undo point. The name is currently unused by both wrappers.
+    fn start_undo_block(&self, name: &str) {
+        let _ = name;
+    }
+
+    /// End the edit started with [`start_undo_block()`][Self::start_undo_block()].
+    fn end_undo_block(&self) {}
+
     /// Serialize the plugin's current state to a serde-serializable object. Useful for implementing
     /// preset handling within a plugin's GUI.
     fn get_state(&self) -> PluginState;
@@ -63,6 +97,211 @@ pub trait GuiContext: Send + Sync + 'static {
     /// host. If the plugin is currently processing audio, then the parameter values will be
     /// restored at the end of the current processing cycle.
     fn set_state(&self, state: PluginState);
+
+    /// The plugin's [`FACTORY_PRESETS`][crate::prelude::Plugin::FACTORY_PRESETS]. This can be used
+    /// to build a preset browser without needing to know the plugin's type.
+    fn factory_presets(&self) -> &'static [FactoryPreset];
+
+    /// Load the factory preset at `index` in [`factory_presets()`][Self::factory_presets()] using
+    /// [`set_state()`][Self::set_state()]. Returns `false` if the index is out of bounds or if the
+    /// preset could not be deserialized.
+    fn load_factory_preset(&self, index: usize) -> bool {
+        match self
+            .factory_presets()
+            .get(index)
+            .and_then(|preset| preset.state())
+        {
+            Some(state) => {
+                self.set_state(state);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get the currently active A/B comparison slot. See
+    /// [`switch_state_slot()`][Self::switch_state_slot()].
+    fn active_state_slot(&self) -> StateSlot;
+
+    /// Switch between two plugin states for A/B comparisons. The current state is stored in the
+    /// active slot, and the state previously stored in `slot` will be restored using the same
+    /// mechanism as [`set_state()`][Self::set_state()], so it's only applied in between two
+    /// processing cycles. If `slot` has not been used before, then it will start out as a copy of
+    /// the current state. Does nothing if `slot` is already active.
+    fn switch_state_slot(&self, slot: StateSlot);
+
+    /// Overwrite the inactive A/B comparison slot with a copy of the current state. This is the
+    /// usual 'A -> B' or 'B -> A' button.
+    fn copy_state_to_other_slot(&self);
+
+    /// The plugin's MIDI learn mappings. This can be used to add MIDI learn to the plugin's
+    /// parameters, see [`MidiLearn`] for more information.
+    fn midi_learn(&self) -> &MidiLearn;
+
+    /// The plugin's current buffer configuration, or `None` if the plugin has not been initialized
+    /// yet. This can be used to convert [`latency_samples()`][Self::latency_samples()] and
+    /// [`tail()`][Self::tail()] to time units.
+    fn buffer_config(&self) -> Option<BufferConfig>;
+
+    /// The latency in samples the plugin currently reports to the host, as set through
+    /// [`InitContext::set_latency_samples()`][crate::prelude::InitContext::set_latency_samples()]
+    /// or [`ProcessContext::set_latency_samples()`][crate::prelude::ProcessContext::set_latency_samples()],
+    /// plus the block size if [`Plugin::FIXED_BLOCK_SIZE`][crate::prelude::Plugin::FIXED_BLOCK_SIZE]
+    /// is set.
+    fn latency_samples(&self) -> u32;
+
+    /// The tail length the plugin currently reports to the host. This is based on the
+    /// [`ProcessStatus`][crate::prelude::ProcessStatus] returned from the last process call.
+    fn tail(&self) -> TailLength;
+
+    /// The transport information from the plugin's most recent process call, or `None` if the
+    /// plugin has not processed any audio yet. Poll this once per frame to animate GUI elements in
+    /// time with the host, like a tempo synced LFO display. See
+    /// [`TransportSnapshot::pos_beats_now()`] for getting a smooth position in between process
+    /// calls.
+    fn transport(&self) -> Option<TransportSnapshot> {
+        None
+    }
+
+    /// Statistics about how long the plugin's recent process calls took, for instance to display a
+    /// DSP load meter. This returns `None` unless
+    /// [`Plugin::COLLECT_PROCESS_TIMINGS`][crate::prelude::Plugin::COLLECT_PROCESS_TIMINGS] is
+    /// enabled and the plugin has processed audio since it was last initialized. This computes the
+    /// statistics from scratch, so it's best to only call this a couple times per second.
+    fn process_timings(&self) -> Option<ProcessTimings> {
+        None
+    }
+}
+
+/// A copy of the transport information from a process call, returned by
+/// [`GuiContext::transport()`]. See [`Transport`] for more information on these fields.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransportSnapshot {
+    /// Whether the transport was running.
+    pub playing: bool,
+    /// The project's tempo in beats per minute.
+    pub tempo: Option<f64>,
+    /// The time signature's numerator.
+    pub time_sig_numerator: Option<i32>,
+    /// The time signature's denominator.
+    pub time_sig_denominator: Option<i32>,
+    /// The position in the song in quarter notes at the start of the process call.
+    pub pos_beats: Option<f64>,
+    /// The position in the song in seconds at the start of the process call.
+    pub pos_seconds: Option<f64>,
+    /// The start of the bar containing `pos_beats`, in quarter notes.
+    pub bar_start_pos_beats: Option<f64>,
+    /// When this snapshot was taken.
+    pub captured_at: Instant,
+}
+
+impl TransportSnapshot {
+    /// Take a snapshot of the transport information at the start of a process call.
+    pub(crate) fn new(transport: &Transport) -> Self {
+        Self {
+            playing: transport.playing,
+            tempo: transport.tempo,
+            time_sig_numerator: transport.time_sig_numerator,
+            time_sig_denominator: transport.time_sig_denominator,
+            pos_beats: transport.pos_beats(),
+            pos_seconds: transport.pos_seconds(),
+            bar_start_pos_beats: transport.bar_start_pos_beats(),
+            captured_at: Instant::now(),
+        }
+    }
+
+    /// The estimated position in quarter notes right now, extrapolated from the position and
+    /// tempo at the time the snapshot was taken. The GUI typically runs at a different rate than
+    /// the audio thread, so this results in smoother animations than using
+    /// [`pos_beats`][Self::pos_beats] directly. This does not know about loops, so the position
+    /// may briefly overshoot the end of a loop. Returns the snapshot's position as is when the
+    /// transport is not playing.
+    pub fn pos_beats_now(&self) -> Option<f64> {
+        let pos_beats = self.pos_beats?;
+        match (self.playing, self.tempo) {
+            (true, Some(tempo)) => {
+                Some(pos_beats + (self.captured_at.elapsed().as_secs_f64() * tempo / 60.0))
+            }
+            _ => Some(pos_beats),
+        }
+    }
+}
+
+/// The plugin's tail length as reported to the host, returned by [`GuiContext::tail()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TailLength {
+    /// The plugin has no tail, or it has not processed any audio yet.
+    #[default]
+    None,
+    /// The plugin's output will decay to silence within this many samples after its input
+    /// becomes silent.
+    Samples(u32),
+    /// The plugin has an infinite tail because it returned
+    /// [`ProcessStatus::KeepAlive`][crate::prelude::ProcessStatus::KeepAlive].
+    Infinite,
+}
+
+impl TailLength {
+    /// The tail length as reported to the host for a process status.
+    pub(crate) fn from_process_status(status: ProcessStatus) -> Self {
+        match status {
+            ProcessStatus::Tail(samples) => TailLength::Samples(samples),
+            ProcessStatus::KeepAlive => TailLength::Infinite,
+            _ => TailLength::None,
+        }
+    }
+}
+
+/// Statistics about the durations of the plugin's most recent process calls, returned by
+/// [`GuiContext::process_timings()`]. Keep in mind that hosts may split up their buffers, so a
+/// process call does not necessarily correspond to one of the host's audio buffers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProcessTimings {
+    /// The number of process calls these statistics were computed from.
+    pub num_blocks: usize,
+    /// The average duration of a process call.
+    pub mean: Duration,
+    /// The median duration of a process call.
+    pub median: Duration,
+    /// The 95th percentile of the process call durations.
+    pub p95: Duration,
+    /// The 99th percentile of the process call durations.
+    pub p99: Duration,
+    /// The longest process call.
+    pub max: Duration,
+    /// The time spent processing divided by the duration of the processed audio. When this
+    /// approaches 1.0, the plugin can no longer process audio in real time.
+    pub load: f32,
+    /// The highest load of a single process call. Spikes here will cause dropouts even when the
+    /// average load is low.
+    pub peak_load: f32,
+}
+
+/// One of the two slots used for comparing plugin states. See [`GuiContext::switch_state_slot()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StateSlot {
+    #[default]
+    A,
+    B,
+}
+
+impl StateSlot {
+    /// Get the other slot.
+    pub fn other(self) -> Self {
+        match self {
+            StateSlot::A => StateSlot::B,
+            StateSlot::B => StateSlot::A,
+        }
+    }
+}
+
+impl Display for StateSlot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StateSlot::A => write!(f, "A"),
+            StateSlot::B => write!(f, "B"),
+        }
+    }
 }
 
 /// An way to run background tasks from the plugin's GUI, equivalent to the
@@ -83,6 +322,8 @@ pub trait GuiContext: Send + Sync + 'static {
 pub struct AsyncExecutor<P: Plugin> {
     pub(crate) execute_background: Arc<dyn Fn(P::BackgroundTask) + Send + Sync>,
     pub(crate) execute_gui: Arc<dyn Fn(P::BackgroundTask) + Send + Sync>,
+    pub(crate) send_to_dsp: Arc<dyn Fn(P::GuiMessage) -> bool + Send + Sync>,
+    pub(crate) next_editor_event: Arc<dyn Fn() -> Option<P::EditorEvent> + Send + Sync>,
 }
 
 // Can't derive this since Rust then requires `P` to also be `Clone`able
@@ -91,6 +332,8 @@ impl<P: Plugin> Clone for AsyncExecutor<P> {
         Self {
             execute_background: self.execute_background.clone(),
             execute_gui: self.execute_gui.clone(),
+            send_to_dsp: self.send_to_dsp.clone(),
+            next_editor_event: self.next_editor_event.clone(),
         }
     }
 }
@@ -126,6 +369,24 @@ impl<P: Plugin> AsyncExecutor<P> {
     pub fn execute_gui(&self, task: P::BackgroundTask) {
         (self.execute_gui)(task);
     }
+
+    /// Send a message to the plugin's audio thread. The plugin can receive these messages in its
+    /// process function using
+    /// [`ProcessContext::next_gui_message()`][crate::prelude::ProcessContext::next_gui_message()].
+    /// Messages are stored in a bounded lock-free queue, so this never blocks. Returns `false` and
+    /// drops the message if the queue is full, which can happen when the plugin is not currently
+    /// processing audio.
+    pub fn send_to_dsp(&self, message: P::GuiMessage) -> bool {
+        (self.send_to_dsp)(message)
+    }
+
+    /// Return the next event sent by the plugin's audio thread through
+    /// [`ProcessContext::send_to_editor()`][crate::prelude::ProcessContext::send_to_editor()], if
+    /// there is one. Events are returned in the order they were sent. Editors should drain all
+    /// pending events once per frame, since the queue drops the oldest events when it is full.
+    pub fn next_editor_event(&self) -> Option<P::EditorEvent> {
+        (self.next_editor_event)()
+    }
 }
 
 impl<'a> ParamSetter<'a> {
@@ -180,4 +441,26 @@ impl<'a> ParamSetter<'a> {
     pub fn end_set_parameter<P: Param>(&self, param: &P) {
         unsafe { self.raw_context.raw_end_set_parameter(param.as_ptr()) };
     }
+
+    /// Start automation gestures for multiple parameters at once, for instance when implementing a
+    /// macro knob or preset morphing. The host will treat the changes as a single edit when
+    /// possible, so it creates one undo step instead of one for every parameter. Obtain the
+    /// pointers using [`Param::as_ptr()`]. Every parameter can then be changed using
+    /// [`set_parameter()`][Self::set_parameter()], and the gestures need to be ended using
+    /// [`end_set_parameters()`][Self::end_set_parameters()] with the same parameters.
+    pub fn begin_set_parameters(&self, params: &[ParamPtr]) {
+        self.raw_context.begin_group_edit();
+        for param in params {
+            unsafe { self.raw_context.raw_begin_set_parameter(*param) };
+        }
+    }
+
+    /// End the automation gestures started with
+    /// [`begin_set_parameters()`][Self::begin_set_parameters()].
+    pub fn end_set_parameters(&self, params: &[ParamPtr]) {
+        for param in params {
+            unsafe { self.raw_context.raw_end_set_parameter(*param) };
+        }
+        self.raw_context.end_group_edit();
+    }
 }
@@ -1,6 +1,6 @@
 //! A context passed during plugin initialization.
 
-use super::PluginApi;
+use super::{HostInfo, PluginApi};
 use crate::prelude::Plugin;
 
 /// Callbacks the plugin can make while it is being initialized. This is passed to the plugin during
@@ -14,6 +14,10 @@ pub trait InitContext<P: Plugin> {
     /// Get the current plugin API.
     fn plugin_api(&self) -> PluginApi;
 
+    /// Get information about the host, like its name and version. Plugins can use this to enable
+    /// workarounds for specific hosts before they start processing audio.
+    fn host_info(&self) -> HostInfo;
+
     /// Run a task directly on this thread. This ensures that the task has finished executing before
     /// the plugin finishes initializing.
     ///
@@ -1,7 +1,7 @@
 //! A context passed during the process function.
 
 use super::PluginApi;
-use crate::prelude::{Plugin, PluginNoteEvent};
+use crate::prelude::{Param, ParamPtr, Plugin, PluginNoteEvent};
 
 /// Contains both context data and callbacks the plugin can use during processing. Most notably this
 /// is how a plugin sends and receives note events, gets transport information, and accesses
@@ -37,7 +37,11 @@ pub trait ProcessContext<P: Plugin> {
     /// your task executor.
     fn execute_gui(&self, task: P::BackgroundTask);
 
-    /// Get information about the current transport position and status.
+    /// Get information about the current transport position and status. This describes the state
+    /// at the start of the buffer passed to the process function. When a CLAP host changes the
+    /// tempo, time signature, or position in the middle of a buffer, the wrapper splits the buffer
+    /// at that point so the next process call receives the updated transport information. This
+    /// happens regardless of [`Plugin::SAMPLE_ACCURATE_AUTOMATION`][crate::prelude::Plugin::SAMPLE_ACCURATE_AUTOMATION].
     fn transport(&self) -> &Transport;
 
     /// Returns the next note event, if there is one. Use
@@ -80,6 +84,28 @@ pub trait ProcessContext<P: Plugin> {
     /// otherwise.
     fn send_event(&mut self, event: PluginNoteEvent<P>);
 
+    /// Return the next message sent by the plugin's editor through
+    /// [`AsyncExecutor::send_to_dsp()`][crate::prelude::AsyncExecutor::send_to_dsp()], if there is
+    /// one. Messages are returned in the order they were sent. This is realtime-safe, so it can be
+    /// called in a loop at the start of every `process()` call:
+    ///
+    /// ```ignore
+    /// while let Some(message) = context.next_gui_message() {
+    ///     match message {
+    ///         GuiMessage::ClearBuffer => self.buffer.fill(0.0),
+    ///     }
+    /// }
+    /// ```
+    fn next_gui_message(&mut self) -> Option<P::GuiMessage>;
+
+    /// Send an event to the plugin's editor, for instance to update a visualization. The editor
+    /// can receive these events using
+    /// [`AsyncExecutor::next_editor_event()`][crate::prelude::AsyncExecutor::next_editor_event()].
+    /// Events are stored in a bounded wait-free queue. If the editor has not drained the queue,
+    /// for instance because it is closed, then the oldest event is dropped to make room for the
+    /// new one.
+    fn send_to_editor(&self, event: P::EditorEvent);
+
     /// Update the current latency of the plugin. If the plugin is currently processing audio, then
     /// this may cause audio playback to be restarted.
     fn set_latency_samples(&self, samples: u32);
@@ -92,15 +118,89 @@ pub trait ProcessContext<P: Plugin> {
     /// monophonic modulation when dropping the capacity down to 1.
     fn set_current_voice_capacity(&self, capacity: u32);
 
-    // TODO: Add this, this works similar to [GuiContext::set_parameter] but it adds the parameter
-    //       change to a queue (or directly to the VST3 plugin's parameter output queues) instead of
-    //       using main thread host automation (and all the locks involved there).
-    // fn set_parameter<P: Param>(&self, param: &P, value: P::Plain);
+    /// Report the plugin's current gain reduction in decibels, for hosts that can show gain
+    /// reduction meters on their mixer channels. This is a positive number, so a compressor that
+    /// attenuates the signal by 6 dB should pass 6.0. Call this once per process call with the
+    /// block's peak or average gain reduction. This is currently only supported for CLAP plugins
+    /// with [`ClapPlugin::CLAP_GAIN_REDUCTION_METERING`][crate::prelude::ClapPlugin::CLAP_GAIN_REDUCTION_METERING]
+    /// set. Does nothing otherwise.
+    #[allow(unused_variables)]
+    fn set_gain_reduction(&self, db: f32) {}
+
+    /// Run `task` once for every index in `0..num_tasks`, and block until all of them have
+    /// finished. If the host provides a thread pool, then the tasks are spread over the host's
+    /// worker threads. This is currently only the case for CLAP hosts that support the thread pool
+    /// extension. Otherwise the tasks run sequentially on the audio thread. See
+    /// [`Buffer::for_each_channel_parallel()`][crate::prelude::Buffer::for_each_channel_parallel()]
+    /// for a convenient way to process channels in parallel.
+    fn execute_parallel(&self, num_tasks: u32, task: &(dyn Fn(u32) + Sync)) {
+        for task_idx in 0..num_tasks {
+            task(task_idx);
+        }
+    }
+
+    /// Inform the host that the plugin is about to change a parameter's value from the audio
+    /// thread. This works similar to
+    /// [`ParamSetter::begin_set_parameter()`][crate::prelude::ParamSetter::begin_set_parameter()],
+    /// but it's realtime-safe. This can be used to record a control signal like an envelope
+    /// follower's output as automation. Must be followed by one or more calls to
+    /// [`set_parameter()`][Self::set_parameter()] and a call to
+    /// [`end_set_parameter()`][Self::end_set_parameter()], possibly spread out over multiple
+    /// process calls. See [`AutomationWriter`][crate::util::AutomationWriter] for a utility that
+    /// handles all of this.
+    ///
+    /// Only CLAP has a notion of gestures for these changes, so this does nothing in the VST3 and
+    /// standalone wrappers.
+    fn begin_set_parameter<T: Param>(&mut self, param: &T) {
+        unsafe { self.raw_begin_set_parameter(param.as_ptr()) };
+    }
+
+    /// Change a parameter's value from the audio thread and inform the host about the change. The
+    /// new value is sent to the host at the start of the current block, and it is applied to the
+    /// parameter after the process function returns. The parameter's smoother will then start
+    /// moving towards the new value.
+    fn set_parameter<T: Param>(&mut self, param: &T, value: T::Plain) {
+        self.set_parameter_normalized(param, param.preview_normalized(value));
+    }
+
+    /// The same as [`set_parameter()`][Self::set_parameter()], but with a normalized value.
+    fn set_parameter_normalized<T: Param>(&mut self, param: &T, normalized: f32) {
+        unsafe { self.raw_set_parameter_normalized(param.as_ptr(), normalized) };
+    }
+
+    /// End the gesture started with [`begin_set_parameter()`][Self::begin_set_parameter()].
+    fn end_set_parameter<T: Param>(&mut self, param: &T) {
+        unsafe { self.raw_end_set_parameter(param.as_ptr()) };
+    }
+
+    /// The type erased version of [`begin_set_parameter()`][Self::begin_set_parameter()].
+    ///
+    /// # Safety
+    ///
+    /// The implementing function still needs to check if `param` actually exists. This function is
+    /// mostly marked as unsafe for API reasons.
+    unsafe fn raw_begin_set_parameter(&mut self, param: ParamPtr);
+
+    /// The type erased version of [`set_parameter_normalized()`][Self::set_parameter_normalized()].
+    ///
+    /// # Safety
+    ///
+    /// The implementing function still needs to check if `param` actually exists. This function is
+    /// mostly marked as unsafe for API reasons.
+    unsafe fn raw_set_parameter_normalized(&mut self, param: ParamPtr, normalized: f32);
+
+    /// The type erased version of [`end_set_parameter()`][Self::end_set_parameter()].
+    ///
+    /// # Safety
+    ///
+    /// The implementing function still needs to check if `param` actually exists. This function is
+    /// mostly marked as unsafe for API reasons.
+    unsafe fn raw_end_set_parameter(&mut self, param: ParamPtr);
 }
 
 /// Information about the plugin's transport. Depending on the plugin API and the host not all
 /// fields may be available.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Transport {
     /// Whether the transport is currently running.
     pub playing: bool,
@@ -108,6 +208,10 @@ pub struct Transport {
     pub recording: bool,
     /// Whether the pre-roll is currently active, if the plugin API reports this information.
     pub preroll_active: Option<bool>,
+    /// Whether the host's loop (or cycle) region is currently active, if the plugin API reports
+    /// this information. The loop range itself is available through
+    /// [`loop_range_samples()`][Self::loop_range_samples()] and the related functions.
+    pub loop_active: Option<bool>,
 
     /// The sample rate in Hertz. Also passed in
     /// [`Plugin::initialize()`][crate::prelude::Plugin::initialize()], so if you need this then you
@@ -150,6 +254,12 @@ pub struct Transport {
     /// that the end is exclusive. Can be calculated from the other loop range information if
     /// needed.
     pub(crate) loop_range_beats: Option<(f64, f64)>,
+
+    /// Whether `playing` changed since the previous process call.
+    pub(crate) playing_changed: bool,
+    /// Whether the playhead was moved since the previous process call, instead of advancing by the
+    /// previous buffer's length.
+    pub(crate) position_jumped: bool,
 }
 
 impl Transport {
@@ -159,6 +269,7 @@ impl Transport {
             playing: false,
             recording: false,
             preroll_active: None,
+            loop_active: None,
 
             sample_rate,
             tempo: None,
@@ -174,9 +285,33 @@ impl Transport {
             loop_range_samples: None,
             loop_range_seconds: None,
             loop_range_beats: None,
+
+            playing_changed: false,
+            position_jumped: false,
         }
     }
 
+    /// Whether the transport started playing since the previous process call. Always `false` for
+    /// the first process call after the plugin has been reset.
+    pub fn started_playing(&self) -> bool {
+        self.playing_changed && self.playing
+    }
+
+    /// Whether the transport stopped playing since the previous process call. Always `false` for
+    /// the first process call after the plugin has been reset.
+    pub fn stopped_playing(&self) -> bool {
+        self.playing_changed && !self.playing
+    }
+
+    /// Whether the playhead was relocated since the previous process call, for instance because the
+    /// user clicked somewhere on the timeline or because the transport looped back to the start of
+    /// the loop range. This can be used to clear delay lines and other buffers that contain audio
+    /// from the old position. Only detected when the host provides position information, and always
+    /// `false` for the first process call after the plugin has been reset.
+    pub fn position_jumped(&self) -> bool {
+        self.position_jumped
+    }
+
     /// The position in the song in samples. Will be calculated from other information if needed.
     pub fn pos_samples(&self) -> Option<i64> {
         match (
@@ -229,6 +364,51 @@ impl Transport {
         }
     }
 
+    /// The number of quarter notes the playhead advances per sample while playing, if the tempo is
+    /// known.
+    pub fn beats_per_sample(&self) -> Option<f64> {
+        self.tempo
+            .map(|tempo| tempo / 60.0 / self.sample_rate as f64)
+    }
+
+    /// Iterate over the position in quarter notes of every sample in a block of `num_samples`
+    /// samples, starting at [`pos_beats()`][Self::pos_beats()]. This can be used to phase-lock
+    /// LFOs and other modulators to the host's grid. The positions are computed from the start
+    /// position instead of being accumulated, so there's no drift. If the block crosses the end of
+    /// an active loop range, then the positions wrap around to the start of the loop just like the
+    /// host's playhead would. While the transport is stopped every sample has the same position.
+    ///
+    /// Returns `None` if the position or the tempo is not known.
+    pub fn pos_beats_iter(&self, num_samples: usize) -> Option<impl Iterator<Item = f64>> {
+        let start_pos_beats = self.pos_beats()?;
+        let beats_per_sample = if self.playing {
+            self.beats_per_sample()?
+        } else {
+            0.0
+        };
+
+        // The playhead only wraps around if it was inside of the loop at the start of the block
+        let loop_range = match (self.loop_active, self.loop_range_beats()) {
+            (Some(false), _) => None,
+            (_, Some((loop_start, loop_end)))
+                if loop_start < loop_end && start_pos_beats < loop_end =>
+            {
+                Some((loop_start, loop_end))
+            }
+            _ => None,
+        };
+
+        Some((0..num_samples).map(move |sample_idx| {
+            let pos_beats = start_pos_beats + (sample_idx as f64 * beats_per_sample);
+            match loop_range {
+                Some((loop_start, loop_end)) if pos_beats >= loop_end => {
+                    loop_start + ((pos_beats - loop_end) % (loop_end - loop_start))
+                }
+                _ => pos_beats,
+            }
+        }))
+    }
+
     /// The last bar's start position in beats. Will be calculated from other information if needed.
     pub fn bar_start_pos_beats(&self) -> Option<f64> {
         if self.bar_start_pos_beats.is_some() {
@@ -1,15 +1,19 @@
 //! Macros for logging and debug assertions. [`nih_dbg!()`], [`nih_trace!()`], and the
 //! `nih_debug_assert_*!()` macros are compiled out during release builds, so they can be used for
 //! asserting adiditonal invariants in debug builds. Check the [`nih_log!()`] macro for more
-//! information on NIH-plug's logger. None of the logging functions are realtime-safe, and you
-//! should avoid using them during release builds in any of the functions that may be called from an
-//! audio thread.
+//! information on NIH-plug's logger. [`nih_log!()`], [`nih_warn!()`], and [`nih_error!()`] don't
+//! block when they're called from the audio thread, but the other macros are not realtime-safe and
+//! you should avoid using them during release builds in any of the functions that may be called
+//! from an audio thread.
 
 // NOTE: Exporting macros in Rust is a bit weird. `#[macro_export]` causes them to be exported to
 //       the crate root, but that makes it difficult to include just the macros without using
 //       `#[macro_use] extern crate nih_plug;`. That's why the macros are also re-exported from this
 //       module.
 
+#[doc(hidden)]
+pub mod realtime;
+
 /// Write something to the logger. This defaults to STDERR unless the user is running Windows and a
 /// debugger has been attached, in which case `OutputDebugString()` will be used instead.
 ///
@@ -20,10 +24,22 @@
 ///   `OutputDebugString()`.
 /// - A file path, in which case the output gets appended to the end of that file which will be
 ///   created if necessary.
+///
+/// This macro is realtime-safe. When it's called from a plugin's audio thread, then the message is
+/// formatted into a fixed size buffer and sent to a background thread through a lock-free queue
+/// instead of being written to the log directly. Messages are truncated to 512 bytes, and they may
+/// be dropped if the audio thread logs a lot of messages in a short amount of time. Keep in mind
+/// that the formatting implementations of the arguments can still allocate.
 #[macro_export]
 macro_rules! nih_log {
-    ($($args:tt)*) => (
-        $crate::log::info!($($args)*)
+    () => (
+        $crate::__nih_log_realtime!($crate::log::Level::Info, module_path!(), "")
+    );
+    (target: $target:expr, $($args:tt)+) => (
+        $crate::__nih_log_realtime!($crate::log::Level::Info, $target, $($args)+)
+    );
+    ($($args:tt)+) => (
+        $crate::__nih_log_realtime!($crate::log::Level::Info, module_path!(), $($args)+)
     );
 }
 #[doc(inline)]
@@ -32,8 +48,14 @@ pub use nih_log;
 /// Similar to `nih_log!()`, but less subtle. Used for printing warnings.
 #[macro_export]
 macro_rules! nih_warn {
-    ($($args:tt)*) => (
-        $crate::log::warn!($($args)*)
+    () => (
+        $crate::__nih_log_realtime!($crate::log::Level::Warn, module_path!(), "")
+    );
+    (target: $target:expr, $($args:tt)+) => (
+        $crate::__nih_log_realtime!($crate::log::Level::Warn, $target, $($args)+)
+    );
+    ($($args:tt)+) => (
+        $crate::__nih_log_realtime!($crate::log::Level::Warn, module_path!(), $($args)+)
     );
 }
 #[doc(inline)]
@@ -42,13 +64,37 @@ pub use nih_warn;
 /// Similar to `nih_log!()`, but more scream-y. Used for printing fatal errors.
 #[macro_export]
 macro_rules! nih_error {
-    ($($args:tt)*) => (
-        $crate::log::error!($($args)*)
+    () => (
+        $crate::__nih_log_realtime!($crate::log::Level::Error, module_path!(), "")
+    );
+    (target: $target:expr, $($args:tt)+) => (
+        $crate::__nih_log_realtime!($crate::log::Level::Error, $target, $($args)+)
+    );
+    ($($args:tt)+) => (
+        $crate::__nih_log_realtime!($crate::log::Level::Error, module_path!(), $($args)+)
     );
 }
 #[doc(inline)]
 pub use nih_error;
 
+/// The implementation for the realtime-safe logging macros.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __nih_log_realtime {
+    ($level:expr, $target:expr, $($args:tt)+) => (
+        $crate::debug::realtime::log(
+            $level,
+            $crate::debug::realtime::Location {
+                target: $target,
+                module_path: module_path!(),
+                file: file!(),
+                line: line!(),
+            },
+            format_args!($($args)+),
+        )
+    );
+}
+
 /// The same as `nih_log!()`, but with source and thread information. Like the
 /// `nih_debug_assert*!()` macros, this is only shown when compiling in debug mode.
 #[macro_export]
@@ -84,7 +130,10 @@ macro_rules! nih_dbg {
 pub use nih_dbg;
 
 /// A `debug_assert!()` analogue that prints the error with line number information instead of
-/// panicking. During tests this is upgraded to a regular panicking `debug_assert!()`.
+/// panicking. During tests this is upgraded to a regular panicking `debug_assert!()`. Like
+/// [`nih_log!()`], failures don't block when they happen on the audio thread. The wrappers use these
+/// to check the host's behavior, since a misbehaving host should not cause the plugin to crash the
+/// user's session.
 ///
 /// TODO: Detect if we're running under a debugger, and trigger a break if we are
 #[macro_export]
@@ -94,7 +143,7 @@ macro_rules! nih_debug_assert {
         if cfg!(test) {
            debug_assert!($cond);
         } else if cfg!(debug_assertions) && !$cond {
-            $crate::util::permit_alloc(|| $crate::log::debug!(concat!("Debug assertion failed: ", stringify!($cond))));
+            $crate::util::permit_alloc(|| $crate::__nih_log_realtime!($crate::log::Level::Debug, module_path!(), concat!("Debug assertion failed: ", stringify!($cond))));
         }
     );
     ($cond:expr, $format:expr $(, $($args:tt)*)?) => (
@@ -102,7 +151,7 @@ macro_rules! nih_debug_assert {
         if cfg!(test) {
            debug_assert!($cond, $format, $($($args)*)?);
         } else if cfg!(debug_assertions) && !$cond {
-            $crate::util::permit_alloc(|| $crate::log::debug!(concat!("Debug assertion failed: ", stringify!($cond), ", ", $format), $($($args)*)?));
+            $crate::util::permit_alloc(|| $crate::__nih_log_realtime!($crate::log::Level::Debug, module_path!(), concat!("Debug assertion failed: ", stringify!($cond), ", ", $format), $($($args)*)?));
         }
     );
 }
@@ -117,14 +166,14 @@ macro_rules! nih_debug_assert_failure {
         if cfg!(test) {
            debug_assert!(false, "Debug assertion failed");
         } else if cfg!(debug_assertions) {
-            $crate::util::permit_alloc(|| $crate::log::debug!("Debug assertion failed"));
+            $crate::util::permit_alloc(|| $crate::__nih_log_realtime!($crate::log::Level::Debug, module_path!(), "Debug assertion failed"));
         }
     );
     ($format:expr $(, $($args:tt)*)?) => (
         if cfg!(test) {
            debug_assert!(false, concat!("Debug assertion failed: ", $format), $($($args)*)?);
         } else if cfg!(debug_assertions) {
-            $crate::util::permit_alloc(|| $crate::log::debug!(concat!("Debug assertion failed: ", $format), $($($args)*)?));
+            $crate::util::permit_alloc(|| $crate::__nih_log_realtime!($crate::log::Level::Debug, module_path!(), concat!("Debug assertion failed: ", $format), $($($args)*)?));
         }
     );
 }
@@ -140,7 +189,7 @@ macro_rules! nih_debug_assert_eq {
         if cfg!(test) {
            debug_assert_eq!($left, $right);
         } else if cfg!(debug_assertions) && $left != $right {
-            $crate::util::permit_alloc(|| $crate::log::debug!(concat!("Debug assertion failed: ", stringify!($left), " != ", stringify!($right))));
+            $crate::util::permit_alloc(|| $crate::__nih_log_realtime!($crate::log::Level::Debug, module_path!(), concat!("Debug assertion failed: ", stringify!($left), " != ", stringify!($right))));
         }
     );
     ($left:expr, $right:expr, $format:expr $(, $($args:tt)*)?) => (
@@ -148,7 +197,7 @@ macro_rules! nih_debug_assert_eq {
         if cfg!(test) {
            debug_assert_eq!($left, $right, $format, $($($args)*)?);
         } else if cfg!(debug_assertions) && $left != $right {
-            $crate::util::permit_alloc(|| $crate::log::debug!(concat!("Debug assertion failed: ", stringify!($left), " != ", stringify!($right), ", ", $format), $($($args)*)?));
+            $crate::util::permit_alloc(|| $crate::__nih_log_realtime!($crate::log::Level::Debug, module_path!(), concat!("Debug assertion failed: ", stringify!($left), " != ", stringify!($right), ", ", $format), $($($args)*)?));
         }
     );
 }
@@ -164,7 +213,7 @@ macro_rules! nih_debug_assert_ne {
         if cfg!(test) {
            debug_assert_ne!($left, $right);
         } else if cfg!(debug_assertions) && $left == $right {
-            $crate::util::permit_alloc(|| $crate::log::debug!(concat!("Debug assertion failed: ", stringify!($left), " == ", stringify!($right))));
+            $crate::util::permit_alloc(|| $crate::__nih_log_realtime!($crate::log::Level::Debug, module_path!(), concat!("Debug assertion failed: ", stringify!($left), " == ", stringify!($right))));
         }
     );
     ($left:expr, $right:expr, $format:expr $(, $($args:tt)*)?) => (
@@ -172,7 +221,7 @@ macro_rules! nih_debug_assert_ne {
         if cfg!(test) {
            debug_assert_ne!($left, $right, $format, $($($args)*)?);
         } else if cfg!(debug_assertions) && $left == $right  {
-            $crate::util::permit_alloc(|| $crate::log::debug!(concat!("Debug assertion failed: ", stringify!($left), " == ", stringify!($right), ", ", $format), $($($args)*)?));
+            $crate::util::permit_alloc(|| $crate::__nih_log_realtime!($crate::log::Level::Debug, module_path!(), concat!("Debug assertion failed: ", stringify!($left), " == ", stringify!($right), ", ", $format), $($($args)*)?));
         }
     );
 }
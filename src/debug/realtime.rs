@@ -0,0 +1,248 @@
+//! The realtime-safe backend for the logging macros. Messages logged from within the wrappers'
+//! audio processing functions are formatted into fixed size records and pushed onto a lock-free
+//! queue. A background thread drains that queue and forwards the messages to the actual logger.
+//! Messages logged from other threads are passed to the logger directly.
+
+use crossbeam::channel;
+use crossbeam::queue::ArrayQueue;
+use log::{Level, Record};
+use parking_lot::Mutex;
+use std::cell::Cell;
+use std::fmt::{self, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// The maximum length of a message logged from the audio thread in bytes. Longer messages are
+/// truncated.
+const MAX_MESSAGE_LEN: usize = 512;
+/// The number of messages that can be queued before new messages are dropped.
+const QUEUE_CAPACITY: usize = 256;
+/// How often the drain thread forwards queued messages to the logger.
+const DRAIN_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Queued messages. This is allocated once when the first drain thread gets spawned.
+static QUEUE: OnceLock<ArrayQueue<QueuedRecord>> = OnceLock::new();
+/// Whether there's currently a drain thread running. If there isn't, then messages are logged
+/// directly since they would otherwise never be printed.
+static DRAIN_THREAD_ACTIVE: AtomicBool = AtomicBool::new(false);
+/// The number of messages dropped because the queue was full. Reported by the drain thread.
+static NUM_DROPPED_MESSAGES: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static::lazy_static! {
+    /// The shared drain thread. Wrapper instances hold a [`LogDrainHandle`], and the thread is
+    /// shut down when the last handle is dropped.
+    static ref DRAIN_THREAD: Mutex<Weak<DrainThread>> = Mutex::new(Weak::new());
+}
+
+thread_local! {
+    /// Set while the current thread is running a wrapper's audio processing function.
+    static IS_REALTIME: Cell<bool> = const { Cell::new(false) };
+}
+
+/// The source location of a log message. This is created by the logging macros.
+#[doc(hidden)]
+pub struct Location {
+    pub target: &'static str,
+    pub module_path: &'static str,
+    pub file: &'static str,
+    pub line: u32,
+}
+
+/// A log message formatted into a fixed size buffer.
+struct QueuedRecord {
+    level: Level,
+    location: Location,
+    message: [u8; MAX_MESSAGE_LEN],
+    message_len: usize,
+}
+
+/// A handle for the shared drain thread. The thread keeps running as long as a handle exists.
+pub(crate) struct LogDrainHandle {
+    _drain_thread: Arc<DrainThread>,
+}
+
+struct DrainThread {
+    shutdown_sender: channel::Sender<()>,
+    join_handle: Option<JoinHandle<()>>,
+}
+
+/// Marks the current thread as a realtime thread while this object is alive. The logging macros
+/// don't block while this is active.
+pub(crate) struct RealtimeScope {
+    was_realtime: bool,
+}
+
+/// The function called by the logging macros. See the module's documentation.
+#[doc(hidden)]
+#[inline]
+pub fn log(level: Level, location: Location, args: fmt::Arguments) {
+    if level > log::STATIC_MAX_LEVEL || level > log::max_level() {
+        return;
+    }
+
+    let is_realtime = IS_REALTIME.try_with(|is_realtime| is_realtime.get()) == Ok(true);
+    match QUEUE.get() {
+        Some(queue) if is_realtime && DRAIN_THREAD_ACTIVE.load(Ordering::Relaxed) => {
+            let mut record = QueuedRecord {
+                level,
+                location,
+                message: [0; MAX_MESSAGE_LEN],
+                message_len: 0,
+            };
+            // Formatting errors are only caused by truncation, and they can safely be ignored
+            let _ = record.write_fmt(args);
+
+            if queue.push(record).is_err() {
+                NUM_DROPPED_MESSAGES.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        _ => log_record(level, &location, args),
+    }
+}
+
+fn log_record(level: Level, location: &Location, args: fmt::Arguments) {
+    log::logger().log(
+        &Record::builder()
+            .args(args)
+            .level(level)
+            .target(location.target)
+            .module_path_static(Some(location.module_path))
+            .file_static(Some(location.file))
+            .line(Some(location.line))
+            .build(),
+    );
+}
+
+/// Forward all queued messages to the logger.
+fn drain_queue() {
+    let queue = match QUEUE.get() {
+        Some(queue) => queue,
+        None => return,
+    };
+
+    while let Some(record) = queue.pop() {
+        log_record(
+            record.level,
+            &record.location,
+            format_args!("{}", record.message()),
+        );
+    }
+
+    let num_dropped_messages = NUM_DROPPED_MESSAGES.swap(0, Ordering::Relaxed);
+    if num_dropped_messages > 0 {
+        log::warn!(
+            "Dropped {num_dropped_messages} log messages from the audio thread because the log \
+             queue was full"
+        );
+    }
+}
+
+impl QueuedRecord {
+    fn message(&self) -> &str {
+        // `write_str()` only ever truncates at character boundaries
+        std::str::from_utf8(&self.message[..self.message_len]).unwrap_or_default()
+    }
+}
+
+impl Write for QueuedRecord {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let remaining_capacity = MAX_MESSAGE_LEN - self.message_len;
+        if s.len() <= remaining_capacity {
+            self.message[self.message_len..self.message_len + s.len()]
+                .copy_from_slice(s.as_bytes());
+            self.message_len += s.len();
+
+            Ok(())
+        } else {
+            let mut truncated_len = remaining_capacity;
+            while !s.is_char_boundary(truncated_len) {
+                truncated_len -= 1;
+            }
+
+            self.message[self.message_len..self.message_len + truncated_len]
+                .copy_from_slice(&s.as_bytes()[..truncated_len]);
+            self.message_len += truncated_len;
+
+            Err(fmt::Error)
+        }
+    }
+}
+
+impl LogDrainHandle {
+    /// Get a handle for the drain thread, spawning it if it is not yet running.
+    pub fn get_or_create() -> Self {
+        let mut drain_thread = DRAIN_THREAD.lock();
+        let drain_thread = match drain_thread.upgrade() {
+            Some(existing_drain_thread) => existing_drain_thread,
+            None => {
+                let new_drain_thread = Arc::new(DrainThread::spawn());
+                *drain_thread = Arc::downgrade(&new_drain_thread);
+
+                new_drain_thread
+            }
+        };
+
+        Self {
+            _drain_thread: drain_thread,
+        }
+    }
+}
+
+impl DrainThread {
+    fn spawn() -> Self {
+        QUEUE.get_or_init(|| ArrayQueue::new(QUEUE_CAPACITY));
+
+        let (shutdown_sender, shutdown_receiver) = channel::bounded(1);
+        let join_handle = thread::Builder::new()
+            .name(String::from("log-drain"))
+            .spawn(move || loop {
+                match shutdown_receiver.recv_timeout(DRAIN_INTERVAL) {
+                    Err(channel::RecvTimeoutError::Timeout) => drain_queue(),
+                    Ok(()) | Err(channel::RecvTimeoutError::Disconnected) => break,
+                }
+            })
+            .expect("Could not spawn the log drain thread");
+        DRAIN_THREAD_ACTIVE.store(true, Ordering::SeqCst);
+
+        Self {
+            shutdown_sender,
+            join_handle: Some(join_handle),
+        }
+    }
+}
+
+impl Drop for DrainThread {
+    fn drop(&mut self) {
+        // New messages are logged directly from this point onwards. Any remaining queued messages
+        // are printed here.
+        DRAIN_THREAD_ACTIVE.store(false, Ordering::SeqCst);
+        self.shutdown_sender
+            .send(())
+            .expect("Failed while sending log drain thread shutdown request");
+        self.join_handle
+            .take()
+            .expect("Missing log drain thread JoinHandle")
+            .join()
+            .expect("Log drain thread panicked");
+
+        drain_queue();
+    }
+}
+
+impl RealtimeScope {
+    pub fn enter() -> Self {
+        Self {
+            was_realtime: IS_REALTIME
+                .try_with(|is_realtime| is_realtime.replace(true))
+                .unwrap_or(false),
+        }
+    }
+}
+
+impl Drop for RealtimeScope {
+    fn drop(&mut self) {
+        let _ = IS_REALTIME.try_with(|is_realtime| is_realtime.set(self.was_realtime));
+    }
+}
@@ -78,6 +78,10 @@
 //!  - After calling `.with_smoother()` during an integer or floating point parameter's creation,
 //!    you can use `param.smoothed` to access smoothed values for that parameter. Be sure to check
 //!    out the [`Smoother`][prelude::Smoother] API for more details.
+//!  - Data that needs to be sent from the audio thread to the editor, like spectra or meter
+//!    readings, should be shared through a [`TripleBuffer`][util::TripleBuffer] or through atomics
+//!    instead of through a mutex. Locking a mutex on the audio thread can cause it to block on the
+//!    GUI thread.
 //!
 //! There's a whole lot more to discuss, but once you understand the above you should be able to
 //! figure out the rest by reading through the examples and the API documentation. Good luck!
@@ -109,8 +113,11 @@ pub mod context;
 pub mod editor;
 mod event_loop;
 pub mod midi;
+#[cfg(feature = "osc")]
+pub mod osc;
 pub mod params;
 pub mod plugin;
+pub mod test;
 pub mod wrapper;
 
 // This is also re-exported from the prelude but since the other export entry points are macros and
@@ -5,6 +5,7 @@ use midi_consts::channel_event as midi;
 use self::sysex::SysExMessage;
 use crate::prelude::Plugin;
 
+pub mod learn;
 pub mod sysex;
 
 pub use midi_consts::channel_event::control_change;
@@ -623,27 +624,36 @@ impl<S: SysExMessage> NoteEvent<S> {
 
     /// Subtract a sample offset from this event's timing, needed to compensate for the block
     /// splitting in the VST3 wrapper implementation because all events have to be read upfront.
-    #[cfg_attr(not(feature = "vst3"), allow(dead_code))]
     pub(crate) fn subtract_timing(&mut self, samples: u32) {
+        *self.timing_mut() -= samples;
+    }
+
+    /// Add a sample offset to this event's timing. Used by the test harness to make the timing of
+    /// output events relative to the start of the process call instead of the current block.
+    pub(crate) fn add_timing(&mut self, samples: u32) {
+        *self.timing_mut() += samples;
+    }
+
+    fn timing_mut(&mut self) -> &mut u32 {
         match self {
-            NoteEvent::NoteOn { timing, .. } => *timing -= samples,
-            NoteEvent::NoteOff { timing, .. } => *timing -= samples,
-            NoteEvent::Choke { timing, .. } => *timing -= samples,
-            NoteEvent::VoiceTerminated { timing, .. } => *timing -= samples,
-            NoteEvent::PolyModulation { timing, .. } => *timing -= samples,
-            NoteEvent::MonoAutomation { timing, .. } => *timing -= samples,
-            NoteEvent::PolyPressure { timing, .. } => *timing -= samples,
-            NoteEvent::PolyVolume { timing, .. } => *timing -= samples,
-            NoteEvent::PolyPan { timing, .. } => *timing -= samples,
-            NoteEvent::PolyTuning { timing, .. } => *timing -= samples,
-            NoteEvent::PolyVibrato { timing, .. } => *timing -= samples,
-            NoteEvent::PolyExpression { timing, .. } => *timing -= samples,
-            NoteEvent::PolyBrightness { timing, .. } => *timing -= samples,
-            NoteEvent::MidiChannelPressure { timing, .. } => *timing -= samples,
-            NoteEvent::MidiPitchBend { timing, .. } => *timing -= samples,
-            NoteEvent::MidiCC { timing, .. } => *timing -= samples,
-            NoteEvent::MidiProgramChange { timing, .. } => *timing -= samples,
-            NoteEvent::MidiSysEx { timing, .. } => *timing -= samples,
+            NoteEvent::NoteOn { timing, .. } => timing,
+            NoteEvent::NoteOff { timing, .. } => timing,
+            NoteEvent::Choke { timing, .. } => timing,
+            NoteEvent::VoiceTerminated { timing, .. } => timing,
+            NoteEvent::PolyModulation { timing, .. } => timing,
+            NoteEvent::MonoAutomation { timing, .. } => timing,
+            NoteEvent::PolyPressure { timing, .. } => timing,
+            NoteEvent::PolyVolume { timing, .. } => timing,
+            NoteEvent::PolyPan { timing, .. } => timing,
+            NoteEvent::PolyTuning { timing, .. } => timing,
+            NoteEvent::PolyVibrato { timing, .. } => timing,
+            NoteEvent::PolyExpression { timing, .. } => timing,
+            NoteEvent::PolyBrightness { timing, .. } => timing,
+            NoteEvent::MidiChannelPressure { timing, .. } => timing,
+            NoteEvent::MidiPitchBend { timing, .. } => timing,
+            NoteEvent::MidiCC { timing, .. } => timing,
+            NoteEvent::MidiProgramChange { timing, .. } => timing,
+            NoteEvent::MidiSysEx { timing, .. } => timing,
         }
     }
 }
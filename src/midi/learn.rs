@@ -0,0 +1,242 @@
+//! MIDI learn, for controlling parameters with MIDI CCs.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::params::internals::ParamPtr;
+use crate::params::Param;
+
+/// The number of MIDI channels times the number of CCs per channel.
+const NUM_CCS: usize = 16 * 128;
+
+/// A MIDI CC that has been mapped to a parameter through [`MidiLearn`]. These are stored in the
+/// plugin's state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MidiMapping {
+    /// The CC's channel, in `0..16`.
+    pub channel: u8,
+    /// The CC number.
+    pub cc: u8,
+    /// The ID of the parameter the CC controls.
+    pub param_id: String,
+}
+
+/// Maps incoming MIDI CCs to parameters. This is managed by the wrapper and can be accessed from
+/// the editor through [`GuiContext::midi_learn()`][crate::prelude::GuiContext::midi_learn()]. A
+/// 'learn' button in the editor calls [`start_learning()`][Self::start_learning()], after which
+/// the next incoming CC is mapped to that parameter. From then on, that CC sets the parameter's
+/// value before the plugin's process function is called, and the CC is not passed to the plugin.
+/// The parameter changes are also sent to the host so they can be recorded as automation. The
+/// mappings are saved as part of the plugin's state.
+///
+/// Every CC controls at most one parameter, and every parameter is controlled by at most one CC.
+/// Since VST3 hosts only send MIDI CCs to plugins that handle them, MIDI learn only works for
+/// plugins with [`Plugin::MIDI_INPUT`][crate::prelude::Plugin::MIDI_INPUT] set to
+/// [`MidiConfig::MidiCCs`][crate::prelude::MidiConfig::MidiCCs] or higher.
+pub struct MidiLearn {
+    /// The IDs and pointers for all of the plugin's parameters, indexed by the values stored in
+    /// `mappings` and `learning`.
+    params: Vec<(String, ParamPtr)>,
+    /// For every channel and CC number, the index of the mapped parameter in `params` plus one, or
+    /// zero if the CC is not mapped. Indexed by `channel * 128 + cc`.
+    mappings: Box<[AtomicU32]>,
+    /// The index of the parameter that should be mapped to the next incoming CC plus one, or zero
+    /// if MIDI learn is not active.
+    learning: AtomicU32,
+}
+
+impl std::fmt::Debug for MidiLearn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MidiLearn")
+            .field("mappings", &self.mappings())
+            .field("is_learning", &self.is_learning())
+            .finish()
+    }
+}
+
+impl MidiLearn {
+    /// Create an object without any mappings for a plugin's parameters.
+    pub(crate) fn new(params: impl IntoIterator<Item = (String, ParamPtr)>) -> Self {
+        Self {
+            params: params.into_iter().collect(),
+            mappings: (0..NUM_CCS).map(|_| AtomicU32::new(0)).collect(),
+            learning: AtomicU32::new(0),
+        }
+    }
+
+    /// Map the next incoming MIDI CC to `param`, replacing the parameter's existing mapping if it
+    /// has one. Calling this again before a CC has been received changes the parameter that will
+    /// be mapped.
+    pub fn start_learning(&self, param: &impl Param) {
+        match self.param_idx(param.as_ptr()) {
+            Some(param_idx) => self.learning.store(param_idx + 1, Ordering::SeqCst),
+            None => nih_debug_assert_failure!("Unknown parameter: {:?}", param.as_ptr()),
+        }
+    }
+
+    /// Stop waiting for a CC after [`start_learning()`][Self::start_learning()].
+    pub fn cancel_learning(&self) {
+        self.learning.store(0, Ordering::SeqCst);
+    }
+
+    /// Whether the next incoming CC will be mapped to a parameter.
+    pub fn is_learning(&self) -> bool {
+        self.learning.load(Ordering::SeqCst) != 0
+    }
+
+    /// Whether the next incoming CC will be mapped to `param`. Useful for highlighting the
+    /// parameter in the editor.
+    pub fn is_learning_param(&self, param: &impl Param) -> bool {
+        match self.param_idx(param.as_ptr()) {
+            Some(param_idx) => self.learning.load(Ordering::SeqCst) == param_idx + 1,
+            None => false,
+        }
+    }
+
+    /// The `(channel, cc)` pair mapped to `param`, if any.
+    pub fn mapping(&self, param: &impl Param) -> Option<(u8, u8)> {
+        let param_idx = self.param_idx(param.as_ptr())?;
+        self.mappings
+            .iter()
+            .position(|mapping| mapping.load(Ordering::SeqCst) == param_idx + 1)
+            .map(|cc_idx| ((cc_idx / 128) as u8, (cc_idx % 128) as u8))
+    }
+
+    /// Remove `param`'s mapping, if it has one.
+    pub fn clear_mapping(&self, param: &impl Param) {
+        if let Some(param_idx) = self.param_idx(param.as_ptr()) {
+            self.remove_param_mappings(param_idx as u32 + 1);
+        }
+    }
+
+    /// Remove all mappings.
+    pub fn clear_all_mappings(&self) {
+        for mapping in self.mappings.iter() {
+            mapping.store(0, Ordering::SeqCst);
+        }
+    }
+
+    /// All current mappings, in order of their channels and CC numbers.
+    pub fn mappings(&self) -> Vec<MidiMapping> {
+        self.mappings
+            .iter()
+            .enumerate()
+            .filter_map(|(cc_idx, mapping)| {
+                let (param_id, _) = self
+                    .params
+                    .get((mapping.load(Ordering::SeqCst) as usize).checked_sub(1)?)?;
+
+                Some(MidiMapping {
+                    channel: (cc_idx / 128) as u8,
+                    cc: (cc_idx % 128) as u8,
+                    param_id: param_id.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Replace the current mappings, for instance when restoring the plugin's state. Mappings for
+    /// unknown parameters are ignored.
+    pub fn set_mappings(&self, mappings: &[MidiMapping]) {
+        self.clear_all_mappings();
+        for mapping in mappings {
+            let Some(param_idx) = self
+                .params
+                .iter()
+                .position(|(id, _)| *id == mapping.param_id)
+            else {
+                nih_debug_assert_failure!("Unknown parameter: {}", mapping.param_id);
+                continue;
+            };
+            if mapping.channel >= 16 || mapping.cc >= 128 {
+                nih_debug_assert_failure!("Invalid MIDI mapping: {:?}", mapping);
+                continue;
+            }
+
+            self.remove_param_mappings(param_idx as u32 + 1);
+            self.mappings[(mapping.channel as usize * 128) + mapping.cc as usize]
+                .store(param_idx as u32 + 1, Ordering::SeqCst);
+        }
+    }
+
+    /// Handle an incoming MIDI CC. If MIDI learn is active, then the CC is first mapped to the
+    /// parameter being learned. Returns the parameter and the normalized value it should be set to
+    /// if the CC is mapped, in which case the CC should not be passed to the plugin. This does not
+    /// allocate and can be called from the audio thread.
+    pub(crate) fn handle_cc(&self, channel: u8, cc: u8, value: f32) -> Option<(ParamPtr, f32)> {
+        if channel >= 16 || cc >= 128 {
+            return None;
+        }
+        let mapping = &self.mappings[(channel as usize * 128) + cc as usize];
+
+        let learning = self.learning.swap(0, Ordering::SeqCst);
+        if learning != 0 {
+            self.remove_param_mappings(learning);
+            mapping.store(learning, Ordering::SeqCst);
+        }
+
+        let (_, param_ptr) = self
+            .params
+            .get((mapping.load(Ordering::SeqCst) as usize).checked_sub(1)?)?;
+
+        Some((*param_ptr, value.clamp(0.0, 1.0)))
+    }
+
+    /// Remove all mappings to a parameter, using the encoding used in `mappings`.
+    fn remove_param_mappings(&self, encoded_param_idx: u32) {
+        for mapping in self.mappings.iter() {
+            let _ =
+                mapping.compare_exchange(encoded_param_idx, 0, Ordering::SeqCst, Ordering::SeqCst);
+        }
+    }
+
+    fn param_idx(&self, param_ptr: ParamPtr) -> Option<u32> {
+        self.params
+            .iter()
+            .position(|(_, ptr)| *ptr == param_ptr)
+            .map(|param_idx| param_idx as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::range::FloatRange;
+    use crate::params::FloatParam;
+
+    #[test]
+    fn learn_and_restore() {
+        let gain = FloatParam::new("Gain", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 });
+        let midi_learn = MidiLearn::new([(String::from("gain"), gain.as_ptr())]);
+
+        // CCs are passed through until they're mapped
+        assert_eq!(midi_learn.handle_cc(0, 1, 0.5), None);
+        midi_learn.start_learning(&gain);
+        assert!(midi_learn.is_learning_param(&gain));
+        assert_eq!(
+            midi_learn.handle_cc(2, 74, 0.25),
+            Some((gain.as_ptr(), 0.25))
+        );
+        assert!(!midi_learn.is_learning());
+        assert_eq!(midi_learn.mapping(&gain), Some((2, 74)));
+        assert_eq!(midi_learn.handle_cc(0, 1, 0.5), None);
+
+        // Learning again replaces the old mapping
+        midi_learn.start_learning(&gain);
+        midi_learn.handle_cc(0, 7, 1.0);
+        let mappings = midi_learn.mappings();
+        assert_eq!(
+            mappings,
+            [MidiMapping {
+                channel: 0,
+                cc: 7,
+                param_id: String::from("gain"),
+            }]
+        );
+
+        midi_learn.clear_mapping(&gain);
+        assert_eq!(midi_learn.handle_cc(0, 7, 1.0), None);
+        midi_learn.set_mappings(&mappings);
+        assert_eq!(midi_learn.mapping(&gain), Some((0, 7)));
+    }
+}
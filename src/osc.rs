@@ -0,0 +1,482 @@
+//! Remote control for a plugin's parameters over [OSC](https://opensoundcontrol.stanford.edu/).
+//! This is useful for headless standalone deployments and for control surfaces like TouchOSC.
+
+use anyhow::{Context, Result};
+use crossbeam::queue::ArrayQueue;
+use rosc::{OscMessage, OscPacket, OscType};
+use std::collections::{HashMap, HashSet};
+use std::io::ErrorKind;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::context::gui::GuiContext;
+use crate::params::internals::ParamPtr;
+use crate::params::{ParamListener, Params};
+
+/// How long the bridge's thread waits for incoming messages before sending out any parameter
+/// changes. This is also the bridge's worst case latency for reporting parameter changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The number of parameter changes that can be queued up between two polls. Older changes are
+/// dropped when the queue is full.
+const CHANGE_QUEUE_CAPACITY: usize = 4096;
+
+/// The maximum number of clients parameter changes are sent to. Messages from new clients are still
+/// handled once this limit has been reached, but those clients won't receive parameter changes
+/// until older clients have expired.
+const MAX_CLIENTS: usize = 16;
+
+/// Clients that haven't sent any messages for this long no longer receive parameter changes. This
+/// limits how long spoofed source addresses can be used to direct traffic at other machines.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Exposes a plugin's parameters over OSC using a UDP socket. Messages are handled on a separate
+/// thread that runs until the bridge is dropped. All parameter values are normalized `[0, 1]`
+/// values, which matches what most control surfaces send by default. The bridge responds to the
+/// following addresses, where `<id>` is a parameter's ID:
+///
+/// - `/param/<id> <value>` sets a parameter to a normalized value. Integer, float, double, and
+///   boolean arguments are accepted. If there's no active gesture for the parameter, then the change
+///   is wrapped in a begin and end gesture so the host records it as a single automation change.
+/// - `/param/<id>/begin` and `/param/<id>/end` start and end a gesture, for instance when a fader is
+///   touched and released. Any values sent in between are part of the same gesture.
+/// - `/param/<id>/get` sends the parameter's current value back to the sender as `/param/<id>`.
+/// - `/params/get` sends the values of all parameters back to the sender.
+///
+/// Every client that has sent a message to the bridge is also sent a `/param/<id> <value>` message
+/// whenever a parameter changes, regardless of whether that change came from the host, the editor,
+/// or another OSC client. This keeps control surfaces in sync with the plugin. At most 16 clients
+/// receive these changes, and clients that have not sent any messages in the last five minutes are
+/// removed from that list. Control surfaces that only listen can periodically send `/params/get` to
+/// keep receiving changes.
+///
+/// Non-finite values are ignored, and all other values are clamped to `[0, 1]`.
+///
+/// Parameters are changed through the [`GuiContext`] from the bridge's thread. The standalone
+/// target can create a bridge using its `--osc-port` option. Plugins can also create one from
+/// [`Editor::spawn()`][crate::prelude::Editor::spawn()] and store it in the editor's handle, in
+/// which case the bridge is only active while the editor is open.
+pub struct OscBridge {
+    local_addr: SocketAddr,
+    params: Arc<dyn Params>,
+    /// Registered on `params` for as long as the bridge is alive.
+    listener: ParamListener,
+
+    should_stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// The state used by the bridge's thread.
+struct BridgeThread {
+    socket: UdpSocket,
+    context: Arc<dyn GuiContext>,
+
+    /// The plugin's parameter IDs and pointers, in the same order as the parameter map.
+    params: Vec<(String, ParamPtr)>,
+    /// Indices into `params` for every parameter ID.
+    param_indices: Arc<HashMap<String, usize>>,
+    /// Changes reported by the parameter listener as `(param_idx, normalized_value)` pairs. The
+    /// listener can run on the audio thread, so sending these changes is deferred to this thread.
+    changes: Arc<ArrayQueue<(usize, f32)>>,
+
+    /// The addresses that recently sent a message to the bridge. Parameter changes are sent to all
+    /// of these clients.
+    clients: Clients,
+    /// Indices into `params` for the parameters that currently have an active gesture started
+    /// through a `/begin` message.
+    active_gestures: HashSet<usize>,
+
+    should_stop: Arc<AtomicBool>,
+}
+
+impl OscBridge {
+    /// Bind to `addr` and start listening for OSC messages. Anyone who can send packets to this
+    /// address can change the plugin's parameters, so this should usually be a loopback address
+    /// like `127.0.0.1:<port>`. Use `0.0.0.0:<port>` only to accept messages from other devices on
+    /// a trusted network. Returns an error if the socket could not be created.
+    pub fn spawn(
+        addr: impl ToSocketAddrs,
+        params: Arc<dyn Params>,
+        context: Arc<dyn GuiContext>,
+    ) -> Result<Self> {
+        let socket = UdpSocket::bind(addr).context("Could not bind the OSC socket")?;
+        socket
+            .set_read_timeout(Some(POLL_INTERVAL))
+            .context("Could not configure the OSC socket")?;
+        let local_addr = socket
+            .local_addr()
+            .context("Could not query the OSC socket's address")?;
+
+        let param_map: Vec<(String, ParamPtr)> = params
+            .param_map()
+            .into_iter()
+            .map(|(param_id, param_ptr, _)| (param_id, param_ptr))
+            .collect();
+        let param_indices: Arc<HashMap<String, usize>> = Arc::new(
+            param_map
+                .iter()
+                .enumerate()
+                .map(|(param_idx, (param_id, _))| (param_id.clone(), param_idx))
+                .collect(),
+        );
+        let changes = Arc::new(ArrayQueue::new(CHANGE_QUEUE_CAPACITY));
+
+        let listener: ParamListener = Arc::new({
+            let param_indices = param_indices.clone();
+            let changes = changes.clone();
+            move |param_id: &str, normalized_value: f32| {
+                if let Some(&param_idx) = param_indices.get(param_id) {
+                    changes.force_push((param_idx, normalized_value));
+                }
+            }
+        });
+        params.add_param_listener(listener.clone());
+
+        let should_stop = Arc::new(AtomicBool::new(false));
+        let spawn_result = thread::Builder::new()
+            .name(String::from("osc-bridge"))
+            .spawn({
+                let bridge_thread = BridgeThread {
+                    socket,
+                    context,
+
+                    params: param_map,
+                    param_indices,
+                    changes,
+
+                    clients: Clients::default(),
+                    active_gestures: HashSet::new(),
+
+                    should_stop: should_stop.clone(),
+                };
+
+                move || bridge_thread.run()
+            });
+        let thread = match spawn_result {
+            Ok(thread) => thread,
+            Err(err) => {
+                params.remove_param_listener(&listener);
+                return Err(err).context("Could not spawn the OSC thread");
+            }
+        };
+
+        Ok(Self {
+            local_addr,
+            params,
+            listener,
+
+            should_stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// The address the bridge is listening on. Useful when binding to port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Drop for OscBridge {
+    fn drop(&mut self) {
+        self.params.remove_param_listener(&self.listener);
+
+        self.should_stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.join().expect("The OSC thread panicked");
+        }
+    }
+}
+
+impl BridgeThread {
+    fn run(mut self) {
+        let mut buffer = [0u8; rosc::decoder::MTU];
+        while !self.should_stop.load(Ordering::Relaxed) {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((size, client)) => match rosc::decoder::decode_udp(&buffer[..size]) {
+                    Ok((_, packet)) => {
+                        if !self.clients.register(client, Instant::now()) {
+                            nih_trace!(
+                                "Not sending parameter changes to {client}, too many OSC clients"
+                            );
+                        }
+                        self.handle_packet(packet, client);
+                    }
+                    Err(err) => nih_trace!("Could not decode OSC packet from {client}: {err:?}"),
+                },
+                Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => (),
+                Err(err) => {
+                    nih_error!("Error while receiving OSC messages, stopping the bridge: {err}");
+                    break;
+                }
+            }
+
+            self.clients.expire(Instant::now());
+            self.send_changes();
+        }
+
+        // Gestures that are still active would otherwise never be ended
+        for param_idx in self.active_gestures.drain() {
+            unsafe { self.context.raw_end_set_parameter(self.params[param_idx].1) };
+        }
+    }
+
+    fn handle_packet(&mut self, packet: OscPacket, client: SocketAddr) {
+        match packet {
+            OscPacket::Message(message) => self.handle_message(message, client),
+            // Bundles are handled immediately, timetags are not supported
+            OscPacket::Bundle(bundle) => {
+                for packet in bundle.content {
+                    self.handle_packet(packet, client);
+                }
+            }
+        }
+    }
+
+    fn handle_message(&mut self, message: OscMessage, client: SocketAddr) {
+        if message.addr == "/params/get" {
+            for param_idx in 0..self.params.len() {
+                self.send_value(param_idx, client);
+            }
+            return;
+        }
+
+        let Some(path) = message.addr.strip_prefix("/param/") else {
+            nih_trace!(
+                "Ignoring OSC message with unknown address '{}'",
+                message.addr
+            );
+            return;
+        };
+        let (param_id, command) = match path.rsplit_once('/') {
+            Some((param_id, command @ ("begin" | "end" | "get"))) => (param_id, Some(command)),
+            _ => (path, None),
+        };
+        let Some(&param_idx) = self.param_indices.get(param_id) else {
+            nih_trace!("Ignoring OSC message for unknown parameter '{param_id}'");
+            return;
+        };
+        let param_ptr = self.params[param_idx].1;
+
+        match command {
+            Some("begin") => {
+                if self.active_gestures.insert(param_idx) {
+                    unsafe { self.context.raw_begin_set_parameter(param_ptr) };
+                }
+            }
+            Some("end") => {
+                if self.active_gestures.remove(&param_idx) {
+                    unsafe { self.context.raw_end_set_parameter(param_ptr) };
+                }
+            }
+            Some(_) => self.send_value(param_idx, client),
+            None => {
+                let Some(normalized_value) = message.args.first().and_then(normalized_arg) else {
+                    nih_trace!("Ignoring OSC message for '{param_id}' without a numeric value");
+                    return;
+                };
+
+                if self.active_gestures.contains(&param_idx) {
+                    unsafe {
+                        self.context
+                            .raw_set_parameter_normalized(param_ptr, normalized_value)
+                    };
+                } else {
+                    unsafe {
+                        self.context.raw_begin_set_parameter(param_ptr);
+                        self.context
+                            .raw_set_parameter_normalized(param_ptr, normalized_value);
+                        self.context.raw_end_set_parameter(param_ptr);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Send all parameter changes reported by the listener since the last poll to all clients.
+    fn send_changes(&self) {
+        while let Some((param_idx, normalized_value)) = self.changes.pop() {
+            for client in self.clients.iter() {
+                self.send(param_idx, normalized_value, client);
+            }
+        }
+    }
+
+    /// Send a parameter's current value to a single client.
+    fn send_value(&self, param_idx: usize, client: SocketAddr) {
+        let normalized_value = unsafe { self.params[param_idx].1.modulated_normalized_value() };
+        self.send(param_idx, normalized_value, client);
+    }
+
+    fn send(&self, param_idx: usize, normalized_value: f32, client: SocketAddr) {
+        let packet = OscPacket::Message(OscMessage {
+            addr: format!("/param/{}", self.params[param_idx].0),
+            args: vec![OscType::Float(normalized_value)],
+        });
+
+        let result = rosc::encoder::encode(&packet)
+            .map_err(|err| anyhow::anyhow!("{err:?}"))
+            .and_then(|data| Ok(self.socket.send_to(&data, client)?));
+        if let Err(err) = result {
+            nih_trace!("Could not send OSC message to {client}: {err}");
+        }
+    }
+}
+
+/// The clients that receive parameter changes, along with the last time they sent a message.
+#[derive(Debug, Default)]
+struct Clients {
+    last_seen: HashMap<SocketAddr, Instant>,
+}
+
+impl Clients {
+    /// Record a message from `client`. Returns `false` if this is a new client and there are
+    /// already [`MAX_CLIENTS`] clients, in which case the client is not added.
+    fn register(&mut self, client: SocketAddr, now: Instant) -> bool {
+        if let Some(last_seen) = self.last_seen.get_mut(&client) {
+            *last_seen = now;
+            return true;
+        }
+
+        self.expire(now);
+        if self.last_seen.len() >= MAX_CLIENTS {
+            return false;
+        }
+
+        self.last_seen.insert(client, now);
+        true
+    }
+
+    /// Remove the clients that haven't sent a message within [`CLIENT_TIMEOUT`].
+    fn expire(&mut self, now: Instant) {
+        self.last_seen
+            .retain(|_, last_seen| now.duration_since(*last_seen) < CLIENT_TIMEOUT);
+    }
+
+    fn iter(&self) -> impl Iterator<Item = SocketAddr> + '_ {
+        self.last_seen.keys().copied()
+    }
+}
+
+/// Convert an OSC argument to a normalized parameter value. Returns `None` for non-numeric and
+/// non-finite values.
+fn normalized_arg(arg: &OscType) -> Option<f32> {
+    let value = match arg {
+        OscType::Float(value) => *value as f64,
+        OscType::Double(value) => *value,
+        OscType::Int(value) => *value as f64,
+        OscType::Long(value) => *value as f64,
+        OscType::Bool(value) => {
+            if *value {
+                1.0
+            } else {
+                0.0
+            }
+        }
+        _ => return None,
+    };
+
+    if value.is_finite() {
+        Some(value.clamp(0.0, 1.0) as f32)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    use crate::prelude::*;
+    use crate::test::fixtures::{TestParams, TestPlugin};
+    use crate::test::MockGuiContext;
+
+    fn send_message(socket: &UdpSocket, bridge: &OscBridge, addr: &str, args: Vec<OscType>) {
+        let packet = OscPacket::Message(OscMessage {
+            addr: String::from(addr),
+            args,
+        });
+        socket
+            .send_to(
+                &rosc::encoder::encode(&packet).unwrap(),
+                bridge.local_addr(),
+            )
+            .unwrap();
+    }
+
+    fn receive_message(socket: &UdpSocket) -> OscMessage {
+        let mut buffer = [0u8; rosc::decoder::MTU];
+        let size = socket.recv(&mut buffer).unwrap();
+        match rosc::decoder::decode_udp(&buffer[..size]).unwrap().1 {
+            OscPacket::Message(message) => message,
+            packet => panic!("Unexpected packet: {packet:?}"),
+        }
+    }
+
+    #[test]
+    fn set_and_get_parameter() {
+        let params = Arc::new(TestParams::default());
+        let context = Arc::new(MockGuiContext::<TestPlugin>::new(params.clone()));
+        let bridge = OscBridge::spawn((Ipv4Addr::LOCALHOST, 0), params.clone(), context).unwrap();
+
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        // Messages are handled in order, so the response to the `get` message includes the values
+        // set by the messages before it. The non-finite value should be ignored.
+        send_message(&socket, &bridge, "/param/gain", vec![OscType::Float(0.25)]);
+        send_message(
+            &socket,
+            &bridge,
+            "/param/gain",
+            vec![OscType::Float(f32::NAN)],
+        );
+        send_message(&socket, &bridge, "/param/gain/get", Vec::new());
+
+        loop {
+            let message = receive_message(&socket);
+            assert_eq!(message.addr, "/param/gain");
+            if message.args == [OscType::Float(0.25)] {
+                break;
+            }
+        }
+        assert_eq!(params.gain.unmodulated_normalized_value(), 0.25);
+    }
+
+    #[test]
+    fn normalized_args() {
+        assert_eq!(normalized_arg(&OscType::Float(0.5)), Some(0.5));
+        assert_eq!(normalized_arg(&OscType::Double(2.0)), Some(1.0));
+        assert_eq!(normalized_arg(&OscType::Int(-1)), Some(0.0));
+        assert_eq!(normalized_arg(&OscType::Bool(true)), Some(1.0));
+        assert_eq!(normalized_arg(&OscType::Float(f32::NAN)), None);
+        assert_eq!(normalized_arg(&OscType::Float(f32::INFINITY)), None);
+        assert_eq!(normalized_arg(&OscType::Double(f64::NEG_INFINITY)), None);
+        assert_eq!(normalized_arg(&OscType::String(String::from("0.5"))), None);
+    }
+
+    #[test]
+    fn clients_are_limited_and_expire() {
+        let now = Instant::now();
+        let client = |port| SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+        let mut clients = Clients::default();
+        for port in 0..MAX_CLIENTS as u16 {
+            assert!(clients.register(client(port), now));
+        }
+        assert!(!clients.register(client(1000), now));
+        // Existing clients can still refresh their timestamps
+        assert!(clients.register(client(0), now + CLIENT_TIMEOUT / 2));
+
+        let later = now + CLIENT_TIMEOUT;
+        assert!(clients.register(client(1000), later));
+        assert_eq!(clients.iter().count(), 2);
+
+        clients.expire(later + CLIENT_TIMEOUT);
+        assert_eq!(clients.iter().count(), 0);
+    }
+}
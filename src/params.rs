@@ -5,10 +5,11 @@
 //! information.
 
 use std::collections::BTreeMap;
-use std::fmt::{Debug, Display};
+use std::fmt::{self, Debug, Display};
 use std::sync::Arc;
 
-use self::internals::ParamPtr;
+use self::internals::{ParamListeners, ParamPtr};
+use crate::context::gui::ParamSetter;
 
 // The proc-macro for deriving `Params`
 pub use nih_plug_derive::Params;
@@ -20,6 +21,8 @@ mod float;
 mod integer;
 
 pub mod internals;
+pub mod macros;
+pub mod morph;
 pub mod persist;
 pub mod range;
 pub mod smoothing;
@@ -50,6 +53,9 @@ bitflags::bitflags! {
         /// Don't show this parameter when generating a generic UI for the plugin using one of
         /// NIH-plug's generic UI widgets.
         const HIDE_IN_GENERIC_UI = 1 << 3;
+        /// Leave this parameter alone when randomizing the plugin's parameters using
+        /// [`Params::randomize()`]. Useful for things like output gain or oversampling settings.
+        const NO_RANDOMIZE = 1 << 4;
     }
 }
 
@@ -162,7 +168,25 @@ pub trait Param: Display + Debug + sealed::Sealed {
     /// Get the string representation for a normalized value. Used as part of the wrappers. Most
     /// plugin formats already have support for units, in which case it shouldn't be part of this
     /// string or some DAWs may show duplicate units.
-    fn normalized_value_to_string(&self, normalized: f32, include_unit: bool) -> String;
+    fn normalized_value_to_string(&self, normalized: f32, include_unit: bool) -> String {
+        let mut string = String::new();
+        // Writing to a `String` cannot fail
+        let _ = self.normalized_value_to_buf(normalized, include_unit, &mut string);
+
+        string
+    }
+
+    /// The same as [`normalized_value_to_string()`][Self::normalized_value_to_string()], but
+    /// writes the string representation to `buf` instead. When `buf` is a fixed size buffer this
+    /// does not allocate, unless the parameter uses a custom `value_to_string` function since
+    /// those return a `String`. The wrappers use this to write parameter values directly to the
+    /// host's buffers.
+    fn normalized_value_to_buf(
+        &self,
+        normalized: f32,
+        include_unit: bool,
+        buf: &mut dyn fmt::Write,
+    ) -> fmt::Result;
 
     /// Get the string representation for a normalized value. Used as part of the wrappers.
     fn string_to_normalized_value(&self, string: &str) -> Option<f32>;
@@ -230,8 +254,16 @@ pub(crate) trait ParamMut: Param {
     /// restoring a plugin so everything is in sync. In that case the smoother should completely
     /// reset to the current value.
     fn update_smoother(&self, sample_rate: f32, reset: bool);
+
+    /// The listeners registered for this parameter through [`Params::add_param_listener()`].
+    fn listeners(&self) -> &ParamListeners;
 }
 
+/// A function that's called whenever a parameter's value changes. The arguments passed to this
+/// function are the parameter's ID and its new **normalized** value. See
+/// [`Params::add_param_listener()`].
+pub type ParamListener = Arc<dyn Fn(&str, f32) + Send + Sync>;
+
 /// Describes a struct containing parameters and other persistent fields.
 ///
 /// # Deriving `Params` and `#[id = "stable"]`
@@ -249,6 +281,13 @@ pub(crate) trait ParamMut: Param {
 /// with the `#[persist = "key"]` attribute containing types that can be serialized and deserialized
 /// with [Serde](https://serde.rs/).
 ///
+/// ## `#[persist_streamed = "key"]`
+///
+/// Large fields like sample data can instead be annotated with `#[persist_streamed = "key"]`. These
+/// fields should implement [`StreamedPersistentField`][persist::StreamedPersistentField], which
+/// writes its data directly to the host's stream in chunks and loads it on a background thread.
+/// [`StreamedBytes`][persist::StreamedBytes] can be used for plain byte buffers.
+///
 /// ## `#[nested]`, `#[nested(group_name = "group name")]`
 ///
 /// Finally, the `Params` object may include parameters from other objects. Setting a group name is
@@ -258,6 +297,14 @@ pub(crate) trait ParamMut: Param {
 ///
 /// Take a look at the example gain example plugin to see how this is used.
 ///
+/// ## Listening to parameter changes
+///
+/// Every `Params` object, including derived ones, can notify other parts of the plugin when a
+/// parameter changes through [`add_param_listener()`][Self::add_param_listener()] and
+/// [`add_param_listener_for()`][Self::add_param_listener_for()]. This is useful for things like
+/// GUI bindings or OSC bridges that need to react to changes for any parameter by its ID, without
+/// having to add callbacks to every parameter by hand.
+///
 /// ## `#[nested(id_prefix = "foo", group_name = "Foo")]`
 ///
 /// Adding this attribute to a `Params` sub-object works similarly to the regular `#[nested]`
@@ -311,6 +358,94 @@ pub unsafe trait Params: 'static + Send + Sync {
     /// [`persist::deserialize_field()`] under the hood.
     #[allow(unused_variables)]
     fn deserialize_fields(&self, serialized: &BTreeMap<String, String>) {}
+
+    /// All fields marked with `#[persist_streamed = "stable_name"]`, along with their keys. These
+    /// are written to and restored from the plugin's state separately from the fields returned by
+    /// [`serialize_fields()`][Self::serialize_fields()]. See
+    /// [`persist::StreamedPersistentField`].
+    fn streamed_fields(&self) -> Vec<(String, &dyn persist::StreamedPersistentField)> {
+        Vec::new()
+    }
+
+    /// Call `listener` whenever any of this object's parameters changes, including the parameters
+    /// in nested `Params` objects. The listener receives the parameter's ID and its new normalized
+    /// value. Like the callbacks set using `with_callback()`, this should not do anything expensive
+    /// as it can be called from both the GUI and the audio thread.
+    ///
+    /// Registering a listener allocates, so this should not be called from the audio thread.
+    /// Parameter changes that happen while a listener is being added or removed are not passed to
+    /// any listeners, to avoid blocking the audio thread.
+    fn add_param_listener(&self, listener: ParamListener) {
+        for (param_id, param_ptr, _) in self.param_map() {
+            unsafe { param_ptr.listeners() }.add(param_id, listener.clone());
+        }
+    }
+
+    /// The same as [`add_param_listener()`][Self::add_param_listener()], but only for the parameter
+    /// with ID `param_id`. Returns `false` if no parameter with that ID exists.
+    fn add_param_listener_for(&self, param_id: &str, listener: ParamListener) -> bool {
+        match self
+            .param_map()
+            .into_iter()
+            .find(|(id, _, _)| id == param_id)
+        {
+            Some((param_id, param_ptr, _)) => {
+                unsafe { param_ptr.listeners() }.add(param_id, listener);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stop calling a listener that was previously added with
+    /// [`add_param_listener()`][Self::add_param_listener()] or
+    /// [`add_param_listener_for()`][Self::add_param_listener_for()]. Listeners are compared by
+    /// their pointers, so this needs to be passed a clone of the same `Arc`.
+    fn remove_param_listener(&self, listener: &ParamListener) {
+        for (_, param_ptr, _) in self.param_map() {
+            unsafe { param_ptr.listeners() }.remove(listener);
+        }
+    }
+
+    /// Randomize this object's parameters, including the parameters in nested `Params` objects.
+    /// This is meant to be called from the editor, for instance from a "randomize patch" button.
+    /// `rng` should return uniformly distributed random numbers in `[0, 1]`, so any random number
+    /// generator can be used, e.g. `&mut || rng.gen()` with the `rand` crate. `amount` in `[0, 1]`
+    /// controls how far every parameter moves from its current value towards a random value, so
+    /// 1.0 completely randomizes the parameters and smaller values mutate the current patch
+    /// instead.
+    ///
+    /// The parameters are changed through `setter` as a single group edit, so the host can undo
+    /// the change in one step. Bypass parameters, hidden parameters, and parameters marked with
+    /// [`ParamFlags::NO_RANDOMIZE`] are left alone.
+    fn randomize(&self, setter: &ParamSetter, rng: &mut dyn FnMut() -> f32, amount: f32) {
+        let params: Vec<ParamPtr> = self
+            .param_map()
+            .into_iter()
+            .map(|(_, param_ptr, _)| param_ptr)
+            .filter(|param_ptr| {
+                !unsafe { param_ptr.flags() }
+                    .intersects(ParamFlags::BYPASS | ParamFlags::HIDDEN | ParamFlags::NO_RANDOMIZE)
+            })
+            .collect();
+        let amount = amount.clamp(0.0, 1.0);
+
+        setter.begin_set_parameters(&params);
+        for param_ptr in &params {
+            unsafe {
+                let current_value = param_ptr.unmodulated_normalized_value();
+                let random_value = rng().clamp(0.0, 1.0);
+                let new_value = current_value + ((random_value - current_value) * amount);
+
+                // This snaps the value to the parameter's steps
+                let new_value = param_ptr.preview_normalized(param_ptr.preview_plain(new_value));
+                setter
+                    .raw_context
+                    .raw_set_parameter_normalized(*param_ptr, new_value);
+            }
+        }
+        setter.end_set_parameters(&params);
+    }
 }
 
 /// This may be useful when building generic UIs using nested `Params` objects.
@@ -326,4 +461,72 @@ unsafe impl<P: Params> Params for Arc<P> {
     fn deserialize_fields(&self, serialized: &BTreeMap<String, String>) {
         self.as_ref().deserialize_fields(serialized)
     }
+
+    fn streamed_fields(&self) -> Vec<(String, &dyn persist::StreamedPersistentField)> {
+        self.as_ref().streamed_fields()
+    }
+
+    fn add_param_listener(&self, listener: ParamListener) {
+        self.as_ref().add_param_listener(listener)
+    }
+
+    fn add_param_listener_for(&self, param_id: &str, listener: ParamListener) -> bool {
+        self.as_ref().add_param_listener_for(param_id, listener)
+    }
+
+    fn remove_param_listener(&self, listener: &ParamListener) {
+        self.as_ref().remove_param_listener(listener)
+    }
+
+    fn randomize(&self, setter: &ParamSetter, rng: &mut dyn FnMut() -> f32, amount: f32) {
+        self.as_ref().randomize(setter, rng, amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use crate::test::fixtures::{TestParams, TestPlugin};
+    use crate::test::{GuiContextCall, MockGuiContext};
+
+    #[test]
+    fn randomize_params() {
+        let params = Arc::new(TestParams::default());
+        let context = MockGuiContext::<TestPlugin>::new(params.clone());
+        let setter = ParamSetter::new(&context);
+
+        // Half of the way from the current value towards the random value
+        params.randomize(&setter, &mut || 0.0, 0.5);
+        assert_eq!(params.gain.value(), 0.5);
+        assert_eq!(params.mode.value(), 0);
+        // All changes are made in a single group edit, and the bypass parameter is left alone
+        assert_eq!(
+            context.take_calls(),
+            [
+                GuiContextCall::BeginGroupEdit,
+                GuiContextCall::BeginSetParameter(String::from("gain")),
+                GuiContextCall::BeginSetParameter(String::from("mode")),
+                GuiContextCall::SetParameterNormalized(String::from("gain"), 0.5),
+                GuiContextCall::SetParameterNormalized(String::from("mode"), 0.0),
+                GuiContextCall::EndSetParameter(String::from("gain")),
+                GuiContextCall::EndSetParameter(String::from("mode")),
+                GuiContextCall::EndGroupEdit,
+            ]
+        );
+
+        params.randomize(&setter, &mut || 1.0, 1.0);
+        assert_eq!(params.gain.value(), 1.0);
+        assert_eq!(params.mode.value(), 3);
+        assert!(!params.bypass.value());
+
+        let params = Arc::new(TestParams {
+            gain: FloatParam::new("Gain", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .no_randomize(),
+            ..TestParams::default()
+        });
+        let context = MockGuiContext::<TestPlugin>::new(params.clone());
+        params.randomize(&ParamSetter::new(&context), &mut || 0.0, 1.0);
+        assert_eq!(params.gain.value(), 1.0);
+    }
 }
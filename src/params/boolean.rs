@@ -1,11 +1,11 @@
 //! Simple boolean parameters.
 
 use atomic_float::AtomicF32;
-use std::fmt::{Debug, Display};
+use std::fmt::{self, Debug, Display};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-use super::internals::ParamPtr;
+use super::internals::{ParamListeners, ParamPtr};
 use super::{Param, ParamFlags, ParamMut};
 
 /// A simple boolean parameter.
@@ -34,6 +34,8 @@ pub struct BoolParam {
     /// multiple times in rapid succession, and it can be run from both the GUI and the audio
     /// thread.
     value_changed: Option<Arc<dyn Fn(bool) + Send + Sync>>,
+    /// Listeners registered through [`Params::add_param_listener()`][super::Params::add_param_listener()].
+    listeners: ParamListeners,
 
     /// The parameter's human readable display name.
     name: String,
@@ -127,12 +129,17 @@ impl Param for BoolParam {
         true
     }
 
-    fn normalized_value_to_string(&self, normalized: f32, _include_unit: bool) -> String {
+    fn normalized_value_to_buf(
+        &self,
+        normalized: f32,
+        _include_unit: bool,
+        buf: &mut dyn fmt::Write,
+    ) -> fmt::Result {
         let value = self.preview_plain(normalized);
         match (value, &self.value_to_string) {
-            (v, Some(f)) => f(v),
-            (true, None) => String::from("On"),
-            (false, None) => String::from("Off"),
+            (v, Some(f)) => buf.write_str(&f(v)),
+            (true, None) => buf.write_str("On"),
+            (false, None) => buf.write_str("Off"),
         }
     }
 
@@ -198,6 +205,7 @@ impl ParamMut for BoolParam {
             if let Some(f) = &self.value_changed {
                 f(value);
             }
+            self.listeners.notify(normalized_value);
 
             true
         } else {
@@ -224,6 +232,10 @@ impl ParamMut for BoolParam {
     fn update_smoother(&self, _sample_rate: f32, _init: bool) {
         // Can't really smooth a binary parameter now can you
     }
+
+    fn listeners(&self) -> &ParamListeners {
+        &self.listeners
+    }
 }
 
 impl BoolParam {
@@ -240,6 +252,7 @@ impl BoolParam {
 
             flags: ParamFlags::default(),
             value_changed: None,
+            listeners: ParamListeners::default(),
 
             name: name.into(),
             poly_modulation_id: None,
@@ -304,7 +317,8 @@ impl BoolParam {
     /// Mark this parameter as a bypass parameter. Plugin hosts can integrate this parameter into
     /// their UI. Only a single [`BoolParam`] can be a bypass parameter, and NIH-plug will add one
     /// if you don't create one yourself. You will need to implement this yourself if your plugin
-    /// introduces latency.
+    /// introduces latency, unless you let the wrapper crossfade to the latency compensated dry
+    /// signal using [`Plugin::BYPASS_CROSSFADE_MS`][crate::prelude::Plugin::BYPASS_CROSSFADE_MS].
     pub fn make_bypass(mut self) -> Self {
         self.flags.insert(ParamFlags::BYPASS);
         self
@@ -332,4 +346,11 @@ impl BoolParam {
         self.flags.insert(ParamFlags::HIDE_IN_GENERIC_UI);
         self
     }
+
+    /// Don't change this parameter when randomizing the plugin's parameters using
+    /// [`Params::randomize()`][crate::prelude::Params::randomize()].
+    pub fn no_randomize(mut self) -> Self {
+        self.flags.insert(ParamFlags::NO_RANDOMIZE);
+        self
+    }
 }
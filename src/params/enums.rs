@@ -1,10 +1,10 @@
 //! Enum parameters. `enum` is a keyword, so `enums` it is.
 
-use std::fmt::{Debug, Display};
+use std::fmt::{self, Debug, Display};
 use std::marker::PhantomData;
 use std::sync::Arc;
 
-use super::internals::ParamPtr;
+use super::internals::{ParamListeners, ParamPtr};
 use super::range::IntRange;
 use super::{IntParam, Param, ParamFlags, ParamMut};
 
@@ -182,9 +182,14 @@ impl<T: Enum + PartialEq> Param for EnumParam<T> {
         T::from_index(self.inner.next_step(T::to_index(from) as i32, finer) as usize)
     }
 
-    fn normalized_value_to_string(&self, normalized: f32, include_unit: bool) -> String {
+    fn normalized_value_to_buf(
+        &self,
+        normalized: f32,
+        include_unit: bool,
+        buf: &mut dyn fmt::Write,
+    ) -> fmt::Result {
         self.inner
-            .normalized_value_to_string(normalized, include_unit)
+            .normalized_value_to_buf(normalized, include_unit, buf)
     }
 
     fn string_to_normalized_value(&self, string: &str) -> Option<f32> {
@@ -265,9 +270,14 @@ impl Param for EnumParamInner {
         self.inner.next_step(from, finer)
     }
 
-    fn normalized_value_to_string(&self, normalized: f32, _include_unit: bool) -> String {
+    fn normalized_value_to_buf(
+        &self,
+        normalized: f32,
+        _include_unit: bool,
+        buf: &mut dyn fmt::Write,
+    ) -> fmt::Result {
         let index = self.preview_plain(normalized);
-        self.variants[index as usize].to_string()
+        buf.write_str(self.variants[index as usize])
     }
 
     fn string_to_normalized_value(&self, string: &str) -> Option<f32> {
@@ -313,6 +323,10 @@ impl<T: Enum + PartialEq> ParamMut for EnumParam<T> {
     fn update_smoother(&self, sample_rate: f32, reset: bool) {
         self.inner.update_smoother(sample_rate, reset)
     }
+
+    fn listeners(&self) -> &ParamListeners {
+        self.inner.listeners()
+    }
 }
 
 impl ParamMut for EnumParamInner {
@@ -331,6 +345,10 @@ impl ParamMut for EnumParamInner {
     fn update_smoother(&self, sample_rate: f32, reset: bool) {
         self.inner.update_smoother(sample_rate, reset)
     }
+
+    fn listeners(&self) -> &ParamListeners {
+        self.inner.listeners()
+    }
 }
 
 impl<T: Enum + PartialEq + 'static> EnumParam<T> {
@@ -413,6 +431,13 @@ impl<T: Enum + PartialEq + 'static> EnumParam<T> {
         self.inner.inner = self.inner.inner.hide_in_generic_ui();
         self
     }
+
+    /// Don't change this parameter when randomizing the plugin's parameters using
+    /// [`Params::randomize()`][crate::prelude::Params::randomize()].
+    pub fn no_randomize(mut self) -> Self {
+        self.inner.inner = self.inner.inner.no_randomize();
+        self
+    }
 }
 
 impl EnumParamInner {
@@ -1,11 +1,12 @@
 //! Continuous (or discrete, with a step size) floating point parameters.
 
 use atomic_float::AtomicF32;
-use std::fmt::{Debug, Display};
+use atomic_refcell::AtomicRefCell;
+use std::fmt::{self, Debug, Display};
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
-use super::internals::ParamPtr;
+use super::internals::{ParamListeners, ParamPtr};
 use super::range::FloatRange;
 use super::smoothing::{Smoother, SmoothingStyle};
 use super::{Param, ParamFlags, ParamMut};
@@ -44,6 +45,8 @@ pub struct FloatParam {
     ///
     /// TODO: We probably also want to pass the old value to this function.
     value_changed: Option<Arc<dyn Fn(f32) + Send + Sync>>,
+    /// Listeners registered through [`Params::add_param_listener()`][super::Params::add_param_listener()].
+    listeners: ParamListeners,
 
     /// The distribution of the parameter's values.
     range: FloatRange,
@@ -62,6 +65,13 @@ pub struct FloatParam {
     /// [`NoteEvent::PolyModulation`][crate::prelude::NoteEvent::PolyModulation] events to the
     /// correct parameter by pattern matching on a constant.
     poly_modulation_id: Option<u32>,
+    /// The auxiliary input port and the channel within that port that provides audio-rate
+    /// modulation for this parameter, if any. See [`with_cv_input()`][Self::with_cv_input()].
+    cv_input: Option<(usize, usize)>,
+    /// The CV signal for the current process call in normalized units. The wrapper copies this from
+    /// the auxiliary input before calling the plugin's process function. Only allocated if
+    /// `cv_input` is set.
+    cv: AtomicRefCell<Vec<f32>>,
     /// Optional custom conversion function from a plain **unnormalized** value to a string.
     value_to_string: Option<Arc<dyn Fn(f32) -> String + Send + Sync>>,
     /// Optional custom conversion function from a string to a plain **unnormalized** value. If the
@@ -152,22 +162,27 @@ impl Param for FloatParam {
         self.range.next_step(from, self.step_size, finer)
     }
 
-    fn normalized_value_to_string(&self, normalized: f32, include_unit: bool) -> String {
+    fn normalized_value_to_buf(
+        &self,
+        normalized: f32,
+        include_unit: bool,
+        buf: &mut dyn fmt::Write,
+    ) -> fmt::Result {
         let value = self.preview_plain(normalized);
-        match (&self.value_to_string, &self.step_size, include_unit) {
-            (Some(f), _, true) => format!("{}{}", f(value), self.unit),
-            (Some(f), _, false) => f(value),
-            (None, Some(step_size), true) => {
-                let num_digits = decimals_from_step_size(*step_size);
-                format!("{:.num_digits$}{}", value, self.unit)
-            }
-            (None, Some(step_size), false) => {
+        match (&self.value_to_string, &self.step_size) {
+            (Some(f), _) => buf.write_str(&f(value))?,
+            (None, Some(step_size)) => {
                 let num_digits = decimals_from_step_size(*step_size);
-                format!("{value:.num_digits$}")
+                write!(buf, "{value:.num_digits$}")?
             }
-            (None, None, true) => format!("{}{}", value, self.unit),
-            (None, None, false) => format!("{value}"),
+            (None, None) => write!(buf, "{value}")?,
+        }
+
+        if include_unit {
+            buf.write_str(self.unit)?;
         }
+
+        Ok(())
     }
 
     fn string_to_normalized_value(&self, string: &str) -> Option<f32> {
@@ -232,6 +247,7 @@ impl ParamMut for FloatParam {
             if let Some(f) = &self.value_changed {
                 f(value);
             }
+            self.listeners.notify(normalized_value);
 
             true
         } else {
@@ -263,6 +279,10 @@ impl ParamMut for FloatParam {
                 .set_target(sample_rate, self.modulated_plain_value());
         }
     }
+
+    fn listeners(&self) -> &ParamListeners {
+        &self.listeners
+    }
 }
 
 impl FloatParam {
@@ -282,17 +302,98 @@ impl FloatParam {
 
             flags: ParamFlags::default(),
             value_changed: None,
+            listeners: ParamListeners::default(),
 
             range,
             step_size: None,
             name: name.into(),
             unit: "",
             poly_modulation_id: None,
+            cv_input: None,
+            cv: AtomicRefCell::new(Vec::new()),
             value_to_string: None,
             string_to_value: None,
         }
     }
 
+    /// The auxiliary input port and channel set with [`with_cv_input()`][Self::with_cv_input()].
+    pub fn cv_input(&self) -> Option<(usize, usize)> {
+        self.cv_input
+    }
+
+    /// The CV signal's normalized offset at a sample index in the current buffer. Always zero if
+    /// the parameter does not have a CV input.
+    #[inline]
+    pub fn cv_offset(&self, sample_idx: usize) -> f32 {
+        if self.cv_input.is_none() {
+            return 0.0;
+        }
+
+        self.cv.borrow().get(sample_idx).copied().unwrap_or(0.0)
+    }
+
+    /// Get the next value from the parameter's smoother with the CV input's value at `sample_idx`
+    /// applied to it. Use this instead of `param.smoothed.next()` for parameters with a
+    /// [CV input][Self::with_cv_input()].
+    #[inline]
+    pub fn next_with_cv(&self, sample_idx: usize) -> f32 {
+        self.apply_cv(self.smoothed.next(), self.cv_offset(sample_idx))
+    }
+
+    /// The same as [`Smoother::next_block()`], but with the CV input's signal applied to the
+    /// smoothed values. `block_start` is the block's offset within the current buffer, as yielded
+    /// by [`Buffer::iter_blocks()`][crate::prelude::Buffer::iter_blocks()].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `block_len > block_values.len()`.
+    pub fn next_block_with_cv(
+        &self,
+        block_values: &mut [f32],
+        block_start: usize,
+        block_len: usize,
+    ) {
+        let block_values = &mut block_values[..block_len];
+        self.smoothed.next_block_exact(block_values);
+        if self.cv_input.is_none() {
+            return;
+        }
+
+        let cv = self.cv.borrow();
+        for (sample_idx, value) in (block_start..).zip(block_values.iter_mut()) {
+            *value = self.apply_cv(*value, cv.get(sample_idx).copied().unwrap_or(0.0));
+        }
+    }
+
+    /// Preallocate the CV buffer. Called by the wrapper when the plugin gets initialized.
+    pub(crate) fn allocate_cv(&self, max_buffer_size: usize) {
+        if self.cv_input.is_some() {
+            let mut cv = self.cv.borrow_mut();
+            cv.clear();
+            cv.reserve_exact(max_buffer_size);
+        }
+    }
+
+    /// Replace the CV signal with `samples` from the auxiliary input, or with `num_samples` samples
+    /// of silence if the input does not exist. Called by the wrapper before the plugin's process
+    /// function. This does not allocate as long as the buffer fits in the preallocated space.
+    pub(crate) fn set_cv(&self, samples: Option<&[f32]>, num_samples: usize) {
+        let mut cv = self.cv.borrow_mut();
+        cv.clear();
+        match samples {
+            Some(samples) => cv.extend_from_slice(&samples[..num_samples.min(samples.len())]),
+            None => cv.resize(num_samples, 0.0),
+        }
+    }
+
+    fn apply_cv(&self, plain: f32, cv_offset: f32) -> f32 {
+        if cv_offset == 0.0 {
+            plain
+        } else {
+            self.preview_plain((self.preview_normalized(plain) + cv_offset).clamp(0.0, 1.0))
+        }
+    }
+
     /// The field's current plain value, after monophonic modulation has been applied. Equivalent to
     /// calling `param.plain_value()`.
     #[inline]
@@ -323,6 +424,23 @@ impl FloatParam {
         self
     }
 
+    /// Accept audio-rate modulation for this parameter from channel `channel_idx` of auxiliary
+    /// input port `aux_input_idx`, like a CV input on a modular synthesizer. The plugin needs to
+    /// declare that port in its
+    /// [`AudioIOLayout::aux_input_ports`][crate::prelude::AudioIOLayout::aux_input_ports]. The
+    /// input's samples are offsets to the parameter's normalized value, so a signal of 1.0 moves the
+    /// parameter across its entire range. If the port or the channel does not exist for the current
+    /// audio IO layout, then the CV signal is silent.
+    ///
+    /// The CV signal is only applied when the plugin reads the parameter's values through
+    /// [`next_with_cv()`][Self::next_with_cv()] or
+    /// [`next_block_with_cv()`][Self::next_block_with_cv()]. The parameter's value as seen by the
+    /// host and the editor does not include it.
+    pub fn with_cv_input(mut self, aux_input_idx: usize, channel_idx: usize) -> Self {
+        self.cv_input = Some((aux_input_idx, channel_idx));
+        self
+    }
+
     /// Set up a smoother that can gradually interpolate changes made to this parameter, preventing
     /// clicks and zipper noises.
     pub fn with_smoother(mut self, style: SmoothingStyle) -> Self {
@@ -418,6 +536,13 @@ impl FloatParam {
         self.flags.insert(ParamFlags::HIDE_IN_GENERIC_UI);
         self
     }
+
+    /// Don't change this parameter when randomizing the plugin's parameters using
+    /// [`Params::randomize()`][crate::prelude::Params::randomize()].
+    pub fn no_randomize(mut self) -> Self {
+        self.flags.insert(ParamFlags::NO_RANDOMIZE);
+        self
+    }
 }
 
 /// Calculate how many decimals to round to when displaying a floating point value with a specific
@@ -1,11 +1,11 @@
 //! Stepped integer parameters.
 
 use atomic_float::AtomicF32;
-use std::fmt::{Debug, Display};
+use std::fmt::{self, Debug, Display};
 use std::sync::atomic::{AtomicI32, Ordering};
 use std::sync::Arc;
 
-use super::internals::ParamPtr;
+use super::internals::{ParamListeners, ParamPtr};
 use super::range::IntRange;
 use super::smoothing::{Smoother, SmoothingStyle};
 use super::{Param, ParamFlags, ParamMut};
@@ -44,6 +44,8 @@ pub struct IntParam {
     ///
     /// TODO: We probably also want to pass the old value to this function.
     value_changed: Option<Arc<dyn Fn(i32) + Send + Sync>>,
+    /// Listeners registered through [`Params::add_param_listener()`][super::Params::add_param_listener()].
+    listeners: ParamListeners,
 
     /// The distribution of the parameter's values.
     range: IntRange,
@@ -144,14 +146,23 @@ impl Param for IntParam {
         self.range.next_step(from)
     }
 
-    fn normalized_value_to_string(&self, normalized: f32, include_unit: bool) -> String {
+    fn normalized_value_to_buf(
+        &self,
+        normalized: f32,
+        include_unit: bool,
+        buf: &mut dyn fmt::Write,
+    ) -> fmt::Result {
         let value = self.preview_plain(normalized);
-        match (&self.value_to_string, include_unit) {
-            (Some(f), true) => format!("{}{}", f(value), self.unit),
-            (Some(f), false) => f(value),
-            (None, true) => format!("{}{}", value, self.unit),
-            (None, false) => format!("{value}"),
+        match &self.value_to_string {
+            Some(f) => buf.write_str(&f(value))?,
+            None => write!(buf, "{value}")?,
+        }
+
+        if include_unit {
+            buf.write_str(self.unit)?;
         }
+
+        Ok(())
     }
 
     fn string_to_normalized_value(&self, string: &str) -> Option<f32> {
@@ -212,6 +223,7 @@ impl ParamMut for IntParam {
             if let Some(f) = &self.value_changed {
                 f(value);
             }
+            self.listeners.notify(normalized_value);
 
             true
         } else {
@@ -243,6 +255,10 @@ impl ParamMut for IntParam {
                 .set_target(sample_rate, self.modulated_plain_value());
         }
     }
+
+    fn listeners(&self) -> &ParamListeners {
+        &self.listeners
+    }
 }
 
 impl IntParam {
@@ -262,6 +278,7 @@ impl IntParam {
 
             flags: ParamFlags::default(),
             value_changed: None,
+            listeners: ParamListeners::default(),
 
             range,
             name: name.into(),
@@ -387,4 +404,11 @@ impl IntParam {
         self.flags.insert(ParamFlags::HIDE_IN_GENERIC_UI);
         self
     }
+
+    /// Don't change this parameter when randomizing the plugin's parameters using
+    /// [`Params::randomize()`][crate::prelude::Params::randomize()].
+    pub fn no_randomize(mut self) -> Self {
+        self.flags.insert(ParamFlags::NO_RANDOMIZE);
+        self
+    }
 }
@@ -1,6 +1,10 @@
 //! Implementation details for the parameter management.
 
-use super::{Param, ParamFlags, ParamMut};
+use parking_lot::RwLock;
+use std::fmt;
+use std::sync::Arc;
+
+use super::{Param, ParamFlags, ParamListener, ParamMut};
 
 /// Internal pointers to parameters. This is an implementation detail used by the wrappers for type
 /// erasure.
@@ -71,12 +75,14 @@ impl ParamPtr {
     param_ptr_forward!(pub unsafe fn previous_normalized_step(&self, from: f32, finer: bool) -> f32);
     param_ptr_forward!(pub unsafe fn next_normalized_step(&self, from: f32, finer: bool) -> f32);
     param_ptr_forward!(pub unsafe fn normalized_value_to_string(&self, normalized: f32, include_unit: bool) -> String);
+    param_ptr_forward!(pub unsafe fn normalized_value_to_buf(&self, normalized: f32, include_unit: bool, buf: &mut dyn fmt::Write) -> fmt::Result);
     param_ptr_forward!(pub unsafe fn string_to_normalized_value(&self, string: &str) -> Option<f32>);
     param_ptr_forward!(pub unsafe fn flags(&self) -> ParamFlags);
 
     param_ptr_forward!(pub(crate) unsafe fn set_normalized_value(&self, normalized: f32) -> bool);
     param_ptr_forward!(pub(crate) unsafe fn modulate_value(&self, modulation_offset: f32) -> bool);
     param_ptr_forward!(pub(crate) unsafe fn update_smoother(&self, sample_rate: f32, reset: bool));
+    param_ptr_forward!(pub(crate) unsafe fn listeners(&self) -> &ParamListeners);
 
     // These functions involve casts since the plugin formats only do floating point types, so we
     // can't generate them with the macro:
@@ -168,3 +174,113 @@ impl ParamPtr {
         }
     }
 }
+
+/// The listeners registered for a single parameter through
+/// [`Params::add_param_listener()`][super::Params::add_param_listener()], along with the parameter
+/// ID they were registered for. Every parameter type stores one of these.
+#[derive(Default)]
+pub struct ParamListeners {
+    listeners: RwLock<Vec<(String, ParamListener)>>,
+}
+
+impl ParamListeners {
+    pub(crate) fn add(&self, param_id: String, listener: ParamListener) {
+        self.listeners.write().push((param_id, listener));
+    }
+
+    pub(crate) fn remove(&self, listener: &ParamListener) {
+        // Comparing the fat pointers directly would also compare the vtables
+        let listener_ptr = Arc::as_ptr(listener) as *const ();
+        self.listeners
+            .write()
+            .retain(|(_, l)| Arc::as_ptr(l) as *const () != listener_ptr);
+    }
+
+    /// Call all registered listeners with the parameter's new normalized value. This never blocks.
+    /// If a listener is being added or removed at the same time, then this does nothing.
+    pub(crate) fn notify(&self, normalized_value: f32) {
+        if let Some(listeners) = self.listeners.try_read() {
+            for (param_id, listener) in listeners.iter() {
+                listener(param_id, normalized_value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::range::FloatRange;
+    use crate::params::{FloatParam, Params};
+
+    struct TestParams {
+        gain: FloatParam,
+        mix: FloatParam,
+    }
+
+    impl Default for TestParams {
+        fn default() -> Self {
+            Self {
+                gain: FloatParam::new("Gain", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 }),
+                mix: FloatParam::new("Mix", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 }),
+            }
+        }
+    }
+
+    unsafe impl Params for TestParams {
+        fn param_map(&self) -> Vec<(String, ParamPtr, String)> {
+            vec![
+                (String::from("gain"), self.gain.as_ptr(), String::new()),
+                (String::from("mix"), self.mix.as_ptr(), String::new()),
+            ]
+        }
+    }
+
+    /// A listener that records all changes it receives.
+    fn recording_listener() -> (ParamListener, Arc<parking_lot::Mutex<Vec<(String, f32)>>>) {
+        let changes = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let listener: ParamListener = Arc::new({
+            let changes = changes.clone();
+            move |param_id: &str, normalized_value: f32| {
+                changes.lock().push((param_id.to_owned(), normalized_value))
+            }
+        });
+
+        (listener, changes)
+    }
+
+    #[test]
+    fn param_listeners() {
+        let params = TestParams::default();
+        let (listener, changes) = recording_listener();
+        assert!(params.add_param_listener_for("gain", listener.clone()));
+        assert!(!params.add_param_listener_for("nope", listener.clone()));
+
+        params.gain.set_normalized_value(0.5);
+        // Setting the same value again should not notify the listener
+        params.gain.set_normalized_value(0.5);
+        // The listener was only added for the gain parameter
+        params.mix.set_normalized_value(0.5);
+        params.remove_param_listener(&listener);
+        params.gain.set_normalized_value(0.25);
+
+        assert_eq!(*changes.lock(), [(String::from("gain"), 0.5)]);
+    }
+
+    #[test]
+    fn param_listeners_for_all_params() {
+        let params = TestParams::default();
+        let (listener, changes) = recording_listener();
+        params.add_param_listener(listener.clone());
+
+        params.gain.set_normalized_value(0.5);
+        params.mix.set_normalized_value(0.25);
+        params.remove_param_listener(&listener);
+        params.mix.set_normalized_value(0.75);
+
+        assert_eq!(
+            *changes.lock(),
+            [(String::from("gain"), 0.5), (String::from("mix"), 0.25)]
+        );
+    }
+}
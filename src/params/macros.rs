@@ -0,0 +1,269 @@
+//! User-assignable macro parameters that modulate other parameters.
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::internals::ParamPtr;
+use super::persist;
+use super::range::FloatRange;
+use super::{FloatParam, Param, Params};
+use crate::formatters;
+
+/// The key used to store the macro assignments in the plugin's state.
+const ASSIGNMENTS_KEY: &str = "macro_assignments";
+
+/// Routes one macro to one target parameter.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MacroAssignment {
+    /// The index of the macro in [`MacroParams::macros`].
+    pub macro_idx: usize,
+    /// The target parameter's ID. If the macros are used in a nested `Params` object with an ID
+    /// prefix, then this should still be the ID as it appears in the plugin's parameter map.
+    pub target: String,
+    /// How far the target parameter moves when the macro is turned all the way up, in `[-1, 1]`.
+    /// This is an offset to the target's normalized value.
+    pub depth: f32,
+    /// The curve applied to the macro's value in `[-1, 1]`. Zero is linear, positive values make
+    /// the target react more strongly at the start of the macro's range, and negative values make
+    /// it react more strongly at the end.
+    pub curve: f32,
+}
+
+/// A set of `N` macro parameters along with their assignments to other parameters. Every macro can
+/// be assigned to any number of target parameters with a per-target depth and curve, and the
+/// assignments are saved in the plugin's state. The macros themselves are regular parameters with
+/// IDs `macro_1` through `macro_N`, so the host can automate them.
+///
+/// Add this to the plugin's parameters as a nested object, and assign the macros from the editor:
+///
+/// ```ignore
+/// #[derive(Params)]
+/// struct MyParams {
+///     #[id = "cutoff"]
+///     cutoff: FloatParam,
+///
+///     #[nested(group = "Macros")]
+///     macros: MacroParams<8>,
+/// }
+///
+/// // In the editor
+/// params.macros.assign(MacroAssignment {
+///     macro_idx: 0,
+///     target: String::from("cutoff"),
+///     depth: 0.5,
+///     curve: 0.0,
+/// });
+///
+/// // In the process function
+/// let cutoff = self.params.macros.modulated_value("cutoff", &self.params.cutoff);
+/// ```
+///
+/// The macros don't change the target parameters' values. Instead the DSP code asks for the
+/// target's modulated value using [`modulated_value()`][Self::modulated_value()], so the
+/// parameters shown in the host keep their own values and the macros' effect is never written to
+/// the state.
+#[derive(Debug)]
+pub struct MacroParams<const N: usize> {
+    /// The macro parameters, in `[0, 1]`.
+    pub macros: [FloatParam; N],
+
+    /// The assignments from macros to target parameters. This is only locked for writing when the
+    /// assignments change.
+    assignments: RwLock<Vec<MacroAssignment>>,
+}
+
+impl<const N: usize> Default for MacroParams<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> MacroParams<N> {
+    /// Create `N` macros without any assignments.
+    pub fn new() -> Self {
+        Self {
+            macros: std::array::from_fn(|macro_idx| {
+                FloatParam::new(
+                    format!("Macro {}", macro_idx + 1),
+                    0.0,
+                    FloatRange::Linear { min: 0.0, max: 1.0 },
+                )
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage())
+            }),
+            assignments: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// A copy of the current assignments.
+    pub fn assignments(&self) -> Vec<MacroAssignment> {
+        self.assignments.read().clone()
+    }
+
+    /// Add an assignment, replacing any existing assignment from the same macro to the same
+    /// target. Returns `false` if the macro index is out of bounds. This should not be called from
+    /// the audio thread.
+    pub fn assign(&self, assignment: MacroAssignment) -> bool {
+        if assignment.macro_idx >= N {
+            nih_debug_assert_failure!("Macro index {} out of bounds", assignment.macro_idx);
+            return false;
+        }
+
+        let mut assignments = self.assignments.write();
+        match assignments.iter_mut().find(|existing| {
+            existing.macro_idx == assignment.macro_idx && existing.target == assignment.target
+        }) {
+            Some(existing) => *existing = assignment,
+            None => assignments.push(assignment),
+        }
+
+        true
+    }
+
+    /// Remove the assignment from a macro to a target parameter. Returns `false` if there was no
+    /// such assignment. This should not be called from the audio thread.
+    pub fn unassign(&self, macro_idx: usize, target: &str) -> bool {
+        let mut assignments = self.assignments.write();
+        let num_assignments = assignments.len();
+        assignments
+            .retain(|assignment| assignment.macro_idx != macro_idx || assignment.target != target);
+
+        assignments.len() != num_assignments
+    }
+
+    /// Remove all assignments. This should not be called from the audio thread.
+    pub fn clear_assignments(&self) {
+        self.assignments.write().clear();
+    }
+
+    /// The sum of all macro offsets for a target parameter, in normalized units. This does not
+    /// allocate or block. If the assignments are being changed at the same time, then this returns
+    /// zero for that one call.
+    pub fn modulation(&self, target: &str) -> f32 {
+        let Some(assignments) = self.assignments.try_read() else {
+            return 0.0;
+        };
+
+        assignments
+            .iter()
+            .filter(|assignment| assignment.target == target)
+            .filter_map(|assignment| {
+                let value = self.macros.get(assignment.macro_idx)?.value();
+                // A curve of 1 takes the macro's square root, and a curve of -1 squares it
+                let exponent = 2.0f32.powf(-assignment.curve.clamp(-1.0, 1.0));
+
+                Some(value.clamp(0.0, 1.0).powf(exponent) * assignment.depth.clamp(-1.0, 1.0))
+            })
+            .sum()
+    }
+
+    /// Apply the macros assigned to `target` to a normalized value. The result is clamped to
+    /// `[0, 1]`.
+    pub fn modulated_normalized(&self, target: &str, normalized: f32) -> f32 {
+        (normalized + self.modulation(target)).clamp(0.0, 1.0)
+    }
+
+    /// The final plain value for a target parameter after applying its host modulation and the
+    /// macros assigned to it. `target` should be the parameter's ID. Note that this bypasses the
+    /// parameter's smoother, so use the macros' own smoothers if the macros are automated
+    /// quickly.
+    pub fn modulated_value<P: Param>(&self, target: &str, param: &P) -> P::Plain {
+        param.preview_plain(self.modulated_normalized(target, param.modulated_normalized_value()))
+    }
+}
+
+unsafe impl<const N: usize> Params for MacroParams<N> {
+    fn param_map(&self) -> Vec<(String, ParamPtr, String)> {
+        self.macros
+            .iter()
+            .enumerate()
+            .map(|(macro_idx, param)| {
+                (
+                    format!("macro_{}", macro_idx + 1),
+                    param.as_ptr(),
+                    String::new(),
+                )
+            })
+            .collect()
+    }
+
+    fn serialize_fields(&self) -> BTreeMap<String, String> {
+        let mut serialized = BTreeMap::new();
+        match persist::serialize_field(&*self.assignments.read()) {
+            Ok(data) => {
+                serialized.insert(String::from(ASSIGNMENTS_KEY), data);
+            }
+            Err(err) => nih_debug_assert_failure!("Could not serialize macro assignments: {}", err),
+        }
+
+        serialized
+    }
+
+    fn deserialize_fields(&self, serialized: &BTreeMap<String, String>) {
+        let Some(data) = serialized.get(ASSIGNMENTS_KEY) else {
+            return;
+        };
+
+        match persist::deserialize_field::<Vec<MacroAssignment>>(data) {
+            Ok(mut assignments) => {
+                assignments.retain(|assignment| assignment.macro_idx < N);
+                *self.assignments.write() = assignments;
+            }
+            Err(err) => {
+                nih_debug_assert_failure!("Could not deserialize macro assignments: {}", err)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assignment(macro_idx: usize, target: &str, depth: f32, curve: f32) -> MacroAssignment {
+        MacroAssignment {
+            macro_idx,
+            target: String::from(target),
+            depth,
+            curve,
+        }
+    }
+
+    #[test]
+    fn macro_modulation() {
+        let macros = MacroParams::<2>::new();
+        assert!(macros.assign(assignment(0, "cutoff", 0.5, 0.0)));
+        assert!(macros.assign(assignment(1, "cutoff", -0.25, 1.0)));
+        assert!(!macros.assign(assignment(2, "cutoff", 1.0, 0.0)));
+
+        macros.macros[0].set_plain_value(1.0);
+        macros.macros[1].set_plain_value(0.25);
+        assert_eq!(macros.modulation("cutoff"), 0.375);
+        assert_eq!(macros.modulation("resonance"), 0.0);
+        assert_eq!(macros.modulated_normalized("cutoff", 0.75), 1.0);
+
+        // Assigning the same macro to the same target again replaces the old assignment
+        macros.assign(assignment(0, "cutoff", 0.25, 0.0));
+        assert_eq!(macros.assignments().len(), 2);
+        assert!(macros.unassign(1, "cutoff"));
+        assert_eq!(macros.modulation("cutoff"), 0.25);
+    }
+
+    #[test]
+    fn persist_assignments() {
+        let macros = MacroParams::<4>::new();
+        macros.assign(assignment(3, "gain", -1.0, 0.5));
+        let serialized = macros.serialize_fields();
+
+        let restored = MacroParams::<4>::new();
+        restored.deserialize_fields(&serialized);
+        assert_eq!(restored.assignments(), macros.assignments());
+
+        // Assignments to macros that don't exist are dropped
+        let fewer_macros = MacroParams::<2>::new();
+        fewer_macros.deserialize_fields(&serialized);
+        assert!(fewer_macros.assignments().is_empty());
+    }
+}
@@ -0,0 +1,273 @@
+//! Morphing between two parameter snapshots, for scene morphing and preset interpolation.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::internals::ParamPtr;
+use super::{ParamFlags, Params};
+use crate::context::gui::ParamSetter;
+
+/// Normalized values for a set of parameters, keyed by the parameters' IDs. This can be stored in
+/// a `#[persist = "..."]` field so the snapshots are saved with the plugin's state.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ParamSnapshot {
+    /// The parameters' normalized values, keyed by their IDs.
+    pub values: BTreeMap<String, f32>,
+}
+
+/// How a [`PresetMorpher`] interpolates a parameter between the two snapshots.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MorphRule {
+    /// Linearly interpolate the normalized values. The default for continuous parameters.
+    Linear,
+    /// Jump from the first snapshot's value to the second snapshot's value when the morph position
+    /// reaches this threshold. The default for stepped parameters, with a threshold of 0.5.
+    Threshold(f32),
+    /// Never change this parameter while morphing. Use this for the parameter that drives the
+    /// morph itself.
+    Fixed,
+}
+
+/// A parameter managed by a [`PresetMorpher`].
+#[derive(Debug, Clone)]
+struct MorphedParam {
+    id: String,
+    ptr: ParamPtr,
+    rule: MorphRule,
+    /// The normalized values in the two snapshots. `None` if the parameter is missing from a
+    /// snapshot, in which case the parameter is not morphed.
+    a: Option<f32>,
+    b: Option<f32>,
+}
+
+/// Interpolates between two parameter snapshots using a single morph position, for instance from a
+/// macro knob in the editor. Every parameter has its own [`MorphRule`]. Continuous parameters are
+/// interpolated linearly by default, and stepped parameters like enums and booleans switch over
+/// halfway through. Bypass parameters are never morphed.
+///
+/// ```ignore
+/// // In the editor, when the morph knob moves
+/// morpher.apply(setter, params.morph.value());
+/// ```
+///
+/// The morph knob should be excluded from morphing with [`MorphRule::Fixed`] if it's one of the
+/// morphed object's parameters.
+#[derive(Debug, Clone)]
+pub struct PresetMorpher {
+    params: Vec<MorphedParam>,
+}
+
+impl ParamSnapshot {
+    /// Capture the current unmodulated values of all of an object's parameters.
+    pub fn capture(params: &dyn Params) -> Self {
+        Self {
+            values: params
+                .param_map()
+                .into_iter()
+                .map(|(param_id, param_ptr, _)| {
+                    (param_id, unsafe {
+                        param_ptr.unmodulated_normalized_value()
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+impl PresetMorpher {
+    /// Create a morpher for an object's parameters. Both snapshots are initialized to the
+    /// parameters' current values.
+    pub fn new(params: &dyn Params) -> Self {
+        Self {
+            params: params
+                .param_map()
+                .into_iter()
+                .filter(|(_, param_ptr, _)| {
+                    !unsafe { param_ptr.flags() }.contains(ParamFlags::BYPASS)
+                })
+                .map(|(id, ptr, _)| {
+                    let value = unsafe { ptr.unmodulated_normalized_value() };
+                    let rule = match unsafe { ptr.step_count() } {
+                        Some(_) => MorphRule::Threshold(0.5),
+                        None => MorphRule::Linear,
+                    };
+
+                    MorphedParam {
+                        id,
+                        ptr,
+                        rule,
+                        a: Some(value),
+                        b: Some(value),
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Change a parameter's morph rule. Returns `false` if the parameter does not exist.
+    pub fn set_rule(&mut self, param_id: &str, rule: MorphRule) -> bool {
+        match self.params.iter_mut().find(|param| param.id == param_id) {
+            Some(param) => {
+                param.rule = rule;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set the snapshot used at morph position 0. Parameters that are missing from the snapshot
+    /// are not morphed.
+    pub fn set_snapshot_a(&mut self, snapshot: &ParamSnapshot) {
+        for param in &mut self.params {
+            param.a = snapshot.values.get(&param.id).copied();
+        }
+    }
+
+    /// Set the snapshot used at morph position 1. Parameters that are missing from the snapshot
+    /// are not morphed.
+    pub fn set_snapshot_b(&mut self, snapshot: &ParamSnapshot) {
+        for param in &mut self.params {
+            param.b = snapshot.values.get(&param.id).copied();
+        }
+    }
+
+    /// The snapshot used at morph position 0.
+    pub fn snapshot_a(&self) -> ParamSnapshot {
+        ParamSnapshot {
+            values: self
+                .params
+                .iter()
+                .filter_map(|param| Some((param.id.clone(), param.a?)))
+                .collect(),
+        }
+    }
+
+    /// The snapshot used at morph position 1.
+    pub fn snapshot_b(&self) -> ParamSnapshot {
+        ParamSnapshot {
+            values: self
+                .params
+                .iter()
+                .filter_map(|param| Some((param.id.clone(), param.b?)))
+                .collect(),
+        }
+    }
+
+    /// Call `f` with every morphed parameter and its normalized value at a morph position in
+    /// `[0, 1]`. This can be used to apply the morph in other ways than through
+    /// [`apply()`][Self::apply()].
+    pub fn for_each_value(&self, position: f32, mut f: impl FnMut(&str, ParamPtr, f32)) {
+        let position = position.clamp(0.0, 1.0);
+        for param in &self.params {
+            let (Some(a), Some(b)) = (param.a, param.b) else {
+                continue;
+            };
+
+            let value = match param.rule {
+                MorphRule::Linear => {
+                    let value = a + ((b - a) * position);
+                    // This snaps the value to the parameter's step size, if it has one
+                    unsafe { param.ptr.preview_normalized(param.ptr.preview_plain(value)) }
+                }
+                MorphRule::Threshold(threshold) if position < threshold => a,
+                MorphRule::Threshold(_) => b,
+                MorphRule::Fixed => continue,
+            };
+
+            f(&param.id, param.ptr, value);
+        }
+    }
+
+    /// Set the parameters to their values at a morph position in `[0, 1]`, where 0 is the first
+    /// snapshot and 1 is the second snapshot. Only parameters whose values change are sent to the
+    /// host, and they are changed as a single group edit. This should be called from the editor.
+    pub fn apply(&self, setter: &ParamSetter, position: f32) {
+        let mut changes = Vec::new();
+        self.for_each_value(position, |_, param_ptr, value| {
+            if unsafe { param_ptr.unmodulated_normalized_value() } != value {
+                changes.push((param_ptr, value));
+            }
+        });
+        if changes.is_empty() {
+            return;
+        }
+
+        let param_ptrs: Vec<ParamPtr> = changes.iter().map(|(param_ptr, _)| *param_ptr).collect();
+        setter.begin_set_parameters(&param_ptrs);
+        for (param_ptr, value) in changes {
+            unsafe {
+                setter
+                    .raw_context
+                    .raw_set_parameter_normalized(param_ptr, value)
+            };
+        }
+        setter.end_set_parameters(&param_ptrs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::prelude::*;
+    use crate::test::fixtures::{TestParams, TestPlugin};
+    use crate::test::MockGuiContext;
+
+    #[test]
+    fn preset_morphing() {
+        let params = Arc::new(TestParams::default());
+        let context = MockGuiContext::<TestPlugin>::new(params.clone());
+        let setter = ParamSetter::new(&context);
+
+        let mut morpher = PresetMorpher::new(&*params);
+        setter.set_parameter(&params.gain, 0.5);
+        setter.set_parameter(&params.mode, 3);
+        setter.set_parameter(&params.bypass, true);
+        morpher.set_snapshot_b(&ParamSnapshot::capture(&*params));
+        setter.set_parameter(&params.bypass, false);
+
+        morpher.apply(&setter, 0.0);
+        assert_eq!(params.gain.value(), 1.0);
+        assert_eq!(params.mode.value(), 0);
+        morpher.apply(&setter, 0.4);
+        approx::assert_relative_eq!(params.gain.value(), 0.8, epsilon = 1e-6);
+        // Stepped parameters switch over halfway through by default
+        assert_eq!(params.mode.value(), 0);
+        morpher.apply(&setter, 0.5);
+        assert_eq!(params.gain.value(), 0.75);
+        assert_eq!(params.mode.value(), 3);
+        // Bypass parameters are never morphed
+        assert!(!params.bypass.value());
+
+        morpher.set_rule("gain", MorphRule::Threshold(0.8));
+        morpher.apply(&setter, 0.7);
+        assert_eq!(params.gain.value(), 1.0);
+        morpher.apply(&setter, 0.8);
+        assert_eq!(params.gain.value(), 0.5);
+
+        morpher.set_rule("gain", MorphRule::Fixed);
+        morpher.apply(&setter, 0.0);
+        assert_eq!(params.gain.value(), 0.5);
+        assert_eq!(params.mode.value(), 0);
+    }
+
+    #[test]
+    fn missing_snapshot_values() {
+        let params = TestParams::default();
+        let mut morpher = PresetMorpher::new(&params);
+        assert!(!morpher.set_rule("bypass", MorphRule::Linear));
+
+        let mut snapshot = ParamSnapshot::default();
+        snapshot.values.insert(String::from("gain"), 0.0);
+        morpher.set_snapshot_b(&snapshot);
+        assert_eq!(morpher.snapshot_b(), snapshot);
+
+        // Parameters that are missing from one of the snapshots are left alone
+        let mut values = Vec::new();
+        morpher.for_each_value(1.0, |param_id, _, value| {
+            values.push((param_id.to_owned(), value))
+        });
+        assert_eq!(values, [(String::from("gain"), 0.0)]);
+    }
+}
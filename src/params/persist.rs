@@ -1,6 +1,9 @@
 //! Traits and helpers for persistent fields. See the [`Params`][super::Params] trait for more
 //! information.
 
+use parking_lot::{RwLock, RwLockReadGuard};
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 /// Re-export for use in the [`Params`][super::Params] proc-macro.
@@ -239,3 +242,124 @@ pub mod serialize_atomic_cell {
         T::deserialize(deserializer).map(AtomicCell::new)
     }
 }
+
+/// Handles persisting large non-parameter fields, like sample data, that should not be serialized
+/// to JSON as part of the plugin's regular state. These types can be used with [`Params`][super::Params]'
+/// `#[persist_streamed = "..."]` attribute.
+///
+/// When the host saves the plugin's state, [`save()`][Self::save()] gets to write the field's
+/// data directly to the host's stream in as many chunks as it wants, so the data never needs to be
+/// serialized into a single buffer. When the state is restored, the field's data is copied out of
+/// the host's stream and [`load()`][Self::load()] is then called from a background thread after
+/// the rest of the plugin's state has been restored.
+///
+/// These fields are not part of the [`PluginState`][crate::prelude::PluginState] objects exposed
+/// through the [`GuiContext`][crate::prelude::GuiContext].
+pub trait StreamedPersistentField: Send + Sync {
+    /// Write the field's data to `writer`. This is called on whatever thread the host saves the
+    /// plugin's state on, so this should not take locks that are held for a long time.
+    fn save(&self, writer: &mut dyn Write) -> io::Result<()>;
+
+    /// Called just before the background thread starts loading the field's data, while the host
+    /// is still restoring the plugin's state. This can be used to mark the field as loading.
+    fn begin_load(&self) {}
+
+    /// Restore the field from the data written by [`save()`][Self::save()]. This is called from a
+    /// background thread. The field should signal that it has been loaded once this function
+    /// returns, even if loading failed.
+    fn load(&self, reader: &mut dyn Read) -> io::Result<()>;
+
+    /// Called instead of [`load()`][Self::load()] after [`begin_load()`][Self::begin_load()] when
+    /// the host restored a newer state before this field's data could be loaded. The newer state's
+    /// data will be loaded afterwards.
+    fn cancel_load(&self) {}
+}
+
+impl<T: StreamedPersistentField> StreamedPersistentField for Arc<T> {
+    fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.as_ref().save(writer)
+    }
+
+    fn begin_load(&self) {
+        self.as_ref().begin_load()
+    }
+
+    fn load(&self, reader: &mut dyn Read) -> io::Result<()> {
+        self.as_ref().load(reader)
+    }
+
+    fn cancel_load(&self) {
+        self.as_ref().cancel_load()
+    }
+}
+
+/// A [`StreamedPersistentField`] containing an arbitrary byte buffer. Use
+/// [`is_loading()`][Self::is_loading()] to check whether the buffer is still being restored on
+/// the background thread.
+#[derive(Debug, Default)]
+pub struct StreamedBytes {
+    data: RwLock<Vec<u8>>,
+    /// The number of loads started with `begin_load()` that have not yet finished or been
+    /// cancelled. This is a counter so an older load finishing does not clear the flag while a
+    /// newer load is still pending.
+    loading: AtomicUsize,
+}
+
+impl StreamedBytes {
+    /// The buffer is written to the host's stream in chunks of this many bytes.
+    const CHUNK_SIZE: usize = 1 << 16;
+
+    /// Create a new field containing `data`.
+    pub fn new(data: Vec<u8>) -> Self {
+        Self {
+            data: RwLock::new(data),
+            loading: AtomicUsize::new(0),
+        }
+    }
+
+    /// Get read access to the stored data.
+    pub fn read(&self) -> RwLockReadGuard<'_, Vec<u8>> {
+        self.data.read()
+    }
+
+    /// Replace the stored data.
+    pub fn set(&self, data: Vec<u8>) {
+        *self.data.write() = data;
+    }
+
+    /// Whether the data is currently being restored on a background thread. The stored data will
+    /// have been updated once this returns `false` again.
+    pub fn is_loading(&self) -> bool {
+        self.loading.load(Ordering::Acquire) > 0
+    }
+}
+
+impl StreamedPersistentField for StreamedBytes {
+    fn save(&self, writer: &mut dyn Write) -> io::Result<()> {
+        for chunk in self.data.read().chunks(Self::CHUNK_SIZE) {
+            writer.write_all(chunk)?;
+        }
+
+        Ok(())
+    }
+
+    fn begin_load(&self) {
+        self.loading.fetch_add(1, Ordering::AcqRel);
+    }
+
+    fn load(&self, reader: &mut dyn Read) -> io::Result<()> {
+        // The lock is only held for swapping the buffers so readers are never blocked for long
+        let mut data = Vec::new();
+        let result = reader.read_to_end(&mut data);
+        if result.is_ok() {
+            *self.data.write() = data;
+        }
+        self.loading.fetch_sub(1, Ordering::AcqRel);
+
+        result.map(|_| ())
+    }
+
+    fn cancel_load(&self) {
+        self.loading.fetch_sub(1, Ordering::AcqRel);
+    }
+}
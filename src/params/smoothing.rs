@@ -385,20 +385,24 @@ impl<T: Smoothable> Smoother<T> {
             if num_smoothed_values == steps_left {
                 // This is the same as calling `next()` `num_smoothed_values` times, but with some
                 // conditionals optimized out
-                block_values[..num_smoothed_values - 1].fill_with(|| {
-                    current = self.style.next(current, target_f32, step_size);
-                    T::from_f32(current)
-                });
+                self.fill_smoothed(
+                    &mut block_values[..num_smoothed_values - 1],
+                    &mut current,
+                    target_f32,
+                    step_size,
+                );
 
                 // In `next()` the last step snaps the value to the target value, so we'll do the
                 // same thing here
                 current = target_f32.to_f32();
                 block_values[num_smoothed_values - 1] = target;
             } else {
-                block_values[..num_smoothed_values].fill_with(|| {
-                    current = self.style.next(current, target_f32, step_size);
-                    T::from_f32(current)
-                });
+                self.fill_smoothed(
+                    &mut block_values[..num_smoothed_values],
+                    &mut current,
+                    target_f32,
+                    step_size,
+                );
             }
 
             block_values[num_smoothed_values..].fill(target);
@@ -411,6 +415,35 @@ impl<T: Smoothable> Smoother<T> {
         }
     }
 
+    /// Fill `values` with the next smoothed values starting from `current` without snapping to the
+    /// target value, and update `current` to the last produced value. With the `simd` feature
+    /// enabled, linear smoothing is computed in SIMD vectors.
+    #[inline]
+    fn fill_smoothed(&self, values: &mut [T], current: &mut f32, target: f32, step_size: f32) {
+        #[cfg(feature = "simd")]
+        if let SmoothingStyle::Linear(_) = self.style {
+            // The ramp is computed in chunks so this doesn't need any scratch buffers
+            const CHUNK_SIZE: usize = 64;
+            let mut ramp = [0.0; CHUNK_SIZE];
+            for chunk in values.chunks_mut(CHUNK_SIZE) {
+                let ramp = &mut ramp[..chunk.len()];
+                crate::util::vectorized::fill_linear_ramp(ramp, *current, step_size);
+                for (value, ramp_value) in chunk.iter_mut().zip(ramp.iter()) {
+                    *value = T::from_f32(*ramp_value);
+                }
+
+                *current = *ramp.last().unwrap();
+            }
+
+            return;
+        }
+
+        values.fill_with(|| {
+            *current = self.style.next(*current, target, step_size);
+            T::from_f32(*current)
+        });
+    }
+
     /// The same as [`next_block()`][Self::next_block()], but with a function applied to each
     /// produced value. The mapping function takes an index in the block and a floating point
     /// representation of the smoother's current value. This allows the modulation to be consistent
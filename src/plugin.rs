@@ -4,8 +4,8 @@
 use std::sync::Arc;
 
 use crate::prelude::{
-    AsyncExecutor, AudioIOLayout, AuxiliaryBuffers, Buffer, BufferConfig, Editor, InitContext,
-    MidiConfig, Params, PluginState, ProcessContext, SysExMessage,
+    AsyncExecutor, AudioIOLayout, AuxiliaryBuffers, Buffer, BufferConfig, Editor, FactoryPreset,
+    InitContext, MidiConfig, Params, PluginState, ProcessContext, SysExMessage,
 };
 
 pub mod clap;
@@ -77,8 +77,13 @@ pub trait Plugin: Default + Send + 'static {
     /// auxiliary input and output ports, if the plugin has any. If the slice is empty, then the
     /// plugin will not have any audio IO.
     ///
-    /// Both [`AudioIOLayout`] and [`PortNames`][crate::prelude::PortNames] have `.const_default()`
-    /// functions for compile-time equivalents to `Default::default()`:
+    /// Surround and ambisonic plugins can assign a named speaker arrangement to each port through
+    /// [`AudioIOLayout::channel_layouts`]. The wrappers map these to the plugin API's speaker
+    /// arrangements.
+    ///
+    /// [`AudioIOLayout`], [`PortNames`][crate::prelude::PortNames], and
+    /// [`ChannelLayouts`][crate::prelude::ChannelLayouts] have `.const_default()` functions for
+    /// compile-time equivalents to `Default::default()`:
     ///
     /// ```
     /// # use nih_plug::prelude::*;
@@ -112,11 +117,105 @@ pub trait Plugin: Default + Send + 'static {
     /// every 64 samples.
     const SAMPLE_ACCURATE_AUTOMATION: bool = false;
 
+    /// If this is set, then the wrapper buffers the host's audio and always calls
+    /// [`process()`][Self::process()] with exactly this many samples, regardless of the host's
+    /// block size. This is useful for FFT-based and other block-based plugins. The buffering adds
+    /// exactly this many samples of latency, which the wrapper adds to the latency reported to the
+    /// host on top of the latency set through
+    /// [`InitContext::set_latency_samples()`][crate::prelude::InitContext::set_latency_samples()].
+    ///
+    /// Because a process call may cover samples from several host blocks, parameter changes are
+    /// applied at the start of the host's block and are never sample accurate in this mode, and
+    /// the [`Transport`][crate::prelude::Transport] describes the host block in which the
+    /// internal block was completed. Incoming note events are delayed along with the audio.
+    /// Output events are sent in the host block in which the internal block was completed, and
+    /// they are clamped to the end of that block if they fall past it.
+    const FIXED_BLOCK_SIZE: Option<u32> = None;
+
     /// If this is set to true, then the plugin will report itself as having a hard realtime
     /// processing requirement when the host asks for it. Supported hosts will never ask the plugin
     /// to do offline processing.
     const HARD_REALTIME_ONLY: bool = false;
 
+    /// If this is set to true, then panics in [`process()`][Self::process()] and in the editor's
+    /// [`spawn()`][Editor::spawn()] function are caught by the CLAP and VST3 wrappers instead of
+    /// unwinding into the host, which would otherwise take down the host's entire process. The
+    /// panic is logged and [`on_panic()`][Self::on_panic()] is called so the plugin can save a
+    /// rescue copy of its state. After a panic in the process function the plugin only outputs
+    /// silence until the host reactivates it. After a panic in `spawn()` the editor simply won't
+    /// open.
+    ///
+    /// This requires the plugin to be compiled with `panic = "unwind"`, which is the default.
+    const CATCH_PANICS: bool = false;
+
+    /// If this is set to true, then the wrappers measure how long each call to
+    /// [`process()`][Self::process()] takes. The editor can retrieve statistics about the recent
+    /// process calls through
+    /// [`GuiContext::process_timings()`][crate::prelude::GuiContext::process_timings()], for
+    /// instance to display a DSP load meter. Measuring this adds a small amount of overhead to
+    /// every process call, so it's disabled by default.
+    const COLLECT_PROCESS_TIMINGS: bool = false;
+
+    /// If this is set to true, then the wrappers stop calling [`process()`][Self::process()] once
+    /// all of the plugin's inputs are silent, there are no incoming note events, and the tail
+    /// reported through [`ProcessStatus::Tail`] has elapsed. The outputs are cleared instead.
+    /// Plugins that return [`ProcessStatus::Normal`] keep getting called until their output is
+    /// silent. This saves CPU on idle tracks in hosts that don't suspend plugins on their own. The
+    /// plugin gets called again as soon as the input is no longer silent.
+    ///
+    /// Only enable this if the plugin's output is fully determined by its input. Plugins that
+    /// produce sound on their own must return [`ProcessStatus::KeepAlive`] while they do so.
+    /// Parameter changes don't wake the plugin up, so smoothers may still be mid-way when
+    /// processing resumes.
+    const SUPPORTS_SILENCE_SKIP: bool = false;
+
+    /// If this is set, then the wrappers crossfade between the plugin's processed output and its
+    /// dry input over this many milliseconds when the plugin's bypass parameter (see
+    /// [`BoolParam::make_bypass()`][crate::prelude::BoolParam::make_bypass()]) changes, instead of
+    /// leaving bypassing up to the plugin. This avoids clicks without any plugin-side code. The dry
+    /// signal is delayed by the plugin's latency so it lines up with the processed signal.
+    ///
+    /// The plugin's process function is still called while it is bypassed, and the plugin should
+    /// ignore the bypass parameter's value when this is enabled. Only the main output is
+    /// crossfaded. Output channels without a matching input channel fade to silence.
+    const BYPASS_CROSSFADE_MS: Option<f32> = None;
+
+    /// If this is set, then the wrappers send at most this many values per second to the host for
+    /// every parameter the plugin changes from the audio thread with
+    /// [`ProcessContext::set_parameter()`][crate::prelude::ProcessContext::set_parameter()]. Values
+    /// set in between are coalesced, and the most recent one is sent once the interval has passed
+    /// or when the plugin calls
+    /// [`end_set_parameter()`][crate::prelude::ProcessContext::end_set_parameter()]. This lets the
+    /// plugin write automation from its DSP code at any rate without flooding the host with
+    /// automation points. Since these changes are applied to the parameter when they are sent to
+    /// the host, the parameter's value is also updated at this rate. The standalone target has no
+    /// host to send automation to, so it ignores this setting.
+    const PARAM_OUTPUT_RATE_HZ: Option<f32> = None;
+
+    /// If this is set, then the plugin is treated as a measurement or analysis plugin, like a
+    /// meter or a spectrum analyzer. The wrappers guarantee that the main output equals the main
+    /// input by restoring the main buffer after every process call, so the plugin can read the
+    /// buffer without having to worry about accidentally modifying the audio. Auxiliary outputs are
+    /// not affected.
+    const ANALYZER_MODE: bool = false;
+
+    /// Presets that are embedded in the plugin's binary. These are exposed to the host through
+    /// CLAP's preset discovery factory and as a VST3 program list, so they show up in the host's
+    /// preset browser without needing an installer to write preset files to disk. The editor can
+    /// list and load them through the [`GuiContext`][crate::prelude::GuiContext].
+    ///
+    /// ```
+    /// # use nih_plug::prelude::*;
+    /// const FACTORY_PRESETS: &'static [FactoryPreset] = &[FactoryPreset {
+    ///     name: "Init",
+    ///     data: br#"{ "params": {}, "fields": {} }"#,
+    /// }];
+    /// ```
+    ///
+    /// The preset data is stored in the same format as the plugin's regular state. See
+    /// [`FactoryPreset::data`] for more information.
+    const FACTORY_PRESETS: &'static [FactoryPreset] = &[];
+
     /// The plugin's SysEx message type if it supports sending or receiving MIDI SysEx messages, or
     /// `()` if it does not. This type can be a struct or enum wrapping around one or more message
     /// types, and the [`SysExMessage`] trait is then used to convert between this type and basic
@@ -144,6 +243,27 @@ pub trait Plugin: Default + Send + 'static {
         Box::new(|_| ())
     }
 
+    /// The type of the messages the plugin's editor can send to its audio thread, or `()` if the
+    /// editor doesn't need to send any. This is usually an enum with commands like loading a sample
+    /// into a slot or clearing a buffer. Messages are sent from the editor using
+    /// [`AsyncExecutor::send_to_dsp()`][crate::prelude::AsyncExecutor::send_to_dsp()] and they are
+    /// received in `process()` by calling
+    /// [`ProcessContext::next_gui_message()`][crate::prelude::ProcessContext::next_gui_message()].
+    /// The framework stores these messages in a bounded lock-free queue, so like with
+    /// [`BackgroundTask`][Self::BackgroundTask], this type should not contain any heap allocated
+    /// data as that would need to be deallocated on the audio thread.
+    type GuiMessage: Send;
+    /// The type of the events the plugin's audio thread can send to its editor, or `()` if it
+    /// doesn't need to send any. This is meant for small, frequent events used for visualizations,
+    /// like note triggers, clip indicators, or gain reduction samples. Events are sent from
+    /// `process()` using
+    /// [`ProcessContext::send_to_editor()`][crate::prelude::ProcessContext::send_to_editor()], and
+    /// the editor drains them every frame using
+    /// [`AsyncExecutor::next_editor_event()`][crate::prelude::AsyncExecutor::next_editor_event()].
+    /// These events are stored in a bounded wait-free queue, so this type should not contain any
+    /// heap allocated data.
+    type EditorEvent: Send;
+
     /// The plugin's parameters. The host will update the parameter values before calling
     /// `process()`. These string parameter IDs parameters should never change as they are used to
     /// distinguish between parameters.
@@ -210,6 +330,26 @@ pub trait Plugin: Default + Send + 'static {
         true
     }
 
+    /// Called instead of [`initialize()`][Self::initialize()] when the plugin is reinitialized with
+    /// the same audio IO layout and buffer sizes it was last initialized with, and only the sample
+    /// rate has changed. This happens when the user switches between 44.1 and 48 kHz in the host's
+    /// audio settings. Plugins can override this to only recompute sample rate dependent values
+    /// like filter coefficients and delay line lengths instead of reallocating everything. The
+    /// default implementation simply calls `initialize()`.
+    ///
+    /// The same rules as for `initialize()` apply. [`deactivate()`][Self::deactivate()] may have
+    /// been called in between, so anything freed there needs to be allocated again here, and
+    /// [`reset()`][Self::reset()] will be called afterwards. Returning `false` means the plugin
+    /// failed to initialize.
+    fn sample_rate_changed(
+        &mut self,
+        audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.initialize(audio_io_layout, buffer_config, context)
+    }
+
     /// Clear internal state such as filters and envelopes. This is always called after
     /// [`initialize()`][Self::initialize()], and it may also be called at any other time from the
     /// audio thread. You should thus not do any allocations in this function.
@@ -220,7 +360,9 @@ pub trait Plugin: Default + Send + 'static {
     /// guaranteed to contain the same number of samples. Lastly, denormals have already been taken
     /// case of by NIH-plug, and you can optionally enable the `assert_process_allocs` feature to
     /// abort the program when any allocation occurs in the process function while running in debug
-    /// mode.
+    /// mode. The offending allocation is logged together with a backtrace before aborting. Known
+    /// safe allocations on cold paths can be allowed by wrapping them in
+    /// [`util::permit_alloc()`][crate::util::permit_alloc()].
     ///
     /// The framework provides convenient iterators on the [`Buffer`] object to process audio either
     /// either per-sample per-channel, or per-block per-channel per-sample. The first approach is
@@ -252,6 +394,14 @@ pub trait Plugin: Default + Send + 'static {
     /// `initialize()` may be called more than once before `deactivate()` is called, for instance
     /// when restoring state while the plugin is still activate.
     fn deactivate(&mut self) {}
+
+    /// Called after the wrapper caught a panic while [`CATCH_PANICS`][Self::CATCH_PANICS] is
+    /// enabled. `state` contains the plugin's parameter values and persistent fields as they were
+    /// when the plugin panicked. It implements serde's `Serialize`, so it can for instance be
+    /// written to a file as JSON so the user's work isn't lost when the host session cannot be
+    /// saved anymore. This is called on the thread that panicked, which is usually the audio
+    /// thread, and allocations are allowed here. The plugin may be in an inconsistent state.
+    fn on_panic(&mut self, state: &PluginState) {}
 }
 
 /// Indicates the current situation after the plugin has processed audio.
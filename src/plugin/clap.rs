@@ -14,12 +14,22 @@ pub trait ClapPlugin: Plugin {
     /// The URL to the plugin's support page, if available.
     const CLAP_SUPPORT_URL: Option<&'static str>;
     /// Keywords describing the plugin. The host may use this to classify the plugin in its plugin
-    /// browser.
+    /// browser. This needs to contain at least one of the main categories, and custom features
+    /// need to be namespaced. `nih_export_clap!()` checks this at compile time, see
+    /// [`ClapFeature::assert_valid()`].
     const CLAP_FEATURES: &'static [ClapFeature];
 
     /// If set, this informs the host about the plugin's capabilities for polyphonic modulation.
     const CLAP_POLY_MODULATION_CONFIG: Option<PolyModulationConfig> = None;
 
+    /// If set, the plugin reports its gain reduction to the host through the [gain adjustment
+    /// metering
+    /// extension](https://github.com/free-audio/clap/blob/main/include/clap/ext/draft/gain-adjustment-metering.h)
+    /// so the host can display it, for instance on the mixer channel. Use
+    /// [`ProcessContext::set_gain_reduction()`][crate::prelude::ProcessContext::set_gain_reduction()]
+    /// to update the value. Only enable this for plugins like compressors and limiters.
+    const CLAP_GAIN_REDUCTION_METERING: bool = false;
+
     /// This function can be implemented to define plugin-specific [remote control
     /// pages](https://github.com/free-audio/clap/blob/main/include/clap/ext/draft/remote-controls.h)
     /// that the host can use to provide better hardware mapping for a plugin. See the linked
@@ -10,8 +10,9 @@ pub trait Vst3Plugin: Plugin {
     const VST3_CLASS_ID: [u8; 16];
     /// One or more subcategories. The host may use these to categorize the plugin. Internally this
     /// slice will be converted to a string where each character is separated by a pipe character
-    /// (`|`). This string has a limit of 127 characters, and anything longer than that will be
-    /// truncated.
+    /// (`|`). This string has a limit of 127 characters, and it needs to contain at least one of
+    /// the main categories. `nih_export_vst3!()` checks this at compile time, see
+    /// [`Vst3SubCategory::assert_valid()`].
     const VST3_SUBCATEGORIES: &'static [Vst3SubCategory];
 
     /// [`VST3_CLASS_ID`][Self::VST3_CLASS_ID`] in the correct order for the current platform so
@@ -14,18 +14,23 @@ pub use crate::formatters;
 pub use crate::util;
 
 pub use crate::audio_setup::{
-    new_nonzero_u32, AudioIOLayout, AuxiliaryBuffers, BufferConfig, PortNames, ProcessMode,
+    new_nonzero_u32, AudioIOLayout, AuxiliaryBuffers, BufferConfig, ChannelLayout, ChannelLayouts,
+    PortNames, ProcessMode, Speaker,
 };
 pub use crate::buffer::Buffer;
-pub use crate::context::gui::{AsyncExecutor, GuiContext, ParamSetter};
+pub use crate::context::gui::{
+    AsyncExecutor, GuiContext, ParamSetter, ProcessTimings, StateSlot, TailLength,
+    TransportSnapshot,
+};
 pub use crate::context::init::InitContext;
 pub use crate::context::process::{ProcessContext, Transport};
 pub use crate::context::remote_controls::{
     RemoteControlsContext, RemoteControlsPage, RemoteControlsSection,
 };
-pub use crate::context::PluginApi;
+pub use crate::context::{HostInfo, PluginApi};
 // This also includes the derive macro
 pub use crate::editor::{Editor, ParentWindowHandle};
+pub use crate::midi::learn::{MidiLearn, MidiMapping};
 pub use crate::midi::sysex::SysExMessage;
 pub use crate::midi::{control_change, MidiConfig, NoteEvent, PluginNoteEvent};
 pub use crate::params::enums::{Enum, EnumParam};
@@ -33,12 +38,12 @@ pub use crate::params::internals::ParamPtr;
 pub use crate::params::range::{FloatRange, IntRange};
 pub use crate::params::smoothing::{AtomicF32, Smoothable, Smoother, SmoothingStyle};
 pub use crate::params::Params;
-pub use crate::params::{BoolParam, FloatParam, IntParam, Param, ParamFlags};
+pub use crate::params::{BoolParam, FloatParam, IntParam, Param, ParamFlags, ParamListener};
 pub use crate::plugin::clap::{ClapPlugin, PolyModulationConfig};
 #[cfg(feature = "vst3")]
 pub use crate::plugin::vst3::Vst3Plugin;
 pub use crate::plugin::{Plugin, ProcessStatus, TaskExecutor};
 pub use crate::wrapper::clap::features::ClapFeature;
-pub use crate::wrapper::state::PluginState;
+pub use crate::wrapper::state::{FactoryPreset, PluginState};
 #[cfg(feature = "vst3")]
 pub use crate::wrapper::vst3::subcategories::Vst3SubCategory;
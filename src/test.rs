@@ -0,0 +1,976 @@
+//! An offline host simulator for testing plugins with `cargo test`, without needing a DAW.
+//!
+//! [`PluginTester`] instantiates a plugin, initializes it, and then runs its process function on
+//! synthetic buffers. Parameter changes and note events can be scheduled at specific samples, and
+//! the note events sent by the plugin are collected so they can be inspected afterwards. Long
+//! inputs are split into blocks of at most the configured maximum buffer size, and when the plugin
+//! uses sample accurate automation the blocks are also split on parameter changes just like the
+//! CLAP and VST3 wrappers would do.
+//!
+//! The mock contexts used by the tester are also exposed directly. [`MockProcessContext`] and
+//! [`MockInitContext`] can be passed to a plugin's functions to assert on things like latency
+//! reporting and output event ordering, and [`MockGuiContext`] can be used to test editor and
+//! preset handling logic without a host.
+//!
+//! For regression tests, a [`Scenario`] can be rendered with
+//! [`PluginTester::render_scenario()`] and compared against a stored reference file using
+//! [`assert_golden_file()`]. Inputs for these renders can be generated using [`impulse()`],
+//! [`sine_sweep()`], [`white_noise()`], and [`pink_noise()`].
+//!
+//! To test a plugin through NIH-plug's CLAP wrapper instead of through its Rust API, the
+//! `test_clap_host` feature adds a `ClapTestHost` that loads a compiled `.clap` bundle and drives
+//! it like a DAW would.
+//!
+//! Finally, [`ProcessBench`] runs a plugin's process function on preallocated blocks of noise for
+//! use in benchmarks, and [`stress_test()`] hammers a plugin with random buffer sizes, layout
+//! changes, state reloads, and out of range parameter values.
+//!
+//! ```ignore
+//! use nih_plug::prelude::*;
+//! use nih_plug::test::PluginTester;
+//!
+//! #[test]
+//! fn applies_gain() {
+//!     let mut tester = PluginTester::<Gain>::new(
+//!         Gain::AUDIO_IO_LAYOUTS[0],
+//!         BufferConfig {
+//!             sample_rate: 44100.0,
+//!             min_buffer_size: None,
+//!             max_buffer_size: 512,
+//!             process_mode: ProcessMode::Offline,
+//!         },
+//!     )
+//!     .expect("The plugin failed to initialize");
+//!     tester.set_parameter_normalized("gain", 0.25);
+//!
+//!     let mut main_io = vec![vec![1.0; 1024]; 2];
+//!     tester.process(&mut main_io);
+//!     // Check the output in `main_io`...
+//! }
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::num::NonZeroU32;
+use std::ptr::NonNull;
+use std::sync::Arc;
+
+use crate::audio_setup::{AudioIOLayout, AuxiliaryBuffers, BufferConfig};
+use crate::midi::PluginNoteEvent;
+use crate::params::internals::ParamPtr;
+use crate::params::Params;
+use crate::plugin::{Plugin, ProcessStatus, TaskExecutor};
+use crate::wrapper::state::{self, PluginState};
+use crate::wrapper::util::analyzer_passthrough::{process_with_passthrough, AnalyzerPassthrough};
+use crate::wrapper::util::buffer_management::{BufferManager, ChannelPointers};
+use crate::wrapper::util::cv_inputs::CvInputs;
+use crate::wrapper::util::transport_tracker::TransportTracker;
+use crate::wrapper::util::{initialize_plugin, process_wrapper};
+
+mod bench;
+#[cfg(feature = "test_clap_host")]
+mod clap_host;
+mod context;
+#[cfg(test)]
+pub(crate) mod fixtures;
+mod golden;
+mod signals;
+mod stress;
+
+pub use self::bench::ProcessBench;
+#[cfg(feature = "test_clap_host")]
+pub use self::clap_host::{ClapParamInfo, ClapTestHost};
+pub use self::context::{
+    GuiContextCall, MockGuiContext, MockInitContext, MockProcessContext, ProcessParamChange,
+};
+pub use self::golden::{
+    assert_golden_file, check_golden_file, Scenario, UPDATE_GOLDEN_FILES_ENV_VAR,
+};
+pub use self::signals::{impulse, pink_noise, sine_sweep, white_noise};
+pub use self::stress::{stress_test, StressTestConfig};
+
+/// Drives a plugin's process function with synthetic buffers. See the [module's
+/// documentation][self] for more information.
+///
+/// All timings passed to this object are relative to the start of the next call to
+/// [`process()`][Self::process()]. Events and parameter changes that fall outside of that call's
+/// buffer are kept for the call after that.
+pub struct PluginTester<P: Plugin> {
+    plugin: P,
+    task_executor: TaskExecutor<P>,
+    params: Arc<dyn Params>,
+    param_id_to_ptr: HashMap<String, ParamPtr>,
+
+    audio_io_layout: AudioIOLayout,
+    buffer_config: BufferConfig,
+    buffer_manager: BufferManager,
+    /// Copies the auxiliary inputs to the parameters that accept audio-rate modulation.
+    cv_inputs: CvInputs,
+    /// Restores the main buffer after processing when [`Plugin::ANALYZER_MODE`] is set.
+    analyzer_passthrough: Option<AnalyzerPassthrough>,
+
+    /// Whether [`Plugin::initialize()`] last returned `true`. The plugin is only deactivated when
+    /// this is set.
+    initialized: bool,
+    /// The context passed to the plugin's process function. Its transport and event queues are
+    /// updated for every block.
+    process_context: MockProcessContext<P>,
+    /// The latency most recently set through the init or process context.
+    latency_samples: u32,
+
+    /// Whether the simulated transport is playing. The position only advances while this is set.
+    playing: bool,
+    tempo: Option<f64>,
+    time_signature: Option<(i32, i32)>,
+    /// The transport's position at the start of the next process call.
+    pos_samples: i64,
+    /// Sets the transport's change flags, just like in the plugin wrappers.
+    transport_tracker: TransportTracker,
+
+    /// Note events for the next process call, sorted by timing.
+    pending_events: VecDeque<PluginNoteEvent<P>>,
+    /// Parameter changes for the next process call as `(timing, param, normalized_value)` tuples,
+    /// sorted by timing.
+    pending_param_changes: VecDeque<(u32, ParamPtr, f32)>,
+    /// The events sent by the plugin, with timings relative to the start of the process call they
+    /// were sent in.
+    output_events: Vec<PluginNoteEvent<P>>,
+}
+
+impl<P: Plugin> PluginTester<P> {
+    /// Instantiate and initialize the plugin using the specified audio IO layout and buffer
+    /// configuration. Returns `None` if [`Plugin::initialize()`] returned `false`.
+    pub fn new(audio_io_layout: AudioIOLayout, buffer_config: BufferConfig) -> Option<Self> {
+        let mut plugin = P::default();
+        let task_executor = plugin.task_executor();
+        let params = plugin.params();
+        let param_id_to_ptr: HashMap<String, ParamPtr> = params
+            .param_map()
+            .into_iter()
+            .map(|(param_id, param_ptr, _)| (param_id, param_ptr))
+            .collect();
+        let cv_inputs = CvInputs::new(param_id_to_ptr.values().copied());
+
+        let mut tester = Self {
+            plugin,
+            task_executor,
+            params,
+            param_id_to_ptr,
+
+            audio_io_layout,
+            buffer_config,
+            buffer_manager: BufferManager::for_audio_io_layout(
+                buffer_config.max_buffer_size as usize,
+                audio_io_layout,
+            ),
+            cv_inputs,
+            analyzer_passthrough: None,
+
+            initialized: false,
+            process_context: MockProcessContext::new(buffer_config.sample_rate),
+            latency_samples: 0,
+
+            playing: false,
+            tempo: None,
+            time_signature: None,
+            pos_samples: 0,
+            transport_tracker: TransportTracker::default(),
+
+            pending_events: VecDeque::new(),
+            pending_param_changes: VecDeque::new(),
+            output_events: Vec::new(),
+        };
+
+        // Just like in the wrappers, the smoothers are set to the parameters' current values before
+        // initializing the plugin
+        tester.reset_smoothers();
+        if tester.initialize_plugin(None) {
+            Some(tester)
+        } else {
+            None
+        }
+    }
+
+    /// Get a reference to the plugin instance.
+    pub fn plugin(&self) -> &P {
+        &self.plugin
+    }
+
+    /// Get a mutable reference to the plugin instance.
+    pub fn plugin_mut(&mut self) -> &mut P {
+        &mut self.plugin
+    }
+
+    /// The plugin's parameters, as returned by [`Plugin::params()`].
+    pub fn params(&self) -> &Arc<dyn Params> {
+        &self.params
+    }
+
+    /// The latency the plugin most recently reported through its init or process context.
+    pub fn latency_samples(&self) -> u32 {
+        self.latency_samples
+    }
+
+    /// The gain reduction in decibels the plugin most recently reported through
+    /// [`ProcessContext::set_gain_reduction()`][crate::prelude::ProcessContext::set_gain_reduction()].
+    pub fn gain_reduction(&self) -> f32 {
+        self.process_context.gain_reduction()
+    }
+
+    /// Deactivate the plugin and initialize it again with a different audio IO layout and buffer
+    /// configuration, like a host would do when the bus configuration or the sample rate changes.
+    /// Returns `false` if [`Plugin::initialize()`] returned `false`, in which case the plugin
+    /// cannot be processed until it has been successfully reinitialized. If only the sample rate
+    /// changed, then [`Plugin::sample_rate_changed()`] is called instead.
+    pub fn reinitialize(
+        &mut self,
+        audio_io_layout: AudioIOLayout,
+        buffer_config: BufferConfig,
+    ) -> bool {
+        let previous_config = if self.initialized {
+            self.plugin.deactivate();
+            self.initialized = false;
+
+            Some((self.audio_io_layout, self.buffer_config))
+        } else {
+            None
+        };
+
+        self.audio_io_layout = audio_io_layout;
+        self.buffer_config = buffer_config;
+        self.buffer_manager = BufferManager::for_audio_io_layout(
+            buffer_config.max_buffer_size as usize,
+            audio_io_layout,
+        );
+        self.reset_smoothers();
+
+        self.initialize_plugin(previous_config)
+    }
+
+    /// The current audio IO layout.
+    pub fn audio_io_layout(&self) -> &AudioIOLayout {
+        &self.audio_io_layout
+    }
+
+    /// The current buffer configuration.
+    pub fn buffer_config(&self) -> &BufferConfig {
+        &self.buffer_config
+    }
+
+    /// Serialize the plugin's current state the same way the plugin wrappers do.
+    pub fn save_state(&self) -> PluginState {
+        unsafe {
+            state::serialize_object::<P>(
+                self.params.clone(),
+                self.param_id_to_ptr
+                    .iter()
+                    .map(|(param_id, param_ptr)| (param_id, *param_ptr)),
+                None,
+            )
+        }
+    }
+
+    /// Restore a state object returned by [`save_state()`][Self::save_state()]. Like in the plugin
+    /// wrappers, the plugin is initialized again and reset after loading the parameter values.
+    /// Returns `false` if the state could not be restored or if [`Plugin::initialize()`] returned
+    /// `false`.
+    pub fn load_state(&mut self, mut state: PluginState) -> bool {
+        let success = unsafe {
+            state::deserialize_object::<P>(
+                &mut state,
+                self.params.clone(),
+                |param_id| self.param_id_to_ptr.get(param_id).copied(),
+                Some(&self.buffer_config),
+                None,
+            )
+        };
+        if !success {
+            return false;
+        }
+
+        self.initialize_plugin(None)
+    }
+
+    /// Call the plugin's [`Plugin::reset()`] function.
+    pub fn reset(&mut self) {
+        self.transport_tracker.reset();
+        process_wrapper("PluginTester::reset()", || self.plugin.reset());
+    }
+
+    /// Immediately set a parameter to a normalized value, without any smoothing. Useful for setting
+    /// up a test. Use [`schedule_parameter_change()`][Self::schedule_parameter_change()] to
+    /// simulate automation instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the plugin does not have a parameter with the specified ID.
+    pub fn set_parameter_normalized(&mut self, param_id: &str, normalized: f32) {
+        let param_ptr = self.param_ptr(param_id);
+        unsafe {
+            param_ptr.set_normalized_value(normalized);
+            param_ptr.update_smoother(self.buffer_config.sample_rate, true);
+        }
+    }
+
+    /// Snap all parameter smoothers to their parameters' current values, as if the smoothing had
+    /// already finished.
+    pub fn reset_smoothers(&mut self) {
+        for param_ptr in self.param_id_to_ptr.values() {
+            unsafe { param_ptr.update_smoother(self.buffer_config.sample_rate, true) };
+        }
+    }
+
+    /// Change a parameter to a normalized value at sample `timing` of the next process call. The
+    /// value is smoothed if the parameter has a smoother. If the plugin does not use sample
+    /// accurate automation, then the change is applied at the start of the block containing
+    /// `timing`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the plugin does not have a parameter with the specified ID.
+    pub fn schedule_parameter_change(&mut self, timing: u32, param_id: &str, normalized: f32) {
+        let param_ptr = self.param_ptr(param_id);
+        let insert_idx = self
+            .pending_param_changes
+            .partition_point(|(change_timing, _, _)| *change_timing <= timing);
+        self.pending_param_changes
+            .insert(insert_idx, (timing, param_ptr, normalized));
+    }
+
+    /// Send a note event to the plugin during the next process call. The event's timing is
+    /// relative to the start of that call.
+    pub fn schedule_event(&mut self, event: PluginNoteEvent<P>) {
+        let timing = event.timing();
+        let insert_idx = self
+            .pending_events
+            .partition_point(|pending_event| pending_event.timing() <= timing);
+        self.pending_events.insert(insert_idx, event);
+    }
+
+    /// The note events sent by the plugin so far. Their timings are relative to the start of the
+    /// process call they were sent in.
+    pub fn output_events(&self) -> &[PluginNoteEvent<P>] {
+        &self.output_events
+    }
+
+    /// Take the note events sent by the plugin so far, clearing the list.
+    pub fn take_output_events(&mut self) -> Vec<PluginNoteEvent<P>> {
+        std::mem::take(&mut self.output_events)
+    }
+
+    /// Start or stop the simulated transport. The transport's position only advances while it is
+    /// playing.
+    pub fn set_playing(&mut self, playing: bool) {
+        self.playing = playing;
+    }
+
+    /// Set the tempo reported to the plugin in beats per minute.
+    pub fn set_tempo(&mut self, tempo: f64) {
+        self.tempo = Some(tempo);
+    }
+
+    /// Set the time signature reported to the plugin.
+    pub fn set_time_signature(&mut self, numerator: i32, denominator: i32) {
+        self.time_signature = Some((numerator, denominator));
+    }
+
+    /// Move the transport to a position in samples.
+    pub fn set_position_samples(&mut self, pos_samples: i64) {
+        self.pos_samples = pos_samples;
+    }
+
+    /// Process the main input and output buffers in place. `main_io` should contain one vector per
+    /// main output channel, and all channels need to have the same length. Any main output channels
+    /// without a corresponding input channel are cleared first. Auxiliary inputs are silent, and
+    /// auxiliary outputs are discarded.
+    ///
+    /// Returns the status returned by the last call to [`Plugin::process()`], or the first error.
+    pub fn process(&mut self, main_io: &mut [Vec<f32>]) -> ProcessStatus {
+        let num_samples = main_io.first().map(Vec::len).unwrap_or(0);
+        let mut aux_inputs = silent_aux_buffers(self.audio_io_layout.aux_input_ports, num_samples);
+        let mut aux_outputs =
+            silent_aux_buffers(self.audio_io_layout.aux_output_ports, num_samples);
+
+        self.process_with_aux(main_io, &mut aux_inputs, &mut aux_outputs)
+    }
+
+    /// Process `num_samples` samples of silence, and return the main output. Useful for testing
+    /// instruments and other plugins without a main input.
+    pub fn render(&mut self, num_samples: usize) -> Vec<Vec<f32>> {
+        let num_output_channels = self
+            .audio_io_layout
+            .main_output_channels
+            .map(NonZeroU32::get)
+            .unwrap_or(0) as usize;
+        let mut main_io = vec![vec![0.0; num_samples]; num_output_channels];
+        let mut aux_inputs = silent_aux_buffers(self.audio_io_layout.aux_input_ports, num_samples);
+        let mut aux_outputs =
+            silent_aux_buffers(self.audio_io_layout.aux_output_ports, num_samples);
+
+        // This also works for plugins without any audio ports, like MIDI effects
+        self.process_samples(num_samples, &mut main_io, &mut aux_inputs, &mut aux_outputs);
+
+        main_io
+    }
+
+    /// The same as [`process()`][Self::process()], but with explicit auxiliary inputs and outputs.
+    /// These slices contain one element per auxiliary port, and each port contains one vector per
+    /// channel. All channels of all buses need to have the same length.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of buses or channels does not match the audio IO layout, or if the
+    /// channels don't all have the same length.
+    pub fn process_with_aux(
+        &mut self,
+        main_io: &mut [Vec<f32>],
+        aux_inputs: &mut [Vec<Vec<f32>>],
+        aux_outputs: &mut [Vec<Vec<f32>>],
+    ) -> ProcessStatus {
+        let num_main_output_channels = self
+            .audio_io_layout
+            .main_output_channels
+            .map(NonZeroU32::get)
+            .unwrap_or(0) as usize;
+        assert_eq!(main_io.len(), num_main_output_channels);
+        assert_eq!(aux_inputs.len(), self.audio_io_layout.aux_input_ports.len());
+        assert_eq!(
+            aux_outputs.len(),
+            self.audio_io_layout.aux_output_ports.len()
+        );
+        for (bus, num_channels) in aux_inputs
+            .iter()
+            .zip(self.audio_io_layout.aux_input_ports)
+            .chain(
+                aux_outputs
+                    .iter()
+                    .zip(self.audio_io_layout.aux_output_ports),
+            )
+        {
+            assert_eq!(bus.len(), num_channels.get() as usize);
+        }
+
+        let num_samples = main_io
+            .iter()
+            .chain(aux_inputs.iter().flatten())
+            .chain(aux_outputs.iter().flatten())
+            .map(Vec::len)
+            .next()
+            .unwrap_or(0);
+
+        self.process_samples(num_samples, main_io, aux_inputs, aux_outputs)
+    }
+
+    /// The implementation for [`process_with_aux()`][Self::process_with_aux()] and
+    /// [`render()`][Self::render()]. The number of samples is passed explicitly so plugins without
+    /// any audio ports can also be tested.
+    fn process_samples(
+        &mut self,
+        num_samples: usize,
+        main_io: &mut [Vec<f32>],
+        aux_inputs: &mut [Vec<Vec<f32>>],
+        aux_outputs: &mut [Vec<Vec<f32>>],
+    ) -> ProcessStatus {
+        assert!(self.initialized, "The plugin is not initialized");
+
+        let num_main_input_channels = self
+            .audio_io_layout
+            .main_input_channels
+            .map(NonZeroU32::get)
+            .unwrap_or(0) as usize;
+        let num_main_output_channels = main_io.len();
+        for channel in main_io
+            .iter()
+            .chain(aux_inputs.iter().flatten())
+            .chain(aux_outputs.iter().flatten())
+        {
+            assert_eq!(channel.len(), num_samples, "Mismatching channel lengths");
+        }
+
+        for channel in main_io.iter_mut().skip(num_main_input_channels) {
+            channel.fill(0.0);
+        }
+
+        // These are the pointer arrays a host would pass to the plugin
+        let mut main_io_ptrs: Vec<*mut f32> = main_io
+            .iter_mut()
+            .map(|channel| channel.as_mut_ptr())
+            .collect();
+        let mut aux_input_ptrs: Vec<Vec<*mut f32>> = aux_inputs
+            .iter_mut()
+            .map(|bus| bus.iter_mut().map(|channel| channel.as_mut_ptr()).collect())
+            .collect();
+        let mut aux_output_ptrs: Vec<Vec<*mut f32>> = aux_outputs
+            .iter_mut()
+            .map(|bus| bus.iter_mut().map(|channel| channel.as_mut_ptr()).collect())
+            .collect();
+
+        let sample_rate = self.buffer_config.sample_rate;
+        let max_block_size = (self.buffer_config.max_buffer_size as usize).max(1);
+        let mut status = ProcessStatus::Normal;
+        let mut block_start = 0;
+        while block_start < num_samples {
+            let mut block_end = (block_start + max_block_size).min(num_samples);
+
+            // Without sample accurate automation, all parameter changes within this block are
+            // applied at its start
+            let apply_until = if P::SAMPLE_ACCURATE_AUTOMATION {
+                block_start
+            } else {
+                block_end - 1
+            };
+            while let Some(&(timing, param_ptr, normalized)) = self.pending_param_changes.front() {
+                if timing as usize > apply_until {
+                    break;
+                }
+
+                self.pending_param_changes.pop_front();
+                unsafe {
+                    param_ptr.set_normalized_value(normalized);
+                    param_ptr.update_smoother(sample_rate, false);
+                }
+            }
+            if P::SAMPLE_ACCURATE_AUTOMATION {
+                if let Some(&(timing, _, _)) = self.pending_param_changes.front() {
+                    block_end = block_end.min(timing as usize);
+                }
+            }
+
+            self.process_context.clear_input_events();
+            while let Some(event) = self.pending_events.front() {
+                if event.timing() as usize >= block_end {
+                    break;
+                }
+
+                let mut event = self.pending_events.pop_front().unwrap();
+                event.subtract_timing(block_start as u32);
+                self.process_context.push_event(event);
+            }
+
+            let block_len = block_end - block_start;
+            let buffers = unsafe {
+                self.buffer_manager
+                    .create_buffers(block_start, block_len, |buffer_source| {
+                        if let Some(ptrs) = NonNull::new(main_io_ptrs.as_mut_ptr()) {
+                            if num_main_output_channels > 0 {
+                                *buffer_source.main_output_channel_pointers =
+                                    Some(ChannelPointers {
+                                        ptrs,
+                                        num_channels: num_main_output_channels,
+                                    });
+                            }
+                            // The main IO is processed in place
+                            if num_main_input_channels > 0 {
+                                *buffer_source.main_input_channel_pointers =
+                                    Some(ChannelPointers {
+                                        ptrs,
+                                        num_channels: num_main_input_channels,
+                                    });
+                            }
+                        }
+
+                        for (channel_pointers, ptrs) in buffer_source
+                            .aux_input_channel_pointers
+                            .iter_mut()
+                            .zip(aux_input_ptrs.iter_mut())
+                        {
+                            *channel_pointers =
+                                NonNull::new(ptrs.as_mut_ptr()).map(|ptrs_ptr| ChannelPointers {
+                                    ptrs: ptrs_ptr,
+                                    num_channels: ptrs.len(),
+                                });
+                        }
+                        for (channel_pointers, ptrs) in buffer_source
+                            .aux_output_channel_pointers
+                            .iter_mut()
+                            .zip(aux_output_ptrs.iter_mut())
+                        {
+                            *channel_pointers =
+                                NonNull::new(ptrs.as_mut_ptr()).map(|ptrs_ptr| ChannelPointers {
+                                    ptrs: ptrs_ptr,
+                                    num_channels: ptrs.len(),
+                                });
+                        }
+                    })
+            };
+
+            let transport = self.process_context.transport_mut();
+            transport.playing = self.playing;
+            transport.tempo = self.tempo;
+            transport.time_sig_numerator = self.time_signature.map(|(numerator, _)| numerator);
+            transport.time_sig_denominator =
+                self.time_signature.map(|(_, denominator)| denominator);
+            self.process_context.set_pos_samples(Some(if self.playing {
+                self.pos_samples + block_start as i64
+            } else {
+                self.pos_samples
+            }));
+            self.transport_tracker
+                .update(self.process_context.transport_mut(), block_len);
+
+            let plugin = &mut self.plugin;
+            let context = &mut self.process_context;
+            let cv_inputs = &self.cv_inputs;
+            let analyzer_passthrough = self.analyzer_passthrough.as_mut();
+            status = process_wrapper("PluginTester::process()", || {
+                let mut aux = AuxiliaryBuffers {
+                    inputs: buffers.aux_inputs,
+                    outputs: buffers.aux_outputs,
+                };
+                cv_inputs.copy_inputs(aux.inputs, buffers.main_buffer.samples());
+
+                process_with_passthrough(analyzer_passthrough, buffers.main_buffer, |buffer| {
+                    plugin.process(buffer, &mut aux, context)
+                })
+            });
+
+            // The output events are stored relative to the start of the process call instead of
+            // the start of the block
+            for mut event in self.process_context.take_output_events() {
+                event.add_timing(block_start as u32);
+                self.output_events.push(event);
+            }
+            if let Some(&latency_samples) = self.process_context.latency_changes().last() {
+                self.latency_samples = latency_samples;
+            }
+            for task in self
+                .process_context
+                .take_background_tasks()
+                .into_iter()
+                .chain(self.process_context.take_gui_tasks())
+            {
+                (self.task_executor)(task);
+            }
+
+            if let ProcessStatus::Error(_) = status {
+                break;
+            }
+
+            block_start = block_end;
+        }
+
+        // Anything that didn't fit in this buffer is moved to the next process call
+        let num_samples_u32 = num_samples as u32;
+        for (timing, _, _) in self.pending_param_changes.iter_mut() {
+            *timing = timing.saturating_sub(num_samples_u32);
+        }
+        for event in self.pending_events.iter_mut() {
+            event.subtract_timing(event.timing().min(num_samples_u32));
+        }
+        if self.playing {
+            self.pos_samples += num_samples as i64;
+        }
+
+        status
+    }
+
+    /// Call [`Plugin::initialize()`] using the current layout and buffer config, and reset the
+    /// plugin if that succeeded. Calls [`Plugin::sample_rate_changed()`] instead if only the sample
+    /// rate differs from `previous_config`.
+    fn initialize_plugin(
+        &mut self,
+        previous_config: Option<(AudioIOLayout, BufferConfig)>,
+    ) -> bool {
+        let mut init_context = MockInitContext::new();
+        self.initialized = initialize_plugin(
+            &mut self.plugin,
+            previous_config,
+            &self.audio_io_layout,
+            &self.buffer_config,
+            &mut init_context,
+        );
+
+        // Background tasks are run directly on the calling thread to keep tests deterministic
+        for task in init_context.take_background_tasks() {
+            (self.task_executor)(task);
+        }
+        if let Some(&latency_samples) = init_context.latency_changes().last() {
+            self.latency_samples = latency_samples;
+        }
+
+        self.process_context = MockProcessContext::new(self.buffer_config.sample_rate);
+        if self.initialized {
+            self.cv_inputs.initialize(
+                &self.audio_io_layout,
+                self.buffer_config.max_buffer_size as usize,
+            );
+            self.analyzer_passthrough = P::ANALYZER_MODE.then(|| {
+                AnalyzerPassthrough::new(
+                    self.audio_io_layout
+                        .main_output_channels
+                        .map(NonZeroU32::get)
+                        .unwrap_or(0) as usize,
+                    self.buffer_config.max_buffer_size as usize,
+                )
+            });
+            self.reset();
+        }
+
+        self.initialized
+    }
+
+    fn param_ptr(&self, param_id: &str) -> ParamPtr {
+        *self
+            .param_id_to_ptr
+            .get(param_id)
+            .unwrap_or_else(|| panic!("Unknown parameter ID '{param_id}'"))
+    }
+}
+
+impl<P: Plugin> Drop for PluginTester<P> {
+    fn drop(&mut self) {
+        if self.initialized {
+            self.plugin.deactivate();
+        }
+    }
+}
+
+/// Allocate silent buffers for auxiliary ports.
+fn silent_aux_buffers(ports: &[NonZeroU32], num_samples: usize) -> Vec<Vec<Vec<f32>>> {
+    ports
+        .iter()
+        .map(|num_channels| vec![vec![0.0; num_samples]; num_channels.get() as usize])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::{TestParams, TestPlugin};
+    use super::*;
+    use crate::prelude::*;
+
+    fn tester() -> PluginTester<TestPlugin> {
+        PluginTester::new(
+            TestPlugin::AUDIO_IO_LAYOUTS[0],
+            BufferConfig {
+                sample_rate: 44100.0,
+                min_buffer_size: None,
+                max_buffer_size: 64,
+                process_mode: ProcessMode::Offline,
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn sample_accurate_parameter_change() {
+        let mut tester = tester();
+        tester.schedule_parameter_change(100, "gain", 0.5);
+
+        let mut main_io = vec![vec![1.0; 200]];
+        assert_eq!(tester.process(&mut main_io), ProcessStatus::Normal);
+        assert!(main_io[0][..100].iter().all(|&sample| sample == 1.0));
+        assert!(main_io[0][100..].iter().all(|&sample| sample == 0.5));
+    }
+
+    #[test]
+    fn event_timing_across_blocks() {
+        let mut tester = tester();
+        let note_on = |timing| NoteEvent::NoteOn {
+            timing,
+            voice_id: None,
+            channel: 0,
+            note: 60,
+            velocity: 1.0,
+        };
+        tester.schedule_event(note_on(130));
+        // This one falls outside of the first process call
+        tester.schedule_event(note_on(250));
+
+        tester.process(&mut [vec![0.0; 200]]);
+        assert_eq!(tester.take_output_events(), [note_on(130)]);
+
+        tester.process(&mut [vec![0.0; 100]]);
+        assert_eq!(tester.take_output_events(), [note_on(50)]);
+    }
+
+    #[test]
+    fn process_bench() {
+        let mut bench = ProcessBench::<TestPlugin>::new(
+            TestPlugin::AUDIO_IO_LAYOUTS[0],
+            BufferConfig {
+                sample_rate: 1000.0,
+                min_buffer_size: None,
+                max_buffer_size: 100,
+                process_mode: ProcessMode::Realtime,
+            },
+        )
+        .unwrap();
+        assert_eq!(bench.block_size(), 100);
+        assert_eq!(bench.process_block(), ProcessStatus::Normal);
+    }
+
+    #[test]
+    fn transport_changes() {
+        let mut tester = tester();
+        let mut transports = Vec::new();
+        let mut record_transport = |tester: &mut PluginTester<TestPlugin>| {
+            let transport = tester.process_context.transport();
+            transports.push((
+                transport.started_playing(),
+                transport.stopped_playing(),
+                transport.position_jumped(),
+            ));
+        };
+
+        tester.process(&mut [vec![0.0; 64]]);
+        record_transport(&mut tester);
+        tester.set_playing(true);
+        tester.process(&mut [vec![0.0; 64]]);
+        record_transport(&mut tester);
+        tester.process(&mut [vec![0.0; 64]]);
+        record_transport(&mut tester);
+        tester.set_position_samples(0);
+        tester.process(&mut [vec![0.0; 64]]);
+        record_transport(&mut tester);
+        tester.set_playing(false);
+        tester.process(&mut [vec![0.0; 64]]);
+        record_transport(&mut tester);
+
+        assert_eq!(
+            transports,
+            [
+                (false, false, false),
+                (true, false, false),
+                (false, false, false),
+                (false, false, true),
+                (false, true, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn stress() {
+        stress_test::<TestPlugin>(&StressTestConfig {
+            num_iterations: 200,
+            max_buffer_size: 256,
+            ..StressTestConfig::default()
+        });
+    }
+
+    #[test]
+    fn mock_process_context_records_output_events() {
+        let mut tester = tester();
+        let mut context = MockProcessContext::<TestPlugin>::new(44100.0);
+        let note_on = |timing, note| NoteEvent::NoteOn {
+            timing,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity: 1.0,
+        };
+        context.push_event(note_on(0, 60));
+        context.push_event(NoteEvent::NoteOff {
+            timing: 1,
+            voice_id: None,
+            channel: 0,
+            note: 60,
+            velocity: 0.0,
+        });
+        context.push_event(note_on(2, 64));
+
+        let mut samples = vec![0.0f32; 4];
+        let mut buffer = Buffer::default();
+        unsafe {
+            buffer.set_slices(samples.len(), |output_slices| {
+                *output_slices = vec![samples.as_mut_slice()];
+            })
+        };
+        tester.plugin_mut().process(
+            &mut buffer,
+            &mut AuxiliaryBuffers {
+                inputs: &mut [],
+                outputs: &mut [],
+            },
+            &mut context,
+        );
+
+        assert_eq!(context.remaining_input_events().count(), 0);
+        assert_eq!(context.output_events(), [note_on(0, 60), note_on(2, 64)]);
+        assert!(context.latency_changes().is_empty());
+    }
+
+    #[test]
+    fn mock_gui_context_state_slots() {
+        let params = Arc::new(TestParams::default());
+        let context = MockGuiContext::<TestPlugin>::new(params.clone());
+        let setter = ParamSetter::new(&context);
+
+        setter.begin_set_parameter(&params.gain);
+        setter.set_parameter(&params.gain, 0.5);
+        setter.end_set_parameter(&params.gain);
+        assert_eq!(params.gain.value(), 0.5);
+
+        context.switch_state_slot(StateSlot::B);
+        setter.set_parameter(&params.gain, 0.25);
+        context.switch_state_slot(StateSlot::A);
+        assert_eq!(params.gain.value(), 0.5);
+
+        assert_eq!(
+            context.take_calls(),
+            [
+                GuiContextCall::BeginSetParameter(String::from("gain")),
+                GuiContextCall::SetParameterNormalized(String::from("gain"), 0.5),
+                GuiContextCall::EndSetParameter(String::from("gain")),
+                GuiContextCall::SwitchStateSlot(StateSlot::B),
+                GuiContextCall::SetParameterNormalized(String::from("gain"), 0.25),
+                GuiContextCall::SwitchStateSlot(StateSlot::A),
+            ]
+        );
+    }
+
+    #[test]
+    fn midi_learn_state() {
+        let params = Arc::new(TestParams::default());
+        let context = MockGuiContext::<TestPlugin>::new(params.clone());
+
+        context.midi_learn().start_learning(&params.gain);
+        assert!(context.receive_midi_cc(0, 74, 0.5));
+        assert_eq!(params.gain.value(), 0.5);
+        let state = context.get_state();
+        assert!(state.midi_mappings.is_some());
+
+        // Loading a state with mappings restores them, and loading a state with an empty list of
+        // mappings clears them. States without any MIDI learn information keep the current
+        // mappings.
+        let other_params = Arc::new(TestParams::default());
+        let other_context = MockGuiContext::<TestPlugin>::new(other_params.clone());
+        other_context.set_state(state);
+        assert_eq!(
+            other_context.midi_learn().mapping(&other_params.gain),
+            Some((0, 74))
+        );
+
+        other_context.set_state(PluginState {
+            midi_mappings: None,
+            ..other_context.get_state()
+        });
+        assert!(other_context.receive_midi_cc(0, 74, 0.25));
+        assert_eq!(other_params.gain.value(), 0.25);
+
+        context.midi_learn().clear_all_mappings();
+        let cleared_state = context.get_state();
+        assert_eq!(cleared_state.midi_mappings, Some(Vec::new()));
+        other_context.set_state(cleared_state);
+        assert_eq!(other_context.midi_learn().mapping(&other_params.gain), None);
+        assert!(!other_context.receive_midi_cc(0, 74, 0.75));
+    }
+
+    #[test]
+    fn sample_rate_only_reinitialization() {
+        let mut tester = tester();
+        assert_eq!(tester.plugin.num_initializations, 1);
+
+        let mut buffer_config = *tester.buffer_config();
+        buffer_config.sample_rate = 48000.0;
+        assert!(tester.reinitialize(TestPlugin::AUDIO_IO_LAYOUTS[0], buffer_config));
+        assert_eq!(tester.plugin.num_initializations, 1);
+        assert_eq!(tester.plugin.num_sample_rate_changes, 1);
+
+        // Changing anything else requires a full reinitialization
+        buffer_config.sample_rate = 44100.0;
+        buffer_config.max_buffer_size = 128;
+        assert!(tester.reinitialize(TestPlugin::AUDIO_IO_LAYOUTS[0], buffer_config));
+        assert_eq!(tester.plugin.num_initializations, 2);
+        assert_eq!(tester.plugin.num_sample_rate_changes, 1);
+    }
+}
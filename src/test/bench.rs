@@ -0,0 +1,159 @@
+//! A harness for benchmarking a plugin's process function, for instance with
+//! [criterion](https://docs.rs/criterion).
+//!
+//! ```ignore
+//! use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+//! use nih_plug::prelude::*;
+//! use nih_plug::test::ProcessBench;
+//!
+//! fn process(c: &mut Criterion) {
+//!     let mut bench = ProcessBench::<Gain>::new(
+//!         Gain::AUDIO_IO_LAYOUTS[0],
+//!         BufferConfig {
+//!             sample_rate: 48000.0,
+//!             min_buffer_size: None,
+//!             max_buffer_size: 512,
+//!             process_mode: ProcessMode::Realtime,
+//!         },
+//!     )
+//!     .expect("The plugin failed to initialize");
+//!
+//!     let mut group = c.benchmark_group("gain");
+//!     group.throughput(Throughput::Elements(bench.block_size() as u64));
+//!     group.bench_function("process", |b| b.iter(|| bench.process_block()));
+//!     group.finish();
+//! }
+//!
+//! criterion_group!(benches, process);
+//! criterion_main!(benches);
+//! ```
+
+use std::hint::black_box;
+use std::num::NonZeroU32;
+
+use super::PluginTester;
+use crate::audio_setup::{AudioIOLayout, BufferConfig};
+use crate::plugin::{Plugin, ProcessStatus};
+use crate::util::WhiteNoise;
+
+/// The peak amplitude of the noise used as the benchmark's input, roughly -12 dBFS.
+const INPUT_AMPLITUDE: f32 = 0.25;
+
+/// Runs a plugin's process function on full size blocks of deterministic noise. See the [module's
+/// documentation][self] for an example.
+///
+/// Every block is processed exactly like [`PluginTester::process()`] would, so flush-to-zero is
+/// enabled during processing to avoid denormals from skewing the results, just like in the plugin
+/// wrappers. The input is restored before every block so feedback in the plugin doesn't cause the
+/// signal to decay or blow up over time.
+pub struct ProcessBench<P: Plugin> {
+    tester: PluginTester<P>,
+
+    /// The input for every block, with one vector per main output channel. Channels without a
+    /// corresponding main input channel are silent.
+    input: Vec<Vec<f32>>,
+    /// The buffers passed to the plugin. These are overwritten with `input` before every block.
+    main_io: Vec<Vec<f32>>,
+    aux_inputs: Vec<Vec<Vec<f32>>>,
+    aux_outputs: Vec<Vec<Vec<f32>>>,
+}
+
+impl<P: Plugin> ProcessBench<P> {
+    /// Instantiate and initialize the plugin, and warm it up using
+    /// [`warm_up()`][Self::warm_up()]. Blocks contain the buffer config's maximum buffer size
+    /// number of samples. Returns `None` if [`Plugin::initialize()`] returned `false`.
+    pub fn new(audio_io_layout: AudioIOLayout, buffer_config: BufferConfig) -> Option<Self> {
+        let tester = PluginTester::new(audio_io_layout, buffer_config)?;
+
+        let block_size = buffer_config.max_buffer_size as usize;
+        let num_input_channels = audio_io_layout
+            .main_input_channels
+            .map(NonZeroU32::get)
+            .unwrap_or(0) as usize;
+        let num_output_channels = audio_io_layout
+            .main_output_channels
+            .map(NonZeroU32::get)
+            .unwrap_or(0) as usize;
+
+        // A fixed seed keeps the results comparable across runs and machines
+        let mut noise = WhiteNoise::new(0x2545_f491);
+        let input = (0..num_output_channels)
+            .map(|channel_idx| {
+                if channel_idx < num_input_channels {
+                    (0..block_size)
+                        .map(|_| noise.next_sample() * INPUT_AMPLITUDE)
+                        .collect()
+                } else {
+                    vec![0.0; block_size]
+                }
+            })
+            .collect::<Vec<_>>();
+        let aux_buffers = |ports: &[NonZeroU32]| -> Vec<Vec<Vec<f32>>> {
+            ports
+                .iter()
+                .map(|num_channels| vec![vec![0.0; block_size]; num_channels.get() as usize])
+                .collect()
+        };
+
+        let mut bench = Self {
+            tester,
+
+            main_io: input.clone(),
+            input,
+            aux_inputs: aux_buffers(audio_io_layout.aux_input_ports),
+            aux_outputs: aux_buffers(audio_io_layout.aux_output_ports),
+        };
+        bench.warm_up();
+
+        Some(bench)
+    }
+
+    /// Get the tester driving the plugin. This can be used to change parameters before running the
+    /// benchmark. Call [`warm_up()`][Self::warm_up()] afterwards to skip any smoothing.
+    pub fn tester(&mut self) -> &mut PluginTester<P> {
+        &mut self.tester
+    }
+
+    /// The number of samples per channel processed by every call to
+    /// [`process_block()`][Self::process_block()].
+    pub fn block_size(&self) -> usize {
+        self.input.first().map(Vec::len).unwrap_or(0)
+    }
+
+    /// Snap all parameter smoothers to their target values, and then process about a second's
+    /// worth of blocks so things like filter states, envelopes, and lazily filled buffers have
+    /// settled before measuring.
+    pub fn warm_up(&mut self) {
+        self.tester.reset_smoothers();
+
+        let sample_rate = self.tester.buffer_config.sample_rate;
+        let num_blocks = (sample_rate as usize / self.block_size().max(1)).max(1);
+        for _ in 0..num_blocks {
+            self.process_block();
+        }
+    }
+
+    /// Process a single block of noise. The output is passed through [`black_box()`] so it cannot
+    /// be optimized away.
+    pub fn process_block(&mut self) -> ProcessStatus {
+        for (channel, input) in self.main_io.iter_mut().zip(&self.input) {
+            channel.copy_from_slice(input);
+        }
+        for channel in self.aux_inputs.iter_mut().flatten() {
+            channel.fill(0.0);
+        }
+
+        let status = self.tester.process_with_aux(
+            &mut self.main_io,
+            &mut self.aux_inputs,
+            &mut self.aux_outputs,
+        );
+        black_box(&self.main_io);
+        black_box(&self.aux_outputs);
+
+        // Events sent by the plugin would otherwise accumulate over the benchmark's run
+        self.tester.output_events.clear();
+
+        status
+    }
+}
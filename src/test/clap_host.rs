@@ -0,0 +1,663 @@
+//! A minimal CLAP host for testing compiled plugin bundles.
+
+use anyhow::{bail, Context, Result};
+use clap_sys::audio_buffer::clap_audio_buffer;
+use clap_sys::entry::clap_plugin_entry;
+use clap_sys::events::{
+    clap_event_header, clap_event_param_value, clap_input_events, clap_output_events,
+    CLAP_CORE_EVENT_SPACE_ID, CLAP_EVENT_PARAM_VALUE,
+};
+use clap_sys::ext::audio_ports::{
+    clap_audio_port_info, clap_plugin_audio_ports, CLAP_EXT_AUDIO_PORTS,
+};
+use clap_sys::ext::params::{clap_param_info, clap_plugin_params, CLAP_EXT_PARAMS};
+use clap_sys::ext::state::{clap_plugin_state, CLAP_EXT_STATE};
+use clap_sys::factory::plugin_factory::{clap_plugin_factory, CLAP_PLUGIN_FACTORY_ID};
+use clap_sys::host::clap_host;
+use clap_sys::id::clap_id;
+use clap_sys::plugin::clap_plugin;
+use clap_sys::process::{clap_process, clap_process_status, CLAP_PROCESS_ERROR};
+use clap_sys::stream::{clap_istream, clap_ostream};
+use clap_sys::version::CLAP_VERSION;
+use libloading::Library;
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Loads a compiled `.clap` bundle and drives it the same way a DAW would, through the plugin's C
+/// API. Unlike [`PluginTester`][super::PluginTester], which calls the plugin's Rust API directly,
+/// this also exercises NIH-plug's CLAP wrapper. That makes it possible to write integration tests
+/// that catch regressions in the wrapper itself, like parameters that are not exposed correctly,
+/// state that does not survive a save and load round trip, or crashes during processing.
+///
+/// Bundles are loaded from disk, so they need to be built first, for instance using `cargo xtask
+/// bundle <package>`. Everything happens on the thread that loaded the bundle, so that thread is
+/// both the host's main thread and its audio thread.
+///
+/// ```ignore
+/// let mut host = ClapTestHost::load("target/bundled/gain.clap")?;
+/// let gain = host.params().into_iter().find(|param| param.name == "Gain").unwrap();
+///
+/// host.activate(44100.0, 512)?;
+/// host.schedule_parameter_change(0, gain.id, 0.5);
+/// let mut main_io = vec![vec![1.0; 512]; 2];
+/// host.process(&mut main_io)?;
+/// ```
+pub struct ClapTestHost {
+    plugin: *const clap_plugin,
+    plugin_id: String,
+
+    /// The host struct and its data need to stay at the same address for as long as the plugin is
+    /// alive.
+    _host: Box<clap_host>,
+    host_data: Box<HostData>,
+
+    /// The sample rate and maximum buffer size, if the plugin is active.
+    activation: Option<(f64, u32)>,
+    is_processing: bool,
+    /// The number of samples processed so far, used for the process call's steady time.
+    steady_time: i64,
+    /// Parameter changes that will be sent to the plugin during the next process call.
+    pending_param_changes: Vec<clap_event_param_value>,
+    /// The channel counts for the plugin's input ports, queried when the plugin is activated.
+    input_port_channels: Vec<u32>,
+    /// The channel counts for the plugin's output ports, queried when the plugin is activated.
+    output_port_channels: Vec<u32>,
+
+    entry: *const clap_plugin_entry,
+    /// Needs to be dropped last since all of the other pointers point into this library.
+    _library: Library,
+}
+
+/// A parameter's information, as reported by the plugin's params extension.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClapParamInfo {
+    pub id: clap_id,
+    pub name: String,
+    /// The parameter's group, as a slash separated path.
+    pub module: String,
+    pub flags: u32,
+    pub min_value: f64,
+    pub max_value: f64,
+    pub default_value: f64,
+}
+
+/// State shared with the plugin through the [`clap_host`]'s `host_data` pointer.
+#[derive(Default)]
+struct HostData {
+    callback_requested: AtomicBool,
+    restart_requested: AtomicBool,
+}
+
+impl ClapTestHost {
+    /// Load the first plugin in a `.clap` bundle. See [`load_plugin()`][Self::load_plugin()].
+    pub fn load(bundle_path: impl AsRef<Path>) -> Result<Self> {
+        Self::load_impl(bundle_path.as_ref(), None)
+    }
+
+    /// Load the plugin with the specified CLAP ID from a `.clap` bundle, and initialize it. On
+    /// Linux and Windows the bundle is the plugin's shared library, and on macOS it's a bundle
+    /// directory containing the library.
+    pub fn load_plugin(bundle_path: impl AsRef<Path>, plugin_id: &str) -> Result<Self> {
+        Self::load_impl(bundle_path.as_ref(), Some(plugin_id))
+    }
+
+    fn load_impl(bundle_path: &Path, plugin_id: Option<&str>) -> Result<Self> {
+        let binary_path = bundle_binary_path(bundle_path)?;
+        let library = unsafe { Library::new(&binary_path) }
+            .with_context(|| format!("Could not load '{}'", binary_path.display()))?;
+        let entry: *const clap_plugin_entry = unsafe {
+            *library
+                .get::<*const clap_plugin_entry>(b"clap_entry\0")
+                .context("The library does not export a 'clap_entry' symbol")?
+        };
+
+        let bundle_path_cstr = CString::new(bundle_path.to_string_lossy().as_bytes())
+            .context("The bundle path contains a null byte")?;
+        let factory = unsafe {
+            let entry = &*entry;
+            if !(entry.init.context("Missing clap_entry::init")?)(bundle_path_cstr.as_ptr()) {
+                bail!("clap_entry::init() returned false");
+            }
+
+            (entry
+                .get_factory
+                .context("Missing clap_entry::get_factory")?)(
+                CLAP_PLUGIN_FACTORY_ID.as_ptr()
+            ) as *const clap_plugin_factory
+        };
+        if factory.is_null() {
+            unsafe { deinit_entry(entry) };
+            bail!("The library does not have a plugin factory");
+        }
+
+        let host_data = Box::<HostData>::default();
+        let host = Box::new(clap_host {
+            clap_version: CLAP_VERSION,
+            host_data: &*host_data as *const HostData as *mut c_void,
+            name: b"NIH-plug test host\0".as_ptr() as *const c_char,
+            vendor: b"NIH-plug\0".as_ptr() as *const c_char,
+            url: b"https://github.com/robbert-vdh/nih-plug\0".as_ptr() as *const c_char,
+            version: b"0.0.0\0".as_ptr() as *const c_char,
+            get_extension: Some(host_get_extension),
+            request_restart: Some(host_request_restart),
+            request_process: Some(host_request_process),
+            request_callback: Some(host_request_callback),
+        });
+
+        let (plugin, plugin_id) = match unsafe { create_plugin(&*factory, &host, plugin_id) } {
+            Ok(result) => result,
+            Err(err) => {
+                unsafe { deinit_entry(entry) };
+                return Err(err);
+            }
+        };
+
+        let mut this = Self {
+            plugin,
+            plugin_id,
+
+            _host: host,
+            host_data,
+
+            activation: None,
+            is_processing: false,
+            steady_time: 0,
+            pending_param_changes: Vec::new(),
+            input_port_channels: Vec::new(),
+            output_port_channels: Vec::new(),
+
+            entry,
+            _library: library,
+        };
+        if !unsafe { ((*plugin).init.context("Missing clap_plugin::init")?)(plugin) } {
+            bail!("clap_plugin::init() returned false");
+        }
+        this.run_main_thread_callbacks();
+
+        Ok(this)
+    }
+
+    /// The loaded plugin's CLAP ID.
+    pub fn plugin_id(&self) -> &str {
+        &self.plugin_id
+    }
+
+    /// Get information about all of the plugin's parameters, in the order reported by the plugin.
+    /// Returns an empty list if the plugin does not support the params extension.
+    pub fn params(&self) -> Vec<ClapParamInfo> {
+        let Some(params) = (unsafe { self.extension::<clap_plugin_params>(CLAP_EXT_PARAMS) })
+        else {
+            return Vec::new();
+        };
+
+        let (Some(count), Some(get_info)) = (params.count, params.get_info) else {
+            return Vec::new();
+        };
+        let num_params = unsafe { count(self.plugin) };
+        (0..num_params)
+            .filter_map(|param_idx| unsafe {
+                let mut info: clap_param_info = std::mem::zeroed();
+                if !get_info(self.plugin, param_idx, &mut info) {
+                    return None;
+                }
+
+                Some(ClapParamInfo {
+                    id: info.id,
+                    name: CStr::from_ptr(info.name.as_ptr())
+                        .to_string_lossy()
+                        .into_owned(),
+                    module: CStr::from_ptr(info.module.as_ptr())
+                        .to_string_lossy()
+                        .into_owned(),
+                    flags: info.flags,
+                    min_value: info.min_value,
+                    max_value: info.max_value,
+                    default_value: info.default_value,
+                })
+            })
+            .collect()
+    }
+
+    /// Get a parameter's current plain CLAP value. Returns `None` if the parameter does not exist.
+    pub fn param_value(&self, param_id: clap_id) -> Option<f64> {
+        let params = unsafe { self.extension::<clap_plugin_params>(CLAP_EXT_PARAMS) }?;
+
+        let mut value = 0.0;
+        if unsafe { (params.get_value?)(self.plugin, param_id, &mut value) } {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    /// Serialize the plugin's state using the state extension.
+    pub fn save_state(&self) -> Result<Vec<u8>> {
+        let state = unsafe { self.extension::<clap_plugin_state>(CLAP_EXT_STATE) }
+            .context("The plugin does not support the state extension")?;
+
+        let mut data = Vec::new();
+        let stream = clap_ostream {
+            ctx: &mut data as *mut Vec<u8> as *mut c_void,
+            write: Some(ostream_write),
+        };
+        if !unsafe {
+            (state.save.context("Missing clap_plugin_state::save")?)(self.plugin, &stream)
+        } {
+            bail!("clap_plugin_state::save() returned false");
+        }
+
+        Ok(data)
+    }
+
+    /// Restore state previously saved with [`save_state()`][Self::save_state()].
+    pub fn load_state(&mut self, data: &[u8]) -> Result<()> {
+        let state = unsafe { self.extension::<clap_plugin_state>(CLAP_EXT_STATE) }
+            .context("The plugin does not support the state extension")?;
+
+        let mut reader = data;
+        let stream = clap_istream {
+            ctx: &mut reader as *mut &[u8] as *mut c_void,
+            read: Some(istream_read),
+        };
+        let success = unsafe {
+            (state.load.context("Missing clap_plugin_state::load")?)(self.plugin, &stream)
+        };
+        self.run_main_thread_callbacks();
+        if !success {
+            bail!("clap_plugin_state::load() returned false");
+        }
+
+        Ok(())
+    }
+
+    /// Activate the plugin so it can process audio. Buffers passed to
+    /// [`process()`][Self::process()] may not be longer than `max_buffer_size`. If the plugin is
+    /// already active, it is deactivated first.
+    pub fn activate(&mut self, sample_rate: f64, max_buffer_size: u32) -> Result<()> {
+        self.deactivate();
+
+        (self.input_port_channels, self.output_port_channels) =
+            match unsafe { self.extension::<clap_plugin_audio_ports>(CLAP_EXT_AUDIO_PORTS) } {
+                Some(audio_ports) => (
+                    unsafe { port_channel_counts(self.plugin, audio_ports, true) },
+                    unsafe { port_channel_counts(self.plugin, audio_ports, false) },
+                ),
+                None => (Vec::new(), Vec::new()),
+            };
+
+        let activate =
+            unsafe { (*self.plugin).activate }.context("Missing clap_plugin::activate")?;
+        if !unsafe { activate(self.plugin, sample_rate, 1, max_buffer_size) } {
+            bail!("clap_plugin::activate() returned false");
+        }
+        self.activation = Some((sample_rate, max_buffer_size));
+        self.run_main_thread_callbacks();
+
+        Ok(())
+    }
+
+    /// Deactivate the plugin if it is active.
+    pub fn deactivate(&mut self) {
+        if self.is_processing {
+            if let Some(stop_processing) = unsafe { (*self.plugin).stop_processing } {
+                unsafe { stop_processing(self.plugin) };
+            }
+            self.is_processing = false;
+        }
+
+        if self.activation.take().is_some() {
+            if let Some(deactivate) = unsafe { (*self.plugin).deactivate } {
+                unsafe { deactivate(self.plugin) };
+            }
+        }
+
+        self.run_main_thread_callbacks();
+    }
+
+    /// Send a parameter change to the plugin during the next call to
+    /// [`process()`][Self::process()]. `value` is the parameter's plain CLAP value, which for
+    /// NIH-plug plugins is the normalized value for continuous parameters and the step index for
+    /// discrete parameters. `timing` is relative to the start of the next process call.
+    pub fn schedule_parameter_change(&mut self, timing: u32, param_id: clap_id, value: f64) {
+        self.pending_param_changes.push(clap_event_param_value {
+            header: clap_event_header {
+                size: std::mem::size_of::<clap_event_param_value>() as u32,
+                time: timing,
+                space_id: CLAP_CORE_EVENT_SPACE_ID,
+                type_: CLAP_EVENT_PARAM_VALUE,
+                flags: 0,
+            },
+            param_id,
+            cookie: std::ptr::null_mut(),
+            note_id: -1,
+            port_index: -1,
+            channel: -1,
+            key: -1,
+            value,
+        });
+    }
+
+    /// Process a block of audio. `main_io` contains the channels for the plugin's first input and
+    /// output port, and it's processed in place. Missing input channels and any auxiliary inputs
+    /// are filled with silence, and auxiliary outputs are discarded. All channels need to have the
+    /// same length. The plugin needs to be [activated][Self::activate()] first.
+    pub fn process(&mut self, main_io: &mut [Vec<f32>]) -> Result<clap_process_status> {
+        let Some((_, max_buffer_size)) = self.activation else {
+            bail!("The plugin needs to be activated before it can process audio");
+        };
+        let num_samples = main_io.first().map(Vec::len).unwrap_or(0);
+        if main_io.iter().any(|channel| channel.len() != num_samples) {
+            bail!("All channels need to have the same length");
+        }
+        if num_samples > max_buffer_size as usize {
+            bail!("The buffer is longer than the maximum buffer size of {max_buffer_size}");
+        }
+
+        if !self.is_processing {
+            let start_processing = unsafe { (*self.plugin).start_processing }
+                .context("Missing clap_plugin::start_processing")?;
+            if !unsafe { start_processing(self.plugin) } {
+                bail!("clap_plugin::start_processing() returned false");
+            }
+            self.is_processing = true;
+        }
+
+        let mut inputs: Vec<Vec<Vec<f32>>> = self
+            .input_port_channels
+            .iter()
+            .enumerate()
+            .map(|(port_idx, &num_channels)| {
+                (0..num_channels as usize)
+                    .map(|channel_idx| match main_io.get(channel_idx) {
+                        Some(channel) if port_idx == 0 => channel.clone(),
+                        _ => vec![0.0; num_samples],
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut outputs: Vec<Vec<Vec<f32>>> = self
+            .output_port_channels
+            .iter()
+            .map(|&num_channels| vec![vec![0.0; num_samples]; num_channels as usize])
+            .collect();
+
+        let mut input_ptrs: Vec<Vec<*mut f32>> = inputs
+            .iter_mut()
+            .map(|port| {
+                port.iter_mut()
+                    .map(|channel| channel.as_mut_ptr())
+                    .collect()
+            })
+            .collect();
+        let mut output_ptrs: Vec<Vec<*mut f32>> = outputs
+            .iter_mut()
+            .map(|port| {
+                port.iter_mut()
+                    .map(|channel| channel.as_mut_ptr())
+                    .collect()
+            })
+            .collect();
+        let input_buffers: Vec<clap_audio_buffer> = input_ptrs
+            .iter_mut()
+            .map(|ptrs| audio_buffer(ptrs))
+            .collect();
+        let mut output_buffers: Vec<clap_audio_buffer> = output_ptrs
+            .iter_mut()
+            .map(|ptrs| audio_buffer(ptrs))
+            .collect();
+
+        // Events that fall outside of this block are clamped by the wrapper
+        self.pending_param_changes
+            .sort_by_key(|event| event.header.time);
+        let param_changes = std::mem::take(&mut self.pending_param_changes);
+        let in_events = clap_input_events {
+            ctx: &param_changes as *const Vec<clap_event_param_value> as *mut c_void,
+            size: Some(input_events_size),
+            get: Some(input_events_get),
+        };
+        let out_events = clap_output_events {
+            ctx: std::ptr::null_mut(),
+            try_push: Some(output_events_try_push),
+        };
+
+        let process_data = clap_process {
+            steady_time: self.steady_time,
+            frames_count: num_samples as u32,
+            transport: std::ptr::null(),
+            audio_inputs: input_buffers.as_ptr(),
+            audio_outputs: output_buffers.as_mut_ptr(),
+            audio_inputs_count: input_buffers.len() as u32,
+            audio_outputs_count: output_buffers.len() as u32,
+            in_events: &in_events,
+            out_events: &out_events,
+        };
+        let process = unsafe { (*self.plugin).process }.context("Missing clap_plugin::process")?;
+        let status = unsafe { process(self.plugin, &process_data) };
+        self.steady_time += num_samples as i64;
+        self.run_main_thread_callbacks();
+
+        if status == CLAP_PROCESS_ERROR {
+            bail!("clap_plugin::process() returned CLAP_PROCESS_ERROR");
+        }
+        if let Some(main_output) = outputs.first() {
+            for (channel, output) in main_io.iter_mut().zip(main_output) {
+                channel.copy_from_slice(output);
+            }
+        }
+
+        Ok(status)
+    }
+
+    /// Whether the plugin has asked the host to restart it since the last call to this function,
+    /// for instance because its latency changed.
+    pub fn take_restart_request(&self) -> bool {
+        self.host_data
+            .restart_requested
+            .swap(false, Ordering::Relaxed)
+    }
+
+    /// Query one of the plugin's extensions.
+    unsafe fn extension<T>(&self, id: &CStr) -> Option<&T> {
+        let get_extension = (*self.plugin).get_extension?;
+        let extension = get_extension(self.plugin, id.as_ptr()) as *const T;
+
+        extension.as_ref()
+    }
+
+    /// Run the plugin's main thread callback if it requested one.
+    fn run_main_thread_callbacks(&mut self) {
+        if self
+            .host_data
+            .callback_requested
+            .swap(false, Ordering::Relaxed)
+        {
+            if let Some(on_main_thread) = unsafe { (*self.plugin).on_main_thread } {
+                unsafe { on_main_thread(self.plugin) };
+            }
+        }
+    }
+}
+
+impl Drop for ClapTestHost {
+    fn drop(&mut self) {
+        self.deactivate();
+
+        unsafe {
+            if let Some(destroy) = (*self.plugin).destroy {
+                destroy(self.plugin);
+            }
+
+            deinit_entry(self.entry);
+        }
+    }
+}
+
+/// The path to the shared library for a `.clap` bundle. macOS bundles are directories containing
+/// the library, while on other platforms the bundle is the library itself.
+fn bundle_binary_path(bundle_path: &Path) -> Result<PathBuf> {
+    if !bundle_path.exists() {
+        bail!("'{}' does not exist", bundle_path.display());
+    }
+
+    if bundle_path.is_dir() {
+        let binary_name = bundle_path
+            .file_stem()
+            .context("The bundle path does not have a file name")?;
+
+        Ok(bundle_path.join("Contents").join("MacOS").join(binary_name))
+    } else {
+        Ok(bundle_path.to_owned())
+    }
+}
+
+/// Create a plugin instance. Returns the plugin's pointer and its ID.
+unsafe fn create_plugin(
+    factory: &clap_plugin_factory,
+    host: &clap_host,
+    plugin_id: Option<&str>,
+) -> Result<(*const clap_plugin, String)> {
+    let get_plugin_count = factory
+        .get_plugin_count
+        .context("Missing clap_plugin_factory::get_plugin_count")?;
+    let get_plugin_descriptor = factory
+        .get_plugin_descriptor
+        .context("Missing clap_plugin_factory::get_plugin_descriptor")?;
+    let create = factory
+        .create_plugin
+        .context("Missing clap_plugin_factory::create_plugin")?;
+
+    let mut descriptor_ids = (0..get_plugin_count(factory)).filter_map(|plugin_idx| {
+        let descriptor = get_plugin_descriptor(factory, plugin_idx);
+        if descriptor.is_null() || (*descriptor).id.is_null() {
+            None
+        } else {
+            Some(CStr::from_ptr((*descriptor).id))
+        }
+    });
+    let descriptor_id = match plugin_id {
+        Some(plugin_id) => descriptor_ids
+            .find(|id| id.to_bytes() == plugin_id.as_bytes())
+            .with_context(|| {
+                format!("The bundle does not contain a plugin with ID '{plugin_id}'")
+            })?,
+        None => descriptor_ids
+            .next()
+            .context("The bundle does not contain any plugins")?,
+    };
+
+    let plugin = create(factory, host, descriptor_id.as_ptr());
+    if plugin.is_null() {
+        bail!("clap_plugin_factory::create_plugin() returned a null pointer");
+    }
+
+    Ok((plugin, descriptor_id.to_string_lossy().into_owned()))
+}
+
+unsafe fn deinit_entry(entry: *const clap_plugin_entry) {
+    if let Some(deinit) = (*entry).deinit {
+        deinit();
+    }
+}
+
+unsafe fn port_channel_counts(
+    plugin: *const clap_plugin,
+    audio_ports: &clap_plugin_audio_ports,
+    is_input: bool,
+) -> Vec<u32> {
+    let (Some(count), Some(get)) = (audio_ports.count, audio_ports.get) else {
+        return Vec::new();
+    };
+
+    (0..count(plugin, is_input))
+        .map(|port_idx| {
+            let mut info: clap_audio_port_info = std::mem::zeroed();
+            if get(plugin, port_idx, is_input, &mut info) {
+                info.channel_count
+            } else {
+                0
+            }
+        })
+        .collect()
+}
+
+fn audio_buffer(channel_ptrs: &mut [*mut f32]) -> clap_audio_buffer {
+    clap_audio_buffer {
+        data32: channel_ptrs.as_mut_ptr(),
+        data64: std::ptr::null_mut(),
+        channel_count: channel_ptrs.len() as u32,
+        latency: 0,
+        constant_mask: 0,
+    }
+}
+
+unsafe extern "C" fn host_get_extension(
+    _host: *const clap_host,
+    _extension_id: *const c_char,
+) -> *const c_void {
+    std::ptr::null()
+}
+
+unsafe extern "C" fn host_request_restart(host: *const clap_host) {
+    let host_data = &*((*host).host_data as *const HostData);
+    host_data.restart_requested.store(true, Ordering::Relaxed);
+}
+
+unsafe extern "C" fn host_request_process(_host: *const clap_host) {}
+
+unsafe extern "C" fn host_request_callback(host: *const clap_host) {
+    let host_data = &*((*host).host_data as *const HostData);
+    host_data.callback_requested.store(true, Ordering::Relaxed);
+}
+
+unsafe extern "C" fn input_events_size(list: *const clap_input_events) -> u32 {
+    let events = &*((*list).ctx as *const Vec<clap_event_param_value>);
+    events.len() as u32
+}
+
+unsafe extern "C" fn input_events_get(
+    list: *const clap_input_events,
+    index: u32,
+) -> *const clap_event_header {
+    let events = &*((*list).ctx as *const Vec<clap_event_param_value>);
+    match events.get(index as usize) {
+        Some(event) => &event.header,
+        None => std::ptr::null(),
+    }
+}
+
+unsafe extern "C" fn output_events_try_push(
+    _list: *const clap_output_events,
+    _event: *const clap_event_header,
+) -> bool {
+    // Output events are not recorded
+    true
+}
+
+unsafe extern "C" fn ostream_write(
+    stream: *const clap_ostream,
+    buffer: *const c_void,
+    size: u64,
+) -> i64 {
+    let data = &mut *((*stream).ctx as *mut Vec<u8>);
+    data.extend_from_slice(std::slice::from_raw_parts(
+        buffer as *const u8,
+        size as usize,
+    ));
+
+    size as i64
+}
+
+unsafe extern "C" fn istream_read(
+    stream: *const clap_istream,
+    buffer: *mut c_void,
+    size: u64,
+) -> i64 {
+    let reader = &mut *((*stream).ctx as *mut &[u8]);
+    let num_bytes = reader.len().min(size as usize);
+    std::ptr::copy_nonoverlapping(reader.as_ptr(), buffer as *mut u8, num_bytes);
+    *reader = &reader[num_bytes..];
+
+    num_bytes as i64
+}
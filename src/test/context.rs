@@ -0,0 +1,571 @@
+//! Mock implementations of the context traits. These record everything the plugin does with them so
+//! the behavior can be asserted on in unit tests. The [`PluginTester`][super::PluginTester] uses the
+//! init and process contexts internally, but they can also be passed to a plugin directly.
+
+use parking_lot::Mutex;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use crate::audio_setup::BufferConfig;
+use crate::context::gui::{GuiContext, StateSlot, TailLength};
+use crate::context::init::InitContext;
+use crate::context::process::{ProcessContext, Transport};
+use crate::context::{HostInfo, PluginApi};
+use crate::midi::learn::MidiLearn;
+use crate::midi::PluginNoteEvent;
+use crate::params::internals::ParamPtr;
+use crate::params::Params;
+use crate::plugin::Plugin;
+use crate::util::permit_alloc;
+use crate::wrapper::state::{self, FactoryPreset, PluginState};
+use crate::wrapper::util::state_slots::StateSlots;
+
+/// An [`InitContext`] that records the latency and voice capacity changes and the background tasks
+/// submitted by the plugin. The tasks are not executed.
+pub struct MockInitContext<P: Plugin> {
+    /// Returned from [`InitContext::host_info()`], set with
+    /// [`set_host_info()`][Self::set_host_info()].
+    host_info: HostInfo,
+
+    latency_changes: RefCell<Vec<u32>>,
+    voice_capacity_changes: RefCell<Vec<u32>>,
+    background_tasks: RefCell<Vec<P::BackgroundTask>>,
+}
+
+/// A [`ProcessContext`] with a programmable transport. Input events are queued with
+/// [`push_event()`][Self::push_event()], and the events sent by the plugin, the latency and voice
+/// capacity changes, and the submitted tasks are recorded in the order they happened. Tasks are not
+/// executed.
+pub struct MockProcessContext<P: Plugin> {
+    transport: Transport,
+
+    /// Events returned by [`ProcessContext::next_event()`].
+    input_events: VecDeque<PluginNoteEvent<P>>,
+    /// Events passed to [`ProcessContext::send_event()`], in the order they were sent.
+    output_events: Vec<PluginNoteEvent<P>>,
+    /// Messages returned by [`ProcessContext::next_gui_message()`].
+    gui_messages: VecDeque<P::GuiMessage>,
+
+    /// Parameter changes made through [`ProcessContext::set_parameter()`] and the related
+    /// functions, in the order they were made.
+    param_changes: Vec<ProcessParamChange>,
+
+    latency_changes: RefCell<Vec<u32>>,
+    voice_capacity_changes: RefCell<Vec<u32>>,
+    gain_reduction_db: Cell<f32>,
+    background_tasks: RefCell<Vec<P::BackgroundTask>>,
+    gui_tasks: RefCell<Vec<P::BackgroundTask>>,
+    editor_events: RefCell<Vec<P::EditorEvent>>,
+}
+
+/// A parameter change made by the plugin through a [`MockProcessContext`]. Compare the parameter
+/// pointers to [`Param::as_ptr()`][crate::prelude::Param::as_ptr()] to find out which parameter was
+/// changed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProcessParamChange {
+    BeginSetParameter(ParamPtr),
+    SetParameterNormalized(ParamPtr, f32),
+    EndSetParameter(ParamPtr),
+}
+
+/// A [`GuiContext`] for testing editors and preset handling without a host. Parameter changes are
+/// applied to the parameters immediately without touching their smoothers, and every call made
+/// through the context is recorded as a [`GuiContextCall`]. States are saved and restored using the
+/// same (de)serialization as the plugin wrappers.
+pub struct MockGuiContext<P: Plugin> {
+    params: Arc<dyn Params>,
+    calls: Mutex<Vec<GuiContextCall>>,
+    state_slots: StateSlots,
+    midi_learn: MidiLearn,
+
+    _phantom: PhantomData<fn() -> P>,
+}
+
+/// A call made through a [`MockGuiContext`]. Parameters are identified by their string IDs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GuiContextCall {
+    BeginSetParameter(String),
+    SetParameterNormalized(String, f32),
+    EndSetParameter(String),
+    BeginGroupEdit,
+    EndGroupEdit,
+    StartUndoBlock(String),
+    EndUndoBlock,
+    RequestResize,
+    SetState,
+    SwitchStateSlot(StateSlot),
+    CopyStateToOtherSlot,
+}
+
+impl<P: Plugin> Default for MockInitContext<P> {
+    fn default() -> Self {
+        Self {
+            host_info: HostInfo::default(),
+
+            latency_changes: RefCell::default(),
+            voice_capacity_changes: RefCell::default(),
+            background_tasks: RefCell::default(),
+        }
+    }
+}
+
+impl<P: Plugin> MockInitContext<P> {
+    /// Create an empty init context.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Change the host information returned from [`InitContext::host_info()`]. Useful for testing
+    /// host-specific workarounds. This defaults to an unnamed host without any capabilities.
+    pub fn set_host_info(&mut self, host_info: HostInfo) {
+        self.host_info = host_info;
+    }
+
+    /// All values passed to [`InitContext::set_latency_samples()`], in order.
+    pub fn latency_changes(&self) -> Vec<u32> {
+        self.latency_changes.borrow().clone()
+    }
+
+    /// All values passed to [`InitContext::set_current_voice_capacity()`], in order.
+    pub fn voice_capacity_changes(&self) -> Vec<u32> {
+        self.voice_capacity_changes.borrow().clone()
+    }
+
+    /// Take the tasks passed to [`InitContext::execute()`] so far.
+    pub fn take_background_tasks(&mut self) -> Vec<P::BackgroundTask> {
+        self.background_tasks.take()
+    }
+}
+
+impl<P: Plugin> MockProcessContext<P> {
+    /// Create a process context with a stopped transport and no position, tempo, or time signature
+    /// information.
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            transport: Transport::new(sample_rate),
+
+            input_events: VecDeque::new(),
+            output_events: Vec::new(),
+            gui_messages: VecDeque::new(),
+
+            param_changes: Vec::new(),
+
+            latency_changes: RefCell::default(),
+            voice_capacity_changes: RefCell::default(),
+            gain_reduction_db: Cell::new(0.0),
+            background_tasks: RefCell::default(),
+            gui_tasks: RefCell::default(),
+            editor_events: RefCell::default(),
+        }
+    }
+
+    /// Get mutable access to the transport's public fields, like the tempo and the playing state.
+    /// Use the setters on this object to change the transport's position.
+    pub fn transport_mut(&mut self) -> &mut Transport {
+        &mut self.transport
+    }
+
+    /// Set the transport's position in samples. The position in seconds and beats is derived from
+    /// this unless they have been set explicitly.
+    pub fn set_pos_samples(&mut self, pos_samples: Option<i64>) {
+        self.transport.pos_samples = pos_samples;
+    }
+
+    /// Set the transport's position in seconds.
+    pub fn set_pos_seconds(&mut self, pos_seconds: Option<f64>) {
+        self.transport.pos_seconds = pos_seconds;
+    }
+
+    /// Set the transport's position in quarter notes.
+    pub fn set_pos_beats(&mut self, pos_beats: Option<f64>) {
+        self.transport.pos_beats = pos_beats;
+    }
+
+    /// Explicitly set the current bar's start position in quarter notes and its bar number. These
+    /// are otherwise derived from the position and the time signature.
+    pub fn set_bar(&mut self, bar: Option<(f64, i32)>) {
+        self.transport.bar_start_pos_beats = bar.map(|(start_beats, _)| start_beats);
+        self.transport.bar_number = bar.map(|(_, number)| number);
+    }
+
+    /// Set the active loop range in samples.
+    pub fn set_loop_range_samples(&mut self, loop_range: Option<(i64, i64)>) {
+        self.transport.loop_range_samples = loop_range;
+    }
+
+    /// Set the active loop range in quarter notes.
+    pub fn set_loop_range_beats(&mut self, loop_range: Option<(f64, f64)>) {
+        self.transport.loop_range_beats = loop_range;
+    }
+
+    /// Set the flags returned by [`Transport::started_playing()`] and
+    /// [`Transport::stopped_playing()`]. Which of the two is set depends on the transport's
+    /// `playing` field.
+    pub fn set_playing_changed(&mut self, playing_changed: bool) {
+        self.transport.playing_changed = playing_changed;
+    }
+
+    /// Set the flag returned by [`Transport::position_jumped()`].
+    pub fn set_position_jumped(&mut self, position_jumped: bool) {
+        self.transport.position_jumped = position_jumped;
+    }
+
+    /// Add an event to the end of the queue read by [`ProcessContext::next_event()`]. Like a real
+    /// host, events should be pushed in timing order.
+    pub fn push_event(&mut self, event: PluginNoteEvent<P>) {
+        self.input_events.push_back(event);
+    }
+
+    /// The events in the input queue that the plugin has not read yet.
+    pub fn remaining_input_events(&self) -> impl Iterator<Item = &PluginNoteEvent<P>> {
+        self.input_events.iter()
+    }
+
+    /// Remove any unread events from the input queue.
+    pub fn clear_input_events(&mut self) {
+        self.input_events.clear();
+    }
+
+    /// Add a message to the end of the queue read by [`ProcessContext::next_gui_message()`], as if
+    /// it was sent by the editor.
+    pub fn push_gui_message(&mut self, message: P::GuiMessage) {
+        self.gui_messages.push_back(message);
+    }
+
+    /// The events sent by the plugin so far, in the order they were sent.
+    pub fn output_events(&self) -> &[PluginNoteEvent<P>] {
+        &self.output_events
+    }
+
+    /// Take the events sent by the plugin so far, clearing the list.
+    pub fn take_output_events(&mut self) -> Vec<PluginNoteEvent<P>> {
+        std::mem::take(&mut self.output_events)
+    }
+
+    /// The parameter changes made by the plugin so far, in the order they were made.
+    pub fn param_changes(&self) -> &[ProcessParamChange] {
+        &self.param_changes
+    }
+
+    /// Take the parameter changes made by the plugin so far, clearing the list.
+    pub fn take_param_changes(&mut self) -> Vec<ProcessParamChange> {
+        std::mem::take(&mut self.param_changes)
+    }
+
+    /// All values passed to [`ProcessContext::set_latency_samples()`], in order.
+    pub fn latency_changes(&self) -> Vec<u32> {
+        self.latency_changes.borrow().clone()
+    }
+
+    /// All values passed to [`ProcessContext::set_current_voice_capacity()`], in order.
+    pub fn voice_capacity_changes(&self) -> Vec<u32> {
+        self.voice_capacity_changes.borrow().clone()
+    }
+
+    /// The last value passed to [`ProcessContext::set_gain_reduction()`], or zero if the plugin
+    /// never reported its gain reduction.
+    pub fn gain_reduction(&self) -> f32 {
+        self.gain_reduction_db.get()
+    }
+
+    /// Take the tasks passed to [`ProcessContext::execute_background()`] so far.
+    pub fn take_background_tasks(&mut self) -> Vec<P::BackgroundTask> {
+        self.background_tasks.take()
+    }
+
+    /// Take the tasks passed to [`ProcessContext::execute_gui()`] so far.
+    pub fn take_gui_tasks(&mut self) -> Vec<P::BackgroundTask> {
+        self.gui_tasks.take()
+    }
+
+    /// Take the events passed to [`ProcessContext::send_to_editor()`] so far. Unlike a real host,
+    /// no events are dropped.
+    pub fn take_editor_events(&mut self) -> Vec<P::EditorEvent> {
+        self.editor_events.take()
+    }
+}
+
+impl<P: Plugin> MockGuiContext<P> {
+    /// Create a GUI context for a plugin's parameters object, as returned by [`Plugin::params()`].
+    pub fn new(params: Arc<dyn Params>) -> Self {
+        let midi_learn = MidiLearn::new(
+            params
+                .param_map()
+                .into_iter()
+                .map(|(param_id, param_ptr, _)| (param_id, param_ptr)),
+        );
+
+        Self {
+            params,
+            calls: Mutex::new(Vec::new()),
+            state_slots: StateSlots::default(),
+            midi_learn,
+
+            _phantom: PhantomData,
+        }
+    }
+
+    /// All calls made through this context so far, in order.
+    pub fn calls(&self) -> Vec<GuiContextCall> {
+        self.calls.lock().clone()
+    }
+
+    /// Take the calls made through this context so far, clearing the list.
+    pub fn take_calls(&self) -> Vec<GuiContextCall> {
+        std::mem::take(&mut *self.calls.lock())
+    }
+
+    /// Simulate the wrapper receiving a MIDI CC, for testing MIDI learn. If the CC is mapped to a
+    /// parameter, either already or because MIDI learn was active, then the parameter is set to the
+    /// CC's value and this returns `true`.
+    pub fn receive_midi_cc(&self, channel: u8, cc: u8, value: f32) -> bool {
+        match self.midi_learn.handle_cc(channel, cc, value) {
+            Some((param_ptr, normalized_value)) => {
+                unsafe { param_ptr.set_normalized_value(normalized_value) };
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn param_id(&self, param: ParamPtr) -> String {
+        self.params
+            .param_map()
+            .into_iter()
+            .find(|(_, param_ptr, _)| *param_ptr == param)
+            .map(|(param_id, _, _)| param_id)
+            .unwrap_or_else(|| panic!("Unknown parameter {param:?}"))
+    }
+
+    fn current_state(&self) -> PluginState {
+        let param_map = self.params.param_map();
+        unsafe {
+            state::serialize_object::<P>(
+                self.params.clone(),
+                param_map
+                    .iter()
+                    .map(|(param_id, param_ptr, _)| (param_id, *param_ptr)),
+                Some(&self.midi_learn),
+            )
+        }
+    }
+
+    fn restore_state(&self, mut state: PluginState) {
+        let param_map = self.params.param_map();
+        let success = unsafe {
+            state::deserialize_object::<P>(
+                &mut state,
+                self.params.clone(),
+                |param_id| {
+                    param_map
+                        .iter()
+                        .find(|(id, _, _)| id == param_id)
+                        .map(|(_, param_ptr, _)| *param_ptr)
+                },
+                None,
+                Some(&self.midi_learn),
+            )
+        };
+        nih_debug_assert!(success, "Could not restore the plugin's state");
+    }
+}
+
+impl<P: Plugin> InitContext<P> for MockInitContext<P> {
+    fn plugin_api(&self) -> PluginApi {
+        PluginApi::Standalone
+    }
+
+    fn host_info(&self) -> HostInfo {
+        self.host_info.clone()
+    }
+
+    fn execute(&self, task: P::BackgroundTask) {
+        self.background_tasks.borrow_mut().push(task);
+    }
+
+    fn set_latency_samples(&self, samples: u32) {
+        self.latency_changes.borrow_mut().push(samples);
+    }
+
+    fn set_current_voice_capacity(&self, capacity: u32) {
+        self.voice_capacity_changes.borrow_mut().push(capacity);
+    }
+}
+
+impl<P: Plugin> ProcessContext<P> for MockProcessContext<P> {
+    fn plugin_api(&self) -> PluginApi {
+        PluginApi::Standalone
+    }
+
+    fn execute_background(&self, task: P::BackgroundTask) {
+        // Recording these is allowed to allocate since a real host would not need to
+        permit_alloc(|| self.background_tasks.borrow_mut().push(task));
+    }
+
+    fn execute_gui(&self, task: P::BackgroundTask) {
+        permit_alloc(|| self.gui_tasks.borrow_mut().push(task));
+    }
+
+    #[inline]
+    fn next_event(&mut self) -> Option<PluginNoteEvent<P>> {
+        self.input_events.pop_front()
+    }
+
+    fn send_event(&mut self, event: PluginNoteEvent<P>) {
+        // Unlike a real host, the number of output events is not bounded here
+        permit_alloc(|| self.output_events.push(event));
+    }
+
+    #[inline]
+    fn next_gui_message(&mut self) -> Option<P::GuiMessage> {
+        self.gui_messages.pop_front()
+    }
+
+    fn send_to_editor(&self, event: P::EditorEvent) {
+        permit_alloc(|| self.editor_events.borrow_mut().push(event));
+    }
+
+    fn set_latency_samples(&self, samples: u32) {
+        permit_alloc(|| self.latency_changes.borrow_mut().push(samples));
+    }
+
+    fn set_current_voice_capacity(&self, capacity: u32) {
+        permit_alloc(|| self.voice_capacity_changes.borrow_mut().push(capacity));
+    }
+
+    fn set_gain_reduction(&self, db: f32) {
+        self.gain_reduction_db.set(db);
+    }
+
+    fn transport(&self) -> &Transport {
+        &self.transport
+    }
+
+    unsafe fn raw_begin_set_parameter(&mut self, param: ParamPtr) {
+        permit_alloc(|| {
+            self.param_changes
+                .push(ProcessParamChange::BeginSetParameter(param))
+        });
+    }
+
+    unsafe fn raw_set_parameter_normalized(&mut self, param: ParamPtr, normalized: f32) {
+        // The wrappers only apply the new value after the process function returns, but this
+        // applies it immediately
+        param.set_normalized_value(normalized);
+        permit_alloc(|| {
+            self.param_changes
+                .push(ProcessParamChange::SetParameterNormalized(
+                    param, normalized,
+                ))
+        });
+    }
+
+    unsafe fn raw_end_set_parameter(&mut self, param: ParamPtr) {
+        permit_alloc(|| {
+            self.param_changes
+                .push(ProcessParamChange::EndSetParameter(param))
+        });
+    }
+}
+
+impl<P: Plugin> GuiContext for MockGuiContext<P> {
+    fn plugin_api(&self) -> PluginApi {
+        PluginApi::Standalone
+    }
+
+    fn request_resize(&self) -> bool {
+        self.calls.lock().push(GuiContextCall::RequestResize);
+
+        true
+    }
+
+    unsafe fn raw_begin_set_parameter(&self, param: ParamPtr) {
+        let param_id = self.param_id(param);
+        self.calls
+            .lock()
+            .push(GuiContextCall::BeginSetParameter(param_id));
+    }
+
+    unsafe fn raw_set_parameter_normalized(&self, param: ParamPtr, normalized: f32) {
+        let param_id = self.param_id(param);
+        param.set_normalized_value(normalized);
+        self.calls
+            .lock()
+            .push(GuiContextCall::SetParameterNormalized(param_id, normalized));
+    }
+
+    unsafe fn raw_end_set_parameter(&self, param: ParamPtr) {
+        let param_id = self.param_id(param);
+        self.calls
+            .lock()
+            .push(GuiContextCall::EndSetParameter(param_id));
+    }
+
+    fn begin_group_edit(&self) {
+        self.calls.lock().push(GuiContextCall::BeginGroupEdit);
+    }
+
+    fn end_group_edit(&self) {
+        self.calls.lock().push(GuiContextCall::EndGroupEdit);
+    }
+
+    fn start_undo_block(&self, name: &str) {
+        self.calls
+            .lock()
+            .push(GuiContextCall::StartUndoBlock(String::from(name)));
+    }
+
+    fn end_undo_block(&self) {
+        self.calls.lock().push(GuiContextCall::EndUndoBlock);
+    }
+
+    fn get_state(&self) -> PluginState {
+        self.current_state()
+    }
+
+    fn set_state(&self, state: PluginState) {
+        self.calls.lock().push(GuiContextCall::SetState);
+        self.restore_state(state);
+    }
+
+    fn factory_presets(&self) -> &'static [FactoryPreset] {
+        P::FACTORY_PRESETS
+    }
+
+    fn active_state_slot(&self) -> StateSlot {
+        self.state_slots.active_slot()
+    }
+
+    fn switch_state_slot(&self, slot: StateSlot) {
+        self.calls
+            .lock()
+            .push(GuiContextCall::SwitchStateSlot(slot));
+        if let Some(new_state) = self.state_slots.switch(slot, || self.current_state()) {
+            self.restore_state(new_state);
+        }
+    }
+
+    fn copy_state_to_other_slot(&self) {
+        self.calls.lock().push(GuiContextCall::CopyStateToOtherSlot);
+        self.state_slots.copy_to_other_slot(self.current_state());
+    }
+
+    fn midi_learn(&self) -> &MidiLearn {
+        &self.midi_learn
+    }
+
+    // There is no plugin instance behind this context, so these report an uninitialized plugin
+    fn buffer_config(&self) -> Option<BufferConfig> {
+        None
+    }
+
+    fn latency_samples(&self) -> u32 {
+        0
+    }
+
+    fn tail(&self) -> TailLength {
+        TailLength::None
+    }
+}
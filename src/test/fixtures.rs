@@ -0,0 +1,143 @@
+//! A plugin for NIH-plug's own unit tests, so the tests for the different modules don't all need to
+//! define their own plugin. The plugin's behavior can be changed through the hooks in
+//! [`GenericTestPlugin`]'s fields.
+
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+use crate::prelude::*;
+
+/// The test plugin used by most tests.
+pub(crate) type TestPlugin = GenericTestPlugin<false>;
+/// The test plugin with [`Plugin::ANALYZER_MODE`] enabled.
+pub(crate) type AnalyzerTestPlugin = GenericTestPlugin<true>;
+
+/// Applies [`TestParams::gain`] and sends every incoming note on event back to the host. Setting
+/// `ANALYZER_MODE` enables [`Plugin::ANALYZER_MODE`]. The plugin supports a mono and a stereo
+/// layout, in that order, and it catches panics.
+#[derive(Default)]
+pub(crate) struct GenericTestPlugin<const ANALYZER_MODE: bool> {
+    pub params: Arc<TestParams>,
+
+    /// Called at the end of every process call, after the gain has been applied.
+    pub process_hook: Option<Box<dyn FnMut(&mut Buffer) + Send>>,
+    /// Called from [`Plugin::on_panic()`] with the rescued state.
+    pub on_panic_hook: Option<Box<dyn FnMut(&PluginState) + Send>>,
+
+    pub num_initializations: usize,
+    pub num_sample_rate_changes: usize,
+}
+
+/// A continuous parameter, a stepped parameter, and a bypass parameter.
+pub(crate) struct TestParams {
+    pub gain: FloatParam,
+    pub mode: IntParam,
+    pub bypass: BoolParam,
+}
+
+impl Default for TestParams {
+    fn default() -> Self {
+        Self {
+            gain: FloatParam::new("Gain", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 }),
+            mode: IntParam::new("Mode", 0, IntRange::Linear { min: 0, max: 3 }),
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
+        }
+    }
+}
+
+unsafe impl Params for TestParams {
+    fn param_map(&self) -> Vec<(String, ParamPtr, String)> {
+        vec![
+            (String::from("gain"), self.gain.as_ptr(), String::new()),
+            (String::from("mode"), self.mode.as_ptr(), String::new()),
+            (String::from("bypass"), self.bypass.as_ptr(), String::new()),
+        ]
+    }
+}
+
+impl<const ANALYZER_MODE: bool> Plugin for GenericTestPlugin<ANALYZER_MODE> {
+    const NAME: &'static str = "Test Plugin";
+    const VENDOR: &'static str = "NIH-plug";
+    const URL: &'static str = "";
+    const EMAIL: &'static str = "";
+    const VERSION: &'static str = "0.0.0";
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(1),
+            main_output_channels: NonZeroU32::new(1),
+            ..AudioIOLayout::const_default()
+        },
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(2),
+            main_output_channels: NonZeroU32::new(2),
+            ..AudioIOLayout::const_default()
+        },
+    ];
+    const MIDI_INPUT: MidiConfig = MidiConfig::Basic;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::Basic;
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+    const ANALYZER_MODE: bool = ANALYZER_MODE;
+    const CATCH_PANICS: bool = true;
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+    type GuiMessage = ();
+    type EditorEvent = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        _buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.num_initializations += 1;
+        true
+    }
+
+    fn sample_rate_changed(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        _buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.num_sample_rate_changes += 1;
+        true
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        while let Some(event) = context.next_event() {
+            if let NoteEvent::NoteOn { .. } = event {
+                context.send_event(event);
+            }
+        }
+
+        for channel_samples in buffer.iter_samples() {
+            let gain = self.params.gain.value();
+            for sample in channel_samples {
+                *sample *= gain;
+            }
+        }
+
+        if let Some(process_hook) = &mut self.process_hook {
+            process_hook(buffer);
+        }
+
+        ProcessStatus::Normal
+    }
+
+    fn on_panic(&mut self, state: &PluginState) {
+        if let Some(on_panic_hook) = &mut self.on_panic_hook {
+            on_panic_hook(state);
+        }
+    }
+}
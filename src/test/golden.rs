@@ -0,0 +1,408 @@
+//! Golden file regression tests. A [`Scenario`] describes the input, note events, and automation
+//! for a single render, and [`assert_golden_file()`] compares the rendered output against a
+//! reference file that's stored alongside the tests.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::num::NonZeroU32;
+use std::path::Path;
+
+use super::PluginTester;
+use crate::midi::PluginNoteEvent;
+use crate::plugin::Plugin;
+
+/// Set this environment variable to any value to overwrite the golden files with the current
+/// output instead of comparing against them.
+pub const UPDATE_GOLDEN_FILES_ENV_VAR: &str = "NIH_PLUG_UPDATE_GOLDEN";
+
+/// The WAVE format tag for IEEE floating point samples.
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+
+/// A scripted render for a [`PluginTester`]. All timings are relative to the start of the render.
+pub struct Scenario<P: Plugin> {
+    num_samples: usize,
+    /// The main input, if any. Contains one vector of `num_samples` samples per input channel.
+    main_input: Option<Vec<Vec<f32>>>,
+    events: Vec<PluginNoteEvent<P>>,
+    /// Parameter changes as `(timing, param_id, normalized_value)` tuples.
+    param_changes: Vec<(u32, String, f32)>,
+}
+
+impl<P: Plugin> Scenario<P> {
+    /// A scenario that renders `num_samples` samples with a silent main input.
+    pub fn new(num_samples: usize) -> Self {
+        Self {
+            num_samples,
+            main_input: None,
+            events: Vec::new(),
+            param_changes: Vec::new(),
+        }
+    }
+
+    /// Use these channels as the main input. Every channel must contain exactly `num_samples`
+    /// samples.
+    pub fn with_input(mut self, main_input: Vec<Vec<f32>>) -> Self {
+        self.main_input = Some(main_input);
+        self
+    }
+
+    /// Send a note event to the plugin.
+    pub fn with_event(mut self, event: PluginNoteEvent<P>) -> Self {
+        self.events.push(event);
+        self
+    }
+
+    /// Change a parameter to a normalized value at sample `timing`. See
+    /// [`PluginTester::schedule_parameter_change()`].
+    pub fn with_automation(mut self, timing: u32, param_id: &str, normalized: f32) -> Self {
+        self.param_changes
+            .push((timing, String::from(param_id), normalized));
+        self
+    }
+
+    /// The number of samples rendered by this scenario.
+    pub fn num_samples(&self) -> usize {
+        self.num_samples
+    }
+}
+
+impl<P: Plugin> PluginTester<P> {
+    /// Render a [`Scenario`] in a single process call, and return the main output. Any main output
+    /// channels without a corresponding input channel in the scenario start out silent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the scenario refers to unknown parameters, or if its input channels don't match
+    /// the scenario's length.
+    pub fn render_scenario(&mut self, scenario: &Scenario<P>) -> Vec<Vec<f32>> {
+        for (timing, param_id, normalized) in &scenario.param_changes {
+            self.schedule_parameter_change(*timing, param_id, *normalized);
+        }
+        for event in &scenario.events {
+            self.schedule_event(event.clone());
+        }
+
+        let num_output_channels = self
+            .audio_io_layout
+            .main_output_channels
+            .map(NonZeroU32::get)
+            .unwrap_or(0) as usize;
+        if num_output_channels == 0 {
+            return self.render(scenario.num_samples);
+        }
+
+        let mut main_io: Vec<Vec<f32>> = (0..num_output_channels)
+            .map(|channel_idx| {
+                match scenario
+                    .main_input
+                    .as_ref()
+                    .and_then(|input| input.get(channel_idx))
+                {
+                    Some(input) => {
+                        assert_eq!(
+                            input.len(),
+                            scenario.num_samples,
+                            "Mismatching input length"
+                        );
+                        input.clone()
+                    }
+                    None => vec![0.0; scenario.num_samples],
+                }
+            })
+            .collect();
+        self.process(&mut main_io);
+
+        main_io
+    }
+}
+
+/// Compare `output` against the golden file at `path`, allowing every sample to deviate by up to
+/// `tolerance`. A tolerance of zero requires the output to be bit-exact. If the
+/// [`UPDATE_GOLDEN_FILES_ENV_VAR`] environment variable is set, then the file is written instead.
+/// A missing golden file is an error otherwise, so a test can't silently pass when its reference
+/// file was never committed.
+///
+/// Files with a `.wav` extension are stored as 32-bit floating point WAV files that can be opened in
+/// any audio editor, using `sample_rate` as their sample rate. All other files are stored as raw
+/// planar 32-bit little-endian floats, one channel after the other.
+///
+/// # Panics
+///
+/// Panics with a description of the first mismatching sample if the output does not match the
+/// golden file, or if the file does not exist or could not be read or written.
+pub fn assert_golden_file(
+    output: &[Vec<f32>],
+    sample_rate: f32,
+    path: impl AsRef<Path>,
+    tolerance: f32,
+) {
+    if let Err(err) = check_golden_file(output, sample_rate, path, tolerance) {
+        panic!("{err:#}");
+    }
+}
+
+/// The non-panicking version of [`assert_golden_file()`].
+pub fn check_golden_file(
+    output: &[Vec<f32>],
+    sample_rate: f32,
+    path: impl AsRef<Path>,
+    tolerance: f32,
+) -> Result<()> {
+    let path = path.as_ref();
+    let num_samples = output.first().map(Vec::len).unwrap_or(0);
+    anyhow::ensure!(
+        output.iter().all(|channel| channel.len() == num_samples),
+        "All output channels need to have the same length"
+    );
+
+    if std::env::var_os(UPDATE_GOLDEN_FILES_ENV_VAR).is_some() {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Could not create '{}'", parent.display()))?;
+        }
+        fs::write(path, encode(output, sample_rate, path))
+            .with_context(|| format!("Could not write '{}'", path.display()))?;
+
+        eprintln!("Wrote golden file '{}'", path.display());
+        return Ok(());
+    }
+
+    anyhow::ensure!(
+        path.exists(),
+        "The golden file '{}' does not exist, run the tests with {UPDATE_GOLDEN_FILES_ENV_VAR}=1 \
+         to create it",
+        path.display()
+    );
+    let data = fs::read(path).with_context(|| format!("Could not read '{}'", path.display()))?;
+    let expected = if is_wav(path) {
+        decode_wav(&data)
+    } else {
+        decode_raw(&data, output.len())
+    }
+    .with_context(|| format!("Could not parse '{}'", path.display()))?;
+
+    anyhow::ensure!(
+        expected.len() == output.len(),
+        "'{}' contains {} channels, but the output has {} channels",
+        path.display(),
+        expected.len(),
+        output.len()
+    );
+    let expected_num_samples = expected.first().map(Vec::len).unwrap_or(0);
+    anyhow::ensure!(
+        expected_num_samples == num_samples,
+        "'{}' contains {} samples per channel, but the output has {} samples per channel",
+        path.display(),
+        expected_num_samples,
+        num_samples
+    );
+
+    let mut first_mismatch = None;
+    let mut max_difference = 0.0f32;
+    let mut num_mismatches = 0usize;
+    for (channel_idx, (expected_channel, output_channel)) in expected.iter().zip(output).enumerate()
+    {
+        for (sample_idx, (&expected_sample, &output_sample)) in
+            expected_channel.iter().zip(output_channel).enumerate()
+        {
+            // NaN and infinity values only match themselves
+            if expected_sample.to_bits() == output_sample.to_bits() {
+                continue;
+            }
+
+            let difference = (expected_sample - output_sample).abs();
+            if difference <= tolerance {
+                continue;
+            }
+
+            num_mismatches += 1;
+            max_difference = max_difference.max(difference);
+            first_mismatch.get_or_insert((channel_idx, sample_idx, expected_sample, output_sample));
+        }
+    }
+
+    match first_mismatch {
+        Some((channel_idx, sample_idx, expected_sample, output_sample)) => anyhow::bail!(
+            "The output does not match '{}' within a tolerance of {tolerance}. {num_mismatches} \
+             samples differ with a maximum difference of {max_difference}. The first mismatch is \
+             on channel {channel_idx} at sample {sample_idx}: expected {expected_sample}, got \
+             {output_sample}. Set the {UPDATE_GOLDEN_FILES_ENV_VAR} environment variable to \
+             update the golden file.",
+            path.display()
+        ),
+        None => Ok(()),
+    }
+}
+
+fn is_wav(path: &Path) -> bool {
+    path.extension()
+        .map(|extension| extension.eq_ignore_ascii_case("wav"))
+        .unwrap_or(false)
+}
+
+fn encode(output: &[Vec<f32>], sample_rate: f32, path: &Path) -> Vec<u8> {
+    if is_wav(path) {
+        encode_wav(output, sample_rate)
+    } else {
+        output
+            .iter()
+            .flatten()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect()
+    }
+}
+
+fn decode_raw(data: &[u8], num_channels: usize) -> Result<Vec<Vec<f32>>> {
+    anyhow::ensure!(
+        data.len() % 4 == 0,
+        "The file is not a multiple of 4 bytes long"
+    );
+    let samples: Vec<f32> = data
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+        .collect();
+    if num_channels == 0 {
+        anyhow::ensure!(samples.is_empty(), "Expected an empty file");
+        return Ok(Vec::new());
+    }
+    anyhow::ensure!(
+        samples.len() % num_channels == 0,
+        "The file's length does not match the output's {num_channels} channels"
+    );
+
+    Ok(samples
+        .chunks_exact(samples.len() / num_channels)
+        .map(<[f32]>::to_vec)
+        .collect())
+}
+
+/// Encode the channels as a 32-bit floating point WAV file with interleaved samples.
+fn encode_wav(output: &[Vec<f32>], sample_rate: f32) -> Vec<u8> {
+    let num_channels = output.len() as u16;
+    let num_samples = output.first().map(Vec::len).unwrap_or(0);
+    let sample_rate = sample_rate.round() as u32;
+    let block_align = num_channels as u32 * 4;
+    let data_len = num_samples as u32 * block_align;
+
+    let mut data = Vec::with_capacity(44 + data_len as usize);
+    data.extend_from_slice(b"RIFF");
+    data.extend_from_slice(&(36 + data_len).to_le_bytes());
+    data.extend_from_slice(b"WAVE");
+
+    data.extend_from_slice(b"fmt ");
+    data.extend_from_slice(&16u32.to_le_bytes());
+    data.extend_from_slice(&WAVE_FORMAT_IEEE_FLOAT.to_le_bytes());
+    data.extend_from_slice(&num_channels.to_le_bytes());
+    data.extend_from_slice(&sample_rate.to_le_bytes());
+    data.extend_from_slice(&(sample_rate * block_align).to_le_bytes());
+    data.extend_from_slice(&(block_align as u16).to_le_bytes());
+    data.extend_from_slice(&32u16.to_le_bytes());
+
+    data.extend_from_slice(b"data");
+    data.extend_from_slice(&data_len.to_le_bytes());
+    for sample_idx in 0..num_samples {
+        for channel in output {
+            data.extend_from_slice(&channel[sample_idx].to_le_bytes());
+        }
+    }
+
+    data
+}
+
+/// Decode a 32-bit floating point WAV file written by [`encode_wav()`] into planar channels. Other
+/// sample formats are not supported.
+fn decode_wav(data: &[u8]) -> Result<Vec<Vec<f32>>> {
+    anyhow::ensure!(
+        data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WAVE",
+        "Not a WAV file"
+    );
+
+    let mut num_channels = None;
+    let mut samples = None;
+    let mut chunks = &data[12..];
+    while chunks.len() >= 8 {
+        let chunk_id = &chunks[0..4];
+        let chunk_len = u32::from_le_bytes(chunks[4..8].try_into().unwrap()) as usize;
+        let chunk = chunks
+            .get(8..8 + chunk_len)
+            .context("Truncated WAV chunk")?;
+
+        match chunk_id {
+            b"fmt " => {
+                anyhow::ensure!(chunk.len() >= 16, "Invalid fmt chunk");
+                let format = u16::from_le_bytes(chunk[0..2].try_into().unwrap());
+                let bits_per_sample = u16::from_le_bytes(chunk[14..16].try_into().unwrap());
+                anyhow::ensure!(
+                    format == WAVE_FORMAT_IEEE_FLOAT && bits_per_sample == 32,
+                    "Only 32-bit floating point WAV files are supported"
+                );
+
+                num_channels = Some(u16::from_le_bytes(chunk[2..4].try_into().unwrap()) as usize);
+            }
+            b"data" => {
+                samples = Some(
+                    chunk
+                        .chunks_exact(4)
+                        .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+                        .collect::<Vec<f32>>(),
+                );
+            }
+            _ => (),
+        }
+
+        // Chunks are padded to an even length
+        let padded_len = (8 + chunk_len + 1) & !1;
+        chunks = chunks.get(padded_len..).unwrap_or_default();
+    }
+
+    let num_channels = num_channels.context("Missing fmt chunk")?;
+    let samples = samples.context("Missing data chunk")?;
+    if num_channels == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut channels = vec![Vec::with_capacity(samples.len() / num_channels); num_channels];
+    for frame in samples.chunks_exact(num_channels) {
+        for (channel, &sample) in channels.iter_mut().zip(frame) {
+            channel.push(sample);
+        }
+    }
+
+    Ok(channels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wav_round_trip() {
+        let output = vec![vec![0.0, 0.5, -1.0], vec![1.0, f32::NAN, 0.25]];
+        let decoded = decode_wav(&encode_wav(&output, 48000.0)).unwrap();
+
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0], output[0]);
+        assert!(decoded[1][1].is_nan());
+        assert_eq!(decoded[1][2], 0.25);
+    }
+
+    #[test]
+    fn tolerance() {
+        let path = std::env::temp_dir().join(format!(
+            "nih_plug_golden_{}_{:?}.raw",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        let _ = fs::remove_file(&path);
+
+        let output = vec![vec![0.0, 0.5, 1.0]];
+        assert!(check_golden_file(&output, 44100.0, &path, 0.0).is_err());
+
+        fs::write(&path, encode(&output, 44100.0, &path)).unwrap();
+        check_golden_file(&output, 44100.0, &path, 0.0).unwrap();
+        check_golden_file(&[vec![0.0, 0.501, 1.0]], 44100.0, &path, 0.01).unwrap();
+        assert!(check_golden_file(&[vec![0.0, 0.6, 1.0]], 44100.0, &path, 0.01).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
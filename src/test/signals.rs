@@ -0,0 +1,57 @@
+//! Input signals for [`PluginTester::process()`][super::PluginTester::process()] and
+//! [`Scenario::with_input()`][super::Scenario::with_input()]. These all return one vector of
+//! samples per channel, using the generators from [`util`][crate::util].
+
+use crate::util::{PinkNoise, SineSweep, WhiteNoise};
+
+/// A unit impulse at the first sample on every channel, followed by silence. Useful for capturing
+/// a plugin's impulse response.
+pub fn impulse(num_channels: usize, num_samples: usize) -> Vec<Vec<f32>> {
+    let mut channel = vec![0.0; num_samples];
+    if let Some(first_sample) = channel.first_mut() {
+        *first_sample = 1.0;
+    }
+
+    vec![channel; num_channels]
+}
+
+/// An exponential sine sweep from `start_hz` to `end_hz` over `num_samples` samples, identical on
+/// every channel. See [`SineSweep`].
+pub fn sine_sweep(
+    num_channels: usize,
+    num_samples: usize,
+    start_hz: f32,
+    end_hz: f32,
+    sample_rate: f32,
+) -> Vec<Vec<f32>> {
+    let mut channel = vec![0.0; num_samples];
+    SineSweep::new(start_hz, end_hz, num_samples, sample_rate).process(&mut channel);
+
+    vec![channel; num_channels]
+}
+
+/// Deterministic white noise in `[-1, 1]`. Every channel contains uncorrelated noise, and the same
+/// seed always produces the same signal.
+pub fn white_noise(num_channels: usize, num_samples: usize, seed: u32) -> Vec<Vec<f32>> {
+    let mut noise = WhiteNoise::new(seed);
+    (0..num_channels)
+        .map(|_| {
+            let mut channel = vec![0.0; num_samples];
+            noise.process(&mut channel);
+            channel
+        })
+        .collect()
+}
+
+/// Deterministic pink noise. Every channel contains uncorrelated noise, and the same seed always
+/// produces the same signal. See [`PinkNoise`].
+pub fn pink_noise(num_channels: usize, num_samples: usize, seed: u32) -> Vec<Vec<f32>> {
+    let mut noise = PinkNoise::new(seed);
+    (0..num_channels)
+        .map(|_| {
+            let mut channel = vec![0.0; num_samples];
+            noise.process(&mut channel);
+            channel
+        })
+        .collect()
+}
@@ -0,0 +1,286 @@
+//! A randomized stress test that does the kinds of things only unusual hosts do. See
+//! [`stress_test()`].
+
+use std::collections::VecDeque;
+use std::num::NonZeroU32;
+use std::panic::{self, AssertUnwindSafe};
+
+use super::PluginTester;
+use crate::audio_setup::{AudioIOLayout, BufferConfig, ProcessMode};
+use crate::plugin::{Plugin, ProcessStatus};
+use crate::util::Xorshift;
+
+/// The number of recent operations printed when the stress test fails.
+const HISTORY_LEN: usize = 16;
+
+/// Normalized values a misbehaving host might send. The plugin should never see anything outside
+/// of `[0, 1]`, so these are all expected to be clamped.
+const OUT_OF_RANGE_VALUES: [f32; 6] = [-1.0, 2.0, -1.0e9, 1.0e9, f32::INFINITY, f32::NEG_INFINITY];
+
+/// Settings for [`stress_test()`].
+#[derive(Debug, Clone)]
+pub struct StressTestConfig {
+    /// The seed for the random number generator. A failing run can be reproduced by running it
+    /// again with the same seed.
+    pub seed: u64,
+    /// The number of random operations to perform.
+    pub num_iterations: usize,
+    /// The largest maximum buffer size the plugin may be initialized with.
+    pub max_buffer_size: u32,
+    /// The sample rates the plugin may be initialized with.
+    pub sample_rates: Vec<f32>,
+    /// Fail if the plugin outputs NaN or infinite samples.
+    pub check_output: bool,
+}
+
+impl Default for StressTestConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0x5eed,
+            num_iterations: 1000,
+            max_buffer_size: 2048,
+            sample_rates: vec![22050.0, 44100.0, 48000.0, 96000.0, 192000.0],
+            check_output: true,
+        }
+    }
+}
+
+/// A single operation performed by the stress test.
+#[derive(Debug, Clone)]
+enum Operation {
+    /// Process a buffer with this many samples of noise.
+    Process(usize),
+    /// Deactivate the plugin and initialize it again using the layout at this index in
+    /// [`Plugin::AUDIO_IO_LAYOUTS`] and a new buffer config.
+    Reinitialize(usize, BufferConfig),
+    /// Save the plugin's state and immediately load it again.
+    SaveLoadState,
+    /// Immediately set a parameter to a (possibly out of range) normalized value.
+    SetParameter(String, f32),
+    /// Automate a parameter to a (possibly out of range) normalized value at a sample offset in the
+    /// next buffer.
+    AutomateParameter(u32, String, f32),
+}
+
+/// Hammer a plugin with random buffer sizes (including empty and single sample buffers), random
+/// audio IO layouts and buffer configurations, rapid state saving and loading, and out of range
+/// parameter values. This catches the class of bugs that only show up in exotic hosts, like
+/// indexing out of bounds on empty buffers or not handling sample rate changes.
+///
+/// The operations are generated from [`StressTestConfig::seed`], so runs are reproducible.
+///
+/// # Panics
+///
+/// Panics if the plugin panics, returns [`ProcessStatus::Error`], or outputs non-finite samples
+/// while [`StressTestConfig::check_output`] is enabled. The seed and the most recent operations are
+/// printed before panicking.
+pub fn stress_test<P: Plugin>(config: &StressTestConfig) {
+    assert!(
+        config.max_buffer_size > 0,
+        "The maximum buffer size cannot be 0"
+    );
+    assert!(
+        !config.sample_rates.is_empty(),
+        "No sample rates were specified"
+    );
+
+    let mut rng = Xorshift::new(config.seed);
+    let mut history: VecDeque<Operation> = VecDeque::with_capacity(HISTORY_LEN);
+
+    let layout = |idx: usize| {
+        P::AUDIO_IO_LAYOUTS
+            .get(idx)
+            .copied()
+            .unwrap_or_else(AudioIOLayout::const_default)
+    };
+    let mut tester = match PluginTester::<P>::new(
+        layout(0),
+        BufferConfig {
+            sample_rate: config.sample_rates[0],
+            min_buffer_size: None,
+            max_buffer_size: config.max_buffer_size,
+            process_mode: ProcessMode::Realtime,
+        },
+    ) {
+        Some(tester) => tester,
+        None => fail(config.seed, &history, "The plugin failed to initialize"),
+    };
+
+    // The parameter map is a hash map, so this needs to be sorted to keep runs reproducible
+    let mut param_ids: Vec<String> = tester.param_id_to_ptr.keys().cloned().collect();
+    param_ids.sort();
+
+    for _ in 0..config.num_iterations {
+        let operation = random_operation::<P>(&mut rng, config, &tester, &param_ids);
+        if history.len() == HISTORY_LEN {
+            history.pop_front();
+        }
+        history.push_back(operation.clone());
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| match operation {
+            Operation::Process(num_samples) => {
+                let (status, output_is_finite) = process_noise(&mut tester, &mut rng, num_samples);
+                if let ProcessStatus::Error(err) = status {
+                    return Err(format!("The plugin returned an error: {err}"));
+                }
+                if config.check_output && !output_is_finite {
+                    return Err(String::from("The plugin produced non-finite output"));
+                }
+
+                Ok(())
+            }
+            Operation::Reinitialize(layout_idx, buffer_config) => {
+                // Plugins are allowed to reject configurations, in which case the test keeps
+                // reinitializing until it succeeds
+                tester.reinitialize(layout(layout_idx), buffer_config);
+                Ok(())
+            }
+            Operation::SaveLoadState => {
+                let state = tester.save_state();
+                if tester.load_state(state) {
+                    Ok(())
+                } else {
+                    Err(String::from("The plugin's own state could not be restored"))
+                }
+            }
+            Operation::SetParameter(ref param_id, normalized) => {
+                tester.set_parameter_normalized(param_id, normalized);
+                Ok(())
+            }
+            Operation::AutomateParameter(timing, ref param_id, normalized) => {
+                tester.schedule_parameter_change(timing, param_id, normalized);
+                Ok(())
+            }
+        }));
+
+        match result {
+            Ok(Ok(())) => (),
+            Ok(Err(message)) => fail(config.seed, &history, &message),
+            Err(payload) => {
+                eprintln!(
+                    "Stress test failed with seed {:#x}. Most recent operations:\n{history:#?}",
+                    config.seed
+                );
+                panic::resume_unwind(payload);
+            }
+        }
+    }
+}
+
+fn fail(seed: u64, history: &VecDeque<Operation>, message: &str) -> ! {
+    panic!(
+        "Stress test failed with seed {seed:#x}: {message}\nMost recent operations:\n{history:#?}"
+    );
+}
+
+/// Pick the next operation. Processing is the most common operation, and it's skipped until the
+/// plugin has been successfully reinitialized.
+fn random_operation<P: Plugin>(
+    rng: &mut Xorshift,
+    config: &StressTestConfig,
+    tester: &PluginTester<P>,
+    param_ids: &[String],
+) -> Operation {
+    let reinitialize = |rng: &mut Xorshift| {
+        let max_buffer_size = match rng.below(4) {
+            0 => 1,
+            1 => config.max_buffer_size,
+            _ => 1 + rng.below(config.max_buffer_size as usize) as u32,
+        };
+
+        Operation::Reinitialize(
+            rng.below(P::AUDIO_IO_LAYOUTS.len().max(1)),
+            BufferConfig {
+                sample_rate: config.sample_rates[rng.below(config.sample_rates.len())],
+                min_buffer_size: None,
+                max_buffer_size,
+                process_mode: if rng.below(2) == 0 {
+                    ProcessMode::Realtime
+                } else {
+                    ProcessMode::Offline
+                },
+            },
+        )
+    };
+    if !tester.initialized {
+        return reinitialize(rng);
+    }
+
+    let max_buffer_size = tester.buffer_config.max_buffer_size as usize;
+    match rng.below(20) {
+        0 => reinitialize(rng),
+        1 => Operation::SaveLoadState,
+        2..=4 if !param_ids.is_empty() => {
+            let param_id = param_ids[rng.below(param_ids.len())].clone();
+            let normalized = if rng.below(2) == 0 {
+                OUT_OF_RANGE_VALUES[rng.below(OUT_OF_RANGE_VALUES.len())]
+            } else {
+                rng.next_f32() * 2.0 - 0.5
+            };
+
+            if rng.below(2) == 0 {
+                Operation::SetParameter(param_id, normalized)
+            } else {
+                Operation::AutomateParameter(
+                    rng.below(max_buffer_size) as u32,
+                    param_id,
+                    normalized,
+                )
+            }
+        }
+        _ => Operation::Process(match rng.below(5) {
+            0 => 0,
+            1 => 1,
+            2 => max_buffer_size,
+            _ => rng.below(max_buffer_size + 1),
+        }),
+    }
+}
+
+/// Process `num_samples` samples of noise on all inputs. Returns the plugin's status and whether
+/// all outputs only contained finite values.
+fn process_noise<P: Plugin>(
+    tester: &mut PluginTester<P>,
+    rng: &mut Xorshift,
+    num_samples: usize,
+) -> (ProcessStatus, bool) {
+    let layout = tester.audio_io_layout;
+    let mut noise = |num_channels: usize| -> Vec<Vec<f32>> {
+        (0..num_channels)
+            .map(|_| {
+                (0..num_samples)
+                    .map(|_| rng.next_f32() * 2.0 - 1.0)
+                    .collect()
+            })
+            .collect()
+    };
+
+    let num_main_channels = layout
+        .main_output_channels
+        .map(NonZeroU32::get)
+        .unwrap_or(0) as usize;
+    let mut main_io = noise(num_main_channels);
+    let mut aux_inputs: Vec<_> = layout
+        .aux_input_ports
+        .iter()
+        .map(|num_channels| noise(num_channels.get() as usize))
+        .collect();
+    let mut aux_outputs: Vec<_> = layout
+        .aux_output_ports
+        .iter()
+        .map(|num_channels| vec![vec![0.0; num_samples]; num_channels.get() as usize])
+        .collect();
+
+    let status =
+        tester.process_samples(num_samples, &mut main_io, &mut aux_inputs, &mut aux_outputs);
+    let output_is_finite = main_io
+        .iter()
+        .chain(aux_outputs.iter().flatten())
+        .flatten()
+        .all(|sample| sample.is_finite());
+
+    // The stress test doesn't care about these, so they shouldn't pile up
+    tester.output_events.clear();
+
+    (status, output_is_finite)
+}
@@ -1,9 +1,81 @@
 //! General conversion functions and utilities.
 
+mod adsr;
+mod audio_bus;
+#[cfg(feature = "audio_file")]
+mod audio_file;
+mod automation_writer;
+mod block_buffer;
+mod delay;
+mod disk_streamer;
+mod dry_wet;
+mod envelope;
+#[cfg(feature = "fft")]
+mod fft;
+mod io_strip;
+mod lfo;
+mod limiter;
+mod loudness;
+mod mono_note_stack;
+mod mpe_encoder;
+pub mod note_fx;
+mod open_url;
+mod oversampling;
+mod pitch_bend;
+mod rng;
+mod shared_data;
+mod smoother_pool;
+#[cfg(feature = "fft")]
+mod spectral;
+mod step_clock;
 mod stft;
+mod sustain_pedal;
+mod test_signal;
+mod triple_buffer;
+mod true_peak;
+pub mod tuning;
+pub(crate) mod vectorized;
+mod voice_allocator;
+pub mod vst2_presets;
 pub mod window;
 
+pub use adsr::{Adsr, AdsrMode, AdsrSettings, AdsrStage};
+pub use audio_bus::{AudioBus, AudioReceiver, AudioSender};
+#[cfg(feature = "audio_file")]
+pub use audio_file::{AudioFile, AudioFileLoader};
+pub use automation_writer::AutomationWriter;
+pub use block_buffer::BlockBuffer;
+pub use delay::{DelayLine, Interpolation};
+pub use disk_streamer::{DiskStreamer, StreamSource, StreamVoice, StreamedSample};
+pub use dry_wet::{DryWetMixer, MixingStyle};
+pub use envelope::{EnvelopeDetector, EnvelopeFollower, ReleaseMode};
+#[cfg(feature = "fft")]
+pub use fft::{FftBackend, RealFft};
+pub use io_strip::{IoStrip, IoStripMeters, IoStripParams};
+pub use lfo::{Lfo, LfoRate, LfoRetrigger, LfoShape};
+pub use limiter::LookaheadLimiter;
+pub use loudness::LoudnessMeter;
+pub use mono_note_stack::{MonoNoteChange, MonoNoteStack, NotePriority, TriggerMode};
+pub use mpe_encoder::MpeEncoder;
+pub use open_url::open_url;
+pub use oversampling::{Oversampler, OversamplingFactor};
+pub use pitch_bend::{
+    pitch_bend_from_14bit, pitch_bend_to_14bit, semitones_to_freq_multiplier, PitchBendRange,
+    PITCH_BEND_CENTER, PITCH_BEND_MAX,
+};
+pub use rng::Xorshift;
+pub use shared_data::{SharedChannel, SharedPublisher};
+pub use smoother_pool::SmootherPool;
+#[cfg(feature = "fft")]
+pub use spectral::{Complex32, SpectralStftHelper};
+pub use step_clock::{ClockTick, StepClock};
 pub use stft::StftHelper;
+pub use sustain_pedal::SustainPedal;
+pub use test_signal::{PinkNoise, SineSweep, WhiteNoise};
+pub use triple_buffer::{TripleBuffer, TripleBufferInput, TripleBufferOutput};
+pub use true_peak::TruePeakDetector;
+pub use vectorized::{apply_gain, apply_gains, mix_scaled, peak};
+pub use voice_allocator::{StealingMode, Voice, VoiceAllocator};
 
 pub const MINUS_INFINITY_DB: f32 = -100.0;
 pub const MINUS_INFINITY_GAIN: f32 = 1e-5; // 10f32.powf(MINUS_INFINITY_DB / 20)
@@ -12,10 +84,11 @@ pub const NOTES: [&str; 12] = [
 ];
 
 /// Temporarily allow allocations within `func` if NIH-plug was configured with the
-/// `assert_process_allocs` feature.
+/// `assert_process_allocs` feature. Use this for known-safe cold paths in your process function,
+/// like a one-off allocation when the host changes the plugin's configuration.
 #[cfg(all(debug_assertions, feature = "assert_process_allocs"))]
 pub fn permit_alloc<T, F: FnOnce() -> T>(func: F) -> T {
-    assert_no_alloc::permit_alloc(func)
+    crate::wrapper::util::alloc_checks::permit_alloc(func)
 }
 
 /// Temporarily allow allocations within `func` if NIH-plug was configured with the
@@ -71,12 +144,12 @@ pub fn gain_to_db_fast(gain: f32) -> f32 {
     f32::max(gain, MINUS_INFINITY_GAIN).ln() * CONVERSION_FACTOR
 }
 
-/// [`db_to_gain_fast()`], but the minimum gain value is set to [`f32::EPSILON`]instead of
+/// [`gain_to_db_fast()`], but the minimum gain value is set to [`f32::EPSILON`] instead of
 /// [`MINUS_INFINITY_GAIN`]. Useful in conjunction with [`db_to_gain_fast()`].
 #[inline]
 pub fn gain_to_db_fast_epsilon(gain: f32) -> f32 {
     const CONVERSION_FACTOR: f32 = std::f32::consts::LOG10_E * 20.0;
-    f32::max(gain, MINUS_INFINITY_GAIN).ln() * CONVERSION_FACTOR
+    f32::max(gain, f32::EPSILON).ln() * CONVERSION_FACTOR
 }
 
 /// Convert a MIDI note ID to a frequency at A4 = 440 Hz equal temperament and middle C = note 60 =
@@ -102,6 +175,22 @@ pub fn freq_to_midi_note(freq: f32) -> f32 {
     ((freq / 440.0).log2() * 12.0) + 69.0
 }
 
+/// An approximation of [`f32_midi_note_to_freq()`] using `exp()`. Will run faster on most
+/// architectures, but the result may be slightly different.
+#[inline]
+pub fn f32_midi_note_to_freq_fast(note: f32) -> f32 {
+    const CONVERSION_FACTOR: f32 = std::f32::consts::LN_2 / 12.0;
+    ((note - 69.0) * CONVERSION_FACTOR).exp() * 440.0
+}
+
+/// An approximation of [`freq_to_midi_note()`] using `ln()`. Will run faster on most
+/// architectures, but the result may be slightly different.
+#[inline]
+pub fn freq_to_midi_note_fast(freq: f32) -> f32 {
+    const CONVERSION_FACTOR: f32 = 12.0 / std::f32::consts::LN_2;
+    ((freq * (1.0 / 440.0)).ln() * CONVERSION_FACTOR) + 69.0
+}
+
 #[cfg(test)]
 mod tests {
     mod db_gain_conversion {
@@ -192,5 +281,64 @@ mod tests {
         fn test_gain_to_db_minus_infinity_negative() {
             approx::assert_relative_eq!(gain_to_db(-2.0), gain_to_db_fast(-2.0), epsilon = 1e-7);
         }
+
+        #[test]
+        fn test_gain_to_db_epsilon_zero() {
+            approx::assert_relative_eq!(
+                gain_to_db_fast_epsilon(0.0),
+                gain_to_db(f32::EPSILON),
+                epsilon = 1e-4
+            );
+        }
+    }
+
+    mod midi_note_conversion {
+        use super::super::*;
+
+        #[test]
+        fn test_midi_note_to_freq_a4() {
+            assert_eq!(midi_note_to_freq(69), 440.0);
+        }
+
+        #[test]
+        fn test_midi_note_to_freq_a5() {
+            assert_eq!(midi_note_to_freq(81), 880.0);
+        }
+
+        #[test]
+        fn test_freq_to_midi_note_a3() {
+            assert_eq!(freq_to_midi_note(220.0), 57.0);
+        }
+
+        #[test]
+        fn test_midi_note_roundtrip() {
+            approx::assert_relative_eq!(
+                freq_to_midi_note(f32_midi_note_to_freq(60.25)),
+                60.25,
+                epsilon = 1e-4
+            );
+        }
+    }
+
+    mod fast_midi_note_conversion {
+        use super::super::*;
+
+        #[test]
+        fn test_midi_note_to_freq() {
+            approx::assert_relative_eq!(
+                f32_midi_note_to_freq(60.0),
+                f32_midi_note_to_freq_fast(60.0),
+                epsilon = 1e-3
+            );
+        }
+
+        #[test]
+        fn test_freq_to_midi_note() {
+            approx::assert_relative_eq!(
+                freq_to_midi_note(1000.0),
+                freq_to_midi_note_fast(1000.0),
+                epsilon = 1e-4
+            );
+        }
     }
 }
@@ -0,0 +1,440 @@
+//! DAHDSR envelope generators for synthesizers.
+
+/// The maximum number of note on and note off events that can be scheduled at the same time.
+const MAX_PENDING_EVENTS: usize = 8;
+/// The overshoot target used for the attack stage in [`AdsrMode::Analog`], relative to the peak
+/// level. Higher values result in a more linear attack.
+const ANALOG_ATTACK_RATIO: f32 = 0.3;
+/// The undershoot target used for the decay and release stages in [`AdsrMode::Analog`].
+const ANALOG_DECAY_RATIO: f32 = 0.0001;
+
+/// How an [`Adsr`] moves between levels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AdsrMode {
+    /// Every stage takes exactly the configured time, with the shape determined by the stage's
+    /// curve. A curve of zero results in linear segments.
+    Digital,
+    /// The stages follow the exponential curves of a charging and discharging capacitor, like in
+    /// analog envelope generators. The attack aims past the peak level so it ends with a sharp
+    /// corner, and the decay and release slow down as they approach their target. The stages
+    /// still end after their configured time. The curve settings are ignored in this mode.
+    Analog,
+}
+
+/// The stage an [`Adsr`] is currently in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AdsrStage {
+    /// The envelope is not playing and outputs zero.
+    Idle,
+    Delay,
+    Attack,
+    Hold,
+    Decay,
+    Sustain,
+    Release,
+}
+
+/// The settings for an [`Adsr`]. These are usually shared by all voices and derived from the
+/// plugin's parameters. All times are in milliseconds and the levels are in `[0, 1]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdsrSettings {
+    pub mode: AdsrMode,
+    /// The time between the note on event and the start of the attack stage.
+    pub delay_ms: f32,
+    pub attack_ms: f32,
+    /// The time the envelope stays at the peak level after the attack stage.
+    pub hold_ms: f32,
+    pub decay_ms: f32,
+    /// The level held after the decay stage until the note is released.
+    pub sustain: f32,
+    pub release_ms: f32,
+
+    /// The curve of the attack stage in [`AdsrMode::Digital`], in `[-1, 1]`. Zero is linear,
+    /// positive values move quickly at the start of the stage and slow down towards its end, and
+    /// negative values start slowly and speed up towards the end.
+    pub attack_curve: f32,
+    /// The curve of the decay stage in [`AdsrMode::Digital`]. See
+    /// [`attack_curve`][Self::attack_curve].
+    pub decay_curve: f32,
+    /// The curve of the release stage in [`AdsrMode::Digital`]. See
+    /// [`attack_curve`][Self::attack_curve].
+    pub release_curve: f32,
+}
+
+/// A DAHDSR (delay, attack, hold, decay, sustain, release) envelope generator. Set the delay and
+/// hold times to zero for a regular ADSR envelope. Note on and note off events can be scheduled
+/// at a sample offset, so the envelope starts and releases at the exact sample the event
+/// occurred even if the voice is rendered a whole block at a time.
+///
+/// This is meant to be stored in the per-voice data of a
+/// [`VoiceAllocator`][super::VoiceAllocator]:
+///
+/// ```ignore
+/// NoteEvent::NoteOn { timing, voice_id, channel, note, .. } => {
+///     let voice = self.voices.note_on(context, timing, voice_id, channel, note);
+///     voice.data.envelope.set_settings(envelope_settings);
+///     voice.data.envelope.note_on(timing);
+/// }
+/// NoteEvent::NoteOff { timing, voice_id, channel, note, .. } => {
+///     for voice in self.voices.note_off(voice_id, channel, note) {
+///         voice.data.envelope.note_off(timing);
+///     }
+/// }
+///
+/// // After rendering the voices
+/// for voice in self.voices.active_voices_mut() {
+///     voice.set_level(voice.data.envelope.value());
+/// }
+/// self.voices.terminate_voices(context, block_end, |voice| voice.data.envelope.is_idle());
+/// ```
+///
+/// Retriggering an envelope that's still playing, for instance when a voice is stolen, starts the
+/// attack from the current level instead of jumping to zero to avoid clicks. Call
+/// [`reset()`][Self::reset()] first to start from zero instead. The envelope does not allocate.
+#[derive(Debug, Clone)]
+pub struct Adsr {
+    settings: AdsrSettings,
+    sample_rate: f32,
+
+    stage: AdsrStage,
+    value: f32,
+    /// The progress through the current stage in `[0, 1]` for timed stages.
+    stage_pos: f32,
+    /// The amount `stage_pos` advances per sample.
+    stage_increment: f32,
+    /// The level at the start of the current stage.
+    start_level: f32,
+    /// The level the current stage ends at.
+    target_level: f32,
+    /// The curve used in digital mode for the current stage.
+    curve: f32,
+    /// The one-pole filter's coefficient and offset for the current stage in analog mode.
+    analog_coefficient: f32,
+    analog_offset: f32,
+
+    /// Events that should be handled in the future as `(samples_until_event, is_note_on)` pairs.
+    pending_events: [(u32, bool); MAX_PENDING_EVENTS],
+    num_pending_events: usize,
+}
+
+impl Default for AdsrSettings {
+    fn default() -> Self {
+        Self {
+            mode: AdsrMode::Digital,
+            delay_ms: 0.0,
+            attack_ms: 5.0,
+            hold_ms: 0.0,
+            decay_ms: 200.0,
+            sustain: 0.7,
+            release_ms: 300.0,
+
+            attack_curve: 0.0,
+            decay_curve: 0.5,
+            release_curve: 0.5,
+        }
+    }
+}
+
+impl Adsr {
+    /// Create an idle envelope. The sample rate defaults to 44.1 kHz until
+    /// [`set_sample_rate()`][Self::set_sample_rate()] is called.
+    pub fn new(settings: AdsrSettings) -> Self {
+        Self {
+            settings,
+            sample_rate: 44_100.0,
+
+            stage: AdsrStage::Idle,
+            value: 0.0,
+            stage_pos: 0.0,
+            stage_increment: 0.0,
+            start_level: 0.0,
+            target_level: 0.0,
+            curve: 0.0,
+            analog_coefficient: 0.0,
+            analog_offset: 0.0,
+
+            pending_events: [(0, false); MAX_PENDING_EVENTS],
+            num_pending_events: 0,
+        }
+    }
+
+    /// Change the sample rate. This takes effect at the start of the next stage.
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// The envelope's settings.
+    pub fn settings(&self) -> &AdsrSettings {
+        &self.settings
+    }
+
+    /// Change the envelope's settings. New times and curves take effect at the start of the next
+    /// stage, while a new sustain level is applied immediately during the sustain stage.
+    pub fn set_settings(&mut self, settings: AdsrSettings) {
+        self.settings = settings;
+    }
+
+    /// The stage the envelope is currently in.
+    pub fn stage(&self) -> AdsrStage {
+        self.stage
+    }
+
+    /// The envelope's current value.
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    /// Whether the envelope has finished playing. Voices can be terminated once this returns
+    /// true.
+    pub fn is_idle(&self) -> bool {
+        self.stage == AdsrStage::Idle && self.num_pending_events == 0
+    }
+
+    /// Whether the envelope is in its release stage.
+    pub fn is_releasing(&self) -> bool {
+        self.stage == AdsrStage::Release
+    }
+
+    /// Immediately stop the envelope and discard all scheduled events.
+    pub fn reset(&mut self) {
+        self.stage = AdsrStage::Idle;
+        self.value = 0.0;
+        self.num_pending_events = 0;
+    }
+
+    /// Start the envelope `timing` samples from now, where `timing` is relative to the next sample
+    /// returned by [`next_sample()`][Self::next_sample()] or written by
+    /// [`process()`][Self::process()]. This is usually the note on event's timing.
+    pub fn note_on(&mut self, timing: u32) {
+        self.schedule(timing, true);
+    }
+
+    /// Start the release stage `timing` samples from now. See [`note_on()`][Self::note_on()].
+    pub fn note_off(&mut self, timing: u32) {
+        self.schedule(timing, false);
+    }
+
+    /// Compute the next sample's value.
+    #[inline]
+    pub fn next_sample(&mut self) -> f32 {
+        if self.num_pending_events > 0 {
+            self.handle_pending_events();
+        }
+
+        match self.stage {
+            AdsrStage::Idle => (),
+            AdsrStage::Sustain => self.value = self.settings.sustain,
+            AdsrStage::Delay | AdsrStage::Hold => {
+                self.stage_pos += self.stage_increment;
+                if self.stage_pos >= 1.0 {
+                    self.finish_stage();
+                }
+            }
+            AdsrStage::Attack | AdsrStage::Decay | AdsrStage::Release => {
+                self.stage_pos = (self.stage_pos + self.stage_increment).min(1.0);
+                match self.settings.mode {
+                    AdsrMode::Digital => {
+                        self.value = self.start_level
+                            + ((self.target_level - self.start_level)
+                                * curve_shape(self.stage_pos, self.curve));
+                    }
+                    AdsrMode::Analog => {
+                        self.value = self.analog_offset + (self.value * self.analog_coefficient);
+                    }
+                }
+
+                let reached_target = if self.target_level >= self.start_level {
+                    self.value >= self.target_level
+                } else {
+                    self.value <= self.target_level
+                };
+                if reached_target || self.stage_pos >= 1.0 {
+                    self.finish_stage();
+                }
+            }
+        }
+
+        self.value
+    }
+
+    /// Write the envelope's values for the next `output.len()` samples to `output`.
+    pub fn process(&mut self, output: &mut [f32]) {
+        for sample in output {
+            *sample = self.next_sample();
+        }
+    }
+
+    fn schedule(&mut self, timing: u32, is_note_on: bool) {
+        if self.num_pending_events == MAX_PENDING_EVENTS {
+            nih_debug_assert_failure!("Too many scheduled envelope events, ignoring the event");
+            return;
+        }
+
+        self.pending_events[self.num_pending_events] = (timing, is_note_on);
+        self.num_pending_events += 1;
+    }
+
+    /// Handle the events that are due at this sample, in the order they were scheduled, and count
+    /// down the other events.
+    fn handle_pending_events(&mut self) {
+        let mut event_idx = 0;
+        while event_idx < self.num_pending_events {
+            let (timing, is_note_on) = &mut self.pending_events[event_idx];
+            if *timing > 0 {
+                *timing -= 1;
+                event_idx += 1;
+                continue;
+            }
+
+            if *is_note_on {
+                self.enter_stage(AdsrStage::Delay);
+            } else if !matches!(self.stage, AdsrStage::Idle | AdsrStage::Release) {
+                self.enter_stage(AdsrStage::Release);
+            }
+
+            self.pending_events
+                .copy_within(event_idx + 1..self.num_pending_events, event_idx);
+            self.num_pending_events -= 1;
+        }
+    }
+
+    /// Move on to the stage after the current stage, setting the value to the current stage's
+    /// target.
+    fn finish_stage(&mut self) {
+        let next_stage = match self.stage {
+            AdsrStage::Delay => AdsrStage::Attack,
+            AdsrStage::Attack => AdsrStage::Hold,
+            AdsrStage::Hold => AdsrStage::Decay,
+            AdsrStage::Decay => AdsrStage::Sustain,
+            AdsrStage::Release | AdsrStage::Idle => AdsrStage::Idle,
+            AdsrStage::Sustain => return,
+        };
+        if self.stage != AdsrStage::Delay {
+            self.value = self.target_level;
+        }
+
+        self.enter_stage(next_stage);
+    }
+
+    fn enter_stage(&mut self, stage: AdsrStage) {
+        let settings = &self.settings;
+        let (length_ms, target_level, curve) = match stage {
+            AdsrStage::Idle => {
+                self.stage = AdsrStage::Idle;
+                self.value = 0.0;
+                return;
+            }
+            AdsrStage::Sustain => {
+                self.stage = AdsrStage::Sustain;
+                self.value = settings.sustain;
+                return;
+            }
+            AdsrStage::Delay => (settings.delay_ms, self.value, 0.0),
+            AdsrStage::Attack => (settings.attack_ms, 1.0, settings.attack_curve),
+            AdsrStage::Hold => (settings.hold_ms, 1.0, 0.0),
+            AdsrStage::Decay => (settings.decay_ms, settings.sustain, settings.decay_curve),
+            AdsrStage::Release => (settings.release_ms, 0.0, settings.release_curve),
+        };
+
+        self.stage = stage;
+        self.stage_pos = 0.0;
+        self.start_level = self.value;
+        self.target_level = target_level;
+        self.curve = curve.clamp(-1.0, 1.0);
+
+        let length_samples = length_ms / 1000.0 * self.sample_rate;
+        if length_samples < 1.0 {
+            // Stages that are shorter than a sample are skipped
+            self.finish_stage();
+            return;
+        }
+        self.stage_increment = length_samples.recip();
+
+        // These are chosen so the filter reaches the target level after exactly `length_samples`
+        // samples by aiming slightly past it
+        let ratio = if stage == AdsrStage::Attack {
+            ANALOG_ATTACK_RATIO
+        } else {
+            ANALOG_DECAY_RATIO
+        };
+        let aim = if target_level >= self.start_level {
+            target_level + ratio
+        } else {
+            target_level - ratio
+        };
+        let distance = (aim - self.start_level).abs().max(f32::EPSILON);
+        self.analog_coefficient = (-(distance / ratio).ln() / length_samples).exp();
+        self.analog_offset = aim * (1.0 - self.analog_coefficient);
+    }
+}
+
+/// Map a stage's progress in `[0, 1]` to the fraction of the distance covered, for a curve in
+/// `[-1, 1]`.
+#[inline]
+fn curve_shape(pos: f32, curve: f32) -> f32 {
+    if curve.abs() < 1e-3 {
+        return pos;
+    }
+
+    let steepness = -curve * 6.0;
+    (steepness * pos).exp_m1() / steepness.exp_m1()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linear_settings(mode: AdsrMode) -> AdsrSettings {
+        AdsrSettings {
+            mode,
+            delay_ms: 0.0,
+            attack_ms: 4.0,
+            hold_ms: 2.0,
+            decay_ms: 4.0,
+            sustain: 0.5,
+            release_ms: 2.0,
+
+            attack_curve: 0.0,
+            decay_curve: 0.0,
+            release_curve: 0.0,
+        }
+    }
+
+    #[test]
+    fn sample_accurate_digital_envelope() {
+        let mut envelope = Adsr::new(linear_settings(AdsrMode::Digital));
+        envelope.set_sample_rate(1000.0);
+        envelope.note_on(1);
+        envelope.note_off(14);
+
+        let mut output = [0.0; 18];
+        envelope.process(&mut output);
+        let expected = [
+            0.0, 0.25, 0.5, 0.75, 1.0, 1.0, 1.0, 0.875, 0.75, 0.625, 0.5, 0.5, 0.5, 0.5, 0.25, 0.0,
+            0.0, 0.0,
+        ];
+        for (sample, expected) in output.iter().zip(expected) {
+            assert!((sample - expected).abs() < 1e-6, "{output:?}");
+        }
+        assert!(envelope.is_idle());
+    }
+
+    #[test]
+    fn analog_envelope_reaches_targets() {
+        let mut envelope = Adsr::new(linear_settings(AdsrMode::Analog));
+        envelope.set_sample_rate(1000.0);
+        envelope.note_on(0);
+
+        let mut output = [0.0; 12];
+        envelope.process(&mut output);
+        // The attack is concave and ends at the peak level
+        assert!(output[0] > 0.25);
+        assert_eq!(output[3], 1.0);
+        assert_eq!(envelope.stage(), AdsrStage::Sustain);
+        assert_eq!(envelope.value(), 0.5);
+
+        envelope.note_off(0);
+        envelope.process(&mut output[..2]);
+        assert!(envelope.is_idle());
+    }
+}
@@ -0,0 +1,258 @@
+//! Sending audio from one plugin instance to another instance in the same process.
+
+use atomic_float::AtomicF32;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use super::shared_data::open_shared_state;
+use crate::buffer::Buffer;
+
+/// A named audio bus shared by all instances of a plugin that are loaded in the same process. One
+/// instance sends audio to the bus using an [`AudioSender`], and any number of other instances can
+/// receive that audio using an [`AudioReceiver`]. This is similar to the sidechain 'link' features
+/// found in some commercial plugins, and it can be used to route audio between tracks in hosts
+/// that don't support sidechaining.
+///
+/// The audio is stored in a lock-free ring buffer, so sending and receiving can be done from the
+/// audio thread without allocating. Since the host may process the receiving instance before the
+/// sending instance, the receiver delays the audio by a fixed amount. Report
+/// [`AudioReceiver::latency_samples()`] to the host through
+/// [`InitContext::set_latency_samples()`][crate::prelude::InitContext::set_latency_samples()] so
+/// the host can compensate for this delay.
+///
+/// ```
+/// # use nih_plug::util::AudioBus;
+/// // In the sender's and the receiver's `initialize()` functions
+/// let bus = AudioBus::open("com.example.link", "bus_1", 2, 8192).unwrap();
+/// let mut sender = bus.sender().unwrap();
+/// let mut receiver = bus.receiver(512);
+///
+/// // In the sender's process function
+/// // sender.write(buffer);
+///
+/// // In the receiver's process function
+/// // receiver.read(buffer);
+/// ```
+///
+/// Keep in mind that hosts with process isolation may load instances in different processes, in
+/// which case they won't be able to send audio to each other.
+#[derive(Clone)]
+pub struct AudioBus {
+    state: Arc<BusState>,
+}
+
+/// Writes audio to an [`AudioBus`]. There can only be one sender per bus at a time.
+pub struct AudioSender {
+    state: Arc<BusState>,
+}
+
+/// Reads audio from an [`AudioBus`] with a fixed delay.
+pub struct AudioReceiver {
+    state: Arc<BusState>,
+    /// The delay between the sender and the receiver, in samples.
+    latency: u32,
+    /// The position in the sender's stream of the next sample to read. This can be negative right
+    /// after the receiver starts reading. `None` if the receiver needs to resynchronize with the
+    /// sender.
+    read_pos: Option<i64>,
+    /// The sender's write position during the last read. Used to detect a sender that stopped
+    /// sending audio.
+    last_write_pos: u64,
+}
+
+/// The ring buffer shared by the sender and the receivers.
+struct BusState {
+    /// The audio for every channel. Each channel has the same length.
+    channels: Vec<Box<[AtomicF32]>>,
+    /// The total number of samples written by the sender. The ring buffer position is this value
+    /// modulo the channels' lengths.
+    write_pos: AtomicU64,
+    /// Whether a sender is connected to the bus.
+    has_sender: AtomicBool,
+}
+
+impl AudioBus {
+    /// Open the bus called `name` for the plugin with ID `plugin_id`, or create it if no other
+    /// instance has opened it yet. New buses have `num_channels` channels and can store up to
+    /// `capacity` samples, which should be larger than the receivers' latency plus the maximum
+    /// buffer size. Returns `None` if the bus already exists with a different number of channels.
+    /// This allocates and locks a process-wide mutex.
+    pub fn open(plugin_id: &str, name: &str, num_channels: usize, capacity: usize) -> Option<Self> {
+        let state = open_shared_state(plugin_id, name, || BusState {
+            channels: (0..num_channels)
+                .map(|_| (0..capacity.max(1)).map(|_| AtomicF32::new(0.0)).collect())
+                .collect(),
+            write_pos: AtomicU64::new(0),
+            has_sender: AtomicBool::new(false),
+        })?;
+        if state.channels.len() != num_channels {
+            nih_debug_assert_failure!(
+                "Audio bus '{}' for '{}' already exists with {} channels",
+                name,
+                plugin_id,
+                state.channels.len()
+            );
+            return None;
+        }
+
+        Some(Self { state })
+    }
+
+    /// The number of channels on this bus.
+    pub fn num_channels(&self) -> usize {
+        self.state.channels.len()
+    }
+
+    /// The maximum number of samples stored in the bus.
+    pub fn capacity(&self) -> usize {
+        self.state.capacity()
+    }
+
+    /// Start sending audio to this bus. Returns `None` if another instance is already sending to
+    /// the bus.
+    pub fn sender(&self) -> Option<AudioSender> {
+        if self.state.has_sender.swap(true, Ordering::SeqCst) {
+            return None;
+        }
+
+        Some(AudioSender {
+            state: self.state.clone(),
+        })
+    }
+
+    /// Start receiving audio from this bus, with a delay of `latency` samples. This should be at
+    /// least as large as the host's maximum buffer size, since the host may process the receiving
+    /// instance before the sending instance.
+    pub fn receiver(&self, latency: u32) -> AudioReceiver {
+        nih_debug_assert!(
+            (latency as usize) < self.capacity(),
+            "The audio bus' capacity should be larger than the receiver's latency"
+        );
+
+        AudioReceiver {
+            state: self.state.clone(),
+            latency,
+            read_pos: None,
+            last_write_pos: 0,
+        }
+    }
+}
+
+impl BusState {
+    fn capacity(&self) -> usize {
+        self.channels.first().map_or(0, |channel| channel.len())
+    }
+}
+
+impl AudioSender {
+    /// Write a buffer to the bus. If the buffer has fewer channels than the bus, then the other
+    /// channels are filled with silence.
+    pub fn write(&mut self, buffer: &Buffer) {
+        let num_samples = buffer.samples();
+        let write_pos = self.state.write_pos.load(Ordering::Relaxed);
+        let capacity = self.state.capacity();
+        let input = buffer.as_slice_immutable();
+        for (channel_idx, channel) in self.state.channels.iter().enumerate() {
+            let input_channel = input.get(channel_idx);
+            for sample_idx in 0..num_samples {
+                let sample = input_channel.map_or(0.0, |input_channel| input_channel[sample_idx]);
+                channel[(write_pos as usize + sample_idx) % capacity]
+                    .store(sample, Ordering::Relaxed);
+            }
+        }
+
+        self.state
+            .write_pos
+            .store(write_pos + num_samples as u64, Ordering::Release);
+    }
+}
+
+impl Drop for AudioSender {
+    fn drop(&mut self) {
+        self.state.has_sender.store(false, Ordering::SeqCst);
+    }
+}
+
+impl AudioReceiver {
+    /// The delay between the sender and this receiver in samples. The receiving plugin should add
+    /// this to its reported latency.
+    pub fn latency_samples(&self) -> u32 {
+        self.latency
+    }
+
+    /// Forget the current position in the sender's stream. Call this from
+    /// [`Plugin::reset()`][crate::prelude::Plugin::reset()].
+    pub fn reset(&mut self) {
+        self.read_pos = None;
+    }
+
+    /// Overwrite the buffer with the next block of audio from the bus. Channels that don't exist
+    /// on the bus, and samples the sender has not sent, are filled with silence.
+    pub fn read(&mut self, buffer: &mut Buffer) {
+        let num_samples = buffer.samples();
+        let capacity = self.state.capacity() as i64;
+        let write_pos = self.state.write_pos.load(Ordering::Acquire);
+
+        // If the sender has been sending audio since the last read but the receiver is too far
+        // ahead or behind, then the receiver starts over at the configured latency. A sender that
+        // stopped sending simply results in silence.
+        let read_pos = match self.read_pos {
+            Some(read_pos)
+                if write_pos == self.last_write_pos
+                    || (read_pos <= write_pos as i64
+                        && write_pos as i64 - read_pos <= capacity - num_samples as i64) =>
+            {
+                read_pos
+            }
+            _ => write_pos as i64 - self.latency as i64,
+        };
+        let oldest_pos = write_pos as i64 - capacity;
+
+        let output = buffer.as_slice();
+        for (channel_idx, output_channel) in output.iter_mut().enumerate() {
+            let channel = self.state.channels.get(channel_idx);
+            for (sample_idx, sample) in output_channel.iter_mut().enumerate() {
+                let pos = read_pos + sample_idx as i64;
+                *sample = match channel {
+                    Some(channel) if pos >= 0 && pos >= oldest_pos && pos < write_pos as i64 => {
+                        channel[(pos % capacity) as usize].load(Ordering::Relaxed)
+                    }
+                    _ => 0.0,
+                };
+            }
+        }
+
+        self.read_pos = Some(read_pos + num_samples as i64);
+        self.last_write_pos = write_pos;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn send_and_receive() {
+        let bus = AudioBus::open("nih_plug.test", "audio_bus", 1, 16).unwrap();
+        let mut sender = bus.sender().unwrap();
+        assert!(bus.sender().is_none());
+        let mut receiver = bus.receiver(4);
+        assert_eq!(receiver.latency_samples(), 4);
+
+        let mut input = [1.0, 2.0, 3.0, 4.0];
+        let mut output = [0.0; 4];
+        let mut input_buffer = Buffer::default();
+        let mut output_buffer = Buffer::default();
+        unsafe {
+            input_buffer.set_slices(4, |slices| *slices = vec![&mut input]);
+            output_buffer.set_slices(4, |slices| *slices = vec![&mut output]);
+        }
+
+        // The host may process the receiver before the sender, which is why there is latency
+        receiver.read(&mut output_buffer);
+        assert_eq!(output_buffer.as_slice_immutable()[0], [0.0; 4]);
+        sender.write(&input_buffer);
+        receiver.read(&mut output_buffer);
+        assert_eq!(output_buffer.as_slice_immutable()[0], [1.0, 2.0, 3.0, 4.0]);
+    }
+}
@@ -0,0 +1,303 @@
+//! Decoding audio files and resampling them to the session's sample rate.
+
+use anyhow::{Context, Result};
+use crossbeam::queue::ArrayQueue;
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use super::permit_alloc;
+
+/// The number of input samples on either side of the output sample used by the resampler's
+/// windowed sinc filter when upsampling. This is scaled up when downsampling.
+const SINC_HALF_WIDTH: f64 = 32.0;
+/// The number of loaded files that can be waiting to be picked up by the audio thread, and the
+/// number of retired files that can be waiting to be deallocated.
+const LOADER_QUEUE_CAPACITY: usize = 8;
+
+/// Decoded audio data with one vector of samples per channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioFile {
+    /// The audio's sample rate in Hz.
+    pub sample_rate: f32,
+    /// The decoded samples for every channel. Every channel has the same length.
+    pub channels: Vec<Vec<f32>>,
+}
+
+/// Decodes and resamples audio files on a background thread and hands them to the audio thread
+/// without allocating or deallocating on the audio thread. This is the loading flow nearly every
+/// sampler and convolution plugin needs.
+///
+/// Store this in an `Arc` in the plugin, load files from a background task using
+/// [`load()`][Self::load()], and pick them up in the process function using
+/// [`receive()`][Self::receive()]. The previous file should be handed back using
+/// [`retire()`][Self::retire()] so it gets deallocated by the next load instead of on the audio
+/// thread.
+///
+/// ```ignore
+/// // In the task executor
+/// Task::LoadSample => {
+///     let path = sample_path.lock().clone();
+///     if let Err(err) = loader.load(&path, sample_rate) {
+///         nih_error!("Could not load '{}': {:#}", path.display(), err);
+///     }
+/// }
+///
+/// // In the process function
+/// if let Some(sample) = self.loader.receive() {
+///     if let Some(old_sample) = self.sample.replace(sample) {
+///         self.loader.retire(old_sample);
+///     }
+/// }
+/// ```
+pub struct AudioFileLoader {
+    /// Files that have been loaded but not yet received by the audio thread.
+    loaded: ArrayQueue<AudioFile>,
+    /// Files the audio thread no longer needs. These are dropped during the next load.
+    retired: ArrayQueue<AudioFile>,
+}
+
+impl AudioFile {
+    /// Decode a WAV, FLAC, or Ogg Vorbis file to `f32` samples. This blocks and allocates, so it
+    /// should be called from a background thread.
+    pub fn decode(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("Could not open '{}'", path.display()))?;
+
+        let mut hint = Hint::new();
+        if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+            hint.with_extension(extension);
+        }
+        let probed = symphonia::default::get_probe()
+            .format(
+                &hint,
+                MediaSourceStream::new(Box::new(file), Default::default()),
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .context("Unsupported audio file format")?;
+        let mut format = probed.format;
+
+        let track = format
+            .tracks()
+            .iter()
+            .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+            .context("The file does not contain any audio")?;
+        let track_id = track.id;
+        let sample_rate = track
+            .codec_params
+            .sample_rate
+            .context("The file does not specify a sample rate")?;
+        let mut decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())
+            .context("Unsupported audio codec")?;
+
+        let mut channels: Vec<Vec<f32>> = Vec::new();
+        let mut sample_buffer: Option<(usize, SampleBuffer<f32>)> = None;
+        loop {
+            let packet = match format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(err)) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                    break
+                }
+                Err(err) => return Err(err).context("Could not read the audio file"),
+            };
+            if packet.track_id() != track_id {
+                continue;
+            }
+
+            let decoded = match decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                // Corrupted packets are skipped
+                Err(SymphoniaError::DecodeError(err)) => {
+                    nih_log!("Skipping corrupted packet: {}", err);
+                    continue;
+                }
+                Err(err) => return Err(err).context("Could not decode the audio file"),
+            };
+
+            let spec = *decoded.spec();
+            let num_channels = spec.channels.count();
+            if channels.len() != num_channels {
+                channels.resize_with(num_channels, Vec::new);
+            }
+
+            let capacity = decoded.capacity();
+            let sample_buffer = match &mut sample_buffer {
+                Some((current_capacity, sample_buffer)) if *current_capacity >= capacity => {
+                    sample_buffer
+                }
+                slot => {
+                    &mut slot
+                        .insert((capacity, SampleBuffer::new(capacity as u64, spec)))
+                        .1
+                }
+            };
+            sample_buffer.copy_interleaved_ref(decoded);
+            for frame in sample_buffer.samples().chunks_exact(num_channels.max(1)) {
+                for (channel, sample) in channels.iter_mut().zip(frame) {
+                    channel.push(*sample);
+                }
+            }
+        }
+
+        Ok(Self {
+            sample_rate: sample_rate as f32,
+            channels,
+        })
+    }
+
+    /// Decode an audio file and resample it to `sample_rate`. See [`decode()`][Self::decode()].
+    pub fn load(path: impl AsRef<Path>, sample_rate: f32) -> Result<Self> {
+        Ok(Self::decode(path)?.resampled(sample_rate))
+    }
+
+    /// The number of channels in the file.
+    pub fn num_channels(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// The file's length in samples.
+    pub fn num_frames(&self) -> usize {
+        self.channels.first().map_or(0, Vec::len)
+    }
+
+    /// Resample the audio to `sample_rate` using a windowed sinc filter. This returns the audio
+    /// as is if it already has the target sample rate. This allocates and takes a while for longer
+    /// files, so it should be called from a background thread.
+    pub fn resampled(self, sample_rate: f32) -> Self {
+        if self.sample_rate == sample_rate || self.sample_rate <= 0.0 || sample_rate <= 0.0 {
+            return self;
+        }
+
+        let ratio = sample_rate as f64 / self.sample_rate as f64;
+        Self {
+            sample_rate,
+            channels: self
+                .channels
+                .iter()
+                .map(|channel| resample_channel(channel, ratio))
+                .collect(),
+        }
+    }
+}
+
+impl Default for AudioFileLoader {
+    fn default() -> Self {
+        Self {
+            loaded: ArrayQueue::new(LOADER_QUEUE_CAPACITY),
+            retired: ArrayQueue::new(LOADER_QUEUE_CAPACITY),
+        }
+    }
+}
+
+impl AudioFileLoader {
+    /// Create a loader without any loaded files.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode an audio file, resample it to `sample_rate`, and queue it for the audio thread. This
+    /// also drops all retired files. This blocks and allocates, so it should be called from a
+    /// background task.
+    pub fn load(&self, path: impl AsRef<Path>, sample_rate: f32) -> Result<()> {
+        while self.retired.pop().is_some() {}
+
+        let file = AudioFile::load(path, sample_rate)?;
+        // If the audio thread has not picked up the older files yet, then those are dropped here
+        // instead
+        drop(self.loaded.force_push(file));
+
+        Ok(())
+    }
+
+    /// Take the oldest loaded file that has not been received yet. This does not allocate and can
+    /// be called from the audio thread.
+    pub fn receive(&self) -> Option<AudioFile> {
+        self.loaded.pop()
+    }
+
+    /// Hand a file the audio thread no longer needs back to the loader so it can be deallocated
+    /// during the next load. This does not allocate unless there are too many retired files, in
+    /// which case the file is dropped on the spot.
+    pub fn retire(&self, file: AudioFile) {
+        if let Err(file) = self.retired.push(file) {
+            nih_debug_assert_failure!("Too many retired audio files, deallocating on this thread");
+            permit_alloc(|| drop(file));
+        }
+    }
+}
+
+/// Resample a single channel by `ratio` (the output sample rate divided by the input sample
+/// rate).
+fn resample_channel(input: &[f32], ratio: f64) -> Vec<f32> {
+    // When downsampling the filter's cutoff frequency needs to be lowered to prevent aliasing
+    let cutoff = ratio.min(1.0);
+    let half_width = SINC_HALF_WIDTH / cutoff;
+    let num_output_samples = (input.len() as f64 * ratio).round() as usize;
+
+    (0..num_output_samples)
+        .map(|output_idx| {
+            let input_pos = output_idx as f64 / ratio;
+            let first_idx = (input_pos - half_width).ceil().max(0.0) as usize;
+            let last_idx = ((input_pos + half_width).floor() as usize).min(input.len() - 1);
+
+            let mut sum = 0.0;
+            for (input_idx, sample) in input.iter().enumerate().take(last_idx + 1).skip(first_idx) {
+                let distance = input_pos - input_idx as f64;
+                sum += *sample as f64 * windowed_sinc(distance, cutoff, half_width);
+            }
+
+            sum as f32
+        })
+        .collect()
+}
+
+/// A Blackman windowed sinc lowpass filter with a cutoff relative to the input's Nyquist
+/// frequency.
+fn windowed_sinc(distance: f64, cutoff: f64, half_width: f64) -> f64 {
+    let window_pos = distance / half_width;
+    if window_pos.abs() >= 1.0 {
+        return 0.0;
+    }
+
+    let x = distance * cutoff * PI;
+    let sinc = if x == 0.0 { 1.0 } else { x.sin() / x };
+    let window = 0.42 + (0.5 * (PI * window_pos).cos()) + (0.08 * (2.0 * PI * window_pos).cos());
+
+    sinc * cutoff * window
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resample_dc() {
+        let file = AudioFile {
+            sample_rate: 44_100.0,
+            channels: vec![vec![1.0; 4410]; 2],
+        };
+
+        let resampled = file.resampled(48_000.0);
+        assert_eq!(resampled.num_channels(), 2);
+        assert_eq!(resampled.num_frames(), 4800);
+        // The edges are attenuated since the filter doesn't see a full kernel there
+        for sample in &resampled.channels[0][100..4700] {
+            assert!((sample - 1.0).abs() < 1e-3, "{sample}");
+        }
+
+        let downsampled = resampled.resampled(22_050.0);
+        assert_eq!(downsampled.num_frames(), 2205);
+        assert!((downsampled.channels[1][1100] - 1.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,142 @@
+//! Turning a control signal computed by the plugin into parameter automation.
+
+use crate::context::process::ProcessContext;
+use crate::params::Param;
+use crate::plugin::Plugin;
+
+/// Changes smaller than this are not sent to the host, since they would only add more automation
+/// points without changing anything audible.
+const MIN_CHANGE: f32 = 1e-4;
+
+/// Sends a control signal computed by the plugin, like the output of an
+/// [`EnvelopeFollower`][super::EnvelopeFollower], to the host as automation for one of the
+/// plugin's parameters. This can be used to build sidechain-to-automation tools. The control
+/// signal is provided once per block as a normalized value. It is smoothed with a one-pole filter
+/// and then sent to the host at a fixed rate through
+/// [`ProcessContext::set_parameter_normalized()`], so the host doesn't get flooded with
+/// automation points.
+///
+/// The automation gesture starts when the first value is sent. Call
+/// [`stop()`][Self::stop()] when the plugin stops writing automation, for instance when the
+/// host's transport stops, so the host knows the gesture has ended. This does not allocate.
+#[derive(Debug, Clone)]
+pub struct AutomationWriter {
+    sample_rate: f32,
+    /// The number of times per second the smoothed value is sent to the host.
+    update_rate_hz: f32,
+    /// The one-pole filter's time constant.
+    smoothing_ms: f32,
+
+    /// The smoothed normalized value, or `None` if no value has been written since the last
+    /// reset.
+    smoothed_value: Option<f32>,
+    /// The value that was last sent to the host.
+    last_sent_value: Option<f32>,
+    /// The number of samples until the next value should be sent to the host.
+    samples_until_update: f32,
+    /// Whether a gesture has been started with [`ProcessContext::begin_set_parameter()`].
+    gesture_active: bool,
+}
+
+impl AutomationWriter {
+    /// Create a new automation writer that sends at most `update_rate_hz` changes per second to the
+    /// host, with a smoothing time constant of `smoothing_ms` milliseconds. The sample rate
+    /// defaults to 44.1 kHz until [`set_sample_rate()`][Self::set_sample_rate()] is called.
+    pub fn new(update_rate_hz: f32, smoothing_ms: f32) -> Self {
+        Self {
+            sample_rate: 44_100.0,
+            update_rate_hz: update_rate_hz.max(f32::EPSILON),
+            smoothing_ms: smoothing_ms.max(0.0),
+
+            smoothed_value: None,
+            last_sent_value: None,
+            samples_until_update: 0.0,
+            gesture_active: false,
+        }
+    }
+
+    /// Set the sample rate used to compute the update rate and the smoothing. Call this from your
+    /// plugin's [`initialize()`][crate::prelude::Plugin::initialize()] function.
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Change the number of changes per second sent to the host.
+    pub fn set_update_rate(&mut self, update_rate_hz: f32) {
+        self.update_rate_hz = update_rate_hz.max(f32::EPSILON);
+    }
+
+    /// Change the smoothing time constant in milliseconds. Zero disables smoothing.
+    pub fn set_smoothing_time(&mut self, smoothing_ms: f32) {
+        self.smoothing_ms = smoothing_ms.max(0.0);
+    }
+
+    /// Forget the smoothed value, so the next block's value is used as is. This does not end the
+    /// current gesture. Call this from [`Plugin::reset()`][crate::prelude::Plugin::reset()].
+    pub fn reset(&mut self) {
+        self.smoothed_value = None;
+        self.last_sent_value = None;
+        self.samples_until_update = 0.0;
+    }
+
+    /// Whether an automation gesture is currently active.
+    pub fn is_writing(&self) -> bool {
+        self.gesture_active
+    }
+
+    /// Write the control signal's normalized value for a block of `num_samples` samples. The value
+    /// is smoothed, and it's sent to `param` if enough time has passed since the last update and
+    /// the value has changed since then.
+    pub fn write_block<P: Plugin, T: Param>(
+        &mut self,
+        context: &mut impl ProcessContext<P>,
+        param: &T,
+        num_samples: usize,
+        normalized_value: f32,
+    ) {
+        let normalized_value = normalized_value.clamp(0.0, 1.0);
+        let smoothed_value = match self.smoothed_value {
+            Some(previous_value) if self.smoothing_ms > 0.0 => {
+                let block_ms = num_samples as f32 / self.sample_rate * 1000.0;
+                let coefficient = (-block_ms / self.smoothing_ms).exp();
+
+                normalized_value + ((previous_value - normalized_value) * coefficient)
+            }
+            _ => normalized_value,
+        };
+        self.smoothed_value = Some(smoothed_value);
+
+        self.samples_until_update -= num_samples as f32;
+        if self.samples_until_update > 0.0 {
+            return;
+        }
+
+        // This keeps the update rate steady regardless of the block size, without trying to catch
+        // up after a very large block
+        let update_interval = self.sample_rate / self.update_rate_hz;
+        self.samples_until_update = (self.samples_until_update + update_interval).max(0.0);
+
+        let changed = match self.last_sent_value {
+            Some(last_sent_value) => (smoothed_value - last_sent_value).abs() >= MIN_CHANGE,
+            None => true,
+        };
+        if changed {
+            if !self.gesture_active {
+                context.begin_set_parameter(param);
+                self.gesture_active = true;
+            }
+
+            context.set_parameter_normalized(param, smoothed_value);
+            self.last_sent_value = Some(smoothed_value);
+        }
+    }
+
+    /// End the automation gesture if one is active. Writing a new block afterwards starts a new
+    /// gesture.
+    pub fn stop<P: Plugin, T: Param>(&mut self, context: &mut impl ProcessContext<P>, param: &T) {
+        if self.gesture_active {
+            context.end_set_parameter(param);
+            self.gesture_active = false;
+        }
+    }
+}
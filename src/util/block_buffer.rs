@@ -0,0 +1,125 @@
+//! Preallocated per-channel scratch buffers.
+
+use crate::buffer::Buffer;
+
+/// Preallocated scratch space for a fixed number of channels, useful for intermediate results like
+/// sidechain signals, filtered copies of the input, or gain reduction curves. Allocate the buffer
+/// in [`Plugin::initialize()`][crate::prelude::Plugin::initialize()] using the maximum buffer size
+/// from the [`BufferConfig`][crate::prelude::BufferConfig], and then borrow `num_samples` long
+/// slices from it in [`Plugin::process()`][crate::prelude::Plugin::process()] without allocating.
+///
+/// The samples for all channels are stored in a single contiguous allocation.
+#[derive(Debug, Clone, Default)]
+pub struct BlockBuffer {
+    /// The samples for all channels. Channel `n` starts at index `n * max_block_size`.
+    samples: Vec<f32>,
+    num_channels: usize,
+    max_block_size: usize,
+}
+
+impl BlockBuffer {
+    /// Allocate a buffer for `num_channels` channels of `max_block_size` samples each. The buffer
+    /// is initialized to zero.
+    pub fn new(num_channels: usize, max_block_size: usize) -> Self {
+        Self {
+            samples: vec![0.0; num_channels * max_block_size],
+            num_channels,
+            max_block_size,
+        }
+    }
+
+    /// Change the buffer's size. This allocates if the new size is larger than the buffer's
+    /// capacity, so it should only be called outside of the process function. The contents of the
+    /// buffer are reset to zero.
+    pub fn resize(&mut self, num_channels: usize, max_block_size: usize) {
+        self.samples.clear();
+        self.samples.resize(num_channels * max_block_size, 0.0);
+        self.num_channels = num_channels;
+        self.max_block_size = max_block_size;
+    }
+
+    /// The number of channels in this buffer.
+    pub fn num_channels(&self) -> usize {
+        self.num_channels
+    }
+
+    /// The maximum number of samples per channel.
+    pub fn max_block_size(&self) -> usize {
+        self.max_block_size
+    }
+
+    /// Get the first `num_samples` samples of a channel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel_idx` or `num_samples` are out of bounds.
+    pub fn channel(&self, channel_idx: usize, num_samples: usize) -> &[f32] {
+        assert!(channel_idx < self.num_channels);
+        assert!(num_samples <= self.max_block_size);
+
+        let start = channel_idx * self.max_block_size;
+        &self.samples[start..start + num_samples]
+    }
+
+    /// Get the first `num_samples` samples of a channel mutably.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel_idx` or `num_samples` are out of bounds.
+    pub fn channel_mut(&mut self, channel_idx: usize, num_samples: usize) -> &mut [f32] {
+        assert!(channel_idx < self.num_channels);
+        assert!(num_samples <= self.max_block_size);
+
+        let start = channel_idx * self.max_block_size;
+        &mut self.samples[start..start + num_samples]
+    }
+
+    /// Iterate over the first `num_samples` samples of every channel. Since the channels don't
+    /// overlap, the yielded slices can be held onto at the same time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num_samples > max_block_size`.
+    pub fn channels_mut(&mut self, num_samples: usize) -> impl Iterator<Item = &mut [f32]> {
+        assert!(num_samples <= self.max_block_size);
+
+        // `chunks_exact_mut()` panics on zero sized chunks, which would happen when
+        // `max_block_size` is zero
+        self.samples
+            .chunks_exact_mut(self.max_block_size.max(1))
+            .take(self.num_channels)
+            .map(move |channel| &mut channel[..num_samples])
+    }
+
+    /// Set the first `num_samples` samples of every channel to zero.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num_samples > max_block_size`.
+    pub fn clear(&mut self, num_samples: usize) {
+        for channel in self.channels_mut(num_samples) {
+            channel.fill(0.0);
+        }
+    }
+
+    /// Copy the contents of `buffer` to this buffer. Returns the number of copied samples per
+    /// channel. Channels that don't exist in `buffer` are left untouched.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffer` contains more channels or samples than this buffer.
+    pub fn copy_from_buffer(&mut self, buffer: &Buffer) -> usize {
+        let num_samples = buffer.samples();
+        assert!(buffer.channels() <= self.num_channels);
+
+        for (source, destination) in buffer
+            .as_slice_immutable()
+            .iter()
+            .zip(self.channels_mut(num_samples))
+        {
+            destination.copy_from_slice(source);
+        }
+
+        num_samples
+    }
+}
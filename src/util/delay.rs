@@ -0,0 +1,211 @@
+//! A circular delay line with fractional delay times.
+
+/// The interpolation used to read fractional delay times from a [`DelayLine`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Interpolation {
+    /// Round the delay time down to a whole number of samples.
+    None,
+    /// Linear interpolation between the two nearest samples. Cheap, but it acts as a lowpass filter
+    /// for fractional delay times.
+    Linear,
+    /// Four point cubic Hermite interpolation. This has a much flatter frequency response than
+    /// linear interpolation at a slightly higher cost.
+    Cubic,
+    /// First order allpass interpolation. This has a flat magnitude response, which makes it a
+    /// good fit for feedback loops and physical models, but it should only be used with slowly
+    /// changing delay times. Since the interpolator is stateful, it should be read from exactly
+    /// once per sample. The delay time needs to be at least half a sample.
+    Allpass,
+}
+
+/// A preallocated circular delay line. Samples are written using [`push()`][Self::push()], and
+/// they can be read back with fractional delay times using [`read()`][Self::read()]. A delay of
+/// zero samples returns the most recently pushed sample.
+///
+/// The buffer is allocated in [`new()`][Self::new()], so the delay line does not allocate when
+/// reading or writing samples.
+#[derive(Debug, Clone)]
+pub struct DelayLine {
+    /// The ring buffer. Its length is a power of two so indices can be wrapped with `mask`.
+    buffer: Vec<f32>,
+    mask: usize,
+    /// The index of the most recently pushed sample.
+    write_pos: usize,
+    max_delay: usize,
+
+    /// The allpass interpolator's previous output.
+    allpass_state: f32,
+}
+
+impl DelayLine {
+    /// Create a delay line that supports delays of up to `max_delay` samples. This allocates and
+    /// should be called outside of the process function, for instance in
+    /// [`Plugin::initialize()`][crate::prelude::Plugin::initialize()].
+    pub fn new(max_delay: usize) -> Self {
+        // Cubic interpolation needs one sample before and two samples after the delay time
+        let buffer_len = (max_delay + 3).next_power_of_two();
+
+        Self {
+            buffer: vec![0.0; buffer_len],
+            mask: buffer_len - 1,
+            write_pos: 0,
+            max_delay,
+
+            allpass_state: 0.0,
+        }
+    }
+
+    /// The maximum delay time in samples.
+    pub fn max_delay(&self) -> usize {
+        self.max_delay
+    }
+
+    /// Clear the delay line's contents.
+    pub fn reset(&mut self) {
+        self.buffer.fill(0.0);
+        self.write_pos = 0;
+        self.allpass_state = 0.0;
+    }
+
+    /// Write a sample to the delay line.
+    #[inline]
+    pub fn push(&mut self, sample: f32) {
+        self.write_pos = (self.write_pos + 1) & self.mask;
+        self.buffer[self.write_pos] = sample;
+    }
+
+    /// Read the sample from `delay` samples ago, where a delay of zero corresponds to the most
+    /// recently pushed sample. The delay time is clamped to `[0, max_delay]`. For
+    /// [`Interpolation::Cubic`] the delay time is clamped to at least one sample since the
+    /// interpolator needs a sample on both sides of the read position, and for
+    /// [`Interpolation::Allpass`] it's clamped to at least half a sample.
+    #[inline]
+    pub fn read(&mut self, delay: f32, interpolation: Interpolation) -> f32 {
+        let min_delay = match interpolation {
+            Interpolation::Cubic => 1.0,
+            Interpolation::Allpass => 0.5,
+            _ => 0.0,
+        };
+        let delay = delay.clamp(min_delay, self.max_delay as f32);
+        let delay_samples = delay as usize;
+        let frac = delay - delay_samples as f32;
+
+        match interpolation {
+            Interpolation::None => self.sample_at(delay_samples),
+            Interpolation::Linear => {
+                let current = self.sample_at(delay_samples);
+                let next = self.sample_at(delay_samples + 1);
+
+                current + (frac * (next - current))
+            }
+            Interpolation::Cubic => {
+                let previous = self.sample_at(delay_samples - 1);
+                let current = self.sample_at(delay_samples);
+                let next = self.sample_at(delay_samples + 1);
+                let after_next = self.sample_at(delay_samples + 2);
+
+                let c1 = 0.5 * (next - previous);
+                let c2 = previous - (2.5 * current) + (2.0 * next) - (0.5 * after_next);
+                let c3 = (0.5 * (after_next - previous)) + (1.5 * (current - next));
+
+                (((c3 * frac + c2) * frac + c1) * frac) + current
+            }
+            Interpolation::Allpass => {
+                // The allpass filter's pole moves towards -1 as the fractional delay approaches
+                // zero, which makes it ring at the Nyquist frequency. Keeping the fractional part in
+                // `[0.5, 1.5)` by moving a sample from the integer delay avoids that.
+                let (delay_samples, frac) = if frac < 0.5 {
+                    (delay_samples - 1, frac + 1.0)
+                } else {
+                    (delay_samples, frac)
+                };
+
+                let current = self.sample_at(delay_samples);
+                let next = self.sample_at(delay_samples + 1);
+
+                let coefficient = (1.0 - frac) / (1.0 + frac);
+                let output = (coefficient * (current - self.allpass_state)) + next;
+                self.allpass_state = output;
+
+                output
+            }
+        }
+    }
+
+    /// Write a sample to the delay line and then read from it. This is the same as calling
+    /// [`push()`][Self::push()] followed by [`read()`][Self::read()].
+    #[inline]
+    pub fn process(&mut self, sample: f32, delay: f32, interpolation: Interpolation) -> f32 {
+        self.push(sample);
+        self.read(delay, interpolation)
+    }
+
+    #[inline]
+    fn sample_at(&self, delay_samples: usize) -> f32 {
+        self.buffer[self.write_pos.wrapping_sub(delay_samples) & self.mask]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The delay line's response to an impulse.
+    fn impulse_response(delay: f32, interpolation: Interpolation, num_samples: usize) -> Vec<f32> {
+        let mut delay_line = DelayLine::new(16);
+        (0..num_samples)
+            .map(|i| delay_line.process(if i == 0 { 1.0 } else { 0.0 }, delay, interpolation))
+            .collect()
+    }
+
+    #[test]
+    fn integer_delays() {
+        for interpolation in [
+            Interpolation::None,
+            Interpolation::Linear,
+            Interpolation::Cubic,
+            Interpolation::Allpass,
+        ] {
+            for delay in 1..8 {
+                let response = impulse_response(delay as f32, interpolation, 16);
+                for (i, sample) in response.into_iter().enumerate() {
+                    let expected = if i == delay { 1.0 } else { 0.0 };
+                    assert!(
+                        (sample - expected).abs() < 1e-6,
+                        "{interpolation:?}, delay {delay}, sample {i}: {sample}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn linear_interpolation() {
+        let response = impulse_response(2.25, Interpolation::Linear, 8);
+        assert_eq!(response, [0.0, 0.0, 0.75, 0.25, 0.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn allpass_fractional_delays() {
+        for delay in [0.5, 1.2, 2.5, 3.75, 7.05] {
+            let response = impulse_response(delay, Interpolation::Allpass, 64);
+
+            // The filter should have unity gain at DC, and its group delay at DC is the delay time
+            let dc_gain: f32 = response.iter().sum();
+            let group_delay: f32 = response
+                .iter()
+                .enumerate()
+                .map(|(i, sample)| i as f32 * sample)
+                .sum::<f32>()
+                / dc_gain;
+            assert!((dc_gain - 1.0).abs() < 1e-4, "delay {delay}: {dc_gain}");
+            assert!(
+                (group_delay - delay).abs() < 1e-3,
+                "delay {delay}: {group_delay}"
+            );
+
+            // With the fractional delay kept in `[0.5, 1.5)` the impulse response decays quickly
+            assert!(response[32..].iter().all(|sample| sample.abs() < 1e-6));
+        }
+    }
+}
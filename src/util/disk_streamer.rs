@@ -0,0 +1,558 @@
+//! Streaming samples from disk for sampler plugins.
+
+use atomic_float::AtomicF32;
+use crossbeam::channel;
+use parking_lot::Mutex;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How often the worker thread refills the voices' ring buffers when it's not woken up by a new
+/// voice.
+const POLL_INTERVAL: Duration = Duration::from_millis(2);
+/// The maximum number of frames read from a source in one go.
+const MAX_READ_FRAMES: usize = 4096;
+
+/// Audio data that can be streamed by a [`DiskStreamer`]. This is usually implemented on top of an
+/// audio file reader like `hound` or `symphonia`. The source is only accessed from the streamer's
+/// worker thread, so reading may block.
+pub trait StreamSource: Send + 'static {
+    /// The number of channels in the source.
+    fn num_channels(&self) -> usize;
+
+    /// The source's length in frames.
+    fn num_frames(&self) -> u64;
+
+    /// Read `output[0].len()` frames starting at `start_frame` into `output`, with one slice per
+    /// channel. The requested range always lies within the source.
+    fn read(&mut self, start_frame: u64, output: &mut [&mut [f32]]) -> io::Result<()>;
+}
+
+/// Streams samples from disk so sampler plugins can play large sample libraries without loading
+/// everything into memory. Every sample loaded with [`load()`][Self::load()] keeps only its first
+/// few frames in memory. When a [`StreamVoice`] starts playing a sample, it plays those preloaded
+/// frames while a background worker thread reads the rest of the sample into the voice's ring
+/// buffer.
+///
+/// Create the streamer along with a fixed number of voices in your plugin's `Default`
+/// implementation or in [`Plugin::initialize()`][crate::prelude::Plugin::initialize()], move the
+/// voices to the audio thread, and load samples from a background task or from the editor. The
+/// voices don't allocate or block.
+///
+/// ```ignore
+/// let (streamer, mut voices) = DiskStreamer::new(32, 2, 16384, 65536);
+/// let sample = streamer.load(MyWavReader::open("piano_c4.wav")?)?;
+///
+/// // On the audio thread
+/// voices[0].start(&sample, 0);
+/// voices[0].read(output);
+/// ```
+///
+/// The preloaded part should be long enough to cover the time it takes to read from the disk after
+/// a voice has been started. If the worker thread can't keep up, then the voice outputs silence
+/// until it catches up.
+pub struct DiskStreamer {
+    /// Requests for the worker thread.
+    sender: channel::Sender<Message>,
+    /// The number of frames kept in memory for every sample.
+    preload_frames: usize,
+    join_handle: Option<JoinHandle<()>>,
+}
+
+/// A sample that can be streamed by a [`DiskStreamer`]. Load these using [`DiskStreamer::load()`].
+pub struct StreamedSample {
+    /// The first frames of the sample, one vector per channel.
+    head: Vec<Vec<f32>>,
+    num_frames: u64,
+    /// The rest of the sample is read from here by the worker thread.
+    source: Mutex<Box<dyn StreamSource>>,
+}
+
+/// A voice that plays a [`StreamedSample`]. This should be used from the audio thread.
+pub struct StreamVoice {
+    voice_idx: usize,
+    ring: Arc<VoiceRing>,
+    sender: channel::Sender<Message>,
+
+    /// The sample being played, if any.
+    sample: Option<Arc<StreamedSample>>,
+    /// The position within the sample of the next frame to play.
+    pos: u64,
+    /// The sample frame stored at the start of the ring buffer.
+    ring_start_frame: u64,
+    /// The number of frames read from the ring buffer since the voice was started. This is the
+    /// same as `ring.consumed`.
+    consumed: u64,
+    /// Incremented every time the voice is started, so stale data in the ring buffer can be
+    /// recognized.
+    generation: u64,
+}
+
+/// The ring buffer shared between a voice and the worker thread.
+struct VoiceRing {
+    /// The frames for every channel. Each channel has the same length.
+    channels: Vec<Box<[AtomicF32]>>,
+    /// The voice's generation the ring buffer's contents belong to.
+    generation: AtomicU64,
+    /// The number of frames written by the worker thread since the voice was started.
+    written: AtomicU64,
+    /// The number of frames read by the voice since it was started.
+    consumed: AtomicU64,
+}
+
+/// The state the worker thread keeps for a playing voice.
+struct WorkerVoice {
+    sample: Arc<StreamedSample>,
+    ring_start_frame: u64,
+}
+
+enum Message {
+    Start {
+        voice_idx: usize,
+        generation: u64,
+        sample: Arc<StreamedSample>,
+        ring_start_frame: u64,
+    },
+    Stop {
+        voice_idx: usize,
+        /// The sample the voice was playing. The voice sends this to the worker thread so the
+        /// sample is never deallocated on the audio thread.
+        sample: Arc<StreamedSample>,
+    },
+    Shutdown,
+}
+
+impl DiskStreamer {
+    /// Create a streamer with `num_voices` voices that can each play up to `max_channels`
+    /// channels. Samples keep their first `preload_frames` frames in memory, and every voice has
+    /// a ring buffer of `buffer_frames` frames. This spawns the worker thread and allocates.
+    pub fn new(
+        num_voices: usize,
+        max_channels: usize,
+        preload_frames: usize,
+        buffer_frames: usize,
+    ) -> (Self, Vec<StreamVoice>) {
+        let (sender, receiver) = channel::bounded(num_voices.max(1) * 4);
+        let rings: Vec<Arc<VoiceRing>> = (0..num_voices)
+            .map(|_| {
+                Arc::new(VoiceRing {
+                    channels: (0..max_channels)
+                        .map(|_| {
+                            (0..buffer_frames.max(1))
+                                .map(|_| AtomicF32::new(0.0))
+                                .collect()
+                        })
+                        .collect(),
+                    generation: AtomicU64::new(0),
+                    written: AtomicU64::new(0),
+                    consumed: AtomicU64::new(0),
+                })
+            })
+            .collect();
+
+        let voices = rings
+            .iter()
+            .enumerate()
+            .map(|(voice_idx, ring)| StreamVoice {
+                voice_idx,
+                ring: ring.clone(),
+                sender: sender.clone(),
+
+                sample: None,
+                pos: 0,
+                ring_start_frame: 0,
+                consumed: 0,
+                generation: 0,
+            })
+            .collect();
+
+        let join_handle = thread::Builder::new()
+            .name(String::from("disk-streamer"))
+            .spawn(move || worker_thread(receiver, rings))
+            .expect("Could not spawn the disk streaming thread");
+
+        (
+            Self {
+                sender,
+                preload_frames,
+                join_handle: Some(join_handle),
+            },
+            voices,
+        )
+    }
+
+    /// Read the first part of a sample into memory so it can be played by the voices. This
+    /// blocks while reading from the source.
+    pub fn load(&self, mut source: impl StreamSource) -> io::Result<Arc<StreamedSample>> {
+        let num_frames = source.num_frames();
+        let num_head_frames = (self.preload_frames as u64).min(num_frames) as usize;
+        let mut head = vec![vec![0.0; num_head_frames]; source.num_channels()];
+        if num_head_frames > 0 {
+            let mut head_slices: Vec<&mut [f32]> = head
+                .iter_mut()
+                .map(|channel| channel.as_mut_slice())
+                .collect();
+            source.read(0, &mut head_slices)?;
+        }
+
+        Ok(Arc::new(StreamedSample {
+            head,
+            num_frames,
+            source: Mutex::new(Box::new(source)),
+        }))
+    }
+}
+
+impl Drop for DiskStreamer {
+    fn drop(&mut self) {
+        // The voices may still be holding on to the sender, so the thread needs to be stopped
+        // explicitly
+        let _ = self.sender.send(Message::Shutdown);
+        if let Some(join_handle) = self.join_handle.take() {
+            if join_handle.join().is_err() {
+                nih_error!("The disk streaming thread panicked");
+            }
+        }
+    }
+}
+
+impl StreamedSample {
+    /// The number of channels in the sample.
+    pub fn num_channels(&self) -> usize {
+        self.head.len()
+    }
+
+    /// The sample's length in frames.
+    pub fn num_frames(&self) -> u64 {
+        self.num_frames
+    }
+
+    /// The number of frames kept in memory.
+    pub fn num_preloaded_frames(&self) -> usize {
+        self.head.first().map_or(0, Vec::len)
+    }
+}
+
+impl StreamVoice {
+    /// Start playing a sample at `start_frame`. If the voice was already playing a sample, then
+    /// that sample is stopped. Starting past the preloaded part of the sample results in silence
+    /// until the worker thread has read the requested part from the disk.
+    pub fn start(&mut self, sample: &Arc<StreamedSample>, start_frame: u64) {
+        self.stop();
+
+        self.generation += 1;
+        self.pos = start_frame;
+        self.ring_start_frame = start_frame.max(sample.num_preloaded_frames() as u64);
+        self.consumed = 0;
+        self.ring.consumed.store(0, Ordering::Release);
+        self.sample = Some(sample.clone());
+
+        if self.ring_start_frame < sample.num_frames
+            && self
+                .sender
+                .try_send(Message::Start {
+                    voice_idx: self.voice_idx,
+                    generation: self.generation,
+                    sample: sample.clone(),
+                    ring_start_frame: self.ring_start_frame,
+                })
+                .is_err()
+        {
+            nih_debug_assert_failure!("The disk streaming queue is full");
+        }
+    }
+
+    /// Stop playing the current sample. The voice's reference to the sample is dropped on the
+    /// worker thread.
+    pub fn stop(&mut self) {
+        if let Some(sample) = self.sample.take() {
+            // If the worker thread has already shut down then the sample can be dropped here
+            if let Err(channel::TrySendError::Full(_)) = self.sender.try_send(Message::Stop {
+                voice_idx: self.voice_idx,
+                sample,
+            }) {
+                nih_debug_assert_failure!("The disk streaming queue is full");
+            }
+        }
+    }
+
+    /// Whether the voice is playing a sample.
+    pub fn is_playing(&self) -> bool {
+        self.sample.is_some()
+    }
+
+    /// The position within the sample of the next frame that will be played.
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// The number of frames past the preloaded part that have been read from the disk but not yet
+    /// played.
+    pub fn buffered_frames(&self) -> u64 {
+        if self.ring.generation.load(Ordering::Acquire) == self.generation {
+            self.ring
+                .written
+                .load(Ordering::Acquire)
+                .saturating_sub(self.consumed)
+        } else {
+            0
+        }
+    }
+
+    /// Overwrite `output` with the next frames of the sample, with one slice per channel. Returns
+    /// the number of frames written. The rest of `output` is filled with silence, and the voice
+    /// stops once it reaches the end of the sample.
+    pub fn read(&mut self, output: &mut [&mut [f32]]) -> usize {
+        let num_frames = output.first().map_or(0, |channel| channel.len());
+        // This borrows the sample instead of cloning the `Arc` since the worker thread may drop the
+        // last other reference once the voice stops
+        let Some(sample) = self.sample.as_ref() else {
+            for channel in output.iter_mut() {
+                channel.fill(0.0);
+            }
+            return 0;
+        };
+
+        let num_head_frames = sample.num_preloaded_frames() as u64;
+        let capacity = self
+            .ring
+            .channels
+            .first()
+            .map_or(1, |channel| channel.len()) as u64;
+        let mut frame_idx = 0;
+        while frame_idx < num_frames && self.pos < sample.num_frames {
+            let remaining = ((num_frames - frame_idx) as u64).min(sample.num_frames - self.pos);
+            let block_len = if self.pos < num_head_frames {
+                let block_len = remaining.min(num_head_frames - self.pos) as usize;
+                for (channel_idx, channel) in output.iter_mut().enumerate() {
+                    let output = &mut channel[frame_idx..frame_idx + block_len];
+                    match sample.head.get(channel_idx) {
+                        Some(head) => output.copy_from_slice(
+                            &head[self.pos as usize..self.pos as usize + block_len],
+                        ),
+                        None => output.fill(0.0),
+                    }
+                }
+
+                block_len
+            } else {
+                // If the worker thread has not yet caught up, then the missing frames are silent
+                let block_len = remaining as usize;
+                let num_buffered = self.buffered_frames().min(remaining) as usize;
+                for (channel_idx, channel) in output.iter_mut().enumerate() {
+                    let output = &mut channel[frame_idx..frame_idx + block_len];
+                    match self.ring.channels.get(channel_idx) {
+                        Some(ring) if channel_idx < sample.num_channels() => {
+                            for (offset, output_sample) in
+                                output[..num_buffered].iter_mut().enumerate()
+                            {
+                                *output_sample = ring
+                                    [((self.consumed + offset as u64) % capacity) as usize]
+                                    .load(Ordering::Relaxed);
+                            }
+                            output[num_buffered..].fill(0.0);
+                        }
+                        _ => output.fill(0.0),
+                    }
+                }
+
+                self.consumed += block_len as u64;
+                self.ring.consumed.store(self.consumed, Ordering::Release);
+
+                block_len
+            };
+
+            self.pos += block_len as u64;
+            frame_idx += block_len;
+        }
+
+        for channel in output.iter_mut() {
+            channel[frame_idx..].fill(0.0);
+        }
+        if self.pos >= sample.num_frames {
+            self.stop();
+        }
+
+        frame_idx
+    }
+}
+
+impl Drop for StreamVoice {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn worker_thread(receiver: channel::Receiver<Message>, rings: Vec<Arc<VoiceRing>>) {
+    let mut voices: Vec<Option<WorkerVoice>> = rings.iter().map(|_| None).collect();
+    let mut scratch: Vec<Vec<f32>> = Vec::new();
+    loop {
+        match receiver.recv_timeout(POLL_INTERVAL) {
+            Ok(Message::Start {
+                voice_idx,
+                generation,
+                sample,
+                ring_start_frame,
+            }) => {
+                let ring = &rings[voice_idx];
+                ring.written.store(0, Ordering::Release);
+                ring.generation.store(generation, Ordering::Release);
+                voices[voice_idx] = Some(WorkerVoice {
+                    sample,
+                    ring_start_frame,
+                });
+            }
+            Ok(Message::Stop { voice_idx, sample }) => {
+                voices[voice_idx] = None;
+                drop(sample);
+            }
+            Ok(Message::Shutdown) | Err(channel::RecvTimeoutError::Disconnected) => return,
+            Err(channel::RecvTimeoutError::Timeout) => (),
+        }
+
+        for (voice, ring) in voices.iter_mut().zip(rings.iter()) {
+            if let Some(worker_voice) = voice {
+                if let Err(err) = fill_ring(worker_voice, ring, &mut scratch) {
+                    nih_error!("Error while streaming sample: {}", err);
+                    *voice = None;
+                }
+            }
+        }
+    }
+}
+
+/// Read as much of the voice's sample into its ring buffer as fits.
+fn fill_ring(voice: &WorkerVoice, ring: &VoiceRing, scratch: &mut Vec<Vec<f32>>) -> io::Result<()> {
+    let capacity = ring.channels.first().map_or(0, |channel| channel.len()) as u64;
+    let consumed = ring.consumed.load(Ordering::Acquire);
+    loop {
+        // After an underrun the voice may have skipped past the frames that were written so far
+        let written = ring.written.load(Ordering::Relaxed).max(consumed);
+        let next_frame = voice.ring_start_frame + written;
+        let num_frames = capacity
+            .saturating_sub(written - consumed)
+            .min(voice.sample.num_frames.saturating_sub(next_frame))
+            .min(MAX_READ_FRAMES as u64) as usize;
+        if num_frames == 0 {
+            return Ok(());
+        }
+
+        scratch.resize_with(voice.sample.num_channels(), Vec::new);
+        for channel in scratch.iter_mut() {
+            channel.resize(num_frames, 0.0);
+        }
+        let mut slices: Vec<&mut [f32]> = scratch
+            .iter_mut()
+            .map(|channel| &mut channel[..num_frames])
+            .collect();
+        voice.sample.source.lock().read(next_frame, &mut slices)?;
+
+        for (ring_channel, channel) in ring.channels.iter().zip(scratch.iter()) {
+            for (offset, sample) in channel[..num_frames].iter().enumerate() {
+                ring_channel[((written + offset as u64) % capacity) as usize]
+                    .store(*sample, Ordering::Relaxed);
+            }
+        }
+        ring.written
+            .store(written + num_frames as u64, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// A mono source where every frame contains its own index.
+    struct RampSource(u64);
+
+    impl StreamSource for RampSource {
+        fn num_channels(&self) -> usize {
+            1
+        }
+
+        fn num_frames(&self) -> u64 {
+            self.0
+        }
+
+        fn read(&mut self, start_frame: u64, output: &mut [&mut [f32]]) -> io::Result<()> {
+            for (offset, sample) in output[0].iter_mut().enumerate() {
+                *sample = (start_frame + offset as u64) as f32;
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Records the thread the source was dropped on.
+    struct DropRecorder(Arc<Mutex<Option<thread::ThreadId>>>);
+
+    impl StreamSource for DropRecorder {
+        fn num_channels(&self) -> usize {
+            1
+        }
+
+        fn num_frames(&self) -> u64 {
+            8
+        }
+
+        fn read(&mut self, _start_frame: u64, output: &mut [&mut [f32]]) -> io::Result<()> {
+            output[0].fill(0.0);
+            Ok(())
+        }
+    }
+
+    impl Drop for DropRecorder {
+        fn drop(&mut self) {
+            *self.0.lock() = Some(thread::current().id());
+        }
+    }
+
+    /// Wait for up to five seconds until `condition` returns true.
+    fn wait_for(mut condition: impl FnMut() -> bool) {
+        let start = Instant::now();
+        while !condition() {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn samples_are_dropped_on_the_worker_thread() {
+        let (streamer, mut voices) = DiskStreamer::new(1, 1, 4, 8);
+        let dropped_on = Arc::new(Mutex::new(None));
+        let sample = streamer.load(DropRecorder(dropped_on.clone())).unwrap();
+        let other_sample = streamer.load(RampSource(4)).unwrap();
+
+        // Starting another sample releases the voice's reference to the first sample, which is
+        // the last reference after the sample is dropped here
+        let voice = &mut voices[0];
+        voice.start(&sample, 0);
+        drop(sample);
+        voice.start(&other_sample, 0);
+
+        wait_for(|| dropped_on.lock().is_some());
+        assert_ne!(*dropped_on.lock(), Some(thread::current().id()));
+    }
+
+    #[test]
+    fn stream_past_preloaded_frames() {
+        let (streamer, mut voices) = DiskStreamer::new(1, 1, 4, 8);
+        let sample = streamer.load(RampSource(10)).unwrap();
+        let voice = &mut voices[0];
+        voice.start(&sample, 0);
+
+        let mut output = [0.0; 4];
+        assert_eq!(voice.read(&mut [&mut output]), 4);
+        assert_eq!(output, [0.0, 1.0, 2.0, 3.0]);
+
+        wait_for(|| voice.buffered_frames() >= 6);
+        assert_eq!(voice.read(&mut [&mut output]), 4);
+        assert_eq!(output, [4.0, 5.0, 6.0, 7.0]);
+        assert_eq!(voice.read(&mut [&mut output]), 2);
+        assert_eq!(output, [8.0, 9.0, 0.0, 0.0]);
+        assert!(!voice.is_playing());
+    }
+}
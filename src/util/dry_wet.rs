@@ -0,0 +1,234 @@
+//! Mixing the unprocessed input signal back in with the processed signal while compensating for
+//! the plugin's latency.
+
+use std::f32;
+
+use super::vectorized::mix_scaled;
+use crate::buffer::Buffer;
+
+/// The crossfade curve used by [`DryWetMixer::mix_in_dry()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MixingStyle {
+    /// Linear crossfade. The dry and wet gains always add up to one, which works best for
+    /// correlated signals, like when the plugin only slightly alters its input.
+    Linear,
+    /// Equal-power crossfade. The dry and wet powers always add up to one, which avoids a dip in
+    /// loudness halfway through the crossfade when the dry and wet signals are not correlated.
+    EqualPower,
+}
+
+/// Stores a copy of a plugin's input so it can be mixed back in with the plugin's output. The dry
+/// signal is delayed by the plugin's latency so it lines up with the wet signal.
+///
+/// All buffers are allocated upfront in [`new()`][Self::new()], so the mixer can be used from the
+/// process function without allocating.
+pub struct DryWetMixer {
+    /// Ring buffers containing the dry signal, indexed by channel. These contain
+    /// `max_block_size + max_latency` samples.
+    delay_buffers: Vec<Vec<f32>>,
+    max_block_size: usize,
+    /// The position in the ring buffers where the next block of dry samples will be written.
+    next_write_position: usize,
+}
+
+impl DryWetMixer {
+    /// Create a mixer for `num_channels` channels that can handle blocks of up to
+    /// `max_block_size` samples, and a latency of up to `max_latency` samples. This allocates and
+    /// should be called outside of the process function, for instance in
+    /// [`Plugin::initialize()`][crate::prelude::Plugin::initialize()].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num_channels == 0 || max_block_size == 0`.
+    pub fn new(num_channels: usize, max_block_size: usize, max_latency: usize) -> Self {
+        assert_ne!(num_channels, 0);
+        assert_ne!(max_block_size, 0);
+
+        Self {
+            delay_buffers: vec![vec![0.0; max_block_size + max_latency]; num_channels],
+            max_block_size,
+            next_write_position: 0,
+        }
+    }
+
+    /// The number of channels this mixer was configured for.
+    pub fn num_channels(&self) -> usize {
+        self.delay_buffers.len()
+    }
+
+    /// Clear the stored dry signal. Call this from
+    /// [`Plugin::reset()`][crate::prelude::Plugin::reset()].
+    pub fn reset(&mut self) {
+        for buffer in &mut self.delay_buffers {
+            buffer.fill(0.0);
+        }
+        self.next_write_position = 0;
+    }
+
+    /// Store the dry signal from `buffer`. This should be called at the start of the process
+    /// function before the buffer's contents get modified, and it should be followed by a call to
+    /// [`mix_in_dry()`][Self::mix_in_dry()] with the same buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffer` does not have the same number of channels as this mixer, or if it
+    /// contains more than `max_block_size` samples.
+    pub fn write_dry(&mut self, buffer: &Buffer) {
+        assert_eq!(buffer.channels(), self.num_channels());
+        let delay_buffer_len = self.delay_buffers[0].len();
+        let num_samples = buffer.samples();
+        assert!(num_samples <= self.max_block_size);
+
+        for (channel, delay_buffer) in buffer
+            .as_slice_immutable()
+            .iter()
+            .zip(self.delay_buffers.iter_mut())
+        {
+            // The block may wrap around at the end of the ring buffer
+            let num_samples_before_wrap =
+                num_samples.min(delay_buffer_len - self.next_write_position);
+            let num_samples_after_wrap = num_samples - num_samples_before_wrap;
+
+            delay_buffer
+                [self.next_write_position..self.next_write_position + num_samples_before_wrap]
+                .copy_from_slice(&channel[..num_samples_before_wrap]);
+            delay_buffer[..num_samples_after_wrap]
+                .copy_from_slice(&channel[num_samples_before_wrap..]);
+        }
+    }
+
+    /// Mix the dry signal stored by the last [`write_dry()`][Self::write_dry()] call into
+    /// `buffer`, which now contains the wet signal. The dry signal is delayed by `latency`
+    /// samples, which should be the same latency the plugin reports to the host. `ratio` is the
+    /// amount of wet signal, where 0.0 results in only the dry signal and 1.0 results in only the
+    /// wet signal.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffer` does not have the same number of channels as this mixer, if it contains
+    /// more samples than the buffer passed to `write_dry()`, or if `latency > max_latency`.
+    pub fn mix_in_dry(
+        &mut self,
+        buffer: &mut Buffer,
+        ratio: f32,
+        style: MixingStyle,
+        latency: usize,
+    ) {
+        assert_eq!(buffer.channels(), self.num_channels());
+        let delay_buffer_len = self.delay_buffers[0].len();
+        let num_samples = buffer.samples();
+        assert!(num_samples + latency <= delay_buffer_len);
+
+        let ratio = ratio.clamp(0.0, 1.0);
+        let (dry_gain, wet_gain) = match style {
+            MixingStyle::Linear => (1.0 - ratio, ratio),
+            MixingStyle::EqualPower => {
+                let angle = ratio * f32::consts::FRAC_PI_2;
+                (angle.cos(), angle.sin())
+            }
+        };
+
+        let read_position =
+            (self.next_write_position + delay_buffer_len - latency) % delay_buffer_len;
+        for (channel, delay_buffer) in buffer.as_slice().iter_mut().zip(self.delay_buffers.iter()) {
+            // The dry signal may wrap around the end of the ring buffer, in which case it's mixed
+            // in as two contiguous parts
+            let (first_part, second_part) =
+                channel.split_at_mut(num_samples.min(delay_buffer_len - read_position));
+            mix_scaled(
+                first_part,
+                wet_gain,
+                &delay_buffer[read_position..],
+                dry_gain,
+            );
+            mix_scaled(second_part, wet_gain, delay_buffer, dry_gain);
+        }
+
+        self.next_write_position = (self.next_write_position + num_samples) % delay_buffer_len;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run a single block through the mixer. The dry signal is `dry`, and the wet signal is
+    /// silence.
+    fn mix_block(
+        mixer: &mut DryWetMixer,
+        dry: &[f32],
+        ratio: f32,
+        style: MixingStyle,
+        latency: usize,
+    ) -> Vec<f32> {
+        let mut samples = dry.to_vec();
+        let mut buffer = Buffer::default();
+        unsafe { buffer.set_slices(samples.len(), |slices| *slices = vec![&mut samples]) };
+
+        mixer.write_dry(&buffer);
+        buffer.as_slice()[0].fill(0.0);
+        mixer.mix_in_dry(&mut buffer, ratio, style, latency);
+
+        samples
+    }
+
+    #[test]
+    fn latency_compensation() {
+        // The block sizes don't evenly divide the ring buffer's length, so the reads and writes wrap
+        // around in the middle of a block
+        let latency = 5;
+        let mut mixer = DryWetMixer::new(1, 8, latency);
+        let input: Vec<f32> = (1..=64).map(|i| i as f32).collect();
+
+        let mut output = Vec::new();
+        let mut block_sizes = [8, 3, 7, 1, 5].into_iter().cycle();
+        let mut remaining = &input[..];
+        while !remaining.is_empty() {
+            let block_size = block_sizes.next().unwrap().min(remaining.len());
+            let (block, rest) = remaining.split_at(block_size);
+            output.extend(mix_block(
+                &mut mixer,
+                block,
+                0.0,
+                MixingStyle::Linear,
+                latency,
+            ));
+            remaining = rest;
+        }
+
+        assert_eq!(output[..latency], [0.0; 5]);
+        assert_eq!(output[latency..], input[..input.len() - latency]);
+    }
+
+    #[test]
+    fn mixing_styles() {
+        let mut mixer = DryWetMixer::new(1, 4, 0);
+        let mut samples = [1.0f32; 4];
+        let mut buffer = Buffer::default();
+        unsafe { buffer.set_slices(4, |slices| *slices = vec![&mut samples]) };
+
+        // With identical dry and wet signals, the linear crossfade keeps the level constant and the
+        // equal-power crossfade boosts it by 3 dB halfway through
+        mixer.write_dry(&buffer);
+        mixer.mix_in_dry(&mut buffer, 0.5, MixingStyle::Linear, 0);
+        mixer.write_dry(&buffer);
+        mixer.mix_in_dry(&mut buffer, 0.5, MixingStyle::EqualPower, 0);
+        for sample in samples {
+            assert!((sample - f32::consts::SQRT_2).abs() < 1e-6, "{sample}");
+        }
+
+        assert_eq!(
+            mix_block(&mut mixer, &[0.5; 4], 0.0, MixingStyle::EqualPower, 0),
+            [0.5; 4]
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn block_larger_than_max_block_size() {
+        // The ring buffer is large enough to hold this block, but the block is still larger than
+        // the maximum block size
+        let mut mixer = DryWetMixer::new(1, 4, 16);
+        mix_block(&mut mixer, &[0.0; 8], 0.5, MixingStyle::Linear, 0);
+    }
+}
@@ -0,0 +1,185 @@
+//! Envelope followers with attack and release ballistics for dynamics processors and meters.
+
+/// How an [`EnvelopeFollower`] measures the level of its input signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EnvelopeDetector {
+    /// Follow the absolute value of the signal.
+    Peak,
+    /// Follow the signal's mean square value and output its square root. With symmetric attack and
+    /// release times this acts like a sliding RMS window.
+    Rms,
+}
+
+/// How an [`EnvelopeFollower`] releases after the input level drops.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReleaseMode {
+    /// Always release using the configured release time.
+    Fixed,
+    /// Release quickly after short transients, and slowly after sustained material. A second
+    /// envelope charges up and decays with `sustain_ms` as its time constant, and the envelope
+    /// never falls below that sustained envelope. Short peaks barely charge the sustained
+    /// envelope, so they release with the regular release time, while longer passages hold the
+    /// envelope up for longer. This is similar to the 'auto release' found on many compressors.
+    ProgramDependent {
+        /// The time constant of the sustained envelope in milliseconds. This should be longer
+        /// than the regular release time.
+        sustain_ms: f32,
+    },
+}
+
+/// A one-pole envelope follower with separate attack and release times. The times are the time
+/// constants of the smoothing filters, meaning the time it takes for the envelope to cover about
+/// 63% of the distance to the new level.
+///
+/// The follower does not allocate, so it can be created and updated from the audio thread. Call
+/// [`set_sample_rate()`][Self::set_sample_rate()] from your plugin's
+/// [`initialize()`][crate::prelude::Plugin::initialize()] function, and
+/// [`reset()`][Self::reset()] from [`Plugin::reset()`][crate::prelude::Plugin::reset()].
+#[derive(Debug, Clone)]
+pub struct EnvelopeFollower {
+    detector: EnvelopeDetector,
+    release_mode: ReleaseMode,
+
+    sample_rate: f32,
+    attack_ms: f32,
+    release_ms: f32,
+
+    attack_coefficient: f32,
+    release_coefficient: f32,
+    sustain_coefficient: f32,
+
+    /// The current envelope. For the RMS detector this contains the mean square value.
+    envelope: f32,
+    /// The sustained envelope used for [`ReleaseMode::ProgramDependent`]. Uses the same units as
+    /// `envelope`.
+    sustained_envelope: f32,
+}
+
+impl EnvelopeFollower {
+    /// Create a new envelope follower. The sample rate defaults to 44.1 kHz until
+    /// [`set_sample_rate()`][Self::set_sample_rate()] is called.
+    pub fn new(detector: EnvelopeDetector, attack_ms: f32, release_ms: f32) -> Self {
+        let mut follower = Self {
+            detector,
+            release_mode: ReleaseMode::Fixed,
+
+            sample_rate: 44_100.0,
+            attack_ms,
+            release_ms,
+
+            attack_coefficient: 0.0,
+            release_coefficient: 0.0,
+            sustain_coefficient: 0.0,
+
+            envelope: 0.0,
+            sustained_envelope: 0.0,
+        };
+        follower.update_coefficients();
+
+        follower
+    }
+
+    /// Use a different [`ReleaseMode`]. Builder-style alternative to
+    /// [`set_release_mode()`][Self::set_release_mode()].
+    pub fn with_release_mode(mut self, release_mode: ReleaseMode) -> Self {
+        self.set_release_mode(release_mode);
+        self
+    }
+
+    /// The detector this envelope follower was configured with.
+    pub fn detector(&self) -> EnvelopeDetector {
+        self.detector
+    }
+
+    /// Change the sample rate. This recomputes the filter coefficients.
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.update_coefficients();
+    }
+
+    /// Change the attack time in milliseconds.
+    pub fn set_attack_ms(&mut self, attack_ms: f32) {
+        self.attack_ms = attack_ms;
+        self.update_coefficients();
+    }
+
+    /// Change the release time in milliseconds.
+    pub fn set_release_ms(&mut self, release_ms: f32) {
+        self.release_ms = release_ms;
+        self.update_coefficients();
+    }
+
+    /// Change how the envelope releases. See [`ReleaseMode`] for more information.
+    pub fn set_release_mode(&mut self, release_mode: ReleaseMode) {
+        self.release_mode = release_mode;
+        self.update_coefficients();
+    }
+
+    /// Reset the envelope to zero.
+    pub fn reset(&mut self) {
+        self.envelope = 0.0;
+        self.sustained_envelope = 0.0;
+    }
+
+    /// The current envelope value, in the same units as the input signal.
+    pub fn value(&self) -> f32 {
+        match self.detector {
+            EnvelopeDetector::Peak => self.envelope,
+            EnvelopeDetector::Rms => self.envelope.sqrt(),
+        }
+    }
+
+    /// Process a single sample and return the new envelope value, in the same units as the input
+    /// signal.
+    #[inline]
+    pub fn process(&mut self, sample: f32) -> f32 {
+        let input = match self.detector {
+            EnvelopeDetector::Peak => sample.abs(),
+            EnvelopeDetector::Rms => sample * sample,
+        };
+
+        let coefficient = if input > self.envelope {
+            self.attack_coefficient
+        } else {
+            self.release_coefficient
+        };
+        self.envelope = input + (coefficient * (self.envelope - input));
+
+        if let ReleaseMode::ProgramDependent { .. } = self.release_mode {
+            self.sustained_envelope = self.envelope
+                + (self.sustain_coefficient * (self.sustained_envelope - self.envelope));
+            self.envelope = self.envelope.max(self.sustained_envelope);
+        }
+
+        self.value()
+    }
+
+    /// Process a block of samples in place, replacing the samples with their envelope values.
+    pub fn process_block(&mut self, block: &mut [f32]) {
+        for sample in block {
+            *sample = self.process(*sample);
+        }
+    }
+
+    fn update_coefficients(&mut self) {
+        self.attack_coefficient = time_constant_coefficient(self.attack_ms, self.sample_rate);
+        self.release_coefficient = time_constant_coefficient(self.release_ms, self.sample_rate);
+        self.sustain_coefficient = match self.release_mode {
+            ReleaseMode::Fixed => 0.0,
+            ReleaseMode::ProgramDependent { sustain_ms } => {
+                time_constant_coefficient(sustain_ms, self.sample_rate)
+            }
+        };
+    }
+}
+
+/// The coefficient for a one-pole smoothing filter with a time constant of `time_ms`
+/// milliseconds. A time of zero results in a coefficient of zero, meaning no smoothing.
+#[inline]
+fn time_constant_coefficient(time_ms: f32, sample_rate: f32) -> f32 {
+    if time_ms <= 0.0 || sample_rate <= 0.0 {
+        0.0
+    } else {
+        (-1.0 / (time_ms / 1000.0 * sample_rate)).exp()
+    }
+}
@@ -0,0 +1,129 @@
+//! A thin wrapper around real valued FFTs with preallocated scratch buffers.
+
+use realfft::num_complex::Complex32;
+use realfft::{ComplexToReal, RealFftPlanner, RealToComplex};
+use std::sync::Arc;
+
+/// A real valued FFT implementation of a fixed size. [`RealFft`] implements this using the
+/// `realfft` crate. Other implementations can be used with code that's generic over this trait,
+/// for instance to use a platform specific FFT library.
+pub trait FftBackend: Send {
+    /// The number of samples in the time domain signal.
+    fn size(&self) -> usize;
+
+    /// Compute the forward FFT of `input`, which contains [`size()`][Self::size()] samples, and
+    /// write the result to `spectrum`, which contains [`size()`][Self::size()] `/ 2 + 1` bins.
+    /// `input` is used as scratch space and its contents are unspecified afterwards.
+    fn forward(&mut self, input: &mut [f32], spectrum: &mut [Complex32]);
+
+    /// Compute the unnormalized inverse FFT of `spectrum` and write the result to `output`. The
+    /// output is scaled by [`size()`][Self::size()], so multiply it by `1.0 / size` to undo a
+    /// forward FFT. `spectrum` is used as scratch space and its contents are unspecified
+    /// afterwards.
+    fn inverse(&mut self, spectrum: &mut [Complex32], output: &mut [f32]);
+
+    /// The number of frequency bins in the spectrum. This is `size / 2 + 1`.
+    fn num_bins(&self) -> usize {
+        self.size() / 2 + 1
+    }
+}
+
+/// Forward and inverse real valued FFTs of a fixed size, using the `realfft` crate. The FFTs are
+/// planned and the scratch buffer is allocated in [`new()`][Self::new()], so the transforms don't
+/// allocate and can be used from the audio thread. Create this in
+/// [`Plugin::initialize()`][crate::prelude::Plugin::initialize()]. This is also what
+/// [`SpectralStftHelper`][super::SpectralStftHelper] uses internally.
+///
+/// ```
+/// # use nih_plug::util::{Complex32, FftBackend, RealFft};
+/// let mut fft = RealFft::new(8);
+/// let mut signal = [1.0; 8];
+/// let mut spectrum = fft.make_spectrum_buffer();
+/// fft.forward(&mut signal, &mut spectrum);
+/// assert_eq!(spectrum[0], Complex32::new(8.0, 0.0));
+/// ```
+#[derive(Clone)]
+pub struct RealFft {
+    r2c_plan: Arc<dyn RealToComplex<f32>>,
+    c2r_plan: Arc<dyn ComplexToReal<f32>>,
+    /// Scratch space for the FFT algorithms, may be empty.
+    scratch_buffer: Vec<Complex32>,
+}
+
+impl RealFft {
+    /// Plan forward and inverse FFTs for signals containing `size` samples. This allocates.
+    pub fn new(size: usize) -> Self {
+        let mut planner = RealFftPlanner::new();
+        let r2c_plan = planner.plan_fft_forward(size);
+        let c2r_plan = planner.plan_fft_inverse(size);
+        let scratch_buffer =
+            vec![Complex32::default(); r2c_plan.get_scratch_len().max(c2r_plan.get_scratch_len())];
+
+        Self {
+            r2c_plan,
+            c2r_plan,
+            scratch_buffer,
+        }
+    }
+
+    /// Allocate a buffer for the spectrum with the correct number of bins.
+    pub fn make_spectrum_buffer(&self) -> Vec<Complex32> {
+        self.r2c_plan.make_output_vec()
+    }
+}
+
+impl FftBackend for RealFft {
+    fn size(&self) -> usize {
+        self.r2c_plan.len()
+    }
+
+    fn forward(&mut self, input: &mut [f32], spectrum: &mut [Complex32]) {
+        if let Err(err) =
+            self.r2c_plan
+                .process_with_scratch(input, spectrum, &mut self.scratch_buffer)
+        {
+            nih_debug_assert_failure!("Forward FFT failed: {}", err);
+        }
+    }
+
+    fn inverse(&mut self, spectrum: &mut [Complex32], output: &mut [f32]) {
+        // The DC and Nyquist bins need to be real valued for the inverse transform
+        if let Some(first_bin) = spectrum.first_mut() {
+            first_bin.im = 0.0;
+        }
+        if output.len() % 2 == 0 {
+            if let Some(last_bin) = spectrum.last_mut() {
+                last_bin.im = 0.0;
+            }
+        }
+
+        if let Err(err) =
+            self.c2r_plan
+                .process_with_scratch(spectrum, output, &mut self.scratch_buffer)
+        {
+            nih_debug_assert_failure!("Inverse FFT failed: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut fft = RealFft::new(16);
+        assert_eq!(fft.num_bins(), 9);
+
+        let signal: Vec<f32> = (0..16).map(|i| (i as f32 * 0.7).sin()).collect();
+        let mut input = signal.clone();
+        let mut spectrum = fft.make_spectrum_buffer();
+        let mut output = vec![0.0; 16];
+        fft.forward(&mut input, &mut spectrum);
+        fft.inverse(&mut spectrum, &mut output);
+
+        for (output_sample, signal_sample) in output.iter().zip(&signal) {
+            assert!((output_sample / 16.0 - signal_sample).abs() < 1e-5);
+        }
+    }
+}
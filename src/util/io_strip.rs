@@ -0,0 +1,278 @@
+//! Input and output trims with metering, the gain staging block found in most effect plugins.
+
+use atomic_float::AtomicF32;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::buffer::Buffer;
+use crate::formatters;
+use crate::params::internals::ParamPtr;
+use crate::params::range::FloatRange;
+use crate::params::smoothing::{Smoother, SmoothingStyle};
+use crate::params::{BoolParam, FloatParam, Param, Params};
+
+/// The range of the input and output trims in decibels, in both directions.
+const TRIM_RANGE_DB: f32 = 24.0;
+/// The smoothing time for the trims and the automatic gain compensation.
+const TRIM_SMOOTHING_MS: f32 = 50.0;
+/// The time it takes for the meters to decay by 12 dB after switching to complete silence.
+const METER_DECAY_MS: f32 = 150.0;
+
+/// The parameters for an [`IoStrip`]: an input trim, an output trim, and an option to
+/// automatically compensate the output for the input trim. This can be added to a plugin's own
+/// parameters using the `#[nested]` attribute. The parameter IDs are `input_trim`, `output_trim`,
+/// and `auto_gain`, so use `#[nested(id_prefix = "...")]` if those clash with the plugin's other
+/// parameters.
+///
+/// ```ignore
+/// #[derive(Params)]
+/// struct MyParams {
+///     #[nested(group = "IO")]
+///     pub io: IoStripParams,
+///
+///     #[id = "drive"]
+///     pub drive: FloatParam,
+/// }
+/// ```
+pub struct IoStripParams {
+    /// The gain applied to the plugin's input, before the plugin's processing.
+    pub input_trim: FloatParam,
+    /// The gain applied to the plugin's output, after the plugin's processing.
+    pub output_trim: FloatParam,
+    /// When enabled, the output is attenuated by the input trim's gain so changing the input trim
+    /// only changes how hard the plugin is driven and not the plugin's output level.
+    pub auto_gain: BoolParam,
+}
+
+/// The peak levels measured by an [`IoStrip`], shared with the editor. The levels decay smoothly
+/// so they can be displayed directly on a peak meter.
+#[derive(Debug)]
+pub struct IoStripMeters {
+    /// The input's peak level as a linear gain value, after applying the input trim.
+    input_peak: AtomicF32,
+    /// The output's peak level as a linear gain value, after applying the output trim.
+    output_peak: AtomicF32,
+}
+
+/// Applies the trims from [`IoStripParams`] to a plugin's input and output, and measures both
+/// signals' peak levels for the editor. Call [`process_input()`][Self::process_input()] at the
+/// start of the process function and [`process_output()`][Self::process_output()] at the end.
+///
+/// ```ignore
+/// // In `initialize()`
+/// self.io_strip.set_sample_rate(buffer_config.sample_rate);
+///
+/// // In `process()`
+/// self.io_strip.process_input(&self.params.io, buffer);
+/// // Process the audio...
+/// self.io_strip.process_output(&self.params.io, buffer);
+///
+/// // In `editor()`, to pass to `nih_plug_vizia::widgets::IoStrip`
+/// let meters = self.io_strip.meters();
+/// ```
+#[derive(Debug)]
+pub struct IoStrip {
+    meters: Arc<IoStripMeters>,
+
+    sample_rate: f32,
+    /// The amount the meters decay by per sample.
+    meter_decay_weight: f32,
+    /// The gain applied to the output to compensate for the input trim. This uses the same
+    /// smoothing style as the input trim so the compensation closely follows the trim.
+    compensation: Smoother<f32>,
+}
+
+impl Default for IoStripParams {
+    fn default() -> Self {
+        let trim = |name: &str| {
+            FloatParam::new(
+                name,
+                1.0,
+                FloatRange::Skewed {
+                    min: super::db_to_gain(-TRIM_RANGE_DB),
+                    max: super::db_to_gain(TRIM_RANGE_DB),
+                    factor: FloatRange::gain_skew_factor(-TRIM_RANGE_DB, TRIM_RANGE_DB),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(TRIM_SMOOTHING_MS))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db())
+        };
+
+        Self {
+            input_trim: trim("Input Trim"),
+            output_trim: trim("Output Trim"),
+            auto_gain: BoolParam::new("Auto Gain", false),
+        }
+    }
+}
+
+unsafe impl Params for IoStripParams {
+    fn param_map(&self) -> Vec<(String, ParamPtr, String)> {
+        vec![
+            (
+                String::from("input_trim"),
+                self.input_trim.as_ptr(),
+                String::new(),
+            ),
+            (
+                String::from("output_trim"),
+                self.output_trim.as_ptr(),
+                String::new(),
+            ),
+            (
+                String::from("auto_gain"),
+                self.auto_gain.as_ptr(),
+                String::new(),
+            ),
+        ]
+    }
+}
+
+impl Default for IoStripMeters {
+    fn default() -> Self {
+        Self {
+            input_peak: AtomicF32::new(0.0),
+            output_peak: AtomicF32::new(0.0),
+        }
+    }
+}
+
+impl IoStripMeters {
+    /// The input's peak level in decibels, after applying the input trim.
+    pub fn input_peak_db(&self) -> f32 {
+        super::gain_to_db(self.input_peak.load(Ordering::Relaxed))
+    }
+
+    /// The output's peak level in decibels, after applying the output trim.
+    pub fn output_peak_db(&self) -> f32 {
+        super::gain_to_db(self.output_peak.load(Ordering::Relaxed))
+    }
+}
+
+impl Default for IoStrip {
+    fn default() -> Self {
+        let mut io_strip = Self {
+            meters: Arc::default(),
+
+            sample_rate: 44_100.0,
+            meter_decay_weight: 0.0,
+            compensation: Smoother::new(SmoothingStyle::Logarithmic(TRIM_SMOOTHING_MS)),
+        };
+        io_strip.set_sample_rate(io_strip.sample_rate);
+        io_strip.compensation.reset(1.0);
+
+        io_strip
+    }
+}
+
+impl IoStrip {
+    /// Create an IO strip. The sample rate defaults to 44.1 kHz until
+    /// [`set_sample_rate()`][Self::set_sample_rate()] is called.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The meters for this IO strip. Pass these to the editor.
+    pub fn meters(&self) -> Arc<IoStripMeters> {
+        self.meters.clone()
+    }
+
+    /// Change the sample rate. Call this from
+    /// [`Plugin::initialize()`][crate::prelude::Plugin::initialize()].
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.meter_decay_weight = 0.25f32.powf((sample_rate * METER_DECAY_MS / 1000.0).recip());
+    }
+
+    /// Reset the automatic gain compensation and the meters. Call this from
+    /// [`Plugin::reset()`][crate::prelude::Plugin::reset()].
+    pub fn reset(&mut self, params: &IoStripParams) {
+        self.compensation.reset(Self::compensation_target(params));
+        self.meters.input_peak.store(0.0, Ordering::Relaxed);
+        self.meters.output_peak.store(0.0, Ordering::Relaxed);
+    }
+
+    /// Apply the input trim to the buffer, and update the input meter.
+    pub fn process_input(&mut self, params: &IoStripParams, buffer: &mut Buffer) {
+        let mut peak = 0.0f32;
+        for channel_samples in buffer.iter_samples() {
+            let gain = params.input_trim.smoothed.next();
+            for sample in channel_samples {
+                *sample *= gain;
+                peak = peak.max(sample.abs());
+            }
+        }
+
+        self.update_meter(&self.meters.input_peak, peak, buffer.samples());
+    }
+
+    /// Apply the output trim and the automatic gain compensation to the buffer, and update the
+    /// output meter.
+    pub fn process_output(&mut self, params: &IoStripParams, buffer: &mut Buffer) {
+        self.compensation
+            .set_target(self.sample_rate, Self::compensation_target(params));
+
+        let mut peak = 0.0f32;
+        for channel_samples in buffer.iter_samples() {
+            let gain = params.output_trim.smoothed.next() * self.compensation.next();
+            for sample in channel_samples {
+                *sample *= gain;
+                peak = peak.max(sample.abs());
+            }
+        }
+
+        self.update_meter(&self.meters.output_peak, peak, buffer.samples());
+    }
+
+    fn compensation_target(params: &IoStripParams) -> f32 {
+        if params.auto_gain.value() {
+            params.input_trim.value().recip()
+        } else {
+            1.0
+        }
+    }
+
+    /// Update a meter with a block's peak. The meter jumps up to louder peaks and decays
+    /// otherwise.
+    fn update_meter(&self, meter: &AtomicF32, peak: f32, num_samples: usize) {
+        let decayed =
+            meter.load(Ordering::Relaxed) * self.meter_decay_weight.powi(num_samples as i32);
+        meter.store(peak.max(decayed), Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn meters_follow_peaks() {
+        let params = IoStripParams::default();
+        params.input_trim.smoothed.reset(1.0);
+        params.output_trim.smoothed.reset(1.0);
+        let mut io_strip = IoStrip::new();
+        io_strip.set_sample_rate(1000.0);
+        io_strip.reset(&params);
+
+        let mut samples = [0.0f32; 150];
+        samples[10] = -0.5;
+        let mut buffer = Buffer::default();
+        unsafe { buffer.set_slices(150, |slices| *slices = vec![&mut samples]) };
+        io_strip.process_input(&params, &mut buffer);
+        io_strip.process_output(&params, &mut buffer);
+
+        let meters = io_strip.meters();
+        let peak_db = crate::util::gain_to_db(0.5);
+        assert!((meters.input_peak_db() - peak_db).abs() < 1e-4);
+        assert!((meters.output_peak_db() - peak_db).abs() < 1e-4);
+
+        // After the decay time of silence the meter has dropped by 12 dB
+        let mut silence = [0.0f32; 150];
+        unsafe { buffer.set_slices(150, |slices| *slices = vec![&mut silence]) };
+        io_strip.process_input(&params, &mut buffer);
+        assert!((meters.input_peak_db() - (peak_db - 12.0)).abs() < 0.1);
+        assert_eq!(samples[10], -0.5);
+    }
+}
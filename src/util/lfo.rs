@@ -0,0 +1,306 @@
+//! A low frequency oscillator with free-running and tempo-synced rates.
+
+use std::f64::consts::TAU;
+
+use super::Xorshift;
+use crate::context::process::Transport;
+
+/// The tempo used for tempo-synced rates when the host doesn't provide one.
+const FALLBACK_TEMPO: f64 = 120.0;
+
+/// The waveform produced by an [`Lfo`]. All shapes are bipolar and produce values in `[-1, 1]`.
+/// Use `(value + 1.0) / 2.0` for a unipolar LFO.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LfoShape {
+    Sine,
+    /// Starts at zero and rises to the peak a quarter of the way through the cycle, just like the
+    /// sine.
+    Triangle,
+    /// Rises from -1 to 1.
+    SawUp,
+    /// Falls from 1 to -1.
+    SawDown,
+    /// 1 for the first half of the cycle, and -1 for the second half.
+    Square,
+    /// A new random value at the start of every cycle.
+    SampleAndHold,
+}
+
+/// How fast an [`Lfo`] runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LfoRate {
+    /// A fixed frequency in Hertz.
+    Hertz(f32),
+    /// A cycle length in quarter notes that follows the host's tempo. See
+    /// [`LfoRate::note_division()`].
+    Beats(f64),
+}
+
+/// When an [`Lfo`]'s phase gets reset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LfoRetrigger {
+    /// The LFO keeps running and [`Lfo::trigger()`] is ignored.
+    FreeRunning,
+    /// The phase is reset to the phase offset whenever [`Lfo::trigger()`] is called, usually on
+    /// every note on event.
+    Retrigger,
+    /// While the transport is playing, the phase is derived from the host's song position so the
+    /// LFO stays locked to the host's grid, even when the playhead is moved or when the transport
+    /// loops. The LFO runs freely while the transport is stopped.
+    Transport,
+}
+
+/// A low frequency oscillator for modulating parameters or DSP code. The LFO can either run at a
+/// fixed rate in Hertz or at a note division that follows the host's tempo, and it can optionally
+/// be phase-locked to the host's song position using [`LfoRetrigger::Transport`].
+///
+/// The output can be computed at block rate using [`next_block()`][Self::next_block()], or at
+/// sample rate using [`process()`][Self::process()]. Both take the transport from the process
+/// context, which is also where the sample rate comes from. The LFO does not allocate.
+///
+/// ```
+/// # use nih_plug::util::{Lfo, LfoRate, LfoRetrigger, LfoShape};
+/// let mut lfo = Lfo::new(LfoShape::Triangle, LfoRate::note_division(1, 8))
+///     .with_retrigger(LfoRetrigger::Transport);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Lfo {
+    shape: LfoShape,
+    rate: LfoRate,
+    retrigger: LfoRetrigger,
+    /// The phase the LFO starts at after a reset or a retrigger, in `[0, 1)`.
+    phase_offset: f64,
+
+    /// The current phase, in `[0, 1)`.
+    phase: f64,
+    /// The value for [`LfoShape::SampleAndHold`].
+    held_value: f32,
+    /// The random number generator used for [`LfoShape::SampleAndHold`].
+    rng: Xorshift,
+}
+
+impl LfoRate {
+    /// A tempo-synced rate where every cycle lasts `numerator / denominator` whole notes. Use
+    /// `note_division(1, 4)` for quarter notes, `note_division(3, 16)` for dotted eighth notes,
+    /// and `note_division(1, 12)` for eighth note triplets.
+    pub fn note_division(numerator: u32, denominator: u32) -> Self {
+        LfoRate::Beats(4.0 * numerator as f64 / denominator.max(1) as f64)
+    }
+}
+
+impl Lfo {
+    /// Create a free-running LFO.
+    pub fn new(shape: LfoShape, rate: LfoRate) -> Self {
+        let mut lfo = Self {
+            shape,
+            rate,
+            retrigger: LfoRetrigger::FreeRunning,
+            phase_offset: 0.0,
+
+            phase: 0.0,
+            held_value: 0.0,
+            rng: Xorshift::new(0x9E37_79B9),
+        };
+        lfo.held_value = lfo.rng.next_bipolar_f32();
+
+        lfo
+    }
+
+    /// Use a different [`LfoRetrigger`] mode. Builder-style alternative to
+    /// [`set_retrigger()`][Self::set_retrigger()].
+    pub fn with_retrigger(mut self, retrigger: LfoRetrigger) -> Self {
+        self.set_retrigger(retrigger);
+        self
+    }
+
+    /// Change the LFO's waveform.
+    pub fn set_shape(&mut self, shape: LfoShape) {
+        self.shape = shape;
+    }
+
+    /// Change the LFO's rate. The phase is not reset.
+    pub fn set_rate(&mut self, rate: LfoRate) {
+        self.rate = rate;
+    }
+
+    /// Change when the LFO's phase gets reset. See [`LfoRetrigger`].
+    pub fn set_retrigger(&mut self, retrigger: LfoRetrigger) {
+        self.retrigger = retrigger;
+    }
+
+    /// Set the phase the LFO starts at after a reset or a retrigger, in `[0, 1)`. With
+    /// [`LfoRetrigger::Transport`] this offsets the LFO relative to the host's grid.
+    pub fn set_phase_offset(&mut self, phase_offset: f32) {
+        self.phase_offset = (phase_offset as f64).rem_euclid(1.0);
+    }
+
+    /// The current phase, in `[0, 1)`.
+    pub fn phase(&self) -> f32 {
+        self.phase as f32
+    }
+
+    /// The LFO's value at the current phase without advancing it.
+    pub fn value(&self) -> f32 {
+        let phase = self.phase;
+        match self.shape {
+            LfoShape::Sine => (phase * TAU).sin() as f32,
+            LfoShape::Triangle => (4.0 * ((phase + 0.75).fract() - 0.5).abs() - 1.0) as f32,
+            LfoShape::SawUp => (2.0 * phase - 1.0) as f32,
+            LfoShape::SawDown => (1.0 - 2.0 * phase) as f32,
+            LfoShape::Square if phase < 0.5 => 1.0,
+            LfoShape::Square => -1.0,
+            LfoShape::SampleAndHold => self.held_value,
+        }
+    }
+
+    /// Reset the phase to the phase offset. Call this from
+    /// [`Plugin::reset()`][crate::prelude::Plugin::reset()].
+    pub fn reset(&mut self) {
+        self.phase = self.phase_offset;
+    }
+
+    /// Reset the phase to the phase offset if the LFO uses [`LfoRetrigger::Retrigger`]. Call this
+    /// for every note on event.
+    pub fn trigger(&mut self) {
+        if self.retrigger == LfoRetrigger::Retrigger {
+            self.phase = self.phase_offset;
+            self.held_value = self.rng.next_bipolar_f32();
+        }
+    }
+
+    /// Compute the LFO's value at the start of a block of `num_samples` samples, and advance the
+    /// LFO to the end of the block. Use this for modulation that's only updated once per block.
+    pub fn next_block(&mut self, transport: &Transport, num_samples: usize) -> f32 {
+        if let Some(phase) = self.transport_phase(transport, 0) {
+            self.set_phase(phase);
+            return self.value();
+        }
+
+        let value = self.value();
+        self.set_phase(self.phase + (self.phase_increment(transport) * num_samples as f64));
+
+        value
+    }
+
+    /// Write the LFO's value for every sample to `output`, advancing the LFO by `output.len()`
+    /// samples.
+    pub fn process(&mut self, transport: &Transport, output: &mut [f32]) {
+        if self.is_transport_locked(transport) {
+            match (self.rate, transport.pos_beats_iter(output.len())) {
+                // This handles loops in the middle of the block
+                (LfoRate::Beats(beats), Some(positions)) => {
+                    for (sample, pos_beats) in output.iter_mut().zip(positions) {
+                        self.set_phase((pos_beats / beats) + self.phase_offset);
+                        *sample = self.value();
+                    }
+
+                    return;
+                }
+                (LfoRate::Hertz(_), _) if transport.pos_seconds().is_some() => {
+                    for (sample_idx, sample) in output.iter_mut().enumerate() {
+                        if let Some(phase) = self.transport_phase(transport, sample_idx) {
+                            self.set_phase(phase);
+                        }
+                        *sample = self.value();
+                    }
+
+                    return;
+                }
+                // Without a known position the LFO runs freely
+                _ => (),
+            }
+        }
+
+        let phase_increment = self.phase_increment(transport);
+        for sample in output.iter_mut() {
+            *sample = self.value();
+            self.set_phase(self.phase + phase_increment);
+        }
+    }
+
+    /// How far the phase advances per sample when the LFO is running freely.
+    fn phase_increment(&self, transport: &Transport) -> f64 {
+        match self.rate {
+            LfoRate::Hertz(frequency) => frequency as f64 / transport.sample_rate as f64,
+            LfoRate::Beats(beats) => {
+                transport.tempo.unwrap_or(FALLBACK_TEMPO)
+                    / 60.0
+                    / transport.sample_rate as f64
+                    / beats
+            }
+        }
+    }
+
+    fn is_transport_locked(&self, transport: &Transport) -> bool {
+        self.retrigger == LfoRetrigger::Transport && transport.playing
+    }
+
+    /// The phase derived from the host's song position at a sample within the current block, if
+    /// the LFO is locked to the transport and the host provides the position.
+    fn transport_phase(&self, transport: &Transport, sample_idx: usize) -> Option<f64> {
+        if !self.is_transport_locked(transport) {
+            return None;
+        }
+
+        let cycles = match self.rate {
+            LfoRate::Hertz(frequency) => {
+                let pos_seconds =
+                    transport.pos_seconds()? + (sample_idx as f64 / transport.sample_rate as f64);
+                pos_seconds * frequency as f64
+            }
+            LfoRate::Beats(beats) => {
+                let pos_beats =
+                    transport.pos_beats()? + (sample_idx as f64 * transport.beats_per_sample()?);
+                pos_beats / beats
+            }
+        };
+
+        Some(cycles + self.phase_offset)
+    }
+
+    /// Move to a new phase, wrapping it to `[0, 1)`. A new sample-and-hold value is picked when
+    /// the phase wraps around.
+    fn set_phase(&mut self, phase: f64) {
+        let phase = phase.rem_euclid(1.0);
+        if phase < self.phase {
+            self.held_value = self.rng.next_bipolar_f32();
+        }
+        self.phase = phase;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn free_running_sine() {
+        let transport = Transport::new(4.0);
+        let mut lfo = Lfo::new(LfoShape::Sine, LfoRate::Hertz(1.0));
+
+        let mut output = [0.0; 4];
+        lfo.process(&transport, &mut output);
+        for (sample, expected) in output.iter().zip([0.0, 1.0, 0.0, -1.0]) {
+            assert!((sample - expected).abs() < 1e-6);
+        }
+        assert_eq!(lfo.next_block(&transport, 2), 0.0);
+        assert_eq!(lfo.phase(), 0.5);
+    }
+
+    #[test]
+    fn transport_locked_saw() {
+        let mut transport = Transport::new(48_000.0);
+        transport.playing = true;
+        transport.tempo = Some(120.0);
+        transport.pos_beats = Some(3.0);
+
+        // A cycle lasts two bars, so three beats in the LFO is at 3/8 of its cycle
+        let mut lfo = Lfo::new(LfoShape::SawUp, LfoRate::note_division(2, 1))
+            .with_retrigger(LfoRetrigger::Transport);
+        assert_eq!(lfo.next_block(&transport, 512), -0.25);
+
+        // The phase follows the playhead instead of accumulating
+        transport.pos_beats = Some(0.0);
+        assert_eq!(lfo.next_block(&transport, 512), -1.0);
+    }
+}
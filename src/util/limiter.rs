@@ -0,0 +1,227 @@
+//! A lookahead peak limiter for safety limiting at the end of a plugin's signal chain.
+
+use std::collections::VecDeque;
+
+use crate::buffer::Buffer;
+
+/// A lookahead peak limiter that keeps the output's sample peaks at or below a ceiling. The input
+/// is delayed by the attack time so the gain reduction can be fully applied by the time a peak
+/// reaches the output, without any overshoot. The gain reduction starts ramping down over the
+/// attack time and recovers with a one-pole release. All channels share the same gain reduction
+/// to preserve the stereo image.
+///
+/// The delay introduces [`latency_samples()`][Self::latency_samples()] samples of latency. Add
+/// this to the latency the plugin reports using
+/// [`InitContext::set_latency_samples()`][crate::prelude::InitContext::set_latency_samples()]. The
+/// limiter allocates its buffers when it's created, so create it in
+/// [`Plugin::initialize()`][crate::prelude::Plugin::initialize()] and call
+/// [`reset()`][Self::reset()] from [`Plugin::reset()`][crate::prelude::Plugin::reset()].
+///
+/// ```ignore
+/// // In `initialize()`
+/// self.limiter = LookaheadLimiter::new(2, 5.0, buffer_config.sample_rate);
+/// self.limiter.set_ceiling_db(-0.3);
+/// context.set_latency_samples(self.limiter.latency_samples());
+///
+/// // At the end of `process()`
+/// self.limiter.process(buffer);
+/// context.set_gain_reduction(self.limiter.gain_reduction_db());
+/// ```
+#[derive(Debug, Clone)]
+pub struct LookaheadLimiter {
+    sample_rate: f32,
+    /// The length of the minimum filter and the moving average filter. The audio is delayed by
+    /// one sample less than this.
+    window_len: usize,
+    /// The ceiling as a linear gain value.
+    ceiling: f32,
+    release_ms: f32,
+    release_coefficient: f32,
+
+    /// Ring buffers of `window_len` samples containing the delayed audio for every channel.
+    delay_buffers: Vec<Vec<f32>>,
+    /// The gain values in the moving average window. Uses the same positions as the delay
+    /// buffers.
+    smoothing_buffer: Vec<f32>,
+    /// The sum of `smoothing_buffer`'s values. This is tracked in double precision to avoid
+    /// accumulating rounding errors.
+    smoothing_sum: f64,
+    /// The position in the ring buffers the next sample will be written to.
+    pos: usize,
+
+    /// The `(sample_idx, gain)` pairs for a sliding window minimum over the required gain values.
+    /// The gain values are increasing from front to back.
+    min_gains: VecDeque<(u64, f32)>,
+    /// The number of samples processed since the last reset.
+    sample_idx: u64,
+    /// The minimum required gain after applying the release.
+    envelope: f32,
+    /// The gain applied to the last processed sample.
+    current_gain: f32,
+}
+
+impl LookaheadLimiter {
+    /// Create a limiter for `num_channels` channels with an attack and lookahead time of
+    /// `attack_ms` milliseconds. The ceiling defaults to 0 dBFS and the release time defaults to
+    /// 100 milliseconds. This allocates.
+    pub fn new(num_channels: usize, attack_ms: f32, sample_rate: f32) -> Self {
+        let window_len = ((attack_ms.max(0.0) / 1000.0 * sample_rate).round() as usize).max(1);
+        let mut limiter = Self {
+            sample_rate,
+            window_len,
+            ceiling: 1.0,
+            release_ms: 100.0,
+            release_coefficient: 0.0,
+
+            delay_buffers: vec![vec![0.0; window_len]; num_channels],
+            smoothing_buffer: vec![1.0; window_len],
+            smoothing_sum: window_len as f64,
+            pos: 0,
+
+            min_gains: VecDeque::with_capacity(window_len),
+            sample_idx: 0,
+            envelope: 1.0,
+            current_gain: 1.0,
+        };
+        limiter.update_release_coefficient();
+
+        limiter
+    }
+
+    /// The limiter's latency in samples. The plugin needs to report this to the host.
+    pub fn latency_samples(&self) -> u32 {
+        (self.window_len - 1) as u32
+    }
+
+    /// Set the maximum output peak level in decibels.
+    pub fn set_ceiling_db(&mut self, ceiling_db: f32) {
+        self.ceiling = crate::util::db_to_gain(ceiling_db);
+    }
+
+    /// Change the release time in milliseconds. This is the time constant of the release filter,
+    /// so it takes about this long for the gain reduction to recover by 63%.
+    pub fn set_release_ms(&mut self, release_ms: f32) {
+        self.release_ms = release_ms;
+        self.update_release_coefficient();
+    }
+
+    /// The current gain reduction in decibels, as a positive number. This can be passed directly
+    /// to [`ProcessContext::set_gain_reduction()`][crate::prelude::ProcessContext::set_gain_reduction()].
+    pub fn gain_reduction_db(&self) -> f32 {
+        -crate::util::gain_to_db(self.current_gain).min(0.0)
+    }
+
+    /// Clear the delay buffers and the gain reduction.
+    pub fn reset(&mut self) {
+        for delay_buffer in &mut self.delay_buffers {
+            delay_buffer.fill(0.0);
+        }
+        self.smoothing_buffer.fill(1.0);
+        self.smoothing_sum = self.window_len as f64;
+        self.pos = 0;
+
+        self.min_gains.clear();
+        self.sample_idx = 0;
+        self.envelope = 1.0;
+        self.current_gain = 1.0;
+    }
+
+    /// Limit the audio in a buffer in place. The buffer should have the same number of channels
+    /// as the limiter.
+    pub fn process(&mut self, buffer: &mut Buffer) {
+        nih_debug_assert_eq!(buffer.channels(), self.delay_buffers.len());
+
+        let num_channels = buffer.channels().min(self.delay_buffers.len());
+        let channels = &mut buffer.as_slice()[..num_channels];
+        for sample_idx in 0..channels.first().map_or(0, |channel| channel.len()) {
+            let peak = channels
+                .iter()
+                .fold(0.0f32, |peak, channel| peak.max(channel[sample_idx].abs()));
+            let gain = self.next_gain(peak);
+
+            for (channel, delay_buffer) in channels.iter_mut().zip(&mut self.delay_buffers) {
+                let sample = &mut channel[sample_idx];
+                delay_buffer[self.pos] = *sample;
+                // The oldest sample in the ring buffer is the one after the current position
+                *sample = delay_buffer[(self.pos + 1) % self.window_len] * gain;
+            }
+
+            self.pos = (self.pos + 1) % self.window_len;
+        }
+    }
+
+    /// Compute the gain for the delayed output sample, given the peak of the next input frame.
+    fn next_gain(&mut self, peak: f32) -> f32 {
+        let required_gain = if peak > self.ceiling {
+            self.ceiling / peak
+        } else {
+            1.0
+        };
+
+        // The minimum required gain over the last `window_len` samples. Values that are higher
+        // than the new value can never become the minimum again. Old values are removed first so
+        // the deque never grows past its initial capacity.
+        let oldest_idx = (self.sample_idx + 1).saturating_sub(self.window_len as u64);
+        while matches!(self.min_gains.front(), Some((idx, _)) if *idx < oldest_idx) {
+            self.min_gains.pop_front();
+        }
+        while matches!(self.min_gains.back(), Some((_, gain)) if *gain >= required_gain) {
+            self.min_gains.pop_back();
+        }
+        self.min_gains.push_back((self.sample_idx, required_gain));
+        let min_gain = self.min_gains.front().map_or(1.0, |(_, gain)| *gain);
+        self.sample_idx += 1;
+
+        // The gain is reduced instantly and then released. The moving average turns the instant
+        // reduction into a ramp over the attack time that ends exactly when the peak reaches the
+        // output.
+        self.envelope = if min_gain < self.envelope {
+            min_gain
+        } else {
+            min_gain + ((self.envelope - min_gain) * self.release_coefficient)
+        };
+        self.smoothing_sum += (self.envelope - self.smoothing_buffer[self.pos]) as f64;
+        self.smoothing_buffer[self.pos] = self.envelope;
+        self.current_gain = ((self.smoothing_sum / self.window_len as f64) as f32).min(1.0);
+
+        self.current_gain
+    }
+
+    fn update_release_coefficient(&mut self) {
+        self.release_coefficient = if self.release_ms > 0.0 {
+            (-1.0 / (self.release_ms / 1000.0 * self.sample_rate)).exp()
+        } else {
+            0.0
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_peaks_without_overshoot() {
+        let mut limiter = LookaheadLimiter::new(1, 1.0, 8000.0);
+        limiter.set_ceiling_db(-6.0);
+        assert_eq!(limiter.latency_samples(), 7);
+
+        let mut input = [0.25f32; 64];
+        input[20] = 1.0;
+        input[21] = -2.0;
+        let mut samples = input;
+        let mut buffer = Buffer::default();
+        unsafe { buffer.set_slices(64, |slices| *slices = vec![&mut samples]) };
+        limiter.process(&mut buffer);
+
+        let ceiling = crate::util::db_to_gain(-6.0);
+        for (output, input) in samples[7..].iter().zip(&input) {
+            assert!(output.abs() <= ceiling * 1.0001);
+            // The signal is attenuated, but not flipped or replaced
+            assert_eq!(output.signum(), input.signum());
+        }
+        assert!(limiter.gain_reduction_db() > 0.0);
+        // The quiet signal before the peaks passes through untouched
+        assert_eq!(samples[10], 0.25);
+    }
+}
@@ -0,0 +1,369 @@
+//! Loudness measurements following ITU-R BS.1770 and EBU R 128.
+
+use std::f64::consts::PI;
+
+use super::{TruePeakDetector, MINUS_INFINITY_DB};
+use crate::audio_setup::Speaker;
+use crate::buffer::Buffer;
+
+/// The length of a measurement block in seconds. Blocks are combined into the 400 ms momentary
+/// and gating windows and into the three second short-term window.
+const BLOCK_SECONDS: f32 = 0.1;
+/// The number of blocks in the momentary loudness window, which is also the gating block length.
+const MOMENTARY_BLOCKS: usize = 4;
+/// The number of blocks in the short-term loudness window.
+const SHORT_TERM_BLOCKS: usize = 30;
+/// Gating blocks below this loudness are ignored for the integrated loudness.
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+/// Gating blocks more than this many LU below the absolute-gated loudness are ignored for the
+/// integrated loudness.
+const RELATIVE_GATE_LU: f64 = -10.0;
+/// The resolution of the histogram used for the integrated loudness, in LU.
+const HISTOGRAM_RESOLUTION_LU: f64 = 0.1;
+/// The loudness of the histogram's highest bin. Louder blocks are counted in that bin.
+const HISTOGRAM_MAX_LUFS: f64 = 30.0;
+
+/// Measures a signal's momentary, short-term, and integrated loudness in LUFS following ITU-R
+/// BS.1770-4 and EBU R 128, as well as its true peak level. The signal is K-weighted, and the
+/// channels are weighted based on the buffer's channel layout. The LFE channel is ignored and
+/// surround channels are boosted by 1.5 dB.
+///
+/// The integrated loudness uses a histogram with a resolution of 0.1 LU for the gating, so it can
+/// be measured over any length of time without allocating. The meter allocates its buffers when
+/// it's created, so create it in [`Plugin::initialize()`][crate::prelude::Plugin::initialize()]
+/// and call [`reset()`][Self::reset()] from [`Plugin::reset()`][crate::prelude::Plugin::reset()]
+/// if the measurement should restart when the host resets the plugin.
+///
+/// ```ignore
+/// // In `process()`
+/// self.loudness_meter.process(buffer);
+/// self.momentary_lufs
+///     .store(self.loudness_meter.momentary_lufs(), Ordering::Relaxed);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LoudnessMeter {
+    /// The K-weighting filters for every channel.
+    filters: Vec<[Biquad; 2]>,
+    /// The weights applied to every channel's mean square, based on the channel layout.
+    channel_weights: Vec<f64>,
+
+    /// The length of a block in samples.
+    block_len: usize,
+    /// The number of samples in the current block so far.
+    block_pos: usize,
+    /// The sum of the weighted squared K-weighted samples in the current block so far.
+    block_sum: f64,
+    /// The weighted mean squares of the last [`SHORT_TERM_BLOCKS`] blocks, used as a ring buffer.
+    block_energies: [f64; SHORT_TERM_BLOCKS],
+    /// The index in `block_energies` the next block's energy will be written to.
+    block_idx: usize,
+    /// The number of finished blocks since the last reset, capped at [`SHORT_TERM_BLOCKS`].
+    num_blocks: usize,
+
+    /// The number of gating blocks and the sum of their energies in every 0.1 LU bin, starting at
+    /// the absolute gate.
+    histogram: Vec<(u64, f64)>,
+
+    true_peak_detector: TruePeakDetector,
+    /// The highest absolute oversampled sample value since the last reset.
+    true_peak: f32,
+}
+
+/// A biquad filter in transposed direct form II. This uses double precision since the K-weighting
+/// highpass filter has a very low cutoff frequency.
+#[derive(Debug, Clone, Copy, Default)]
+struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+
+    s1: f64,
+    s2: f64,
+}
+
+impl LoudnessMeter {
+    /// Create a meter for `num_channels` channels. This allocates.
+    pub fn new(num_channels: usize, sample_rate: f32) -> Self {
+        let num_histogram_bins =
+            ((HISTOGRAM_MAX_LUFS - ABSOLUTE_GATE_LUFS) / HISTOGRAM_RESOLUTION_LU).round() as usize
+                + 1;
+
+        Self {
+            filters: vec![k_weighting_filters(sample_rate as f64); num_channels],
+            channel_weights: vec![1.0; num_channels],
+
+            block_len: ((sample_rate * BLOCK_SECONDS).round() as usize).max(1),
+            block_pos: 0,
+            block_sum: 0.0,
+            block_energies: [0.0; SHORT_TERM_BLOCKS],
+            block_idx: 0,
+            num_blocks: 0,
+
+            histogram: vec![(0, 0.0); num_histogram_bins],
+
+            true_peak_detector: TruePeakDetector::new(num_channels),
+            true_peak: 0.0,
+        }
+    }
+
+    /// Clear the filters and all measurements.
+    pub fn reset(&mut self) {
+        for filters in &mut self.filters {
+            for filter in filters {
+                filter.reset();
+            }
+        }
+
+        self.block_pos = 0;
+        self.block_sum = 0.0;
+        self.block_energies.fill(0.0);
+        self.block_idx = 0;
+        self.num_blocks = 0;
+        self.histogram.fill((0, 0.0));
+
+        self.true_peak_detector.reset();
+        self.true_peak = 0.0;
+    }
+
+    /// The loudness over the last 400 milliseconds in LUFS, or [`MINUS_INFINITY_DB`] if less than
+    /// 400 milliseconds have been measured.
+    pub fn momentary_lufs(&self) -> f32 {
+        self.windowed_lufs(MOMENTARY_BLOCKS)
+    }
+
+    /// The loudness over the last three seconds in LUFS, or [`MINUS_INFINITY_DB`] if less than
+    /// three seconds have been measured.
+    pub fn short_term_lufs(&self) -> f32 {
+        self.windowed_lufs(SHORT_TERM_BLOCKS)
+    }
+
+    /// The gated loudness since the last reset in LUFS, or [`MINUS_INFINITY_DB`] if the signal has
+    /// been silent. This iterates over a histogram with a thousand bins, so avoid calling it for
+    /// every block if it's not needed.
+    pub fn integrated_lufs(&self) -> f32 {
+        let (count, energy) = self
+            .histogram
+            .iter()
+            .fold((0, 0.0), |(count, energy), (bin_count, bin_energy)| {
+                (count + bin_count, energy + bin_energy)
+            });
+        if count == 0 {
+            return MINUS_INFINITY_DB;
+        }
+
+        let relative_gate = energy_to_lufs(energy / count as f64) + RELATIVE_GATE_LU;
+        let first_bin = ((relative_gate - ABSOLUTE_GATE_LUFS) / HISTOGRAM_RESOLUTION_LU)
+            .ceil()
+            .max(0.0) as usize;
+        let (count, energy) = self.histogram[first_bin.min(self.histogram.len())..]
+            .iter()
+            .fold((0, 0.0), |(count, energy), (bin_count, bin_energy)| {
+                (count + bin_count, energy + bin_energy)
+            });
+        if count == 0 {
+            return MINUS_INFINITY_DB;
+        }
+
+        energy_to_lufs(energy / count as f64) as f32
+    }
+
+    /// The highest true peak level since the last reset in dBTP. See [`TruePeakDetector`].
+    pub fn true_peak_db(&self) -> f32 {
+        super::gain_to_db(self.true_peak)
+    }
+
+    /// Measure a block of audio. The buffer should have the same number of channels as the meter.
+    /// The buffer is not modified.
+    pub fn process(&mut self, buffer: &Buffer) {
+        nih_debug_assert_eq!(buffer.channels(), self.filters.len());
+
+        self.update_channel_weights(buffer.speakers());
+
+        let num_channels = buffer.channels().min(self.filters.len());
+        let channels = &buffer.as_slice_immutable()[..num_channels];
+        for sample_idx in 0..buffer.samples() {
+            for (channel_idx, channel) in channels.iter().enumerate() {
+                let sample = channel[sample_idx];
+
+                let [shelf, highpass] = &mut self.filters[channel_idx];
+                let weighted = highpass.process(shelf.process(sample as f64));
+                self.block_sum += self.channel_weights[channel_idx] * weighted * weighted;
+
+                let true_peak = self.true_peak_detector.process_sample(channel_idx, sample);
+                self.true_peak = self.true_peak.max(true_peak);
+            }
+
+            self.block_pos += 1;
+            if self.block_pos == self.block_len {
+                self.finish_block();
+            }
+        }
+    }
+
+    /// Store the current block's energy, and add the gating block ending at this block to the
+    /// histogram.
+    fn finish_block(&mut self) {
+        self.block_energies[self.block_idx] = self.block_sum / self.block_len as f64;
+        self.block_idx = (self.block_idx + 1) % SHORT_TERM_BLOCKS;
+        self.num_blocks = (self.num_blocks + 1).min(SHORT_TERM_BLOCKS);
+        self.block_pos = 0;
+        self.block_sum = 0.0;
+
+        // The gating blocks are 400 ms long and overlap by 75%
+        if self.num_blocks >= MOMENTARY_BLOCKS {
+            let energy = self.windowed_energy(MOMENTARY_BLOCKS);
+            let loudness = energy_to_lufs(energy);
+            if loudness > ABSOLUTE_GATE_LUFS {
+                let bin_idx = (((loudness - ABSOLUTE_GATE_LUFS) / HISTOGRAM_RESOLUTION_LU)
+                    as usize)
+                    .min(self.histogram.len() - 1);
+                let (count, bin_energy) = &mut self.histogram[bin_idx];
+                *count += 1;
+                *bin_energy += energy;
+            }
+        }
+    }
+
+    /// The mean energy of the last `num_blocks` blocks.
+    fn windowed_energy(&self, num_blocks: usize) -> f64 {
+        let sum: f64 = (1..=num_blocks)
+            .map(|offset| {
+                self.block_energies
+                    [(self.block_idx + SHORT_TERM_BLOCKS - offset) % SHORT_TERM_BLOCKS]
+            })
+            .sum();
+
+        sum / num_blocks as f64
+    }
+
+    fn windowed_lufs(&self, num_blocks: usize) -> f32 {
+        if self.num_blocks < num_blocks {
+            return MINUS_INFINITY_DB;
+        }
+
+        (energy_to_lufs(self.windowed_energy(num_blocks)) as f32).max(MINUS_INFINITY_DB)
+    }
+
+    /// Set the channel weights from BS.1770-4 for the buffer's speakers. The side channels in 7.1
+    /// layouts are the surround channels, and the back channels are only treated as surround
+    /// channels when there are no side channels.
+    fn update_channel_weights(&mut self, speakers: Option<&[Speaker]>) {
+        let Some(speakers) = speakers else {
+            self.channel_weights.fill(1.0);
+            return;
+        };
+
+        let has_side_channels = speakers.contains(&Speaker::SideLeft);
+        for (weight, speaker) in self.channel_weights.iter_mut().zip(speakers) {
+            *weight = match speaker {
+                Speaker::LowFrequency => 0.0,
+                Speaker::SideLeft | Speaker::SideRight => 1.41,
+                Speaker::BackLeft | Speaker::BackRight if !has_side_channels => 1.41,
+                _ => 1.0,
+            };
+        }
+    }
+}
+
+impl Biquad {
+    #[inline]
+    fn process(&mut self, sample: f64) -> f64 {
+        let result = (self.b0 * sample) + self.s1;
+        self.s1 = (self.b1 * sample) - (self.a1 * result) + self.s2;
+        self.s2 = (self.b2 * sample) - (self.a2 * result);
+
+        result
+    }
+
+    fn reset(&mut self) {
+        self.s1 = 0.0;
+        self.s2 = 0.0;
+    }
+}
+
+/// Convert a weighted mean square to LUFS.
+fn energy_to_lufs(energy: f64) -> f64 {
+    -0.691 + (10.0 * energy.max(1e-20).log10())
+}
+
+/// The two stage K-weighting filter from BS.1770-4, consisting of a high shelf that models the
+/// acoustic effects of the head followed by the RLB highpass filter. The coefficients are derived
+/// for the sample rate so they match the standard's 48 kHz coefficients at that sample rate.
+fn k_weighting_filters(sample_rate: f64) -> [Biquad; 2] {
+    let shelf = {
+        let frequency = 1681.974450955533;
+        let gain_db = 3.999843853973347;
+        let q = 0.7071752369554196;
+
+        let k = (PI * frequency / sample_rate).tan();
+        let vh = 10.0f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + (k / q) + (k * k);
+        Biquad {
+            b0: (vh + (vb * k / q) + (k * k)) / a0,
+            b1: 2.0 * ((k * k) - vh) / a0,
+            b2: (vh - (vb * k / q) + (k * k)) / a0,
+            a1: 2.0 * ((k * k) - 1.0) / a0,
+            a2: (1.0 - (k / q) + (k * k)) / a0,
+            ..Default::default()
+        }
+    };
+
+    let highpass = {
+        let frequency = 38.13547087602444;
+        let q = 0.5003270373238773;
+
+        let k = (PI * frequency / sample_rate).tan();
+        let a0 = 1.0 + (k / q) + (k * k);
+        Biquad {
+            b0: 1.0,
+            b1: -2.0,
+            b2: 1.0,
+            a1: 2.0 * ((k * k) - 1.0) / a0,
+            a2: (1.0 - (k / q) + (k * k)) / a0,
+            ..Default::default()
+        }
+    };
+
+    [shelf, highpass]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stereo_sine_reference_level() {
+        // BS.1770 is calibrated so a 0 dBFS 997 Hz sine wave in a single channel measures at
+        // -3.01 LUFS, so the same sine wave in both stereo channels measures at 0 LUFS
+        let sample_rate = 48_000.0;
+        let num_samples = 4 * 48_000;
+        let mut channels: Vec<Vec<f32>> = (0..2)
+            .map(|_| {
+                (0..num_samples)
+                    .map(|i| (i as f32 / sample_rate * 997.0 * std::f32::consts::TAU).sin())
+                    .collect()
+            })
+            .collect();
+
+        let mut meter = LoudnessMeter::new(2, sample_rate);
+        let mut buffer = Buffer::default();
+        unsafe {
+            buffer.set_slices(num_samples, |slices| {
+                *slices = channels.iter_mut().map(|c| c.as_mut_slice()).collect()
+            })
+        };
+        meter.process(&buffer);
+
+        assert!(
+            meter.momentary_lufs().abs() < 0.05,
+            "{}",
+            meter.momentary_lufs()
+        );
+        assert!(meter.short_term_lufs().abs() < 0.05);
+        assert!(meter.integrated_lufs().abs() < 0.1);
+        assert!(meter.true_peak_db().abs() < 0.1);
+    }
+}
@@ -0,0 +1,358 @@
+//! Note priority, legato, and portamento handling for monophonic synthesizers.
+
+use crate::midi::NoteEvent;
+use crate::params::smoothing::{Smoother, SmoothingStyle};
+
+/// The maximum number of held notes a [`MonoNoteStack`] keeps track of. When more notes are held,
+/// the oldest note is forgotten.
+const MAX_HELD_NOTES: usize = 128;
+
+/// Which of the held notes a [`MonoNoteStack`] plays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NotePriority {
+    /// Play the most recently pressed note. Releasing it returns to the previously pressed note
+    /// that's still held.
+    Last,
+    /// Play the highest held note.
+    Highest,
+    /// Play the lowest held note.
+    Lowest,
+}
+
+/// What happens when a [`MonoNoteStack`] switches notes while another note is still held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TriggerMode {
+    /// Every note change retriggers the envelopes.
+    Retrigger,
+    /// Only notes played while no other notes are held trigger the envelopes. Other note changes
+    /// only change the pitch.
+    Legato,
+}
+
+/// A change to the played note, returned by [`MonoNoteStack::handle_event()`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MonoNoteChange {
+    /// A note should be (re)triggered, starting its envelopes from the beginning.
+    Trigger {
+        /// The note's MIDI key number, in `0..128`.
+        note: u8,
+        /// The note's velocity, in `[0, 1]`.
+        velocity: f32,
+    },
+    /// The played note changed without retriggering the envelopes.
+    Legato {
+        /// The note's MIDI key number, in `0..128`.
+        note: u8,
+        /// The note's velocity, in `[0, 1]`.
+        velocity: f32,
+    },
+    /// All notes have been released and the envelopes should start their release stage.
+    Release,
+}
+
+/// A held note.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct HeldNote {
+    channel: u8,
+    note: u8,
+    velocity: f32,
+}
+
+/// Turns a stream of polyphonic [`NoteEvent`]s into a single monophonic note using
+/// [`NotePriority`] and [`TriggerMode`], and glides the pitch between notes with a configurable
+/// portamento time. Portamento is only applied when switching between overlapping notes, a note
+/// played after all other notes have been released starts at its own pitch.
+///
+/// Pass every incoming note event to [`handle_event()`][Self::handle_event()] to find out when to
+/// trigger or release the synth's envelopes, and call [`next_pitch()`][Self::next_pitch()] once per
+/// sample to get the gliding pitch. This does not allocate after it has been created. Call
+/// [`set_sample_rate()`][Self::set_sample_rate()] from your plugin's
+/// [`initialize()`][crate::prelude::Plugin::initialize()] function, and
+/// [`reset()`][Self::reset()] from [`Plugin::reset()`][crate::prelude::Plugin::reset()].
+#[derive(Debug)]
+pub struct MonoNoteStack {
+    priority: NotePriority,
+    trigger_mode: TriggerMode,
+    sample_rate: f32,
+
+    /// The held notes, in the order they were pressed.
+    held_notes: Vec<HeldNote>,
+    /// The note that's currently playing, or `None` once all notes are released. The pitch
+    /// smoother keeps the last note's pitch for the release stage.
+    current_note: Option<HeldNote>,
+    /// The pitch as a fractional MIDI note number. The smoothing style's duration is the
+    /// portamento time.
+    pitch: Smoother<f32>,
+}
+
+impl MonoNoteStack {
+    /// Create a new note stack. The portamento time is in milliseconds, and it's the time it takes
+    /// to glide between two notes regardless of their interval. The sample rate defaults to
+    /// 44.1 kHz until [`set_sample_rate()`][Self::set_sample_rate()] is called.
+    pub fn new(priority: NotePriority, trigger_mode: TriggerMode, portamento_ms: f32) -> Self {
+        Self {
+            priority,
+            trigger_mode,
+            sample_rate: 44_100.0,
+
+            held_notes: Vec::with_capacity(MAX_HELD_NOTES),
+            current_note: None,
+            pitch: Smoother::new(SmoothingStyle::Linear(portamento_ms.max(0.0))),
+        }
+    }
+
+    /// Change the note priority. This takes effect on the next note event.
+    pub fn set_priority(&mut self, priority: NotePriority) {
+        self.priority = priority;
+    }
+
+    /// Change the trigger mode. This takes effect on the next note event.
+    pub fn set_trigger_mode(&mut self, trigger_mode: TriggerMode) {
+        self.trigger_mode = trigger_mode;
+    }
+
+    /// Change the portamento time in milliseconds. This takes effect on the next note change.
+    pub fn set_portamento_time(&mut self, portamento_ms: f32) {
+        self.pitch.style = SmoothingStyle::Linear(portamento_ms.max(0.0));
+    }
+
+    /// Set the sample rate used to compute the portamento glide.
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Forget all held notes.
+    pub fn reset(&mut self) {
+        self.held_notes.clear();
+        self.current_note = None;
+    }
+
+    /// The note that's currently playing, if any notes are held.
+    pub fn current_note(&self) -> Option<u8> {
+        self.current_note.map(|held_note| held_note.note)
+    }
+
+    /// The pitch for the next sample as a fractional MIDI note number. Use
+    /// [`f32_midi_note_to_freq()`][super::f32_midi_note_to_freq()] to convert this to a frequency.
+    /// This should be called exactly once per sample.
+    #[inline]
+    pub fn next_pitch(&self) -> f32 {
+        self.pitch.next()
+    }
+
+    /// Handle a note event. Note on, note off, and choke events are handled, and all other events
+    /// are ignored. Choke events are treated the same as note off events. Returns how the played
+    /// note changed, if it did.
+    pub fn handle_event<S>(&mut self, event: &NoteEvent<S>) -> Option<MonoNoteChange> {
+        match *event {
+            NoteEvent::NoteOn {
+                channel,
+                note,
+                velocity,
+                ..
+            } => {
+                self.remove_held_note(channel, note);
+                if self.held_notes.len() == MAX_HELD_NOTES {
+                    self.held_notes.remove(0);
+                }
+                let pressed_note = HeldNote {
+                    channel,
+                    note,
+                    velocity,
+                };
+                self.held_notes.push(pressed_note);
+
+                self.update_current_note(Some(pressed_note))
+            }
+            NoteEvent::NoteOff { channel, note, .. } | NoteEvent::Choke { channel, note, .. } => {
+                if self.remove_held_note(channel, note) {
+                    self.update_current_note(None)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Remove a note from the held notes. Returns `false` if the note was not held.
+    fn remove_held_note(&mut self, channel: u8, note: u8) -> bool {
+        match self
+            .held_notes
+            .iter()
+            .position(|held_note| held_note.channel == channel && held_note.note == note)
+        {
+            Some(idx) => {
+                self.held_notes.remove(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Pick the note to play after the held notes changed, and update the pitch accordingly.
+    /// `pressed_note` is the note that was just pressed, if the held notes changed because of a
+    /// note on event.
+    fn update_current_note(&mut self, pressed_note: Option<HeldNote>) -> Option<MonoNoteChange> {
+        let new_note = match self.priority {
+            NotePriority::Last => self.held_notes.last(),
+            NotePriority::Highest => self
+                .held_notes
+                .iter()
+                .max_by_key(|held_note| held_note.note),
+            NotePriority::Lowest => self
+                .held_notes
+                .iter()
+                .min_by_key(|held_note| held_note.note),
+        }
+        .copied();
+
+        let was_playing = self.current_note.is_some();
+        match new_note {
+            Some(new_note) if was_playing => {
+                if self.current_note.map(|held_note| held_note.note) == Some(new_note.note) {
+                    self.current_note = Some(new_note);
+
+                    // Only pressing the playing note again retriggers it, and only in retrigger
+                    // mode. Pressing or releasing other notes does not change anything.
+                    return match self.trigger_mode {
+                        TriggerMode::Retrigger if pressed_note == Some(new_note) => {
+                            Some(MonoNoteChange::Trigger {
+                                note: new_note.note,
+                                velocity: new_note.velocity,
+                            })
+                        }
+                        _ => None,
+                    };
+                }
+
+                self.current_note = Some(new_note);
+                self.pitch
+                    .set_target(self.sample_rate, new_note.note as f32);
+
+                Some(match self.trigger_mode {
+                    TriggerMode::Retrigger => MonoNoteChange::Trigger {
+                        note: new_note.note,
+                        velocity: new_note.velocity,
+                    },
+                    TriggerMode::Legato => MonoNoteChange::Legato {
+                        note: new_note.note,
+                        velocity: new_note.velocity,
+                    },
+                })
+            }
+            Some(new_note) => {
+                self.current_note = Some(new_note);
+                self.pitch.reset(new_note.note as f32);
+
+                Some(MonoNoteChange::Trigger {
+                    note: new_note.note,
+                    velocity: new_note.velocity,
+                })
+            }
+            None if was_playing => {
+                // The pitch stays at the last note so the release stage sounds right
+                self.current_note = None;
+
+                Some(MonoNoteChange::Release)
+            }
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note_on(note: u8) -> NoteEvent<()> {
+        NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity: 1.0,
+        }
+    }
+
+    fn note_off(note: u8) -> NoteEvent<()> {
+        NoteEvent::NoteOff {
+            timing: 0,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity: 0.0,
+        }
+    }
+
+    #[test]
+    fn last_note_priority_legato() {
+        let mut stack = MonoNoteStack::new(NotePriority::Last, TriggerMode::Legato, 0.0);
+
+        assert_eq!(
+            stack.handle_event(&note_on(60)),
+            Some(MonoNoteChange::Trigger {
+                note: 60,
+                velocity: 1.0
+            })
+        );
+        assert_eq!(
+            stack.handle_event(&note_on(64)),
+            Some(MonoNoteChange::Legato {
+                note: 64,
+                velocity: 1.0
+            })
+        );
+        assert_eq!(stack.next_pitch(), 64.0);
+
+        // Releasing the played note returns to the previous note
+        assert_eq!(
+            stack.handle_event(&note_off(64)),
+            Some(MonoNoteChange::Legato {
+                note: 60,
+                velocity: 1.0
+            })
+        );
+        assert_eq!(
+            stack.handle_event(&note_off(60)),
+            Some(MonoNoteChange::Release)
+        );
+        assert_eq!(stack.current_note(), None);
+    }
+
+    #[test]
+    fn lowest_note_priority_retrigger() {
+        let mut stack = MonoNoteStack::new(NotePriority::Lowest, TriggerMode::Retrigger, 0.0);
+
+        stack.handle_event(&note_on(60));
+        // A higher note doesn't change anything
+        assert_eq!(stack.handle_event(&note_on(64)), None);
+        assert_eq!(
+            stack.handle_event(&note_on(55)),
+            Some(MonoNoteChange::Trigger {
+                note: 55,
+                velocity: 1.0
+            })
+        );
+        assert_eq!(stack.handle_event(&note_off(64)), None);
+        assert_eq!(stack.current_note(), Some(55));
+    }
+
+    #[test]
+    fn portamento() {
+        let mut stack = MonoNoteStack::new(NotePriority::Last, TriggerMode::Legato, 10.0);
+        stack.set_sample_rate(1000.0);
+
+        // The first note should not glide
+        stack.handle_event(&note_on(60));
+        assert_eq!(stack.next_pitch(), 60.0);
+
+        // The glide takes 10 samples at this sample rate
+        stack.handle_event(&note_on(70));
+        assert_eq!(stack.next_pitch(), 61.0);
+        for _ in 0..8 {
+            stack.next_pitch();
+        }
+        assert_eq!(stack.next_pitch(), 70.0);
+    }
+}
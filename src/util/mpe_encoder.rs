@@ -0,0 +1,592 @@
+//! Conversion from per-note expressions to MPE MIDI output.
+
+use super::pitch_bend::{pitch_bend_from_14bit, PitchBendRange, PITCH_BEND_CENTER};
+use crate::midi::NoteEvent;
+
+/// The per-channel controls an [`MpeEncoder`] uses to send a note's expressions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dimension {
+    /// Sent as pitch bend, converted from [`NoteEvent::PolyTuning`].
+    PitchBend,
+    /// Sent as channel pressure, converted from [`NoteEvent::PolyPressure`].
+    Pressure,
+    /// Sent as CC74, converted from [`NoteEvent::PolyBrightness`].
+    Brightness,
+    /// Sent as CC7, converted from [`NoteEvent::PolyVolume`].
+    Volume,
+    /// Sent as CC10, converted from [`NoteEvent::PolyPan`].
+    Pan,
+    /// Sent as CC11, converted from [`NoteEvent::PolyExpression`].
+    Expression,
+    /// Sent as CC1, converted from [`NoteEvent::PolyVibrato`].
+    Vibrato,
+}
+
+const NUM_DIMENSIONS: usize = 7;
+const DIMENSIONS: [Dimension; NUM_DIMENSIONS] = [
+    Dimension::PitchBend,
+    Dimension::Pressure,
+    Dimension::Brightness,
+    Dimension::Volume,
+    Dimension::Pan,
+    Dimension::Expression,
+    Dimension::Vibrato,
+];
+
+/// A member channel used by an [`MpeEncoder`].
+#[derive(Debug, Clone, Copy)]
+struct MemberChannel {
+    /// The note playing on this channel as a `(voice_id, channel, note)` triple, using the note's
+    /// original channel.
+    note: Option<(Option<i32>, u8, u8)>,
+    /// When the channel was last used to start or stop a note. Used to pick the channel that has
+    /// been idle the longest so release tails can ring out, and to pick the oldest note when a
+    /// note needs to be stolen.
+    last_used: u64,
+    /// The last values sent for every [`Dimension`], indexed by the dimension's position in
+    /// `DIMENSIONS`.
+    values: [f32; NUM_DIMENSIONS],
+}
+
+/// Converts notes with per-note expressions into MPE (MIDI Polyphonic Expression) MIDI, so a
+/// plugin can drive external MPE synths using [`NoteEvent`]s. Every note gets its own member
+/// channel in an MPE lower zone, where channel 0 is the master channel and channels 1 through
+/// `num_member_channels` are the member channels. The note's expressions are then sent as channel
+/// wide messages on that member channel:
+///
+/// | Note expression                   | MPE message                                  |
+/// | --------------------------------- | -------------------------------------------- |
+/// | [`NoteEvent::PolyTuning`]         | Pitch bend, see [`set_pitch_bend_range()`]   |
+/// | [`NoteEvent::PolyPressure`]       | Channel pressure                             |
+/// | [`NoteEvent::PolyBrightness`]     | CC74                                         |
+/// | [`NoteEvent::PolyVolume`]         | CC7, with the gain clamped to `[0, 1]`       |
+/// | [`NoteEvent::PolyPan`]            | CC10                                         |
+/// | [`NoteEvent::PolyExpression`]     | CC11                                         |
+/// | [`NoteEvent::PolyVibrato`]        | CC1                                          |
+///
+/// Pass every outgoing event to [`process()`][Self::process()] and send the events it emits to
+/// the host instead. Other events are passed through unchanged. When a member channel is reused,
+/// its controls are first reset to their defaults so the previous note's expressions don't carry
+/// over. If more notes are played than there are member channels, then the oldest note is
+/// stopped. Choke events are sent as note off events since they don't exist in MIDI. The plugin
+/// needs to set [`Plugin::MIDI_OUTPUT`][crate::prelude::Plugin::MIDI_OUTPUT] to
+/// [`MidiConfig::MidiCCs`][crate::prelude::MidiConfig::MidiCCs] for the converted events to be
+/// sent.
+///
+/// Receiving synths need to know about the zone layout. Send the events from
+/// [`configuration_events()`][Self::configuration_events()] when playback starts, or whenever the
+/// configuration changes.
+///
+/// This does not allocate. Call [`reset()`][Self::reset()] from
+/// [`Plugin::reset()`][crate::prelude::Plugin::reset()].
+///
+/// [`set_pitch_bend_range()`]: Self::set_pitch_bend_range()
+#[derive(Debug, Clone)]
+pub struct MpeEncoder {
+    /// The member channels, starting at MIDI channel 1.
+    channels: [MemberChannel; 15],
+    /// The number of member channels in use, in `1..=15`.
+    num_member_channels: usize,
+    /// The pitch bend range used on the member channels.
+    pitch_bend_range: PitchBendRange,
+    /// Incremented every time a channel is used.
+    counter: u64,
+}
+
+impl Dimension {
+    /// The value the synth assumes before this control has been sent.
+    fn default_value(self) -> f32 {
+        match self {
+            Dimension::PitchBend => pitch_bend_from_14bit(PITCH_BEND_CENTER),
+            Dimension::Pressure => 0.0,
+            Dimension::Brightness => 64.0 / 127.0,
+            Dimension::Volume => 1.0,
+            Dimension::Pan => 64.0 / 127.0,
+            Dimension::Expression => 1.0,
+            Dimension::Vibrato => 0.0,
+        }
+    }
+
+    /// The event for setting this control to a normalized value.
+    fn event<S>(self, timing: u32, channel: u8, value: f32) -> NoteEvent<S> {
+        let cc = |cc: u8| NoteEvent::MidiCC {
+            timing,
+            channel,
+            cc,
+            value,
+        };
+
+        match self {
+            Dimension::PitchBend => NoteEvent::MidiPitchBend {
+                timing,
+                channel,
+                value,
+            },
+            Dimension::Pressure => NoteEvent::MidiChannelPressure {
+                timing,
+                channel,
+                pressure: value,
+            },
+            Dimension::Brightness => cc(74),
+            Dimension::Volume => cc(7),
+            Dimension::Pan => cc(10),
+            Dimension::Expression => cc(11),
+            Dimension::Vibrato => cc(1),
+        }
+    }
+}
+
+impl Default for MpeEncoder {
+    fn default() -> Self {
+        Self::new(15)
+    }
+}
+
+impl MpeEncoder {
+    /// Create an encoder for a lower zone with `num_member_channels` member channels, clamped to
+    /// `[1, 15]`. The member channels use the standard MPE pitch bend range of 48 semitones.
+    pub fn new(num_member_channels: u8) -> Self {
+        Self {
+            channels: [MemberChannel {
+                note: None,
+                last_used: 0,
+                values: DIMENSIONS.map(Dimension::default_value),
+            }; 15],
+            num_member_channels: num_member_channels.clamp(1, 15) as usize,
+            pitch_bend_range: PitchBendRange::MPE,
+            counter: 0,
+        }
+    }
+
+    /// The number of member channels.
+    pub fn num_member_channels(&self) -> u8 {
+        self.num_member_channels as u8
+    }
+
+    /// The pitch bend range used to convert [`NoteEvent::PolyTuning`] events to pitch bend.
+    pub fn pitch_bend_range(&self) -> PitchBendRange {
+        self.pitch_bend_range
+    }
+
+    /// Change the pitch bend range used on the member channels. This should match the receiving
+    /// synth's setting, which can be changed by sending the events from
+    /// [`configuration_events()`][Self::configuration_events()]. Only the upwards range is sent
+    /// to the synth.
+    pub fn set_pitch_bend_range(&mut self, pitch_bend_range: PitchBendRange) {
+        self.pitch_bend_range = pitch_bend_range;
+    }
+
+    /// Forget all playing notes and the sent controller values without emitting any events.
+    pub fn reset(&mut self) {
+        *self = Self {
+            pitch_bend_range: self.pitch_bend_range,
+            ..Self::new(self.num_member_channels as u8)
+        };
+    }
+
+    /// Emit the MPE Configuration Message for the zone, followed by the pitch bend range for
+    /// every member channel. These are sent as RPN messages using CCs 101, 100, 6, and 38.
+    pub fn configuration_events<S>(&self, timing: u32, mut emit: impl FnMut(NoteEvent<S>)) {
+        let mut rpn = |channel: u8, rpn: u8, msb: u8, lsb: Option<u8>| {
+            let cc = |cc: u8, value: u8| NoteEvent::MidiCC {
+                timing,
+                channel,
+                cc,
+                value: value as f32 / 127.0,
+            };
+
+            emit(cc(101, 0));
+            emit(cc(100, rpn));
+            emit(cc(6, msb));
+            if let Some(lsb) = lsb {
+                emit(cc(38, lsb));
+            }
+        };
+
+        // RPN 6 is the MPE Configuration Message, sent on the zone's master channel
+        rpn(0, 6, self.num_member_channels as u8, None);
+
+        // RPN 0 is the pitch bend sensitivity in semitones and cents
+        let range = self.pitch_bend_range.up.clamp(0.0, 127.0);
+        let semitones = range.trunc() as u8;
+        let cents = ((range.fract() * 100.0).round() as u8).min(99);
+        for channel in 1..=self.num_member_channels as u8 {
+            rpn(channel, 0, semitones, Some(cents));
+        }
+    }
+
+    /// Convert an event. The converted events are passed to `emit`, in order, using the original
+    /// event's timing.
+    pub fn process<S>(&mut self, event: NoteEvent<S>, mut emit: impl FnMut(NoteEvent<S>)) {
+        match event {
+            NoteEvent::NoteOn {
+                timing,
+                voice_id,
+                channel,
+                note,
+                velocity,
+            } => {
+                let channel_idx = self.allocate_channel(timing, &mut emit);
+                self.counter += 1;
+                let member = &mut self.channels[channel_idx];
+                member.note = Some((voice_id, channel, note));
+                member.last_used = self.counter;
+
+                emit(NoteEvent::NoteOn {
+                    timing,
+                    voice_id,
+                    channel: channel_idx as u8 + 1,
+                    note,
+                    velocity,
+                });
+            }
+            NoteEvent::NoteOff {
+                timing,
+                voice_id,
+                channel,
+                note,
+                velocity,
+            } => self.stop_note(timing, voice_id, channel, note, velocity, &mut emit),
+            NoteEvent::Choke {
+                timing,
+                voice_id,
+                channel,
+                note,
+            } => self.stop_note(timing, voice_id, channel, note, 0.0, &mut emit),
+            NoteEvent::PolyTuning {
+                timing,
+                voice_id,
+                channel,
+                note,
+                tuning,
+            } => {
+                let value = self.pitch_bend_range.normalized(tuning);
+                self.set_dimension(
+                    (voice_id, channel, note),
+                    Dimension::PitchBend,
+                    timing,
+                    value,
+                    &mut emit,
+                );
+            }
+            NoteEvent::PolyPressure {
+                timing,
+                voice_id,
+                channel,
+                note,
+                pressure,
+            } => self.set_dimension(
+                (voice_id, channel, note),
+                Dimension::Pressure,
+                timing,
+                pressure,
+                &mut emit,
+            ),
+            NoteEvent::PolyBrightness {
+                timing,
+                voice_id,
+                channel,
+                note,
+                brightness,
+            } => self.set_dimension(
+                (voice_id, channel, note),
+                Dimension::Brightness,
+                timing,
+                brightness,
+                &mut emit,
+            ),
+            NoteEvent::PolyVolume {
+                timing,
+                voice_id,
+                channel,
+                note,
+                gain,
+            } => self.set_dimension(
+                (voice_id, channel, note),
+                Dimension::Volume,
+                timing,
+                gain.clamp(0.0, 1.0),
+                &mut emit,
+            ),
+            NoteEvent::PolyPan {
+                timing,
+                voice_id,
+                channel,
+                note,
+                pan,
+            } => self.set_dimension(
+                (voice_id, channel, note),
+                Dimension::Pan,
+                timing,
+                (pan + 1.0) / 2.0,
+                &mut emit,
+            ),
+            NoteEvent::PolyExpression {
+                timing,
+                voice_id,
+                channel,
+                note,
+                expression,
+            } => self.set_dimension(
+                (voice_id, channel, note),
+                Dimension::Expression,
+                timing,
+                expression,
+                &mut emit,
+            ),
+            NoteEvent::PolyVibrato {
+                timing,
+                voice_id,
+                channel,
+                note,
+                vibrato,
+            } => self.set_dimension(
+                (voice_id, channel, note),
+                Dimension::Vibrato,
+                timing,
+                vibrato,
+                &mut emit,
+            ),
+            event => emit(event),
+        }
+    }
+
+    /// Find a member channel for a new note and reset its controls. This is the free channel that
+    /// has been idle the longest. If all channels are in use, then the oldest note is stopped.
+    fn allocate_channel<S>(&mut self, timing: u32, emit: &mut impl FnMut(NoteEvent<S>)) -> usize {
+        let channels = &mut self.channels[..self.num_member_channels];
+        let channel_idx = match channels
+            .iter()
+            .enumerate()
+            .filter(|(_, member)| member.note.is_none())
+            .min_by_key(|(_, member)| member.last_used)
+        {
+            Some((channel_idx, _)) => channel_idx,
+            None => {
+                let (channel_idx, member) = channels
+                    .iter_mut()
+                    .enumerate()
+                    .min_by_key(|(_, member)| member.last_used)
+                    .expect("There is always at least one member channel");
+                if let Some((voice_id, _, note)) = member.note.take() {
+                    emit(NoteEvent::NoteOff {
+                        timing,
+                        voice_id,
+                        channel: channel_idx as u8 + 1,
+                        note,
+                        velocity: 0.0,
+                    });
+                }
+
+                channel_idx
+            }
+        };
+
+        let member = &mut channels[channel_idx];
+        for (dimension, value) in DIMENSIONS.iter().zip(member.values.iter_mut()) {
+            if *value != dimension.default_value() {
+                *value = dimension.default_value();
+                emit(dimension.event(timing, channel_idx as u8 + 1, *value));
+            }
+        }
+
+        channel_idx
+    }
+
+    /// Send a note off for a note on its member channel and free the channel.
+    fn stop_note<S>(
+        &mut self,
+        timing: u32,
+        voice_id: Option<i32>,
+        channel: u8,
+        note: u8,
+        velocity: f32,
+        emit: &mut impl FnMut(NoteEvent<S>),
+    ) {
+        let Some(channel_idx) = self.find_note((voice_id, channel, note)) else {
+            return;
+        };
+
+        self.counter += 1;
+        let member = &mut self.channels[channel_idx];
+        let (voice_id, _, note) = member.note.take().expect("Found an empty channel");
+        member.last_used = self.counter;
+
+        emit(NoteEvent::NoteOff {
+            timing,
+            voice_id,
+            channel: channel_idx as u8 + 1,
+            note,
+            velocity,
+        });
+    }
+
+    /// Send a control change for a note on its member channel, if the value changed. Expressions
+    /// for notes that are not playing are ignored.
+    fn set_dimension<S>(
+        &mut self,
+        note: (Option<i32>, u8, u8),
+        dimension: Dimension,
+        timing: u32,
+        value: f32,
+        emit: &mut impl FnMut(NoteEvent<S>),
+    ) {
+        let Some(channel_idx) = self.find_note(note) else {
+            return;
+        };
+
+        let dimension_idx = DIMENSIONS
+            .iter()
+            .position(|d| *d == dimension)
+            .expect("Unknown dimension");
+        let value = value.clamp(0.0, 1.0);
+        let sent_value = &mut self.channels[channel_idx].values[dimension_idx];
+        if *sent_value != value {
+            *sent_value = value;
+            emit(dimension.event(timing, channel_idx as u8 + 1, value));
+        }
+    }
+
+    /// Find the member channel a note is playing on. Notes are matched on their voice IDs if the
+    /// event has one, and on their original channel and note numbers otherwise.
+    fn find_note(&self, (voice_id, channel, note): (Option<i32>, u8, u8)) -> Option<usize> {
+        self.channels[..self.num_member_channels]
+            .iter()
+            .position(|member| match (member.note, voice_id) {
+                (Some((Some(playing_voice_id), _, _)), Some(voice_id)) => {
+                    playing_voice_id == voice_id
+                }
+                (Some((_, playing_channel, playing_note)), _) => {
+                    playing_channel == channel && playing_note == note
+                }
+                (None, _) => false,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(encoder: &mut MpeEncoder, event: NoteEvent<()>) -> Vec<NoteEvent<()>> {
+        let mut events = Vec::new();
+        encoder.process(event, |event| events.push(event));
+
+        events
+    }
+
+    fn note_on(note: u8) -> NoteEvent<()> {
+        note_on_channel(note, 0)
+    }
+
+    fn note_on_channel(note: u8, channel: u8) -> NoteEvent<()> {
+        NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: None,
+            channel,
+            note,
+            velocity: 1.0,
+        }
+    }
+
+    #[test]
+    fn rotate_channels() {
+        let mut encoder = MpeEncoder::new(2);
+        assert_eq!(process(&mut encoder, note_on(60))[0].channel(), Some(1));
+        assert_eq!(process(&mut encoder, note_on(64))[0].channel(), Some(2));
+
+        // The expressions are sent on the note's member channel
+        assert_eq!(
+            process(
+                &mut encoder,
+                NoteEvent::PolyBrightness {
+                    timing: 0,
+                    voice_id: None,
+                    channel: 0,
+                    note: 64,
+                    brightness: 1.0,
+                }
+            ),
+            [NoteEvent::MidiCC {
+                timing: 0,
+                channel: 2,
+                cc: 74,
+                value: 1.0,
+            }]
+        );
+
+        // With all channels in use, the oldest note is stopped
+        let events = process(&mut encoder, note_on(67));
+        assert_eq!(
+            events,
+            [
+                NoteEvent::NoteOff {
+                    timing: 0,
+                    voice_id: None,
+                    channel: 1,
+                    note: 60,
+                    velocity: 0.0,
+                },
+                note_on_channel(67, 1),
+            ]
+        );
+
+        // Reusing a channel resets the previous note's expressions
+        process(
+            &mut encoder,
+            NoteEvent::NoteOff {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note: 64,
+                velocity: 0.0,
+            },
+        );
+        process(
+            &mut encoder,
+            NoteEvent::Choke {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note: 67,
+            },
+        );
+        let events = process(&mut encoder, note_on(72));
+        assert_eq!(
+            events,
+            [
+                NoteEvent::MidiCC {
+                    timing: 0,
+                    channel: 2,
+                    cc: 74,
+                    value: 64.0 / 127.0,
+                },
+                note_on_channel(72, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn tuning_to_pitch_bend() {
+        let mut encoder = MpeEncoder::new(15);
+        process(&mut encoder, note_on(60));
+        let events = process(
+            &mut encoder,
+            NoteEvent::PolyTuning {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note: 60,
+                tuning: -48.0,
+            },
+        );
+        assert_eq!(
+            events,
+            [NoteEvent::MidiPitchBend {
+                timing: 0,
+                channel: 1,
+                value: 0.0,
+            }]
+        );
+    }
+}
@@ -0,0 +1,422 @@
+//! Composable note event transformations for building MIDI effects.
+//!
+//! Every transformation implements [`NoteTransform`], and transformations can be chained with
+//! [`NoteTransform::then()`]. The transformations' settings are public fields, so they can be
+//! updated from the plugin's parameters at the start of every process call. Transformations that
+//! change or filter notes remember what happened to every held note, so changing the settings
+//! while notes are held never results in stuck notes:
+//!
+//! ```ignore
+//! // `self.transpose`, `self.key_range`, and `self.velocity_curve` are stored in the plugin
+//! self.transpose.semitones = self.params.transpose.value();
+//! self.key_range.lowest_note = self.params.lowest_note.value() as u8;
+//! self.key_range.highest_note = self.params.highest_note.value() as u8;
+//! self.velocity_curve.curve = self.params.velocity_curve.value();
+//!
+//! let mut note_fx = (&mut self.key_range)
+//!     .then(&mut self.transpose)
+//!     .then(&mut self.velocity_curve);
+//! while let Some(event) = context.next_event() {
+//!     if let Some(event) = note_fx.transform(event) {
+//!         context.send_event(event);
+//!     }
+//! }
+//! ```
+//!
+//! None of these transformations allocate after they have been created.
+
+use crate::midi::NoteEvent;
+
+/// A step in a note event processing chain.
+pub trait NoteTransform<S> {
+    /// Transform an event. Returning `None` filters out the event.
+    fn transform(&mut self, event: NoteEvent<S>) -> Option<NoteEvent<S>>;
+
+    /// Forget all held notes. Call this from
+    /// [`Plugin::reset()`][crate::prelude::Plugin::reset()].
+    fn reset(&mut self) {}
+
+    /// Run `next` on the events produced by this transformation.
+    fn then<T: NoteTransform<S>>(self, next: T) -> Then<Self, T>
+    where
+        Self: Sized,
+    {
+        Then {
+            first: self,
+            second: next,
+        }
+    }
+}
+
+/// Two chained transformations, created using [`NoteTransform::then()`].
+#[derive(Debug, Clone)]
+pub struct Then<A, B> {
+    /// The transformation that's applied first.
+    pub first: A,
+    /// The transformation that's applied to the first transformation's output.
+    pub second: B,
+}
+
+/// Transposes notes by a number of semitones. Notes that would end up outside of the MIDI note
+/// range are filtered out.
+#[derive(Debug, Clone)]
+pub struct Transpose {
+    /// The number of semitones to transpose by. Changing this only affects new notes.
+    pub semitones: i32,
+
+    held_notes: HeldNotes,
+}
+
+/// Only passes through events on specific MIDI channels. Events without a channel are always
+/// passed through.
+#[derive(Debug, Clone)]
+pub struct ChannelFilter {
+    /// A bit mask of the enabled channels, where the least significant bit is channel 0.
+    pub channels: u16,
+
+    held_notes: HeldNotes,
+}
+
+/// Reshapes note on velocities using a power curve.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VelocityCurve {
+    /// The curve's shape in `[-1, 1]`. Zero leaves the velocities unchanged, positive values make
+    /// soft notes louder, and negative values make soft notes softer.
+    pub curve: f32,
+    /// Velocities are scaled to `[min_velocity, max_velocity]` after applying the curve.
+    pub min_velocity: f32,
+    /// Velocities are scaled to `[min_velocity, max_velocity]` after applying the curve.
+    pub max_velocity: f32,
+}
+
+/// Only passes through notes in a key range, for instance to build keyboard splits.
+#[derive(Debug, Clone)]
+pub struct KeyRange {
+    /// The lowest note that's passed through.
+    pub lowest_note: u8,
+    /// The highest note that's passed through.
+    pub highest_note: u8,
+
+    held_notes: HeldNotes,
+}
+
+/// What happened to every held note, indexed by `channel * 128 + note`. This is used to route
+/// note off and note expression events for a note the same way as its note on event, even if the
+/// transformation's settings have changed since.
+#[derive(Debug, Clone)]
+struct HeldNotes {
+    /// `None` if the note is not held, `Some(None)` if the note on event was filtered out, and
+    /// `Some(Some(note))` if the note on event was sent for a (possibly different) note.
+    notes: Vec<Option<Option<u8>>>,
+}
+
+impl<S, A: NoteTransform<S>, B: NoteTransform<S>> NoteTransform<S> for Then<A, B> {
+    fn transform(&mut self, event: NoteEvent<S>) -> Option<NoteEvent<S>> {
+        self.first
+            .transform(event)
+            .and_then(|event| self.second.transform(event))
+    }
+
+    fn reset(&mut self) {
+        self.first.reset();
+        self.second.reset();
+    }
+}
+
+impl<S, T: NoteTransform<S>> NoteTransform<S> for &mut T {
+    fn transform(&mut self, event: NoteEvent<S>) -> Option<NoteEvent<S>> {
+        (**self).transform(event)
+    }
+
+    fn reset(&mut self) {
+        (**self).reset()
+    }
+}
+
+impl Default for Transpose {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl Transpose {
+    /// Create a transformation that transposes notes by a number of semitones.
+    pub fn new(semitones: i32) -> Self {
+        Self {
+            semitones,
+            held_notes: HeldNotes::new(),
+        }
+    }
+}
+
+impl<S> NoteTransform<S> for Transpose {
+    fn transform(&mut self, event: NoteEvent<S>) -> Option<NoteEvent<S>> {
+        let semitones = self.semitones;
+        self.held_notes.route(event, |_, note| {
+            let note = note as i32 + semitones;
+            (0..128).contains(&note).then_some(note as u8)
+        })
+    }
+
+    fn reset(&mut self) {
+        self.held_notes.reset();
+    }
+}
+
+impl Default for ChannelFilter {
+    fn default() -> Self {
+        Self::new(u16::MAX)
+    }
+}
+
+impl ChannelFilter {
+    /// Create a filter for a bit mask of channels, where the least significant bit is channel 0.
+    pub fn new(channels: u16) -> Self {
+        Self {
+            channels,
+            held_notes: HeldNotes::new(),
+        }
+    }
+
+    /// Create a filter that only passes through a single channel.
+    pub fn single(channel: u8) -> Self {
+        Self::new(1 << (channel % 16))
+    }
+
+    /// Whether events on this channel are passed through.
+    pub fn is_enabled(&self, channel: u8) -> bool {
+        channel_enabled(self.channels, channel)
+    }
+}
+
+impl<S> NoteTransform<S> for ChannelFilter {
+    fn transform(&mut self, event: NoteEvent<S>) -> Option<NoteEvent<S>> {
+        let channels = self.channels;
+        if note_of(&event).is_some() {
+            self.held_notes.route(event, |channel, note| {
+                channel_enabled(channels, channel).then_some(note)
+            })
+        } else {
+            match event.channel() {
+                Some(channel) if !channel_enabled(channels, channel) => None,
+                _ => Some(event),
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.held_notes.reset();
+    }
+}
+
+impl Default for VelocityCurve {
+    fn default() -> Self {
+        Self::new(0.0)
+    }
+}
+
+impl VelocityCurve {
+    /// Create a velocity curve with the full `[0, 1]` output range.
+    pub fn new(curve: f32) -> Self {
+        Self {
+            curve,
+            min_velocity: 0.0,
+            max_velocity: 1.0,
+        }
+    }
+
+    /// Apply the curve to a `[0, 1]` velocity.
+    pub fn apply(&self, velocity: f32) -> f32 {
+        // A curve of 1 takes the velocity's square root, and a curve of -1 squares the velocity
+        let exponent = 2.0f32.powf(-self.curve.clamp(-1.0, 1.0));
+        let velocity = velocity.clamp(0.0, 1.0).powf(exponent);
+
+        self.min_velocity + (self.max_velocity - self.min_velocity) * velocity
+    }
+}
+
+impl<S> NoteTransform<S> for VelocityCurve {
+    fn transform(&mut self, mut event: NoteEvent<S>) -> Option<NoteEvent<S>> {
+        if let NoteEvent::NoteOn { velocity, .. } = &mut event {
+            *velocity = self.apply(*velocity);
+        }
+
+        Some(event)
+    }
+}
+
+impl Default for KeyRange {
+    fn default() -> Self {
+        Self::new(0, 127)
+    }
+}
+
+impl KeyRange {
+    /// Create a filter that only passes through notes in `lowest_note..=highest_note`.
+    pub fn new(lowest_note: u8, highest_note: u8) -> Self {
+        Self {
+            lowest_note,
+            highest_note,
+            held_notes: HeldNotes::new(),
+        }
+    }
+}
+
+impl<S> NoteTransform<S> for KeyRange {
+    fn transform(&mut self, event: NoteEvent<S>) -> Option<NoteEvent<S>> {
+        let range = self.lowest_note..=self.highest_note;
+        self.held_notes
+            .route(event, |_, note| range.contains(&note).then_some(note))
+    }
+
+    fn reset(&mut self) {
+        self.held_notes.reset();
+    }
+}
+
+impl HeldNotes {
+    fn new() -> Self {
+        Self {
+            notes: vec![None; 16 * 128],
+        }
+    }
+
+    fn reset(&mut self) {
+        self.notes.fill(None);
+    }
+
+    /// Route a note event using `map`, which maps a channel and note to a new note or `None` if the
+    /// note should be filtered out. Note on events use `map`, and the other events for a note use
+    /// whatever `map` returned for the note on event. Events that don't belong to a note are passed
+    /// through.
+    fn route<S>(
+        &mut self,
+        mut event: NoteEvent<S>,
+        map: impl Fn(u8, u8) -> Option<u8>,
+    ) -> Option<NoteEvent<S>> {
+        let (channel, note) = match note_of(&event) {
+            Some((channel, note)) if channel < 16 && note < 128 => (channel, note),
+            _ => return Some(event),
+        };
+        let held_note = &mut self.notes[channel as usize * 128 + note as usize];
+
+        let new_note = match event {
+            NoteEvent::NoteOn { .. } => {
+                let new_note = map(channel, note);
+                *held_note = Some(new_note);
+
+                new_note
+            }
+            NoteEvent::NoteOff { .. } | NoteEvent::Choke { .. } => {
+                held_note.take().unwrap_or_else(|| map(channel, note))
+            }
+            _ => held_note.unwrap_or_else(|| map(channel, note)),
+        }?;
+        set_note(&mut event, new_note);
+
+        Some(event)
+    }
+}
+
+/// Whether a channel is enabled in a bit mask of channels.
+fn channel_enabled(channels: u16, channel: u8) -> bool {
+    channel < 16 && channels & (1 << channel) != 0
+}
+
+/// The channel and note for events that belong to a note.
+fn note_of<S>(event: &NoteEvent<S>) -> Option<(u8, u8)> {
+    match *event {
+        NoteEvent::NoteOn { channel, note, .. }
+        | NoteEvent::NoteOff { channel, note, .. }
+        | NoteEvent::Choke { channel, note, .. }
+        | NoteEvent::VoiceTerminated { channel, note, .. }
+        | NoteEvent::PolyPressure { channel, note, .. }
+        | NoteEvent::PolyVolume { channel, note, .. }
+        | NoteEvent::PolyPan { channel, note, .. }
+        | NoteEvent::PolyTuning { channel, note, .. }
+        | NoteEvent::PolyVibrato { channel, note, .. }
+        | NoteEvent::PolyExpression { channel, note, .. }
+        | NoteEvent::PolyBrightness { channel, note, .. } => Some((channel, note)),
+        _ => None,
+    }
+}
+
+/// Change the note for events that belong to a note.
+fn set_note<S>(event: &mut NoteEvent<S>, new_note: u8) {
+    match event {
+        NoteEvent::NoteOn { note, .. }
+        | NoteEvent::NoteOff { note, .. }
+        | NoteEvent::Choke { note, .. }
+        | NoteEvent::VoiceTerminated { note, .. }
+        | NoteEvent::PolyPressure { note, .. }
+        | NoteEvent::PolyVolume { note, .. }
+        | NoteEvent::PolyPan { note, .. }
+        | NoteEvent::PolyTuning { note, .. }
+        | NoteEvent::PolyVibrato { note, .. }
+        | NoteEvent::PolyExpression { note, .. }
+        | NoteEvent::PolyBrightness { note, .. } => *note = new_note,
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note_on(channel: u8, note: u8) -> NoteEvent<()> {
+        NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: None,
+            channel,
+            note,
+            velocity: 0.25,
+        }
+    }
+
+    fn note_off(channel: u8, note: u8) -> NoteEvent<()> {
+        NoteEvent::NoteOff {
+            timing: 0,
+            voice_id: None,
+            channel,
+            note,
+            velocity: 0.0,
+        }
+    }
+
+    #[test]
+    fn transpose_keeps_held_notes() {
+        let mut transpose = Transpose::new(12);
+        assert_eq!(transpose.transform(note_on(0, 60)), Some(note_on(0, 72)));
+
+        // The note off should still match the transposed note on
+        transpose.semitones = -12;
+        assert_eq!(transpose.transform(note_off(0, 60)), Some(note_off(0, 72)));
+        assert_eq!(transpose.transform(note_on(0, 5)), None);
+        assert_eq!(transpose.transform(note_off(0, 5)), None);
+    }
+
+    #[test]
+    fn chain() {
+        let mut channel_filter = ChannelFilter::single(1);
+        let mut key_range = KeyRange::new(48, 59);
+        let mut note_fx = (&mut channel_filter)
+            .then(&mut key_range)
+            .then(VelocityCurve::new(0.0))
+            .then(Transpose::new(12));
+
+        assert_eq!(note_fx.transform(note_on(0, 50)), None);
+        assert_eq!(note_fx.transform(note_on(1, 60)), None);
+        assert_eq!(note_fx.transform(note_on(1, 50)), Some(note_on(1, 62)));
+        assert_eq!(note_fx.transform(note_off(1, 50)), Some(note_off(1, 62)));
+    }
+
+    #[test]
+    fn velocity_curve() {
+        let mut curve = VelocityCurve::new(1.0);
+        assert_eq!(curve.apply(0.25), 0.5);
+        curve.curve = -1.0;
+        assert_eq!(curve.apply(0.5), 0.25);
+        curve.min_velocity = 0.5;
+        assert_eq!(curve.apply(0.0), 0.5);
+    }
+}
@@ -0,0 +1,88 @@
+//! Opening URLs in the user's web browser.
+
+use std::io;
+use std::process::{Command, Stdio};
+
+/// Open a URL like a plugin's manual or store page in the user's default web browser. This is
+/// meant to be called from an editor, for instance when clicking on a 'visit website' button. The
+/// URL is opened in the background, so this won't block the GUI thread. Only `http://`,
+/// `https://`, and `mailto:` URLs are supported since other schemes can be used to open local
+/// files or start other applications. An error is returned for other URLs, for malformed URLs, or
+/// if the platform's URL handler could not be started.
+///
+/// This uses `xdg-open` on Linux and the BSDs, `open` on macOS, and the `url.dll` protocol handler
+/// on Windows.
+pub fn open_url(url: &str) -> io::Result<()> {
+    check_url(url)?;
+
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        // Unlike `cmd /C start`, this does not need any escaping and doesn't flash a console window
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+
+    let mut child = command
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    // The process needs to be reaped once it exits, but there's no need to block the GUI for that
+    std::thread::spawn(move || child.wait());
+
+    Ok(())
+}
+
+/// Make sure `url` is safe to pass to the platform's URL handler.
+fn check_url(url: &str) -> io::Result<()> {
+    let invalid_url = |reason: &str| {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("'{url}' is not a valid URL: {reason}"),
+        ))
+    };
+
+    // The URL is passed to another program as an argument, so it should not be possible to inject
+    // options this way
+    if url.is_empty() || url.starts_with('-') || url.contains(char::is_control) {
+        return invalid_url("the URL is malformed");
+    }
+
+    match url.split_once(':') {
+        Some((scheme, rest)) if scheme.eq_ignore_ascii_case("mailto") && !rest.is_empty() => Ok(()),
+        Some((scheme, rest))
+            if (scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https"))
+                && rest.len() > 2
+                && rest.starts_with("//") =>
+        {
+            Ok(())
+        }
+        _ => invalid_url("only http, https, and mailto URLs are supported"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allowed_schemes() {
+        assert!(check_url("https://nih-plug.robbertvanderhelm.nl/").is_ok());
+        assert!(check_url("HTTP://example.com").is_ok());
+        assert!(check_url("mailto:someone@example.com").is_ok());
+
+        assert!(check_url("").is_err());
+        assert!(check_url("https://").is_err());
+        assert!(check_url("file:///etc/passwd").is_err());
+        assert!(check_url("javascript:alert(1)").is_err());
+        assert!(check_url("C:\\Windows\\System32\\calc.exe").is_err());
+        assert!(check_url("--help").is_err());
+        assert!(check_url("https://example.com/\nfoo").is_err());
+    }
+}
@@ -0,0 +1,460 @@
+//! Oversampling using cascaded polyphase halfband filters.
+
+use std::f32;
+
+use super::window;
+use crate::buffer::Buffer;
+
+/// The number of taps in each polyphase branch of the first halfband stage. This is the stage
+/// that needs to filter out everything above the original Nyquist frequency, so it needs the
+/// steepest filter.
+const FIRST_STAGE_BRANCH_TAPS: usize = 32;
+/// The number of taps in each polyphase branch of the subsequent halfband stages. Since these
+/// stages operate on signals that have already been band limited by the previous stage, their
+/// transition bands can be much wider.
+const LATER_STAGES_BRANCH_TAPS: usize = 8;
+
+/// The amount of oversampling an [`Oversampler`] performs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OversamplingFactor {
+    TwoTimes,
+    FourTimes,
+    EightTimes,
+    SixteenTimes,
+}
+
+impl OversamplingFactor {
+    /// The ratio between the oversampled sample rate and the original sample rate.
+    pub fn ratio(self) -> usize {
+        1 << self.num_stages()
+    }
+
+    /// The number of 2x halfband stages needed for this factor.
+    fn num_stages(self) -> usize {
+        match self {
+            OversamplingFactor::TwoTimes => 1,
+            OversamplingFactor::FourTimes => 2,
+            OversamplingFactor::EightTimes => 3,
+            OversamplingFactor::SixteenTimes => 4,
+        }
+    }
+}
+
+/// Upsamples audio, runs a callback on the upsampled signal, and then downsamples the result back
+/// to the original sample rate. Each doubling of the sample rate is done using a linear phase
+/// polyphase halfband FIR filter, so the oversampler introduces a fixed amount of latency that can
+/// be queried using [`latency_samples()`][Self::latency_samples()]. A small delay is added at the
+/// oversampled rate so this latency is always a whole number of samples.
+///
+/// All buffers are allocated upfront in [`new()`][Self::new()], so the process functions don't
+/// allocate. Blocks larger than the maximum block size are processed in smaller chunks.
+pub struct Oversampler {
+    factor: OversamplingFactor,
+    max_block_size: usize,
+
+    /// The coefficients for the non-trivial polyphase branch of each halfband stage. The other
+    /// branch is a pure delay.
+    stage_coefficients: Vec<Vec<f32>>,
+    /// The filter state for each stage, indexed by `[channel_idx][stage_idx]`.
+    channel_states: Vec<Vec<HalfbandState>>,
+    /// Delay lines running at the oversampled rate to round the total latency up to a whole
+    /// number of samples at the original rate, indexed by channel.
+    compensation_delays: Vec<DelayLine>,
+    /// The upsampled signal after each stage. Buffer `i` contains `max_block_size * 2^(i + 1)`
+    /// samples. These are shared between channels since channels are processed one at a time.
+    stage_buffers: Vec<Vec<f32>>,
+
+    latency: u32,
+}
+
+/// The state for a single channel of a single halfband stage.
+struct HalfbandState {
+    /// The history for the upsampler's filtering branch.
+    upsampling_history: DelayLine,
+    /// The history for the even samples passed to the downsampler's filtering branch.
+    downsampling_even_history: DelayLine,
+    /// The history for the odd samples passed to the downsampler's delay branch.
+    downsampling_odd_history: DelayLine,
+}
+
+/// A delay line that stores its samples twice so the last `len` samples can always be read as a
+/// single contiguous slice, starting with the most recent sample.
+struct DelayLine {
+    buffer: Vec<f32>,
+    pos: usize,
+}
+
+impl Oversampler {
+    /// Create an oversampler for `num_channels` channels that can process up to `max_block_size`
+    /// samples at a time. This allocates and should be called outside of the process function, for
+    /// instance in [`Plugin::initialize()`][crate::prelude::Plugin::initialize()].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num_channels == 0 || max_block_size == 0`.
+    pub fn new(num_channels: usize, max_block_size: usize, factor: OversamplingFactor) -> Self {
+        assert_ne!(num_channels, 0);
+        assert_ne!(max_block_size, 0);
+
+        let num_stages = factor.num_stages();
+        let stage_coefficients: Vec<Vec<f32>> = (0..num_stages)
+            .map(|stage_idx| {
+                halfband_branch_coefficients(if stage_idx == 0 {
+                    FIRST_STAGE_BRANCH_TAPS
+                } else {
+                    LATER_STAGES_BRANCH_TAPS
+                })
+            })
+            .collect();
+
+        // A halfband filter with `2k` taps in its filtering branch has a group delay of `2k - 1`
+        // samples at the upsampled rate. Upsampling and downsampling together thus add `2k - 1`
+        // samples of latency at the stage's input rate.
+        let ratio = factor.ratio();
+        let oversampled_latency: usize = stage_coefficients
+            .iter()
+            .enumerate()
+            .map(|(stage_idx, coefficients)| (coefficients.len() - 1) * (ratio >> stage_idx))
+            .sum();
+        let compensation_delay = (ratio - (oversampled_latency % ratio)) % ratio;
+        let latency = ((oversampled_latency + compensation_delay) / ratio) as u32;
+
+        Self {
+            factor,
+            max_block_size,
+
+            channel_states: (0..num_channels)
+                .map(|_| {
+                    stage_coefficients
+                        .iter()
+                        .map(|coefficients| HalfbandState {
+                            upsampling_history: DelayLine::new(coefficients.len()),
+                            downsampling_even_history: DelayLine::new(coefficients.len()),
+                            // The odd samples are delayed by `k` samples, and the delay
+                            // line also includes the current sample
+                            downsampling_odd_history: DelayLine::new(coefficients.len() / 2 + 1),
+                        })
+                        .collect()
+                })
+                .collect(),
+            compensation_delays: (0..num_channels)
+                .map(|_| DelayLine::new(compensation_delay + 1))
+                .collect(),
+            stage_buffers: (0..num_stages)
+                .map(|stage_idx| vec![0.0; max_block_size << (stage_idx + 1)])
+                .collect(),
+            stage_coefficients,
+
+            latency,
+        }
+    }
+
+    /// The oversampling factor this oversampler was configured with.
+    pub fn factor(&self) -> OversamplingFactor {
+        self.factor
+    }
+
+    /// The number of channels this oversampler was configured for.
+    pub fn num_channels(&self) -> usize {
+        self.channel_states.len()
+    }
+
+    /// The amount of latency introduced by this oversampler, in samples at the original sample
+    /// rate. Report this to the host using
+    /// [`InitContext::set_latency_samples()`][crate::prelude::InitContext::set_latency_samples()].
+    pub fn latency_samples(&self) -> u32 {
+        self.latency
+    }
+
+    /// Clear the filter states. Call this from [`Plugin::reset()`][crate::prelude::Plugin::reset()].
+    pub fn reset(&mut self) {
+        for state in self.channel_states.iter_mut().flatten() {
+            state.upsampling_history.reset();
+            state.downsampling_even_history.reset();
+            state.downsampling_odd_history.reset();
+        }
+        for delay in &mut self.compensation_delays {
+            delay.reset();
+        }
+    }
+
+    /// Oversample `buffer` and run `process_cb` on every sample of the oversampled signal. The
+    /// arguments to the callback are `process_cb(channel_idx, sample)`, and the callback should
+    /// return the processed sample.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffer` does not have the same number of channels as this oversampler.
+    pub fn process_per_sample<F>(&mut self, buffer: &mut Buffer, mut process_cb: F)
+    where
+        F: FnMut(usize, f32) -> f32,
+    {
+        self.process_block(buffer, |channel_idx, block| {
+            for sample in block {
+                *sample = process_cb(channel_idx, *sample);
+            }
+        });
+    }
+
+    /// Oversample `buffer` and run `process_cb` on blocks of the oversampled signal. The arguments
+    /// to the callback are `process_cb(channel_idx, oversampled_block)`. The block contains the
+    /// oversampled audio for a single channel and can be modified in place. It contains at most
+    /// `max_block_size` times the oversampling ratio samples.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffer` does not have the same number of channels as this oversampler.
+    pub fn process_block<F>(&mut self, buffer: &mut Buffer, mut process_cb: F)
+    where
+        F: FnMut(usize, &mut [f32]),
+    {
+        assert_eq!(buffer.channels(), self.num_channels());
+
+        for (channel_idx, channel) in buffer.as_slice().iter_mut().enumerate() {
+            for block in channel.chunks_mut(self.max_block_size) {
+                self.process_channel_block(channel_idx, block, &mut process_cb);
+            }
+        }
+    }
+
+    fn process_channel_block<F>(
+        &mut self,
+        channel_idx: usize,
+        block: &mut [f32],
+        process_cb: &mut F,
+    ) where
+        F: FnMut(usize, &mut [f32]),
+    {
+        let num_stages = self.stage_coefficients.len();
+        let states = &mut self.channel_states[channel_idx];
+
+        // Upsample the block one stage at a time. Stage `i` reads from stage `i - 1`'s buffer, or
+        // from the block itself for the first stage.
+        for stage_idx in 0..num_stages {
+            let (previous_buffers, current_buffers) = self.stage_buffers.split_at_mut(stage_idx);
+            let input: &[f32] = match previous_buffers.last() {
+                Some(previous_buffer) => &previous_buffer[..block.len() << stage_idx],
+                None => &*block,
+            };
+            let output = &mut current_buffers[0][..block.len() << (stage_idx + 1)];
+
+            states[stage_idx].upsample(&self.stage_coefficients[stage_idx], input, output);
+        }
+
+        let oversampled_block =
+            &mut self.stage_buffers[num_stages - 1][..block.len() << num_stages];
+        let compensation_delay = &mut self.compensation_delays[channel_idx];
+        for sample in oversampled_block.iter_mut() {
+            *sample = compensation_delay.process(*sample);
+        }
+        process_cb(channel_idx, oversampled_block);
+
+        // And then downsample the result again in the reverse order
+        for stage_idx in (0..num_stages).rev() {
+            let (previous_buffers, current_buffers) = self.stage_buffers.split_at_mut(stage_idx);
+            let input = &current_buffers[0][..block.len() << (stage_idx + 1)];
+            let output: &mut [f32] = match previous_buffers.last_mut() {
+                Some(previous_buffer) => &mut previous_buffer[..block.len() << stage_idx],
+                None => &mut *block,
+            };
+
+            states[stage_idx].downsample(&self.stage_coefficients[stage_idx], input, output);
+        }
+    }
+}
+
+impl HalfbandState {
+    /// Upsample `input` by a factor two into `output`, which must be twice as long as `input`.
+    fn upsample(&mut self, coefficients: &[f32], input: &[f32], output: &mut [f32]) {
+        debug_assert_eq!(output.len(), input.len() * 2);
+
+        // The even output samples are computed using the filtering branch, and the odd samples are
+        // delayed copies of the input since the center tap is the only non-zero odd coefficient.
+        // The coefficients are scaled by two to compensate for the zero stuffing.
+        let center_delay = coefficients.len() / 2 - 1;
+        for (input_sample, output_samples) in input.iter().zip(output.chunks_exact_mut(2)) {
+            let history = self.upsampling_history.push(*input_sample);
+
+            output_samples[0] = 2.0 * convolve(coefficients, history);
+            output_samples[1] = history[center_delay];
+        }
+    }
+
+    /// Downsample `input` by a factor two into `output`, which must be half as long as `input`.
+    fn downsample(&mut self, coefficients: &[f32], input: &[f32], output: &mut [f32]) {
+        debug_assert_eq!(input.len(), output.len() * 2);
+
+        // This is the same filter as in `upsample()`, but with the branches swapped around
+        for (input_samples, output_sample) in input.chunks_exact(2).zip(output.iter_mut()) {
+            let even_history = self.downsampling_even_history.push(input_samples[0]);
+            let filtered = convolve(coefficients, even_history);
+            let odd_history = self.downsampling_odd_history.push(input_samples[1]);
+            let delayed = odd_history[odd_history.len() - 1];
+
+            *output_sample = filtered + (0.5 * delayed);
+        }
+    }
+}
+
+impl DelayLine {
+    /// Create a delay line that remembers the last `len` samples, including the current one.
+    fn new(len: usize) -> Self {
+        Self {
+            buffer: vec![0.0; len * 2],
+            pos: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.buffer.len() / 2
+    }
+
+    fn reset(&mut self) {
+        self.buffer.fill(0.0);
+        self.pos = 0;
+    }
+
+    /// Add a sample to the delay line and return the last `len` samples, with the most recent
+    /// sample at index 0.
+    fn push(&mut self, sample: f32) -> &[f32] {
+        let len = self.len();
+        self.pos = (self.pos + len - 1) % len;
+        self.buffer[self.pos] = sample;
+        self.buffer[self.pos + len] = sample;
+
+        &self.buffer[self.pos..self.pos + len]
+    }
+
+    /// Add a sample to the delay line and return the sample from `len - 1` samples ago.
+    fn process(&mut self, sample: f32) -> f32 {
+        let history = self.push(sample);
+        history[history.len() - 1]
+    }
+}
+
+#[inline]
+fn convolve(coefficients: &[f32], history: &[f32]) -> f32 {
+    coefficients
+        .iter()
+        .zip(history)
+        .map(|(coefficient, sample)| coefficient * sample)
+        .sum()
+}
+
+/// Compute the coefficients of the filtering polyphase branch of a windowed sinc halfband lowpass
+/// filter. The full filter contains `2 * num_taps - 1` taps. Only the even taps are returned since
+/// the odd taps are all zero, except for the center tap which is always 0.5.
+fn halfband_branch_coefficients(num_taps: usize) -> Vec<f32> {
+    debug_assert!(num_taps >= 2 && num_taps % 2 == 0);
+
+    let filter_len = (num_taps * 2) - 1;
+    let center = (filter_len - 1) as f32 / 2.0;
+    let window_function = window::blackman_harris(filter_len);
+
+    let mut coefficients: Vec<f32> = (0..num_taps)
+        .map(|tap_idx| {
+            let filter_idx = tap_idx * 2;
+            let x = (filter_idx as f32 - center) * f32::consts::FRAC_PI_2;
+
+            0.5 * (x.sin() / x) * window_function[filter_idx]
+        })
+        .collect();
+
+    // The branch's DC gain should be exactly 0.5 so the full filter has unity gain at DC
+    let sum: f32 = coefficients.iter().sum();
+    for coefficient in &mut coefficients {
+        *coefficient *= 0.5 / sum;
+    }
+
+    coefficients
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FACTORS: [OversamplingFactor; 4] = [
+        OversamplingFactor::TwoTimes,
+        OversamplingFactor::FourTimes,
+        OversamplingFactor::EightTimes,
+        OversamplingFactor::SixteenTimes,
+    ];
+
+    /// Run `samples` through an oversampler with a small maximum block size so the blocks are split
+    /// up, using `process_cb` as the callback.
+    fn oversample(
+        factor: OversamplingFactor,
+        samples: &mut [f32],
+        process_cb: impl FnMut(usize, f32) -> f32,
+    ) -> u32 {
+        let mut oversampler = Oversampler::new(1, 64, factor);
+        let mut buffer = Buffer::default();
+        unsafe { buffer.set_slices(samples.len(), |slices| *slices = vec![samples]) };
+        oversampler.process_per_sample(&mut buffer, process_cb);
+
+        oversampler.latency_samples()
+    }
+
+    #[test]
+    fn reported_latency() {
+        for factor in FACTORS {
+            let mut samples = [0.0f32; 256];
+            samples[0] = 1.0;
+            let latency = oversample(factor, &mut samples, |_, sample| sample);
+
+            let (peak_idx, _) = samples
+                .iter()
+                .enumerate()
+                .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))
+                .unwrap();
+            assert_eq!(peak_idx, latency as usize, "{factor:?}");
+        }
+    }
+
+    #[test]
+    fn passband_round_trip() {
+        for factor in FACTORS {
+            let mut dc = [1.0f32; 512];
+            oversample(factor, &mut dc, |_, sample| sample);
+            for sample in &dc[256..] {
+                assert!((sample - 1.0).abs() < 1e-3, "{factor:?}: {sample}");
+            }
+
+            // A tone well below the original Nyquist frequency should come out unchanged, apart
+            // from the latency
+            let input: Vec<f32> = (0..512)
+                .map(|i| (i as f32 * 0.05 * f32::consts::TAU).sin())
+                .collect();
+            let mut output = input.clone();
+            let latency = oversample(factor, &mut output, |_, sample| sample) as usize;
+            for (input_sample, output_sample) in input.iter().zip(&output[latency..]).skip(256) {
+                assert!(
+                    (input_sample - output_sample).abs() < 1e-2,
+                    "{factor:?}: {input_sample} != {output_sample}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_above_nyquist() {
+        for factor in FACTORS {
+            // The callback replaces the signal with a tone at 0.75 times the original sample rate,
+            // which should be filtered out when downsampling
+            let frequency = 0.75 / factor.ratio() as f32;
+            let mut oversampled_idx = 0;
+            let mut samples = [0.0f32; 512];
+            oversample(factor, &mut samples, |_, _| {
+                let sample = (oversampled_idx as f32 * frequency * f32::consts::TAU).sin();
+                oversampled_idx += 1;
+
+                sample
+            });
+
+            let peak = samples[256..]
+                .iter()
+                .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+            assert!(peak < 1e-3, "{factor:?}: {peak}");
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Conversions for MIDI pitch bend.
+
+/// The 14-bit pitch bend value that corresponds to no pitch bend.
+pub const PITCH_BEND_CENTER: u16 = 0x2000;
+/// The highest possible 14-bit pitch bend value.
+pub const PITCH_BEND_MAX: u16 = 0x3fff;
+
+/// How far a full pitch bend in either direction bends the pitch, in semitones. Most synths use
+/// two semitones for regular MIDI pitch bend. MPE uses a default of 48 semitones for the per-note
+/// member channels so a controller can slide freely between notes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PitchBendRange {
+    /// The number of semitones the pitch is raised when the pitch bend is at its maximum value.
+    pub up: f32,
+    /// The number of semitones the pitch is lowered when the pitch bend is at its minimum value.
+    /// This should be a positive number.
+    pub down: f32,
+}
+
+impl Default for PitchBendRange {
+    fn default() -> Self {
+        Self::MIDI
+    }
+}
+
+impl PitchBendRange {
+    /// The conventional range for regular MIDI pitch bend, two semitones in either direction.
+    pub const MIDI: PitchBendRange = PitchBendRange::symmetric(2.0);
+    /// The default range for MPE member channels, 48 semitones in either direction.
+    pub const MPE: PitchBendRange = PitchBendRange::symmetric(48.0);
+
+    /// A range that bends the pitch up and down by the same number of semitones.
+    pub const fn symmetric(semitones: f32) -> Self {
+        Self {
+            up: semitones,
+            down: semitones,
+        }
+    }
+
+    /// Convert a normalized `[0, 1]` pitch bend value as found in
+    /// [`NoteEvent::MidiPitchBend`][crate::prelude::NoteEvent::MidiPitchBend] to an offset in
+    /// semitones. The value is rounded to the nearest 14-bit value first, so `0x2000` results in
+    /// exactly zero semitones even though it's not exactly 0.5 when normalized.
+    pub fn semitones(&self, normalized_value: f32) -> f32 {
+        self.semitones_14bit(pitch_bend_to_14bit(normalized_value))
+    }
+
+    /// The same as [`semitones()`][Self::semitones()], but for a raw 14-bit pitch bend value.
+    pub fn semitones_14bit(&self, value: u16) -> f32 {
+        let offset = value.min(PITCH_BEND_MAX) as f32 - PITCH_BEND_CENTER as f32;
+        if offset >= 0.0 {
+            offset / (PITCH_BEND_MAX - PITCH_BEND_CENTER) as f32 * self.up
+        } else {
+            offset / PITCH_BEND_CENTER as f32 * self.down
+        }
+    }
+
+    /// The inverse of [`semitones()`][Self::semitones()]. Converts an offset in semitones to a
+    /// normalized `[0, 1]` pitch bend value, clamping offsets that fall outside of the range.
+    pub fn normalized(&self, semitones: f32) -> f32 {
+        let offset = if semitones > 0.0 {
+            semitones / self.up * (PITCH_BEND_MAX - PITCH_BEND_CENTER) as f32
+        } else if semitones < 0.0 {
+            semitones / self.down * PITCH_BEND_CENTER as f32
+        } else {
+            0.0
+        };
+        let value = (PITCH_BEND_CENTER as f32 + offset).round();
+
+        pitch_bend_from_14bit(value.clamp(0.0, PITCH_BEND_MAX as f32) as u16)
+    }
+
+    /// Convert a normalized `[0, 1]` pitch bend value to a frequency multiplier. Multiply a note's
+    /// frequency with this value to apply the pitch bend.
+    pub fn freq_multiplier(&self, normalized_value: f32) -> f32 {
+        semitones_to_freq_multiplier(self.semitones(normalized_value))
+    }
+}
+
+/// Convert a normalized `[0, 1]` pitch bend value to a raw 14-bit pitch bend value.
+#[inline]
+pub fn pitch_bend_to_14bit(normalized_value: f32) -> u16 {
+    (normalized_value.clamp(0.0, 1.0) * PITCH_BEND_MAX as f32).round() as u16
+}
+
+/// Convert a raw 14-bit pitch bend value to a normalized `[0, 1]` value.
+#[inline]
+pub fn pitch_bend_from_14bit(value: u16) -> f32 {
+    value.min(PITCH_BEND_MAX) as f32 / PITCH_BEND_MAX as f32
+}
+
+/// Convert an offset in semitones to a frequency multiplier.
+#[inline]
+pub fn semitones_to_freq_multiplier(semitones: f32) -> f32 {
+    2.0f32.powf(semitones / 12.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn center_is_exact() {
+        let range = PitchBendRange::MPE;
+        assert_eq!(range.semitones_14bit(PITCH_BEND_CENTER), 0.0);
+        assert_eq!(
+            range.semitones(pitch_bend_from_14bit(PITCH_BEND_CENTER)),
+            0.0
+        );
+        assert_eq!(
+            range.freq_multiplier(pitch_bend_from_14bit(PITCH_BEND_CENTER)),
+            1.0
+        );
+    }
+
+    #[test]
+    fn asymmetric_range() {
+        let range = PitchBendRange {
+            up: 2.0,
+            down: 12.0,
+        };
+        assert_eq!(range.semitones(0.0), -12.0);
+        assert_eq!(range.semitones(1.0), 2.0);
+        assert!((range.freq_multiplier(0.0) - 0.5).abs() < 1e-6);
+    }
+}
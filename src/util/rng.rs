@@ -0,0 +1,76 @@
+//! A small deterministic random number generator.
+
+/// A xorshift random number generator. This is fast, doesn't allocate, and the same seed always
+/// produces the same sequence, which makes it a good fit for noise generators, random modulation,
+/// and reproducible tests. It should not be used for anything that needs to be unpredictable.
+#[derive(Debug, Clone)]
+pub struct Xorshift(u64);
+
+impl Xorshift {
+    /// Create a random number generator. Different seeds produce uncorrelated sequences.
+    pub fn new(seed: u64) -> Self {
+        // The xorshift generator would get stuck at zero
+        Self(if seed == 0 {
+            0x2545_f491_4f6c_dd1d
+        } else {
+            seed
+        })
+    }
+
+    /// A random number in the full `u64` range.
+    #[inline]
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A random number in `[0, n)`, or 0 if `n` is 0.
+    #[inline]
+    pub fn below(&mut self, n: usize) -> usize {
+        if n == 0 {
+            0
+        } else {
+            (self.next_u64() % n as u64) as usize
+        }
+    }
+
+    /// A random number in `[0, 1)`.
+    #[inline]
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// A random number in `[-1, 1)`.
+    #[inline]
+    pub fn next_bipolar_f32(&mut self) -> f32 {
+        self.next_f32() * 2.0 - 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deterministic_and_bounded() {
+        let mut rng = Xorshift::new(0x5eed);
+        let mut same_rng = Xorshift::new(0x5eed);
+        for _ in 0..4096 {
+            assert_eq!(rng.next_u64(), same_rng.next_u64());
+        }
+
+        for _ in 0..4096 {
+            let value = rng.next_f32();
+            assert!((0.0..1.0).contains(&value));
+            let value = rng.next_bipolar_f32();
+            assert!((-1.0..1.0).contains(&value));
+            assert!(rng.below(7) < 7);
+        }
+
+        // A zero seed would otherwise only ever produce zeroes
+        assert_ne!(Xorshift::new(0).next_u64(), 0);
+        assert_eq!(Xorshift::new(1).below(0), 0);
+    }
+}
@@ -0,0 +1,211 @@
+//! Sharing data between plugin instances loaded in the same process.
+
+use parking_lot::{Mutex, RwLock};
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+
+use super::triple_buffer::{TripleBuffer, TripleBufferInput, TripleBufferOutput};
+
+lazy_static::lazy_static! {
+    /// All channels that are currently open in this process, keyed by the plugin ID and the
+    /// channel's name. The channels are removed from this map once the last handle to them is
+    /// dropped.
+    static ref CHANNELS: Mutex<HashMap<(String, String), Weak<dyn Any + Send + Sync>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// A named channel shared by all instances of a plugin that are loaded in the same process. Every
+/// instance can publish its own value to the channel using a [`SharedPublisher`], and any instance
+/// can read the latest values from all publishers. This can be used to build metering suites where
+/// a master bus analyzer collects the levels from sender plugins on every channel strip.
+///
+/// Publishing and reading are lock-free and don't allocate, so both can be done from the audio
+/// thread. Opening a channel and creating or dropping publishers do allocate, so do that in the
+/// plugin's `Default` implementation or in
+/// [`Plugin::initialize()`][crate::prelude::Plugin::initialize()].
+///
+/// ```
+/// # use nih_plug::util::SharedChannel;
+/// // In every sender instance
+/// let channel = SharedChannel::<f32>::open("com.example.meter", "levels").unwrap();
+/// let mut publisher = channel.publisher(0.0);
+///
+/// // In the sender's process function
+/// publisher.write(-6.0);
+///
+/// // In the collector instance's process function or editor
+/// let collector = SharedChannel::<f32>::open("com.example.meter", "levels").unwrap();
+/// collector.for_each(|_, level| assert_eq!(*level, -6.0));
+/// ```
+///
+/// Keep in mind that hosts with process isolation may load instances in different processes, in
+/// which case they won't see each other.
+pub struct SharedChannel<T: Send + 'static> {
+    state: Arc<ChannelState<T>>,
+}
+
+/// Publishes one instance's value to a [`SharedChannel`]. The value is removed from the channel
+/// when this is dropped.
+pub struct SharedPublisher<T: Send + 'static> {
+    state: Arc<ChannelState<T>>,
+    /// This publisher's unique ID within the channel.
+    id: u64,
+    input: TripleBufferInput<T>,
+}
+
+/// The publishers connected to a channel.
+struct ChannelState<T: Send> {
+    /// The reading halves of the publishers' triple buffers. This is only locked for writing when
+    /// a publisher is added or removed.
+    slots: RwLock<Vec<(u64, Mutex<TripleBufferOutput<T>>)>>,
+    /// The ID for the next publisher.
+    next_id: AtomicU64,
+}
+
+impl<T: Send + 'static> Clone for SharedChannel<T> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<T: Send + 'static> SharedChannel<T> {
+    /// Open the channel called `name` for the plugin with ID `plugin_id`, or create it if no other
+    /// instance has opened it yet. Using the plugin's CLAP or VST3 ID here prevents other plugins
+    /// from accidentally connecting to the channel. Returns `None` if the channel has already been
+    /// opened with a different value type. This allocates and locks a process-wide mutex.
+    pub fn open(plugin_id: &str, name: &str) -> Option<Self> {
+        let state = open_shared_state(plugin_id, name, || ChannelState {
+            slots: RwLock::new(Vec::new()),
+            next_id: AtomicU64::new(0),
+        })?;
+
+        Some(Self { state })
+    }
+
+    /// Add a publisher to this channel. Its value is initialized to `initial_value`. This
+    /// allocates.
+    pub fn publisher(&self, initial_value: T) -> SharedPublisher<T>
+    where
+        T: Clone,
+    {
+        let (input, output) = TripleBuffer::new(initial_value).split();
+        let id = self.state.next_id.fetch_add(1, Ordering::Relaxed);
+        self.state.slots.write().push((id, Mutex::new(output)));
+
+        SharedPublisher {
+            state: self.state.clone(),
+            id,
+            input,
+        }
+    }
+
+    /// The number of publishers currently connected to this channel.
+    pub fn num_publishers(&self) -> usize {
+        self.state.slots.read().len()
+    }
+
+    /// Call `f` with every publisher's ID and its most recently published value. This does not
+    /// allocate or block. Publishers that are being added or removed at the same time or that are
+    /// being read by another instance at the same time are skipped for that one call.
+    pub fn for_each(&self, mut f: impl FnMut(u64, &T)) {
+        let Some(slots) = self.state.slots.try_read() else {
+            return;
+        };
+
+        for (id, output) in slots.iter() {
+            if let Some(mut output) = output.try_lock() {
+                f(*id, output.read());
+            }
+        }
+    }
+}
+
+impl<T: Send + 'static> SharedPublisher<T> {
+    /// This publisher's ID. This is the ID passed to [`SharedChannel::for_each()`]'s callback, so
+    /// an instance can recognize and skip its own value.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Get a mutable reference to the value that will be published next. See
+    /// [`TripleBufferInput::input_buffer()`].
+    pub fn input_buffer(&mut self) -> &mut T {
+        self.input.input_buffer()
+    }
+
+    /// Make the input buffer visible to the other instances.
+    pub fn publish(&mut self) {
+        self.input.publish();
+    }
+
+    /// Overwrite the input buffer with `value` and publish it.
+    pub fn write(&mut self, value: T) {
+        self.input.write(value);
+    }
+}
+
+impl<T: Send + 'static> Drop for SharedPublisher<T> {
+    fn drop(&mut self) {
+        self.state.slots.write().retain(|(id, _)| *id != self.id);
+    }
+}
+
+/// Get the state registered under `plugin_id` and `name`, or register the state returned by
+/// `create` if there is none. Returns `None` if the existing state has a different type. This
+/// allocates and locks a process-wide mutex.
+pub(super) fn open_shared_state<S: Any + Send + Sync>(
+    plugin_id: &str,
+    name: &str,
+    create: impl FnOnce() -> S,
+) -> Option<Arc<S>> {
+    let mut channels = CHANNELS.lock();
+    channels.retain(|_, channel| channel.strong_count() > 0);
+
+    let key = (String::from(plugin_id), String::from(name));
+    if let Some(state) = channels.get(&key).and_then(Weak::upgrade) {
+        return match state.downcast::<S>() {
+            Ok(state) => Some(state),
+            Err(_) => {
+                nih_debug_assert_failure!(
+                    "Shared channel '{}' for '{}' was already opened with a different type",
+                    name,
+                    plugin_id
+                );
+                None
+            }
+        };
+    }
+
+    let state = Arc::new(create());
+    let erased_state: Arc<dyn Any + Send + Sync> = state.clone();
+    channels.insert(key, Arc::downgrade(&erased_state));
+
+    Some(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn publish_between_instances() {
+        let sender_channel = SharedChannel::<f32>::open("nih_plug.test", "levels").unwrap();
+        let mut sender_1 = sender_channel.publisher(0.0);
+        let mut sender_2 = sender_channel.publisher(0.0);
+        sender_1.write(-6.0);
+        sender_2.write(-12.0);
+
+        let collector = SharedChannel::<f32>::open("nih_plug.test", "levels").unwrap();
+        let mut levels = Vec::new();
+        collector.for_each(|id, level| levels.push((id, *level)));
+        assert_eq!(levels, [(sender_1.id(), -6.0), (sender_2.id(), -12.0)]);
+
+        drop(sender_1);
+        assert_eq!(collector.num_publishers(), 1);
+        assert!(SharedChannel::<u32>::open("nih_plug.test", "other").is_some());
+    }
+}
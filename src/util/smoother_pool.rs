@@ -0,0 +1,294 @@
+//! Parameter smoothing for many voices at once.
+
+use crate::params::smoothing::SmoothingStyle;
+
+/// Smoothers for `M` parameters in each of `N` voices, for polyphonic synthesizers. Unlike
+/// [`Smoother`][crate::prelude::Smoother], which uses atomics so it can be shared with the editor,
+/// the smoothing state here is stored in plain contiguous arrays owned by the audio thread. Voices
+/// are reset and rendered as a whole, so per-voice parameters like filter cutoffs or oscillator
+/// detune amounts don't need thousands of individual per-sample smoother calls.
+///
+/// Voices are addressed by index, which matches [`Voice::index()`][super::Voice::index()] when
+/// the pool has the same number of voices as the [`VoiceAllocator`][super::VoiceAllocator]. Reset
+/// a voice's smoothers with [`reset_voice()`][Self::reset_voice()] after starting a note, and fill
+/// a block of values for each parameter with [`next_voice_block()`][Self::next_voice_block()]
+/// while rendering it.
+///
+/// All storage is allocated in [`new()`][Self::new()], so none of the other functions allocate.
+#[derive(Debug, Clone)]
+pub struct SmootherPool {
+    num_voices: usize,
+    /// The smoothing style for each parameter.
+    styles: Vec<SmoothingStyle>,
+
+    // These are all indexed by `voice_idx * num_params + param_idx`, so a single voice's smoothers
+    // are stored next to each other
+    /// The value for the current sample.
+    current: Vec<f32>,
+    /// The value each smoother is moving towards.
+    target: Vec<f32>,
+    /// The step size or coefficient computed with [`SmoothingStyle::step_size()`].
+    step_size: Vec<f32>,
+    /// The number of steps of smoothing left to take.
+    steps_left: Vec<u32>,
+}
+
+impl SmootherPool {
+    /// Create smoothers for `num_voices` voices, with one smoother per voice for every smoothing
+    /// style in `styles`. All smoothers start out at zero.
+    pub fn new(num_voices: usize, styles: Vec<SmoothingStyle>) -> Self {
+        let num_smoothers = num_voices * styles.len();
+
+        Self {
+            num_voices,
+            styles,
+
+            current: vec![0.0; num_smoothers],
+            target: vec![0.0; num_smoothers],
+            step_size: vec![0.0; num_smoothers],
+            steps_left: vec![0; num_smoothers],
+        }
+    }
+
+    /// The number of voices in the pool.
+    pub fn num_voices(&self) -> usize {
+        self.num_voices
+    }
+
+    /// The number of smoothed parameters per voice.
+    pub fn num_params(&self) -> usize {
+        self.styles.len()
+    }
+
+    /// Immediately set all of a voice's smoothers to `values`, which contains a value for every
+    /// parameter. Call this after starting a new note on the voice.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `values` doesn't contain a value for every parameter or if the voice index is out
+    /// of bounds.
+    pub fn reset_voice(&mut self, voice_idx: usize, values: &[f32]) {
+        assert_eq!(values.len(), self.num_params());
+
+        let range = self.voice_range(voice_idx);
+        self.current[range.clone()].copy_from_slice(values);
+        self.target[range.clone()].copy_from_slice(values);
+        self.steps_left[range].fill(0);
+    }
+
+    /// Immediately set the smoothers for every voice to `values`, which contains a value for every
+    /// parameter. Call this from [`Plugin::reset()`][crate::prelude::Plugin::reset()].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `values` doesn't contain a value for every parameter.
+    pub fn reset_all(&mut self, values: &[f32]) {
+        for voice_idx in 0..self.num_voices {
+            self.reset_voice(voice_idx, values);
+        }
+    }
+
+    /// Start smoothing a voice's parameter towards a new target value.
+    pub fn set_target(
+        &mut self,
+        sample_rate: f32,
+        voice_idx: usize,
+        param_idx: usize,
+        target: f32,
+    ) {
+        let idx = self.smoother_idx(voice_idx, param_idx);
+        let style = &self.styles[param_idx];
+
+        let steps_left = style.num_steps(sample_rate);
+        self.target[idx] = target;
+        self.steps_left[idx] = steps_left;
+        if steps_left > 0 {
+            self.step_size[idx] = style.step_size(self.current[idx], target, steps_left);
+        } else {
+            self.current[idx] = target;
+            self.step_size[idx] = 0.0;
+        }
+    }
+
+    /// Start smoothing a parameter towards a new target value for every voice. Useful when a
+    /// parameter that's shared by all voices changes.
+    pub fn set_target_all_voices(&mut self, sample_rate: f32, param_idx: usize, target: f32) {
+        for voice_idx in 0..self.num_voices {
+            self.set_target(sample_rate, voice_idx, param_idx, target);
+        }
+    }
+
+    /// The most recent value produced by a voice's smoother for a parameter.
+    pub fn value(&self, voice_idx: usize, param_idx: usize) -> f32 {
+        self.current[self.smoother_idx(voice_idx, param_idx)]
+    }
+
+    /// Whether a voice's smoother for a parameter is still moving towards its target value.
+    pub fn is_smoothing(&self, voice_idx: usize, param_idx: usize) -> bool {
+        self.steps_left[self.smoother_idx(voice_idx, param_idx)] > 0
+    }
+
+    /// Whether any of a voice's smoothers are still moving towards their target values. If this
+    /// returns `false`, then the values from the last block can be reused.
+    pub fn is_voice_smoothing(&self, voice_idx: usize) -> bool {
+        self.steps_left[self.voice_range(voice_idx)]
+            .iter()
+            .any(|&steps_left| steps_left > 0)
+    }
+
+    /// Fill `block_values` with the next smoothed values for a voice's parameter.
+    pub fn next_block(&mut self, voice_idx: usize, param_idx: usize, block_values: &mut [f32]) {
+        let idx = self.smoother_idx(voice_idx, param_idx);
+        fill_block(
+            &self.styles[param_idx],
+            &mut self.current[idx],
+            self.target[idx],
+            self.step_size[idx],
+            &mut self.steps_left[idx],
+            block_values,
+        );
+    }
+
+    /// Fill `block_values[param_idx][..block_len]` with the next smoothed values for each of a
+    /// voice's parameters. `block_values` needs to contain a buffer for every parameter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `block_values` doesn't contain a buffer for every parameter, or if one of the
+    /// buffers is shorter than `block_len`.
+    pub fn next_voice_block<B: AsMut<[f32]>>(
+        &mut self,
+        voice_idx: usize,
+        block_values: &mut [B],
+        block_len: usize,
+    ) {
+        assert_eq!(block_values.len(), self.num_params());
+
+        let range = self.voice_range(voice_idx);
+        for (param_idx, (block_values, idx)) in block_values.iter_mut().zip(range).enumerate() {
+            fill_block(
+                &self.styles[param_idx],
+                &mut self.current[idx],
+                self.target[idx],
+                self.step_size[idx],
+                &mut self.steps_left[idx],
+                &mut block_values.as_mut()[..block_len],
+            );
+        }
+    }
+
+    /// Advance all of a voice's smoothers by `num_samples` samples without producing any values.
+    /// Useful for voices that are silent but still need to stay in sync.
+    pub fn skip_voice(&mut self, voice_idx: usize, num_samples: usize) {
+        if num_samples == 0 {
+            return;
+        }
+
+        let range = self.voice_range(voice_idx);
+        for (param_idx, idx) in range.enumerate() {
+            let steps_left = &mut self.steps_left[idx];
+            if *steps_left == 0 {
+                continue;
+            }
+
+            // This snaps to the target value in the same way as `fill_block()`
+            if *steps_left as usize <= num_samples {
+                self.current[idx] = self.target[idx];
+                *steps_left = 0;
+            } else {
+                self.current[idx] = self.styles[param_idx].next_step(
+                    self.current[idx],
+                    self.target[idx],
+                    self.step_size[idx],
+                    num_samples as u32,
+                );
+                *steps_left -= num_samples as u32;
+            }
+        }
+    }
+
+    fn smoother_idx(&self, voice_idx: usize, param_idx: usize) -> usize {
+        nih_debug_assert!(voice_idx < self.num_voices);
+        nih_debug_assert!(param_idx < self.num_params());
+
+        voice_idx * self.num_params() + param_idx
+    }
+
+    fn voice_range(&self, voice_idx: usize) -> std::ops::Range<usize> {
+        let start = voice_idx * self.num_params();
+        start..start + self.num_params()
+    }
+}
+
+/// Fill `block_values` with the next smoothed values for a single smoother. This works the same way
+/// as [`Smoother::next_block_exact()`][crate::prelude::Smoother::next_block_exact()].
+#[inline]
+fn fill_block(
+    style: &SmoothingStyle,
+    current: &mut f32,
+    target: f32,
+    step_size: f32,
+    steps_left: &mut u32,
+    block_values: &mut [f32],
+) {
+    let num_smoothed_values = block_values.len().min(*steps_left as usize);
+    if num_smoothed_values > 0 {
+        // The last step snaps to the target value to avoid quantization errors
+        let last_step_in_block = num_smoothed_values == *steps_left as usize;
+        let num_steps_to_compute = if last_step_in_block {
+            num_smoothed_values - 1
+        } else {
+            num_smoothed_values
+        };
+        for value in &mut block_values[..num_steps_to_compute] {
+            *current = style.next(*current, target, step_size);
+            *value = *current;
+        }
+        if last_step_in_block {
+            *current = target;
+            block_values[num_smoothed_values - 1] = target;
+        }
+
+        *steps_left -= num_smoothed_values as u32;
+    }
+
+    block_values[num_smoothed_values..].fill(target);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::smoothing::Smoother;
+
+    #[test]
+    fn matches_smoother() {
+        const SAMPLE_RATE: f32 = 100.0;
+        let style = SmoothingStyle::Exponential(100.0);
+        let smoother: Smoother<f32> = Smoother::new(style.clone());
+        smoother.reset(1.0);
+        smoother.set_target(SAMPLE_RATE, 5.0);
+
+        let mut pool = SmootherPool::new(2, vec![SmoothingStyle::None, style]);
+        pool.reset_all(&[0.0, 1.0]);
+        pool.set_target(SAMPLE_RATE, 1, 1, 5.0);
+        assert!(!pool.is_voice_smoothing(0));
+        assert!(pool.is_voice_smoothing(1));
+
+        let mut expected = [0.0; 6];
+        let mut block_values = [[0.0; 6]; 2];
+        for _ in 0..2 {
+            smoother.next_block_exact(&mut expected);
+            pool.next_voice_block(1, &mut block_values, 6);
+            assert_eq!(block_values[1], expected);
+        }
+        assert_eq!(block_values[0], [0.0; 6]);
+        assert!(!pool.is_smoothing(1, 1));
+
+        pool.reset_voice(1, &[0.0, 1.0]);
+        pool.set_target(SAMPLE_RATE, 1, 1, 5.0);
+        pool.skip_voice(1, 9);
+        assert!(pool.is_smoothing(1, 1));
+        pool.skip_voice(1, 1);
+        assert_eq!(pool.value(1, 1), 5.0);
+    }
+}
@@ -0,0 +1,214 @@
+//! A higher level wrapper around the [`StftHelper`] for plugins that process audio in the
+//! frequency domain.
+
+use super::fft::{FftBackend, RealFft};
+use super::stft::{StftInput, StftInputMut};
+use super::window;
+use super::StftHelper;
+
+/// Re-export for the spectral callbacks.
+pub use realfft::num_complex::Complex32;
+
+/// Handles the entire short-time Fourier transform for spectral plugins. Audio is buffered in
+/// overlapping windows using a [`StftHelper`], the analysis window gets applied, the windowed
+/// signal is transformed into the frequency domain, your callback modifies the spectrum, and the
+/// result is transformed back, windowed again, and overlap-added to the output. The output is
+/// delayed by [`latency_samples()`][Self::latency_samples()] samples.
+///
+/// A Hann window is used for both analysis and synthesis by default. The output is normalized for
+/// both the FFT and the windows' overlap, so a callback that doesn't modify the spectrum results
+/// in the input signal delayed by the latency. For the squared windows to add up to a constant
+/// value the overlap amount should be at least 4 when using a Hann window.
+///
+/// All buffers are allocated upfront in [`new()`][Self::new()], so the process functions don't
+/// allocate.
+pub struct SpectralStftHelper<const NUM_SIDECHAIN_INPUTS: usize = 0> {
+    stft: StftHelper<NUM_SIDECHAIN_INPUTS>,
+    overlap_times: usize,
+
+    transform: SpectralTransform,
+}
+
+/// The windowing and FFT state shared by [`SpectralStftHelper::process()`] and
+/// [`SpectralStftHelper::process_sidechain()`]. This is stored separately from the [`StftHelper`]
+/// so it can be borrowed from the STFT callbacks.
+struct SpectralTransform {
+    /// The window applied before the forward FFT and after the inverse FFT.
+    window_function: Vec<f32>,
+    /// The gain applied after the inverse FFT to compensate for the FFT's scaling and the overlap
+    /// of the squared windows.
+    gain_compensation: f32,
+
+    fft: RealFft,
+    /// The spectrum for the current window. This contains `window_size / 2 + 1` bins.
+    complex_fft_buffer: Vec<Complex32>,
+}
+
+impl<const NUM_SIDECHAIN_INPUTS: usize> SpectralStftHelper<NUM_SIDECHAIN_INPUTS> {
+    /// Create a spectral STFT helper for `num_channels` channels that processes windows of
+    /// `window_size` samples, with a new window every `window_size / overlap_times` samples. This
+    /// allocates and should be called outside of the process function, for instance in
+    /// [`Plugin::initialize()`][crate::prelude::Plugin::initialize()].
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the arguments are zero, or if `overlap_times > window_size`.
+    pub fn new(num_channels: usize, window_size: usize, overlap_times: usize) -> Self {
+        assert_ne!(overlap_times, 0);
+        assert!(overlap_times <= window_size);
+
+        let fft = RealFft::new(window_size);
+        let complex_fft_buffer = fft.make_spectrum_buffer();
+
+        let mut helper = Self {
+            stft: StftHelper::new(num_channels, window_size, 0),
+            overlap_times,
+
+            transform: SpectralTransform {
+                window_function: window::hann(window_size),
+                gain_compensation: 1.0,
+
+                fft,
+                complex_fft_buffer,
+            },
+        };
+        helper.update_gain_compensation();
+
+        helper
+    }
+
+    /// Replace the Hann window used for analysis and synthesis with another window function.
+    /// The gain compensation is recomputed for the new window. The window should be suitable for
+    /// the configured overlap amount, meaning that the sum of the overlapping squared windows
+    /// should be constant. Check the [`window`] module for the available window functions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window_function` does not contain exactly `window_size` samples.
+    pub fn set_window_function(&mut self, window_function: &[f32]) {
+        assert_eq!(window_function.len(), self.window_size());
+
+        self.transform
+            .window_function
+            .copy_from_slice(window_function);
+        self.update_gain_compensation();
+    }
+
+    /// The number of samples in each window.
+    pub fn window_size(&self) -> usize {
+        self.transform.window_function.len()
+    }
+
+    /// The number of frequency bins passed to the process callbacks. This is `window_size / 2 +
+    /// 1`.
+    pub fn num_bins(&self) -> usize {
+        self.transform.complex_fft_buffer.len()
+    }
+
+    /// The amount of latency introduced by this helper. Report this to the host using
+    /// [`InitContext::set_latency_samples()`][crate::prelude::InitContext::set_latency_samples()].
+    pub fn latency_samples(&self) -> u32 {
+        self.stft.latency_samples()
+    }
+
+    /// Clear the internal buffers. Call this from [`Plugin::reset()`][crate::prelude::Plugin::reset()].
+    pub fn reset(&mut self) {
+        self.stft.set_block_size(self.window_size());
+    }
+
+    /// Process the audio in `main_buffer` in the frequency domain. `process_cb` is called for
+    /// every channel of every window with the arguments `process_cb(channel_idx, spectrum)`, where
+    /// `spectrum` contains [`num_bins()`][Self::num_bins()] frequency bins. The spectrum can be
+    /// modified in place and the result will be written back to the buffer after a delay of
+    /// [`latency_samples()`][Self::latency_samples()] samples.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `main_buffer` does not have the same number of channels as this helper.
+    pub fn process<M, F>(&mut self, main_buffer: &mut M, mut process_cb: F)
+    where
+        M: StftInputMut,
+        F: FnMut(usize, &mut [Complex32]),
+    {
+        let transform = &mut self.transform;
+        self.stft.process_overlap_add(
+            main_buffer,
+            self.overlap_times,
+            |channel_idx, real_buffer| {
+                process_cb(channel_idx, transform.analyze(real_buffer));
+                transform.synthesize(real_buffer);
+            },
+        );
+    }
+
+    /// The same as [`process()`][Self::process()], but with sidechain inputs that are analyzed
+    /// before the main input is processed. The callback's second argument is
+    /// `sidechain_buffer_idx`, which is `None` for the main input. Only the main input's spectrum
+    /// is transformed back and written to the output.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `main_buffer` or the sidechain buffers do not have the same number of channels as
+    /// this helper, or if the sidechain buffers do not contain the same number of samples as the
+    /// main buffer.
+    pub fn process_sidechain<M, S, F>(
+        &mut self,
+        main_buffer: &mut M,
+        sidechain_buffers: [&S; NUM_SIDECHAIN_INPUTS],
+        mut process_cb: F,
+    ) where
+        M: StftInputMut,
+        S: StftInput,
+        F: FnMut(usize, Option<usize>, &mut [Complex32]),
+    {
+        let transform = &mut self.transform;
+        self.stft.process_overlap_add_sidechain(
+            main_buffer,
+            sidechain_buffers,
+            self.overlap_times,
+            |channel_idx, sidechain_idx, real_buffer| {
+                process_cb(channel_idx, sidechain_idx, transform.analyze(real_buffer));
+                if sidechain_idx.is_none() {
+                    transform.synthesize(real_buffer);
+                }
+            },
+        );
+    }
+
+    /// Compute the gain needed to compensate for the unnormalized inverse FFT and for the
+    /// overlapping squared analysis and synthesis windows.
+    fn update_gain_compensation(&mut self) {
+        let window_size = self.window_size();
+        let squared_window_sum: f32 = self.transform.window_function.iter().map(|x| x * x).sum();
+        let hop_size = (window_size / self.overlap_times) as f32;
+
+        // This is the average value of the overlapping squared windows
+        let window_overlap_gain = squared_window_sum / hop_size;
+        self.transform.gain_compensation = if window_overlap_gain > 0.0 {
+            (window_overlap_gain * window_size as f32).recip()
+        } else {
+            0.0
+        };
+    }
+}
+
+impl SpectralTransform {
+    /// Apply the analysis window to `real_buffer` and transform it to the frequency domain. The
+    /// returned spectrum can be modified before calling [`synthesize()`][Self::synthesize()].
+    fn analyze(&mut self, real_buffer: &mut [f32]) -> &mut [Complex32] {
+        window::multiply_with_window(real_buffer, &self.window_function);
+        self.fft.forward(real_buffer, &mut self.complex_fft_buffer);
+
+        &mut self.complex_fft_buffer
+    }
+
+    /// Transform the spectrum from the last [`analyze()`][Self::analyze()] call back to
+    /// `real_buffer`, and apply the synthesis window and the gain compensation.
+    fn synthesize(&mut self, real_buffer: &mut [f32]) {
+        self.fft.inverse(&mut self.complex_fft_buffer, real_buffer);
+
+        for (sample, window_sample) in real_buffer.iter_mut().zip(&self.window_function) {
+            *sample *= window_sample * self.gain_compensation;
+        }
+    }
+}
@@ -0,0 +1,218 @@
+//! A transport-synced step clock for arpeggiators and step sequencers.
+
+use crate::context::process::Transport;
+
+/// A step triggered by a [`StepClock`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockTick {
+    /// The step's sample offset within the current buffer.
+    pub timing: u32,
+    /// The step's index counted from the start of the song. Use `step.rem_euclid(num_steps)` to get
+    /// the position within a sequence. Since this is derived from the host's position, sequences
+    /// stay in sync when the playhead is moved or when the transport loops.
+    pub step: i64,
+}
+
+/// Converts the host's transport into sample accurate step triggers, with configurable step
+/// lengths and swing. This is the timing part of an arpeggiator or a step sequencer. The steps are
+/// aligned to the host's grid, so a clock with a step length of a sixteenth note triggers on every
+/// sixteenth note in the project.
+///
+/// Call [`process()`][Self::process()] once per buffer. The clock only runs while the transport is
+/// playing and the host provides both the tempo and the position. If playback starts in the middle
+/// of a step, then the first tick happens at the start of the next step.
+#[derive(Debug, Clone)]
+pub struct StepClock {
+    /// The length of a step in quarter notes.
+    step_length: f64,
+    /// How far every second step is delayed, as a fraction of a step, in `[0, 1)`.
+    swing: f64,
+    /// The position in quarter notes at the last sample of the previous buffer. `None` if the
+    /// transport was not playing or if the playhead jumped.
+    last_pos_beats: Option<f64>,
+}
+
+impl StepClock {
+    /// Create a clock with steps of `step_length` quarter notes. Use 0.25 for sixteenth notes,
+    /// 0.5 for eighth notes, and 1.0 / 3.0 for eighth note triplets.
+    pub fn new(step_length: f64) -> Self {
+        Self {
+            step_length: sanitize_step_length(step_length),
+            swing: 0.0,
+            last_pos_beats: None,
+        }
+    }
+
+    /// Create a clock with `steps_per_bar` steps in a 4/4 bar. Use 16 for sixteenth notes.
+    pub fn with_division(steps_per_bar: u32) -> Self {
+        Self::new(4.0 / steps_per_bar.max(1) as f64)
+    }
+
+    /// The length of a step in quarter notes.
+    pub fn step_length(&self) -> f64 {
+        self.step_length
+    }
+
+    /// Change the length of a step in quarter notes. This can be changed while the transport is
+    /// running, in which case the next tick happens on the new grid.
+    pub fn set_step_length(&mut self, step_length: f64) {
+        self.step_length = sanitize_step_length(step_length);
+    }
+
+    /// The swing amount, see [`set_swing()`][Self::set_swing()].
+    pub fn swing(&self) -> f64 {
+        self.swing
+    }
+
+    /// Delay every second (odd) step by a fraction of a step. 0 results in straight steps, and
+    /// 1/3 results in a triplet feel. Clamped to `[0, 0.95]`.
+    pub fn set_swing(&mut self, swing: f64) {
+        self.swing = swing.clamp(0.0, 0.95);
+    }
+
+    /// Forget the previous position. Call this from
+    /// [`Plugin::reset()`][crate::prelude::Plugin::reset()].
+    pub fn reset(&mut self) {
+        self.last_pos_beats = None;
+    }
+
+    /// The position of a step in quarter notes, including swing.
+    pub fn step_pos_beats(&self, step: i64) -> f64 {
+        let pos_beats = step as f64 * self.step_length;
+        if step.rem_euclid(2) == 1 {
+            pos_beats + (self.swing * self.step_length)
+        } else {
+            pos_beats
+        }
+    }
+
+    /// Call `tick` for every step that starts in the next `num_samples` samples, in order. Call
+    /// this once at the start of every process call, before handling the buffer.
+    pub fn process(
+        &mut self,
+        transport: &Transport,
+        num_samples: usize,
+        mut tick: impl FnMut(ClockTick),
+    ) {
+        if !transport.playing || transport.position_jumped() || transport.started_playing() {
+            self.last_pos_beats = None;
+        }
+        if !transport.playing {
+            return;
+        }
+
+        let (Some(beats_per_sample), Some(pos_beats_iter)) = (
+            transport.beats_per_sample(),
+            transport.pos_beats_iter(num_samples),
+        ) else {
+            self.last_pos_beats = None;
+            return;
+        };
+
+        for (sample_idx, pos_beats) in pos_beats_iter.enumerate() {
+            let step = self.step_at(pos_beats);
+            let triggered = match self.last_pos_beats {
+                Some(last_pos_beats) => step != self.step_at(last_pos_beats),
+                // Right after starting, only trigger if the step started at this exact sample
+                None => pos_beats - self.step_pos_beats(step) < beats_per_sample,
+            };
+            if triggered {
+                tick(ClockTick {
+                    timing: sample_idx as u32,
+                    step,
+                });
+            }
+
+            self.last_pos_beats = Some(pos_beats);
+        }
+    }
+
+    /// The index of the step that's playing at a position in quarter notes.
+    fn step_at(&self, pos_beats: f64) -> i64 {
+        // Steps come in pairs where the second step is delayed by the swing
+        let pair_length = self.step_length * 2.0;
+        let pair = (pos_beats / pair_length).floor();
+        let pos_in_pair = pos_beats - (pair * pair_length);
+        let second_step_start = self.step_length * (1.0 + self.swing);
+
+        (pair as i64 * 2) + (pos_in_pair >= second_step_start) as i64
+    }
+}
+
+/// Make sure the step length is positive and not so short that the clock would trigger on almost
+/// every sample.
+fn sanitize_step_length(step_length: f64) -> f64 {
+    if step_length.is_finite() && step_length > 1.0 / 256.0 {
+        step_length
+    } else {
+        nih_debug_assert_failure!("Invalid step length {}", step_length);
+        1.0 / 256.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playing_transport(pos_beats: f64) -> Transport {
+        // These values result in a beats per sample value that can be represented exactly
+        let mut transport = Transport::new(4096.0);
+        transport.playing = true;
+        transport.tempo = Some(60.0);
+        transport.pos_beats = Some(pos_beats);
+
+        transport
+    }
+
+    fn ticks(clock: &mut StepClock, transport: &Transport, num_samples: usize) -> Vec<ClockTick> {
+        let mut ticks = Vec::new();
+        clock.process(transport, num_samples, |tick| ticks.push(tick));
+
+        ticks
+    }
+
+    #[test]
+    fn straight_sixteenths() {
+        // A sixteenth note is 1024 samples long
+        let mut clock = StepClock::with_division(16);
+        let transport = playing_transport(0.0);
+
+        assert_eq!(
+            ticks(&mut clock, &transport, 2200),
+            [
+                ClockTick { timing: 0, step: 0 },
+                ClockTick {
+                    timing: 1024,
+                    step: 1
+                },
+                ClockTick {
+                    timing: 2048,
+                    step: 2
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn swing_and_late_start() {
+        let mut clock = StepClock::with_division(16);
+        clock.set_swing(0.5);
+
+        // Starting halfway into the first step should not trigger it. The second step is delayed
+        // by half a step.
+        let transport = playing_transport(0.125);
+        assert_eq!(
+            ticks(&mut clock, &transport, 2000),
+            [
+                ClockTick {
+                    timing: 1024,
+                    step: 1
+                },
+                ClockTick {
+                    timing: 1536,
+                    step: 2
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,346 @@
+//! Sustain and sostenuto pedal handling for synthesizers.
+
+use crate::midi::NoteEvent;
+
+/// The CC number for the sustain (damper) pedal.
+const SUSTAIN_CC: u8 = 64;
+/// The CC number for the sostenuto pedal.
+const SOSTENUTO_CC: u8 = 66;
+
+/// The number of MIDI channels times the number of notes per channel.
+const NUM_NOTES: usize = 16 * 128;
+
+/// The state of a single note on a single channel.
+#[derive(Debug, Clone, Copy, Default)]
+struct NoteState {
+    /// Whether the key is physically held down.
+    key_down: bool,
+    /// Whether the key was held down when the sostenuto pedal was pressed. These notes are held
+    /// until the sostenuto pedal is released.
+    sostenuto: bool,
+    /// A note off event that's being held back by one of the pedals, stored as the event's voice ID
+    /// and velocity.
+    pending_note_off: Option<(Option<i32>, f32)>,
+}
+
+/// Applies the sustain (CC64) and sostenuto (CC66) pedals to a stream of [`NoteEvent`]s by holding
+/// back note off events until the pedals are released. Synths can then treat note off events as
+/// the start of a voice's release stage without having to know about pedals.
+///
+/// Pass every incoming event to [`process()`][Self::process()], and handle the events it emits
+/// instead of the original events:
+///
+/// - While the sustain pedal is down, note off events are held back until the pedal is released.
+/// - When the sostenuto pedal is pressed, the notes that are held at that moment are sustained
+///   until the sostenuto pedal is released. Notes played afterwards are not affected.
+/// - Playing a note that's only still sounding because of a pedal first releases the old note
+///   before the new note on event is emitted, so voices for the same note don't pile up.
+/// - Choke events remove any held back note off events for the note.
+///
+/// The held back note off events are emitted with the pedal's timing. The pedal CC events
+/// themselves are always passed through. Pedals are considered down when their value is at least
+/// 0.5, and every channel has its own pedals. This does not allocate after it has been created.
+/// Call [`reset()`][Self::reset()] from [`Plugin::reset()`][crate::prelude::Plugin::reset()].
+#[derive(Debug)]
+pub struct SustainPedal {
+    /// The state for every note, indexed by `channel * 128 + note`.
+    notes: Vec<NoteState>,
+    /// Whether the sustain pedal is down, per channel.
+    sustain_down: [bool; 16],
+    /// Whether the sostenuto pedal is down, per channel.
+    sostenuto_down: [bool; 16],
+}
+
+impl Default for SustainPedal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SustainPedal {
+    /// Create a new pedal handler with both pedals released on all channels.
+    pub fn new() -> Self {
+        Self {
+            notes: vec![NoteState::default(); NUM_NOTES],
+            sustain_down: [false; 16],
+            sostenuto_down: [false; 16],
+        }
+    }
+
+    /// Release both pedals and forget all held notes without emitting any events.
+    pub fn reset(&mut self) {
+        self.notes.fill(NoteState::default());
+        self.sustain_down = [false; 16];
+        self.sostenuto_down = [false; 16];
+    }
+
+    /// Whether the sustain pedal is currently down on this channel.
+    pub fn is_sustain_down(&self, channel: u8) -> bool {
+        self.sustain_down
+            .get(channel as usize)
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Whether the sostenuto pedal is currently down on this channel.
+    pub fn is_sostenuto_down(&self, channel: u8) -> bool {
+        self.sostenuto_down
+            .get(channel as usize)
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Handle an event, and call `emit` with the events the synth should handle instead. This
+    /// emits zero or more events. All events other than note on, note off, choke, and pedal events
+    /// are passed through unchanged.
+    pub fn process<S>(&mut self, event: NoteEvent<S>, mut emit: impl FnMut(NoteEvent<S>)) {
+        match event {
+            NoteEvent::NoteOn {
+                timing,
+                channel,
+                note,
+                ..
+            } => {
+                if let Some(state) = self.note_state(channel, note) {
+                    state.key_down = true;
+                    if let Some((voice_id, velocity)) = state.pending_note_off.take() {
+                        emit(NoteEvent::NoteOff {
+                            timing,
+                            voice_id,
+                            channel,
+                            note,
+                            velocity,
+                        });
+                    }
+                }
+
+                emit(event);
+            }
+            NoteEvent::NoteOff {
+                voice_id,
+                channel,
+                note,
+                velocity,
+                ..
+            } => {
+                let sustain_down = self.is_sustain_down(channel);
+                match self.note_state(channel, note) {
+                    Some(state) => {
+                        state.key_down = false;
+                        if sustain_down || state.sostenuto {
+                            state.pending_note_off = Some((voice_id, velocity));
+                        } else {
+                            emit(event);
+                        }
+                    }
+                    None => emit(event),
+                }
+            }
+            NoteEvent::Choke { channel, note, .. } => {
+                if let Some(state) = self.note_state(channel, note) {
+                    *state = NoteState::default();
+                }
+
+                emit(event);
+            }
+            NoteEvent::MidiCC {
+                timing,
+                channel,
+                cc: SUSTAIN_CC,
+                value,
+            } if (channel as usize) < 16 => {
+                let down = value >= 0.5;
+                let was_down = std::mem::replace(&mut self.sustain_down[channel as usize], down);
+
+                emit(event);
+                if was_down && !down {
+                    self.release_pending_notes(timing, channel, emit);
+                }
+            }
+            NoteEvent::MidiCC {
+                timing,
+                channel,
+                cc: SOSTENUTO_CC,
+                value,
+            } if (channel as usize) < 16 => {
+                let down = value >= 0.5;
+                let was_down = std::mem::replace(&mut self.sostenuto_down[channel as usize], down);
+
+                emit(event);
+                if !was_down && down {
+                    for state in self.channel_notes(channel) {
+                        state.sostenuto = state.key_down || state.pending_note_off.is_some();
+                    }
+                } else if was_down && !down {
+                    for state in self.channel_notes(channel) {
+                        state.sostenuto = false;
+                    }
+                    self.release_pending_notes(timing, channel, emit);
+                }
+            }
+            event => emit(event),
+        }
+    }
+
+    /// Emit the held back note off events on a channel for notes that are no longer held by either
+    /// pedal.
+    fn release_pending_notes<S>(
+        &mut self,
+        timing: u32,
+        channel: u8,
+        mut emit: impl FnMut(NoteEvent<S>),
+    ) {
+        if self.is_sustain_down(channel) {
+            return;
+        }
+
+        for (note, state) in self.channel_notes(channel).enumerate() {
+            if state.sostenuto {
+                continue;
+            }
+
+            if let Some((voice_id, velocity)) = state.pending_note_off.take() {
+                emit(NoteEvent::NoteOff {
+                    timing,
+                    voice_id,
+                    channel,
+                    note: note as u8,
+                    velocity,
+                });
+            }
+        }
+    }
+
+    fn note_state(&mut self, channel: u8, note: u8) -> Option<&mut NoteState> {
+        if channel < 16 && note < 128 {
+            Some(&mut self.notes[channel as usize * 128 + note as usize])
+        } else {
+            None
+        }
+    }
+
+    fn channel_notes(&mut self, channel: u8) -> impl Iterator<Item = &mut NoteState> {
+        let start = channel as usize * 128;
+        self.notes[start..start + 128].iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note_on(timing: u32, note: u8) -> NoteEvent<()> {
+        NoteEvent::NoteOn {
+            timing,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity: 1.0,
+        }
+    }
+
+    fn note_off(timing: u32, note: u8) -> NoteEvent<()> {
+        NoteEvent::NoteOff {
+            timing,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity: 0.0,
+        }
+    }
+
+    fn pedal(timing: u32, cc: u8, down: bool) -> NoteEvent<()> {
+        NoteEvent::MidiCC {
+            timing,
+            channel: 0,
+            cc,
+            value: if down { 1.0 } else { 0.0 },
+        }
+    }
+
+    /// Process the events and return the emitted note events, without the pedal events.
+    fn process(pedal: &mut SustainPedal, events: Vec<NoteEvent<()>>) -> Vec<NoteEvent<()>> {
+        let mut emitted = Vec::new();
+        for event in events {
+            pedal.process(event, |event| {
+                if !matches!(event, NoteEvent::MidiCC { .. }) {
+                    emitted.push(event)
+                }
+            });
+        }
+
+        emitted
+    }
+
+    #[test]
+    fn sustain() {
+        let mut sustain_pedal = SustainPedal::new();
+        let emitted = process(
+            &mut sustain_pedal,
+            vec![
+                note_on(0, 60),
+                pedal(1, SUSTAIN_CC, true),
+                note_off(2, 60),
+                note_on(3, 64),
+                note_off(4, 64),
+                pedal(5, SUSTAIN_CC, false),
+            ],
+        );
+
+        assert_eq!(
+            emitted,
+            [
+                note_on(0, 60),
+                note_on(3, 64),
+                note_off(5, 60),
+                note_off(5, 64)
+            ]
+        );
+    }
+
+    #[test]
+    fn sustain_retrigger() {
+        let mut sustain_pedal = SustainPedal::new();
+        let emitted = process(
+            &mut sustain_pedal,
+            vec![
+                pedal(0, SUSTAIN_CC, true),
+                note_on(1, 60),
+                note_off(2, 60),
+                note_on(3, 60),
+                pedal(4, SUSTAIN_CC, false),
+            ],
+        );
+
+        // The second note on should release the first note, and the note is still held when the
+        // pedal is released
+        assert_eq!(emitted, [note_on(1, 60), note_off(3, 60), note_on(3, 60)]);
+    }
+
+    #[test]
+    fn sostenuto() {
+        let mut sustain_pedal = SustainPedal::new();
+        let emitted = process(
+            &mut sustain_pedal,
+            vec![
+                note_on(0, 60),
+                pedal(1, SOSTENUTO_CC, true),
+                note_on(2, 64),
+                note_off(3, 60),
+                note_off(4, 64),
+                pedal(5, SOSTENUTO_CC, false),
+            ],
+        );
+
+        // Only the note held while pressing the pedal is sustained
+        assert_eq!(
+            emitted,
+            [
+                note_on(0, 60),
+                note_on(2, 64),
+                note_off(4, 64),
+                note_off(5, 60)
+            ]
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! A lock-free triple buffer for sharing data between the audio thread and the editor.
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+/// Set on the shared back buffer index when the back buffer contains data the output has not yet
+/// seen.
+const BACK_BUFFER_DIRTY: u8 = 0b100;
+/// The bits in the shared back buffer index containing the actual index.
+const BACK_BUFFER_INDEX_MASK: u8 = 0b011;
+
+/// A lock-free and wait-free triple buffer. This is the recommended way to send analysis data like
+/// spectra, peak meters, or oscilloscope snapshots from
+/// [`Plugin::process()`][crate::prelude::Plugin::process()] to your editor without using mutexes.
+/// The writer always has a buffer to write to and the reader always has a consistent snapshot to
+/// read from. Values the reader doesn't get to see in time are simply overwritten, which is
+/// usually what you want for GUI data. For simple `Copy` values, an `AtomicCell` from the
+/// `crossbeam` crate or NIH-plug's [`AtomicF32`][crate::prelude::AtomicF32] may be enough.
+///
+/// Create the buffer with [`TripleBuffer::new()`] in your plugin's `Default` implementation or in
+/// [`Plugin::initialize()`][crate::prelude::Plugin::initialize()], and then
+/// [`split()`][TripleBuffer::split()] it into an input for the audio thread and an output for the
+/// editor. Neither half allocates after the buffer has been created, as long as `T` itself doesn't
+/// allocate when being written to.
+///
+/// ```
+/// # use nih_plug::util::TripleBuffer;
+/// let (mut input, mut output) = TripleBuffer::new([0.0f32; 4]).split();
+///
+/// // On the audio thread
+/// input.input_buffer()[1] = 0.5;
+/// input.publish();
+///
+/// // In the editor
+/// assert_eq!(output.read()[1], 0.5);
+/// ```
+pub struct TripleBuffer<T: Send> {
+    input: TripleBufferInput<T>,
+    output: TripleBufferOutput<T>,
+}
+
+/// The writing half of a [`TripleBuffer`]. This should be used from the audio thread.
+pub struct TripleBufferInput<T: Send> {
+    shared: Arc<SharedState<T>>,
+    /// The index of the buffer currently owned by the input.
+    input_idx: u8,
+}
+
+/// The reading half of a [`TripleBuffer`]. This should be used from the editor.
+pub struct TripleBufferOutput<T: Send> {
+    shared: Arc<SharedState<T>>,
+    /// The index of the buffer currently owned by the output.
+    output_idx: u8,
+}
+
+/// The buffers and the index of the back buffer shared between the input and the output.
+struct SharedState<T> {
+    buffers: [UnsafeCell<T>; 3],
+    /// The index of the back buffer, possibly combined with the [`BACK_BUFFER_DIRTY`] flag. The
+    /// input and the output swap their own buffer with this buffer, so each of the three buffers
+    /// is always owned by exactly one of the input, the output, and this back buffer slot.
+    back_info: AtomicU8,
+}
+
+// SAFETY: The input and the output only ever access the buffer they currently own, and ownership is
+//         transferred through `back_info` using acquire-release semantics
+unsafe impl<T: Send> Sync for SharedState<T> {}
+
+impl<T: Clone + Send> TripleBuffer<T> {
+    /// Create a new triple buffer where all three buffers are initialized to `initial_value`. This
+    /// allocates.
+    pub fn new(initial_value: T) -> Self {
+        Self::with_buffers(initial_value.clone(), initial_value.clone(), initial_value)
+    }
+}
+
+impl<T: Default + Send> Default for TripleBuffer<T> {
+    fn default() -> Self {
+        Self::with_buffers(T::default(), T::default(), T::default())
+    }
+}
+
+impl<T: Send> TripleBuffer<T> {
+    fn with_buffers(input_buffer: T, back_buffer: T, output_buffer: T) -> Self {
+        let shared = Arc::new(SharedState {
+            buffers: [
+                UnsafeCell::new(input_buffer),
+                UnsafeCell::new(back_buffer),
+                UnsafeCell::new(output_buffer),
+            ],
+            back_info: AtomicU8::new(1),
+        });
+
+        Self {
+            input: TripleBufferInput {
+                shared: shared.clone(),
+                input_idx: 0,
+            },
+            output: TripleBufferOutput {
+                shared,
+                output_idx: 2,
+            },
+        }
+    }
+
+    /// Split the triple buffer into an input for the audio thread and an output for the editor.
+    pub fn split(self) -> (TripleBufferInput<T>, TripleBufferOutput<T>) {
+        (self.input, self.output)
+    }
+}
+
+impl<T: Send> TripleBufferInput<T> {
+    /// Get a mutable reference to the input buffer. This still contains the data from before the
+    /// last [`publish()`][Self::publish()] call, so if the buffer is only partially updated then
+    /// the value should be reset first. The changes only become visible to the output after
+    /// publishing them.
+    pub fn input_buffer(&mut self) -> &mut T {
+        // SAFETY: The input exclusively owns this buffer until it gets published
+        unsafe { &mut *self.shared.buffers[self.input_idx as usize].get() }
+    }
+
+    /// Publish the input buffer so it can be read by the output. Returns `true` if the previously
+    /// published value was overwritten before the output got to read it.
+    pub fn publish(&mut self) -> bool {
+        let former_back_info = self
+            .shared
+            .back_info
+            .swap(self.input_idx | BACK_BUFFER_DIRTY, Ordering::AcqRel);
+        self.input_idx = former_back_info & BACK_BUFFER_INDEX_MASK;
+
+        former_back_info & BACK_BUFFER_DIRTY != 0
+    }
+
+    /// Overwrite the input buffer with `value` and publish it. See
+    /// [`publish()`][Self::publish()].
+    pub fn write(&mut self, value: T) -> bool {
+        *self.input_buffer() = value;
+        self.publish()
+    }
+}
+
+impl<T: Send> TripleBufferOutput<T> {
+    /// Whether the input has published a value that has not yet been read.
+    pub fn updated(&self) -> bool {
+        self.shared.back_info.load(Ordering::Relaxed) & BACK_BUFFER_DIRTY != 0
+    }
+
+    /// Fetch the most recently published value if there is one, and return a reference to it. If
+    /// nothing new has been published since the last call, then this returns the same value as
+    /// before.
+    pub fn read(&mut self) -> &T {
+        self.update();
+        self.output_buffer()
+    }
+
+    /// Get a mutable reference to the output buffer without fetching a new value. This can be
+    /// used to process the data in place.
+    pub fn output_buffer(&mut self) -> &mut T {
+        // SAFETY: The output exclusively owns this buffer until it gets swapped with the back
+        //         buffer
+        unsafe { &mut *self.shared.buffers[self.output_idx as usize].get() }
+    }
+
+    /// Swap the output buffer with the back buffer if the input has published a new value since
+    /// the last update. Returns `true` if the output buffer was updated.
+    pub fn update(&mut self) -> bool {
+        if !self.updated() {
+            return false;
+        }
+
+        let former_back_info = self
+            .shared
+            .back_info
+            .swap(self.output_idx, Ordering::AcqRel);
+        self.output_idx = former_back_info & BACK_BUFFER_INDEX_MASK;
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_initial_value() {
+        let (_input, mut output) = TripleBuffer::new(42).split();
+
+        assert!(!output.updated());
+        assert_eq!(*output.read(), 42);
+    }
+
+    #[test]
+    fn read_latest_value() {
+        let (mut input, mut output) = TripleBuffer::new(0).split();
+
+        assert!(!input.write(1));
+        assert!(input.write(2));
+        assert!(output.updated());
+        assert_eq!(*output.read(), 2);
+        assert!(!output.updated());
+        assert_eq!(*output.read(), 2);
+
+        assert!(!input.write(3));
+        assert_eq!(*output.read(), 3);
+    }
+
+    #[test]
+    fn concurrent_reads_are_consistent() {
+        let (mut input, mut output) = TripleBuffer::new([0u32; 16]).split();
+
+        let writer = std::thread::spawn(move || {
+            for i in 1..=10_000 {
+                input.write([i; 16]);
+            }
+        });
+
+        let mut last_value = 0;
+        while last_value < 10_000 {
+            let values = output.read();
+            assert!(values.iter().all(|value| *value == values[0]));
+            assert!(values[0] >= last_value);
+            last_value = values[0];
+        }
+
+        writer.join().unwrap();
+    }
+}
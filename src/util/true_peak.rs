@@ -0,0 +1,142 @@
+//! True peak detection using oversampling, following ITU-R BS.1770.
+
+use std::f64::consts::PI;
+
+use crate::buffer::Buffer;
+
+/// The oversampling factor used to estimate the peaks between samples.
+const OVERSAMPLING: usize = 4;
+/// The number of input samples used to compute every oversampled sample.
+const NUM_TAPS: usize = 12;
+
+/// Measures a signal's true peak level by oversampling it by a factor of four, as described in
+/// ITU-R BS.1770-4. A signal's true peak can be several decibels higher than its sample peak, and
+/// these inter-sample peaks can clip after sample rate conversion or lossy encoding, which is why
+/// mastering limiters and meters should use these values instead of the sample peak.
+///
+/// The detector does not allocate after it's been created. The oversampling filter adds a small
+/// delay of six samples to the reported peaks.
+///
+/// ```ignore
+/// // In `process()`
+/// let true_peak_db = util::gain_to_db(self.true_peak_detector.process(buffer));
+/// self.true_peak_meter.store(true_peak_db, Ordering::Relaxed);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TruePeakDetector {
+    /// The polyphase interpolation filter, with one set of taps per phase.
+    filter: [[f32; NUM_TAPS]; OVERSAMPLING],
+    /// The last [`NUM_TAPS`] input samples for every channel, stored twice in a row so they can be
+    /// read as a contiguous slice.
+    history: Vec<[f32; NUM_TAPS * 2]>,
+    /// The position in every channel's history the next sample will be written to.
+    history_pos: Vec<usize>,
+}
+
+impl TruePeakDetector {
+    /// Create a detector for `num_channels` channels. This allocates.
+    pub fn new(num_channels: usize) -> Self {
+        Self {
+            filter: interpolation_filter(),
+            history: vec![[0.0; NUM_TAPS * 2]; num_channels],
+            history_pos: vec![0; num_channels],
+        }
+    }
+
+    /// The number of channels this detector was created for.
+    pub fn num_channels(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Clear the filter's history.
+    pub fn reset(&mut self) {
+        for history in &mut self.history {
+            history.fill(0.0);
+        }
+        self.history_pos.fill(0);
+    }
+
+    /// Compute the true peak of a block of audio as a linear gain value, taking all channels into
+    /// account. The buffer is not modified. The buffer should have the same number of channels as
+    /// the detector. Use [`gain_to_db()`][super::gain_to_db()] to convert the result to dBTP.
+    pub fn process(&mut self, buffer: &Buffer) -> f32 {
+        nih_debug_assert_eq!(buffer.channels(), self.num_channels());
+
+        let mut peak = 0.0f32;
+        for (channel_idx, channel) in buffer
+            .as_slice_immutable()
+            .iter()
+            .take(self.num_channels())
+            .enumerate()
+        {
+            for sample in channel.iter() {
+                peak = peak.max(self.process_sample(channel_idx, *sample));
+            }
+        }
+
+        peak
+    }
+
+    /// Add a single sample to a channel's history, and return the absolute peak value of the
+    /// sample and the oversampled values leading up to it.
+    #[inline]
+    pub fn process_sample(&mut self, channel_idx: usize, sample: f32) -> f32 {
+        let pos = self.history_pos[channel_idx];
+        let history = &mut self.history[channel_idx];
+        history[pos] = sample;
+        history[pos + NUM_TAPS] = sample;
+        self.history_pos[channel_idx] = (pos + 1) % NUM_TAPS;
+
+        // This contains the oldest sample first and the new sample last, just like the filter
+        let window = &history[pos + 1..pos + 1 + NUM_TAPS];
+        self.filter.iter().fold(sample.abs(), |peak, phase| {
+            let oversampled: f32 = phase.iter().zip(window).map(|(h, x)| h * x).sum();
+            peak.max(oversampled.abs())
+        })
+    }
+}
+
+/// A Blackman windowed sinc interpolation filter for 4x oversampling, split into one set of taps
+/// per phase. The taps are ordered from the oldest input sample to the newest input sample.
+fn interpolation_filter() -> [[f32; NUM_TAPS]; OVERSAMPLING] {
+    let half_width = (NUM_TAPS / 2) as f64 + 0.5;
+    let mut filter = [[0.0; NUM_TAPS]; OVERSAMPLING];
+    for (phase_idx, phase) in filter.iter_mut().enumerate() {
+        // Every phase interpolates a point between the two input samples in the middle
+        let position = (NUM_TAPS / 2 - 1) as f64 + (phase_idx as f64 / OVERSAMPLING as f64);
+        for (tap_idx, tap) in phase.iter_mut().enumerate() {
+            let distance = position - tap_idx as f64;
+            let x = distance * PI;
+            let sinc = if x == 0.0 { 1.0 } else { x.sin() / x };
+            let window_pos = distance / half_width;
+            let window =
+                0.42 + (0.5 * (PI * window_pos).cos()) + (0.08 * (2.0 * PI * window_pos).cos());
+
+            *tap = (sinc * window) as f32;
+        }
+    }
+
+    filter
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_inter_sample_peaks() {
+        // A sine wave at a quarter of the sample rate with a 45 degree phase offset never hits its
+        // peaks on a sample, so its sample peak is 3 dB below its true peak
+        let mut detector = TruePeakDetector::new(1);
+        let mut peak = 0.0f32;
+        let mut sample_peak = 0.0f32;
+        for i in 0..256 {
+            let sample = ((i as f32 + 0.5) * std::f32::consts::FRAC_PI_2).sin();
+            sample_peak = sample_peak.max(sample.abs());
+            peak = peak.max(detector.process_sample(0, sample));
+        }
+
+        assert!((sample_peak - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-5);
+        assert!((peak - 1.0).abs() < 0.05, "{peak}");
+    }
+}
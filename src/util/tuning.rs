@@ -0,0 +1,551 @@
+//! Microtuning using [Scala](https://www.huygens-fokker.org/scala/scl_format.html) scale and
+//! keyboard mapping files, and optionally by following an MTS-ESP master plugin.
+//!
+//! Parsing tuning files allocates, so this should be done on a background thread, for instance
+//! using [`Plugin::task_executor()`][crate::prelude::Plugin::task_executor()]. The resulting
+//! [`Tuning`] is a fixed size table that can be sent to the audio thread and used from there.
+//! Voice code should call [`Tuner::note_to_freq()`] instead of
+//! [`midi_note_to_freq()`][super::midi_note_to_freq()] to support all tuning sources at once.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+
+#[cfg(feature = "mts_esp")]
+mod mts_esp;
+
+/// The largest map size accepted in a `.kbm` file. A mapping can't repeat over more keys than MIDI
+/// has.
+const MAX_MAP_SIZE: usize = 128;
+
+#[cfg(feature = "mts_esp")]
+pub use mts_esp::MtsEspClient;
+
+/// A scale parsed from a Scala `.scl` file. The scale's degrees are stored in cents, starting with
+/// the first degree above the root. The last degree is the scale's period, usually an octave.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scale {
+    description: String,
+    /// The scale's degrees in cents relative to the root, not including the root itself.
+    degrees: Vec<f64>,
+}
+
+/// A keyboard mapping parsed from a Scala `.kbm` file. This determines which MIDI notes map to
+/// which scale degrees, and which note is tuned to which frequency.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyboardMapping {
+    /// The first MIDI note that is retuned. Notes below this are unmapped.
+    pub first_note: u8,
+    /// The last MIDI note that is retuned. Notes above this are unmapped.
+    pub last_note: u8,
+    /// The MIDI note the first entry in the mapping is mapped to.
+    pub middle_note: u8,
+    /// The MIDI note that is tuned to `reference_freq`.
+    pub reference_note: u8,
+    /// The frequency for `reference_note`, in Hertz.
+    pub reference_freq: f64,
+    /// The scale degree the mapping repeats at. The interval for this degree is added for every
+    /// repetition of the mapping.
+    pub octave_degree: usize,
+    /// Maps keys to scale degrees, relative to the middle note. `None` means the key is unmapped.
+    /// An empty mapping maps every key to consecutive scale degrees.
+    pub mapping: Vec<Option<usize>>,
+}
+
+/// A tuning table for all 128 MIDI notes, created from a [`Scale`] and a [`KeyboardMapping`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tuning {
+    frequencies: [f32; 128],
+    /// Whether the notes are mapped. Unmapped notes should not sound.
+    mapped: [bool; 128],
+}
+
+/// Combines a static [`Tuning`] with an optional MTS-ESP client. When the client is connected to an
+/// MTS-ESP master, that master's tuning is used. Otherwise the static tuning is used. This lets
+/// voice code use a single [`note_to_freq()`][Self::note_to_freq()] function regardless of where
+/// the tuning comes from.
+#[derive(Debug, Default)]
+pub struct Tuner {
+    tuning: Tuning,
+    #[cfg(feature = "mts_esp")]
+    mts_esp: Option<MtsEspClient>,
+}
+
+impl Scale {
+    /// Parse the contents of a `.scl` file.
+    pub fn parse(scl: &str) -> Result<Self> {
+        let mut lines = scl.lines().filter(|line| !line.starts_with('!'));
+        let description = lines
+            .next()
+            .context("The scale is missing a description")?
+            .trim()
+            .to_owned();
+        let num_degrees: usize = lines
+            .next()
+            .context("The scale is missing the number of notes")?
+            .trim()
+            .parse()
+            .context("The scale's number of notes is not a number")?;
+
+        let degrees = lines
+            .filter(|line| !line.trim().is_empty())
+            .take(num_degrees)
+            .map(parse_pitch)
+            .collect::<Result<Vec<f64>>>()?;
+        if degrees.len() != num_degrees {
+            anyhow::bail!(
+                "The scale should contain {num_degrees} notes, but it contains {}",
+                degrees.len()
+            );
+        }
+        if degrees.is_empty() {
+            anyhow::bail!("The scale does not contain any notes");
+        }
+
+        Ok(Self {
+            description,
+            degrees,
+        })
+    }
+
+    /// Read and parse a `.scl` file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let scl = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read '{}'", path.display()))?;
+
+        Self::parse(&scl).with_context(|| format!("Could not parse '{}'", path.display()))
+    }
+
+    /// An equal temperament scale with `num_degrees` notes per octave.
+    pub fn equal_temperament(num_degrees: usize) -> Self {
+        let num_degrees = num_degrees.max(1);
+
+        Self {
+            description: format!("{num_degrees}-tone equal temperament"),
+            degrees: (1..=num_degrees)
+                .map(|degree| degree as f64 * 1200.0 / num_degrees as f64)
+                .collect(),
+        }
+    }
+
+    /// The scale's description from the first line of the `.scl` file.
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// The number of notes in the scale, including the period.
+    pub fn len(&self) -> usize {
+        self.degrees.len()
+    }
+
+    /// Always `false`, since a scale needs at least one note.
+    pub fn is_empty(&self) -> bool {
+        self.degrees.is_empty()
+    }
+
+    /// The interval in cents between the root and a scale degree. Degrees outside of the scale
+    /// repeat at the scale's period. Degree 0 is the root.
+    pub fn cents(&self, degree: i64) -> f64 {
+        let num_degrees = self.degrees.len() as i64;
+        let period = self.degrees[self.degrees.len() - 1];
+        let periods = degree.div_euclid(num_degrees);
+        let degree_in_period = degree.rem_euclid(num_degrees);
+
+        let cents_in_period = if degree_in_period == 0 {
+            0.0
+        } else {
+            self.degrees[degree_in_period as usize - 1]
+        };
+
+        periods as f64 * period + cents_in_period
+    }
+}
+
+impl Default for KeyboardMapping {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+impl KeyboardMapping {
+    /// The standard mapping for 12-note scales: every key maps to the next scale degree starting at
+    /// middle C, and A4 is tuned to 440 Hz.
+    pub fn standard() -> Self {
+        Self::linear(60, 69, 440.0)
+    }
+
+    /// Map every key to the next scale degree, with the scale's root at `middle_note` and
+    /// `reference_note` tuned to `reference_freq`. This is the same as a `.kbm` file with a map
+    /// size of zero.
+    pub fn linear(middle_note: u8, reference_note: u8, reference_freq: f64) -> Self {
+        Self {
+            first_note: 0,
+            last_note: 127,
+            middle_note,
+            reference_note,
+            reference_freq,
+            octave_degree: 0,
+            mapping: Vec::new(),
+        }
+    }
+
+    /// Parse the contents of a `.kbm` file.
+    pub fn parse(kbm: &str) -> Result<Self> {
+        let mut lines = kbm
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.starts_with('!') && !line.is_empty());
+        let mut next_line = |name: &str| {
+            lines
+                .next()
+                .with_context(|| format!("The keyboard mapping is missing the {name}"))
+        };
+
+        let map_size: usize = parse_field(next_line("map size")?, "map size")?;
+        if map_size > MAX_MAP_SIZE {
+            anyhow::bail!(
+                "The keyboard mapping's map size should be at most {MAX_MAP_SIZE}, but it is \
+                 {map_size}"
+            );
+        }
+        let first_note = parse_field(next_line("first note")?, "first note")?;
+        let last_note = parse_field(next_line("last note")?, "last note")?;
+        let middle_note = parse_field(next_line("middle note")?, "middle note")?;
+        let reference_note = parse_field(next_line("reference note")?, "reference note")?;
+        let reference_freq = parse_field(next_line("reference frequency")?, "reference frequency")?;
+        let octave_degree = parse_field(next_line("octave degree")?, "octave degree")?;
+
+        // The mapping may be shorter than the map size, in which case the remaining keys are
+        // unmapped
+        let mut mapping = Vec::with_capacity(map_size);
+        for line in lines.take(map_size) {
+            mapping.push(if line.starts_with('x') {
+                None
+            } else {
+                Some(parse_field(line, "mapping")?)
+            });
+        }
+        mapping.resize(map_size, None);
+
+        let mapping = Self {
+            first_note,
+            last_note,
+            middle_note,
+            reference_note,
+            reference_freq,
+            octave_degree,
+            mapping,
+        };
+        if mapping.first_note > 127 || mapping.last_note > 127 || mapping.middle_note > 127 {
+            anyhow::bail!("The keyboard mapping's notes should be in the range 0-127");
+        }
+        if mapping.reference_note > 127 {
+            anyhow::bail!("The keyboard mapping's reference note should be in the range 0-127");
+        }
+        if mapping.reference_freq.is_nan() || mapping.reference_freq <= 0.0 {
+            anyhow::bail!("The keyboard mapping's reference frequency should be positive");
+        }
+
+        Ok(mapping)
+    }
+
+    /// Read and parse a `.kbm` file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let kbm = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read '{}'", path.display()))?;
+
+        Self::parse(&kbm).with_context(|| format!("Could not parse '{}'", path.display()))
+    }
+
+    /// The interval in cents between the scale's root and `note` when using this mapping for
+    /// `scale`. Returns `None` if the note is unmapped.
+    fn cents(&self, scale: &Scale, note: u8) -> Option<f64> {
+        let offset = note as i64 - self.middle_note as i64;
+        if self.mapping.is_empty() {
+            return Some(scale.cents(offset));
+        }
+
+        let map_size = self.mapping.len() as i64;
+        let repetitions = offset.div_euclid(map_size);
+        let degree = self.mapping[offset.rem_euclid(map_size) as usize]?;
+
+        Some(
+            repetitions as f64 * scale.cents(self.octave_degree as i64)
+                + scale.cents(degree as i64),
+        )
+    }
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self::equal_temperament()
+    }
+}
+
+impl Tuning {
+    /// Compute the tuning table for a scale and a keyboard mapping. Returns an error if the
+    /// keyboard mapping's reference note is not mapped.
+    pub fn new(scale: &Scale, mapping: &KeyboardMapping) -> Result<Self> {
+        let reference_cents = mapping
+            .cents(scale, mapping.reference_note)
+            .context("The keyboard mapping's reference note is not mapped")?;
+
+        let mut tuning = Self::equal_temperament();
+        for note in 0..128u8 {
+            let cents = if (mapping.first_note..=mapping.last_note).contains(&note) {
+                mapping.cents(scale, note)
+            } else {
+                None
+            };
+
+            match cents {
+                Some(cents) => {
+                    tuning.frequencies[note as usize] = (mapping.reference_freq
+                        * 2.0f64.powf((cents - reference_cents) / 1200.0))
+                        as f32;
+                }
+                None => tuning.mapped[note as usize] = false,
+            }
+        }
+
+        Ok(tuning)
+    }
+
+    /// Load a tuning from a `.scl` file and an optional `.kbm` file. If no keyboard mapping is
+    /// provided, then [`KeyboardMapping::standard()`] is used.
+    pub fn load(scl_path: impl AsRef<Path>, kbm_path: Option<&Path>) -> Result<Self> {
+        let scale = Scale::load(scl_path)?;
+        let mapping = match kbm_path {
+            Some(kbm_path) => KeyboardMapping::load(kbm_path)?,
+            None => KeyboardMapping::standard(),
+        };
+
+        Self::new(&scale, &mapping)
+    }
+
+    /// Standard 12-tone equal temperament with A4 at 440 Hz.
+    pub fn equal_temperament() -> Self {
+        let mut frequencies = [0.0; 128];
+        for (note, frequency) in frequencies.iter_mut().enumerate() {
+            *frequency = super::midi_note_to_freq(note as u8);
+        }
+
+        Self {
+            frequencies,
+            mapped: [true; 128],
+        }
+    }
+
+    /// The frequency for a MIDI note. Unmapped notes keep their 12-tone equal temperament
+    /// frequency.
+    #[inline]
+    pub fn note_to_freq(&self, note: u8) -> f32 {
+        self.frequencies[(note as usize).min(127)]
+    }
+
+    /// Whether a MIDI note is mapped. Notes that are not mapped should not be played.
+    #[inline]
+    pub fn is_mapped(&self, note: u8) -> bool {
+        self.mapped[(note as usize).min(127)]
+    }
+}
+
+impl Tuner {
+    /// Create a tuner that uses a static tuning.
+    pub fn new(tuning: Tuning) -> Self {
+        Self {
+            tuning,
+            #[cfg(feature = "mts_esp")]
+            mts_esp: None,
+        }
+    }
+
+    /// The static tuning that's used when there's no MTS-ESP master.
+    pub fn tuning(&self) -> &Tuning {
+        &self.tuning
+    }
+
+    /// Change the static tuning. The tuning can be loaded on a background thread and then sent to
+    /// the audio thread, since this does not allocate.
+    pub fn set_tuning(&mut self, tuning: Tuning) {
+        self.tuning = tuning;
+    }
+
+    /// Register as an MTS-ESP client. This loads the MTS-ESP library, so it should not be called
+    /// from the audio thread. Returns an error if the library is not installed, in which case the
+    /// static tuning keeps being used.
+    #[cfg(feature = "mts_esp")]
+    pub fn connect_mts_esp(&mut self) -> Result<()> {
+        if self.mts_esp.is_none() {
+            self.mts_esp = Some(MtsEspClient::new()?);
+        }
+
+        Ok(())
+    }
+
+    /// Deregister as an MTS-ESP client.
+    #[cfg(feature = "mts_esp")]
+    pub fn disconnect_mts_esp(&mut self) {
+        self.mts_esp = None;
+    }
+
+    /// The frequency for a note on a MIDI channel. This uses the MTS-ESP master's tuning if there
+    /// is one, and the static tuning otherwise. The channel is only used for MTS-ESP's
+    /// multi-channel tunings.
+    #[inline]
+    pub fn note_to_freq(&self, note: u8, channel: u8) -> f32 {
+        #[cfg(feature = "mts_esp")]
+        if let Some(frequency) = self
+            .mts_esp
+            .as_ref()
+            .and_then(|mts_esp| mts_esp.note_to_freq(note, channel))
+        {
+            return frequency;
+        }
+        #[cfg(not(feature = "mts_esp"))]
+        let _ = channel;
+
+        self.tuning.note_to_freq(note)
+    }
+
+    /// Whether a note on a MIDI channel should be ignored because it's not part of the tuning.
+    #[inline]
+    pub fn should_filter_note(&self, note: u8, channel: u8) -> bool {
+        #[cfg(feature = "mts_esp")]
+        if let Some(should_filter) = self
+            .mts_esp
+            .as_ref()
+            .and_then(|mts_esp| mts_esp.should_filter_note(note, channel))
+        {
+            return should_filter;
+        }
+        #[cfg(not(feature = "mts_esp"))]
+        let _ = channel;
+
+        !self.tuning.is_mapped(note)
+    }
+}
+
+/// Parse a pitch line from a `.scl` file to cents. Pitches containing a period are in cents, and
+/// other pitches are ratios or whole numbers. Anything after the first token is a comment.
+fn parse_pitch(line: &str) -> Result<f64> {
+    let pitch = line
+        .split_whitespace()
+        .next()
+        .context("Empty pitch in the scale")?;
+
+    if pitch.contains('.') {
+        return pitch
+            .parse()
+            .with_context(|| format!("'{pitch}' is not a valid value in cents"));
+    }
+
+    let (numerator, denominator) = pitch.split_once('/').unwrap_or((pitch, "1"));
+    let numerator: u64 = numerator
+        .parse()
+        .with_context(|| format!("'{pitch}' is not a valid ratio"))?;
+    let denominator: u64 = denominator
+        .parse()
+        .with_context(|| format!("'{pitch}' is not a valid ratio"))?;
+    if numerator == 0 || denominator == 0 {
+        anyhow::bail!("'{pitch}' is not a positive ratio");
+    }
+
+    Ok((numerator as f64 / denominator as f64).log2() * 1200.0)
+}
+
+/// Parse a single value from a `.kbm` file. Anything after the first token is a comment.
+fn parse_field<T: std::str::FromStr>(line: &str, name: &str) -> Result<T> {
+    line.split_whitespace()
+        .next()
+        .and_then(|value| value.parse().ok())
+        .with_context(|| format!("The keyboard mapping's {name} '{line}' is not valid"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_scale() {
+        let scale = Scale::parse(
+            "! meantone.scl
+!
+Quarter-comma meantone
+ 3
+!
+ 193.157
+ 5/4 major third
+ 2
+",
+        )
+        .unwrap();
+
+        assert_eq!(scale.description(), "Quarter-comma meantone");
+        assert_eq!(scale.len(), 3);
+        assert_eq!(scale.cents(1), 193.157);
+        assert!((scale.cents(2) - 386.3137).abs() < 1e-3);
+        assert_eq!(scale.cents(3), 1200.0);
+        assert!((scale.cents(-2) - -1006.843).abs() < 1e-3);
+    }
+
+    #[test]
+    fn standard_tuning_matches_equal_temperament() {
+        let tuning =
+            Tuning::new(&Scale::equal_temperament(12), &KeyboardMapping::standard()).unwrap();
+        for note in 0..128 {
+            let expected = crate::util::midi_note_to_freq(note);
+            assert!((tuning.note_to_freq(note) - expected).abs() / expected < 1e-5);
+            assert!(tuning.is_mapped(note));
+        }
+    }
+
+    #[test]
+    fn keyboard_mapping() {
+        // Only the white keys are mapped to a 7-note scale, with middle C at 261.63 Hz
+        let mapping = KeyboardMapping::parse(
+            "12
+0
+127
+60
+60
+261.63
+7
+0
+x
+1
+x
+2
+3
+x
+4
+x
+5
+x
+6
+",
+        )
+        .unwrap();
+        let tuning = Tuning::new(&Scale::equal_temperament(7), &mapping).unwrap();
+
+        assert_eq!(tuning.note_to_freq(60), 261.63);
+        assert!(!tuning.is_mapped(61));
+        assert!((tuning.note_to_freq(72) - 523.26).abs() < 1e-3);
+        assert!((tuning.note_to_freq(48) - 130.815).abs() < 1e-3);
+    }
+
+    #[test]
+    fn keyboard_mapping_size() {
+        let kbm = |map_size: usize| format!("{map_size}\n0\n127\n60\n69\n440\n12\n0\n");
+
+        // Mappings can be shorter than their map size, the remaining keys are unmapped
+        let mapping = KeyboardMapping::parse(&kbm(12)).unwrap();
+        assert_eq!(mapping.mapping.len(), 12);
+        assert_eq!(mapping.mapping[0], Some(0));
+        assert!(mapping.mapping[1..].iter().all(Option::is_none));
+
+        assert!(KeyboardMapping::parse(&kbm(MAX_MAP_SIZE + 1)).is_err());
+        assert!(KeyboardMapping::parse(&kbm(usize::MAX)).is_err());
+    }
+}
@@ -0,0 +1,158 @@
+//! A client for [MTS-ESP](https://github.com/ODDSound/MTS-ESP), which lets a single master plugin
+//! set the tuning for all client plugins in a session.
+
+use anyhow::{Context, Result};
+use libloading::Library;
+use std::ffi::c_char;
+use std::path::PathBuf;
+
+/// The functions exported by the MTS-ESP library that are used by clients. These match the
+/// function pointers loaded by the official `libMTSClient.cpp`.
+struct MtsEspFunctions {
+    register_client: unsafe extern "C" fn(),
+    deregister_client: unsafe extern "C" fn(),
+    has_master: unsafe extern "C" fn() -> bool,
+    should_filter_note_multi_channel: unsafe extern "C" fn(c_char, c_char) -> bool,
+    get_tuning_table: unsafe extern "C" fn() -> *const f64,
+    get_multi_channel_tuning_table: unsafe extern "C" fn(c_char) -> *const f64,
+    use_multi_channel_tuning: unsafe extern "C" fn(c_char) -> bool,
+}
+
+/// An MTS-ESP client. The MTS-ESP library is loaded from its standard installation location when
+/// the client is created, and the client is registered with the library for as long as this
+/// object is alive. Querying the tuning does not allocate or block, so the query functions can be
+/// called from the audio thread. See [`Tuner`][super::Tuner] for combining this with a fallback
+/// tuning.
+pub struct MtsEspClient {
+    functions: MtsEspFunctions,
+    /// Keeps the library loaded for as long as the function pointers are used.
+    _library: Library,
+}
+
+impl std::fmt::Debug for MtsEspClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MtsEspClient")
+            .field("has_master", &self.has_master())
+            .finish()
+    }
+}
+
+// SAFETY: The MTS-ESP library is designed to be queried from any thread, including the audio thread
+unsafe impl Send for MtsEspClient {}
+unsafe impl Sync for MtsEspClient {}
+
+impl MtsEspClient {
+    /// Load the MTS-ESP library and register as a client. Returns an error if the library is not
+    /// installed or if it does not export the expected functions.
+    pub fn new() -> Result<Self> {
+        let library_path = library_path()?;
+        let library = unsafe { Library::new(&library_path) }.with_context(|| {
+            format!(
+                "Could not load the MTS-ESP library from '{}'",
+                library_path.display()
+            )
+        })?;
+
+        let functions = unsafe {
+            MtsEspFunctions {
+                register_client: load_symbol(&library, b"MTS_RegisterClient\0")?,
+                deregister_client: load_symbol(&library, b"MTS_DeregisterClient\0")?,
+                has_master: load_symbol(&library, b"MTS_HasMaster\0")?,
+                should_filter_note_multi_channel: load_symbol(
+                    &library,
+                    b"MTS_ShouldFilterNoteMultiChannel\0",
+                )?,
+                get_tuning_table: load_symbol(&library, b"MTS_GetTuningTable\0")?,
+                get_multi_channel_tuning_table: load_symbol(
+                    &library,
+                    b"MTS_GetMultiChannelTuningTable\0",
+                )?,
+                use_multi_channel_tuning: load_symbol(&library, b"MTS_UseMultiChannelTuning\0")?,
+            }
+        };
+
+        unsafe { (functions.register_client)() };
+
+        Ok(Self {
+            functions,
+            _library: library,
+        })
+    }
+
+    /// Whether there's currently an MTS-ESP master plugin in the session.
+    pub fn has_master(&self) -> bool {
+        unsafe { (self.functions.has_master)() }
+    }
+
+    /// The master's frequency for a note on a MIDI channel. Returns `None` if there's no master, in
+    /// which case a fallback tuning should be used. The channel is only used if the master provides
+    /// separate tunings per channel.
+    pub fn note_to_freq(&self, note: u8, channel: u8) -> Option<f32> {
+        if note > 127 || !self.has_master() {
+            return None;
+        }
+
+        unsafe {
+            let mut table = std::ptr::null();
+            if channel < 16 && (self.functions.use_multi_channel_tuning)(channel as c_char) {
+                table = (self.functions.get_multi_channel_tuning_table)(channel as c_char);
+            }
+            if table.is_null() {
+                table = (self.functions.get_tuning_table)();
+            }
+            if table.is_null() {
+                return None;
+            }
+
+            Some(*table.add(note as usize) as f32)
+        }
+    }
+
+    /// Whether the master wants a note on a MIDI channel to be ignored, for instance because it's
+    /// not mapped to a scale degree. Returns `None` if there's no master.
+    pub fn should_filter_note(&self, note: u8, channel: u8) -> Option<bool> {
+        if note > 127 || !self.has_master() {
+            return None;
+        }
+
+        Some(unsafe {
+            (self.functions.should_filter_note_multi_channel)(note as c_char, channel as c_char)
+        })
+    }
+}
+
+impl Drop for MtsEspClient {
+    fn drop(&mut self) {
+        unsafe { (self.functions.deregister_client)() };
+    }
+}
+
+/// The MTS-ESP library's standard installation location for the current platform.
+fn library_path() -> Result<PathBuf> {
+    if cfg!(target_os = "windows") {
+        let program_files = std::env::var_os("CommonProgramFiles")
+            .context("Could not find the Common Files directory")?;
+
+        Ok(PathBuf::from(program_files)
+            .join("MTS-ESP")
+            .join("LIBMTS.dll"))
+    } else if cfg!(target_os = "macos") {
+        Ok(PathBuf::from(
+            "/Library/Application Support/MTS-ESP/libMTS.dylib",
+        ))
+    } else {
+        Ok(PathBuf::from("/usr/local/lib/libMTS.so"))
+    }
+}
+
+/// Load a function pointer from the library. `name` must be null terminated.
+unsafe fn load_symbol<T: Copy>(library: &Library, name: &[u8]) -> Result<T> {
+    let symbol = library.get::<T>(name).with_context(|| {
+        format!(
+            "The MTS-ESP library does not export '{}'",
+            String::from_utf8_lossy(&name[..name.len() - 1])
+        )
+    })?;
+
+    Ok(*symbol)
+}
@@ -0,0 +1,200 @@
+//! Gain and mixing operations on slices of samples. When the `simd` feature is enabled these
+//! process the samples in `std::simd` vectors, and otherwise they use plain loops that the compiler
+//! may or may not be able to vectorize on its own.
+
+#[cfg(feature = "simd")]
+use std::simd::{f32x8, SimdFloat};
+
+/// The number of samples processed at once when the `simd` feature is enabled. Eight lanes fill an
+/// AVX register, and they're split into two operations on platforms that only support 128-bit
+/// vectors.
+#[cfg(feature = "simd")]
+const LANES: usize = 8;
+
+/// Multiply every sample by `gain`.
+#[inline]
+pub fn apply_gain(samples: &mut [f32], gain: f32) {
+    #[cfg(feature = "simd")]
+    {
+        let gain_vector = f32x8::splat(gain);
+        let mut chunks = samples.chunks_exact_mut(LANES);
+        for chunk in &mut chunks {
+            (f32x8::from_slice(chunk) * gain_vector).copy_to_slice(chunk);
+        }
+
+        for sample in chunks.into_remainder() {
+            *sample *= gain;
+        }
+    }
+
+    #[cfg(not(feature = "simd"))]
+    for sample in samples {
+        *sample *= gain;
+    }
+}
+
+/// Multiply every sample by the gain at the same index in `gains`, for instance a block of values
+/// produced by [`Smoother::next_block()`][crate::prelude::Smoother::next_block()].
+///
+/// # Panics
+///
+/// Panics if `gains` contains fewer values than `samples`.
+#[inline]
+pub fn apply_gains(samples: &mut [f32], gains: &[f32]) {
+    assert!(gains.len() >= samples.len());
+    let gains = &gains[..samples.len()];
+
+    #[cfg(feature = "simd")]
+    {
+        let mut chunks = samples.chunks_exact_mut(LANES);
+        let mut gain_chunks = gains.chunks_exact(LANES);
+        for (chunk, gain_chunk) in (&mut chunks).zip(&mut gain_chunks) {
+            (f32x8::from_slice(chunk) * f32x8::from_slice(gain_chunk)).copy_to_slice(chunk);
+        }
+
+        for (sample, gain) in chunks
+            .into_remainder()
+            .iter_mut()
+            .zip(gain_chunks.remainder())
+        {
+            *sample *= gain;
+        }
+    }
+
+    #[cfg(not(feature = "simd"))]
+    for (sample, gain) in samples.iter_mut().zip(gains) {
+        *sample *= gain;
+    }
+}
+
+/// Compute `output = (output * output_gain) + (input * input_gain)` for every sample. This can be
+/// used for crossfades and for mixing signals together.
+///
+/// # Panics
+///
+/// Panics if `input` contains fewer samples than `output`.
+#[inline]
+pub fn mix_scaled(output: &mut [f32], output_gain: f32, input: &[f32], input_gain: f32) {
+    assert!(input.len() >= output.len());
+    let input = &input[..output.len()];
+
+    #[cfg(feature = "simd")]
+    {
+        let output_gain_vector = f32x8::splat(output_gain);
+        let input_gain_vector = f32x8::splat(input_gain);
+        let mut chunks = output.chunks_exact_mut(LANES);
+        let mut input_chunks = input.chunks_exact(LANES);
+        for (chunk, input_chunk) in (&mut chunks).zip(&mut input_chunks) {
+            let mixed = f32x8::from_slice(chunk) * output_gain_vector
+                + f32x8::from_slice(input_chunk) * input_gain_vector;
+            mixed.copy_to_slice(chunk);
+        }
+
+        for (sample, input_sample) in chunks
+            .into_remainder()
+            .iter_mut()
+            .zip(input_chunks.remainder())
+        {
+            *sample = (*sample * output_gain) + (input_sample * input_gain);
+        }
+    }
+
+    #[cfg(not(feature = "simd"))]
+    for (sample, input_sample) in output.iter_mut().zip(input) {
+        *sample = (*sample * output_gain) + (input_sample * input_gain);
+    }
+}
+
+/// The absolute peak value in `samples`, or zero if the slice is empty.
+#[inline]
+pub fn peak(samples: &[f32]) -> f32 {
+    #[cfg(feature = "simd")]
+    {
+        let mut chunks = samples.chunks_exact(LANES);
+        let mut peaks = f32x8::splat(0.0);
+        for chunk in &mut chunks {
+            peaks = peaks.simd_max(f32x8::from_slice(chunk).abs());
+        }
+
+        chunks
+            .remainder()
+            .iter()
+            .fold(peaks.reduce_max(), |peak, sample| peak.max(sample.abs()))
+    }
+
+    #[cfg(not(feature = "simd"))]
+    samples
+        .iter()
+        .fold(0.0f32, |peak, sample| peak.max(sample.abs()))
+}
+
+/// Fill `values` with a linear ramp, where `values[i] = start + (step_size * (i + 1))`. Used for
+/// linear parameter smoothing.
+#[inline]
+pub(crate) fn fill_linear_ramp(values: &mut [f32], start: f32, step_size: f32) {
+    #[cfg(feature = "simd")]
+    {
+        let offsets = f32x8::from_array([1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0]);
+        let step_size_vector = f32x8::splat(step_size);
+        for (chunk_idx, chunk) in values.chunks_mut(LANES).enumerate() {
+            let chunk_start = f32x8::splat(start + (step_size * (chunk_idx * LANES) as f32));
+            let ramp = (chunk_start + offsets * step_size_vector).to_array();
+            chunk.copy_from_slice(&ramp[..chunk.len()]);
+        }
+    }
+
+    #[cfg(not(feature = "simd"))]
+    for (idx, value) in values.iter_mut().enumerate() {
+        *value = start + (step_size * (idx + 1) as f32);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::smoothing::{Smoother, SmoothingStyle};
+
+    #[test]
+    fn linear_ramp_matches_scalar() {
+        // This is not a multiple of the number of lanes, so the last chunk is partially filled
+        let mut ramp = [0.0; 37];
+        fill_linear_ramp(&mut ramp, 0.25, 0.01);
+
+        let mut current = 0.25;
+        for value in ramp {
+            current += 0.01;
+            approx::assert_relative_eq!(value, current, epsilon = 1e-5);
+        }
+    }
+
+    /// The block smoothing functions use [`fill_linear_ramp()`] for linear smoothing. These should
+    /// produce the same values as calling [`Smoother::next()`] in a loop for every smoothing style.
+    #[test]
+    fn smoothed_block_matches_next() {
+        for style in [
+            SmoothingStyle::None,
+            SmoothingStyle::Linear(100.0),
+            SmoothingStyle::Logarithmic(100.0),
+            SmoothingStyle::Exponential(100.0),
+        ] {
+            let block_smoother = Smoother::<f32>::new(style.clone());
+            let scalar_smoother = Smoother::<f32>::new(style.clone());
+            for smoother in [&block_smoother, &scalar_smoother] {
+                smoother.reset(0.4);
+                smoother.set_target(1000.0, 0.8);
+            }
+
+            // The smoothing period is 100 samples long, so this also covers the final value that
+            // snaps to the target
+            let mut block = [0.0; 150];
+            block_smoother.next_block_exact(&mut block);
+            for (idx, value) in block.into_iter().enumerate() {
+                let expected = scalar_smoother.next();
+                assert!(
+                    (value - expected).abs() <= 1e-5,
+                    "{style:?}, sample {idx}: {value} != {expected}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,706 @@
+//! A polyphonic voice manager for synthesizers.
+
+use std::cmp::Ordering;
+
+use crate::context::process::ProcessContext;
+use crate::midi::NoteEvent;
+use crate::params::smoothing::Smoother;
+use crate::params::{FloatParam, Param};
+use crate::plugin::Plugin;
+
+use super::pitch_bend::{semitones_to_freq_multiplier, PitchBendRange};
+
+/// Which voice a [`VoiceAllocator`] reuses when a new note is started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StealingMode {
+    /// Use a free voice if there is one, and otherwise steal the voice that was started the
+    /// longest time ago. Voices that are already releasing are stolen before held voices.
+    Oldest,
+    /// Use a free voice if there is one, and otherwise steal the voice with the lowest level as
+    /// reported through [`Voice::set_level()`]. Voices that are already releasing are stolen before
+    /// held voices.
+    Quietest,
+    /// Restart the voice that's playing the same note on the same channel if there is one, even
+    /// if there are free voices. Otherwise this behaves like [`StealingMode::Oldest`]. This
+    /// prevents the same note from stacking up when it is played repeatedly with long release
+    /// times.
+    SameNote,
+}
+
+/// A voice managed by a [`VoiceAllocator`]. The synth's own per-voice state is stored in
+/// [`data`][Self::data].
+#[derive(Debug)]
+pub struct Voice<V> {
+    /// The voice's index in the allocator. This never changes.
+    idx: usize,
+    /// The voice's ID. This is either the ID sent by the host, or a fallback ID computed from the
+    /// note and channel if the host did not send one.
+    voice_id: i32,
+    /// The note's channel, in `0..16`.
+    channel: u8,
+    /// The note's MIDI key number, in `0..128`.
+    note: u8,
+    /// Incremented for every started voice. Used to find the oldest voice.
+    age: u64,
+    /// Whether this slot is currently in use.
+    active: bool,
+    /// Whether the voice received a note off event.
+    releasing: bool,
+    /// The voice's level as reported by the plugin, used for [`StealingMode::Quietest`].
+    level: f32,
+    /// Set when the voice is started, and cleared once the voice is rendered for the first time
+    /// through [`VoiceAllocator::active_voices_mut()`]. Polyphonic modulation received before then
+    /// immediately jumps to the modulated value instead of fading in.
+    fresh: bool,
+    /// The voice's normalized modulation offset and a smoother for the modulated plain value,
+    /// indexed by poly modulation ID. `None` if the parameter is not modulated for this voice.
+    modulation: Vec<Option<(f32, Smoother<f32>)>>,
+    /// The pitch bend for the voice's channel, in semitones.
+    pitch_bend: f32,
+    /// The voice's tuning from [`NoteEvent::PolyTuning`] events, in semitones.
+    tuning: f32,
+
+    /// The synth's own state for this voice, like oscillator phases and envelopes. When a voice
+    /// is started this still contains the state of the voice that previously used this slot, so
+    /// it needs to be reinitialized after calling [`VoiceAllocator::note_on()`].
+    pub data: V,
+}
+
+/// A fixed size pool of voices for polyphonic synthesizers. This takes care of voice stealing,
+/// matching note off, choke, and polyphonic modulation events to the right voices using either the
+/// host's voice IDs or the note and channel, and sending [`NoteEvent::VoiceTerminated`] events back
+/// to the host when voices end or get stolen. That last part is required for CLAP's polyphonic
+/// modulation to work correctly.
+///
+/// Voices are allocated once in [`new()`][Self::new()], so none of the other functions allocate.
+/// Each voice also has its own smoothers for polyphonically modulated parameters, see
+/// [`handle_poly_modulation()`][Self::handle_poly_modulation()] and
+/// [`Voice::poly_modulation()`].
+#[derive(Debug)]
+pub struct VoiceAllocator<V> {
+    voices: Vec<Voice<V>>,
+    stealing_mode: StealingMode,
+    /// The age assigned to the next started voice.
+    next_age: u64,
+    /// The range used to convert [`NoteEvent::MidiPitchBend`] events to semitones.
+    pitch_bend_range: PitchBendRange,
+    /// The current pitch bend for each MIDI channel, in semitones. New voices start out with their
+    /// channel's pitch bend.
+    channel_pitch_bend: [f32; 16],
+}
+
+impl<V> Voice<V> {
+    /// The voice's index in the voice allocator, in `0..capacity`. This stays the same for the
+    /// allocator's lifetime, so it can be used to index per-voice state stored outside of the
+    /// voice, like a [`SmootherPool`][super::SmootherPool].
+    pub fn index(&self) -> usize {
+        self.idx
+    }
+
+    /// The voice's ID. If the host did not send a voice ID with the note on event, then this is
+    /// derived from the note and channel.
+    pub fn voice_id(&self) -> i32 {
+        self.voice_id
+    }
+
+    /// The note's channel, in `0..16`.
+    pub fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    /// The note's MIDI key number, in `0..128`.
+    pub fn note(&self) -> u8 {
+        self.note
+    }
+
+    /// Whether the voice has received a note off event and is now in its release stage.
+    pub fn is_releasing(&self) -> bool {
+        self.releasing
+    }
+
+    /// Report the voice's current level, for instance its amplitude envelope's value. This is used
+    /// to pick a voice to steal with [`StealingMode::Quietest`]. Voices that have not reported a
+    /// level yet are considered to be the loudest.
+    pub fn set_level(&mut self, level: f32) {
+        self.level = level;
+    }
+
+    /// The smoother for the polyphonically modulated plain value of the parameter with this poly
+    /// modulation ID. Returns `None` if the parameter is not modulated for this voice, in which case
+    /// the parameter's global smoother should be used instead.
+    pub fn poly_modulation(&self, poly_modulation_id: u32) -> Option<&Smoother<f32>> {
+        self.modulation
+            .get(poly_modulation_id as usize)?
+            .as_ref()
+            .map(|(_, smoother)| smoother)
+    }
+
+    /// The voice's pitch offset in semitones. This is the sum of the pitch bend on the voice's
+    /// channel and the voice's own tuning from [`NoteEvent::PolyTuning`] events.
+    pub fn pitch_offset(&self) -> f32 {
+        self.pitch_bend + self.tuning
+    }
+
+    /// The voice's fractional note number with [`pitch_offset()`][Self::pitch_offset()] applied.
+    /// Pass this to [`f32_midi_note_to_freq()`][crate::util::f32_midi_note_to_freq()] to get the
+    /// voice's frequency.
+    pub fn pitch(&self) -> f32 {
+        self.note as f32 + self.pitch_offset()
+    }
+
+    /// [`pitch_offset()`][Self::pitch_offset()] as a frequency multiplier. This is useful for
+    /// synths that already compute the note's base frequency when the voice is started.
+    pub fn freq_multiplier(&self) -> f32 {
+        semitones_to_freq_multiplier(self.pitch_offset())
+    }
+}
+
+impl<V: Default> VoiceAllocator<V> {
+    /// Create a voice allocator with `num_voices` voices. `num_poly_modulation_ids` is one higher
+    /// than the highest poly modulation ID used in the plugin's parameters, or zero if the plugin
+    /// does not support polyphonic modulation.
+    pub fn new(
+        num_voices: usize,
+        num_poly_modulation_ids: usize,
+        stealing_mode: StealingMode,
+    ) -> Self {
+        nih_debug_assert_ne!(num_voices, 0);
+
+        Self {
+            voices: (0..num_voices)
+                .map(|idx| Voice {
+                    idx,
+                    voice_id: 0,
+                    channel: 0,
+                    note: 0,
+                    age: 0,
+                    active: false,
+                    releasing: false,
+                    level: f32::INFINITY,
+                    fresh: false,
+                    modulation: (0..num_poly_modulation_ids).map(|_| None).collect(),
+                    pitch_bend: 0.0,
+                    tuning: 0.0,
+
+                    data: V::default(),
+                })
+                .collect(),
+            stealing_mode,
+            next_age: 0,
+            pitch_bend_range: PitchBendRange::default(),
+            channel_pitch_bend: [0.0; 16],
+        }
+    }
+}
+
+impl<V> VoiceAllocator<V> {
+    /// The number of voices in the pool.
+    pub fn capacity(&self) -> usize {
+        self.voices.len()
+    }
+
+    /// The number of voices that are currently playing, including releasing voices.
+    pub fn num_active_voices(&self) -> usize {
+        self.voices.iter().filter(|voice| voice.active).count()
+    }
+
+    /// The current voice stealing mode.
+    pub fn stealing_mode(&self) -> StealingMode {
+        self.stealing_mode
+    }
+
+    /// Change the voice stealing mode. This only affects voices started after this call.
+    pub fn set_stealing_mode(&mut self, stealing_mode: StealingMode) {
+        self.stealing_mode = stealing_mode;
+    }
+
+    /// The range used to convert [`NoteEvent::MidiPitchBend`] events to semitones.
+    pub fn pitch_bend_range(&self) -> PitchBendRange {
+        self.pitch_bend_range
+    }
+
+    /// Change the range used to convert [`NoteEvent::MidiPitchBend`] events to semitones. This
+    /// defaults to [`PitchBendRange::MIDI`]. Use [`PitchBendRange::MPE`] for MPE controllers.
+    /// Only affects pitch bend events received after this call.
+    pub fn set_pitch_bend_range(&mut self, pitch_bend_range: PitchBendRange) {
+        self.pitch_bend_range = pitch_bend_range;
+    }
+
+    /// Stop all voices without informing the host, and reset the channels' pitch bend. Call this
+    /// from [`Plugin::reset()`][crate::prelude::Plugin::reset()].
+    pub fn reset(&mut self) {
+        for voice in &mut self.voices {
+            voice.active = false;
+        }
+        self.channel_pitch_bend = [0.0; 16];
+    }
+
+    /// Start a new voice for a [`NoteEvent::NoteOn`] event. If a voice needs to be stolen then a
+    /// [`NoteEvent::VoiceTerminated`] event is sent for it at `timing`. The returned voice's
+    /// [`data`][Voice::data] still contains the previous voice's state and should be initialized
+    /// by the caller.
+    pub fn note_on<P: Plugin>(
+        &mut self,
+        context: &mut impl ProcessContext<P>,
+        timing: u32,
+        voice_id: Option<i32>,
+        channel: u8,
+        note: u8,
+    ) -> &mut Voice<V> {
+        let voice_idx = self.voice_idx_to_start(channel, note);
+        let age = self.next_age;
+        self.next_age = self.next_age.wrapping_add(1);
+
+        let voice = &mut self.voices[voice_idx];
+        if voice.active {
+            terminate_voice(context, timing, voice);
+        }
+
+        voice.voice_id = voice_id.unwrap_or_else(|| compute_fallback_voice_id(note, channel));
+        voice.channel = channel;
+        voice.note = note;
+        voice.age = age;
+        voice.active = true;
+        voice.releasing = false;
+        voice.level = f32::INFINITY;
+        voice.fresh = true;
+        voice.pitch_bend = self.channel_pitch_bend[channel as usize % 16];
+        voice.tuning = 0.0;
+        voice
+            .modulation
+            .iter_mut()
+            .for_each(|modulation| *modulation = None);
+
+        voice
+    }
+
+    /// Mark the voices matching a [`NoteEvent::NoteOff`] event as releasing, and return them so
+    /// their release stage can be started. If `voice_id` is set then only that voice is matched.
+    /// Otherwise all held voices playing that note on that channel are matched.
+    ///
+    /// The voices keep playing until they are terminated, usually using
+    /// [`terminate_voices()`][Self::terminate_voices()] once their release stage has ended.
+    pub fn note_off(
+        &mut self,
+        voice_id: Option<i32>,
+        channel: u8,
+        note: u8,
+    ) -> impl Iterator<Item = &mut Voice<V>> {
+        self.voices
+            .iter_mut()
+            .filter(move |voice| {
+                voice.active && !voice.releasing && matches_event(voice, voice_id, channel, note)
+            })
+            .map(|voice| {
+                voice.releasing = true;
+                voice
+            })
+    }
+
+    /// Immediately stop the voices matching a [`NoteEvent::Choke`] event, and send
+    /// [`NoteEvent::VoiceTerminated`] events for them. Uses the same matching rules as
+    /// [`note_off()`][Self::note_off()], except that releasing voices are also matched.
+    pub fn choke<P: Plugin>(
+        &mut self,
+        context: &mut impl ProcessContext<P>,
+        timing: u32,
+        voice_id: Option<i32>,
+        channel: u8,
+        note: u8,
+    ) {
+        for voice in &mut self.voices {
+            if voice.active && matches_event(voice, voice_id, channel, note) {
+                terminate_voice(context, timing, voice);
+            }
+        }
+    }
+
+    /// Stop all active voices for which `predicate` returns true, and send
+    /// [`NoteEvent::VoiceTerminated`] events for them. This is typically used at the end of a block
+    /// to stop voices whose release stage has ended.
+    pub fn terminate_voices<P: Plugin>(
+        &mut self,
+        context: &mut impl ProcessContext<P>,
+        timing: u32,
+        mut predicate: impl FnMut(&Voice<V>) -> bool,
+    ) {
+        for voice in &mut self.voices {
+            if voice.active && predicate(voice) {
+                terminate_voice(context, timing, voice);
+            }
+        }
+    }
+
+    /// Get the active voice with this voice ID, if there is one.
+    pub fn voice_mut(&mut self, voice_id: i32) -> Option<&mut Voice<V>> {
+        self.voices
+            .iter_mut()
+            .find(|voice| voice.active && voice.voice_id == voice_id)
+    }
+
+    /// Iterate over all active voices, for instance to render them. Polyphonic modulation received
+    /// for a voice before it's returned from this function for the first time jumps to the
+    /// modulated value immediately. Afterwards new modulation values are smoothed.
+    pub fn active_voices_mut(&mut self) -> impl Iterator<Item = &mut Voice<V>> {
+        self.voices
+            .iter_mut()
+            .filter(|voice| voice.active)
+            .map(|voice| {
+                voice.fresh = false;
+                voice
+            })
+    }
+
+    /// Handle a [`NoteEvent::PolyModulation`] event for `param`, which should be the parameter
+    /// with this poly modulation ID. This updates the voice's smoother for the parameter, which can
+    /// be read with [`Voice::poly_modulation()`]. Events for voices that don't exist (anymore) are
+    /// ignored, since hosts may send these before a voice has started or after it has been
+    /// terminated.
+    pub fn handle_poly_modulation(
+        &mut self,
+        sample_rate: f32,
+        voice_id: i32,
+        poly_modulation_id: u32,
+        normalized_offset: f32,
+        param: &FloatParam,
+    ) {
+        let voice = match self.voice_mut(voice_id) {
+            Some(voice) => voice,
+            None => return,
+        };
+        let fresh = voice.fresh;
+        let modulation = match voice.modulation.get_mut(poly_modulation_id as usize) {
+            Some(modulation) => modulation,
+            None => {
+                nih_debug_assert_failure!(
+                    "Polyphonic modulation sent for unknown poly modulation ID {}",
+                    poly_modulation_id
+                );
+                return;
+            }
+        };
+
+        // Modulation starts out at the parameter's current smoothed value, so modulating an
+        // already playing voice fades in smoothly
+        let target_plain_value = param.preview_modulated(normalized_offset);
+        let (offset, smoother) =
+            modulation.get_or_insert_with(|| (normalized_offset, param.smoothed.clone()));
+        *offset = normalized_offset;
+        if fresh {
+            smoother.reset(target_plain_value);
+        } else {
+            smoother.set_target(sample_rate, target_plain_value);
+        }
+    }
+
+    /// Handle a [`NoteEvent::MonoAutomation`] event for `param`, which should be the parameter with
+    /// this poly modulation ID. The modulation offsets are relative to the parameter's automated
+    /// value, so this updates the smoothing targets for all voices that modulate the parameter.
+    pub fn handle_mono_automation(
+        &mut self,
+        sample_rate: f32,
+        poly_modulation_id: u32,
+        normalized_value: f32,
+        param: &FloatParam,
+    ) {
+        for voice in self.voices.iter_mut().filter(|voice| voice.active) {
+            if let Some(Some((normalized_offset, smoother))) =
+                voice.modulation.get(poly_modulation_id as usize)
+            {
+                let target_plain_value = param.preview_plain(normalized_value + normalized_offset);
+                smoother.set_target(sample_rate, target_plain_value);
+            }
+        }
+    }
+
+    /// Handle a [`NoteEvent::MidiPitchBend`] event. The pitch bend is converted to semitones using
+    /// the [pitch bend range][Self::set_pitch_bend_range()] and applied to all voices on the
+    /// channel, including voices started later, through [`Voice::pitch_offset()`].
+    ///
+    /// With regular MIDI this bends all notes on the channel. MPE controllers send each note on its
+    /// own channel, so there the same mechanism results in per-note pitch bend. MPE's per-note pitch
+    /// bend and CLAP's [`NoteEvent::PolyTuning`] events thus end up in the same place.
+    pub fn handle_pitch_bend(&mut self, channel: u8, normalized_value: f32) {
+        let Some(channel_pitch_bend) = self.channel_pitch_bend.get_mut(channel as usize) else {
+            nih_debug_assert_failure!("Pitch bend sent for invalid channel {}", channel);
+            return;
+        };
+        *channel_pitch_bend = self.pitch_bend_range.semitones(normalized_value);
+
+        let pitch_bend = *channel_pitch_bend;
+        for voice in self
+            .voices
+            .iter_mut()
+            .filter(|voice| voice.active && voice.channel == channel)
+        {
+            voice.pitch_bend = pitch_bend;
+        }
+    }
+
+    /// Handle a [`NoteEvent::PolyTuning`] event. This sets the tuning for the matching voices,
+    /// which is added to the channel's pitch bend in [`Voice::pitch_offset()`]. Uses the same
+    /// matching rules as [`choke()`][Self::choke()].
+    pub fn handle_poly_tuning(
+        &mut self,
+        voice_id: Option<i32>,
+        channel: u8,
+        note: u8,
+        tuning: f32,
+    ) {
+        for voice in &mut self.voices {
+            if voice.active && matches_event(voice, voice_id, channel, note) {
+                voice.tuning = tuning;
+            }
+        }
+    }
+
+    /// Find the index of the voice that should be used for a new note.
+    fn voice_idx_to_start(&self, channel: u8, note: u8) -> usize {
+        if self.stealing_mode == StealingMode::SameNote {
+            if let Some(idx) = self
+                .voices
+                .iter()
+                .position(|voice| voice.active && voice.channel == channel && voice.note == note)
+            {
+                return idx;
+            }
+        }
+
+        if let Some(idx) = self.voices.iter().position(|voice| !voice.active) {
+            return idx;
+        }
+
+        // All voices are in use, so one needs to be stolen. Releasing voices are always stolen
+        // first.
+        let (idx, _) = self
+            .voices
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                b.releasing
+                    .cmp(&a.releasing)
+                    .then_with(|| match self.stealing_mode {
+                        StealingMode::Quietest => a
+                            .level
+                            .partial_cmp(&b.level)
+                            .unwrap_or(Ordering::Equal)
+                            .then_with(|| a.age.cmp(&b.age)),
+                        StealingMode::Oldest | StealingMode::SameNote => a.age.cmp(&b.age),
+                    })
+            })
+            .expect("The voice allocator has no voices");
+
+        idx
+    }
+}
+
+/// Whether a voice matches a note off or choke event. Events with voice IDs only match that voice,
+/// and other events match all voices for the note and channel.
+fn matches_event<V>(voice: &Voice<V>, voice_id: Option<i32>, channel: u8, note: u8) -> bool {
+    match voice_id {
+        Some(voice_id) => voice.voice_id == voice_id,
+        None => voice.channel == channel && voice.note == note,
+    }
+}
+
+/// Deactivate a voice and inform the host that it has ended.
+fn terminate_voice<P: Plugin, V>(
+    context: &mut impl ProcessContext<P>,
+    timing: u32,
+    voice: &mut Voice<V>,
+) {
+    context.send_event(NoteEvent::VoiceTerminated {
+        timing,
+        voice_id: Some(voice.voice_id),
+        channel: voice.channel,
+        note: voice.note,
+    });
+    voice.active = false;
+}
+
+/// Compute a voice ID in case the host doesn't provide them. Polyphonic modulation will not work in
+/// this case, but playing notes will.
+const fn compute_fallback_voice_id(note: u8, channel: u8) -> i32 {
+    note as i32 | ((channel as i32) << 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::fixtures::TestPlugin;
+    use crate::test::MockProcessContext;
+
+    /// The `(voice_id, note)` pairs of the voices that were terminated through `context`.
+    fn terminated_voices(context: &mut MockProcessContext<TestPlugin>) -> Vec<(Option<i32>, u8)> {
+        context
+            .take_output_events()
+            .into_iter()
+            .filter_map(|event| match event {
+                NoteEvent::VoiceTerminated { voice_id, note, .. } => Some((voice_id, note)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The notes of all active voices, sorted.
+    fn active_notes(voices: &mut VoiceAllocator<()>) -> Vec<u8> {
+        let mut notes: Vec<u8> = voices
+            .active_voices_mut()
+            .map(|voice| voice.note())
+            .collect();
+        notes.sort_unstable();
+        notes
+    }
+
+    #[test]
+    fn polyphony_limit() {
+        let mut context = MockProcessContext::<TestPlugin>::new(44_100.0);
+        let mut voices = VoiceAllocator::<()>::new(2, 0, StealingMode::Oldest);
+        assert_eq!(voices.capacity(), 2);
+
+        for note in 60..64 {
+            voices.note_on(&mut context, 0, None, 0, note);
+            assert!(voices.num_active_voices() <= voices.capacity());
+        }
+        assert_eq!(voices.num_active_voices(), 2);
+        assert_eq!(
+            terminated_voices(&mut context),
+            [
+                (Some(compute_fallback_voice_id(60, 0)), 60),
+                (Some(compute_fallback_voice_id(61, 0)), 61),
+            ]
+        );
+
+        voices.reset();
+        assert_eq!(voices.num_active_voices(), 0);
+        assert!(terminated_voices(&mut context).is_empty());
+    }
+
+    #[test]
+    fn steal_oldest() {
+        let mut context = MockProcessContext::<TestPlugin>::new(44_100.0);
+        let mut voices = VoiceAllocator::<()>::new(3, 0, StealingMode::Oldest);
+        for note in [60, 62, 64] {
+            voices.note_on(&mut context, 0, None, 0, note);
+        }
+
+        voices.note_on(&mut context, 0, None, 0, 65);
+        assert_eq!(active_notes(&mut voices), [62, 64, 65]);
+
+        // Releasing voices are stolen before held voices, even if they are younger
+        assert_eq!(voices.note_off(None, 0, 65).count(), 1);
+        voices.note_on(&mut context, 0, None, 0, 67);
+        assert_eq!(active_notes(&mut voices), [62, 64, 67]);
+        assert_eq!(
+            terminated_voices(&mut context)
+                .into_iter()
+                .map(|(_, note)| note)
+                .collect::<Vec<_>>(),
+            [60, 65]
+        );
+    }
+
+    #[test]
+    fn steal_quietest() {
+        let mut context = MockProcessContext::<TestPlugin>::new(44_100.0);
+        let mut voices = VoiceAllocator::<()>::new(3, 0, StealingMode::Quietest);
+        voices.note_on(&mut context, 0, None, 0, 60).set_level(0.5);
+        voices.note_on(&mut context, 0, None, 0, 62).set_level(0.1);
+        // This voice hasn't reported a level yet, so it counts as the loudest voice
+        voices.note_on(&mut context, 0, None, 0, 64);
+
+        voices.note_on(&mut context, 0, None, 0, 65).set_level(0.2);
+        assert_eq!(active_notes(&mut voices), [60, 64, 65]);
+
+        voices.note_on(&mut context, 0, None, 0, 67);
+        assert_eq!(active_notes(&mut voices), [60, 64, 67]);
+    }
+
+    #[test]
+    fn steal_same_note() {
+        let mut context = MockProcessContext::<TestPlugin>::new(44_100.0);
+        let mut voices = VoiceAllocator::<()>::new(4, 0, StealingMode::SameNote);
+        voices.note_on(&mut context, 0, None, 0, 60);
+        voices.note_on(&mut context, 0, None, 0, 62);
+        assert_eq!(voices.note_off(None, 0, 60).count(), 1);
+
+        // The releasing voice for the same note is restarted even though there are free voices
+        let voice = voices.note_on(&mut context, 0, None, 0, 60);
+        assert_eq!(voice.index(), 0);
+        assert!(!voice.is_releasing());
+        assert_eq!(voices.num_active_voices(), 2);
+        assert_eq!(
+            terminated_voices(&mut context),
+            [(Some(compute_fallback_voice_id(60, 0)), 60)]
+        );
+
+        // The same note on another channel is a different voice
+        voices.note_on(&mut context, 0, None, 1, 60);
+        assert_eq!(voices.num_active_voices(), 3);
+    }
+
+    #[test]
+    fn release_handling() {
+        let mut context = MockProcessContext::<TestPlugin>::new(44_100.0);
+        let mut voices = VoiceAllocator::<()>::new(4, 0, StealingMode::Oldest);
+        voices.note_on(&mut context, 0, None, 0, 60);
+        voices.note_on(&mut context, 0, None, 0, 60);
+        voices.note_on(&mut context, 0, None, 0, 62);
+
+        // Without voice IDs, all held voices for the note are released
+        let released: Vec<usize> = voices
+            .note_off(None, 0, 60)
+            .map(|voice| voice.index())
+            .collect();
+        assert_eq!(released, [0, 1]);
+        // Voices that are already releasing are not matched again
+        assert_eq!(voices.note_off(None, 0, 60).count(), 0);
+        assert_eq!(voices.num_active_voices(), 3);
+
+        voices.terminate_voices(&mut context, 0, |voice| voice.is_releasing());
+        assert_eq!(active_notes(&mut voices), [62]);
+        assert_eq!(terminated_voices(&mut context).len(), 2);
+
+        // Choking also matches releasing voices
+        assert_eq!(voices.note_off(None, 0, 62).count(), 1);
+        voices.choke(&mut context, 0, None, 0, 62);
+        assert_eq!(voices.num_active_voices(), 0);
+        assert_eq!(
+            terminated_voices(&mut context),
+            [(Some(compute_fallback_voice_id(62, 0)), 62)]
+        );
+    }
+
+    #[test]
+    fn note_id_matching() {
+        let mut context = MockProcessContext::<TestPlugin>::new(44_100.0);
+        let mut voices = VoiceAllocator::<()>::new(4, 0, StealingMode::Oldest);
+        voices.note_on(&mut context, 0, Some(10), 0, 60);
+        voices.note_on(&mut context, 0, Some(11), 0, 60);
+        voices.note_on(&mut context, 0, None, 0, 60);
+        assert_eq!(
+            voices
+                .voice_mut(compute_fallback_voice_id(60, 0))
+                .map(|voice| voice.index()),
+            Some(2)
+        );
+
+        // Events with a voice ID only match that voice
+        let released: Vec<i32> = voices
+            .note_off(Some(11), 0, 60)
+            .map(|voice| voice.voice_id())
+            .collect();
+        assert_eq!(released, [11]);
+        assert!(voices.voice_mut(11).unwrap().is_releasing());
+        assert!(!voices.voice_mut(10).unwrap().is_releasing());
+
+        voices.choke(&mut context, 0, Some(10), 0, 60);
+        assert!(voices.voice_mut(10).is_none());
+        assert_eq!(terminated_voices(&mut context), [(Some(10), 60)]);
+        assert_eq!(voices.num_active_voices(), 2);
+
+        // Unknown voice IDs don't match anything
+        assert_eq!(voices.note_off(Some(12), 0, 60).count(), 0);
+        assert!(voices.voice_mut(12).is_none());
+    }
+}
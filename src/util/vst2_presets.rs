@@ -0,0 +1,450 @@
+//! Parsing for VST2 `.fxp` program and `.fxb` bank files. This can be used to offer a way to import
+//! presets from a plugin's old VST2 version. NIH-plug does not know anything about how the old
+//! plugin stored its state, so the actual conversion to a [`PluginState`] needs to be done by the
+//! plugin through [`Vst2Program::convert()`] or [`Vst2Preset::convert()`]. The resulting state
+//! objects can then be loaded through [`GuiContext::set_state()`][crate::prelude::GuiContext::set_state()].
+//!
+//! All values in these files are stored in big-endian byte order.
+
+use anyhow::{Context, Result};
+
+use crate::prelude::PluginState;
+
+/// The magic number every FXP and FXB file starts with.
+const CHUNK_MAGIC: &[u8; 4] = b"CcnK";
+/// A program containing normalized parameter values.
+const PROGRAM_PARAMS_MAGIC: &[u8; 4] = b"FxCk";
+/// A program containing an opaque chunk.
+const PROGRAM_CHUNK_MAGIC: &[u8; 4] = b"FPCh";
+/// A bank containing a list of regular programs.
+const BANK_PROGRAMS_MAGIC: &[u8; 4] = b"FxBk";
+/// A bank containing an opaque chunk.
+const BANK_CHUNK_MAGIC: &[u8; 4] = b"FBCh";
+
+/// The size of the program name field in an FXP file, including the null terminator.
+const PROGRAM_NAME_SIZE: usize = 28;
+/// The size of a program's header, up to and including the name. Used to limit how much memory is
+/// preallocated for a bank's programs.
+const PROGRAM_HEADER_SIZE: usize = 7 * 4 + PROGRAM_NAME_SIZE;
+/// The size of the reserved space in a bank's header. Version 2 banks use the first four bytes of
+/// this to store the current program index.
+const BANK_RESERVED_SIZE: usize = 128;
+
+/// A parsed `.fxp` or `.fxb` file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Vst2Preset {
+    /// A single program, stored in an `.fxp` file.
+    Program(Vst2Program),
+    /// A bank of programs, stored in an `.fxb` file.
+    Bank(Vst2Bank),
+}
+
+/// A single VST2 program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vst2Program {
+    /// The plugin's four character unique ID, as set through `AEffect::uniqueID`.
+    pub plugin_id: [u8; 4],
+    /// The plugin's version at the time this program was saved.
+    pub plugin_version: i32,
+    /// The program's name. This is limited to 27 characters by the file format.
+    pub name: String,
+    /// The program's data.
+    pub data: Vst2PresetData,
+}
+
+/// A bank containing multiple VST2 programs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vst2Bank {
+    /// The plugin's four character unique ID, as set through `AEffect::uniqueID`.
+    pub plugin_id: [u8; 4],
+    /// The plugin's version at the time this bank was saved.
+    pub plugin_version: i32,
+    /// The index of the active program. Only stored in version 2 banks.
+    pub current_program: Option<i32>,
+    /// The number of programs stored in the bank. For chunk banks this is only informative.
+    pub num_programs: usize,
+    /// The bank's data.
+    pub data: Vst2BankData,
+}
+
+/// The contents of a [`Vst2Program`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Vst2PresetData {
+    /// A list of normalized `[0, 1]` parameter values, indexed by the old VST2 parameter indices.
+    Params(Vec<f32>),
+    /// The plugin's opaque state chunk, as returned by `effGetChunk`.
+    Chunk(Vec<u8>),
+}
+
+/// The contents of a [`Vst2Bank`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Vst2BankData {
+    /// The individual programs in the bank.
+    Programs(Vec<Vst2Program>),
+    /// The plugin's opaque bank chunk, as returned by `effGetChunk` with `isPreset` set to false.
+    Chunk(Vec<u8>),
+}
+
+impl Vst2Preset {
+    /// Parse the contents of an `.fxp` or `.fxb` file. If `expected_plugin_id` is set, then this
+    /// will return an error if the file was saved by a different plugin.
+    pub fn parse(bytes: &[u8], expected_plugin_id: Option<[u8; 4]>) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
+        let preset = match reader.peek_fx_magic()? {
+            magic if &magic == PROGRAM_PARAMS_MAGIC || &magic == PROGRAM_CHUNK_MAGIC => {
+                Vst2Preset::Program(Vst2Program::read(&mut reader)?)
+            }
+            magic if &magic == BANK_PROGRAMS_MAGIC || &magic == BANK_CHUNK_MAGIC => {
+                Vst2Preset::Bank(Vst2Bank::read(&mut reader)?)
+            }
+            magic => anyhow::bail!("Unknown preset type {:?}", String::from_utf8_lossy(&magic)),
+        };
+
+        if let Some(expected_plugin_id) = expected_plugin_id {
+            let plugin_id = preset.plugin_id();
+            anyhow::ensure!(
+                plugin_id == expected_plugin_id,
+                "The preset belongs to a plugin with ID {:?}, expected {:?}",
+                String::from_utf8_lossy(&plugin_id),
+                String::from_utf8_lossy(&expected_plugin_id)
+            );
+        }
+
+        Ok(preset)
+    }
+
+    /// The unique ID of the plugin this preset was saved with.
+    pub fn plugin_id(&self) -> [u8; 4] {
+        match self {
+            Vst2Preset::Program(program) => program.plugin_id,
+            Vst2Preset::Bank(bank) => bank.plugin_id,
+        }
+    }
+
+    /// Convert all programs in this file to [`PluginState`] objects using a plugin-specific
+    /// converter. For single programs and for banks containing individual programs the converter
+    /// gets called once for every program, with the program's name as the first argument. Chunk
+    /// banks are passed to the converter as a single [`Vst2PresetData::Chunk`] with an empty name,
+    /// so the plugin will need to split those up itself. Programs the converter returns `None` for
+    /// are skipped.
+    pub fn convert<F>(&self, mut converter: F) -> Vec<(String, PluginState)>
+    where
+        F: FnMut(&str, &Vst2PresetData) -> Option<PluginState>,
+    {
+        match self {
+            Vst2Preset::Program(program) => program
+                .convert(&mut converter)
+                .map(|state| vec![(program.name.clone(), state)])
+                .unwrap_or_default(),
+            Vst2Preset::Bank(Vst2Bank {
+                data: Vst2BankData::Programs(programs),
+                ..
+            }) => programs
+                .iter()
+                .filter_map(|program| {
+                    program
+                        .convert(&mut converter)
+                        .map(|state| (program.name.clone(), state))
+                })
+                .collect(),
+            Vst2Preset::Bank(Vst2Bank {
+                data: Vst2BankData::Chunk(chunk),
+                ..
+            }) => converter("", &Vst2PresetData::Chunk(chunk.clone()))
+                .map(|state| vec![(String::new(), state)])
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl Vst2Program {
+    /// Convert this program to a [`PluginState`] object using a plugin-specific converter. See
+    /// [`Vst2Preset::convert()`].
+    pub fn convert<F>(&self, converter: F) -> Option<PluginState>
+    where
+        F: FnOnce(&str, &Vst2PresetData) -> Option<PluginState>,
+    {
+        converter(&self.name, &self.data)
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self> {
+        let magic = reader.read_header()?;
+        let _format_version = reader.read_i32()?;
+        let plugin_id = reader.read_magic()?;
+        let plugin_version = reader.read_i32()?;
+        let num_params = reader.read_count()?;
+        let name = reader.read_string(PROGRAM_NAME_SIZE)?;
+
+        let data = if &magic == PROGRAM_PARAMS_MAGIC {
+            // The count comes from the file, so it can't be trusted for preallocating memory
+            let mut params = Vec::with_capacity(num_params.min(reader.remaining() / 4));
+            for _ in 0..num_params {
+                params.push(reader.read_f32()?);
+            }
+
+            Vst2PresetData::Params(params)
+        } else {
+            let chunk_size = reader.read_count()?;
+            Vst2PresetData::Chunk(reader.read_bytes(chunk_size)?.to_vec())
+        };
+
+        Ok(Self {
+            plugin_id,
+            plugin_version,
+            name,
+            data,
+        })
+    }
+}
+
+impl Vst2Bank {
+    fn read(reader: &mut Reader) -> Result<Self> {
+        let magic = reader.read_header()?;
+        let format_version = reader.read_i32()?;
+        let plugin_id = reader.read_magic()?;
+        let plugin_version = reader.read_i32()?;
+        let num_programs = reader.read_count()?;
+
+        let reserved = reader.read_bytes(BANK_RESERVED_SIZE)?;
+        let current_program = if format_version >= 2 {
+            Some(i32::from_be_bytes(reserved[..4].try_into().unwrap()))
+        } else {
+            None
+        };
+
+        let data = if &magic == BANK_PROGRAMS_MAGIC {
+            let mut programs =
+                Vec::with_capacity(num_programs.min(reader.remaining() / PROGRAM_HEADER_SIZE));
+            for _ in 0..num_programs {
+                programs.push(
+                    Vst2Program::read(reader)
+                        .with_context(|| format!("Could not read program {}", programs.len()))?,
+                );
+            }
+
+            Vst2BankData::Programs(programs)
+        } else {
+            let chunk_size = reader.read_count()?;
+            Vst2BankData::Chunk(reader.read_bytes(chunk_size)?.to_vec())
+        };
+
+        Ok(Self {
+            plugin_id,
+            plugin_version,
+            current_program,
+            num_programs,
+            data,
+        })
+    }
+}
+
+/// A simple big-endian cursor over the file's bytes.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    /// The number of bytes left to read.
+    fn remaining(&self) -> usize {
+        self.bytes.len()
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        anyhow::ensure!(
+            self.bytes.len() >= len,
+            "Unexpected end of file, tried to read {len} bytes but only {} are left",
+            self.bytes.len()
+        );
+
+        let (result, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+
+        Ok(result)
+    }
+
+    fn read_magic(&mut self) -> Result<[u8; 4]> {
+        Ok(self.read_bytes(4)?.try_into().unwrap())
+    }
+
+    fn read_i32(&mut self) -> Result<i32> {
+        Ok(i32::from_be_bytes(self.read_magic()?))
+    }
+
+    fn read_f32(&mut self) -> Result<f32> {
+        Ok(f32::from_be_bytes(self.read_magic()?))
+    }
+
+    /// Read a count or a size. Negative values are treated as errors.
+    fn read_count(&mut self) -> Result<usize> {
+        let count = self.read_i32()?;
+        usize::try_from(count).with_context(|| format!("Invalid count or size {count}"))
+    }
+
+    /// Read a fixed size null-terminated string.
+    fn read_string(&mut self, size: usize) -> Result<String> {
+        let bytes = self.read_bytes(size)?;
+        let len = bytes.iter().position(|&c| c == 0).unwrap_or(size);
+
+        Ok(String::from_utf8_lossy(&bytes[..len]).into_owned())
+    }
+
+    /// Read the `CcnK` magic, the byte size, and the actual format's magic. Returns the latter.
+    fn read_header(&mut self) -> Result<[u8; 4]> {
+        let chunk_magic = self.read_magic()?;
+        anyhow::ensure!(
+            &chunk_magic == CHUNK_MAGIC,
+            "Not a VST2 preset file, expected the file to start with 'CcnK'"
+        );
+        let _byte_size = self.read_i32()?;
+
+        self.read_magic()
+    }
+
+    /// Check which type of file this is without advancing the reader.
+    fn peek_fx_magic(&self) -> Result<[u8; 4]> {
+        Reader::new(self.bytes).read_header()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build an FXP file's bytes. If `chunk` is set then this creates a chunk program, otherwise
+    /// the parameters are stored directly.
+    fn make_fxp(name: &str, params: &[f32], chunk: Option<&[u8]>) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(match chunk {
+            Some(_) => PROGRAM_CHUNK_MAGIC,
+            None => PROGRAM_PARAMS_MAGIC,
+        });
+        body.extend_from_slice(&1i32.to_be_bytes());
+        body.extend_from_slice(b"Nih!");
+        body.extend_from_slice(&1000i32.to_be_bytes());
+        body.extend_from_slice(&(params.len() as i32).to_be_bytes());
+        let mut name_bytes = [0u8; PROGRAM_NAME_SIZE];
+        name_bytes[..name.len()].copy_from_slice(name.as_bytes());
+        body.extend_from_slice(&name_bytes);
+        match chunk {
+            Some(chunk) => {
+                body.extend_from_slice(&(chunk.len() as i32).to_be_bytes());
+                body.extend_from_slice(chunk);
+            }
+            None => {
+                for param in params {
+                    body.extend_from_slice(&param.to_be_bytes());
+                }
+            }
+        }
+
+        let mut file = Vec::new();
+        file.extend_from_slice(CHUNK_MAGIC);
+        file.extend_from_slice(&(body.len() as i32).to_be_bytes());
+        file.extend_from_slice(&body);
+
+        file
+    }
+
+    #[test]
+    fn parse_params_program() {
+        let bytes = make_fxp("Init", &[0.0, 0.5, 1.0], None);
+        let preset = Vst2Preset::parse(&bytes, Some(*b"Nih!")).unwrap();
+
+        assert_eq!(
+            preset,
+            Vst2Preset::Program(Vst2Program {
+                plugin_id: *b"Nih!",
+                plugin_version: 1000,
+                name: String::from("Init"),
+                data: Vst2PresetData::Params(vec![0.0, 0.5, 1.0]),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_chunk_program() {
+        let bytes = make_fxp("Chunky", &[], Some(b"opaque data"));
+        let preset = Vst2Preset::parse(&bytes, None).unwrap();
+
+        match preset {
+            Vst2Preset::Program(program) => {
+                assert_eq!(program.name, "Chunky");
+                assert_eq!(program.data, Vst2PresetData::Chunk(b"opaque data".to_vec()));
+            }
+            Vst2Preset::Bank(_) => panic!("Expected a program"),
+        }
+    }
+
+    #[test]
+    fn parse_program_bank() {
+        let programs = [
+            make_fxp("First", &[0.25], None),
+            make_fxp("Second", &[0.75], None),
+        ];
+
+        let mut body = Vec::new();
+        body.extend_from_slice(BANK_PROGRAMS_MAGIC);
+        body.extend_from_slice(&2i32.to_be_bytes());
+        body.extend_from_slice(b"Nih!");
+        body.extend_from_slice(&1000i32.to_be_bytes());
+        body.extend_from_slice(&2i32.to_be_bytes());
+        let mut reserved = [0u8; BANK_RESERVED_SIZE];
+        reserved[..4].copy_from_slice(&1i32.to_be_bytes());
+        body.extend_from_slice(&reserved);
+        for program in &programs {
+            body.extend_from_slice(program);
+        }
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(CHUNK_MAGIC);
+        bytes.extend_from_slice(&(body.len() as i32).to_be_bytes());
+        bytes.extend_from_slice(&body);
+
+        let preset = Vst2Preset::parse(&bytes, None).unwrap();
+        match &preset {
+            Vst2Preset::Bank(bank) => {
+                assert_eq!(bank.current_program, Some(1));
+                assert_eq!(bank.num_programs, 2);
+            }
+            Vst2Preset::Program(_) => panic!("Expected a bank"),
+        }
+
+        let names: Vec<_> = preset
+            .convert(|_, _| {
+                Some(PluginState {
+                    version: String::new(),
+                    params: Default::default(),
+                    fields: Default::default(),
+                    midi_mappings: None,
+                })
+            })
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, ["First", "Second"]);
+    }
+
+    #[test]
+    fn reject_wrong_plugin_id() {
+        let bytes = make_fxp("Init", &[0.5], None);
+        assert!(Vst2Preset::parse(&bytes, Some(*b"Nope")).is_err());
+    }
+
+    #[test]
+    fn reject_truncated_file() {
+        let bytes = make_fxp("Init", &[0.5, 0.5], None);
+        assert!(Vst2Preset::parse(&bytes[..bytes.len() - 2], None).is_err());
+    }
+
+    #[test]
+    fn reject_huge_param_count() {
+        // The parameter count is at a fixed offset after the `CcnK` header
+        let mut bytes = make_fxp("Init", &[0.5], None);
+        bytes[24..28].copy_from_slice(&i32::MAX.to_be_bytes());
+        assert!(Vst2Preset::parse(&bytes, None).is_err());
+    }
+}
@@ -48,6 +48,152 @@ pub fn hann_in_place(window: &mut [f32]) {
     }
 }
 
+/// A Hamming window function.
+///
+/// <https://en.wikipedia.org/wiki/Window_function#Hann_and_Hamming_windows>
+pub fn hamming(size: usize) -> Vec<f32> {
+    let mut window = vec![0.0; size];
+    hamming_in_place(&mut window);
+
+    window
+}
+
+/// The same as [`hamming()`], but filling an existing slice instead.
+pub fn hamming_in_place(window: &mut [f32]) {
+    generalized_cosine_in_place(window, &[0.54, 0.46]);
+}
+
+/// A four term Blackman-Harris window function. This has much lower side lobes than the regular
+/// [`blackman()`] window at the cost of a wider main lobe.
+///
+/// <https://en.wikipedia.org/wiki/Window_function#Blackman%E2%80%93Harris_window>
+pub fn blackman_harris(size: usize) -> Vec<f32> {
+    let mut window = vec![0.0; size];
+    blackman_harris_in_place(&mut window);
+
+    window
+}
+
+/// The same as [`blackman_harris()`], but filling an existing slice instead.
+pub fn blackman_harris_in_place(window: &mut [f32]) {
+    generalized_cosine_in_place(window, &[0.35875, 0.48829, 0.14128, 0.01168]);
+}
+
+/// A five term flat-top window function. The main lobe is very wide and flat, which makes this
+/// useful for accurately measuring the amplitude of sinusoids.
+///
+/// <https://en.wikipedia.org/wiki/Window_function#Flat_top_window>
+pub fn flat_top(size: usize) -> Vec<f32> {
+    let mut window = vec![0.0; size];
+    flat_top_in_place(&mut window);
+
+    window
+}
+
+/// The same as [`flat_top()`], but filling an existing slice instead.
+pub fn flat_top_in_place(window: &mut [f32]) {
+    generalized_cosine_in_place(
+        window,
+        &[
+            0.215_578_95,
+            0.416_631_58,
+            0.277_263_16,
+            0.083_578_95,
+            0.006_947_368,
+        ],
+    );
+}
+
+/// Compute the sum of `window_function` overlapped with itself every `hop_size` samples, and
+/// write the result for a single hop to `sum`. If all values in `sum` are (roughly) the same, then
+/// the window satisfies the constant overlap-add (COLA) constraint for that hop size. This does
+/// not allocate.
+///
+/// # Panics
+///
+/// Panics if `hop_size` is zero or if `sum` does not contain exactly `hop_size` samples.
+pub fn overlap_add_sum(window_function: &[f32], hop_size: usize, sum: &mut [f32]) {
+    assert_ne!(hop_size, 0);
+    assert_eq!(sum.len(), hop_size);
+
+    for (offset, sample) in sum.iter_mut().enumerate() {
+        *sample = window_function.iter().skip(offset).step_by(hop_size).sum();
+    }
+}
+
+/// The same as [`overlap_add_sum()`], but summing the squared window instead. This is the
+/// constraint that needs to hold when the same window is used for both analysis and synthesis,
+/// like in [`StftHelper::process_overlap_add()`][super::StftHelper::process_overlap_add()].
+///
+/// # Panics
+///
+/// Panics if `hop_size` is zero or if `sum` does not contain exactly `hop_size` samples.
+pub fn overlap_add_squared_sum(window_function: &[f32], hop_size: usize, sum: &mut [f32]) {
+    assert_ne!(hop_size, 0);
+    assert_eq!(sum.len(), hop_size);
+
+    for (offset, sample) in sum.iter_mut().enumerate() {
+        *sample = window_function
+            .iter()
+            .skip(offset)
+            .step_by(hop_size)
+            .map(|x| x * x)
+            .sum();
+    }
+}
+
+/// Check whether `window_function` satisfies the constant overlap-add constraint when overlapped
+/// every `hop_size` samples. The window's overlapped sum may not deviate by more than `tolerance`
+/// relative to its mean. Set `squared` to check the squared window instead, as described in
+/// [`overlap_add_squared_sum()`]. This does not allocate.
+///
+/// # Panics
+///
+/// Panics if `hop_size` is zero.
+pub fn is_cola(window_function: &[f32], hop_size: usize, squared: bool, tolerance: f32) -> bool {
+    assert_ne!(hop_size, 0);
+
+    let (mut min, mut max, mut total) = (f32::INFINITY, f32::NEG_INFINITY, 0.0);
+    for offset in 0..hop_size {
+        let sum: f32 = window_function
+            .iter()
+            .skip(offset)
+            .step_by(hop_size)
+            .map(|x| if squared { x * x } else { *x })
+            .sum();
+
+        min = min.min(sum);
+        max = max.max(sum);
+        total += sum;
+    }
+
+    let mean = total / hop_size as f32;
+    mean > 0.0 && (max - min) / mean <= tolerance
+}
+
+/// Fill `window` with a generalized cosine window with the form `a0 - a1 * cos(x) + a2 * cos(2x) -
+/// a3 * cos(3x) + ...`.
+fn generalized_cosine_in_place(window: &mut [f32], coefficients: &[f32]) {
+    let size = window.len();
+
+    let scale = (size as f32 - 1.0).recip() * f32::consts::TAU;
+    for (i, sample) in window.iter_mut().enumerate() {
+        let x = i as f32 * scale;
+        *sample = coefficients
+            .iter()
+            .enumerate()
+            .map(|(term, coefficient)| {
+                let value = coefficient * (term as f32 * x).cos();
+                if term % 2 == 0 {
+                    value
+                } else {
+                    -value
+                }
+            })
+            .sum();
+    }
+}
+
 /// Multiply a buffer with a window function.
 #[inline]
 pub fn multiply_with_window(buffer: &mut [f32], window_function: &[f32]) {
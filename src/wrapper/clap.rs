@@ -3,13 +3,17 @@ mod util;
 
 mod context;
 mod descriptor;
+mod ext;
 pub mod features;
+mod preset_discovery;
 mod wrapper;
 
 /// Re-export for the macro
 pub use self::descriptor::PluginDescriptor;
+pub use self::preset_discovery::PresetDiscoveryFactory;
 pub use self::wrapper::Wrapper;
 pub use clap_sys::entry::clap_plugin_entry;
+pub use clap_sys::factory::draft::preset_discovery::CLAP_PRESET_DISCOVERY_FACTORY_ID;
 pub use clap_sys::factory::plugin_factory::{clap_plugin_factory, CLAP_PLUGIN_FACTORY_ID};
 pub use clap_sys::host::clap_host;
 pub use clap_sys::plugin::{clap_plugin, clap_plugin_descriptor};
@@ -27,8 +31,8 @@ macro_rules! nih_export_clap {
         mod clap {
             use $crate::prelude::nih_debug_assert_eq;
             use $crate::wrapper::setup_logger;
-            use $crate::wrapper::clap::{PluginDescriptor, Wrapper};
-            use $crate::wrapper::clap::{CLAP_PLUGIN_FACTORY_ID, clap_host, clap_plugin, clap_plugin_descriptor, clap_plugin_factory};
+            use $crate::wrapper::clap::{PluginDescriptor, PresetDiscoveryFactory, Wrapper};
+            use $crate::wrapper::clap::{CLAP_PLUGIN_FACTORY_ID, CLAP_PRESET_DISCOVERY_FACTORY_ID, clap_host, clap_plugin, clap_plugin_descriptor, clap_plugin_factory};
             use ::std::collections::HashSet;
             use ::std::ffi::{CStr, c_void};
             use ::std::os::raw::c_char;
@@ -38,12 +42,21 @@ macro_rules! nih_export_clap {
             // if the types are not public because this is a child module.
             use super::*;
 
+            // Typos and missing main categories in the plugins' features result in compile errors
+            $(const _: () = $crate::wrapper::clap::features::ClapFeature::assert_valid(
+                <$plugin_ty as $crate::prelude::ClapPlugin>::CLAP_FEATURES,
+            );)+
+
             const CLAP_PLUGIN_FACTORY: clap_plugin_factory = clap_plugin_factory {
                 get_plugin_count: Some(get_plugin_count),
                 get_plugin_descriptor: Some(get_plugin_descriptor),
                 create_plugin: Some(create_plugin),
             };
 
+            // Exposes the plugins' factory presets to the host, if any of the plugins has them
+            static CLAP_PRESET_DISCOVERY_FACTORY: PresetDiscoveryFactory =
+                PresetDiscoveryFactory::new(|| plugin_descriptors());
+
             // Sneaky way to get the number of expanded elements
             const PLUGIN_COUNT: usize = [$(stringify!($plugin_ty)),+].len();
 
@@ -122,8 +135,17 @@ macro_rules! nih_export_clap {
             pub extern "C" fn deinit() {}
 
             pub extern "C" fn get_factory(factory_id: *const c_char) -> *const c_void {
-                if !factory_id.is_null() && unsafe { CStr::from_ptr(factory_id) } == CLAP_PLUGIN_FACTORY_ID {
+                if factory_id.is_null() {
+                    return ::std::ptr::null();
+                }
+
+                let factory_id = unsafe { CStr::from_ptr(factory_id) };
+                if factory_id == CLAP_PLUGIN_FACTORY_ID {
                     &CLAP_PLUGIN_FACTORY as *const _ as *const c_void
+                } else if factory_id == CLAP_PRESET_DISCOVERY_FACTORY_ID
+                    && CLAP_PRESET_DISCOVERY_FACTORY.has_presets()
+                {
+                    &CLAP_PRESET_DISCOVERY_FACTORY as *const _ as *const c_void
                 } else {
                     ::std::ptr::null()
                 }
@@ -6,13 +6,16 @@ use clap_sys::id::{clap_id, CLAP_INVALID_ID};
 use clap_sys::string_sizes::CLAP_NAME_SIZE;
 use std::cell::Cell;
 use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use super::wrapper::{OutputParamEvent, Task, Wrapper};
 use crate::event_loop::EventLoop;
+use crate::midi::learn::MidiLearn;
 use crate::prelude::{
-    ClapPlugin, GuiContext, InitContext, ParamPtr, PluginApi, PluginNoteEvent, ProcessContext,
-    RemoteControlsContext, RemoteControlsPage, RemoteControlsSection, Transport,
+    BufferConfig, ClapPlugin, FactoryPreset, GuiContext, HostInfo, InitContext, ParamPtr,
+    PluginApi, PluginNoteEvent, ProcessContext, ProcessTimings, RemoteControlsContext,
+    RemoteControlsPage, RemoteControlsSection, StateSlot, TailLength, Transport, TransportSnapshot,
 };
 use crate::wrapper::util::strlcpy;
 
@@ -78,6 +81,10 @@ impl<P: ClapPlugin> InitContext<P> for WrapperInitContext<'_, P> {
         PluginApi::Clap
     }
 
+    fn host_info(&self) -> HostInfo {
+        self.wrapper.host_info()
+    }
+
     fn execute(&self, task: P::BackgroundTask) {
         (self.wrapper.task_executor.lock())(task);
     }
@@ -120,13 +127,72 @@ impl<P: ClapPlugin> ProcessContext<P> for WrapperProcessContext<'_, P> {
         self.output_events_guard.push_back(event);
     }
 
+    #[inline]
+    fn next_gui_message(&mut self) -> Option<P::GuiMessage> {
+        self.wrapper.gui_messages.pop()
+    }
+
+    fn send_to_editor(&self, event: P::EditorEvent) {
+        self.wrapper.editor_events.force_push(event);
+    }
+
     fn set_latency_samples(&self, samples: u32) {
         self.wrapper.set_latency_samples(samples)
     }
 
+    fn execute_parallel(&self, num_tasks: u32, task: &(dyn Fn(u32) + Sync)) {
+        self.wrapper.execute_parallel(num_tasks, task);
+    }
+
     fn set_current_voice_capacity(&self, capacity: u32) {
         self.wrapper.set_current_voice_capacity(capacity)
     }
+
+    fn set_gain_reduction(&self, db: f32) {
+        self.wrapper.gain_reduction_db.store(db, Ordering::Relaxed);
+    }
+
+    // These events are written to the host's output event queue at the end of the current block
+    unsafe fn raw_begin_set_parameter(&mut self, param: ParamPtr) {
+        match self.wrapper.param_ptr_to_hash.get(&param) {
+            Some(hash) => {
+                let event = OutputParamEvent::BeginGesture { param_hash: *hash };
+                let success = self.wrapper.queue_parameter_event_from_process(event);
+                nih_debug_assert!(success, "Parameter output event queue was full");
+            }
+            None => nih_debug_assert_failure!("Unknown parameter: {:?}", param),
+        }
+    }
+
+    unsafe fn raw_set_parameter_normalized(&mut self, param: ParamPtr, normalized: f32) {
+        let normalized = self
+            .wrapper
+            .param_output_limiter
+            .borrow_mut()
+            .set(param, normalized);
+        if let Some(normalized) = normalized {
+            self.wrapper
+                .queue_parameter_value_from_process(param, normalized);
+        }
+    }
+
+    unsafe fn raw_end_set_parameter(&mut self, param: ParamPtr) {
+        // A value held back by the rate limiting still needs to be part of the gesture
+        let pending_value = self.wrapper.param_output_limiter.borrow_mut().end(param);
+        if let Some(normalized) = pending_value {
+            self.wrapper
+                .queue_parameter_value_from_process(param, normalized);
+        }
+
+        match self.wrapper.param_ptr_to_hash.get(&param) {
+            Some(hash) => {
+                let event = OutputParamEvent::EndGesture { param_hash: *hash };
+                let success = self.wrapper.queue_parameter_event_from_process(event);
+                nih_debug_assert!(success, "Parameter output event queue was full");
+            }
+            None => nih_debug_assert_failure!("Unknown parameter: {:?}", param),
+        }
+    }
 }
 
 impl<P: ClapPlugin> GuiContext for WrapperGuiContext<P> {
@@ -138,6 +204,10 @@ impl<P: ClapPlugin> GuiContext for WrapperGuiContext<P> {
         self.wrapper.request_resize()
     }
 
+    fn end_undo_block(&self) {
+        self.wrapper.mark_state_dirty();
+    }
+
     // All of these functions are supposed to be called from the main thread, so we'll put some
     // trust in the caller and assume that this is indeed the case
     unsafe fn raw_begin_set_parameter(&self, param: ParamPtr) {
@@ -240,6 +310,54 @@ impl<P: ClapPlugin> GuiContext for WrapperGuiContext<P> {
     fn set_state(&self, state: crate::wrapper::state::PluginState) {
         self.wrapper.set_state_object_from_gui(state)
     }
+
+    fn factory_presets(&self) -> &'static [FactoryPreset] {
+        P::FACTORY_PRESETS
+    }
+
+    fn active_state_slot(&self) -> StateSlot {
+        self.wrapper.state_slots.active_slot()
+    }
+
+    fn switch_state_slot(&self, slot: StateSlot) {
+        let new_state = self
+            .wrapper
+            .state_slots
+            .switch(slot, || self.wrapper.get_state_object());
+        if let Some(new_state) = new_state {
+            self.wrapper.set_state_object_from_gui(new_state);
+        }
+    }
+
+    fn copy_state_to_other_slot(&self) {
+        self.wrapper
+            .state_slots
+            .copy_to_other_slot(self.wrapper.get_state_object());
+    }
+
+    fn midi_learn(&self) -> &MidiLearn {
+        &self.wrapper.midi_learn
+    }
+
+    fn buffer_config(&self) -> Option<BufferConfig> {
+        self.wrapper.current_buffer_config.load()
+    }
+
+    fn latency_samples(&self) -> u32 {
+        self.wrapper.current_latency.load(Ordering::SeqCst) + P::FIXED_BLOCK_SIZE.unwrap_or(0)
+    }
+
+    fn tail(&self) -> TailLength {
+        TailLength::from_process_status(self.wrapper.last_process_status.load())
+    }
+
+    fn transport(&self) -> Option<TransportSnapshot> {
+        self.wrapper.last_transport.load()
+    }
+
+    fn process_timings(&self) -> Option<ProcessTimings> {
+        self.wrapper.process_timings.statistics()
+    }
 }
 
 /// A remote control section. The plugin can fill this with information for one or more pages.
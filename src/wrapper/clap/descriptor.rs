@@ -3,7 +3,7 @@ use clap_sys::version::CLAP_VERSION;
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
 
-use crate::prelude::ClapPlugin;
+use crate::prelude::{ClapPlugin, FactoryPreset};
 
 /// A static descriptor for a plugin. This is used in both the descriptor and on the plugin object
 /// itself.
@@ -21,6 +21,8 @@ pub struct PluginDescriptor {
     clap_support_url: Option<CString>,
     clap_description: Option<CString>,
     clap_features: Vec<CString>,
+    /// The plugin's factory presets. These are exposed through the preset discovery factory.
+    factory_presets: &'static [FactoryPreset],
 
     /// The contains pointers to the strings in `clap_features`.
     clap_features_ptrs: Vec<*const c_char>,
@@ -54,6 +56,7 @@ impl PluginDescriptor {
                 .map(|feat| feat.as_str())
                 .map(|s| CString::new(s).expect("`CLAP_FEATURES` contained null bytes"))
                 .collect(),
+            factory_presets: P::FACTORY_PRESETS,
 
             // These need to be initialized later as they contain pointers to the fields in this
             // descriptor
@@ -107,4 +110,8 @@ impl PluginDescriptor {
     pub fn clap_id(&self) -> &CStr {
         self.clap_id.as_c_str()
     }
+
+    pub fn factory_presets(&self) -> &'static [FactoryPreset] {
+        self.factory_presets
+    }
 }
@@ -0,0 +1,9 @@
+//! Bindings for CLAP extensions that are not yet part of the `clap-sys` version NIH-plug depends
+//! on. These mirror the layout of `clap_sys::ext` so they can be replaced with the upstream
+//! bindings once those are available.
+
+#![allow(dead_code, non_camel_case_types)]
+
+pub mod ambisonic;
+pub mod gain_adjustment_metering;
+pub mod surround;
@@ -0,0 +1,49 @@
+//! Bindings for the `clap.ambisonic` extension.
+
+use clap_sys::plugin::clap_plugin;
+use std::ffi::CStr;
+
+pub const CLAP_EXT_AMBISONIC: &CStr =
+    unsafe { CStr::from_bytes_with_nul_unchecked(b"clap.ambisonic/3\0") };
+
+/// The port type for ambisonic ports.
+pub const CLAP_PORT_AMBISONIC: &CStr =
+    unsafe { CStr::from_bytes_with_nul_unchecked(b"ambisonic\0") };
+
+pub const CLAP_AMBISONIC_ORDERING_FUMA: u32 = 0;
+pub const CLAP_AMBISONIC_ORDERING_ACN: u32 = 1;
+
+pub const CLAP_AMBISONIC_NORMALIZATION_MAXN: u32 = 0;
+pub const CLAP_AMBISONIC_NORMALIZATION_SN3D: u32 = 1;
+pub const CLAP_AMBISONIC_NORMALIZATION_N3D: u32 = 2;
+pub const CLAP_AMBISONIC_NORMALIZATION_SN2D: u32 = 3;
+pub const CLAP_AMBISONIC_NORMALIZATION_N2D: u32 = 4;
+
+/// The channel ordering and normalization used by an ambisonic port.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct clap_ambisonic_config {
+    pub ordering: u32,
+    pub normalization: u32,
+}
+
+/// Describes the channel ordering and normalization used by the plugin's ambisonic ports. These
+/// functions should only be called from the main thread.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct clap_plugin_ambisonic {
+    pub is_config_supported: Option<
+        unsafe extern "C" fn(
+            plugin: *const clap_plugin,
+            config: *const clap_ambisonic_config,
+        ) -> bool,
+    >,
+    pub get_config: Option<
+        unsafe extern "C" fn(
+            plugin: *const clap_plugin,
+            is_input: bool,
+            port_index: u32,
+            config: *mut clap_ambisonic_config,
+        ) -> bool,
+    >,
+}
@@ -0,0 +1,16 @@
+//! Bindings for the draft `clap.gain-adjustment-metering` extension.
+
+use clap_sys::plugin::clap_plugin;
+use std::ffi::CStr;
+
+pub const CLAP_EXT_GAIN_ADJUSTMENT_METERING: &CStr =
+    unsafe { CStr::from_bytes_with_nul_unchecked(b"clap.gain-adjustment-metering/0\0") };
+
+/// Lets the host display the gain adjustment a compressor or limiter currently applies, in
+/// decibels. Negative values mean the signal is being attenuated. This should only be called
+/// from the audio thread.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct clap_plugin_gain_adjustment_metering {
+    pub get: Option<unsafe extern "C" fn(plugin: *const clap_plugin) -> f64>,
+}
@@ -0,0 +1,48 @@
+//! Bindings for the `clap.surround` extension.
+
+use clap_sys::plugin::clap_plugin;
+use std::ffi::CStr;
+
+pub const CLAP_EXT_SURROUND: &CStr =
+    unsafe { CStr::from_bytes_with_nul_unchecked(b"clap.surround/4\0") };
+
+/// The port type for surround ports.
+pub const CLAP_PORT_SURROUND: &CStr = unsafe { CStr::from_bytes_with_nul_unchecked(b"surround\0") };
+
+pub const CLAP_SURROUND_FL: u8 = 0;
+pub const CLAP_SURROUND_FR: u8 = 1;
+pub const CLAP_SURROUND_FC: u8 = 2;
+pub const CLAP_SURROUND_LFE: u8 = 3;
+pub const CLAP_SURROUND_BL: u8 = 4;
+pub const CLAP_SURROUND_BR: u8 = 5;
+pub const CLAP_SURROUND_FLC: u8 = 6;
+pub const CLAP_SURROUND_FRC: u8 = 7;
+pub const CLAP_SURROUND_BC: u8 = 8;
+pub const CLAP_SURROUND_SL: u8 = 9;
+pub const CLAP_SURROUND_SR: u8 = 10;
+pub const CLAP_SURROUND_TC: u8 = 11;
+pub const CLAP_SURROUND_TFL: u8 = 12;
+pub const CLAP_SURROUND_TFC: u8 = 13;
+pub const CLAP_SURROUND_TFR: u8 = 14;
+pub const CLAP_SURROUND_TBL: u8 = 15;
+pub const CLAP_SURROUND_TBC: u8 = 16;
+pub const CLAP_SURROUND_TBR: u8 = 17;
+
+/// Describes the speaker arrangement of the plugin's surround ports. A channel mask has bit `n`
+/// set for every `CLAP_SURROUND_*` speaker `n` in the arrangement. These functions should only be
+/// called from the main thread.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct clap_plugin_surround {
+    pub is_channel_mask_supported:
+        Option<unsafe extern "C" fn(plugin: *const clap_plugin, channel_mask: u64) -> bool>,
+    pub get_channel_map: Option<
+        unsafe extern "C" fn(
+            plugin: *const clap_plugin,
+            is_input: bool,
+            port_index: u32,
+            channel_map: *mut u8,
+            channel_map_capacity: u32,
+        ) -> u32,
+    >,
+}
@@ -11,8 +11,8 @@ pub enum ClapFeature {
     AudioEffect,
     NoteDetector,
     NoteEffect,
-    // These are optional
     Analyzer,
+    // These are optional
     Synthesizer,
     Sampler,
     Drum,
@@ -53,7 +53,7 @@ pub enum ClapFeature {
 }
 
 impl ClapFeature {
-    pub fn as_str(&self) -> &'static str {
+    pub const fn as_str(&self) -> &'static str {
         match self {
             ClapFeature::Instrument => "instrument",
             ClapFeature::AudioEffect => "audio-effect",
@@ -94,19 +94,70 @@ impl ClapFeature {
             ClapFeature::Stereo => "stereo",
             ClapFeature::Surround => "surround",
             ClapFeature::Ambisonic => "ambisonic",
-            ClapFeature::Custom(s) => {
-                // Custom features must be prefixed with a namespace. We'll use `.split(':').all()`
-                // here instead of `.split_once()` in case the user for whatever reason uses more
-                // than one colon (which the docs don't say anything about, but uh yeah).
-                nih_debug_assert!(
-                    s.contains(':') && s.split(':').all(|x| !x.is_empty()),
-                    "'{s}' is not a valid feature, custom features must be namespaced (e.g. \
-                     'nih:{s}')",
-                    s = s
-                );
+            // These are checked in `assert_valid()`
+            ClapFeature::Custom(s) => s,
+        }
+    }
+
+    /// Check a plugin's features. This is called in a constant context by `nih_export_clap!()`, so
+    /// mistakes result in compile errors instead of the plugin silently missing from the host's
+    /// plugin browser. This fails if:
+    ///
+    /// - None of the main categories (instrument, audio effect, note detector, note effect, or
+    ///   analyzer) are present.
+    /// - A custom feature is not prefixed with a namespace in the format `namespace:feature_name`.
+    ///   Predefined features like `audio-effect` should use their own variant instead.
+    pub const fn assert_valid(features: &[ClapFeature]) {
+        let mut has_main_category = false;
+        let mut i = 0;
+        while i < features.len() {
+            match features[i] {
+                ClapFeature::Instrument
+                | ClapFeature::AudioEffect
+                | ClapFeature::NoteDetector
+                | ClapFeature::NoteEffect
+                | ClapFeature::Analyzer => has_main_category = true,
+                ClapFeature::Custom(s) => assert!(
+                    is_namespaced(s),
+                    "Custom CLAP features must be namespaced (e.g. 'nih:feature-name')"
+                ),
+                _ => (),
+            }
+
+            i += 1;
+        }
+
+        assert!(
+            has_main_category,
+            "'CLAP_FEATURES' needs to contain at least one of 'ClapFeature::Instrument', \
+             'ClapFeature::AudioEffect', 'ClapFeature::NoteDetector', 'ClapFeature::NoteEffect', \
+             or 'ClapFeature::Analyzer'"
+        );
+    }
+}
 
-                s
+/// Whether a custom feature is prefixed with a namespace. This checks that the string contains at
+/// least one colon and that none of the colon-separated parts are empty, in case the user for
+/// whatever reason uses more than one colon (which the docs don't say anything about, but uh yeah).
+const fn is_namespaced(feature: &str) -> bool {
+    let bytes = feature.as_bytes();
+    let mut has_colon = false;
+    let mut part_len = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b':' {
+            if part_len == 0 {
+                return false;
             }
+
+            has_colon = true;
+            part_len = 0;
+        } else {
+            part_len += 1;
         }
+
+        i += 1;
     }
+
+    has_colon && part_len > 0
 }
@@ -0,0 +1,284 @@
+//! A CLAP preset discovery factory that exposes the plugins' [`FactoryPreset`]s to the host.
+
+use clap_sys::factory::draft::preset_discovery::{
+    clap_plugin_id, clap_preset_discovery_factory, clap_preset_discovery_indexer,
+    clap_preset_discovery_location, clap_preset_discovery_metadata_receiver,
+    clap_preset_discovery_provider, clap_preset_discovery_provider_descriptor,
+    CLAP_PRESET_DISCOVERY_IS_FACTORY_CONTENT, CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN,
+};
+use clap_sys::version::CLAP_VERSION;
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::c_char;
+use std::sync::OnceLock;
+
+use super::descriptor::PluginDescriptor;
+use crate::prelude::FactoryPreset;
+
+/// The null terminated ABI name used in a `clap_plugin_id` to refer to a CLAP plugin.
+const CLAP_PLUGIN_ABI: &[u8] = b"clap\0";
+
+/// A `clap_preset_discovery_factory` with one preset provider for every exported plugin that has
+/// factory presets. This is exposed by `nih_export_clap!()`.
+#[doc(hidden)]
+#[repr(C)]
+pub struct PresetDiscoveryFactory {
+    // Keep the vtable as the first field so we can do a simple pointer cast from the factory
+    // pointer passed to the callbacks
+    clap_preset_discovery_factory: clap_preset_discovery_factory,
+
+    /// Returns the descriptors for all plugins exported by the library.
+    plugin_descriptors: fn() -> &'static [PluginDescriptor],
+    /// The providers for the plugins with factory presets. Initialized on first use.
+    providers: OnceLock<Vec<ProviderDescriptor>>,
+}
+
+/// A static descriptor for a single plugin's preset provider.
+struct ProviderDescriptor {
+    id: CString,
+    name: CString,
+    vendor: CString,
+    /// The ID of the plugin these presets belong to.
+    plugin_id: CString,
+    factory_presets: &'static [FactoryPreset],
+
+    /// Contains pointers to the strings above. This is fine without pinning since the `CString`s'
+    /// data lives on the heap.
+    descriptor: clap_preset_discovery_provider_descriptor,
+}
+
+unsafe impl Send for ProviderDescriptor {}
+unsafe impl Sync for ProviderDescriptor {}
+
+/// A preset provider instance created by the host through the factory.
+#[repr(C)]
+struct PresetDiscoveryProvider {
+    clap_preset_discovery_provider: clap_preset_discovery_provider,
+
+    descriptor: &'static ProviderDescriptor,
+    indexer: *const clap_preset_discovery_indexer,
+}
+
+impl PresetDiscoveryFactory {
+    /// Create the factory. `plugin_descriptors` should return the descriptors for all plugins
+    /// exported by the library.
+    pub const fn new(plugin_descriptors: fn() -> &'static [PluginDescriptor]) -> Self {
+        Self {
+            clap_preset_discovery_factory: clap_preset_discovery_factory {
+                count: Some(Self::count),
+                get_descriptor: Some(Self::get_descriptor),
+                create: Some(Self::create),
+            },
+
+            plugin_descriptors,
+            providers: OnceLock::new(),
+        }
+    }
+
+    /// Whether any of the exported plugins has factory presets. The factory should not be exposed
+    /// to the host if this returns `false`.
+    pub fn has_presets(&self) -> bool {
+        !self.providers().is_empty()
+    }
+
+    fn providers(&self) -> &[ProviderDescriptor] {
+        self.providers.get_or_init(|| {
+            (self.plugin_descriptors)()
+                .iter()
+                .filter(|descriptor| !descriptor.factory_presets().is_empty())
+                .map(|descriptor| {
+                    let plugin_descriptor = descriptor.clap_plugin_descriptor();
+                    let (plugin_id, plugin_name, vendor) = unsafe {
+                        (
+                            CStr::from_ptr(plugin_descriptor.id),
+                            CStr::from_ptr(plugin_descriptor.name).to_string_lossy(),
+                            CStr::from_ptr(plugin_descriptor.vendor),
+                        )
+                    };
+
+                    let mut provider = ProviderDescriptor {
+                        id: CString::new(format!(
+                            "{}.factory-presets",
+                            plugin_id.to_string_lossy()
+                        ))
+                        .unwrap(),
+                        name: CString::new(format!("{plugin_name} Factory Presets")).unwrap(),
+                        vendor: vendor.to_owned(),
+                        plugin_id: plugin_id.to_owned(),
+                        factory_presets: descriptor.factory_presets(),
+
+                        descriptor: clap_preset_discovery_provider_descriptor {
+                            clap_version: CLAP_VERSION,
+                            id: std::ptr::null(),
+                            name: std::ptr::null(),
+                            vendor: std::ptr::null(),
+                        },
+                    };
+                    provider.descriptor.id = provider.id.as_ptr();
+                    provider.descriptor.name = provider.name.as_ptr();
+                    provider.descriptor.vendor = provider.vendor.as_ptr();
+
+                    provider
+                })
+                .collect()
+        })
+    }
+
+    unsafe extern "C" fn count(factory: *const clap_preset_discovery_factory) -> u32 {
+        check_null_ptr!(0, factory);
+        let this = &*(factory as *const Self);
+
+        this.providers().len() as u32
+    }
+
+    unsafe extern "C" fn get_descriptor(
+        factory: *const clap_preset_discovery_factory,
+        index: u32,
+    ) -> *const clap_preset_discovery_provider_descriptor {
+        check_null_ptr!(std::ptr::null(), factory);
+        let this = &*(factory as *const Self);
+
+        match this.providers().get(index as usize) {
+            Some(provider) => &provider.descriptor,
+            None => std::ptr::null(),
+        }
+    }
+
+    unsafe extern "C" fn create(
+        factory: *const clap_preset_discovery_factory,
+        indexer: *const clap_preset_discovery_indexer,
+        provider_id: *const c_char,
+    ) -> *const clap_preset_discovery_provider {
+        check_null_ptr!(std::ptr::null(), factory, indexer, provider_id);
+        let this = &*(factory as *const Self);
+
+        let provider_id = CStr::from_ptr(provider_id);
+        let descriptor = match this
+            .providers()
+            .iter()
+            .find(|provider| provider.id.as_c_str() == provider_id)
+        {
+            Some(descriptor) => descriptor,
+            None => {
+                nih_debug_assert_failure!("Unknown preset provider ID {:?}", provider_id);
+                return std::ptr::null();
+            }
+        };
+
+        let provider = Box::new(PresetDiscoveryProvider {
+            clap_preset_discovery_provider: clap_preset_discovery_provider {
+                desc: &descriptor.descriptor,
+                provider_data: std::ptr::null_mut(),
+                init: Some(PresetDiscoveryProvider::init),
+                destroy: Some(PresetDiscoveryProvider::destroy),
+                get_metadata: Some(PresetDiscoveryProvider::get_metadata),
+                get_extension: Some(PresetDiscoveryProvider::get_extension),
+            },
+
+            descriptor,
+            indexer,
+        });
+
+        // The pointer gets turned back into a `Box` in `PresetDiscoveryProvider::destroy()`
+        let provider = Box::into_raw(provider);
+        (*provider).clap_preset_discovery_provider.provider_data = provider as *mut c_void;
+
+        &(*provider).clap_preset_discovery_provider
+    }
+}
+
+impl PresetDiscoveryProvider {
+    unsafe extern "C" fn init(provider: *const clap_preset_discovery_provider) -> bool {
+        check_null_ptr!(false, provider, (*provider).provider_data);
+        let this = &*((*provider).provider_data as *const Self);
+
+        let indexer = &*this.indexer;
+        let declare_location = match indexer.declare_location {
+            Some(declare_location) => declare_location,
+            None => return false,
+        };
+
+        // The factory presets are stored in the plugin itself, so there's no file or directory to
+        // point the host to
+        let location = clap_preset_discovery_location {
+            flags: CLAP_PRESET_DISCOVERY_IS_FACTORY_CONTENT,
+            name: this.descriptor.name.as_ptr(),
+            kind: CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN,
+            location: std::ptr::null(),
+        };
+
+        declare_location(this.indexer, &location)
+    }
+
+    unsafe extern "C" fn destroy(provider: *const clap_preset_discovery_provider) {
+        check_null_ptr!((), provider, (*provider).provider_data);
+
+        drop(Box::from_raw((*provider).provider_data as *mut Self));
+    }
+
+    unsafe extern "C" fn get_metadata(
+        provider: *const clap_preset_discovery_provider,
+        location_kind: u32,
+        _location: *const c_char,
+        metadata_receiver: *const clap_preset_discovery_metadata_receiver,
+    ) -> bool {
+        check_null_ptr!(
+            false,
+            provider,
+            (*provider).provider_data,
+            metadata_receiver
+        );
+        let this = &*((*provider).provider_data as *const Self);
+        let receiver = &*metadata_receiver;
+
+        if location_kind != CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN {
+            nih_debug_assert_failure!(
+                "Host requested presets for an unknown location kind {}",
+                location_kind
+            );
+            return false;
+        }
+
+        let (begin_preset, add_plugin_id) = match (receiver.begin_preset, receiver.add_plugin_id) {
+            (Some(begin_preset), Some(add_plugin_id)) => (begin_preset, add_plugin_id),
+            _ => return false,
+        };
+
+        let plugin_id = clap_plugin_id {
+            abi: CLAP_PLUGIN_ABI.as_ptr() as *const c_char,
+            id: this.descriptor.plugin_id.as_ptr(),
+        };
+        for (preset_idx, preset) in this.descriptor.factory_presets.iter().enumerate() {
+            // The load key is the preset's index, which is what the plugin's preset-load extension
+            // expects
+            let name = match CString::new(preset.name) {
+                Ok(name) => name,
+                Err(_) => {
+                    nih_debug_assert_failure!(
+                        "Factory preset name {:?} contained null bytes",
+                        preset.name
+                    );
+                    continue;
+                }
+            };
+            let load_key = CString::new(preset_idx.to_string()).unwrap();
+
+            if !begin_preset(metadata_receiver, name.as_ptr(), load_key.as_ptr()) {
+                break;
+            }
+
+            add_plugin_id(metadata_receiver, &plugin_id);
+            if let Some(set_flags) = receiver.set_flags {
+                set_flags(metadata_receiver, CLAP_PRESET_DISCOVERY_IS_FACTORY_CONTENT);
+            }
+        }
+
+        true
+    }
+
+    unsafe extern "C" fn get_extension(
+        _provider: *const clap_preset_discovery_provider,
+        _extension_id: *const c_char,
+    ) -> *const c_void {
+        std::ptr::null()
+    }
+}
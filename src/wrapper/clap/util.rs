@@ -1,7 +1,17 @@
+use clap_sys::ext::audio_ports::{CLAP_PORT_MONO, CLAP_PORT_STEREO};
 use clap_sys::stream::{clap_istream, clap_ostream};
+use std::io;
 use std::mem::MaybeUninit;
 use std::ops::Deref;
-use std::os::raw::c_void;
+use std::os::raw::{c_char, c_void};
+
+use super::ext::ambisonic::CLAP_PORT_AMBISONIC;
+use super::ext::surround::{
+    CLAP_PORT_SURROUND, CLAP_SURROUND_BL, CLAP_SURROUND_BR, CLAP_SURROUND_FC, CLAP_SURROUND_FL,
+    CLAP_SURROUND_FR, CLAP_SURROUND_LFE, CLAP_SURROUND_SL, CLAP_SURROUND_SR, CLAP_SURROUND_TBL,
+    CLAP_SURROUND_TBR, CLAP_SURROUND_TFL, CLAP_SURROUND_TFR,
+};
+use crate::audio_setup::{ChannelLayout, Speaker};
 
 /// Early exit out of a function with the specified return value when one of the passed pointers is
 /// null.
@@ -58,6 +68,48 @@ pub(crate) use check_null_ptr_msg;
 pub(crate) use clap_call;
 pub(crate) use unsafe_clap_call;
 
+/// The CLAP port type for a port with the given channel layout. Ports without a channel layout
+/// don't have a port type.
+pub fn port_type(channel_layout: Option<ChannelLayout>) -> *const c_char {
+    match channel_layout {
+        Some(ChannelLayout::Mono) => CLAP_PORT_MONO.as_ptr(),
+        Some(ChannelLayout::Stereo) => CLAP_PORT_STEREO.as_ptr(),
+        Some(channel_layout) if channel_layout.ambisonic_order().is_some() => {
+            CLAP_PORT_AMBISONIC.as_ptr()
+        }
+        Some(_) => CLAP_PORT_SURROUND.as_ptr(),
+        None => std::ptr::null(),
+    }
+}
+
+/// The CLAP surround extension's speaker identifier for a speaker.
+pub fn surround_speaker_id(speaker: Speaker) -> u8 {
+    (match speaker {
+        Speaker::FrontLeft => CLAP_SURROUND_FL,
+        Speaker::FrontRight => CLAP_SURROUND_FR,
+        Speaker::FrontCenter => CLAP_SURROUND_FC,
+        Speaker::LowFrequency => CLAP_SURROUND_LFE,
+        Speaker::BackLeft => CLAP_SURROUND_BL,
+        Speaker::BackRight => CLAP_SURROUND_BR,
+        Speaker::SideLeft => CLAP_SURROUND_SL,
+        Speaker::SideRight => CLAP_SURROUND_SR,
+        Speaker::TopFrontLeft => CLAP_SURROUND_TFL,
+        Speaker::TopFrontRight => CLAP_SURROUND_TFR,
+        Speaker::TopBackLeft => CLAP_SURROUND_TBL,
+        Speaker::TopBackRight => CLAP_SURROUND_TBR,
+    }) as u8
+}
+
+/// The CLAP surround extension's channel mask for a speaker based channel layout, or `None` for
+/// ambisonic layouts.
+pub fn surround_channel_mask(channel_layout: ChannelLayout) -> Option<u64> {
+    channel_layout.speakers().map(|speakers| {
+        speakers.iter().fold(0, |mask, &speaker| {
+            mask | (1 << surround_speaker_id(speaker))
+        })
+    })
+}
+
 /// Send+Sync wrapper around CLAP host extension pointers.
 pub struct ClapPtr<T> {
     inner: *const T,
@@ -165,3 +217,47 @@ pub fn write_stream(stream: &clap_ostream, slice: &[u8]) -> bool {
 
     true
 }
+
+/// An [`io::Read`] implementation for a CLAP input stream.
+pub struct IStreamReader<'a>(pub &'a clap_istream);
+
+impl io::Read for IStreamReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let stream = self.0;
+        let bytes_read = unsafe_clap_call! {
+            stream=>read(stream, buf.as_mut_ptr() as *mut c_void, buf.len() as u64)
+        };
+        if bytes_read < 0 {
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                "Error while reading from the stream",
+            ))
+        } else {
+            Ok(bytes_read as usize)
+        }
+    }
+}
+
+/// An [`io::Write`] implementation for a CLAP output stream.
+pub struct OStreamWriter<'a>(pub &'a clap_ostream);
+
+impl io::Write for OStreamWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let stream = self.0;
+        let bytes_written = unsafe_clap_call! {
+            stream=>write(stream, buf.as_ptr() as *const c_void, buf.len() as u64)
+        };
+        if bytes_written < 0 {
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                "Error while writing to the stream",
+            ))
+        } else {
+            Ok(bytes_written as usize)
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -17,11 +17,11 @@ use clap_sys::events::{
 };
 use clap_sys::ext::audio_ports::{
     clap_audio_port_info, clap_plugin_audio_ports, CLAP_AUDIO_PORT_IS_MAIN, CLAP_EXT_AUDIO_PORTS,
-    CLAP_PORT_MONO, CLAP_PORT_STEREO,
 };
 use clap_sys::ext::audio_ports_config::{
     clap_audio_ports_config, clap_plugin_audio_ports_config, CLAP_EXT_AUDIO_PORTS_CONFIG,
 };
+use clap_sys::ext::draft::preset_load::{clap_plugin_preset_load, CLAP_EXT_PRESET_LOAD};
 use clap_sys::ext::draft::remote_controls::{
     clap_plugin_remote_controls, clap_remote_controls_page, CLAP_EXT_REMOTE_CONTROLS,
 };
@@ -44,13 +44,17 @@ use clap_sys::ext::render::{
     clap_plugin_render, clap_plugin_render_mode, CLAP_EXT_RENDER, CLAP_RENDER_OFFLINE,
     CLAP_RENDER_REALTIME,
 };
-use clap_sys::ext::state::{clap_plugin_state, CLAP_EXT_STATE};
+use clap_sys::ext::state::{clap_host_state, clap_plugin_state, CLAP_EXT_STATE};
 use clap_sys::ext::tail::{clap_plugin_tail, CLAP_EXT_TAIL};
 use clap_sys::ext::thread_check::{clap_host_thread_check, CLAP_EXT_THREAD_CHECK};
+use clap_sys::ext::thread_pool::{
+    clap_host_thread_pool, clap_plugin_thread_pool, CLAP_EXT_THREAD_POOL,
+};
 use clap_sys::ext::voice_info::{
     clap_host_voice_info, clap_plugin_voice_info, clap_voice_info, CLAP_EXT_VOICE_INFO,
     CLAP_VOICE_INFO_SUPPORTS_OVERLAPPING_NOTES,
 };
+use clap_sys::factory::draft::preset_discovery::CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN;
 use clap_sys::fixedpoint::{CLAP_BEATTIME_FACTOR, CLAP_SECTIME_FACTOR};
 use clap_sys::host::clap_host;
 use clap_sys::id::{clap_id, CLAP_INVALID_ID};
@@ -69,6 +73,7 @@ use std::any::Any;
 use std::borrow::Borrow;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::ffi::{c_void, CStr};
+use std::io::Read;
 use std::mem;
 use std::num::NonZeroU32;
 use std::os::raw::c_char;
@@ -80,21 +85,43 @@ use std::time::Duration;
 
 use super::context::{WrapperGuiContext, WrapperInitContext, WrapperProcessContext};
 use super::descriptor::PluginDescriptor;
-use super::util::ClapPtr;
+use super::ext::ambisonic::{
+    clap_ambisonic_config, clap_plugin_ambisonic, CLAP_AMBISONIC_NORMALIZATION_SN3D,
+    CLAP_AMBISONIC_ORDERING_ACN, CLAP_EXT_AMBISONIC,
+};
+use super::ext::gain_adjustment_metering::{
+    clap_plugin_gain_adjustment_metering, CLAP_EXT_GAIN_ADJUSTMENT_METERING,
+};
+use super::ext::surround::{clap_plugin_surround, CLAP_EXT_SURROUND};
+use super::util::{port_type, surround_channel_mask, surround_speaker_id, ClapPtr};
+use crate::debug::realtime::LogDrainHandle;
 use crate::event_loop::{BackgroundThread, EventLoop, MainThreadExecutor, TASK_QUEUE_CAPACITY};
+use crate::midi::learn::MidiLearn;
 use crate::midi::MidiResult;
 use crate::prelude::{
-    AsyncExecutor, AudioIOLayout, AuxiliaryBuffers, BufferConfig, ClapPlugin, Editor, MidiConfig,
-    NoteEvent, ParamFlags, ParamPtr, Params, ParentWindowHandle, Plugin, PluginNoteEvent,
-    ProcessMode, ProcessStatus, SysExMessage, TaskExecutor, Transport,
+    AsyncExecutor, AudioIOLayout, AuxiliaryBuffers, BufferConfig, ChannelLayout, ClapPlugin,
+    Editor, HostInfo, MidiConfig, NoteEvent, ParamFlags, ParamPtr, Params, ParentWindowHandle,
+    Plugin, PluginNoteEvent, ProcessMode, ProcessStatus, SysExMessage, TaskExecutor, Transport,
+    TransportSnapshot,
 };
 use crate::util::permit_alloc;
 use crate::wrapper::clap::context::RemoteControlPages;
-use crate::wrapper::clap::util::{read_stream, write_stream};
+use crate::wrapper::clap::util::{read_stream, write_stream, IStreamReader, OStreamWriter};
 use crate::wrapper::state::{self, PluginState};
+use crate::wrapper::util::analyzer_passthrough::{process_with_passthrough, AnalyzerPassthrough};
 use crate::wrapper::util::buffer_management::{BufferManager, ChannelPointers};
+use crate::wrapper::util::bypass_crossfade::{self, BypassCrossfader};
+use crate::wrapper::util::cv_inputs::CvInputs;
+use crate::wrapper::util::fixed_block::FixedBlockAdapter;
+use crate::wrapper::util::panic_guard::{self, PanicGuard};
+use crate::wrapper::util::param_output_limiter::ParamOutputLimiter;
+use crate::wrapper::util::process_timings::ProcessTimingCollector;
+use crate::wrapper::util::silence_skip::{self, SilenceSkipper};
+use crate::wrapper::util::state_slots::StateSlots;
+use crate::wrapper::util::transport_tracker::TransportTracker;
 use crate::wrapper::util::{
-    clamp_input_event_timing, clamp_output_event_timing, hash_param_id, process_wrapper, strlcpy,
+    clamp_input_event_timing, clamp_output_event_timing, hash_param_id, initialize_plugin,
+    process_wrapper, strlcpy, CStrWriter, EDITOR_EVENT_QUEUE_CAPACITY, GUI_MESSAGE_QUEUE_CAPACITY,
 };
 
 /// How many output parameter changes we can store in our output parameter change queue. Storing
@@ -113,6 +140,9 @@ pub struct Wrapper<P: ClapPlugin> {
     /// `ParamPtr`s are guaranteed to live at least as long as this object and we can interact with
     /// the `Params` object without having to acquire a lock on `plugin`.
     params: Arc<dyn Params>,
+    /// Restores the plugin's `#[persist_streamed]` fields on a background thread after loading
+    /// state.
+    streamed_fields_loader: state::StreamedFieldsLoader,
     /// The plugin's editor, if it has one. This object does not do anything on its own, but we need
     /// to instantiate this in advance so we don't need to lock the entire [`Plugin`] object when
     /// creating an editor. Wrapped in an `AtomicRefCell` because it needs to be initialized late.
@@ -132,7 +162,10 @@ pub struct Wrapper<P: ClapPlugin> {
     current_audio_io_layout: AtomicCell<AudioIOLayout>,
     /// The current buffer configuration, containing the sample rate and the maximum block size.
     /// Will be set in `clap_plugin::activate()`.
-    current_buffer_config: AtomicCell<Option<BufferConfig>>,
+    pub current_buffer_config: AtomicCell<Option<BufferConfig>>,
+    /// The audio IO layout and buffer config the plugin was last successfully initialized with in
+    /// `clap_plugin::activate()`. Used to detect sample rate only changes.
+    last_initialized_config: AtomicCell<Option<(AudioIOLayout, BufferConfig)>>,
     /// The current audio processing mode. Set through the render extension. Defaults to realtime.
     pub current_process_mode: AtomicCell<ProcessMode>,
     /// The incoming events for the plugin, if `P::MIDI_INPUT` is set to `MidiConfig::Basic` or
@@ -145,13 +178,42 @@ pub struct Wrapper<P: ClapPlugin> {
     /// `input_events`.
     output_events: AtomicRefCell<VecDeque<PluginNoteEvent<P>>>,
     /// The last process status returned by the plugin. This is used for tail handling.
-    last_process_status: AtomicCell<ProcessStatus>,
+    pub last_process_status: AtomicCell<ProcessStatus>,
+    /// The transport information from the most recent process call, for
+    /// [`GuiContext::transport()`][crate::prelude::GuiContext::transport()].
+    pub last_transport: AtomicCell<Option<TransportSnapshot>>,
+    /// Used to detect transport starts, stops, and jumps between process calls.
+    transport_tracker: AtomicRefCell<TransportTracker>,
+    /// Decides when process calls can be skipped when `P::SUPPORTS_SILENCE_SKIP` is enabled.
+    silence_skipper: AtomicRefCell<SilenceSkipper>,
+    /// Keeps track of whether the plugin panicked during processing when `P::CATCH_PANICS` is
+    /// enabled. Reset when the plugin is activated again.
+    panic_guard: PanicGuard,
+    /// Measures the durations of the plugin's process calls when `P::COLLECT_PROCESS_TIMINGS` is
+    /// enabled. Reset when the plugin is activated again.
+    pub process_timings: ProcessTimingCollector,
     /// The current latency in samples, as set by the plugin through the [`ProcessContext`]. Uses
     /// the latency extension.
     pub current_latency: AtomicU32,
     /// A data structure that helps manage and create buffers for all of the plugin's inputs and
     /// outputs based on channel pointers provided by the host.
     buffer_manager: AtomicRefCell<BufferManager>,
+    /// Buffers the host's audio when `P::FIXED_BLOCK_SIZE` is set so the plugin always processes
+    /// blocks of that size. Allocated when the plugin gets activated.
+    fixed_block_adapter: AtomicRefCell<Option<FixedBlockAdapter<P::SysExMessage>>>,
+    /// The plugin's bypass parameter, if `P::BYPASS_CROSSFADE_MS` is set and the plugin has one.
+    bypass_param: Option<ParamPtr>,
+    /// Crossfades between the processed and the dry signal when `bypass_param` changes. Allocated
+    /// when the plugin gets activated.
+    bypass_crossfader: AtomicRefCell<Option<BypassCrossfader>>,
+    /// Restores the main buffer after processing if `P::ANALYZER_MODE` is set. Allocated when the
+    /// plugin gets activated.
+    analyzer_passthrough: AtomicRefCell<Option<AnalyzerPassthrough>>,
+    /// Copies the auxiliary inputs to the parameters that accept audio-rate modulation.
+    cv_inputs: CvInputs,
+    /// Limits how often parameter changes made from the audio thread are sent to the host, if
+    /// `P::PARAM_OUTPUT_RATE_HZ` is set.
+    pub param_output_limiter: AtomicRefCell<ParamOutputLimiter>,
     /// The plugin is able to restore state through a method on the `GuiContext`. To avoid changing
     /// parameters mid-processing and running into garbled data if the host also tries to load state
     /// at the same time the restoring happens at the end of each processing call. If this zero
@@ -163,6 +225,13 @@ pub struct Wrapper<P: ClapPlugin> {
     updated_state_sender: channel::Sender<PluginState>,
     /// The receiver belonging to [`new_state_sender`][Self::new_state_sender].
     updated_state_receiver: channel::Receiver<PluginState>,
+    /// The states used for A/B comparisons through the `GuiContext`.
+    pub state_slots: StateSlots,
+    /// The MIDI CCs mapped to parameters through the `GuiContext`. Mapped CCs are applied in
+    /// [`handle_in_event()`][Self::handle_in_event()] and they are not passed to the plugin.
+    pub midi_learn: MidiLearn,
+    /// Keeps the thread alive that forwards messages logged from the audio thread to the logger.
+    _log_drain: LogDrainHandle,
 
     // We'll query all of the host's extensions upfront
     host_callback: ClapPtr<clap_host>,
@@ -178,6 +247,8 @@ pub struct Wrapper<P: ClapPlugin> {
 
     clap_plugin_audio_ports: clap_plugin_audio_ports,
 
+    clap_plugin_ambisonic: clap_plugin_ambisonic,
+
     clap_plugin_gui: clap_plugin_gui,
     host_gui: AtomicRefCell<Option<ClapPtr<clap_host_gui>>>,
 
@@ -226,6 +297,14 @@ pub struct Wrapper<P: ClapPlugin> {
 
     host_thread_check: AtomicRefCell<Option<ClapPtr<clap_host_thread_check>>>,
 
+    clap_plugin_thread_pool: clap_plugin_thread_pool,
+    host_thread_pool: AtomicRefCell<Option<ClapPtr<clap_host_thread_pool>>>,
+    /// The task passed to [`execute_parallel()`][Self::execute_parallel()]. This is only set while
+    /// the host's thread pool is running it.
+    parallel_task: AtomicRefCell<Option<ParallelTask>>,
+
+    clap_plugin_preset_load: clap_plugin_preset_load,
+
     clap_plugin_remote_controls: clap_plugin_remote_controls,
     /// The plugin's remote control pages, if it defines any. Filled when initializing the plugin.
     remote_control_pages: Vec<clap_remote_controls_page>,
@@ -233,9 +312,17 @@ pub struct Wrapper<P: ClapPlugin> {
     clap_plugin_render: clap_plugin_render,
 
     clap_plugin_state: clap_plugin_state,
+    host_state: AtomicRefCell<Option<ClapPtr<clap_host_state>>>,
+
+    clap_plugin_surround: clap_plugin_surround,
 
     clap_plugin_tail: clap_plugin_tail,
 
+    clap_plugin_gain_adjustment_metering: clap_plugin_gain_adjustment_metering,
+    /// The gain reduction in decibels set through
+    /// [`ProcessContext::set_gain_reduction()`][crate::prelude::ProcessContext::set_gain_reduction()].
+    pub gain_reduction_db: AtomicF32,
+
     clap_plugin_voice_info: clap_plugin_voice_info,
     host_voice_info: AtomicRefCell<Option<ClapPtr<clap_host_voice_info>>>,
     /// If `P::CLAP_POLY_MODULATION_CONFIG` is set, then the plugin can configure the current number
@@ -249,6 +336,14 @@ pub struct Wrapper<P: ClapPlugin> {
     /// [`on_main_thread()`][Self::on_main_thread()] on the main thread, and then continue to pop
     /// tasks off this queue there until it is empty.
     tasks: ArrayQueue<Task<P>>,
+    /// Messages sent from the editor to the audio thread through
+    /// [`AsyncExecutor::send_to_dsp()`], received using
+    /// [`ProcessContext::next_gui_message()`][crate::prelude::ProcessContext::next_gui_message()].
+    pub gui_messages: ArrayQueue<P::GuiMessage>,
+    /// Events sent from the audio thread to the editor through
+    /// [`ProcessContext::send_to_editor()`][crate::prelude::ProcessContext::send_to_editor()],
+    /// received using [`AsyncExecutor::next_editor_event()`].
+    pub editor_events: ArrayQueue<P::EditorEvent>,
     /// The ID of the main thread. In practice this is the ID of the thread that created this
     /// object. If the host supports the thread check extension (and
     /// [`host_thread_check`][Self::host_thread_check] thus contains a value), then that extension
@@ -313,6 +408,15 @@ pub enum OutputParamEvent {
     EndGesture { param_hash: u32 },
 }
 
+/// A pointer to the task passed to [`Wrapper::execute_parallel()`], so it can be called from
+/// `clap_plugin_thread_pool::exec()` on the host's worker threads.
+struct ParallelTask(*const (dyn Fn(u32) + Sync));
+
+// SAFETY: The task itself is `Sync`, and the pointer is only dereferenced while
+//         `Wrapper::execute_parallel()` is blocking on the host's thread pool
+unsafe impl Send for ParallelTask {}
+unsafe impl Sync for ParallelTask {}
+
 /// Because CLAP has this [`clap_host::request_host_callback()`] function, we don't need to use
 /// `OsEventLoop` and can instead just request a main thread callback directly.
 impl<P: ClapPlugin> EventLoop<Task<P>, Wrapper<P>> for Wrapper<P> {
@@ -488,6 +592,27 @@ impl<P: ClapPlugin> Wrapper<P> {
                 ptr.poly_modulation_id().map(|id| (*hash, id))
             })
             .collect();
+        let bypass_param = bypass_crossfade::find_bypass_param::<P>(
+            param_id_hashes_ptrs_groups
+                .iter()
+                .map(|(_, _, ptr, _)| *ptr),
+        );
+        let cv_inputs = CvInputs::new(
+            param_id_hashes_ptrs_groups
+                .iter()
+                .map(|(_, _, ptr, _)| *ptr),
+        );
+        let param_output_limiter = ParamOutputLimiter::new(
+            P::PARAM_OUTPUT_RATE_HZ,
+            param_id_hashes_ptrs_groups
+                .iter()
+                .map(|(_, _, ptr, _)| *ptr),
+        );
+        let midi_learn = MidiLearn::new(
+            param_id_hashes_ptrs_groups
+                .iter()
+                .map(|(id, _, ptr, _)| (id.clone(), *ptr)),
+        );
 
         if cfg!(debug_assertions) {
             let param_map = params.param_map();
@@ -539,6 +664,7 @@ impl<P: ClapPlugin> Wrapper<P> {
             plugin: Mutex::new(plugin),
             task_executor,
             params,
+            streamed_fields_loader: state::StreamedFieldsLoader::default(),
             // Initialized later as it needs a reference to the wrapper for the async executor
             editor: AtomicRefCell::new(None),
             editor_handle: Mutex::new(None),
@@ -549,10 +675,16 @@ impl<P: ClapPlugin> Wrapper<P> {
                 P::AUDIO_IO_LAYOUTS.first().copied().unwrap_or_default(),
             ),
             current_buffer_config: AtomicCell::new(None),
+            last_initialized_config: AtomicCell::new(None),
             current_process_mode: AtomicCell::new(ProcessMode::Realtime),
             input_events: AtomicRefCell::new(VecDeque::with_capacity(512)),
             output_events: AtomicRefCell::new(VecDeque::with_capacity(512)),
             last_process_status: AtomicCell::new(ProcessStatus::Normal),
+            last_transport: AtomicCell::new(None),
+            transport_tracker: AtomicRefCell::new(TransportTracker::default()),
+            silence_skipper: AtomicRefCell::new(SilenceSkipper::default()),
+            panic_guard: PanicGuard::default(),
+            process_timings: ProcessTimingCollector::default(),
             current_latency: AtomicU32::new(0),
             // This is initialized just before calling `Plugin::initialize()` so that during the
             // process call buffers can be initialized without any allocations
@@ -560,8 +692,17 @@ impl<P: ClapPlugin> Wrapper<P> {
                 0,
                 AudioIOLayout::default(),
             )),
+            fixed_block_adapter: AtomicRefCell::new(None),
+            bypass_param,
+            bypass_crossfader: AtomicRefCell::new(None),
+            analyzer_passthrough: AtomicRefCell::new(None),
+            cv_inputs,
+            param_output_limiter: AtomicRefCell::new(param_output_limiter),
             updated_state_sender,
             updated_state_receiver,
+            state_slots: StateSlots::default(),
+            midi_learn,
+            _log_drain: LogDrainHandle::get_or_create(),
 
             host_callback,
 
@@ -597,6 +738,11 @@ impl<P: ClapPlugin> Wrapper<P> {
                 get: Some(Self::ext_audio_ports_get),
             },
 
+            clap_plugin_ambisonic: clap_plugin_ambisonic {
+                is_config_supported: Some(Self::ext_ambisonic_is_config_supported),
+                get_config: Some(Self::ext_ambisonic_get_config),
+            },
+
             clap_plugin_gui: clap_plugin_gui {
                 is_api_supported: Some(Self::ext_gui_is_api_supported),
                 get_preferred_api: Some(Self::ext_gui_get_preferred_api),
@@ -646,12 +792,22 @@ impl<P: ClapPlugin> Wrapper<P> {
 
             host_thread_check: AtomicRefCell::new(None),
 
+            clap_plugin_thread_pool: clap_plugin_thread_pool {
+                exec: Some(Self::ext_thread_pool_exec),
+            },
+            host_thread_pool: AtomicRefCell::new(None),
+            parallel_task: AtomicRefCell::new(None),
+
             clap_plugin_remote_controls: clap_plugin_remote_controls {
                 count: Some(Self::ext_remote_controls_count),
                 get: Some(Self::ext_remote_controls_get),
             },
             remote_control_pages,
 
+            clap_plugin_preset_load: clap_plugin_preset_load {
+                from_location: Some(Self::ext_preset_load_from_location),
+            },
+
             clap_plugin_render: clap_plugin_render {
                 has_hard_realtime_requirement: Some(Self::ext_render_has_hard_realtime_requirement),
                 set: Some(Self::ext_render_set),
@@ -661,11 +817,22 @@ impl<P: ClapPlugin> Wrapper<P> {
                 save: Some(Self::ext_state_save),
                 load: Some(Self::ext_state_load),
             },
+            host_state: AtomicRefCell::new(None),
+
+            clap_plugin_surround: clap_plugin_surround {
+                is_channel_mask_supported: Some(Self::ext_surround_is_channel_mask_supported),
+                get_channel_map: Some(Self::ext_surround_get_channel_map),
+            },
 
             clap_plugin_tail: clap_plugin_tail {
                 get: Some(Self::ext_tail_get),
             },
 
+            clap_plugin_gain_adjustment_metering: clap_plugin_gain_adjustment_metering {
+                get: Some(Self::ext_gain_adjustment_metering_get),
+            },
+            gain_reduction_db: AtomicF32::new(0.0),
+
             clap_plugin_voice_info: clap_plugin_voice_info {
                 get: Some(Self::ext_voice_info_get),
             },
@@ -683,6 +850,8 @@ impl<P: ClapPlugin> Wrapper<P> {
             ),
 
             tasks: ArrayQueue::new(TASK_QUEUE_CAPACITY),
+            gui_messages: ArrayQueue::new(GUI_MESSAGE_QUEUE_CAPACITY),
+            editor_events: ArrayQueue::new(EDITOR_EVENT_QUEUE_CAPACITY),
             main_thread_id: thread::current().id(),
             // Initialized later as it needs a reference to the wrapper for the executor
             background_thread: AtomicRefCell::new(None),
@@ -718,6 +887,24 @@ impl<P: ClapPlugin> Wrapper<P> {
                         nih_debug_assert!(task_posted, "The task queue is full, dropping task...");
                     }
                 }),
+                send_to_dsp: Arc::new({
+                    let wrapper = wrapper.clone();
+
+                    move |message| {
+                        let message_posted = wrapper.gui_messages.push(message).is_ok();
+                        nih_debug_assert!(
+                            message_posted,
+                            "The GUI message queue is full, dropping message..."
+                        );
+
+                        message_posted
+                    }
+                }),
+                next_editor_event: Arc::new({
+                    let wrapper = wrapper.clone();
+
+                    move || wrapper.editor_events.pop()
+                }),
             })
             .map(Mutex::new);
 
@@ -787,6 +974,40 @@ impl<P: ClapPlugin> Wrapper<P> {
         result
     }
 
+    /// The same as [`queue_parameter_event()`][Self::queue_parameter_event()], but for events
+    /// queued by the plugin from its process function. These are sent to the host at the end of
+    /// the current block, so no parameter flush needs to be requested.
+    pub fn queue_parameter_event_from_process(&self, event: OutputParamEvent) -> bool {
+        self.output_parameter_events.push(event).is_ok()
+    }
+
+    /// Queue a new value for a parameter changed by the plugin from its process function. See
+    /// [`queue_parameter_event_from_process()`][Self::queue_parameter_event_from_process()].
+    pub fn queue_parameter_value_from_process(&self, param: ParamPtr, normalized: f32) {
+        match self.param_ptr_to_hash.get(&param) {
+            Some(hash) => {
+                let event = OutputParamEvent::SetValue {
+                    param_hash: *hash,
+                    clap_plain_value: normalized as f64
+                        * unsafe { param.step_count() }.unwrap_or(1) as f64,
+                };
+                let success = self.queue_parameter_event_from_process(event);
+                nih_debug_assert!(success, "Parameter output event queue was full");
+            }
+            None => nih_debug_assert_failure!("Unknown parameter: {:?}", param),
+        }
+    }
+
+    /// Send the parameter values held back by `P::PARAM_OUTPUT_RATE_HZ` whose interval has passed.
+    /// Called before every call to the plugin's process function.
+    fn flush_param_output(&self, num_samples: usize) {
+        self.param_output_limiter
+            .borrow_mut()
+            .begin_block(num_samples, |param, normalized| {
+                self.queue_parameter_value_from_process(param, normalized)
+            });
+    }
+
     /// Request a resize based on the editor's current reported size. As of CLAP 0.24 this can
     /// safely be called from any thread. If this returns `false`, then the plugin should reset its
     /// size back to the previous value.
@@ -811,6 +1032,44 @@ impl<P: ClapPlugin> Wrapper<P> {
         }
     }
 
+    /// Tell the host that the plugin's state has changed outside of a parameter change, so it can
+    /// mark the project as modified. Must be called from the main thread.
+    pub fn mark_state_dirty(&self) {
+        if let Some(host_state) = &*self.host_state.borrow() {
+            unsafe_clap_call! { host_state=>mark_dirty(&*self.host_callback) };
+        }
+    }
+
+    /// Run `task` for every index in `0..num_tasks` using the host's thread pool, and block until
+    /// all tasks have finished. Falls back to running the tasks sequentially on the calling thread
+    /// if the host does not support the thread pool extension or if it refuses the request. Must
+    /// be called from the audio thread.
+    pub fn execute_parallel(&self, num_tasks: u32, task: &(dyn Fn(u32) + Sync)) {
+        if let Some(host_thread_pool) = &*self.host_thread_pool.borrow() {
+            // SAFETY: The host only calls `clap_plugin_thread_pool::exec()` during the
+            //         `request_exec()` call, and the pointer is cleared again right after that, so
+            //         it never outlives `task`
+            *self.parallel_task.borrow_mut() = Some(ParallelTask(unsafe {
+                mem::transmute::<*const (dyn Fn(u32) + Sync + '_), *const (dyn Fn(u32) + Sync)>(
+                    task,
+                )
+            }));
+            let success = unsafe_clap_call! {
+                host_thread_pool=>request_exec(&*self.host_callback, num_tasks)
+            };
+            *self.parallel_task.borrow_mut() = None;
+
+            // If the host refused the request, then none of the tasks have been run
+            if success {
+                return;
+            }
+        }
+
+        for task_idx in 0..num_tasks {
+            task(task_idx);
+        }
+    }
+
     /// Convenience function for setting a value for a parameter as triggered by a VST3 parameter
     /// update. The same rate is for updating parameter smoothing.
     ///
@@ -1648,7 +1907,9 @@ impl<P: ClapPlugin> Wrapper<P> {
                         input_events.push_back(note_event);
                     }
                     Ok(note_event) if P::MIDI_INPUT >= MidiConfig::MidiCCs => {
-                        input_events.push_back(note_event);
+                        if !self.apply_midi_learn(&note_event) {
+                            input_events.push_back(note_event);
+                        }
                     }
                     Ok(_) => (),
                     Err(n) => nih_debug_assert_failure!("Unhandled MIDI message type {}", n),
@@ -1659,9 +1920,13 @@ impl<P: ClapPlugin> Wrapper<P> {
             {
                 let event = &*(event as *const clap_event_midi_sysex);
 
+                if event.buffer.is_null() {
+                    nih_debug_assert_failure!("The host sent a SysEx event with a null buffer");
+                    return;
+                }
+
                 // `NoteEvent::from_midi` prints some tracing if parsing fails, which is not
                 // necessarily an error
-                assert!(!event.buffer.is_null());
                 let sysex_buffer = std::slice::from_raw_parts(event.buffer, event.size as usize);
                 if let Ok(note_event) = NoteEvent::from_midi(timing, sysex_buffer) {
                     input_events.push_back(note_event);
@@ -1677,6 +1942,45 @@ impl<P: ClapPlugin> Wrapper<P> {
         }
     }
 
+    /// If `event` is a MIDI CC that has been mapped to a parameter through MIDI learn, then set the
+    /// parameter and send the change to the host. Returns `true` if the event should not be passed
+    /// to the plugin.
+    fn apply_midi_learn(&self, event: &PluginNoteEvent<P>) -> bool {
+        let NoteEvent::MidiCC {
+            channel, cc, value, ..
+        } = *event
+        else {
+            return false;
+        };
+        let Some((param_ptr, normalized_value)) = self.midi_learn.handle_cc(channel, cc, value)
+        else {
+            return false;
+        };
+
+        match self.param_ptr_to_hash.get(&param_ptr) {
+            Some(hash) => {
+                let clap_plain_value =
+                    normalized_value as f64 * unsafe { param_ptr.step_count() }.unwrap_or(1) as f64;
+                self.update_plain_value_by_hash(
+                    *hash,
+                    ClapParamUpdate::PlainValueSet(clap_plain_value),
+                    self.current_buffer_config.load().map(|c| c.sample_rate),
+                );
+
+                // The host also needs to know about the change so it can record it
+                let event = OutputParamEvent::SetValue {
+                    param_hash: *hash,
+                    clap_plain_value,
+                };
+                let success = self.queue_parameter_event_from_process(event);
+                nih_debug_assert!(success, "Parameter output event queue was full");
+            }
+            None => nih_debug_assert_failure!("Unknown parameter: {:?}", param_ptr),
+        }
+
+        true
+    }
+
     /// Get the plugin's state object, may be called by the plugin's GUI as part of its own preset
     /// management. The wrapper doesn't use these functions and serializes and deserializes directly
     /// the JSON in the relevant plugin API methods instead.
@@ -1685,6 +1989,7 @@ impl<P: ClapPlugin> Wrapper<P> {
             state::serialize_object::<P>(
                 self.params.clone(),
                 state::make_params_iter(&self.param_by_hash, &self.param_id_to_hash),
+                Some(&self.midi_learn),
             )
         }
     }
@@ -1746,6 +2051,31 @@ impl<P: ClapPlugin> Wrapper<P> {
         }
     }
 
+    /// Information about the host, based on the `clap_host` struct and the host's supported
+    /// extensions.
+    pub fn host_info(&self) -> HostInfo {
+        let host_string = |s: *const c_char| {
+            if s.is_null() {
+                return None;
+            }
+
+            let s = unsafe { CStr::from_ptr(s) }.to_string_lossy();
+            if s.is_empty() {
+                None
+            } else {
+                Some(s.into_owned())
+            }
+        };
+
+        HostInfo {
+            name: host_string(self.host_callback.name),
+            vendor: host_string(self.host_callback.vendor),
+            version: host_string(self.host_callback.version),
+            supports_latency_changes: self.host_latency.borrow().is_some(),
+            supports_parallel_execution: self.host_thread_pool.borrow().is_some(),
+        }
+    }
+
     pub fn set_current_voice_capacity(&self, capacity: u32) {
         match P::CLAP_POLY_MODULATION_CONFIG {
             Some(config) => {
@@ -1796,6 +2126,7 @@ impl<P: ClapPlugin> Wrapper<P> {
                 self.params.clone(),
                 state::make_params_getter(&self.param_by_hash, &self.param_id_to_hash),
                 self.current_buffer_config.load().as_ref(),
+                Some(&self.midi_learn),
             )
         });
         if !success {
@@ -1814,7 +2145,7 @@ impl<P: ClapPlugin> Wrapper<P> {
                 plugin.initialize(&audio_io_layout, &buffer_config, &mut init_context)
             });
             if success {
-                process_wrapper(|| plugin.reset());
+                process_wrapper("Plugin::reset() after loading state", || plugin.reset());
             }
         }
 
@@ -1848,6 +2179,8 @@ impl<P: ClapPlugin> Wrapper<P> {
             query_host_extension::<clap_host_latency>(&wrapper.host_callback, CLAP_EXT_LATENCY);
         *wrapper.host_params.borrow_mut() =
             query_host_extension::<clap_host_params>(&wrapper.host_callback, CLAP_EXT_PARAMS);
+        *wrapper.host_state.borrow_mut() =
+            query_host_extension::<clap_host_state>(&wrapper.host_callback, CLAP_EXT_STATE);
         *wrapper.host_voice_info.borrow_mut() = query_host_extension::<clap_host_voice_info>(
             &wrapper.host_callback,
             CLAP_EXT_VOICE_INFO,
@@ -1856,6 +2189,10 @@ impl<P: ClapPlugin> Wrapper<P> {
             &wrapper.host_callback,
             CLAP_EXT_THREAD_CHECK,
         );
+        *wrapper.host_thread_pool.borrow_mut() = query_host_extension::<clap_host_thread_pool>(
+            &wrapper.host_callback,
+            CLAP_EXT_THREAD_POOL,
+        );
 
         true
     }
@@ -1893,7 +2230,13 @@ impl<P: ClapPlugin> Wrapper<P> {
         // NOTE: This needs to be dropped after the `plugin` lock to avoid deadlocks
         let mut init_context = wrapper.make_init_context();
         let mut plugin = wrapper.plugin.lock();
-        if plugin.initialize(&audio_io_layout, &buffer_config, &mut init_context) {
+        if initialize_plugin(
+            &mut *plugin,
+            wrapper.last_initialized_config.load(),
+            &audio_io_layout,
+            &buffer_config,
+            &mut init_context,
+        ) {
             // NOTE: `Plugin::reset()` is called in `clap_plugin::start_processing()` instead of in
             //       this function
 
@@ -1901,12 +2244,50 @@ impl<P: ClapPlugin> Wrapper<P> {
             // pointers into a set of `Buffer` objects for the plugin's main and auxiliary IO
             *wrapper.buffer_manager.borrow_mut() =
                 BufferManager::for_audio_io_layout(max_frames_count as usize, audio_io_layout);
+            *wrapper.fixed_block_adapter.borrow_mut() = P::FIXED_BLOCK_SIZE
+                .map(|block_size| FixedBlockAdapter::new(block_size, &audio_io_layout));
+            *wrapper.bypass_crossfader.borrow_mut() = wrapper.bypass_param.map(|_| {
+                BypassCrossfader::new(
+                    P::BYPASS_CROSSFADE_MS.unwrap_or_default(),
+                    buffer_config.sample_rate,
+                    max_frames_count as usize,
+                    audio_io_layout
+                        .main_output_channels
+                        .map(NonZeroU32::get)
+                        .unwrap_or(0) as usize,
+                    wrapper.current_latency.load(Ordering::SeqCst)
+                        + P::FIXED_BLOCK_SIZE.unwrap_or(0),
+                )
+            });
+            *wrapper.analyzer_passthrough.borrow_mut() = P::ANALYZER_MODE.then(|| {
+                AnalyzerPassthrough::new(
+                    audio_io_layout
+                        .main_output_channels
+                        .map(NonZeroU32::get)
+                        .unwrap_or(0) as usize,
+                    max_frames_count as usize,
+                )
+            });
+            wrapper
+                .cv_inputs
+                .initialize(&audio_io_layout, max_frames_count as usize);
+            wrapper
+                .param_output_limiter
+                .borrow_mut()
+                .initialize(buffer_config.sample_rate);
 
             // Also store this for later, so we can reinitialize the plugin after restoring state
             wrapper.current_buffer_config.store(Some(buffer_config));
+            wrapper
+                .last_initialized_config
+                .store(Some((audio_io_layout, buffer_config)));
+            wrapper.panic_guard.reset();
+            wrapper.process_timings.reset();
 
             true
         } else {
+            wrapper.last_initialized_config.store(None);
+
             false
         }
     }
@@ -1926,11 +2307,24 @@ impl<P: ClapPlugin> Wrapper<P> {
 
         // Always reset the processing status when the plugin gets activated or deactivated
         wrapper.last_process_status.store(ProcessStatus::Normal);
+        wrapper.transport_tracker.borrow_mut().reset();
+        wrapper.silence_skipper.borrow_mut().reset();
+        if let Some(adapter) = wrapper.fixed_block_adapter.borrow_mut().as_mut() {
+            adapter.reset();
+        }
+        if let (Some(crossfader), Some(bypass_param)) = (
+            wrapper.bypass_crossfader.borrow_mut().as_mut(),
+            wrapper.bypass_param,
+        ) {
+            crossfader.reset(bypass_crossfade::is_bypassed(bypass_param));
+        }
         wrapper.is_processing.store(true, Ordering::SeqCst);
 
         // To be consistent with the VST3 wrapper, we'll also reset the buffers here in addition to
         // the dedicated `reset()` function.
-        process_wrapper(|| wrapper.plugin.lock().reset());
+        process_wrapper("clap_plugin::start_processing()", || {
+            wrapper.plugin.lock().reset()
+        });
 
         true
     }
@@ -1940,13 +2334,26 @@ impl<P: ClapPlugin> Wrapper<P> {
         let wrapper = &*((*plugin).plugin_data as *const Self);
 
         wrapper.is_processing.store(false, Ordering::SeqCst);
+        // The meter should not stay stuck at its last value while the plugin isn't processing
+        wrapper.gain_reduction_db.store(0.0, Ordering::Relaxed);
     }
 
     unsafe extern "C" fn reset(plugin: *const clap_plugin) {
         check_null_ptr!((), plugin, (*plugin).plugin_data);
         let wrapper = &*((*plugin).plugin_data as *const Self);
 
-        process_wrapper(|| wrapper.plugin.lock().reset());
+        wrapper.transport_tracker.borrow_mut().reset();
+        wrapper.silence_skipper.borrow_mut().reset();
+        if let Some(adapter) = wrapper.fixed_block_adapter.borrow_mut().as_mut() {
+            adapter.reset();
+        }
+        if let (Some(crossfader), Some(bypass_param)) = (
+            wrapper.bypass_crossfader.borrow_mut().as_mut(),
+            wrapper.bypass_param,
+        ) {
+            crossfader.reset(bypass_crossfade::is_bypassed(bypass_param));
+        }
+        process_wrapper("clap_plugin::reset()", || wrapper.plugin.lock().reset());
     }
 
     unsafe extern "C" fn process(
@@ -1958,7 +2365,7 @@ impl<P: ClapPlugin> Wrapper<P> {
 
         // Panic on allocations if the `assert_process_allocs` feature has been enabled, and make
         // sure that FTZ is set up correctly
-        process_wrapper(|| {
+        process_wrapper("clap_plugin::process()", || {
             // We need to handle incoming automation and MIDI events. Since we don't support sample
             // accuration automation yet and there's no way to get the last event for a parameter,
             // we'll process every incoming event.
@@ -1968,6 +2375,10 @@ impl<P: ClapPlugin> Wrapper<P> {
             let current_audio_io_layout = wrapper.current_audio_io_layout.load();
             let has_main_input = current_audio_io_layout.main_input_channels.is_some();
             let has_main_output = current_audio_io_layout.main_output_channels.is_some();
+            let num_main_input_channels = current_audio_io_layout
+                .main_input_channels
+                .map(NonZeroU32::get)
+                .unwrap_or(0) as usize;
             let aux_input_start_idx = if has_main_input { 1 } else { 0 };
             let aux_output_start_idx = if has_main_output { 1 } else { 0 };
 
@@ -2155,11 +2566,18 @@ impl<P: ClapPlugin> Wrapper<P> {
                 let mut transport = Transport::new(sample_rate);
                 if !transport_info.is_null() {
                     let context = &*transport_info;
+                    // The transport information either comes from the process call, or from a
+                    // transport event in the middle of the buffer. In the latter case the
+                    // position is already correct at the event's timing, which is where the block
+                    // was split.
+                    let transport_offset = block_start.saturating_sub(context.header.time as usize);
 
                     transport.playing = context.flags & CLAP_TRANSPORT_IS_PLAYING != 0;
                     transport.recording = context.flags & CLAP_TRANSPORT_IS_RECORDING != 0;
                     transport.preroll_active =
                         Some(context.flags & CLAP_TRANSPORT_IS_WITHIN_PRE_ROLL != 0);
+                    transport.loop_active =
+                        Some(context.flags & CLAP_TRANSPORT_IS_LOOP_ACTIVE != 0);
                     if context.flags & CLAP_TRANSPORT_HAS_TEMPO != 0 {
                         transport.tempo = Some(context.tempo);
                     }
@@ -2174,12 +2592,12 @@ impl<P: ClapPlugin> Wrapper<P> {
                         // We can't use the functions on the transport information object for this
                         // because we don't have any sample information.
                         if P::SAMPLE_ACCURATE_AUTOMATION
-                            && block_start > 0
+                            && transport_offset > 0
                             && (context.flags & CLAP_TRANSPORT_HAS_TEMPO != 0)
                         {
                             transport.pos_beats = Some(
                                 beats
-                                    + (block_start as f64 / sample_rate as f64 / 60.0
+                                    + (transport_offset as f64 / sample_rate as f64 / 60.0
                                         * context.tempo),
                             );
                         } else {
@@ -2191,17 +2609,17 @@ impl<P: ClapPlugin> Wrapper<P> {
 
                         // Same here
                         if P::SAMPLE_ACCURATE_AUTOMATION
-                            && block_start > 0
+                            && transport_offset > 0
                             && (context.flags & CLAP_TRANSPORT_HAS_TEMPO != 0)
                         {
                             transport.pos_seconds =
-                                Some(seconds + (block_start as f64 / sample_rate as f64));
+                                Some(seconds + (transport_offset as f64 / sample_rate as f64));
                         } else {
                             transport.pos_seconds = Some(seconds);
                         }
                     }
                     // TODO: CLAP does not mention whether this is behind a flag or not
-                    if P::SAMPLE_ACCURATE_AUTOMATION && block_start > 0 {
+                    if P::SAMPLE_ACCURATE_AUTOMATION && transport_offset > 0 {
                         transport.bar_start_pos_beats = match transport.bar_start_pos_beats() {
                             Some(updated) => Some(updated),
                             None => Some(context.bar_start as f64 / CLAP_BEATTIME_FACTOR as f64),
@@ -2235,6 +2653,13 @@ impl<P: ClapPlugin> Wrapper<P> {
                         ));
                     }
                 }
+                wrapper
+                    .transport_tracker
+                    .borrow_mut()
+                    .update(&mut transport, block_len);
+                wrapper
+                    .last_transport
+                    .store(Some(TransportSnapshot::new(&transport)));
 
                 let result = if buffer_is_valid {
                     let mut plugin = wrapper.plugin.lock();
@@ -2245,10 +2670,129 @@ impl<P: ClapPlugin> Wrapper<P> {
                         inputs: buffers.aux_inputs,
                         outputs: buffers.aux_outputs,
                     };
-                    let mut context = wrapper.make_process_context(transport);
-                    let result = plugin.process(buffers.main_buffer, &mut aux, &mut context);
-                    wrapper.last_process_status.store(result);
-                    result
+                    let mut fixed_block_adapter = wrapper.fixed_block_adapter.borrow_mut();
+                    let mut bypass_crossfader = wrapper.bypass_crossfader.borrow_mut();
+                    if let Some(crossfader) = bypass_crossfader.as_mut() {
+                        crossfader.set_latency(
+                            wrapper.current_latency.load(Ordering::SeqCst)
+                                + P::FIXED_BLOCK_SIZE.unwrap_or(0),
+                        );
+                        crossfader.store_dry(buffers.main_buffer, num_main_input_channels);
+                    }
+                    let mut silence_skipper = wrapper.silence_skipper.borrow_mut();
+                    let skip_block = silence_skipper.should_skip::<P>(
+                        || {
+                            // Audio buffered by the fixed block adapter needs to be sent to the
+                            // host before the plugin can be skipped
+                            silence_skip::input_is_silent(
+                                buffers.main_buffer,
+                                num_main_input_channels,
+                                aux.inputs,
+                                !wrapper.input_events.borrow().is_empty(),
+                            ) && !fixed_block_adapter
+                                .as_ref()
+                                .is_some_and(|adapter| adapter.has_buffered_output())
+                        },
+                        block_len,
+                    );
+                    let result = if wrapper.panic_guard.has_panicked() {
+                        None
+                    } else if skip_block {
+                        panic_guard::silence_outputs(buffers.main_buffer, aux.outputs);
+                        Some(ProcessStatus::Normal)
+                    } else {
+                        let result = wrapper.panic_guard.catch_process::<P, _>(|| {
+                            wrapper
+                                .process_timings
+                                .time::<P, _>(block_len, sample_rate, || {
+                                    match fixed_block_adapter.as_mut() {
+                                        // The adapter calls the plugin once for every completed
+                                        // block, and those blocks' output events are sent right
+                                        // away at the host sample their output starts at
+                                        Some(adapter) => adapter.process(
+                                            buffers.main_buffer,
+                                            &mut aux,
+                                            &wrapper.input_events,
+                                            |buffer, aux, output_offset| {
+                                                wrapper
+                                                    .cv_inputs
+                                                    .copy_inputs(aux.inputs, buffer.samples());
+                                                wrapper.flush_param_output(buffer.samples());
+                                                let mut context =
+                                                    wrapper.make_process_context(transport.clone());
+                                                let result = process_with_passthrough(
+                                                    wrapper
+                                                        .analyzer_passthrough
+                                                        .borrow_mut()
+                                                        .as_mut(),
+                                                    buffer,
+                                                    |buffer| {
+                                                        plugin.process(buffer, aux, &mut context)
+                                                    },
+                                                );
+                                                drop(context);
+
+                                                if !process.out_events.is_null() {
+                                                    wrapper.handle_out_events(
+                                                        &*process.out_events,
+                                                        (block_start + output_offset)
+                                                            .min(total_buffer_len - 1),
+                                                        total_buffer_len,
+                                                    );
+                                                }
+
+                                                result
+                                            },
+                                        ),
+                                        None => {
+                                            wrapper.cv_inputs.copy_inputs(
+                                                aux.inputs,
+                                                buffers.main_buffer.samples(),
+                                            );
+                                            wrapper
+                                                .flush_param_output(buffers.main_buffer.samples());
+                                            let mut context =
+                                                wrapper.make_process_context(transport);
+                                            process_with_passthrough(
+                                                wrapper.analyzer_passthrough.borrow_mut().as_mut(),
+                                                buffers.main_buffer,
+                                                |buffer| {
+                                                    plugin.process(buffer, &mut aux, &mut context)
+                                                },
+                                            )
+                                        }
+                                    }
+                                })
+                        });
+                        if result.is_none() {
+                            panic_guard::rescue_state(&mut *plugin, || wrapper.get_state_object());
+                        }
+
+                        result
+                    };
+
+                    match result {
+                        Some(result) => {
+                            if let (Some(crossfader), Some(bypass_param)) =
+                                (bypass_crossfader.as_mut(), wrapper.bypass_param)
+                            {
+                                crossfader.apply(
+                                    buffers.main_buffer,
+                                    bypass_crossfade::is_bypassed(bypass_param),
+                                );
+                            }
+
+                            wrapper.last_process_status.store(result);
+                            silence_skipper.update(result, || {
+                                silence_skip::output_is_silent(buffers.main_buffer, aux.outputs)
+                            });
+                            result
+                        }
+                        None => {
+                            panic_guard::silence_outputs(buffers.main_buffer, aux.outputs);
+                            ProcessStatus::Normal
+                        }
+                    }
                 } else {
                     ProcessStatus::Normal
                 };
@@ -2317,6 +2861,10 @@ impl<P: ClapPlugin> Wrapper<P> {
             &wrapper.clap_plugin_audio_ports_config as *const _ as *const c_void
         } else if id == CLAP_EXT_AUDIO_PORTS {
             &wrapper.clap_plugin_audio_ports as *const _ as *const c_void
+        } else if id == CLAP_EXT_AMBISONIC && Self::has_channel_layout(|l| l.speakers().is_none()) {
+            &wrapper.clap_plugin_ambisonic as *const _ as *const c_void
+        } else if id == CLAP_EXT_GAIN_ADJUSTMENT_METERING && P::CLAP_GAIN_REDUCTION_METERING {
+            &wrapper.clap_plugin_gain_adjustment_metering as *const _ as *const c_void
         } else if id == CLAP_EXT_GUI && wrapper.editor.borrow().is_some() {
             // Only report that we support this extension if the plugin has an editor
             &wrapper.clap_plugin_gui as *const _ as *const c_void
@@ -2328,14 +2876,24 @@ impl<P: ClapPlugin> Wrapper<P> {
             &wrapper.clap_plugin_note_ports as *const _ as *const c_void
         } else if id == CLAP_EXT_PARAMS {
             &wrapper.clap_plugin_params as *const _ as *const c_void
+        } else if id == CLAP_EXT_PRESET_LOAD && !P::FACTORY_PRESETS.is_empty() {
+            &wrapper.clap_plugin_preset_load as *const _ as *const c_void
         } else if id == CLAP_EXT_REMOTE_CONTROLS {
             &wrapper.clap_plugin_remote_controls as *const _ as *const c_void
         } else if id == CLAP_EXT_RENDER {
             &wrapper.clap_plugin_render as *const _ as *const c_void
         } else if id == CLAP_EXT_STATE {
             &wrapper.clap_plugin_state as *const _ as *const c_void
+        } else if id == CLAP_EXT_SURROUND
+            && Self::has_channel_layout(|l| surround_channel_mask(l).is_some())
+        {
+            // Mono and stereo ports also get a channel map, but there's no need to advertise the
+            // extension if those are the only layouts
+            &wrapper.clap_plugin_surround as *const _ as *const c_void
         } else if id == CLAP_EXT_TAIL {
             &wrapper.clap_plugin_tail as *const _ as *const c_void
+        } else if id == CLAP_EXT_THREAD_POOL {
+            &wrapper.clap_plugin_thread_pool as *const _ as *const c_void
         } else if id == CLAP_EXT_VOICE_INFO && P::CLAP_POLY_MODULATION_CONFIG.is_some() {
             &wrapper.clap_plugin_voice_info as *const _ as *const c_void
         } else {
@@ -2377,16 +2935,8 @@ impl<P: ClapPlugin> Wrapper<P> {
                 let main_input_channels = audio_io_layout.main_input_channels.map(NonZeroU32::get);
                 let main_output_channels =
                     audio_io_layout.main_output_channels.map(NonZeroU32::get);
-                let input_port_type = match main_input_channels {
-                    Some(1) => CLAP_PORT_MONO.as_ptr(),
-                    Some(2) => CLAP_PORT_STEREO.as_ptr(),
-                    _ => std::ptr::null(),
-                };
-                let output_port_type = match main_output_channels {
-                    Some(1) => CLAP_PORT_MONO.as_ptr(),
-                    Some(2) => CLAP_PORT_STEREO.as_ptr(),
-                    _ => std::ptr::null(),
-                };
+                let input_port_type = port_type(audio_io_layout.main_input_channel_layout());
+                let output_port_type = port_type(audio_io_layout.main_output_channel_layout());
 
                 *config = std::mem::zeroed();
 
@@ -2532,11 +3082,11 @@ impl<P: ClapPlugin> Wrapper<P> {
             (n, false) => current_audio_io_layout.aux_output_ports[n as usize].get(),
         };
 
-        let port_type = match channel_count {
-            1 => CLAP_PORT_MONO.as_ptr(),
-            2 => CLAP_PORT_STEREO.as_ptr(),
-            _ => std::ptr::null(),
-        };
+        let port_type = port_type(Self::port_channel_layout(
+            &current_audio_io_layout,
+            is_input,
+            index,
+        ));
 
         *info = std::mem::zeroed();
 
@@ -2576,6 +3126,81 @@ impl<P: ClapPlugin> Wrapper<P> {
         true
     }
 
+    /// Whether any of the plugin's audio IO layouts contains a port with a channel layout that
+    /// matches the predicate.
+    fn has_channel_layout(predicate: impl Fn(ChannelLayout) -> bool) -> bool {
+        P::AUDIO_IO_LAYOUTS.iter().any(|layout| {
+            let channel_layouts = &layout.channel_layouts;
+            channel_layouts
+                .main_input
+                .iter()
+                .chain(channel_layouts.main_output.iter())
+                .chain(channel_layouts.aux_inputs)
+                .chain(channel_layouts.aux_outputs)
+                .any(|&channel_layout| predicate(channel_layout))
+        })
+    }
+
+    /// The channel layout for the CLAP audio port with the given index. The main port, if the
+    /// layout has one, always comes first.
+    fn port_channel_layout(
+        audio_io_layout: &AudioIOLayout,
+        is_input: bool,
+        index: u32,
+    ) -> Option<ChannelLayout> {
+        let index = index as usize;
+        match (index, is_input) {
+            (0, true) if audio_io_layout.main_input_channels.is_some() => {
+                audio_io_layout.main_input_channel_layout()
+            }
+            (0, false) if audio_io_layout.main_output_channels.is_some() => {
+                audio_io_layout.main_output_channel_layout()
+            }
+            (n, true) if audio_io_layout.main_input_channels.is_some() => {
+                audio_io_layout.aux_input_channel_layout(n - 1)
+            }
+            (n, false) if audio_io_layout.main_output_channels.is_some() => {
+                audio_io_layout.aux_output_channel_layout(n - 1)
+            }
+            (n, true) => audio_io_layout.aux_input_channel_layout(n),
+            (n, false) => audio_io_layout.aux_output_channel_layout(n),
+        }
+    }
+
+    unsafe extern "C" fn ext_ambisonic_is_config_supported(
+        plugin: *const clap_plugin,
+        config: *const clap_ambisonic_config,
+    ) -> bool {
+        check_null_ptr!(false, plugin, (*plugin).plugin_data, config);
+
+        // All of NIH-plug's ambisonic layouts use AmbiX
+        (*config).ordering == CLAP_AMBISONIC_ORDERING_ACN
+            && (*config).normalization == CLAP_AMBISONIC_NORMALIZATION_SN3D
+    }
+
+    unsafe extern "C" fn ext_ambisonic_get_config(
+        plugin: *const clap_plugin,
+        is_input: bool,
+        port_index: u32,
+        config: *mut clap_ambisonic_config,
+    ) -> bool {
+        check_null_ptr!(false, plugin, (*plugin).plugin_data, config);
+        let wrapper = &*((*plugin).plugin_data as *const Self);
+
+        let current_audio_io_layout = wrapper.current_audio_io_layout.load();
+        match Self::port_channel_layout(&current_audio_io_layout, is_input, port_index) {
+            Some(channel_layout) if channel_layout.ambisonic_order().is_some() => {
+                *config = clap_ambisonic_config {
+                    ordering: CLAP_AMBISONIC_ORDERING_ACN,
+                    normalization: CLAP_AMBISONIC_NORMALIZATION_SN3D,
+                };
+
+                true
+            }
+            _ => false,
+        }
+    }
+
     unsafe extern "C" fn ext_gui_is_api_supported(
         _plugin: *const clap_plugin,
         api: *const c_char,
@@ -2787,12 +3412,24 @@ impl<P: ClapPlugin> Wrapper<P> {
                 };
 
                 // This extension is only exposed when we have an editor
-                *editor_handle = Some(wrapper.editor.borrow().as_ref().unwrap().lock().spawn(
-                    ParentWindowHandle { handle },
-                    wrapper.clone().make_gui_context(),
-                ));
-
-                true
+                let spawned = panic_guard::catch_panic::<P, _>(|| {
+                    wrapper.editor.borrow().as_ref().unwrap().lock().spawn(
+                        ParentWindowHandle { handle },
+                        wrapper.clone().make_gui_context(),
+                    )
+                });
+                match spawned {
+                    Some(editor) => {
+                        *editor_handle = Some(editor);
+                        true
+                    }
+                    None => {
+                        panic_guard::rescue_state(&mut *wrapper.plugin.lock(), || {
+                            wrapper.get_state_object()
+                        });
+                        false
+                    }
+                }
             } else {
                 nih_debug_assert_failure!(
                     "Host tried to attach editor while the editor is already attached"
@@ -2835,7 +3472,8 @@ impl<P: ClapPlugin> Wrapper<P> {
         check_null_ptr!(0, plugin, (*plugin).plugin_data);
         let wrapper = &*((*plugin).plugin_data as *const Self);
 
-        wrapper.current_latency.load(Ordering::SeqCst)
+        // The buffering for `P::FIXED_BLOCK_SIZE` adds another block of latency
+        wrapper.current_latency.load(Ordering::SeqCst) + P::FIXED_BLOCK_SIZE.unwrap_or(0)
     }
 
     unsafe extern "C" fn ext_note_ports_count(_plugin: *const clap_plugin, is_input: bool) -> u32 {
@@ -2985,13 +3623,13 @@ impl<P: ClapPlugin> Wrapper<P> {
 
         match wrapper.param_by_hash.get(&param_id) {
             Some(param_ptr) => {
-                strlcpy(
-                    dest,
-                    // CLAP does not have a separate unit, so we'll include the unit here
-                    &param_ptr.normalized_value_to_string(
-                        value as f32 / param_ptr.step_count().unwrap_or(1) as f32,
-                        true,
-                    ),
+                // CLAP does not have a separate unit, so we'll include the unit here. This writes
+                // directly to the host's buffer. Values that don't fit are truncated, so the
+                // result can be ignored.
+                let _ = param_ptr.normalized_value_to_buf(
+                    value as f32 / param_ptr.step_count().unwrap_or(1) as f32,
+                    true,
+                    &mut CStrWriter::new(dest),
                 );
 
                 true
@@ -3045,6 +3683,52 @@ impl<P: ClapPlugin> Wrapper<P> {
         }
     }
 
+    unsafe extern "C" fn ext_preset_load_from_location(
+        plugin: *const clap_plugin,
+        location_kind: u32,
+        _location: *const c_char,
+        load_key: *const c_char,
+    ) -> bool {
+        check_null_ptr!(false, plugin, (*plugin).plugin_data, load_key);
+        let wrapper = &*((*plugin).plugin_data as *const Self);
+
+        // The only presets we expose through the preset discovery factory are the plugin's factory
+        // presets, and those use the preset's index as the load key
+        if location_kind != CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN {
+            nih_debug_assert_failure!(
+                "Host tried to load a preset from an unknown location kind {}",
+                location_kind
+            );
+            return false;
+        }
+
+        let load_key = CStr::from_ptr(load_key);
+        let preset = load_key
+            .to_str()
+            .ok()
+            .and_then(|key| key.parse::<usize>().ok())
+            .and_then(|preset_idx| P::FACTORY_PRESETS.get(preset_idx));
+        let preset = match preset {
+            Some(preset) => preset,
+            None => {
+                nih_debug_assert_failure!("Host tried to load an unknown preset {:?}", load_key);
+                return false;
+            }
+        };
+
+        match preset.state() {
+            Some(mut state) => {
+                let success = wrapper.set_state_inner(&mut state);
+                if success {
+                    nih_trace!("Loaded factory preset '{}'", preset.name);
+                }
+
+                success
+            }
+            None => false,
+        }
+    }
+
     unsafe extern "C" fn ext_remote_controls_count(plugin: *const clap_plugin) -> u32 {
         check_null_ptr!(0, plugin, (*plugin).plugin_data);
         let wrapper = &*((*plugin).plugin_data as *const Self);
@@ -3107,6 +3791,7 @@ impl<P: ClapPlugin> Wrapper<P> {
         let serialized = state::serialize_json::<P>(
             wrapper.params.clone(),
             state::make_params_iter(&wrapper.param_by_hash, &wrapper.param_id_to_hash),
+            Some(&wrapper.midi_learn),
         );
         match serialized {
             Ok(serialized) => {
@@ -3128,6 +3813,18 @@ impl<P: ClapPlugin> Wrapper<P> {
 
                 nih_trace!("Saved state ({} bytes)", serialized.len());
 
+                // Large `#[persist_streamed]` fields are written directly to the stream after the
+                // regular state
+                if !wrapper.params.streamed_fields().is_empty() {
+                    if let Err(err) = state::write_streamed_fields(
+                        wrapper.params.as_ref(),
+                        &mut OStreamWriter(&*stream),
+                    ) {
+                        nih_debug_assert_failure!("Could not save streamed fields: {:#}", err);
+                        return false;
+                    }
+                }
+
                 true
             }
             Err(err) => {
@@ -3164,11 +3861,36 @@ impl<P: ClapPlugin> Wrapper<P> {
         }
         read_buffer.set_len(length as usize);
 
+        // If the plugin has any `#[persist_streamed]` fields, then those will follow the regular
+        // state. Older states and states for plugins without streamed fields end here.
+        let mut streamed_fields = Vec::new();
+        let mut magic = [0u8; state::STREAMED_FIELDS_MAGIC.len()];
+        if read_stream(&*stream, magic.as_mut_slice()) && magic == state::STREAMED_FIELDS_MAGIC {
+            // The streamed fields are read into memory first so their lengths can be checked
+            // against the amount of data that's actually there
+            let mut streamed_buffer = Vec::new();
+            if let Err(err) = IStreamReader(&*stream).read_to_end(&mut streamed_buffer) {
+                nih_debug_assert_failure!("Could not read streamed fields: {}", err);
+                return false;
+            }
+
+            match state::read_streamed_fields(&streamed_buffer) {
+                Ok(fields) => streamed_fields = fields,
+                Err(err) => {
+                    nih_debug_assert_failure!("Could not read streamed fields: {:#}", err);
+                    return false;
+                }
+            }
+        }
+
         match state::deserialize_json(&read_buffer) {
             Some(mut state) => {
                 let success = wrapper.set_state_inner(&mut state);
                 if success {
                     nih_trace!("Loaded state ({} bytes)", read_buffer.len());
+                    wrapper
+                        .streamed_fields_loader
+                        .load(wrapper.params.clone(), streamed_fields);
                 }
 
                 success
@@ -3177,6 +3899,45 @@ impl<P: ClapPlugin> Wrapper<P> {
         }
     }
 
+    unsafe extern "C" fn ext_surround_is_channel_mask_supported(
+        plugin: *const clap_plugin,
+        channel_mask: u64,
+    ) -> bool {
+        check_null_ptr!(false, plugin, (*plugin).plugin_data);
+
+        Self::has_channel_layout(|channel_layout| {
+            surround_channel_mask(channel_layout) == Some(channel_mask)
+        })
+    }
+
+    unsafe extern "C" fn ext_surround_get_channel_map(
+        plugin: *const clap_plugin,
+        is_input: bool,
+        port_index: u32,
+        channel_map: *mut u8,
+        channel_map_capacity: u32,
+    ) -> u32 {
+        check_null_ptr!(0, plugin, (*plugin).plugin_data, channel_map);
+        let wrapper = &*((*plugin).plugin_data as *const Self);
+
+        let current_audio_io_layout = wrapper.current_audio_io_layout.load();
+        let speakers =
+            match Self::port_channel_layout(&current_audio_io_layout, is_input, port_index)
+                .and_then(|channel_layout| channel_layout.speakers())
+            {
+                Some(speakers) => speakers,
+                None => return 0,
+            };
+
+        let channel_map =
+            std::slice::from_raw_parts_mut(channel_map, channel_map_capacity as usize);
+        for (speaker_id, &speaker) in channel_map.iter_mut().zip(speakers) {
+            *speaker_id = surround_speaker_id(speaker);
+        }
+
+        speakers.len().min(channel_map_capacity as usize) as u32
+    }
+
     unsafe extern "C" fn ext_tail_get(plugin: *const clap_plugin) -> u32 {
         check_null_ptr!(0, plugin, (*plugin).plugin_data);
         let wrapper = &*((*plugin).plugin_data as *const Self);
@@ -3188,6 +3949,30 @@ impl<P: ClapPlugin> Wrapper<P> {
         }
     }
 
+    unsafe extern "C" fn ext_gain_adjustment_metering_get(plugin: *const clap_plugin) -> f64 {
+        check_null_ptr!(0.0, plugin, (*plugin).plugin_data);
+        let wrapper = &*((*plugin).plugin_data as *const Self);
+
+        // CLAP expects the gain adjustment, so gain reduction is a negative value
+        -wrapper.gain_reduction_db.load(Ordering::Relaxed) as f64
+    }
+
+    unsafe extern "C" fn ext_thread_pool_exec(plugin: *const clap_plugin, task_index: u32) {
+        check_null_ptr!((), plugin, (*plugin).plugin_data);
+        let wrapper = &*((*plugin).plugin_data as *const Self);
+
+        // This may be called from the host's worker threads, so FTZ needs to be enabled there as
+        // well
+        process_wrapper("clap_plugin_thread_pool::exec()", || {
+            match &*wrapper.parallel_task.borrow() {
+                Some(ParallelTask(task)) => (**task)(task_index),
+                None => nih_debug_assert_failure!(
+                    "The host called 'clap_plugin_thread_pool::exec()' without a pending request"
+                ),
+            }
+        });
+    }
+
     unsafe extern "C" fn ext_voice_info_get(
         plugin: *const clap_plugin,
         info: *mut clap_voice_info,
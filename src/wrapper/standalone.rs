@@ -12,6 +12,8 @@ use crate::prelude::Plugin;
 mod backend;
 mod config;
 mod context;
+#[cfg(feature = "http_api")]
+mod http_api;
 mod wrapper;
 
 /// Open an NIH-plug plugin as a standalone application. If the plugin has an editor, this will open
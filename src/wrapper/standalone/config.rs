@@ -93,6 +93,46 @@ pub struct WrapperConfig {
     /// The time signature's denominator.
     #[clap(value_parser, long, default_value = "4")]
     pub timesig_denom: u32,
+
+    /// If set, the plugin's parameters can be controlled over OSC using this UDP port.
+    ///
+    /// See the documentation for `nih_plug::osc::OscBridge` for the supported addresses.
+    #[cfg(feature = "osc")]
+    #[clap(value_parser, long)]
+    pub osc_port: Option<u16>,
+
+    /// The address the OSC bridge listens on.
+    ///
+    /// This only accepts messages from this machine by default. Anyone who can send messages to the
+    /// bridge can change the plugin's parameters, so only use '0.0.0.0' on trusted networks.
+    #[cfg(feature = "osc")]
+    #[clap(value_parser, long, default_value = "127.0.0.1")]
+    pub osc_address: std::net::IpAddr,
+
+    /// Don't open the plugin's editor, even if it has one.
+    ///
+    /// Use this together with the network control options when running the plugin on a machine
+    /// without a display. The application runs until it is terminated.
+    #[clap(value_parser, long)]
+    pub headless: bool,
+
+    /// If set, the plugin's parameters and presets can be controlled through an HTTP API on this
+    /// port.
+    ///
+    /// Supports 'GET /params', 'GET /params/<id>', 'PUT /params/<id>' with a '{"value": 0.5}'
+    /// body, 'GET /state' and 'PUT /state' using the plugin's JSON state format, 'GET /presets',
+    /// and 'POST /presets/<index>'. All parameter values are normalized values.
+    #[cfg(feature = "http_api")]
+    #[clap(value_parser, long)]
+    pub http_port: Option<u16>,
+
+    /// The address the HTTP API listens on.
+    ///
+    /// This only accepts connections from this machine by default. Anyone who can connect to the
+    /// API can change the plugin's state, so only use '0.0.0.0' on trusted networks.
+    #[cfg(feature = "http_api")]
+    #[clap(value_parser, long, default_value = "127.0.0.1")]
+    pub http_address: std::net::IpAddr,
 }
 
 /// Determines which audio and MIDI backend should be used.
@@ -1,10 +1,13 @@
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use super::backend::Backend;
 use super::wrapper::{Task, Wrapper};
+use crate::midi::learn::MidiLearn;
 use crate::prelude::{
-    GuiContext, InitContext, ParamPtr, Plugin, PluginApi, PluginNoteEvent, ProcessContext,
-    Transport,
+    BufferConfig, FactoryPreset, GuiContext, HostInfo, InitContext, ParamPtr, Plugin, PluginApi,
+    PluginNoteEvent, ProcessContext, ProcessTimings, StateSlot, TailLength, Transport,
+    TransportSnapshot,
 };
 
 /// An [`InitContext`] implementation for the standalone wrapper.
@@ -16,7 +19,6 @@ pub(crate) struct WrapperInitContext<'a, P: Plugin, B: Backend<P>> {
 /// can hold on to lock guards for event queues. Otherwise reading these events would require
 /// constant unnecessary atomic operations to lock the uncontested `RwLock`s.
 pub(crate) struct WrapperProcessContext<'a, P: Plugin, B: Backend<P>> {
-    #[allow(dead_code)]
     pub(super) wrapper: &'a Wrapper<P, B>,
     pub(super) input_events: &'a [PluginNoteEvent<P>],
     // The current index in `input_events`, since we're not actually popping anything from a queue
@@ -41,6 +43,12 @@ impl<P: Plugin, B: Backend<P>> InitContext<P> for WrapperInitContext<'_, P, B> {
         PluginApi::Standalone
     }
 
+    fn host_info(&self) -> HostInfo {
+        // The standalone target is its own host, and it doesn't support latency reporting or
+        // parallel execution
+        HostInfo::default()
+    }
+
     fn execute(&self, task: P::BackgroundTask) {
         (self.wrapper.task_executor.lock())(task);
     }
@@ -90,6 +98,15 @@ impl<P: Plugin, B: Backend<P>> ProcessContext<P> for WrapperProcessContext<'_, P
         self.output_events.push(event);
     }
 
+    #[inline]
+    fn next_gui_message(&mut self) -> Option<P::GuiMessage> {
+        self.wrapper.gui_messages.pop()
+    }
+
+    fn send_to_editor(&self, event: P::EditorEvent) {
+        self.wrapper.editor_events.force_push(event);
+    }
+
     fn set_latency_samples(&self, samples: u32) {
         self.wrapper.set_latency_samples(samples)
     }
@@ -97,6 +114,17 @@ impl<P: Plugin, B: Backend<P>> ProcessContext<P> for WrapperProcessContext<'_, P
     fn set_current_voice_capacity(&self, _capacity: u32) {
         // This is only supported by CLAP
     }
+
+    unsafe fn raw_begin_set_parameter(&mut self, _param: ParamPtr) {
+        // Since there's no automation being recorded here, gestures don't mean anything
+    }
+
+    unsafe fn raw_set_parameter_normalized(&mut self, param: ParamPtr, normalized: f32) {
+        // This is applied at the end of the processing cycle
+        self.wrapper.set_parameter(param, normalized);
+    }
+
+    unsafe fn raw_end_set_parameter(&mut self, _param: ParamPtr) {}
 }
 
 impl<P: Plugin, B: Backend<P>> GuiContext for WrapperGuiContext<P, B> {
@@ -159,4 +187,53 @@ impl<P: Plugin, B: Backend<P>> GuiContext for WrapperGuiContext<P, B> {
     fn set_state(&self, state: crate::wrapper::state::PluginState) {
         self.wrapper.set_state_object_from_gui(state)
     }
+
+    fn factory_presets(&self) -> &'static [FactoryPreset] {
+        P::FACTORY_PRESETS
+    }
+
+    fn active_state_slot(&self) -> StateSlot {
+        self.wrapper.state_slots.active_slot()
+    }
+
+    fn switch_state_slot(&self, slot: StateSlot) {
+        let new_state = self
+            .wrapper
+            .state_slots
+            .switch(slot, || self.wrapper.get_state_object());
+        if let Some(new_state) = new_state {
+            self.wrapper.set_state_object_from_gui(new_state);
+        }
+    }
+
+    fn copy_state_to_other_slot(&self) {
+        self.wrapper
+            .state_slots
+            .copy_to_other_slot(self.wrapper.get_state_object());
+    }
+
+    fn midi_learn(&self) -> &MidiLearn {
+        &self.wrapper.midi_learn
+    }
+
+    fn buffer_config(&self) -> Option<BufferConfig> {
+        Some(self.wrapper.buffer_config)
+    }
+
+    fn latency_samples(&self) -> u32 {
+        self.wrapper.current_latency.load(Ordering::SeqCst)
+    }
+
+    fn tail(&self) -> TailLength {
+        // The standalone wrapper doesn't have a notion of tails
+        TailLength::None
+    }
+
+    fn transport(&self) -> Option<TransportSnapshot> {
+        self.wrapper.last_transport.load()
+    }
+
+    fn process_timings(&self) -> Option<ProcessTimings> {
+        self.wrapper.process_timings.statistics()
+    }
 }
@@ -0,0 +1,365 @@
+//! A small HTTP API for controlling a standalone plugin's parameters and presets over the network.
+//! This is mostly useful in combination with `--headless` for installations and embedded devices.
+//!
+//! Only plain HTTP requests are supported. There is no WebSocket API, so clients that want to
+//! follow parameter changes need to poll `GET /params`.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::prelude::{GuiContext, ParamPtr, Params, PluginState};
+
+/// The maximum size of a `PUT /state` request's body. Larger requests are rejected without reading
+/// the body.
+const MAX_STATE_BODY_SIZE: usize = 16 * 1024 * 1024;
+/// The maximum size of a `PUT /params/<id>` request's body.
+const MAX_PARAM_BODY_SIZE: usize = 4096;
+
+/// Serves the HTTP API on a separate thread until this object is dropped. The API supports the
+/// following requests. All parameter values are normalized `[0, 1]` values.
+///
+/// - `GET /params` lists all parameters as [`ParamInfo`] objects.
+/// - `GET /params/<id>` returns a single [`ParamInfo`] object.
+/// - `PUT /params/<id>` sets a parameter. The body should be a [`ParamUpdate`] object like
+///   `{ "value": 0.5 }`.
+/// - `GET /state` returns the plugin's current state in the same JSON format used for presets.
+/// - `PUT /state` restores the plugin's state from that same JSON format.
+/// - `GET /presets` lists the plugin's factory presets as `{ "index": 0, "name": "Init" }` objects.
+/// - `POST /presets/<index>` loads a factory preset.
+///
+/// Request bodies larger than 16 MiB are rejected with a `413` status code.
+pub struct HttpApi {
+    server: Arc<Server>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// The state used by the API's thread.
+struct ApiThread {
+    server: Arc<Server>,
+    context: Arc<dyn GuiContext>,
+    /// The plugin's parameter map, as returned by [`Params::param_map()`].
+    param_map: Vec<(String, ParamPtr, String)>,
+}
+
+/// A parameter as returned by the API.
+#[derive(Serialize)]
+struct ParamInfo<'a> {
+    id: &'a str,
+    name: &'a str,
+    /// The parameter's group, or an empty string for top level parameters.
+    group: &'a str,
+    value: f32,
+    default_value: f32,
+    /// The parameter's current value formatted as a string, including the unit.
+    display: String,
+    /// The number of steps for discrete parameters, or `null` for continuous parameters.
+    step_count: Option<usize>,
+}
+
+/// The body for a `PUT /params/<id>` request.
+#[derive(Deserialize)]
+struct ParamUpdate {
+    value: f32,
+}
+
+/// A factory preset as returned by the API.
+#[derive(Serialize)]
+struct PresetInfo {
+    index: usize,
+    name: &'static str,
+}
+
+/// A response's status code and JSON body.
+type ApiResponse = (u16, String);
+
+impl HttpApi {
+    /// Start serving the API on the specified address and port. Anyone who can reach this address
+    /// can change the plugin's parameters and state, so this should be a loopback address unless
+    /// the user explicitly asked for something else.
+    pub fn spawn(
+        address: IpAddr,
+        port: u16,
+        params: Arc<dyn Params>,
+        context: Arc<dyn GuiContext>,
+    ) -> Result<Self> {
+        let server = Arc::new(
+            Server::http((address, port))
+                .map_err(|err| anyhow!("Could not start the HTTP server: {err}"))?,
+        );
+
+        let api_thread = ApiThread {
+            server: server.clone(),
+            context,
+            param_map: params.param_map(),
+        };
+        let thread = thread::Builder::new()
+            .name(String::from("http-api"))
+            .spawn(move || api_thread.run())?;
+
+        Ok(Self {
+            server,
+            thread: Some(thread),
+        })
+    }
+
+    /// The address the API is being served on. This is useful when the API was spawned on port 0.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+}
+
+impl Drop for HttpApi {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(thread) = self.thread.take() {
+            thread.join().expect("The HTTP API thread panicked");
+        }
+    }
+}
+
+impl ApiThread {
+    fn run(self) {
+        for mut request in self.server.incoming_requests() {
+            let (status_code, body) = self.handle_request(&mut request);
+            let response = Response::from_string(body)
+                .with_status_code(status_code)
+                .with_header(
+                    Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap(),
+                );
+
+            if let Err(err) = request.respond(response) {
+                nih_trace!("Could not respond to an HTTP API request: {err}");
+            }
+        }
+    }
+
+    fn handle_request(&self, request: &mut Request) -> ApiResponse {
+        let path = request
+            .url()
+            .split('?')
+            .next()
+            .unwrap_or_default()
+            .to_owned();
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        let method = request.method().clone();
+
+        match (&method, segments.as_slice()) {
+            (Method::Get, ["params"]) => {
+                let params: Vec<ParamInfo> = self
+                    .param_map
+                    .iter()
+                    .map(|(param_id, param_ptr, group)| param_info(param_id, param_ptr, group))
+                    .collect();
+
+                json_response(&params)
+            }
+            (Method::Get, ["params", param_id]) => match self.find_param(param_id) {
+                Some((param_id, param_ptr, group)) => {
+                    json_response(&param_info(param_id, param_ptr, group))
+                }
+                None => error_response(404, "Unknown parameter"),
+            },
+            (Method::Put, ["params", param_id]) => {
+                let Some((param_id, param_ptr, group)) = self.find_param(param_id) else {
+                    return error_response(404, "Unknown parameter");
+                };
+                let json = match read_body(request, MAX_PARAM_BODY_SIZE) {
+                    Ok(json) => json,
+                    Err(response) => return response,
+                };
+                let update: ParamUpdate = match serde_json::from_str(&json) {
+                    Ok(update) => update,
+                    Err(err) => return error_response(400, &err.to_string()),
+                };
+
+                unsafe {
+                    self.context.raw_begin_set_parameter(*param_ptr);
+                    self.context
+                        .raw_set_parameter_normalized(*param_ptr, update.value.clamp(0.0, 1.0));
+                    self.context.raw_end_set_parameter(*param_ptr);
+                }
+
+                json_response(&param_info(param_id, param_ptr, group))
+            }
+            (Method::Get, ["state"]) => match self.context.get_state().to_json() {
+                Ok(json) => (200, json),
+                Err(err) => error_response(500, &format!("{err:#}")),
+            },
+            (Method::Put, ["state"]) => {
+                let json = match read_body(request, MAX_STATE_BODY_SIZE) {
+                    Ok(json) => json,
+                    Err(response) => return response,
+                };
+
+                match PluginState::from_json(&json) {
+                    Ok(state) => {
+                        self.context.set_state(state);
+                        (204, String::new())
+                    }
+                    Err(err) => error_response(400, &format!("{err:#}")),
+                }
+            }
+            (Method::Get, ["presets"]) => {
+                let presets: Vec<PresetInfo> = self
+                    .context
+                    .factory_presets()
+                    .iter()
+                    .enumerate()
+                    .map(|(index, preset)| PresetInfo {
+                        index,
+                        name: preset.name,
+                    })
+                    .collect();
+
+                json_response(&presets)
+            }
+            (Method::Post, ["presets", index]) => match index.parse() {
+                Ok(index) if self.context.load_factory_preset(index) => (204, String::new()),
+                _ => error_response(404, "Unknown preset"),
+            },
+            (_, ["params"] | ["params", _] | ["state"] | ["presets"] | ["presets", _]) => {
+                error_response(405, "Method not allowed")
+            }
+            _ => error_response(404, "Not found"),
+        }
+    }
+
+    fn find_param(&self, param_id: &str) -> Option<&(String, ParamPtr, String)> {
+        self.param_map.iter().find(|(id, _, _)| id == param_id)
+    }
+}
+
+fn param_info<'a>(param_id: &'a str, param_ptr: &'a ParamPtr, group: &'a str) -> ParamInfo<'a> {
+    unsafe {
+        let value = param_ptr.unmodulated_normalized_value();
+
+        ParamInfo {
+            id: param_id,
+            name: param_ptr.name(),
+            group,
+            value,
+            default_value: param_ptr.default_normalized_value(),
+            display: param_ptr.normalized_value_to_string(value, true),
+            step_count: param_ptr.step_count(),
+        }
+    }
+}
+
+/// Read a request's body, rejecting bodies larger than `max_size` bytes. The `Content-Length`
+/// header is checked first so oversized bodies don't need to be read at all, and the reader is
+/// capped in case the header is missing or wrong.
+fn read_body(request: &mut Request, max_size: usize) -> std::result::Result<String, ApiResponse> {
+    if request.body_length().is_some_and(|len| len > max_size) {
+        return Err(error_response(413, "Request body too large"));
+    }
+
+    let mut body = String::new();
+    if let Err(err) = request
+        .as_reader()
+        .take(max_size as u64 + 1)
+        .read_to_string(&mut body)
+    {
+        return Err(error_response(400, &err.to_string()));
+    }
+    if body.len() > max_size {
+        return Err(error_response(413, "Request body too large"));
+    }
+
+    Ok(body)
+}
+
+fn json_response<T: Serialize + ?Sized>(value: &T) -> ApiResponse {
+    match serde_json::to_string(value) {
+        Ok(json) => (200, json),
+        Err(err) => error_response(500, &err.to_string()),
+    }
+}
+
+fn error_response(status_code: u16, message: &str) -> ApiResponse {
+    (
+        status_code,
+        serde_json::json!({ "error": message }).to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::{Ipv4Addr, Shutdown, TcpStream};
+
+    use crate::prelude::*;
+    use crate::test::fixtures::{TestParams, TestPlugin};
+    use crate::test::MockGuiContext;
+
+    fn spawn_api() -> (HttpApi, Arc<TestParams>) {
+        let params = Arc::new(TestParams::default());
+        let context = Arc::new(MockGuiContext::<TestPlugin>::new(params.clone()));
+        let api = HttpApi::spawn(Ipv4Addr::LOCALHOST.into(), 0, params.clone(), context).unwrap();
+
+        (api, params)
+    }
+
+    /// Send a raw HTTP request to the API and return the raw response.
+    fn send_request(api: &HttpApi, request: &str) -> String {
+        let mut stream = TcpStream::connect(api.local_addr().unwrap()).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        stream.shutdown(Shutdown::Write).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn binds_requested_address() {
+        let (api, _) = spawn_api();
+        assert!(api.local_addr().unwrap().ip().is_loopback());
+    }
+
+    #[test]
+    fn set_parameter() {
+        let (api, params) = spawn_api();
+        let body = r#"{ "value": 0.25 }"#;
+        let response = send_request(
+            &api,
+            &format!(
+                "PUT /params/gain HTTP/1.1\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{body}",
+                body.len()
+            ),
+        );
+
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        assert_eq!(params.gain.unmodulated_normalized_value(), 0.25);
+    }
+
+    #[test]
+    fn unknown_parameter() {
+        let (api, _) = spawn_api();
+        let response = send_request(
+            &api,
+            "GET /params/foo HTTP/1.1\r\nConnection: close\r\n\r\n",
+        );
+
+        assert!(response.starts_with("HTTP/1.1 404"), "{response}");
+    }
+
+    #[test]
+    fn rejects_oversized_state() {
+        let (api, _) = spawn_api();
+        let response = send_request(
+            &api,
+            &format!(
+                "PUT /state HTTP/1.1\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{{}}",
+                MAX_STATE_BODY_SIZE + 1
+            ),
+        );
+
+        assert!(response.starts_with("HTTP/1.1 413"), "{response}");
+    }
+}
@@ -1,11 +1,13 @@
 use atomic_refcell::AtomicRefCell;
 use baseview::{EventStatus, Window, WindowHandler, WindowOpenOptions};
+use crossbeam::atomic::AtomicCell;
 use crossbeam::channel::{self, Sender};
 use crossbeam::queue::ArrayQueue;
 use parking_lot::Mutex;
 use raw_window_handle::HasRawWindowHandle;
 use std::any::Any;
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::num::NonZeroU32;
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::Arc;
 use std::thread;
@@ -13,15 +15,28 @@ use std::thread;
 use super::backend::Backend;
 use super::config::WrapperConfig;
 use super::context::{WrapperGuiContext, WrapperInitContext, WrapperProcessContext};
+use crate::debug::realtime::LogDrainHandle;
 use crate::event_loop::{EventLoop, MainThreadExecutor, OsEventLoop};
+use crate::midi::learn::MidiLearn;
 use crate::prelude::{
-    AsyncExecutor, AudioIOLayout, BufferConfig, Editor, ParamFlags, ParamPtr, Params,
-    ParentWindowHandle, Plugin, PluginNoteEvent, ProcessMode, ProcessStatus, TaskExecutor,
-    Transport,
+    AsyncExecutor, AudioIOLayout, BufferConfig, Editor, MidiConfig, NoteEvent, ParamFlags,
+    ParamPtr, Params, ParentWindowHandle, Plugin, PluginNoteEvent, ProcessMode, ProcessStatus,
+    TaskExecutor, Transport, TransportSnapshot,
 };
 use crate::util::permit_alloc;
 use crate::wrapper::state::{self, PluginState};
-use crate::wrapper::util::process_wrapper;
+use crate::wrapper::util::analyzer_passthrough::{process_with_passthrough, AnalyzerPassthrough};
+use crate::wrapper::util::bypass_crossfade::{self, BypassCrossfader};
+use crate::wrapper::util::cv_inputs::CvInputs;
+use crate::wrapper::util::fixed_block::FixedBlockAdapter;
+use crate::wrapper::util::panic_guard;
+use crate::wrapper::util::process_timings::ProcessTimingCollector;
+use crate::wrapper::util::silence_skip::{self, SilenceSkipper};
+use crate::wrapper::util::state_slots::StateSlots;
+use crate::wrapper::util::transport_tracker::TransportTracker;
+use crate::wrapper::util::{
+    process_wrapper, EDITOR_EVENT_QUEUE_CAPACITY, GUI_MESSAGE_QUEUE_CAPACITY,
+};
 
 /// How many parameter changes we can store in our unprocessed parameter change queue. Storing more
 /// than this many parameters at a time will cause changes to get lost.
@@ -66,12 +81,20 @@ pub struct Wrapper<P: Plugin, B: Backend<P>> {
 
     /// The bus and buffer configurations are static for the standalone target.
     audio_io_layout: AudioIOLayout,
-    buffer_config: BufferConfig,
+    pub buffer_config: BufferConfig,
 
     /// Parameter changes that have been output by the GUI that have not yet been set in the plugin.
     /// This queue will be flushed at the end of every processing cycle, just like in the plugin
     /// versions.
     unprocessed_param_changes: ArrayQueue<(ParamPtr, f32)>,
+    /// Messages sent from the editor to the audio thread through
+    /// [`AsyncExecutor::send_to_dsp()`], received using
+    /// [`ProcessContext::next_gui_message()`][crate::prelude::ProcessContext::next_gui_message()].
+    pub gui_messages: ArrayQueue<P::GuiMessage>,
+    /// Events sent from the audio thread to the editor through
+    /// [`ProcessContext::send_to_editor()`][crate::prelude::ProcessContext::send_to_editor()],
+    /// received using [`AsyncExecutor::next_editor_event()`].
+    pub editor_events: ArrayQueue<P::EditorEvent>,
     /// The plugin is able to restore state through a method on the `GuiContext`. To avoid changing
     /// parameters mid-processing and running into garbled data if the host also tries to load state
     /// at the same time the restoring happens at the end of each processing call. If this zero
@@ -83,11 +106,24 @@ pub struct Wrapper<P: Plugin, B: Backend<P>> {
     updated_state_sender: channel::Sender<PluginState>,
     /// The receiver belonging to [`new_state_sender`][Self::new_state_sender].
     updated_state_receiver: channel::Receiver<PluginState>,
+    /// The states used for A/B comparisons through the `GuiContext`.
+    pub state_slots: StateSlots,
+    /// The MIDI CCs mapped to parameters through the `GuiContext`. See
+    /// [`apply_midi_learn()`][Self::apply_midi_learn()].
+    pub midi_learn: MidiLearn,
+    /// Measures the durations of the plugin's process calls when `P::COLLECT_PROCESS_TIMINGS` is
+    /// enabled.
+    pub process_timings: ProcessTimingCollector,
+    /// The transport information from the most recent process call, for
+    /// [`GuiContext::transport()`][crate::prelude::GuiContext::transport()].
+    pub last_transport: AtomicCell<Option<TransportSnapshot>>,
+    /// Keeps the thread alive that forwards messages logged from the audio thread to the logger.
+    _log_drain: LogDrainHandle,
     /// The current latency in samples, as set by the plugin through the [`InitContext`] and the
     /// [`ProcessContext`]. This value may not be used depending on the audio backend, but it's
     /// still kept track of to avoid firing debug assertions multiple times for the same latency
     /// value.
-    current_latency: AtomicU32,
+    pub current_latency: AtomicU32,
 }
 
 /// Tasks that can be sent from the plugin to be executed on the main thread in a non-blocking
@@ -230,6 +266,11 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
             // Also initialized later as it also needs a reference to the wrapper
             event_loop: AtomicRefCell::new(None),
 
+            midi_learn: MidiLearn::new(
+                param_map
+                    .iter()
+                    .map(|(param_id, param_ptr, _)| (param_id.clone(), *param_ptr)),
+            ),
             param_ptr_to_id: param_map
                 .iter()
                 .map(|(param_id, param_ptr, _)| (*param_ptr, param_id.clone()))
@@ -250,8 +291,14 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
             config,
 
             unprocessed_param_changes: ArrayQueue::new(EVENT_QUEUE_CAPACITY),
+            gui_messages: ArrayQueue::new(GUI_MESSAGE_QUEUE_CAPACITY),
+            editor_events: ArrayQueue::new(EDITOR_EVENT_QUEUE_CAPACITY),
             updated_state_sender,
             updated_state_receiver,
+            state_slots: StateSlots::default(),
+            process_timings: ProcessTimingCollector::default(),
+            last_transport: AtomicCell::new(None),
+            _log_drain: LogDrainHandle::get_or_create(),
             current_latency: AtomicU32::new(0),
         });
 
@@ -279,6 +326,24 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
                         nih_debug_assert!(task_posted, "The task queue is full, dropping task...");
                     }
                 }),
+                send_to_dsp: Arc::new({
+                    let wrapper = wrapper.clone();
+
+                    move |message| {
+                        let message_posted = wrapper.gui_messages.push(message).is_ok();
+                        nih_debug_assert!(
+                            message_posted,
+                            "The GUI message queue is full, dropping message..."
+                        );
+
+                        message_posted
+                    }
+                }),
+                next_editor_event: Arc::new({
+                    let wrapper = wrapper.clone();
+
+                    move || wrapper.editor_events.pop()
+                }),
             })
             .map(|editor| Arc::new(Mutex::new(editor)));
 
@@ -296,7 +361,7 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
             ) {
                 return Err(WrapperError::InitializationFailed);
             }
-            process_wrapper(|| plugin.reset());
+            process_wrapper("Plugin::reset() after initialization", || plugin.reset());
         }
 
         Ok(wrapper)
@@ -320,7 +385,60 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
             thread::spawn(move || this.run_audio_thread(terminate_audio_thread, gui_task_sender))
         };
 
-        match self.editor.borrow().clone() {
+        // The network control APIs are stopped when this function returns
+        #[cfg(feature = "osc")]
+        let _osc_bridge = match self.config.osc_port {
+            Some(port) => {
+                let bridge = crate::osc::OscBridge::spawn(
+                    (self.config.osc_address, port),
+                    self.params.clone(),
+                    self.clone().make_gui_context(),
+                );
+                match bridge {
+                    Ok(bridge) => {
+                        nih_log!("Listening for OSC messages on {}", bridge.local_addr());
+                        Some(bridge)
+                    }
+                    Err(err) => {
+                        nih_error!("Could not start the OSC bridge: {err:#}");
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+        #[cfg(feature = "http_api")]
+        let _http_api = match self.config.http_port {
+            Some(port) => {
+                let http_api = super::http_api::HttpApi::spawn(
+                    self.config.http_address,
+                    port,
+                    self.params.clone(),
+                    self.clone().make_gui_context(),
+                );
+                match http_api {
+                    Ok(http_api) => {
+                        nih_log!(
+                            "Serving the HTTP API on {}",
+                            std::net::SocketAddr::new(self.config.http_address, port)
+                        );
+                        Some(http_api)
+                    }
+                    Err(err) => {
+                        nih_error!("Could not start the HTTP API: {err:#}");
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let editor = self
+            .editor
+            .borrow()
+            .clone()
+            .filter(|_| !self.config.headless);
+        match editor {
             Some(editor) => {
                 let context = self.clone().make_gui_context();
 
@@ -366,7 +484,11 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
             None => {
                 // TODO: Properly block until SIGINT is received if the plugin does not have an editor
                 // TODO: Make sure to handle `GuiTask::Close` here as well
-                nih_log!("{} does not have a GUI, blocking indefinitely...", P::NAME);
+                if self.config.headless {
+                    nih_log!("Running {} headless, blocking indefinitely...", P::NAME);
+                } else {
+                    nih_log!("{} does not have a GUI, blocking indefinitely...", P::NAME);
+                }
                 std::thread::park();
             }
         }
@@ -408,6 +530,33 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
         push_successful
     }
 
+    /// If `event` is a MIDI CC that has been mapped to a parameter through MIDI learn, then set the
+    /// parameter. Returns `true` if the event should not be passed to the plugin.
+    fn apply_midi_learn(&self, event: &PluginNoteEvent<P>) -> bool {
+        if P::MIDI_INPUT < MidiConfig::MidiCCs {
+            return false;
+        }
+        let NoteEvent::MidiCC {
+            channel, cc, value, ..
+        } = *event
+        else {
+            return false;
+        };
+        let Some((param_ptr, normalized_value)) = self.midi_learn.handle_cc(channel, cc, value)
+        else {
+            return false;
+        };
+
+        if unsafe { param_ptr.set_normalized_value(normalized_value) } {
+            unsafe { param_ptr.update_smoother(self.buffer_config.sample_rate, false) };
+            let task_posted =
+                self.schedule_gui(Task::ParameterValueChanged(param_ptr, normalized_value));
+            nih_debug_assert!(task_posted, "The task queue is full, dropping task...");
+        }
+
+        true
+    }
+
     /// Get the plugin's state object, may be called by the plugin's GUI as part of its own preset
     /// management. The wrapper doesn't use these functions and serializes and deserializes directly
     /// the JSON in the relevant plugin API methods instead.
@@ -418,6 +567,7 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
                 self.param_id_to_ptr
                     .iter()
                     .map(|(param_id, param_ptr)| (param_id, *param_ptr)),
+                Some(&self.midi_learn),
             )
         }
     }
@@ -494,24 +644,154 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
         should_terminate: Arc<AtomicBool>,
         gui_task_sender: channel::Sender<GuiTask>,
     ) {
+        let mut transport_tracker = TransportTracker::default();
+        // With `P::FIXED_BLOCK_SIZE` the incoming events are moved to this queue, and the adapter
+        // moves them back to the queue when processing the block they belong to
+        let mut fixed_block_adapter = P::FIXED_BLOCK_SIZE
+            .map(|block_size| FixedBlockAdapter::new(block_size, &self.audio_io_layout));
+        let block_input_events = AtomicRefCell::new(VecDeque::with_capacity(EVENT_QUEUE_CAPACITY));
+        // The input events without the MIDI CCs that have been mapped through MIDI learn
+        let mut unmapped_input_events = Vec::with_capacity(EVENT_QUEUE_CAPACITY);
+        let mut silence_skipper = SilenceSkipper::default();
+        let bypass_param = bypass_crossfade::find_bypass_param::<P>(
+            self.params.param_map().into_iter().map(|(_, ptr, _)| ptr),
+        );
+        let mut bypass_crossfader = bypass_param.map(|bypass_param| {
+            let mut crossfader = BypassCrossfader::new(
+                P::BYPASS_CROSSFADE_MS.unwrap_or_default(),
+                self.buffer_config.sample_rate,
+                self.buffer_config.max_buffer_size as usize,
+                self.audio_io_layout
+                    .main_output_channels
+                    .map(NonZeroU32::get)
+                    .unwrap_or(0) as usize,
+                self.current_latency.load(Ordering::SeqCst) + P::FIXED_BLOCK_SIZE.unwrap_or(0),
+            );
+            crossfader.reset(bypass_crossfade::is_bypassed(bypass_param));
+
+            crossfader
+        });
+        let mut analyzer_passthrough = P::ANALYZER_MODE.then(|| {
+            AnalyzerPassthrough::new(
+                self.audio_io_layout
+                    .main_output_channels
+                    .map(NonZeroU32::get)
+                    .unwrap_or(0) as usize,
+                self.buffer_config.max_buffer_size as usize,
+            )
+        });
+        let cv_inputs = CvInputs::new(self.params.param_map().into_iter().map(|(_, ptr, _)| ptr));
+        cv_inputs.initialize(
+            &self.audio_io_layout,
+            self.buffer_config.max_buffer_size as usize,
+        );
+        let num_main_input_channels = self
+            .audio_io_layout
+            .main_input_channels
+            .map(NonZeroU32::get)
+            .unwrap_or(0) as usize;
         self.clone().backend.borrow_mut().run(
-            move |buffer, aux, transport, input_events, output_events| {
+            move |buffer, aux, mut transport, input_events, output_events| {
                 // TODO: This process wrapper should actually be in the backends (since the backends
                 //       should also not allocate in their audio callbacks), but that's a bit more
                 //       error prone
-                process_wrapper(|| {
+                process_wrapper("the standalone audio callback", || {
                     if should_terminate.load(Ordering::SeqCst) {
                         return false;
                     }
 
+                    unmapped_input_events.clear();
+                    unmapped_input_events.extend(
+                        input_events
+                            .iter()
+                            .filter(|event| !self.apply_midi_learn(event))
+                            .cloned(),
+                    );
+                    let input_events = unmapped_input_events.as_slice();
+
                     let sample_rate = self.buffer_config.sample_rate;
-                    {
+                    transport_tracker.update(&mut transport, buffer.samples());
+                    self.last_transport
+                        .store(Some(TransportSnapshot::new(&transport)));
+                    let skip_block = silence_skipper.should_skip::<P>(
+                        || {
+                            // Audio buffered by the fixed block adapter needs to be sent to the
+                            // host before the plugin can be skipped
+                            silence_skip::input_is_silent(
+                                buffer,
+                                num_main_input_channels,
+                                aux.inputs,
+                                !input_events.is_empty(),
+                            ) && !fixed_block_adapter
+                                .as_ref()
+                                .is_some_and(|adapter| adapter.has_buffered_output())
+                        },
+                        buffer.samples(),
+                    );
+                    if let Some(crossfader) = bypass_crossfader.as_mut() {
+                        crossfader.store_dry(buffer, num_main_input_channels);
+                    }
+                    if skip_block {
+                        panic_guard::silence_outputs(buffer, aux.outputs);
+                    } else {
                         let mut plugin = self.plugin.lock();
-                        if let ProcessStatus::Error(err) = plugin.process(
-                            buffer,
-                            aux,
-                            &mut self.make_process_context(transport, input_events, output_events),
-                        ) {
+                        let result = self.process_timings.time::<P, _>(
+                            buffer.samples(),
+                            sample_rate,
+                            || match fixed_block_adapter.as_mut() {
+                                Some(adapter) => {
+                                    block_input_events
+                                        .borrow_mut()
+                                        .extend(input_events.iter().cloned());
+
+                                    adapter.process(
+                                        buffer,
+                                        aux,
+                                        &block_input_events,
+                                        |buffer, aux, output_offset| {
+                                            let num_output_events = output_events.len();
+                                            cv_inputs.copy_inputs(aux.inputs, buffer.samples());
+                                            let mut block_input_events =
+                                                block_input_events.borrow_mut();
+                                            let mut context = self.make_process_context(
+                                                transport.clone(),
+                                                block_input_events.make_contiguous(),
+                                                output_events,
+                                            );
+                                            let result = process_with_passthrough(
+                                                analyzer_passthrough.as_mut(),
+                                                buffer,
+                                                |buffer| plugin.process(buffer, aux, &mut context),
+                                            );
+                                            drop(context);
+
+                                            for event in &mut output_events[num_output_events..] {
+                                                event.add_timing(output_offset as u32);
+                                            }
+
+                                            result
+                                        },
+                                    )
+                                }
+                                None => {
+                                    cv_inputs.copy_inputs(aux.inputs, buffer.samples());
+                                    let mut context = self.make_process_context(
+                                        transport,
+                                        input_events,
+                                        output_events,
+                                    );
+                                    process_with_passthrough(
+                                        analyzer_passthrough.as_mut(),
+                                        buffer,
+                                        |buffer| plugin.process(buffer, aux, &mut context),
+                                    )
+                                }
+                            },
+                        );
+                        silence_skipper.update(result, || {
+                            silence_skip::output_is_silent(buffer, aux.outputs)
+                        });
+                        if let ProcessStatus::Error(err) = result {
                             nih_error!("The plugin returned an error while processing:");
                             nih_error!("{}", err);
 
@@ -524,6 +804,11 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
                             return false;
                         }
                     }
+                    if let (Some(crossfader), Some(bypass_param)) =
+                        (bypass_crossfader.as_mut(), bypass_param)
+                    {
+                        crossfader.apply(buffer, bypass_crossfade::is_bypassed(bypass_param));
+                    }
 
                     // Any output note events are now in a vector that can be processed by the
                     // audio/MIDI backend
@@ -623,6 +908,7 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
                 self.params.clone(),
                 |param_id| self.param_id_to_ptr.get(param_id).copied(),
                 Some(&self.buffer_config),
+                Some(&self.midi_learn),
             )
         });
         if !success {
@@ -645,7 +931,7 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
                 )
             });
             if success {
-                process_wrapper(|| plugin.reset());
+                process_wrapper("Plugin::reset() after loading state", || plugin.reset());
             }
         }
 
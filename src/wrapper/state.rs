@@ -2,10 +2,14 @@
 //! to plugins through the [`GuiContext`][crate::prelude::GuiContext].
 
 use anyhow::{Context, Result};
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
+use crate::midi::learn::{MidiLearn, MidiMapping};
 use crate::params::ParamMut;
 use crate::prelude::{BufferConfig, Param, ParamPtr, Params, Plugin};
 
@@ -50,6 +54,50 @@ pub struct PluginState {
     /// The individual fields are also serialized as JSON so they can safely be restored
     /// independently of the other fields.
     pub fields: BTreeMap<String, String>,
+    /// The MIDI CCs mapped to parameters using [`MidiLearn`]. Loading a state replaces all current
+    /// mappings with these, so an empty list clears them. This is only `None` for states that don't
+    /// contain any MIDI learn information, like states saved by older versions of NIH-plug, in
+    /// which case loading the state keeps the current mappings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub midi_mappings: Option<Vec<MidiMapping>>,
+}
+
+impl PluginState {
+    /// Serialize this state to a JSON string. This is the same uncompressed format NIH-plug uses to
+    /// store the plugin's state, and it can be used to implement 'copy settings' buttons by putting
+    /// the string on the clipboard. See [`from_json()`][Self::from_json()] for the inverse.
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self).context("Could not serialize the plugin state")
+    }
+
+    /// Parse a state previously serialized with [`to_json()`][Self::to_json()]. The string usually
+    /// comes from the clipboard, so it may not contain a plugin state at all. The result can be
+    /// passed to [`GuiContext::set_state()`][crate::prelude::GuiContext::set_state()].
+    pub fn from_json(json: &str) -> Result<PluginState> {
+        serde_json::from_str(json).context("The string does not contain a valid plugin state")
+    }
+}
+
+/// A preset that's embedded in the plugin's binary. See
+/// [`Plugin::FACTORY_PRESETS`][crate::prelude::Plugin::FACTORY_PRESETS].
+#[derive(Debug, Clone, Copy)]
+pub struct FactoryPreset {
+    /// The preset's name as shown in the host's preset browser.
+    pub name: &'static str,
+    /// The preset's serialized [`PluginState`]. This uses the same format NIH-plug uses to store
+    /// the plugin's state, so this can be a JSON file containing a serialized `PluginState`
+    /// object, or a Zstandard compressed version of that JSON data if the `zstd` feature is
+    /// enabled. This is usually loaded with `include_bytes!()`.
+    pub data: &'static [u8],
+}
+
+impl FactoryPreset {
+    /// Deserialize this preset's state. Returns `None` and logs an error if the preset's data is
+    /// not a valid serialized [`PluginState`].
+    pub fn state(&self) -> Option<PluginState> {
+        // SAFETY: This doesn't touch any of the plugin's parameters
+        unsafe { deserialize_json(self.data) }
+    }
 }
 
 /// Create a parameters iterator from the hashtables stored in the plugin wrappers. This avoids
@@ -85,6 +133,7 @@ pub(crate) fn make_params_getter<'a>(
 pub(crate) unsafe fn serialize_object<'a, P: Plugin>(
     plugin_params: Arc<dyn Params>,
     params_iter: impl IntoIterator<Item = (&'a String, ParamPtr)>,
+    midi_learn: Option<&MidiLearn>,
 ) -> PluginState {
     // We'll serialize parameter values as a simple `string_param_id: display_value` map.
     // NOTE: If the plugin is being modulated (and the plugin is a CLAP plugin in Bitwig Studio),
@@ -121,10 +170,15 @@ pub(crate) unsafe fn serialize_object<'a, P: Plugin>(
     // storing things like sample data.
     let fields = plugin_params.serialize_fields();
 
+    // This is stored even if there are no mappings so loading the state also clears mappings
+    // that have been made since
+    let midi_mappings = midi_learn.map(|midi_learn| midi_learn.mappings());
+
     PluginState {
         version: String::from(P::VERSION),
         params,
         fields,
+        midi_mappings,
     }
 }
 
@@ -134,8 +188,9 @@ pub(crate) unsafe fn serialize_object<'a, P: Plugin>(
 pub(crate) unsafe fn serialize_json<'a, P: Plugin>(
     plugin_params: Arc<dyn Params>,
     params_iter: impl IntoIterator<Item = (&'a String, ParamPtr)>,
+    midi_learn: Option<&MidiLearn>,
 ) -> Result<Vec<u8>> {
-    let plugin_state = serialize_object::<P>(plugin_params, params_iter);
+    let plugin_state = serialize_object::<P>(plugin_params, params_iter, midi_learn);
     let json = serde_json::to_vec(&plugin_state).context("Could not format as JSON")?;
 
     #[cfg(feature = "zstd")]
@@ -176,6 +231,7 @@ pub(crate) unsafe fn deserialize_object<P: Plugin>(
     plugin_params: Arc<dyn Params>,
     params_getter: impl Fn(&str) -> Option<ParamPtr>,
     current_buffer_config: Option<&BufferConfig>,
+    midi_learn: Option<&MidiLearn>,
 ) -> bool {
     // This lets the plugin perform migrations on old state if needed
     P::filter_state(state);
@@ -235,6 +291,10 @@ pub(crate) unsafe fn deserialize_object<P: Plugin>(
     // storing things like sample data.
     plugin_params.deserialize_fields(&state.fields);
 
+    if let (Some(midi_learn), Some(midi_mappings)) = (midi_learn, &state.midi_mappings) {
+        midi_learn.set_mappings(midi_mappings);
+    }
+
     true
 }
 
@@ -292,3 +352,182 @@ pub(crate) unsafe fn deserialize_json(state: &[u8]) -> Option<PluginState> {
 
     result
 }
+
+/// Marks the start of the plugin's `#[persist_streamed]` fields in the plugin's state. These are
+/// stored after the regular JSON state, and only when the plugin has streamed fields so the state
+/// format stays the same for all other plugins.
+pub(crate) const STREAMED_FIELDS_MAGIC: [u8; 8] = *b"NIH-STRM";
+
+/// Write the plugin's `#[persist_streamed]` fields to `writer`, starting with
+/// [`STREAMED_FIELDS_MAGIC`]. Every field is stored as its key followed by the field's data as a
+/// sequence of length-prefixed chunks terminated by an empty chunk. That way the fields can write
+/// their data directly to the host's stream without needing to know its size upfront.
+pub(crate) fn write_streamed_fields(
+    plugin_params: &dyn Params,
+    writer: &mut impl Write,
+) -> Result<()> {
+    let fields = plugin_params.streamed_fields();
+
+    writer.write_all(&STREAMED_FIELDS_MAGIC)?;
+    writer.write_all(&(fields.len() as u32).to_le_bytes())?;
+    for (key, field) in fields {
+        writer.write_all(&(key.len() as u32).to_le_bytes())?;
+        writer.write_all(key.as_bytes())?;
+
+        field
+            .save(&mut ChunkWriter { inner: writer })
+            .with_context(|| format!("Could not save '{key}'"))?;
+        writer.write_all(&0u32.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Read the streamed fields written by [`write_streamed_fields()`]. The [`STREAMED_FIELDS_MAGIC`]
+/// should already have been stripped from `data`. The fields' data is copied as is so it can be
+/// restored from a background thread using a [`StreamedFieldsLoader`]. All lengths are checked
+/// against the remaining data before anything is allocated, so a corrupted state results in an
+/// error instead of a huge allocation.
+pub(crate) fn read_streamed_fields(mut data: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+    /// Take `len` bytes from the start of `data`.
+    fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+        if len > data.len() {
+            anyhow::bail!(
+                "Unexpected end of state, expected {len} bytes but only {} are left",
+                data.len()
+            );
+        }
+
+        let (taken, rest) = data.split_at(len);
+        *data = rest;
+
+        Ok(taken)
+    }
+
+    fn take_u32(data: &mut &[u8]) -> Result<usize> {
+        let bytes = take(data, 4)?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+    }
+
+    // Every field takes up at least eight bytes for its key length and its terminating chunk
+    let num_fields = take_u32(&mut data)?;
+    let mut fields = Vec::with_capacity(num_fields.min(data.len() / 8));
+    for _ in 0..num_fields {
+        let key_len = take_u32(&mut data)?;
+        let key = String::from_utf8(take(&mut data, key_len)?.to_vec())
+            .context("Invalid streamed field key")?;
+
+        let mut field_data = Vec::new();
+        loop {
+            let chunk_len = take_u32(&mut data)?;
+            if chunk_len == 0 {
+                break;
+            }
+
+            field_data.extend_from_slice(
+                take(&mut data, chunk_len).with_context(|| format!("Could not read '{key}'"))?,
+            );
+        }
+
+        fields.push((key, field_data));
+    }
+
+    Ok(fields)
+}
+
+/// Restores the streamed fields read by [`read_streamed_fields()`] on a background thread. Every
+/// wrapper instance has one of these. Loads never run concurrently, and a load that has been
+/// superseded by a newer state by the time it gets to run is skipped so an older state can never
+/// overwrite a newer one.
+#[derive(Default)]
+pub(crate) struct StreamedFieldsLoader {
+    /// Incremented for every load. A load only restores its fields while this still matches the
+    /// generation it was started with.
+    generation: Arc<AtomicU64>,
+    /// Held by the background thread while it restores the fields.
+    load_lock: Arc<Mutex<()>>,
+}
+
+impl StreamedFieldsLoader {
+    /// Restore `fields` on a background thread. This calls
+    /// [`StreamedPersistentField::begin_load()`] for all fields before returning. Loads that are
+    /// still pending are cancelled, even if `fields` is empty.
+    ///
+    /// [`StreamedPersistentField::begin_load()`]: crate::params::persist::StreamedPersistentField::begin_load()
+    pub fn load(&self, plugin_params: Arc<dyn Params>, fields: Vec<(String, Vec<u8>)>) {
+        let load_generation = self.generation.fetch_add(1, Ordering::AcqRel) + 1;
+        if fields.is_empty() {
+            return;
+        }
+
+        let streamed_fields = plugin_params.streamed_fields();
+        for (key, _) in &fields {
+            match streamed_fields
+                .iter()
+                .find(|(field_key, _)| field_key == key)
+            {
+                Some((_, field)) => field.begin_load(),
+                None => nih_debug_assert_failure!("Unknown streamed field: {}", key),
+            }
+        }
+
+        let generation = self.generation.clone();
+        let load_lock = self.load_lock.clone();
+        let spawn_result = std::thread::Builder::new()
+            .name(String::from("state-loader"))
+            .spawn(move || {
+                let _load_guard = load_lock.lock();
+
+                let streamed_fields = plugin_params.streamed_fields();
+                for (key, data) in fields {
+                    let Some((_, field)) = streamed_fields
+                        .iter()
+                        .find(|(field_key, _)| *field_key == key)
+                    else {
+                        continue;
+                    };
+
+                    if generation.load(Ordering::Acquire) != load_generation {
+                        nih_trace!("Skipping stale streamed field '{}'", key);
+                        field.cancel_load();
+                        continue;
+                    }
+
+                    match field.load(&mut data.as_slice()) {
+                        Ok(()) => {
+                            nih_trace!("Loaded streamed field '{}' ({} bytes)", key, data.len())
+                        }
+                        Err(err) => nih_error!("Could not load streamed field '{}': {}", key, err),
+                    }
+                }
+            });
+        if let Err(err) = spawn_result {
+            nih_error!("Could not spawn the state loading thread: {}", err);
+        }
+    }
+}
+
+/// Writes everything as length-prefixed chunks to the inner writer. See
+/// [`write_streamed_fields()`].
+struct ChunkWriter<'a, W: Write> {
+    inner: &'a mut W,
+}
+
+impl<W: Write> Write for ChunkWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // An empty chunk would terminate the field
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let chunk = &buf[..buf.len().min(u32::MAX as usize)];
+        self.inner.write_all(&(chunk.len() as u32).to_le_bytes())?;
+        self.inner.write_all(chunk)?;
+
+        Ok(chunk.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -1,13 +1,30 @@
 use backtrace::Backtrace;
 use std::cmp;
+use std::fmt;
 use std::marker::PhantomData;
 use std::os::raw::c_char;
 
+use crate::audio_setup::{AudioIOLayout, BufferConfig};
+use crate::context::init::InitContext;
+use crate::debug::realtime::RealtimeScope;
+use crate::plugin::Plugin;
 use crate::util::permit_alloc;
 
+#[cfg(all(debug_assertions, feature = "assert_process_allocs"))]
+pub(crate) mod alloc_checks;
+pub(crate) mod analyzer_passthrough;
 pub(crate) mod buffer_management;
+pub(crate) mod bypass_crossfade;
 #[cfg(debug_assertions)]
 pub(crate) mod context_checks;
+pub(crate) mod cv_inputs;
+pub(crate) mod fixed_block;
+pub(crate) mod panic_guard;
+pub(crate) mod param_output_limiter;
+pub(crate) mod process_timings;
+pub(crate) mod silence_skip;
+pub(crate) mod state_slots;
+pub(crate) mod transport_tracker;
 
 /// The bit that controls flush-to-zero behavior for denormals in 32 and 64-bit floating point
 /// numbers on AArch64.
@@ -16,16 +33,26 @@ pub(crate) mod context_checks;
 #[cfg(target_arch = "aarch64")]
 const AARCH64_FTZ_BIT: u64 = 1 << 24;
 
+/// The number of messages the editor can send to the audio thread through
+/// [`AsyncExecutor::send_to_dsp()`][crate::prelude::AsyncExecutor::send_to_dsp()] before they are
+/// received in the process function. Any further messages are dropped.
+pub(crate) const GUI_MESSAGE_QUEUE_CAPACITY: usize = 512;
+/// The number of events the audio thread can send to the editor through
+/// [`ProcessContext::send_to_editor()`][crate::prelude::ProcessContext::send_to_editor()] before the
+/// oldest events are dropped. This is higher than the capacity of the GUI message queue since these
+/// are typically sent every processing cycle, and the editor only drains them once per frame.
+pub(crate) const EDITOR_EVENT_QUEUE_CAPACITY: usize = 4096;
+
 #[cfg(all(
     debug_assertions,
-    physical_sizefeature = "assert_process_allocs",
+    feature = "assert_process_allocs",
     all(windows, target_env = "gnu")
 ))]
 compile_error!("The 'assert_process_allocs' feature does not work correctly in combination with the 'x86_64-pc-windows-gnu' target, see https://github.com/Windfisch/rust-assert-no-alloc/issues/7");
 
 #[cfg(all(debug_assertions, feature = "assert_process_allocs"))]
 #[global_allocator]
-static A: assert_no_alloc::AllocDisabler = assert_no_alloc::AllocDisabler;
+static A: alloc_checks::ProcessAllocChecker = alloc_checks::ProcessAllocChecker;
 
 /// A Rabin fingerprint based string hash for parameter ID strings.
 pub fn hash_param_id(id: &str) -> u32 {
@@ -41,6 +68,35 @@ pub fn hash_param_id(id: &str) -> u32 {
     hash
 }
 
+/// Initialize the plugin for a new audio IO layout and buffer config. If the plugin was last
+/// successfully initialized with `previous_config`, and only the sample rate has changed since
+/// then, then [`Plugin::sample_rate_changed()`] is called instead of [`Plugin::initialize()`].
+/// `previous_config` should be reset to `None` when this returns `false`.
+pub(crate) fn initialize_plugin<P: Plugin>(
+    plugin: &mut P,
+    previous_config: Option<(AudioIOLayout, BufferConfig)>,
+    audio_io_layout: &AudioIOLayout,
+    buffer_config: &BufferConfig,
+    context: &mut impl InitContext<P>,
+) -> bool {
+    let only_sample_rate_changed = match previous_config {
+        Some((previous_layout, previous_buffer_config)) => {
+            previous_layout == *audio_io_layout
+                && previous_buffer_config.sample_rate != buffer_config.sample_rate
+                && previous_buffer_config.min_buffer_size == buffer_config.min_buffer_size
+                && previous_buffer_config.max_buffer_size == buffer_config.max_buffer_size
+                && previous_buffer_config.process_mode == buffer_config.process_mode
+        }
+        None => false,
+    };
+
+    if only_sample_rate_changed {
+        plugin.sample_rate_changed(audio_io_layout, buffer_config, context)
+    } else {
+        plugin.initialize(audio_io_layout, buffer_config, context)
+    }
+}
+
 /// The equivalent of the `strlcpy()` C function. Copy `src` to `dest` as a null-terminated
 /// C-string. If `dest` does not have enough capacity, add a null terminator at the end to prevent
 /// buffer overflows.
@@ -60,6 +116,54 @@ pub fn strlcpy(dest: &mut [c_char], src: &str) {
     dest[copy_len] = 0;
 }
 
+/// A [`fmt::Write`] implementation that writes to a C-string buffer, similar to [`strlcpy()`]. The
+/// buffer is always null terminated, and text that doesn't fit is truncated at a character
+/// boundary. Used to format parameter values without allocating.
+pub struct CStrWriter<'a> {
+    dest: &'a mut [c_char],
+    len: usize,
+}
+
+impl<'a> CStrWriter<'a> {
+    /// Start writing to `dest`. This clears any existing string in `dest`.
+    pub fn new(dest: &'a mut [c_char]) -> Self {
+        if let Some(first) = dest.first_mut() {
+            *first = 0;
+        }
+
+        Self { dest, len: 0 }
+    }
+}
+
+impl fmt::Write for CStrWriter<'_> {
+    /// Returns an error if the text did not fit. The part that did fit will still have been
+    /// written.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.dest.is_empty() {
+            return Err(fmt::Error);
+        }
+
+        // Make sure there's always room for a null terminator
+        let mut copy_len = cmp::min(self.dest.len() - 1 - self.len, s.len());
+        while !s.is_char_boundary(copy_len) {
+            copy_len -= 1;
+        }
+
+        let src_bytes: &[u8] = &s.as_bytes()[..copy_len];
+        let src_bytes_signed: &[c_char] =
+            unsafe { &*(src_bytes as *const [u8] as *const [c_char]) };
+        self.dest[self.len..self.len + copy_len].copy_from_slice(src_bytes_signed);
+        self.len += copy_len;
+        self.dest[self.len] = 0;
+
+        if copy_len == s.len() {
+            Ok(())
+        } else {
+            Err(fmt::Error)
+        }
+    }
+}
+
 /// Clamp an input event's timing to the buffer length. Emits a debug assertion failure if it was
 /// out of bounds.
 #[inline]
@@ -134,8 +238,10 @@ pub fn setup_logger() {
 fn log_panics() {
     std::panic::set_hook(Box::new(|info| {
         permit_alloc(|| {
-            // All of this is directly copied from `permit_no_alloc`, except that `error!()` became
-            // `nih_error!()` and `Shim` has been inlined
+            // All of this is directly copied from `permit_no_alloc`, except that `Shim` has been
+            // inlined. This uses `log::error!()` directly instead of `nih_error!()` so the message
+            // doesn't end up in the realtime logging queue, since the process may not live long
+            // enough for that queue to be drained.
             let backtrace = Backtrace::new();
 
             let thread = std::thread::current();
@@ -151,7 +257,7 @@ fn log_panics() {
 
             match info.location() {
                 Some(location) => {
-                    nih_error!(
+                    log::error!(
                         target: "panic", "thread '{}' panicked at '{}': {}:{}\n{:?}",
                         thread,
                         msg,
@@ -161,7 +267,7 @@ fn log_panics() {
                     );
                 }
                 None => {
-                    nih_error!(
+                    log::error!(
                         target: "panic",
                         "thread '{}' panicked at '{}'\n{:?}",
                         thread,
@@ -176,14 +282,22 @@ fn log_panics() {
 
 /// A wrapper around the entire process function, including the plugin wrapper parts. This sets up
 /// `assert_no_alloc` if needed, while also making sure that things like FTZ are set up correctly if
-/// the host has not already done so.
-pub fn process_wrapper<T, F: FnOnce() -> T>(f: F) -> T {
+/// the host has not already done so. `call` is the name of the wrapper call this is used in. If the
+/// plugin allocates while allocations are disallowed, then this name is logged together with a
+/// backtrace.
+#[cfg_attr(
+    not(all(debug_assertions, feature = "assert_process_allocs")),
+    allow(unused_variables)
+)]
+pub fn process_wrapper<T, F: FnOnce() -> T>(call: &'static str, f: F) -> T {
     // Make sure FTZ is always enabled, even if the host doesn't do it for us
     let _ftz_guard = ScopedFtz::enable();
+    // The logging macros won't block while this is active
+    let _realtime_scope = RealtimeScope::enter();
 
     cfg_if::cfg_if! {
         if #[cfg(all(debug_assertions, feature = "assert_process_allocs"))] {
-            assert_no_alloc::assert_no_alloc(f)
+            alloc_checks::assert_no_alloc(call, f)
         } else {
             f()
         }
@@ -277,6 +391,7 @@ impl Drop for ScopedFtz {
 #[cfg(test)]
 mod miri {
     use std::ffi::CStr;
+    use std::fmt::Write;
 
     use super::*;
 
@@ -301,4 +416,17 @@ mod miri {
             Ok("Hello")
         );
     }
+
+    #[test]
+    fn cstr_writer_overflow() {
+        // The multibyte character doesn't fit and should not be split
+        let mut dest = [0; 10];
+        let result = write!(CStrWriter::new(&mut dest), "{}, {}!", "Hello", "wörld");
+
+        assert!(result.is_err());
+        assert_eq!(
+            unsafe { CStr::from_ptr(dest.as_ptr()) }.to_str(),
+            Ok("Hello, w")
+        );
+    }
 }
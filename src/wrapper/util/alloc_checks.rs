@@ -0,0 +1,108 @@
+//! Allocation checks for the `assert_process_allocs` feature. This wraps `assert_no_alloc`'s
+//! allocator so allocations in the process function are reported together with a backtrace and
+//! the name of the wrapper call they happened in before `assert_no_alloc` aborts the process.
+
+use backtrace::Backtrace;
+use std::alloc::{GlobalAlloc, Layout};
+use std::cell::Cell;
+
+thread_local! {
+    /// The name of the wrapper call currently being checked on this thread, if any.
+    static CURRENT_CALL: Cell<Option<&'static str>> = const { Cell::new(None) };
+    /// The number of nested [`permit_alloc()`] scopes inside of the current checked call.
+    static PERMIT_DEPTH: Cell<u32> = const { Cell::new(0) };
+}
+
+/// The global allocator used when the `assert_process_allocs` feature is enabled.
+pub struct ProcessAllocChecker;
+
+unsafe impl GlobalAlloc for ProcessAllocChecker {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        check_allocation("Allocated", layout);
+        assert_no_alloc::AllocDisabler.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        check_allocation("Allocated", layout);
+        assert_no_alloc::AllocDisabler.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        check_allocation("Reallocated", layout);
+        assert_no_alloc::AllocDisabler.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        check_allocation("Deallocated", layout);
+        assert_no_alloc::AllocDisabler.dealloc(ptr, layout)
+    }
+}
+
+/// Run `f` while disallowing allocations. `call` is the name of the wrapper call that's being
+/// checked, and it's included in the error message when `f` does allocate.
+pub fn assert_no_alloc<T, F: FnOnce() -> T>(call: &'static str, f: F) -> T {
+    // These are restored when the guard is dropped, even if `f` panics
+    let _guard = ScopeGuard {
+        previous_call: CURRENT_CALL.with(|current_call| current_call.replace(Some(call))),
+        previous_permit_depth: PERMIT_DEPTH.with(|permit_depth| permit_depth.replace(0)),
+    };
+
+    assert_no_alloc::assert_no_alloc(f)
+}
+
+/// Run `f` while allowing allocations, even when this is called from within
+/// [`assert_no_alloc()`].
+pub fn permit_alloc<T, F: FnOnce() -> T>(f: F) -> T {
+    let previous_permit_depth = PERMIT_DEPTH.with(|permit_depth| {
+        let previous_permit_depth = permit_depth.get();
+        permit_depth.set(previous_permit_depth + 1);
+
+        previous_permit_depth
+    });
+    let _guard = ScopeGuard {
+        previous_call: CURRENT_CALL.with(|current_call| current_call.get()),
+        previous_permit_depth,
+    };
+
+    assert_no_alloc::permit_alloc(f)
+}
+
+/// Restores the thread local state when a scope ends.
+struct ScopeGuard {
+    previous_call: Option<&'static str>,
+    previous_permit_depth: u32,
+}
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        CURRENT_CALL.with(|current_call| current_call.set(self.previous_call));
+        PERMIT_DEPTH.with(|permit_depth| permit_depth.set(self.previous_permit_depth));
+    }
+}
+
+/// Log an error with a backtrace if the current thread is not allowed to allocate. The actual
+/// enforcement is left to `assert_no_alloc`.
+fn check_allocation(action: &str, layout: Layout) {
+    // The thread locals may already have been destroyed during thread shutdown
+    let call = match CURRENT_CALL.try_with(|current_call| current_call.get()) {
+        Ok(Some(call)) => call,
+        _ => return,
+    };
+    if PERMIT_DEPTH.try_with(|permit_depth| permit_depth.get()) != Ok(0) {
+        return;
+    }
+
+    // Capturing the backtrace and logging the message both allocate. This bypasses the realtime
+    // logging queue since `assert_no_alloc` aborts the process right after this.
+    permit_alloc(|| {
+        let backtrace = Backtrace::new();
+        log::error!(
+            "{} {} bytes during {} while allocations are not allowed. Wrap known-safe cold paths \
+             in `nih_plug::util::permit_alloc()` to allow allocations there.\n{:?}",
+            action,
+            layout.size(),
+            call,
+            backtrace
+        );
+    });
+}
@@ -0,0 +1,114 @@
+//! Passing the main input through unchanged for plugins that set `Plugin::ANALYZER_MODE`.
+
+use crate::buffer::Buffer;
+use crate::plugin::ProcessStatus;
+use crate::util::permit_alloc;
+
+/// Call `process` with the main buffer, and restore the main buffer afterwards if `passthrough` is
+/// set.
+pub fn process_with_passthrough<'a>(
+    passthrough: Option<&mut AnalyzerPassthrough>,
+    main_buffer: &mut Buffer<'a>,
+    process: impl FnOnce(&mut Buffer<'a>) -> ProcessStatus,
+) -> ProcessStatus {
+    match passthrough {
+        Some(passthrough) => {
+            passthrough.store(main_buffer);
+            let result = process(main_buffer);
+            passthrough.restore(main_buffer);
+
+            result
+        }
+        None => process(main_buffer),
+    }
+}
+
+/// Restores the main buffer to what it was before the plugin's process function was called, so
+/// the plugin's output always equals its input regardless of what the plugin does with the buffer.
+/// Allocated when the plugin gets activated.
+#[derive(Debug)]
+pub struct AnalyzerPassthrough {
+    /// The main buffer's contents from before the process call.
+    input: Vec<Vec<f32>>,
+}
+
+impl AnalyzerPassthrough {
+    /// Preallocate storage for a main buffer with `num_channels` channels and up to
+    /// `max_buffer_size` samples.
+    pub fn new(num_channels: usize, max_buffer_size: usize) -> Self {
+        Self {
+            input: vec![vec![0.0; max_buffer_size]; num_channels],
+        }
+    }
+
+    /// Store the main buffer's contents before the plugin processes it.
+    pub fn store(&mut self, main_buffer: &Buffer) {
+        let num_samples = main_buffer.samples();
+        for (input, channel) in self.input.iter_mut().zip(main_buffer.as_slice_immutable()) {
+            if input.len() < num_samples {
+                nih_debug_assert_failure!("The host sent a larger block than it said it would");
+                permit_alloc(|| input.resize(num_samples, 0.0));
+            }
+
+            input[..num_samples].copy_from_slice(&channel[..num_samples]);
+        }
+    }
+
+    /// Undo any changes the plugin made to the main buffer.
+    pub fn restore(&self, main_buffer: &mut Buffer) {
+        let num_samples = main_buffer.samples();
+        for (input, channel) in self.input.iter().zip(main_buffer.as_slice()) {
+            channel[..num_samples].copy_from_slice(&input[..num_samples]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use atomic_float::AtomicF32;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    use crate::prelude::*;
+    use crate::test::fixtures::AnalyzerTestPlugin;
+    use crate::test::PluginTester;
+
+    #[test]
+    fn restores_main_buffer() {
+        let mut tester = PluginTester::<AnalyzerTestPlugin>::new(
+            AnalyzerTestPlugin::AUDIO_IO_LAYOUTS[1],
+            BufferConfig {
+                sample_rate: 44100.0,
+                min_buffer_size: None,
+                max_buffer_size: 64,
+                process_mode: ProcessMode::Offline,
+            },
+        )
+        .unwrap();
+
+        // The plugin measures its input's peak, and then overwrites the buffer
+        let peak = Arc::new(AtomicF32::new(0.0));
+        tester.plugin_mut().process_hook = Some(Box::new({
+            let peak = peak.clone();
+            move |buffer| {
+                for channel in buffer.as_slice() {
+                    for sample in channel.iter_mut() {
+                        peak.fetch_max(sample.abs(), Ordering::Relaxed);
+                        *sample = 0.0;
+                    }
+                }
+            }
+        }));
+
+        let input = vec![
+            (0..100).map(|i| i as f32 / 100.0).collect::<Vec<f32>>(),
+            (0..100).map(|i| -(i as f32) / 200.0).collect::<Vec<f32>>(),
+        ];
+        let mut main_io = input.clone();
+        tester.process(&mut main_io);
+
+        // The plugin saw the input and cleared the buffer, but the output still equals the input
+        assert_eq!(peak.load(Ordering::Relaxed), 0.99);
+        assert_eq!(main_io, input);
+    }
+}
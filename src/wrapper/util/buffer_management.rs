@@ -100,16 +100,18 @@ impl BufferManager {
                 );
             })
         };
+        main_buffer.set_channel_layout(audio_io_layout.main_output_channel_layout());
 
         let mut aux_input_buffers = Vec::with_capacity(audio_io_layout.aux_input_ports.len());
         let mut aux_input_storage = Vec::with_capacity(audio_io_layout.aux_input_ports.len());
-        for num_channels in audio_io_layout.aux_input_ports {
+        for (port_idx, num_channels) in audio_io_layout.aux_input_ports.iter().enumerate() {
             let mut buffer = Buffer::default();
             unsafe {
                 buffer.set_slices(0, |slices| {
                     slices.resize_with(num_channels.get() as usize, || &mut []);
                 })
             };
+            buffer.set_channel_layout(audio_io_layout.aux_input_channel_layout(port_idx));
 
             aux_input_buffers.push(buffer);
             aux_input_storage.push(vec![
@@ -119,13 +121,14 @@ impl BufferManager {
         }
 
         let mut aux_output_buffers = Vec::with_capacity(audio_io_layout.aux_output_ports.len());
-        for num_channels in audio_io_layout.aux_output_ports {
+        for (port_idx, num_channels) in audio_io_layout.aux_output_ports.iter().enumerate() {
             let mut buffer = Buffer::default();
             unsafe {
                 buffer.set_slices(0, |slices| {
                     slices.resize_with(num_channels.get() as usize, || &mut []);
                 })
             };
+            buffer.set_channel_layout(audio_io_layout.aux_output_channel_layout(port_idx));
 
             aux_output_buffers.push(buffer);
         }
@@ -376,7 +379,7 @@ impl BufferManager {
 #[cfg(any(miri, test))]
 mod miri {
     use super::*;
-    use crate::prelude::{new_nonzero_u32, PortNames};
+    use crate::prelude::{new_nonzero_u32, ChannelLayouts, PortNames};
 
     const BUFFER_SIZE: usize = 512;
     const NUM_MAIN_INPUT_CHANNELS: usize = 1;
@@ -391,6 +394,7 @@ mod miri {
         aux_input_ports: &[new_nonzero_u32(NUM_AUX_CHANNELS as u32); NUM_AUX_PORTS],
         aux_output_ports: &[new_nonzero_u32(NUM_AUX_CHANNELS as u32); NUM_AUX_PORTS],
         names: PortNames::const_default(),
+        channel_layouts: ChannelLayouts::const_default(),
     };
 
     #[test]
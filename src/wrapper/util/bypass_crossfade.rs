@@ -0,0 +1,214 @@
+//! Crossfading between the processed and the dry signal when the plugin gets bypassed, for
+//! [`Plugin::BYPASS_CROSSFADE_MS`].
+
+use crate::buffer::Buffer;
+use crate::params::internals::ParamPtr;
+use crate::params::ParamFlags;
+use crate::plugin::Plugin;
+use crate::util::permit_alloc;
+
+/// Find the plugin's bypass parameter if [`Plugin::BYPASS_CROSSFADE_MS`] is set. Returns `None`
+/// if the option is disabled or if the plugin doesn't have a bypass parameter, in which case the
+/// wrapper should not create a [`BypassCrossfader`].
+pub fn find_bypass_param<P: Plugin>(
+    param_ptrs: impl IntoIterator<Item = ParamPtr>,
+) -> Option<ParamPtr> {
+    P::BYPASS_CROSSFADE_MS?;
+
+    let bypass_param = param_ptrs
+        .into_iter()
+        .find(|ptr| unsafe { ptr.flags() }.contains(ParamFlags::BYPASS));
+    nih_debug_assert!(
+        bypass_param.is_some(),
+        "'Plugin::BYPASS_CROSSFADE_MS' is set, but the plugin doesn't have a bypass parameter"
+    );
+
+    bypass_param
+}
+
+/// Whether the bypass parameter is currently enabled.
+pub fn is_bypassed(bypass_param: ParamPtr) -> bool {
+    let normalized_value = unsafe { bypass_param.modulated_normalized_value() };
+
+    normalized_value >= 0.5
+}
+
+/// Ramps the main output between the plugin's processed output and the dry input signal when the
+/// bypass parameter changes. The dry signal is delayed by the plugin's latency, so both signals
+/// line up during the crossfade. The plugin keeps getting called while bypassed, so it should not
+/// implement bypassing itself when this is used. All storage is allocated up front, although
+/// changing the latency while processing reallocates the delay lines.
+pub struct BypassCrossfader {
+    /// The amount `mix` changes every sample while crossfading.
+    step_size: f32,
+    /// The current crossfade position. `0.0` is the processed signal, and `1.0` is the dry signal.
+    mix: f32,
+
+    /// The (delayed) dry signal for the current block, for each of the main output channels.
+    /// Output channels without a matching input channel receive silence.
+    dry: Vec<Vec<f32>>,
+    /// Ring buffers for delaying the dry signal by the plugin's latency, one per output channel.
+    /// These are empty when the plugin doesn't have any latency.
+    delay_lines: Vec<Vec<f32>>,
+    /// The current read and write position in the delay lines.
+    delay_pos: usize,
+}
+
+impl BypassCrossfader {
+    /// Allocate a crossfader for a main output with `num_channels` channels and blocks of up to
+    /// `max_buffer_size` samples. The crossfade takes `crossfade_ms` milliseconds.
+    pub fn new(
+        crossfade_ms: f32,
+        sample_rate: f32,
+        max_buffer_size: usize,
+        num_channels: usize,
+        latency: u32,
+    ) -> Self {
+        let crossfade_samples = (crossfade_ms / 1000.0 * sample_rate).round().max(1.0);
+
+        Self {
+            step_size: crossfade_samples.recip(),
+            mix: 0.0,
+
+            dry: vec![vec![0.0; max_buffer_size]; num_channels],
+            delay_lines: vec![vec![0.0; latency as usize]; num_channels],
+            delay_pos: 0,
+        }
+    }
+
+    /// Clear the delay lines and jump to the end of any ongoing crossfade.
+    pub fn reset(&mut self, bypassed: bool) {
+        self.mix = if bypassed { 1.0 } else { 0.0 };
+        for delay_line in &mut self.delay_lines {
+            delay_line.fill(0.0);
+        }
+        self.delay_pos = 0;
+    }
+
+    /// Change the dry signal's delay if the plugin's latency changed. This reallocates and clears
+    /// the delay lines, so it should only happen rarely.
+    pub fn set_latency(&mut self, latency: u32) {
+        let latency = latency as usize;
+        if self.delay_lines.first().map(Vec::len).unwrap_or(latency) == latency {
+            return;
+        }
+
+        permit_alloc(|| {
+            for delay_line in &mut self.delay_lines {
+                delay_line.clear();
+                delay_line.resize(latency, 0.0);
+            }
+        });
+        self.delay_pos = 0;
+    }
+
+    /// Store the dry input signal before the plugin processes the main buffer in place. The main
+    /// input is stored in the first `num_main_input_channels` channels of the buffer at this
+    /// point.
+    pub fn store_dry(&mut self, main_buffer: &Buffer, num_main_input_channels: usize) {
+        let num_samples = main_buffer.samples();
+        let channels = main_buffer.as_slice_immutable();
+        for (channel_idx, (dry, delay_line)) in self
+            .dry
+            .iter_mut()
+            .zip(self.delay_lines.iter_mut())
+            .enumerate()
+        {
+            if dry.len() < num_samples {
+                nih_debug_assert_failure!("The host sent a larger block than it said it would");
+                permit_alloc(|| dry.resize(num_samples, 0.0));
+            }
+
+            let dry = &mut dry[..num_samples];
+            match channels.get(channel_idx) {
+                Some(channel) if channel_idx < num_main_input_channels => {
+                    dry.copy_from_slice(&channel[..num_samples])
+                }
+                _ => dry.fill(0.0),
+            }
+
+            // Swapping the new samples with the oldest samples in the ring buffer delays the dry
+            // signal by exactly the delay line's length
+            if !delay_line.is_empty() {
+                let mut pos = self.delay_pos;
+                for sample in dry {
+                    std::mem::swap(sample, &mut delay_line[pos]);
+                    pos += 1;
+                    if pos == delay_line.len() {
+                        pos = 0;
+                    }
+                }
+            }
+        }
+
+        if let Some(delay_line_len) = self.delay_lines.first().map(Vec::len) {
+            if delay_line_len > 0 {
+                self.delay_pos = (self.delay_pos + num_samples) % delay_line_len;
+            }
+        }
+    }
+
+    /// Mix the dry signal stored with [`store_dry()`][Self::store_dry()] into the processed main
+    /// output, moving the crossfade towards the dry signal if `bypassed` is set and towards the
+    /// processed signal otherwise.
+    pub fn apply(&mut self, main_buffer: &mut Buffer, bypassed: bool) {
+        let num_samples = main_buffer.samples();
+        let target_mix = if bypassed { 1.0 } else { 0.0 };
+        if self.mix == target_mix {
+            if bypassed {
+                for (channel, dry) in main_buffer.as_slice().iter_mut().zip(&self.dry) {
+                    channel.copy_from_slice(&dry[..num_samples]);
+                }
+            }
+
+            return;
+        }
+
+        let start_mix = self.mix;
+        let step_size = if bypassed {
+            self.step_size
+        } else {
+            -self.step_size
+        };
+        for (channel, dry) in main_buffer.as_slice().iter_mut().zip(&self.dry) {
+            let mut mix = start_mix;
+            for (sample, dry_sample) in channel.iter_mut().zip(dry) {
+                mix = (mix + step_size).clamp(0.0, 1.0);
+                *sample += (dry_sample - *sample) * mix;
+            }
+        }
+
+        self.mix = (start_mix + (step_size * num_samples as f32)).clamp(0.0, 1.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latency_aligned_crossfade() {
+        let mut crossfader = BypassCrossfader::new(2.0, 1000.0, 4, 1, 1);
+
+        let mut samples = vec![1.0f32, 2.0, 3.0, 4.0];
+        let mut buffer = Buffer::default();
+        unsafe {
+            buffer.set_slices(4, |slices| {
+                *slices = vec![&mut *(samples.as_mut_slice() as *mut [f32])];
+            })
+        };
+
+        // The plugin outputs silence, so the output only contains the dry signal delayed by one
+        // sample and faded in over two samples
+        crossfader.store_dry(&buffer, 1);
+        buffer.as_slice()[0].fill(0.0);
+        crossfader.apply(&mut buffer, true);
+        assert_eq!(buffer.as_slice()[0], [0.0, 1.0, 2.0, 3.0]);
+
+        buffer.as_slice()[0].copy_from_slice(&[5.0, 6.0, 7.0, 8.0]);
+        crossfader.store_dry(&buffer, 1);
+        buffer.as_slice()[0].fill(0.0);
+        crossfader.apply(&mut buffer, false);
+        assert_eq!(buffer.as_slice()[0], [2.0, 0.0, 0.0, 0.0]);
+    }
+}
@@ -0,0 +1,113 @@
+//! Routing auxiliary inputs to parameters that accept audio-rate modulation.
+
+use crate::audio_setup::AudioIOLayout;
+use crate::buffer::Buffer;
+use crate::params::internals::ParamPtr;
+use crate::params::FloatParam;
+
+/// The parameters that have been set up with
+/// [`FloatParam::with_cv_input()`][crate::prelude::FloatParam::with_cv_input()]. Before every call
+/// to the plugin's process function, the wrapper copies the auxiliary input channels to these
+/// parameters' CV buffers.
+#[derive(Debug, Default)]
+pub struct CvInputs {
+    /// Only contains `ParamPtr::FloatParam`s with a CV input.
+    params: Vec<ParamPtr>,
+}
+
+impl CvInputs {
+    /// Find the parameters with CV inputs.
+    pub fn new(param_ptrs: impl IntoIterator<Item = ParamPtr>) -> Self {
+        Self {
+            params: param_ptrs
+                .into_iter()
+                .filter(|param_ptr| {
+                    float_param(param_ptr).is_some_and(|param| param.cv_input().is_some())
+                })
+                .collect(),
+        }
+    }
+
+    /// Preallocate the parameters' CV buffers. Called when the plugin gets initialized.
+    pub fn initialize(&self, audio_io_layout: &AudioIOLayout, max_buffer_size: usize) {
+        for param in self.params.iter().filter_map(float_param) {
+            if let Some((aux_input_idx, _)) = param.cv_input() {
+                nih_debug_assert!(
+                    aux_input_idx < audio_io_layout.aux_input_ports.len(),
+                    "The CV input for '{}' uses auxiliary input {}, but the audio IO layout only \
+                     has {} auxiliary inputs",
+                    param.name(),
+                    aux_input_idx,
+                    audio_io_layout.aux_input_ports.len()
+                );
+            }
+
+            param.allocate_cv(max_buffer_size);
+        }
+    }
+
+    /// Copy the CV inputs for the next `num_samples` samples from the auxiliary input buffers to
+    /// the parameters. Inputs that don't exist result in silence. This does not allocate.
+    pub fn copy_inputs(&self, aux_inputs: &[Buffer], num_samples: usize) {
+        for param in self.params.iter().filter_map(float_param) {
+            let samples = param.cv_input().and_then(|(aux_input_idx, channel_idx)| {
+                aux_inputs
+                    .get(aux_input_idx)?
+                    .as_slice_immutable()
+                    .get(channel_idx)
+                    .map(|channel| &**channel)
+            });
+
+            param.set_cv(samples, num_samples);
+        }
+    }
+}
+
+fn float_param(param_ptr: &ParamPtr) -> Option<&FloatParam> {
+    match param_ptr {
+        // SAFETY: The parameters outlive the wrapper
+        ParamPtr::FloatParam(param) => Some(unsafe { &**param }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio_setup::new_nonzero_u32;
+    use crate::params::range::FloatRange;
+    use crate::params::Param;
+
+    #[test]
+    fn cv_offsets_smoothed_values() {
+        let cutoff = FloatParam::new("Cutoff", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 })
+            .with_cv_input(0, 1);
+        let cv_inputs = CvInputs::new([cutoff.as_ptr()]);
+        cv_inputs.initialize(
+            &AudioIOLayout {
+                aux_input_ports: &[new_nonzero_u32(2)],
+                ..AudioIOLayout::const_default()
+            },
+            4,
+        );
+
+        let mut real_buffers = vec![vec![0.0; 4], vec![0.0, 0.25, 1.0, -1.0]];
+        let mut aux_input = Buffer::default();
+        unsafe {
+            aux_input.set_slices(4, |output_slices| {
+                *output_slices = real_buffers.iter_mut().map(|s| s.as_mut_slice()).collect();
+            })
+        };
+        cv_inputs.copy_inputs(std::slice::from_ref(&aux_input), 4);
+
+        let mut values = [0.0; 4];
+        cutoff.next_block_with_cv(&mut values, 0, 4);
+        assert_eq!(values, [0.5, 0.75, 1.0, 0.0]);
+        assert_eq!(cutoff.next_with_cv(1), 0.75);
+
+        // Missing inputs result in silence
+        cv_inputs.copy_inputs(&[], 4);
+        cutoff.next_block_with_cv(&mut values, 0, 4);
+        assert_eq!(values, [0.5; 4]);
+    }
+}
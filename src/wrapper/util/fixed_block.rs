@@ -0,0 +1,379 @@
+//! Buffering the host's audio so the plugin's process function is always called with the same
+//! number of samples, for
+//! [`Plugin::FIXED_BLOCK_SIZE`][crate::prelude::Plugin::FIXED_BLOCK_SIZE].
+
+use atomic_refcell::AtomicRefCell;
+use std::collections::VecDeque;
+use std::num::NonZeroU32;
+
+use crate::audio_setup::{AudioIOLayout, AuxiliaryBuffers, ChannelLayout};
+use crate::buffer::Buffer;
+use crate::midi::sysex::SysExMessage;
+use crate::midi::NoteEvent;
+use crate::plugin::ProcessStatus;
+use crate::wrapper::util::silence_skip;
+
+/// The number of incoming note events that can be held back until the block they belong to gets
+/// processed without having to allocate.
+const PENDING_EVENTS_CAPACITY: usize = 2048;
+
+/// Collects the host's audio until a full block of
+/// [`Plugin::FIXED_BLOCK_SIZE`][crate::prelude::Plugin::FIXED_BLOCK_SIZE] samples is
+/// available, and then processes that block. The host receives the output of the previously
+/// processed block while the next block is being collected, so this adds exactly one block of
+/// latency. All storage is allocated up front, so processing doesn't allocate.
+pub struct FixedBlockAdapter<S> {
+    block_size: usize,
+    /// The number of samples that have been collected for the next block.
+    num_collected: usize,
+
+    /// The main IO's input for the block that's being collected. This gets processed in place and
+    /// then swapped with `main_output`.
+    main_input: Vec<Vec<f32>>,
+    /// The output of the last processed block, which is sent to the host while the next block is
+    /// being collected.
+    main_output: Vec<Vec<f32>>,
+    /// The auxiliary inputs for the block that's being collected, indexed by `[port][channel]`.
+    aux_inputs: Vec<Vec<Vec<f32>>>,
+    /// The plugin writes its auxiliary outputs here. This gets swapped with `aux_outputs_ready`
+    /// after processing a block.
+    aux_outputs: Vec<Vec<Vec<f32>>>,
+    /// The auxiliary outputs of the last processed block.
+    aux_outputs_ready: Vec<Vec<Vec<f32>>>,
+
+    /// [`Buffer`]s pointing to the storage above. The `'static` lifetimes are shortened when
+    /// passing these to the plugin, and the slices are reassigned before every block.
+    main_buffer: Buffer<'static>,
+    aux_input_buffers: Vec<Buffer<'static>>,
+    aux_output_buffers: Vec<Buffer<'static>>,
+
+    /// Incoming note events for the blocks that haven't been processed yet. The timings are
+    /// relative to the start of the block that's being collected.
+    pending_events: VecDeque<NoteEvent<S>>,
+    /// The status returned by the last processed block. This is returned to the host when a host
+    /// block doesn't complete an internal block.
+    last_status: ProcessStatus,
+}
+
+impl<S: SysExMessage> FixedBlockAdapter<S> {
+    /// Allocate the buffers for `block_size` samples for an audio IO layout.
+    pub fn new(block_size: u32, audio_io_layout: &AudioIOLayout) -> Self {
+        let block_size = block_size.max(1) as usize;
+        let num_main_channels = audio_io_layout
+            .main_output_channels
+            .map(NonZeroU32::get)
+            .unwrap_or(0) as usize;
+        let make_ports = |ports: &[NonZeroU32]| -> Vec<Vec<Vec<f32>>> {
+            ports
+                .iter()
+                .map(|num_channels| vec![vec![0.0; block_size]; num_channels.get() as usize])
+                .collect()
+        };
+        let make_buffers = |ports: &[NonZeroU32],
+                            channel_layout: fn(&AudioIOLayout, usize) -> Option<ChannelLayout>|
+         -> Vec<Buffer<'static>> {
+            ports
+                .iter()
+                .enumerate()
+                .map(|(port_idx, num_channels)| {
+                    let mut buffer = Buffer::default();
+                    unsafe {
+                        buffer.set_slices(0, |slices| {
+                            slices.resize_with(num_channels.get() as usize, || &mut []);
+                        })
+                    };
+                    buffer.set_channel_layout(channel_layout(audio_io_layout, port_idx));
+
+                    buffer
+                })
+                .collect()
+        };
+
+        let mut main_buffer = Buffer::default();
+        unsafe {
+            main_buffer.set_slices(0, |slices| {
+                slices.resize_with(num_main_channels, || &mut []);
+            })
+        };
+        main_buffer.set_channel_layout(audio_io_layout.main_output_channel_layout());
+
+        Self {
+            block_size,
+            num_collected: 0,
+
+            main_input: vec![vec![0.0; block_size]; num_main_channels],
+            main_output: vec![vec![0.0; block_size]; num_main_channels],
+            aux_inputs: make_ports(audio_io_layout.aux_input_ports),
+            aux_outputs: make_ports(audio_io_layout.aux_output_ports),
+            aux_outputs_ready: make_ports(audio_io_layout.aux_output_ports),
+
+            main_buffer,
+            aux_input_buffers: make_buffers(
+                audio_io_layout.aux_input_ports,
+                AudioIOLayout::aux_input_channel_layout,
+            ),
+            aux_output_buffers: make_buffers(
+                audio_io_layout.aux_output_ports,
+                AudioIOLayout::aux_output_channel_layout,
+            ),
+
+            pending_events: VecDeque::with_capacity(PENDING_EVENTS_CAPACITY),
+            last_status: ProcessStatus::Normal,
+        }
+    }
+
+    /// Clear the buffered audio and events. The next call to [`process()`][Self::process()] starts
+    /// collecting a new block.
+    pub fn reset(&mut self) {
+        self.num_collected = 0;
+        for channel in self.main_output.iter_mut().chain(
+            self.aux_outputs_ready
+                .iter_mut()
+                .flat_map(|port| port.iter_mut()),
+        ) {
+            channel.fill(0.0);
+        }
+
+        self.pending_events.clear();
+        self.last_status = ProcessStatus::Normal;
+    }
+
+    /// Whether the output of the last processed block still contains audio that hasn't been sent
+    /// to the host yet. Process calls should not be skipped while this is the case, or that audio
+    /// would be lost.
+    pub fn has_buffered_output(&self) -> bool {
+        self.main_output
+            .iter()
+            .chain(self.aux_outputs_ready.iter().flatten())
+            .any(|channel| !silence_skip::is_silent(&channel[self.num_collected..]))
+    }
+
+    /// Exchange the host's block with the buffered audio, calling `process_block` for every
+    /// internal block that gets completed along the way. `input_events` contains the host block's
+    /// note events when this is called. These events are held back, and before calling
+    /// `process_block` the events belonging to that block are moved back into `input_events`.
+    ///
+    /// `process_block` receives the block's buffers and the index of the host sample the block's
+    /// output starts at, which should be added to the timings of the block's output events. If the
+    /// block was completed at the very end of the host's block, then this is equal to the host
+    /// block's length. Returns the status of the last processed block.
+    pub fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        aux: &mut AuxiliaryBuffers,
+        input_events: &AtomicRefCell<VecDeque<NoteEvent<S>>>,
+        mut process_block: impl FnMut(&mut Buffer, &mut AuxiliaryBuffers, usize) -> ProcessStatus,
+    ) -> ProcessStatus {
+        {
+            let mut input_events = input_events.borrow_mut();
+            for mut event in input_events.drain(..) {
+                event.add_timing(self.num_collected as u32);
+
+                // Dropping events is better than allocating here
+                if self.pending_events.len() < self.pending_events.capacity() {
+                    self.pending_events.push_back(event);
+                } else {
+                    nih_debug_assert_failure!("Too many pending events, dropping event");
+                }
+            }
+        }
+
+        let num_samples = buffer.samples();
+        let mut sample_idx = 0;
+        while sample_idx < num_samples {
+            let chunk_len = (self.block_size - self.num_collected).min(num_samples - sample_idx);
+            let host_range = sample_idx..sample_idx + chunk_len;
+            let block_range = self.num_collected..self.num_collected + chunk_len;
+
+            for ((host_channel, input), output) in buffer
+                .as_slice()
+                .iter_mut()
+                .zip(self.main_input.iter_mut())
+                .zip(self.main_output.iter())
+            {
+                input[block_range.clone()].copy_from_slice(&host_channel[host_range.clone()]);
+                host_channel[host_range.clone()].copy_from_slice(&output[block_range.clone()]);
+            }
+            for (host_port, port) in aux.inputs.iter_mut().zip(self.aux_inputs.iter_mut()) {
+                for (host_channel, input) in host_port.as_slice().iter().zip(port.iter_mut()) {
+                    input[block_range.clone()].copy_from_slice(&host_channel[host_range.clone()]);
+                }
+            }
+            for (host_port, port) in aux.outputs.iter_mut().zip(self.aux_outputs_ready.iter()) {
+                for (host_channel, output) in host_port.as_slice().iter_mut().zip(port.iter()) {
+                    host_channel[host_range.clone()].copy_from_slice(&output[block_range.clone()]);
+                }
+            }
+
+            sample_idx += chunk_len;
+            self.num_collected += chunk_len;
+            if self.num_collected == self.block_size {
+                self.last_status = self.process_collected_block(input_events, |buffer, aux| {
+                    process_block(buffer, aux, sample_idx)
+                });
+                self.num_collected = 0;
+            }
+        }
+
+        // The output is delayed by a full block, and so is the tail
+        match self.last_status {
+            ProcessStatus::Tail(samples) => {
+                ProcessStatus::Tail(samples.saturating_add(self.block_size as u32))
+            }
+            status => status,
+        }
+    }
+
+    /// Process the block in `main_input` and `aux_inputs`, and make its outputs available to the
+    /// host.
+    fn process_collected_block(
+        &mut self,
+        input_events: &AtomicRefCell<VecDeque<NoteEvent<S>>>,
+        process_block: impl FnOnce(&mut Buffer, &mut AuxiliaryBuffers) -> ProcessStatus,
+    ) -> ProcessStatus {
+        {
+            let mut input_events = input_events.borrow_mut();
+            input_events.clear();
+            while let Some(event) = self.pending_events.front() {
+                if event.timing() >= self.block_size as u32 {
+                    break;
+                }
+
+                input_events.push_back(self.pending_events.pop_front().unwrap());
+            }
+        }
+        for event in self.pending_events.iter_mut() {
+            event.subtract_timing(self.block_size as u32);
+        }
+
+        for channel in self.aux_outputs.iter_mut().flat_map(|port| port.iter_mut()) {
+            channel.fill(0.0);
+        }
+
+        // SAFETY: The storage is not accessed directly while the plugin holds these buffers, and
+        //         the slices are reassigned before the next block
+        unsafe {
+            self.main_buffer.set_slices(self.block_size, |slices| {
+                for (slice, channel) in slices.iter_mut().zip(self.main_input.iter_mut()) {
+                    *slice = &mut *(channel.as_mut_slice() as *mut [f32]);
+                }
+            });
+            for (buffer, port) in self
+                .aux_input_buffers
+                .iter_mut()
+                .zip(self.aux_inputs.iter_mut())
+                .chain(
+                    self.aux_output_buffers
+                        .iter_mut()
+                        .zip(self.aux_outputs.iter_mut()),
+                )
+            {
+                buffer.set_slices(self.block_size, |slices| {
+                    for (slice, channel) in slices.iter_mut().zip(port.iter_mut()) {
+                        *slice = &mut *(channel.as_mut_slice() as *mut [f32]);
+                    }
+                });
+            }
+        }
+
+        let status = process_block(
+            &mut self.main_buffer,
+            &mut AuxiliaryBuffers {
+                inputs: &mut self.aux_input_buffers,
+                outputs: &mut self.aux_output_buffers,
+            },
+        );
+
+        // The processed input becomes the output for the next block, and the old output's storage
+        // is reused for collecting the next block's input
+        std::mem::swap(&mut self.main_input, &mut self.main_output);
+        std::mem::swap(&mut self.aux_outputs, &mut self.aux_outputs_ready);
+
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK_SIZE: u32 = 4;
+
+    fn note_on(timing: u32) -> NoteEvent<()> {
+        NoteEvent::NoteOn {
+            timing,
+            voice_id: None,
+            channel: 0,
+            note: 60,
+            velocity: 1.0,
+        }
+    }
+
+    /// Process a host block of mono audio with a plugin that doubles its input. Returns the output
+    /// offsets and the events of every processed block.
+    fn process_host_block(
+        adapter: &mut FixedBlockAdapter<()>,
+        input_events: &AtomicRefCell<VecDeque<NoteEvent<()>>>,
+        samples: &mut [f32],
+    ) -> Vec<(usize, Vec<NoteEvent<()>>)> {
+        let mut buffer = Buffer::default();
+        unsafe {
+            buffer.set_slices(samples.len(), |slices| {
+                *slices = vec![&mut *(samples as *mut [f32])];
+            })
+        };
+
+        let mut processed_blocks = Vec::new();
+        adapter.process(
+            &mut buffer,
+            &mut AuxiliaryBuffers {
+                inputs: &mut [],
+                outputs: &mut [],
+            },
+            input_events,
+            |buffer, _aux, output_offset| {
+                assert_eq!(buffer.samples(), BLOCK_SIZE as usize);
+                for sample in buffer.as_slice()[0].iter_mut() {
+                    *sample *= 2.0;
+                }
+
+                let events = input_events.borrow_mut().drain(..).collect();
+                processed_blocks.push((output_offset, events));
+
+                ProcessStatus::Normal
+            },
+        );
+
+        processed_blocks
+    }
+
+    #[test]
+    fn fixed_block_latency() {
+        let layout = AudioIOLayout {
+            main_input_channels: NonZeroU32::new(1),
+            main_output_channels: NonZeroU32::new(1),
+            ..AudioIOLayout::const_default()
+        };
+        let mut adapter = FixedBlockAdapter::<()>::new(BLOCK_SIZE, &layout);
+        let input_events = AtomicRefCell::new(VecDeque::new());
+
+        // The host's block sizes don't line up with the internal block size
+        let mut first_block = [1.0, 2.0, 3.0];
+        assert!(process_host_block(&mut adapter, &input_events, &mut first_block).is_empty());
+        assert_eq!(first_block, [0.0; 3]);
+
+        input_events.borrow_mut().push_back(note_on(1));
+        let mut second_block = [4.0, 5.0, 6.0, 7.0, 8.0];
+        let processed_blocks = process_host_block(&mut adapter, &input_events, &mut second_block);
+        assert_eq!(second_block, [0.0, 2.0, 4.0, 6.0, 8.0]);
+
+        // The event at the second host sample is the fifth sample overall, so it belongs to the
+        // second internal block
+        assert_eq!(processed_blocks, [(1, vec![]), (5, vec![note_on(0)])]);
+
+        let mut third_block = [9.0; 4];
+        let processed_blocks = process_host_block(&mut adapter, &input_events, &mut third_block);
+        assert_eq!(third_block, [10.0, 12.0, 14.0, 16.0]);
+        assert_eq!(processed_blocks, [(4, vec![])]);
+    }
+}
@@ -0,0 +1,180 @@
+//! Catching panics in the plugin for [`Plugin::CATCH_PANICS`].
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::buffer::Buffer;
+use crate::plugin::Plugin;
+use crate::util::permit_alloc;
+use crate::wrapper::state::PluginState;
+
+/// Keeps track of whether the plugin panicked during audio processing. Once that happens the
+/// wrapper should only output silence until the plugin gets initialized again, after which
+/// [`reset()`][Self::reset()] should be called.
+#[derive(Debug, Default)]
+pub struct PanicGuard {
+    panicked: AtomicBool,
+}
+
+impl PanicGuard {
+    /// Whether the plugin panicked during processing since the last call to
+    /// [`reset()`][Self::reset()]. The wrapper should output silence instead of calling the
+    /// plugin's process function when this is set.
+    pub fn has_panicked(&self) -> bool {
+        self.panicked.load(Ordering::Relaxed)
+    }
+
+    /// Clear the panicked flag after the plugin has been initialized again.
+    pub fn reset(&self) {
+        self.panicked.store(false, Ordering::Relaxed);
+    }
+
+    /// Run `f`, which calls the plugin's process function. If [`Plugin::CATCH_PANICS`] is enabled
+    /// and `f` panics, then the panicked flag is set and `None` is returned. The panic itself has
+    /// already been logged by the panic hook at that point.
+    pub fn catch_process<P: Plugin, T>(&self, f: impl FnOnce() -> T) -> Option<T> {
+        let result = catch_panic::<P, T>(f);
+        if result.is_none() {
+            nih_error!(
+                "The plugin panicked while processing audio, it will output silence until it is \
+                 reactivated by the host"
+            );
+            self.panicked.store(true, Ordering::Relaxed);
+        }
+
+        result
+    }
+}
+
+/// Run `f`. If [`Plugin::CATCH_PANICS`] is enabled and `f` panics, then `None` is returned instead
+/// of unwinding further. Without this, a panic in an `extern "C"` function would abort the host.
+pub fn catch_panic<P: Plugin, T>(f: impl FnOnce() -> T) -> Option<T> {
+    if P::CATCH_PANICS {
+        // The panic hook allocates to log the panic, so this needs to be allowed here
+        permit_alloc(|| panic::catch_unwind(AssertUnwindSafe(f))).ok()
+    } else {
+        Some(f())
+    }
+}
+
+/// Call [`Plugin::on_panic()`] with the plugin's current state after a panic was caught. Panics in
+/// that function or while serializing the state are caught as well, since the plugin is already in
+/// an unknown state at this point.
+pub fn rescue_state<P: Plugin>(plugin: &mut P, get_state: impl FnOnce() -> PluginState) {
+    let _ = catch_panic::<P, ()>(|| permit_alloc(|| plugin.on_panic(&get_state())));
+}
+
+/// Fill the main output buffer and all auxiliary output buffers with silence. Used in place of the
+/// plugin's output after it panicked, and when a process call gets skipped because of
+/// [`Plugin::SUPPORTS_SILENCE_SKIP`].
+pub fn silence_outputs(main_buffer: &mut Buffer, aux_outputs: &mut [Buffer]) {
+    for channel in main_buffer.as_slice() {
+        channel.fill(0.0);
+    }
+    for buffer in aux_outputs {
+        for channel in buffer.as_slice() {
+            channel.fill(0.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parking_lot::Mutex;
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::prelude::*;
+    use crate::test::fixtures::TestPlugin;
+    use crate::test::MockProcessContext;
+
+    /// A test plugin that outputs 0.5, and that panics while `should_panic` is set. The state passed
+    /// to [`Plugin::on_panic()`] is stored in `rescued_state`.
+    fn panicking_plugin(
+        should_panic: Arc<AtomicBool>,
+        rescued_state: Arc<Mutex<Option<PluginState>>>,
+    ) -> TestPlugin {
+        TestPlugin {
+            process_hook: Some(Box::new(move |buffer| {
+                for channel in buffer.as_slice() {
+                    channel.fill(0.5);
+                }
+                if should_panic.load(Ordering::Relaxed) {
+                    panic!("Test panic");
+                }
+            })),
+            on_panic_hook: Some(Box::new(move |state| {
+                *rescued_state.lock() = Some(state.clone());
+            })),
+            ..TestPlugin::default()
+        }
+    }
+
+    /// Process a block the same way the wrappers do, and return the status and the output.
+    fn process(guard: &PanicGuard, plugin: &mut TestPlugin) -> (Option<ProcessStatus>, [f32; 8]) {
+        let mut samples = [1.0f32; 8];
+        let mut buffer = Buffer::default();
+        unsafe { buffer.set_slices(samples.len(), |slices| *slices = vec![&mut samples]) };
+        let mut aux = AuxiliaryBuffers {
+            inputs: &mut [],
+            outputs: &mut [],
+        };
+        let mut context = MockProcessContext::<TestPlugin>::new(44_100.0);
+
+        let result = if guard.has_panicked() {
+            None
+        } else {
+            let result = guard.catch_process::<TestPlugin, _>(|| {
+                plugin.process(&mut buffer, &mut aux, &mut context)
+            });
+            if result.is_none() {
+                rescue_state(plugin, || PluginState {
+                    version: String::from(TestPlugin::VERSION),
+                    params: BTreeMap::new(),
+                    fields: BTreeMap::new(),
+                    midi_mappings: None,
+                });
+            }
+
+            result
+        };
+        if result.is_none() {
+            silence_outputs(&mut buffer, aux.outputs);
+        }
+
+        (result, samples)
+    }
+
+    #[test]
+    fn process_panics_output_silence() {
+        let guard = PanicGuard::default();
+        let should_panic = Arc::new(AtomicBool::new(false));
+        let rescued_state = Arc::new(Mutex::new(None));
+        let mut plugin = panicking_plugin(should_panic.clone(), rescued_state.clone());
+        assert_eq!(
+            process(&guard, &mut plugin),
+            (Some(ProcessStatus::Normal), [0.5; 8])
+        );
+
+        should_panic.store(true, Ordering::Relaxed);
+        assert_eq!(process(&guard, &mut plugin), (None, [0.0; 8]));
+        assert!(guard.has_panicked());
+        assert_eq!(
+            rescued_state
+                .lock()
+                .as_ref()
+                .map(|state| state.version.as_str()),
+            Some(TestPlugin::VERSION)
+        );
+
+        // The plugin is not called again until it has been reinitialized
+        should_panic.store(false, Ordering::Relaxed);
+        assert_eq!(process(&guard, &mut plugin), (None, [0.0; 8]));
+        guard.reset();
+        assert_eq!(
+            process(&guard, &mut plugin),
+            (Some(ProcessStatus::Normal), [0.5; 8])
+        );
+    }
+}
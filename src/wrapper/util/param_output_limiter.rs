@@ -0,0 +1,169 @@
+//! Rate limiting for parameter changes sent from the audio thread.
+
+use std::collections::HashMap;
+
+use crate::params::internals::ParamPtr;
+
+/// Limits how often the values set through
+/// [`ProcessContext::set_parameter()`][crate::prelude::ProcessContext::set_parameter()] are sent to
+/// the host when [`Plugin::PARAM_OUTPUT_RATE_HZ`][crate::prelude::Plugin::PARAM_OUTPUT_RATE_HZ] is
+/// set. A parameter's first change is sent right away. Changes made before the interval has
+/// passed only replace the parameter's pending value, and that value is sent at the start of the
+/// first process call after the interval has passed. This way plugins can write automation from
+/// their DSP code every sample without flooding the host with automation points.
+#[derive(Debug)]
+pub struct ParamOutputLimiter {
+    /// The maximum number of values per second sent for a single parameter, if rate limiting is
+    /// enabled.
+    rate_hz: Option<f32>,
+    /// The minimum number of samples between two values sent for the same parameter. Zero disables
+    /// rate limiting.
+    interval_samples: u64,
+    /// The sample position of the current process call, counted from the last call to
+    /// [`initialize()`][Self::initialize()].
+    current_sample: u64,
+    /// The sample position of the next process call.
+    next_block_start: u64,
+    /// The state for every parameter. This is filled when the object is created, so it never
+    /// allocates afterwards.
+    params: HashMap<ParamPtr, ParamOutputState>,
+}
+
+#[derive(Debug, Default)]
+struct ParamOutputState {
+    /// The sample position the last value for this parameter was sent at.
+    last_sent_sample: Option<u64>,
+    /// A value that still needs to be sent once the interval has passed.
+    pending_value: Option<f32>,
+}
+
+impl ParamOutputLimiter {
+    /// Create a limiter for a plugin's parameters. `rate_hz` should be
+    /// `Plugin::PARAM_OUTPUT_RATE_HZ`. The limiter doesn't do anything until
+    /// [`initialize()`][Self::initialize()] has been called.
+    pub fn new(rate_hz: Option<f32>, param_ptrs: impl IntoIterator<Item = ParamPtr>) -> Self {
+        Self {
+            rate_hz,
+            interval_samples: 0,
+            current_sample: 0,
+            next_block_start: 0,
+            params: match rate_hz {
+                Some(_) => param_ptrs
+                    .into_iter()
+                    .map(|param_ptr| (param_ptr, ParamOutputState::default()))
+                    .collect(),
+                None => HashMap::new(),
+            },
+        }
+    }
+
+    /// Compute the interval for the sample rate, and forget all sent and pending values. Called
+    /// when the plugin gets initialized.
+    pub fn initialize(&mut self, sample_rate: f32) {
+        self.interval_samples = match self.rate_hz {
+            Some(rate_hz) if rate_hz > 0.0 => (sample_rate / rate_hz).round().max(1.0) as u64,
+            Some(_) => {
+                nih_debug_assert_failure!("PARAM_OUTPUT_RATE_HZ must be positive");
+                0
+            }
+            None => 0,
+        };
+        self.current_sample = 0;
+        self.next_block_start = 0;
+        for state in self.params.values_mut() {
+            *state = ParamOutputState::default();
+        }
+    }
+
+    /// Called before every call to the plugin's process function with the number of samples that
+    /// will be processed. Pending values whose interval has passed are sent through `send`.
+    pub fn begin_block(&mut self, num_samples: usize, mut send: impl FnMut(ParamPtr, f32)) {
+        self.current_sample = self.next_block_start;
+        self.next_block_start += num_samples as u64;
+        if self.interval_samples == 0 {
+            return;
+        }
+
+        for (param_ptr, state) in self.params.iter_mut() {
+            if !state.is_due(self.current_sample, self.interval_samples) {
+                continue;
+            }
+
+            if let Some(value) = state.pending_value.take() {
+                state.last_sent_sample = Some(self.current_sample);
+                send(*param_ptr, value);
+            }
+        }
+    }
+
+    /// The plugin set a parameter's value. Returns the value if it should be sent to the host right
+    /// away.
+    pub fn set(&mut self, param_ptr: ParamPtr, normalized: f32) -> Option<f32> {
+        if self.interval_samples == 0 {
+            return Some(normalized);
+        }
+
+        let Some(state) = self.params.get_mut(&param_ptr) else {
+            return Some(normalized);
+        };
+        if state.is_due(self.current_sample, self.interval_samples) {
+            state.last_sent_sample = Some(self.current_sample);
+            state.pending_value = None;
+            Some(normalized)
+        } else {
+            state.pending_value = Some(normalized);
+            None
+        }
+    }
+
+    /// The plugin ended its automation gesture for a parameter. Returns the parameter's pending
+    /// value, if it has one, so it can be sent before the gesture ends.
+    pub fn end(&mut self, param_ptr: ParamPtr) -> Option<f32> {
+        let state = self.params.get_mut(&param_ptr)?;
+        let value = state.pending_value.take()?;
+        state.last_sent_sample = Some(self.current_sample);
+
+        Some(value)
+    }
+}
+
+impl ParamOutputState {
+    fn is_due(&self, current_sample: u64, interval_samples: u64) -> bool {
+        match self.last_sent_sample {
+            Some(last_sent_sample) => current_sample >= last_sent_sample + interval_samples,
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::range::FloatRange;
+    use crate::params::{FloatParam, Param};
+
+    #[test]
+    fn limit_output_rate() {
+        let gain = FloatParam::new("Gain", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 });
+        let mut limiter = ParamOutputLimiter::new(Some(100.0), [gain.as_ptr()]);
+        // This results in one value every 100 samples
+        limiter.initialize(10_000.0);
+
+        let mut sent = Vec::new();
+        limiter.begin_block(64, |_, value| sent.push(value));
+        assert_eq!(limiter.set(gain.as_ptr(), 0.1), Some(0.1));
+        assert_eq!(limiter.set(gain.as_ptr(), 0.2), None);
+        assert_eq!(limiter.set(gain.as_ptr(), 0.3), None);
+
+        // The interval hasn't passed yet at sample 64
+        limiter.begin_block(64, |_, value| sent.push(value));
+        assert!(sent.is_empty());
+        limiter.begin_block(64, |_, value| sent.push(value));
+        assert_eq!(sent, [0.3]);
+
+        // Ending the gesture sends the pending value right away
+        assert_eq!(limiter.set(gain.as_ptr(), 0.4), None);
+        assert_eq!(limiter.end(gain.as_ptr()), Some(0.4));
+        assert_eq!(limiter.end(gain.as_ptr()), None);
+    }
+}
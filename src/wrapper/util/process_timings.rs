@@ -0,0 +1,145 @@
+//! Measuring how long the plugin's process function takes for
+//! [`Plugin::COLLECT_PROCESS_TIMINGS`], and emitting `tracing` spans for process calls.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::context::gui::ProcessTimings;
+use crate::plugin::Plugin;
+#[cfg(feature = "tracing")]
+use crate::util::permit_alloc;
+
+/// The number of process calls the statistics are computed over.
+const NUM_TIMINGS: usize = 1024;
+
+/// A lock-free ring buffer containing the durations of the last [`NUM_TIMINGS`] process calls.
+/// The audio thread writes to this, and the GUI thread computes statistics from it.
+#[derive(Debug)]
+pub struct ProcessTimingCollector {
+    /// The process call's duration and the duration of the audio it processed, both in nanoseconds
+    /// and packed into the high and low 32 bits respectively. Packing these makes sure the GUI
+    /// never sees a duration paired with the wrong block.
+    timings: Box<[AtomicU64]>,
+    /// The total number of recorded process calls. The next timing is written to
+    /// `num_recorded % NUM_TIMINGS`.
+    num_recorded: AtomicUsize,
+}
+
+impl Default for ProcessTimingCollector {
+    fn default() -> Self {
+        Self {
+            timings: (0..NUM_TIMINGS).map(|_| AtomicU64::new(0)).collect(),
+            num_recorded: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl ProcessTimingCollector {
+    /// Forget all recorded timings. Called when the plugin gets reinitialized since the block
+    /// sizes and sample rate may have changed.
+    pub fn reset(&self) {
+        self.num_recorded.store(0, Ordering::Relaxed);
+    }
+
+    /// Run `f`, which calls the plugin's process function for a block of `num_samples` samples. If
+    /// [`Plugin::COLLECT_PROCESS_TIMINGS`] is enabled then the duration of the call is recorded,
+    /// and if the `tracing` feature is enabled then the call is wrapped in a `process` span.
+    pub fn time<P: Plugin, T>(
+        &self,
+        num_samples: usize,
+        sample_rate: f32,
+        f: impl FnOnce() -> T,
+    ) -> T {
+        // Subscribers may allocate when entering and exiting spans
+        #[cfg(feature = "tracing")]
+        let span = permit_alloc(|| tracing::trace_span!("process", num_samples).entered());
+
+        let result = if P::COLLECT_PROCESS_TIMINGS {
+            let start = Instant::now();
+            let result = f();
+            let duration = start.elapsed();
+
+            // This cast saturates, so an invalid sample rate won't cause any problems here
+            let block_duration_ns = (num_samples as f64 / sample_rate as f64 * 1e9) as u64;
+            self.record(duration, Duration::from_nanos(block_duration_ns));
+
+            result
+        } else {
+            f()
+        };
+
+        #[cfg(feature = "tracing")]
+        permit_alloc(|| drop(span));
+
+        result
+    }
+
+    /// Compute statistics over the recorded timings. Returns `None` if nothing has been recorded
+    /// yet. This allocates, so it should not be called from the audio thread.
+    pub fn statistics(&self) -> Option<ProcessTimings> {
+        let num_timings = self.num_recorded.load(Ordering::Acquire).min(NUM_TIMINGS);
+        if num_timings == 0 {
+            return None;
+        }
+
+        let mut durations = Vec::with_capacity(num_timings);
+        let mut total_duration = Duration::ZERO;
+        let mut total_block_duration = Duration::ZERO;
+        let mut peak_load = 0.0f32;
+        for timing in &self.timings[..num_timings] {
+            let (duration, block_duration) = unpack_timing(timing.load(Ordering::Relaxed));
+            durations.push(duration);
+            total_duration += duration;
+            total_block_duration += block_duration;
+            if !block_duration.is_zero() {
+                peak_load = peak_load.max(duration.as_secs_f32() / block_duration.as_secs_f32());
+            }
+        }
+        durations.sort_unstable();
+
+        let percentile = |p: f32| durations[((num_timings - 1) as f32 * p).round() as usize];
+        Some(ProcessTimings {
+            num_blocks: num_timings,
+            mean: total_duration / num_timings as u32,
+            median: percentile(0.5),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            max: durations[num_timings - 1],
+            load: if total_block_duration.is_zero() {
+                0.0
+            } else {
+                total_duration.as_secs_f32() / total_block_duration.as_secs_f32()
+            },
+            peak_load,
+        })
+    }
+
+    fn record(&self, duration: Duration, block_duration: Duration) {
+        let idx = self.num_recorded.load(Ordering::Relaxed);
+        self.timings[idx % NUM_TIMINGS]
+            .store(pack_timing(duration, block_duration), Ordering::Relaxed);
+
+        // Wrap around at a multiple of the buffer size so the reader can tell whether the buffer
+        // has been filled yet
+        let next_idx = if idx + 1 >= NUM_TIMINGS * 2 {
+            NUM_TIMINGS
+        } else {
+            idx + 1
+        };
+        self.num_recorded.store(next_idx, Ordering::Release);
+    }
+}
+
+fn pack_timing(duration: Duration, block_duration: Duration) -> u64 {
+    let duration_ns = duration.as_nanos().min(u32::MAX as u128) as u64;
+    let block_duration_ns = block_duration.as_nanos().min(u32::MAX as u128) as u64;
+
+    (duration_ns << 32) | block_duration_ns
+}
+
+fn unpack_timing(timing: u64) -> (Duration, Duration) {
+    (
+        Duration::from_nanos(timing >> 32),
+        Duration::from_nanos(timing & u32::MAX as u64),
+    )
+}
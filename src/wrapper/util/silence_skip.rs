@@ -0,0 +1,160 @@
+//! Skipping process calls while the plugin's input is silent and its tail has ended, for
+//! [`Plugin::SUPPORTS_SILENCE_SKIP`].
+
+use crate::buffer::Buffer;
+use crate::plugin::{Plugin, ProcessStatus};
+use crate::util::vectorized;
+
+/// Samples below this level (-140 dBFS) are considered to be silent.
+const SILENCE_THRESHOLD: f32 = 1e-7;
+
+/// Keeps track of how much of the plugin's tail is left while its input is silent, so the wrapper
+/// can skip calling the plugin's process function once the tail has ended. This is separate from
+/// the host's own suspend mechanism, since many hosts keep calling the plugin regardless of what
+/// it reports.
+#[derive(Debug, Default)]
+pub struct SilenceSkipper {
+    /// The number of samples of tail left since the input became silent, or `None` if the plugin
+    /// should keep getting called. That's the case when it requested to be kept alive, or when it
+    /// returned [`ProcessStatus::Normal`] and its output was not yet silent. This starts out as
+    /// `None` so nothing gets skipped until the plugin has reported its tail at least once.
+    remaining_tail: Option<u64>,
+    /// Whether the input in the last block was silent.
+    input_silent: bool,
+}
+
+impl SilenceSkipper {
+    /// Forget the previously reported tail. Called when the plugin gets reset.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Whether the process call for a block of `num_samples` samples should be skipped. The
+    /// outputs should be cleared instead of calling the plugin if this returns `true`. Always
+    /// returns `false` without calling `input_silent` if [`Plugin::SUPPORTS_SILENCE_SKIP`] is
+    /// disabled. If the plugin does get called, then its result should be passed to
+    /// [`update()`][Self::update()].
+    pub fn should_skip<P: Plugin>(
+        &mut self,
+        input_silent: impl FnOnce() -> bool,
+        num_samples: usize,
+    ) -> bool {
+        P::SUPPORTS_SILENCE_SKIP && self.tail_ended(input_silent(), num_samples)
+    }
+
+    /// Count down the remaining tail for a block of `num_samples` samples if the input is silent.
+    /// Returns `true` if the input is silent and the tail had already ended before this block.
+    fn tail_ended(&mut self, input_silent: bool, num_samples: usize) -> bool {
+        self.input_silent = input_silent;
+        if !input_silent {
+            return false;
+        }
+
+        match self.remaining_tail {
+            Some(0) => true,
+            Some(remaining_tail) => {
+                self.remaining_tail = Some(remaining_tail.saturating_sub(num_samples as u64));
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Update the remaining tail based on the status returned by the plugin's process function.
+    /// While the input stays silent, repeated [`ProcessStatus::Tail`]s don't restart the
+    /// countdown. [`ProcessStatus::Normal`] means the plugin should keep getting called until its
+    /// output is silent, which is checked using `output_silent`.
+    pub fn update(&mut self, status: ProcessStatus, output_silent: impl FnOnce() -> bool) {
+        match status {
+            ProcessStatus::Normal => {
+                self.remaining_tail = if output_silent() { Some(0) } else { None }
+            }
+            ProcessStatus::Tail(samples) if !self.input_silent || self.remaining_tail.is_none() => {
+                self.remaining_tail = Some(samples as u64)
+            }
+            ProcessStatus::Tail(_) => (),
+            ProcessStatus::KeepAlive => self.remaining_tail = None,
+            ProcessStatus::Error(_) => (),
+        }
+    }
+}
+
+/// Check whether the plugin's input is silent. This looks at the main input channels, which are
+/// stored in the first `num_main_input_channels` channels of the main buffer before processing,
+/// at the auxiliary inputs, and at whether there are any incoming note events.
+pub fn input_is_silent(
+    main_buffer: &Buffer,
+    num_main_input_channels: usize,
+    aux_inputs: &[Buffer],
+    has_input_events: bool,
+) -> bool {
+    let channel_is_silent = |channel: &&mut [f32]| is_silent(channel);
+
+    !has_input_events
+        && main_buffer
+            .as_slice_immutable()
+            .iter()
+            .take(num_main_input_channels)
+            .all(channel_is_silent)
+        && aux_inputs
+            .iter()
+            .all(|buffer| buffer.as_slice_immutable().iter().all(channel_is_silent))
+}
+
+/// Check whether the plugin's main and auxiliary outputs are silent after processing.
+pub fn output_is_silent(main_buffer: &Buffer, aux_outputs: &[Buffer]) -> bool {
+    main_buffer
+        .as_slice_immutable()
+        .iter()
+        .chain(
+            aux_outputs
+                .iter()
+                .flat_map(|buffer| buffer.as_slice_immutable().iter()),
+        )
+        .all(|channel| is_silent(channel))
+}
+
+/// Whether all samples in `samples` are below the silence threshold.
+pub fn is_silent(samples: &[f32]) -> bool {
+    vectorized::peak(samples) < SILENCE_THRESHOLD
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skip_after_tail() {
+        let mut skipper = SilenceSkipper::default();
+
+        // Nothing is skipped until the plugin has reported its tail
+        assert!(!skipper.tail_ended(true, 100));
+        skipper.update(ProcessStatus::Tail(150), || true);
+        assert!(!skipper.tail_ended(true, 100));
+        skipper.update(ProcessStatus::Tail(150), || true);
+        assert!(!skipper.tail_ended(true, 100));
+        skipper.update(ProcessStatus::Tail(150), || true);
+        assert!(skipper.tail_ended(true, 100));
+
+        // Non-silent input restarts the tail
+        assert!(!skipper.tail_ended(false, 100));
+        skipper.update(ProcessStatus::Tail(150), || true);
+        assert!(!skipper.tail_ended(true, 100));
+        skipper.update(ProcessStatus::KeepAlive, || true);
+        assert!(!skipper.tail_ended(true, 1000));
+    }
+
+    #[test]
+    fn normal_waits_for_silent_output() {
+        let mut skipper = SilenceSkipper::default();
+
+        // The plugin keeps getting called as long as its output is not silent
+        assert!(!skipper.tail_ended(true, 100));
+        skipper.update(ProcessStatus::Normal, || false);
+        assert!(!skipper.tail_ended(true, 100));
+        skipper.update(ProcessStatus::Normal, || false);
+        assert!(!skipper.tail_ended(true, 100));
+        skipper.update(ProcessStatus::Normal, || true);
+        assert!(skipper.tail_ended(true, 100));
+    }
+}
@@ -0,0 +1,61 @@
+//! Storage for the A/B comparison slots exposed through the [`GuiContext`][crate::prelude::GuiContext].
+
+use crossbeam::atomic::AtomicCell;
+use parking_lot::Mutex;
+
+use crate::prelude::{PluginState, StateSlot};
+
+/// The two plugin states used for A/B comparisons. The active slot's contents are only updated when
+/// switching away from it, since the plugin's current state is always the active slot's state.
+#[derive(Default)]
+pub struct StateSlots {
+    /// The stored states, indexed by [`StateSlot`]. A slot will be empty until it has been used.
+    slots: Mutex<[Option<PluginState>; 2]>,
+    /// The slot the plugin's current state belongs to. This is only written to while holding the
+    /// `slots` lock so concurrent switches can't both act on the same old slot.
+    active_slot: AtomicCell<StateSlot>,
+}
+
+impl StateSlots {
+    /// The slot the plugin's current state belongs to.
+    pub fn active_slot(&self) -> StateSlot {
+        self.active_slot.load()
+    }
+
+    /// Store the plugin's current state in the active slot and make `slot` the active slot. Returns
+    /// the state that should be restored for `slot`, if any. If `slot` has not been used before then
+    /// it starts out as a copy of the current state and nothing needs to be restored. The
+    /// `current_state` function is only called when `slot` is not already the active slot.
+    pub fn switch(
+        &self,
+        slot: StateSlot,
+        current_state: impl FnOnce() -> PluginState,
+    ) -> Option<PluginState> {
+        let mut slots = self.slots.lock();
+        let old_slot = self.active_slot.load();
+        if slot == old_slot {
+            return None;
+        }
+
+        slots[slot_idx(old_slot)] = Some(current_state());
+        self.active_slot.store(slot);
+
+        // The new slot's state is about to become the plugin's current state, so there's no need to
+        // keep it around
+        slots[slot_idx(slot)].take()
+    }
+
+    /// Overwrite the inactive slot with the plugin's current state.
+    pub fn copy_to_other_slot(&self, current_state: PluginState) {
+        let mut slots = self.slots.lock();
+        let other_slot = self.active_slot.load().other();
+        slots[slot_idx(other_slot)] = Some(current_state);
+    }
+}
+
+fn slot_idx(slot: StateSlot) -> usize {
+    match slot {
+        StateSlot::A => 0,
+        StateSlot::B => 1,
+    }
+}
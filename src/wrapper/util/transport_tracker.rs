@@ -0,0 +1,51 @@
+//! Detecting transport changes between process calls.
+
+use crate::context::process::Transport;
+
+/// Differences smaller than this many samples between the expected and the reported playhead
+/// position are not considered to be jumps, since the positions reported by the host may have
+/// been converted from seconds or beats.
+const JUMP_TOLERANCE_SAMPLES: i64 = 1;
+
+/// Remembers the transport's state at the end of the previous process call so the
+/// [`Transport::started_playing()`], [`Transport::stopped_playing()`], and
+/// [`Transport::position_jumped()`] flags can be set for the next call. This should be reset
+/// whenever the plugin gets reset.
+#[derive(Debug, Default)]
+pub struct TransportTracker {
+    /// Whether the transport was playing during the previous process call, or `None` if there has
+    /// not been a previous process call.
+    prev_playing: Option<bool>,
+    /// The position the playhead should be at during the next process call if it was not moved.
+    expected_pos_samples: Option<i64>,
+}
+
+impl TransportTracker {
+    /// Set the change flags on `transport` by comparing it to the previous process call's
+    /// transport, and then store its information for the next call. `num_samples` is the length of
+    /// the buffer `transport` belongs to.
+    pub fn update(&mut self, transport: &mut Transport, num_samples: usize) {
+        let pos_samples = transport.pos_samples();
+
+        transport.playing_changed =
+            matches!(self.prev_playing, Some(prev_playing) if prev_playing != transport.playing);
+        transport.position_jumped = match (self.expected_pos_samples, pos_samples) {
+            (Some(expected_pos_samples), Some(pos_samples)) => {
+                (pos_samples - expected_pos_samples).abs() > JUMP_TOLERANCE_SAMPLES
+            }
+            _ => false,
+        };
+
+        self.prev_playing = Some(transport.playing);
+        self.expected_pos_samples = if transport.playing {
+            pos_samples.map(|pos_samples| pos_samples + num_samples as i64)
+        } else {
+            pos_samples
+        };
+    }
+
+    /// Forget about the previous process call. The next call won't report any changes.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
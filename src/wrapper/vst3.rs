@@ -43,6 +43,12 @@ macro_rules! nih_export_vst3 {
             // if the types are not public because this is a child module.
             use super::*;
 
+            // Typos and missing main categories in the plugins' subcategories result in compile
+            // errors
+            $(const _: () = $crate::wrapper::vst3::subcategories::Vst3SubCategory::assert_valid(
+                <$plugin_ty as $crate::prelude::Vst3Plugin>::VST3_SUBCATEGORIES,
+            );)+
+
             // Sneaky way to get the number of expanded elements
             const PLUGIN_COUNT: usize = [$(stringify!($plugin_ty)),+].len();
 
@@ -3,11 +3,13 @@ use std::cell::Cell;
 use std::collections::VecDeque;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use vst3_sys::vst::IComponentHandler;
+use vst3_sys::vst::{IComponentHandler, IComponentHandler2};
 
+use crate::midi::learn::MidiLearn;
 use crate::prelude::{
-    GuiContext, InitContext, ParamPtr, PluginApi, PluginNoteEvent, PluginState, ProcessContext,
-    Transport, Vst3Plugin,
+    BufferConfig, FactoryPreset, GuiContext, HostInfo, InitContext, ParamPtr, PluginApi,
+    PluginNoteEvent, PluginState, ProcessContext, ProcessTimings, StateSlot, TailLength, Transport,
+    TransportSnapshot, Vst3Plugin,
 };
 
 use super::inner::{Task, WrapperInner};
@@ -67,6 +69,19 @@ impl<P: Vst3Plugin> InitContext<P> for WrapperInitContext<'_, P> {
         PluginApi::Vst3
     }
 
+    fn host_info(&self) -> HostInfo {
+        HostInfo {
+            name: self.inner.host_name.borrow().clone(),
+            // VST3 hosts only report their name
+            vendor: None,
+            version: None,
+            // Latency changes are always reported through `IComponentHandler::restart_component()`
+            supports_latency_changes: true,
+            // VST3 doesn't have an equivalent to CLAP's thread pool extension
+            supports_parallel_execution: false,
+        }
+    }
+
     fn execute(&self, task: P::BackgroundTask) {
         (self.inner.task_executor.lock())(task);
     }
@@ -109,6 +124,15 @@ impl<P: Vst3Plugin> ProcessContext<P> for WrapperProcessContext<'_, P> {
         self.output_events_guard.push_back(event);
     }
 
+    #[inline]
+    fn next_gui_message(&mut self) -> Option<P::GuiMessage> {
+        self.inner.gui_messages.pop()
+    }
+
+    fn send_to_editor(&self, event: P::EditorEvent) {
+        self.inner.editor_events.force_push(event);
+    }
+
     fn set_latency_samples(&self, samples: u32) {
         self.inner.set_latency_samples(samples)
     }
@@ -116,6 +140,32 @@ impl<P: Vst3Plugin> ProcessContext<P> for WrapperProcessContext<'_, P> {
     fn set_current_voice_capacity(&self, _capacity: u32) {
         // This is only supported by CLAP
     }
+
+    unsafe fn raw_begin_set_parameter(&mut self, _param: ParamPtr) {
+        // VST3 has no gestures for parameter changes made from the audio thread
+    }
+
+    unsafe fn raw_set_parameter_normalized(&mut self, param: ParamPtr, normalized: f32) {
+        let normalized = self
+            .inner
+            .param_output_limiter
+            .borrow_mut()
+            .set(param, normalized);
+        if let Some(normalized) = normalized {
+            self.inner
+                .queue_parameter_value_from_process(param, normalized);
+        }
+    }
+
+    unsafe fn raw_end_set_parameter(&mut self, param: ParamPtr) {
+        // VST3 doesn't have gestures for these changes, but this is a good moment to send the
+        // value that's being held back by the rate limiting
+        let pending_value = self.inner.param_output_limiter.borrow_mut().end(param);
+        if let Some(normalized) = pending_value {
+            self.inner
+                .queue_parameter_value_from_process(param, normalized);
+        }
+    }
 }
 
 impl<P: Vst3Plugin> GuiContext for WrapperGuiContext<P> {
@@ -221,6 +271,46 @@ impl<P: Vst3Plugin> GuiContext for WrapperGuiContext<P> {
         }
     }
 
+    fn begin_group_edit(&self) {
+        match &*self.inner.component_handler.borrow() {
+            // Group edits are part of an optional extension interface
+            Some(handler) => {
+                if let Some(handler) = handler.cast::<dyn IComponentHandler2>() {
+                    unsafe { handler.start_group_edit() };
+                }
+            }
+            None => nih_debug_assert_failure!("Component handler not yet set"),
+        }
+    }
+
+    fn end_group_edit(&self) {
+        match &*self.inner.component_handler.borrow() {
+            Some(handler) => {
+                if let Some(handler) = handler.cast::<dyn IComponentHandler2>() {
+                    unsafe { handler.finish_group_edit() };
+                }
+            }
+            None => nih_debug_assert_failure!("Component handler not yet set"),
+        }
+    }
+
+    fn start_undo_block(&self, _name: &str) {
+        self.begin_group_edit();
+    }
+
+    fn end_undo_block(&self) {
+        self.end_group_edit();
+
+        match &*self.inner.component_handler.borrow() {
+            Some(handler) => {
+                if let Some(handler) = handler.cast::<dyn IComponentHandler2>() {
+                    unsafe { handler.set_dirty(1) };
+                }
+            }
+            None => nih_debug_assert_failure!("Component handler not yet set"),
+        }
+    }
+
     fn get_state(&self) -> PluginState {
         self.inner.get_state_object()
     }
@@ -228,4 +318,52 @@ impl<P: Vst3Plugin> GuiContext for WrapperGuiContext<P> {
     fn set_state(&self, state: PluginState) {
         self.inner.set_state_object_from_gui(state)
     }
+
+    fn factory_presets(&self) -> &'static [FactoryPreset] {
+        P::FACTORY_PRESETS
+    }
+
+    fn active_state_slot(&self) -> StateSlot {
+        self.inner.state_slots.active_slot()
+    }
+
+    fn switch_state_slot(&self, slot: StateSlot) {
+        let new_state = self
+            .inner
+            .state_slots
+            .switch(slot, || self.inner.get_state_object());
+        if let Some(new_state) = new_state {
+            self.inner.set_state_object_from_gui(new_state);
+        }
+    }
+
+    fn copy_state_to_other_slot(&self) {
+        self.inner
+            .state_slots
+            .copy_to_other_slot(self.inner.get_state_object());
+    }
+
+    fn midi_learn(&self) -> &MidiLearn {
+        &self.inner.midi_learn
+    }
+
+    fn buffer_config(&self) -> Option<BufferConfig> {
+        self.inner.current_buffer_config.load()
+    }
+
+    fn latency_samples(&self) -> u32 {
+        self.inner.current_latency.load(Ordering::SeqCst) + P::FIXED_BLOCK_SIZE.unwrap_or(0)
+    }
+
+    fn tail(&self) -> TailLength {
+        TailLength::from_process_status(self.inner.last_process_status.load())
+    }
+
+    fn transport(&self) -> Option<TransportSnapshot> {
+        self.inner.last_transport.load()
+    }
+
+    fn process_timings(&self) -> Option<ProcessTimings> {
+        self.inner.process_timings.statistics()
+    }
 }
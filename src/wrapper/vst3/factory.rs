@@ -108,8 +108,8 @@ impl PluginInfo {
 /// Build a pipe separated subcategories string for a VST3 plugin.
 fn make_subcategories_string<P: Vst3Plugin>() -> String {
     // No idea if any hosts do something with OnlyRT, but it's part of VST3's example categories
-    // list. Plugins should not be adding this feature manually
-    nih_debug_assert!(!P::VST3_SUBCATEGORIES.contains(&Vst3SubCategory::Custom("OnlyRT")));
+    // list. Plugins cannot add this feature manually, that's checked in
+    // `Vst3SubCategory::assert_valid()`.
     let subcategory_string = P::VST3_SUBCATEGORIES
         .iter()
         .map(Vst3SubCategory::as_str)
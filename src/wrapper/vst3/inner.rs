@@ -1,6 +1,7 @@
 use atomic_refcell::AtomicRefCell;
 use crossbeam::atomic::AtomicCell;
 use crossbeam::channel::{self, SendTimeoutError};
+use crossbeam::queue::ArrayQueue;
 use parking_lot::{Mutex, RwLock};
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
@@ -12,17 +13,38 @@ use vst3_sys::vst::{IComponentHandler, RestartFlags};
 use super::context::{WrapperGuiContext, WrapperInitContext, WrapperProcessContext};
 use super::note_expressions::NoteExpressionController;
 use super::param_units::ParamUnits;
-use super::util::{ObjectPtr, VstPtr, VST3_MIDI_PARAMS_END, VST3_MIDI_PARAMS_START};
+use super::util::{
+    ObjectPtr, VstPtr, VST3_MIDI_PARAMS_END, VST3_MIDI_PARAMS_START, VST3_PROGRAM_CHANGE_PARAM_ID,
+};
 use super::view::WrapperView;
+use crate::debug::realtime::LogDrainHandle;
 use crate::event_loop::{EventLoop, MainThreadExecutor, OsEventLoop};
+use crate::midi::learn::MidiLearn;
 use crate::prelude::{
-    AsyncExecutor, AudioIOLayout, BufferConfig, Editor, MidiConfig, ParamFlags, ParamPtr, Params,
-    Plugin, PluginNoteEvent, ProcessMode, ProcessStatus, TaskExecutor, Transport, Vst3Plugin,
+    AsyncExecutor, AudioIOLayout, BufferConfig, Editor, MidiConfig, NoteEvent, ParamFlags,
+    ParamPtr, Params, Plugin, PluginNoteEvent, ProcessMode, ProcessStatus, TaskExecutor, Transport,
+    TransportSnapshot, Vst3Plugin,
 };
 use crate::util::permit_alloc;
 use crate::wrapper::state::{self, PluginState};
+use crate::wrapper::util::analyzer_passthrough::AnalyzerPassthrough;
 use crate::wrapper::util::buffer_management::BufferManager;
-use crate::wrapper::util::{hash_param_id, process_wrapper};
+use crate::wrapper::util::bypass_crossfade::{self, BypassCrossfader};
+use crate::wrapper::util::cv_inputs::CvInputs;
+use crate::wrapper::util::fixed_block::FixedBlockAdapter;
+use crate::wrapper::util::panic_guard::PanicGuard;
+use crate::wrapper::util::param_output_limiter::ParamOutputLimiter;
+use crate::wrapper::util::process_timings::ProcessTimingCollector;
+use crate::wrapper::util::silence_skip::SilenceSkipper;
+use crate::wrapper::util::state_slots::StateSlots;
+use crate::wrapper::util::transport_tracker::TransportTracker;
+use crate::wrapper::util::{
+    hash_param_id, process_wrapper, EDITOR_EVENT_QUEUE_CAPACITY, GUI_MESSAGE_QUEUE_CAPACITY,
+};
+
+/// How many parameter changes the plugin can make from its process function per block. Changes
+/// beyond this are dropped.
+const OUTPUT_PARAM_CHANGES_CAPACITY: usize = 2048;
 
 /// The actual wrapper bits. We need this as an `Arc<T>` so we can safely use our event loop API.
 /// Since we can't combine that with VST3's interior reference counting this just has to be moved to
@@ -36,6 +58,9 @@ pub(crate) struct WrapperInner<P: Vst3Plugin> {
     /// `ParamPtr`s are guaranteed to live at least as long as this object and we can interact with
     /// the `Params` object without having to acquire a lock on `plugin`.
     pub params: Arc<dyn Params>,
+    /// Restores the plugin's `#[persist_streamed]` fields on a background thread after loading
+    /// state.
+    pub streamed_fields_loader: state::StreamedFieldsLoader,
     /// The plugin's editor, if it has one. This object does not do anything on its own, but we need
     /// to instantiate this in advance so we don't need to lock the entire [`Plugin`] object when
     /// creating an editor. Wrapped in an `AtomicRefCell` because it needs to be initialized late.
@@ -44,6 +69,10 @@ pub(crate) struct WrapperInner<P: Vst3Plugin> {
     /// The host's [`IComponentHandler`] instance, if passed through
     /// [`IEditController::set_component_handler`].
     pub component_handler: AtomicRefCell<Option<VstPtr<dyn IComponentHandler>>>,
+    /// The host's name, if the host passed an
+    /// [`IHostApplication`][vst3_sys::vst::IHostApplication] to
+    /// [`IPluginBase::initialize`][vst3_sys::base::IPluginBase::initialize].
+    pub host_name: AtomicRefCell<Option<String>>,
 
     /// Our own [`IPlugView`] instance. This is set while the editor is actually visible (which is
     /// different form the lifetime of [`WrapperView`][super::WrapperView] itself).
@@ -71,16 +100,48 @@ pub(crate) struct WrapperInner<P: Vst3Plugin> {
     /// The current buffer configuration, containing the sample rate and the maximum block size.
     /// Will be set in `IAudioProcessor::setupProcessing()`.
     pub current_buffer_config: AtomicCell<Option<BufferConfig>>,
+    /// The audio IO layout and buffer config the plugin was last successfully initialized with in
+    /// `IComponent::setActive()`. Used to detect sample rate only changes.
+    pub last_initialized_config: AtomicCell<Option<(AudioIOLayout, BufferConfig)>>,
     /// The current audio processing mode. Set in `IAudioProcessor::setup_processing()`.
     pub current_process_mode: AtomicCell<ProcessMode>,
     /// The last process status returned by the plugin. This is used for tail handling.
     pub last_process_status: AtomicCell<ProcessStatus>,
+    /// The transport information from the most recent process call, for
+    /// [`GuiContext::transport()`][crate::prelude::GuiContext::transport()].
+    pub last_transport: AtomicCell<Option<TransportSnapshot>>,
+    /// Used to detect transport starts, stops, and jumps between process calls.
+    pub transport_tracker: AtomicRefCell<TransportTracker>,
+    /// Decides when process calls can be skipped when `P::SUPPORTS_SILENCE_SKIP` is enabled.
+    pub silence_skipper: AtomicRefCell<SilenceSkipper>,
+    /// Keeps track of whether the plugin panicked during processing when `P::CATCH_PANICS` is
+    /// enabled. Reset when the plugin is activated again.
+    pub panic_guard: PanicGuard,
+    /// Measures the durations of the plugin's process calls when `P::COLLECT_PROCESS_TIMINGS` is
+    /// enabled. Reset when the plugin is activated again.
+    pub process_timings: ProcessTimingCollector,
     /// The current latency in samples, as set by the plugin through the [`InitContext`] and the
     /// [`ProcessContext`].
     pub current_latency: AtomicU32,
     /// A data structure that helps manage and create buffers for all of the plugin's inputs and
     /// outputs based on channel pointers provided by the host.
     pub buffer_manager: AtomicRefCell<BufferManager>,
+    /// Buffers the host's audio when `P::FIXED_BLOCK_SIZE` is set so the plugin always processes
+    /// blocks of that size. Allocated when the plugin gets activated.
+    pub fixed_block_adapter: AtomicRefCell<Option<FixedBlockAdapter<P::SysExMessage>>>,
+    /// The plugin's bypass parameter, if `P::BYPASS_CROSSFADE_MS` is set and the plugin has one.
+    pub bypass_param: Option<ParamPtr>,
+    /// Crossfades between the processed and the dry signal when `bypass_param` changes. Allocated
+    /// when the plugin gets activated.
+    pub bypass_crossfader: AtomicRefCell<Option<BypassCrossfader>>,
+    /// Restores the main buffer after processing if `P::ANALYZER_MODE` is set. Allocated when the
+    /// plugin gets activated.
+    pub analyzer_passthrough: AtomicRefCell<Option<AnalyzerPassthrough>>,
+    /// Copies the auxiliary inputs to the parameters that accept audio-rate modulation.
+    pub cv_inputs: CvInputs,
+    /// Limits how often parameter changes made from the audio thread are sent to the host, if
+    /// `P::PARAM_OUTPUT_RATE_HZ` is set.
+    pub param_output_limiter: AtomicRefCell<ParamOutputLimiter>,
     /// The incoming events for the plugin, if `P::ACCEPTS_MIDI` is set. If
     /// `P::SAMPLE_ACCURATE_AUTOMATION`, this is also read in lockstep with the parameter change
     /// block splitting.
@@ -92,6 +153,19 @@ pub(crate) struct WrapperInner<P: Vst3Plugin> {
     /// Stores any events the plugin has output during the current processing cycle, analogous to
     /// `input_events`.
     pub output_events: AtomicRefCell<VecDeque<PluginNoteEvent<P>>>,
+    /// Parameter changes made by the plugin from its process function through
+    /// [`ProcessContext::set_parameter()`][crate::prelude::ProcessContext::set_parameter()], as
+    /// `(param_hash, normalized_value)` pairs. These are applied and sent to the host through the
+    /// output parameter changes at the end of each block.
+    pub output_param_changes: ArrayQueue<(u32, f32)>,
+    /// Messages sent from the editor to the audio thread through
+    /// [`AsyncExecutor::send_to_dsp()`], received using
+    /// [`ProcessContext::next_gui_message()`][crate::prelude::ProcessContext::next_gui_message()].
+    pub gui_messages: ArrayQueue<P::GuiMessage>,
+    /// Events sent from the audio thread to the editor through
+    /// [`ProcessContext::send_to_editor()`][crate::prelude::ProcessContext::send_to_editor()],
+    /// received using [`AsyncExecutor::next_editor_event()`].
+    pub editor_events: ArrayQueue<P::EditorEvent>,
     /// VST3 has several useful predefined note expressions, but for some reason they are the only
     /// note event type that don't have MIDI note ID and channel fields. So we need to keep track of
     /// the most recent VST3 note IDs we've seen, and then map those back to MIDI note IDs and
@@ -118,6 +192,16 @@ pub(crate) struct WrapperInner<P: Vst3Plugin> {
     pub updated_state_sender: channel::Sender<PluginState>,
     /// The receiver belonging to [`new_state_sender`][Self::new_state_sender].
     pub updated_state_receiver: channel::Receiver<PluginState>,
+    /// The states used for A/B comparisons through the `GuiContext`.
+    pub state_slots: StateSlots,
+    /// The MIDI CCs mapped to parameters through the `GuiContext`. See
+    /// [`apply_midi_learn()`][Self::apply_midi_learn()].
+    pub midi_learn: MidiLearn,
+    /// Keeps the thread alive that forwards messages logged from the audio thread to the logger.
+    _log_drain: LogDrainHandle,
+    /// The index of the factory preset last selected through the program change parameter. Only
+    /// used when `P::FACTORY_PRESETS` is not empty.
+    pub current_factory_preset: AtomicCell<usize>,
 
     /// The keys from `param_map` in a stable order.
     pub param_hashes: Vec<u32>,
@@ -209,6 +293,27 @@ impl<P: Vst3Plugin> WrapperInner<P> {
                 (id, hash, ptr, group)
             })
             .collect();
+        let bypass_param = bypass_crossfade::find_bypass_param::<P>(
+            param_id_hashes_ptrs_groups
+                .iter()
+                .map(|(_, _, ptr, _)| *ptr),
+        );
+        let cv_inputs = CvInputs::new(
+            param_id_hashes_ptrs_groups
+                .iter()
+                .map(|(_, _, ptr, _)| *ptr),
+        );
+        let param_output_limiter = ParamOutputLimiter::new(
+            P::PARAM_OUTPUT_RATE_HZ,
+            param_id_hashes_ptrs_groups
+                .iter()
+                .map(|(_, _, ptr, _)| *ptr),
+        );
+        let midi_learn = MidiLearn::new(
+            param_id_hashes_ptrs_groups
+                .iter()
+                .map(|(id, _, ptr, _)| (id.clone(), *ptr)),
+        );
         if cfg!(debug_assertions) {
             let param_map = params.param_map();
             let param_ids: HashSet<_> = param_id_hashes_ptrs_groups
@@ -244,6 +349,14 @@ impl<P: Vst3Plugin> WrapperInner<P> {
                         id
                     );
                 }
+
+                if !P::FACTORY_PRESETS.is_empty() && *hash == VST3_PROGRAM_CHANGE_PARAM_ID {
+                    nih_debug_assert_failure!(
+                        "Parameter '{}' collides with the automatically generated program change \
+                         parameter, consider giving it a different ID",
+                        id
+                    );
+                }
             }
         }
 
@@ -278,10 +391,12 @@ impl<P: Vst3Plugin> WrapperInner<P> {
             plugin: Mutex::new(plugin),
             task_executor,
             params,
+            streamed_fields_loader: state::StreamedFieldsLoader::default(),
             // Initialized later as it needs a reference to the wrapper for the async executor
             editor: AtomicRefCell::new(None),
 
             component_handler: AtomicRefCell::new(None),
+            host_name: AtomicRefCell::new(None),
 
             plug_view: RwLock::new(None),
 
@@ -296,8 +411,14 @@ impl<P: Vst3Plugin> WrapperInner<P> {
                 P::AUDIO_IO_LAYOUTS.first().copied().unwrap_or_default(),
             ),
             current_buffer_config: AtomicCell::new(None),
+            last_initialized_config: AtomicCell::new(None),
             current_process_mode: AtomicCell::new(ProcessMode::Realtime),
             last_process_status: AtomicCell::new(ProcessStatus::Normal),
+            last_transport: AtomicCell::new(None),
+            transport_tracker: AtomicRefCell::new(TransportTracker::default()),
+            silence_skipper: AtomicRefCell::new(SilenceSkipper::default()),
+            panic_guard: PanicGuard::default(),
+            process_timings: ProcessTimingCollector::default(),
             current_latency: AtomicU32::new(0),
             // This is initialized just before calling `Plugin::initialize()` so that during the
             // process call buffers can be initialized without any allocations
@@ -305,12 +426,25 @@ impl<P: Vst3Plugin> WrapperInner<P> {
                 0,
                 AudioIOLayout::default(),
             )),
+            fixed_block_adapter: AtomicRefCell::new(None),
+            bypass_param,
+            bypass_crossfader: AtomicRefCell::new(None),
+            analyzer_passthrough: AtomicRefCell::new(None),
+            cv_inputs,
+            param_output_limiter: AtomicRefCell::new(param_output_limiter),
             input_events: AtomicRefCell::new(VecDeque::with_capacity(1024)),
             output_events: AtomicRefCell::new(VecDeque::with_capacity(1024)),
+            output_param_changes: ArrayQueue::new(OUTPUT_PARAM_CHANGES_CAPACITY),
+            gui_messages: ArrayQueue::new(GUI_MESSAGE_QUEUE_CAPACITY),
+            editor_events: ArrayQueue::new(EDITOR_EVENT_QUEUE_CAPACITY),
             note_expression_controller: AtomicRefCell::new(NoteExpressionController::default()),
             process_events: AtomicRefCell::new(Vec::with_capacity(4096)),
             updated_state_sender,
             updated_state_receiver,
+            state_slots: StateSlots::default(),
+            midi_learn,
+            _log_drain: LogDrainHandle::get_or_create(),
+            current_factory_preset: AtomicCell::new(0),
 
             param_hashes,
             param_by_hash,
@@ -347,6 +481,24 @@ impl<P: Vst3Plugin> WrapperInner<P> {
                         nih_debug_assert!(task_posted, "The task queue is full, dropping task...");
                     }
                 }),
+                send_to_dsp: Arc::new({
+                    let wrapper = wrapper.clone();
+
+                    move |message| {
+                        let message_posted = wrapper.gui_messages.push(message).is_ok();
+                        nih_debug_assert!(
+                            message_posted,
+                            "The GUI message queue is full, dropping message..."
+                        );
+
+                        message_posted
+                    }
+                }),
+                next_editor_event: Arc::new({
+                    let wrapper = wrapper.clone();
+
+                    move || wrapper.editor_events.pop()
+                }),
             })
             .map(|editor| Arc::new(Mutex::new(editor)));
 
@@ -461,6 +613,59 @@ impl<P: Vst3Plugin> WrapperInner<P> {
         }
     }
 
+    /// Queue a new value for a parameter changed by the plugin from its process function in
+    /// [`output_param_changes`][Self::output_param_changes]. These are applied and sent to the host
+    /// at the end of the current block.
+    pub fn queue_parameter_value_from_process(&self, param: ParamPtr, normalized: f32) {
+        match self.param_ptr_to_hash.get(&param) {
+            Some(hash) => {
+                let success = self.output_param_changes.push((*hash, normalized)).is_ok();
+                nih_debug_assert!(success, "Parameter output queue was full");
+            }
+            None => nih_debug_assert_failure!("Unknown parameter: {:?}", param),
+        }
+    }
+
+    /// Send the parameter values held back by `P::PARAM_OUTPUT_RATE_HZ` whose interval has passed.
+    /// Called before every call to the plugin's process function.
+    pub fn flush_param_output(&self, num_samples: usize) {
+        self.param_output_limiter
+            .borrow_mut()
+            .begin_block(num_samples, |param, normalized| {
+                self.queue_parameter_value_from_process(param, normalized)
+            });
+    }
+
+    /// If `event` is a MIDI CC that has been mapped to a parameter through MIDI learn, then return
+    /// the parameter's hash and its new normalized value. The change is also queued in
+    /// [`output_param_changes`][Self::output_param_changes] so it's sent to the host. The event
+    /// should not be passed to the plugin in that case.
+    pub fn apply_midi_learn(&self, event: &PluginNoteEvent<P>) -> Option<(u32, f32)> {
+        let NoteEvent::MidiCC {
+            channel, cc, value, ..
+        } = *event
+        else {
+            return None;
+        };
+        let (param_ptr, normalized_value) = self.midi_learn.handle_cc(channel, cc, value)?;
+
+        match self.param_ptr_to_hash.get(&param_ptr) {
+            Some(hash) => {
+                let success = self
+                    .output_param_changes
+                    .push((*hash, normalized_value))
+                    .is_ok();
+                nih_debug_assert!(success, "Parameter output queue was full");
+
+                Some((*hash, normalized_value))
+            }
+            None => {
+                nih_debug_assert_failure!("Unknown parameter: {:?}", param_ptr);
+                None
+            }
+        }
+    }
+
     /// Get the plugin's state object, may be called by the plugin's GUI as part of its own preset
     /// management. The wrapper doesn't use these functions and serializes and deserializes directly
     /// the JSON in the relevant plugin API methods instead.
@@ -469,6 +674,7 @@ impl<P: Vst3Plugin> WrapperInner<P> {
             state::serialize_object::<P>(
                 self.params.clone(),
                 state::make_params_iter(&self.param_by_hash, &self.param_id_to_hash),
+                Some(&self.midi_learn),
             )
         }
     }
@@ -562,6 +768,7 @@ impl<P: Vst3Plugin> WrapperInner<P> {
                 self.params.clone(),
                 state::make_params_getter(&self.param_by_hash, &self.param_id_to_hash),
                 buffer_config.as_ref(),
+                Some(&self.midi_learn),
             )
         });
         if !success {
@@ -580,7 +787,7 @@ impl<P: Vst3Plugin> WrapperInner<P> {
                 plugin.initialize(&audio_io_layout, &buffer_config, &mut init_context)
             });
             if success {
-                process_wrapper(|| plugin.reset());
+                process_wrapper("Plugin::reset() after loading state", || plugin.reset());
             }
         }
 
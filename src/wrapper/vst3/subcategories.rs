@@ -46,7 +46,7 @@ pub enum Vst3SubCategory {
 }
 
 impl Vst3SubCategory {
-    pub fn as_str(&self) -> &'static str {
+    pub const fn as_str(&self) -> &'static str {
         match self {
             Vst3SubCategory::Fx => "Fx",
             Vst3SubCategory::Instrument => "Instrument",
@@ -75,15 +75,73 @@ impl Vst3SubCategory {
             Vst3SubCategory::Stereo => "Stereo",
             Vst3SubCategory::Surround => "Surround",
             Vst3SubCategory::Ambisonics => "Ambisonics",
-            Vst3SubCategory::Custom(s) => {
-                nih_debug_assert!(
-                    !s.contains('|'),
-                    "'{}' contains a pipe character ('|'), which is not allowed",
-                    s
-                );
+            // These are checked in `assert_valid()`
+            Vst3SubCategory::Custom(s) => s,
+        }
+    }
+
+    /// Check a plugin's subcategories. This is called in a constant context by
+    /// `nih_export_vst3!()`, so mistakes result in compile errors instead of the plugin silently
+    /// missing from the host's plugin browser. This fails if:
+    ///
+    /// - None of the main categories (`Fx`, `Instrument`, or `Spatial`) are present.
+    /// - A custom subcategory is empty, contains a pipe character, or starts with `Only`. The
+    ///   `Only*` subcategories are reserved for the framework.
+    /// - The subcategory string would be longer than 127 characters.
+    pub const fn assert_valid(subcategories: &[Vst3SubCategory]) {
+        let mut has_main_category = false;
+        // This includes the pipe characters separating the subcategories
+        let mut string_len = 0;
+        let mut i = 0;
+        while i < subcategories.len() {
+            match subcategories[i] {
+                Vst3SubCategory::Fx | Vst3SubCategory::Instrument | Vst3SubCategory::Spatial => {
+                    has_main_category = true
+                }
+                Vst3SubCategory::Custom(s) => {
+                    let bytes = s.as_bytes();
+                    assert!(
+                        !bytes.is_empty(),
+                        "Custom VST3 subcategories cannot be empty"
+                    );
+                    assert!(
+                        !(bytes.len() >= 4
+                            && bytes[0] == b'O'
+                            && bytes[1] == b'n'
+                            && bytes[2] == b'l'
+                            && bytes[3] == b'y'),
+                        "The 'Only*' VST3 subcategories are added by the framework and should not \
+                         be added manually"
+                    );
+
+                    let mut j = 0;
+                    while j < bytes.len() {
+                        assert!(
+                            bytes[j] != b'|',
+                            "Custom VST3 subcategories cannot contain pipe characters ('|')"
+                        );
 
-                s
+                        j += 1;
+                    }
+                }
+                _ => (),
             }
+
+            if i > 0 {
+                string_len += 1;
+            }
+            string_len += subcategories[i].as_str().len();
+            i += 1;
         }
+
+        assert!(
+            has_main_category,
+            "'VST3_SUBCATEGORIES' needs to contain at least one of 'Vst3SubCategory::Fx', \
+             'Vst3SubCategory::Instrument', or 'Vst3SubCategory::Spatial'"
+        );
+        assert!(
+            string_len <= 127,
+            "The VST3 subcategory string cannot be longer than 127 characters"
+        );
     }
 }
@@ -1,10 +1,16 @@
 use std::cmp;
+use std::ffi::c_void;
+use std::fmt;
+use std::io;
 use std::ops::Deref;
+use vst3_sys::base::{kResultOk, IBStream};
 use vst3_sys::interfaces::IUnknown;
-use vst3_sys::vst::TChar;
+use vst3_sys::vst::{SpeakerArrangement, TChar};
 use vst3_sys::ComInterface;
 use widestring::U16CString;
 
+use crate::audio_setup::{ChannelLayout, Speaker};
+
 /// When `Plugin::MIDI_INPUT` is set to `MidiConfig::MidiCCs` or higher then we'll register 130*16
 /// additional parameters to handle MIDI CCs, channel pressure, and pitch bend, in that order.
 /// vst3-sys doesn't expose these constants.
@@ -19,6 +25,56 @@ pub const VST3_MIDI_PARAMS_START: u32 = VST3_MIDI_PARAMS_END - VST3_MIDI_NUM_PAR
 /// The (exclusive) end of the MIDI CC parameter range. Anything above this is reserved by the host.
 pub const VST3_MIDI_PARAMS_END: u32 = 1 << 31;
 
+/// When `Plugin::FACTORY_PRESETS` is not empty, then we'll expose the presets through a program
+/// list on the root unit. Hosts select programs from that list using a special program change
+/// parameter, which is placed just before the MIDI CC parameter range.
+pub const VST3_PROGRAM_CHANGE_PARAM_ID: u32 = VST3_MIDI_PARAMS_START - 1;
+/// The ID for the factory preset program list.
+pub const VST3_FACTORY_PRESETS_PROGRAM_LIST_ID: i32 = 0;
+
+/// The VST3 speaker bit for each [`Speaker`]. vst3-sys only exposes the predefined arrangements,
+/// not the individual speakers.
+const fn speaker_bit(speaker: Speaker) -> SpeakerArrangement {
+    match speaker {
+        Speaker::FrontLeft => 1 << 0,
+        Speaker::FrontRight => 1 << 1,
+        Speaker::FrontCenter => 1 << 2,
+        Speaker::LowFrequency => 1 << 3,
+        Speaker::BackLeft => 1 << 4,
+        Speaker::BackRight => 1 << 5,
+        Speaker::SideLeft => 1 << 9,
+        Speaker::SideRight => 1 << 10,
+        Speaker::TopFrontLeft => 1 << 12,
+        Speaker::TopFrontRight => 1 << 14,
+        Speaker::TopBackLeft => 1 << 15,
+        Speaker::TopBackRight => 1 << 17,
+    }
+}
+
+/// VST3's `kSpeakerM`. Mono buses use this dedicated speaker instead of the center speaker.
+const SPEAKER_MONO_BIT: SpeakerArrangement = 1 << 19;
+
+/// The VST3 speaker bits for the ambisonic channels in ACN order, `kSpeakerACN0` through
+/// `kSpeakerACN15`. Channels in a VST3 bus are ordered by their speaker bits, and these bits are
+/// not contiguous.
+const AMBISONIC_ACN_BITS: [u32; 16] = [
+    20, 21, 22, 23, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48, 49,
+];
+
+/// Convert a channel layout to a VST3 speaker arrangement. The channels in a VST3 bus are ordered
+/// by their speaker bits, which matches the channel order documented on [`ChannelLayout`].
+pub fn speaker_arrangement(layout: ChannelLayout) -> SpeakerArrangement {
+    match (layout, layout.speakers()) {
+        (ChannelLayout::Mono, _) => SPEAKER_MONO_BIT,
+        (_, Some(speakers)) => speakers.iter().fold(0, |arrangement, &speaker| {
+            arrangement | speaker_bit(speaker)
+        }),
+        (_, None) => AMBISONIC_ACN_BITS[..layout.num_channels() as usize]
+            .iter()
+            .fold(0, |arrangement, bit| arrangement | (1 << bit)),
+    }
+}
+
 /// Early exit out of a VST3 function when one of the passed pointers is null
 macro_rules! check_null_ptr {
     ($ptr:expr $(, $ptrs:expr)* $(, )?) => {
@@ -59,6 +115,55 @@ pub fn u16strlcpy(dest: &mut [TChar], src: &str) {
     dest[copy_len] = 0;
 }
 
+/// A [`fmt::Write`] implementation that writes UTF-16 to a null terminated `TChar` buffer, similar
+/// to [`u16strlcpy()`]. Text that doesn't fit is truncated without splitting surrogate pairs. Used
+/// to format parameter values without allocating.
+pub struct U16CStrWriter<'a> {
+    dest: &'a mut [TChar],
+    len: usize,
+}
+
+impl<'a> U16CStrWriter<'a> {
+    /// Start writing to `dest`. This clears any existing string in `dest`.
+    pub fn new(dest: &'a mut [TChar]) -> Self {
+        if let Some(first) = dest.first_mut() {
+            *first = 0;
+        }
+
+        Self { dest, len: 0 }
+    }
+}
+
+impl fmt::Write for U16CStrWriter<'_> {
+    /// Returns an error if the text did not fit. The part that did fit will still have been
+    /// written.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.dest.is_empty() {
+            return Err(fmt::Error);
+        }
+
+        let mut result = Ok(());
+        for c in s.chars() {
+            let mut utf16_buffer = [0u16; 2];
+            let utf16_chars = c.encode_utf16(&mut utf16_buffer);
+
+            // Make sure there's always room for a null terminator
+            if self.len + utf16_chars.len() >= self.dest.len() {
+                result = Err(fmt::Error);
+                break;
+            }
+
+            for utf16_char in utf16_chars {
+                self.dest[self.len] = *utf16_char as TChar;
+                self.len += 1;
+            }
+        }
+        self.dest[self.len] = 0;
+
+        result
+    }
+}
+
 /// Send+Sync wrapper for these interface pointers.
 #[repr(transparent)]
 pub struct VstPtr<T: vst3_sys::ComInterface + ?Sized> {
@@ -116,8 +221,37 @@ unsafe impl<T: ComInterface + ?Sized> Sync for VstPtr<T> {}
 unsafe impl<T: IUnknown> Send for ObjectPtr<T> {}
 unsafe impl<T: IUnknown> Sync for ObjectPtr<T> {}
 
+/// An [`io::Write`] implementation for a VST3 `IBStream`.
+pub struct IBStreamWriter<'a>(pub &'a vst3_sys::VstPtr<dyn IBStream>);
+
+impl io::Write for IBStreamWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut num_bytes_written = 0;
+        let result = unsafe {
+            self.0.write(
+                buf.as_ptr() as *const c_void,
+                buf.len().min(i32::MAX as usize) as i32,
+                &mut num_bytes_written,
+            )
+        };
+        if result == kResultOk {
+            Ok(num_bytes_written as usize)
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                "Error while writing to the stream",
+            ))
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod miri {
+    use std::fmt::Write;
     use widestring::U16CStr;
 
     use super::*;
@@ -147,4 +281,49 @@ mod miri {
             "Hello"
         );
     }
+
+    #[test]
+    fn u16cstr_writer_overflow() {
+        let mut dest = [0; 9];
+        let result = write!(U16CStrWriter::new(&mut dest), "{}, {}!", "Hello", "world");
+
+        assert!(result.is_err());
+        assert_eq!(
+            unsafe { U16CStr::from_ptr_str(dest.as_ptr() as *const u16) }
+                .to_string()
+                .unwrap(),
+            "Hello, w"
+        );
+    }
+
+    #[test]
+    fn speaker_arrangements_match_sdk() {
+        // These are the `kMono`, `kStereo`, `k50`, `k51`, `k70Music`, `k71Music`, `k71_4`, and
+        // `kAmbi*ACN` arrangements from the VST3 SDK's `vstspeaker.h`
+        let expected: [(ChannelLayout, SpeakerArrangement); 10] = [
+            (ChannelLayout::Mono, 0x80000),
+            (ChannelLayout::Stereo, 0x3),
+            (ChannelLayout::Surround50, 0x37),
+            (ChannelLayout::Surround51, 0x3f),
+            (ChannelLayout::Surround70, 0x637),
+            (ChannelLayout::Surround71, 0x63f),
+            (ChannelLayout::Surround714, 0x2d63f),
+            (ChannelLayout::FirstOrderAmbisonics, 0xf00000),
+            (ChannelLayout::SecondOrderAmbisonics, 0x7c000f00000),
+            (ChannelLayout::ThirdOrderAmbisonics, 0x3ffc000f00000),
+        ];
+
+        for (layout, arrangement) in expected {
+            assert_eq!(
+                speaker_arrangement(layout),
+                arrangement,
+                "{layout:?} does not match the SDK's arrangement"
+            );
+            assert_eq!(
+                speaker_arrangement(layout).count_ones(),
+                layout.num_channels(),
+                "{layout:?} has the wrong number of channels"
+            );
+        }
+    }
 }
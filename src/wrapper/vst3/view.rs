@@ -15,6 +15,7 @@ use super::inner::{Task, WrapperInner};
 use super::util::{ObjectPtr, VstPtr};
 use crate::plugin::vst3::Vst3Plugin;
 use crate::prelude::{Editor, ParentWindowHandle};
+use crate::wrapper::util::panic_guard;
 
 // Alias needed for the VST3 attribute macro
 use vst3_sys as vst3_com;
@@ -208,13 +209,15 @@ impl<P: Vst3Plugin> RunLoopEventHandler<P> {
         // to vtable poitners.
         let event_handler: SharedVstPtr<dyn IEventHandler> =
             unsafe { mem::transmute(&handler.__ieventhandlervptr as *const *const _) };
-        assert_eq!(
-            unsafe {
-                handler
-                    .run_loop
-                    .register_event_handler(event_handler, handler.socket_read_fd)
-            },
-            kResultOk
+        let result = unsafe {
+            handler
+                .run_loop
+                .register_event_handler(event_handler, handler.socket_read_fd)
+        };
+        nih_debug_assert_eq!(
+            result,
+            kResultOk,
+            "The host failed to register the run loop event handler"
         );
 
         handler
@@ -234,16 +237,14 @@ impl<P: Vst3Plugin> RunLoopEventHandler<P> {
         //      being posted yet. In practice this won't cause any issues however.
         let notify_value = 1i8;
         const NOTIFY_VALUE_SIZE: usize = std::mem::size_of::<i8>();
-        assert_eq!(
-            unsafe {
-                libc::write(
-                    self.socket_write_fd,
-                    &notify_value as *const _ as *const c_void,
-                    NOTIFY_VALUE_SIZE,
-                )
-            },
-            NOTIFY_VALUE_SIZE as isize
-        );
+        let num_bytes_written = unsafe {
+            libc::write(
+                self.socket_write_fd,
+                &notify_value as *const _ as *const c_void,
+                NOTIFY_VALUE_SIZE,
+            )
+        };
+        nih_debug_assert_eq!(num_bytes_written, NOTIFY_VALUE_SIZE as isize);
 
         Ok(())
     }
@@ -315,10 +316,21 @@ impl<P: Vst3Plugin> IPlugView for WrapperView<P> {
                 }
             };
 
-            *editor_handle = Some(self.editor.lock().spawn(
-                ParentWindowHandle { handle },
-                self.inner.clone().make_gui_context(),
-            ));
+            let spawned = panic_guard::catch_panic::<P, _>(|| {
+                self.editor.lock().spawn(
+                    ParentWindowHandle { handle },
+                    self.inner.clone().make_gui_context(),
+                )
+            });
+            match spawned {
+                Some(editor) => *editor_handle = Some(editor),
+                None => {
+                    panic_guard::rescue_state(&mut *self.inner.plugin.lock(), || {
+                        self.inner.get_state_object()
+                    });
+                    return kResultFalse;
+                }
+            }
             *self.inner.plug_view.write() = Some(ObjectPtr::from(self));
 
             kResultOk
@@ -490,14 +502,12 @@ impl<P: Vst3Plugin> IEventHandler for RunLoopEventHandler<P> {
 
             let mut notify_value = 1i8;
             const NOTIFY_VALUE_SIZE: usize = std::mem::size_of::<i8>();
-            assert_eq!(
-                libc::read(
-                    self.socket_read_fd,
-                    &mut notify_value as *mut _ as *mut c_void,
-                    NOTIFY_VALUE_SIZE
-                ),
-                NOTIFY_VALUE_SIZE as isize
+            let num_bytes_read = libc::read(
+                self.socket_read_fd,
+                &mut notify_value as *mut _ as *mut c_void,
+                NOTIFY_VALUE_SIZE,
             );
+            nih_debug_assert_eq!(num_bytes_read, NOTIFY_VALUE_SIZE as isize);
         }
     }
 }
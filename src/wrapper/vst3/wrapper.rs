@@ -1,5 +1,7 @@
+use anyhow::Context;
 use std::borrow::Borrow;
 use std::ffi::c_void;
+use std::io::Write;
 use std::mem::{self, MaybeUninit};
 use std::num::NonZeroU32;
 use std::ptr::NonNull;
@@ -8,13 +10,15 @@ use std::sync::Arc;
 use vst3_com::vst::{DataEvent, IProcessContextRequirementsFlags, ProcessModes};
 use vst3_sys::base::{kInvalidArgument, kNoInterface, kResultFalse, kResultOk, tresult, TBool};
 use vst3_sys::base::{IBStream, IPluginBase};
+use vst3_sys::interfaces::IUnknown;
 use vst3_sys::utils::SharedVstPtr;
 use vst3_sys::vst::{
     kNoParamId, kNoParentUnitId, kNoProgramListId, kRootUnitId, Event, EventTypes, IAudioProcessor,
-    IComponent, IEditController, IEventList, IMidiMapping, INoteExpressionController,
-    IParamValueQueue, IParameterChanges, IProcessContextRequirements, IUnitInfo,
-    LegacyMidiCCOutEvent, NoteExpressionTypeInfo, NoteExpressionValueDescription, NoteOffEvent,
-    NoteOnEvent, ParameterFlags, PolyPressureEvent, ProgramListInfo, TChar, UnitInfo,
+    IComponent, IEditController, IEventList, IHostApplication, IMidiMapping,
+    INoteExpressionController, IParamValueQueue, IParameterChanges, IProcessContextRequirements,
+    IUnitInfo, LegacyMidiCCOutEvent, NoteExpressionTypeInfo, NoteExpressionValueDescription,
+    NoteOffEvent, NoteOnEvent, ParameterFlags, PolyPressureEvent, ProgramListInfo,
+    SpeakerArrangement, String128, TChar, UnitInfo,
 };
 use vst3_sys::VST3;
 use widestring::U16CStr;
@@ -22,18 +26,29 @@ use widestring::U16CStr;
 use super::inner::{ProcessEvent, WrapperInner};
 use super::note_expressions::{self, NoteExpressionController};
 use super::util::{
-    u16strlcpy, VstPtr, VST3_MIDI_CCS, VST3_MIDI_NUM_PARAMS, VST3_MIDI_PARAMS_START,
+    speaker_arrangement, u16strlcpy, IBStreamWriter, U16CStrWriter, VstPtr, VST3_MIDI_CCS,
+    VST3_MIDI_NUM_PARAMS, VST3_MIDI_PARAMS_START,
+};
+use super::util::{
+    VST3_FACTORY_PRESETS_PROGRAM_LIST_ID, VST3_MIDI_CHANNELS, VST3_MIDI_PARAMS_END,
+    VST3_PROGRAM_CHANGE_PARAM_ID,
 };
-use super::util::{VST3_MIDI_CHANNELS, VST3_MIDI_PARAMS_END};
 use super::view::WrapperView;
 use crate::prelude::{
-    AuxiliaryBuffers, BufferConfig, MidiConfig, NoteEvent, ParamFlags, ProcessMode, ProcessStatus,
-    SysExMessage, Transport, Vst3Plugin,
+    AuxiliaryBuffers, BufferConfig, ChannelLayout, MidiConfig, NoteEvent, ParamFlags, ProcessMode,
+    ProcessStatus, SysExMessage, Transport, TransportSnapshot, Vst3Plugin,
 };
 use crate::util::permit_alloc;
 use crate::wrapper::state;
+use crate::wrapper::util::analyzer_passthrough::{process_with_passthrough, AnalyzerPassthrough};
 use crate::wrapper::util::buffer_management::{BufferManager, ChannelPointers};
-use crate::wrapper::util::{clamp_input_event_timing, clamp_output_event_timing, process_wrapper};
+use crate::wrapper::util::bypass_crossfade::{self, BypassCrossfader};
+use crate::wrapper::util::fixed_block::FixedBlockAdapter;
+use crate::wrapper::util::panic_guard;
+use crate::wrapper::util::silence_skip;
+use crate::wrapper::util::{
+    clamp_input_event_timing, clamp_output_event_timing, initialize_plugin, process_wrapper,
+};
 
 // Alias needed for the VST3 attribute macro
 use vst3_sys as vst3_com;
@@ -55,6 +70,66 @@ impl<P: Vst3Plugin> Wrapper<P> {
     pub fn new() -> Box<Self> {
         Self::allocate(WrapperInner::new())
     }
+
+    /// Split a state written by `IComponent::get_state()` into the regular JSON state and the data
+    /// for the plugin's `#[persist_streamed]` fields, if it has any.
+    fn split_streamed_state(state: &[u8]) -> anyhow::Result<(&[u8], Vec<(String, Vec<u8>)>)> {
+        let magic_len = state::STREAMED_FIELDS_MAGIC.len();
+        if !state.starts_with(&state::STREAMED_FIELDS_MAGIC) {
+            return Ok((state, Vec::new()));
+        }
+
+        let mut length_bytes = [0u8; 8];
+        let length_bytes_end = magic_len + length_bytes.len();
+        length_bytes.copy_from_slice(
+            state
+                .get(magic_len..length_bytes_end)
+                .context("Unexpected end of state")?,
+        );
+        let json_end = usize::try_from(u64::from_le_bytes(length_bytes))
+            .ok()
+            .and_then(|json_len| length_bytes_end.checked_add(json_len))
+            .context("Invalid state length")?;
+        let json_state = state
+            .get(length_bytes_end..json_end)
+            .context("Unexpected end of state")?;
+
+        let streamed_state = &state[json_end..];
+        if !streamed_state.starts_with(&state::STREAMED_FIELDS_MAGIC) {
+            anyhow::bail!("Missing streamed fields");
+        }
+
+        Ok((
+            json_state,
+            state::read_streamed_fields(&streamed_state[magic_len..])?,
+        ))
+    }
+
+    /// The number of program change parameters. This is one if the plugin has factory presets, and
+    /// zero otherwise.
+    fn num_program_change_params() -> i32 {
+        if P::FACTORY_PRESETS.is_empty() {
+            0
+        } else {
+            1
+        }
+    }
+
+    /// Convert the program change parameter's normalized value to a factory preset index.
+    fn factory_preset_idx(value_normalized: f64) -> usize {
+        let max_idx = P::FACTORY_PRESETS.len().saturating_sub(1);
+        ((value_normalized.clamp(0.0, 1.0) * max_idx as f64).round() as usize).min(max_idx)
+    }
+
+    /// Convert a factory preset index to the program change parameter's normalized value.
+    fn factory_preset_normalized(preset_idx: usize) -> f64 {
+        let max_idx = P::FACTORY_PRESETS.len().saturating_sub(1);
+        if max_idx == 0 {
+            0.0
+        } else {
+            preset_idx as f64 / max_idx as f64
+        }
+    }
 }
 
 impl<P: Vst3Plugin> Drop for Wrapper<P> {
@@ -64,8 +139,25 @@ impl<P: Vst3Plugin> Drop for Wrapper<P> {
 }
 
 impl<P: Vst3Plugin> IPluginBase for Wrapper<P> {
-    unsafe fn initialize(&self, _context: *mut c_void) -> tresult {
-        // We currently don't need or allow any initialization logic
+    unsafe fn initialize(&self, context: *mut c_void) -> tresult {
+        // The correct argument type is missing from the bindings
+        let context: SharedVstPtr<dyn IUnknown> = mem::transmute(context);
+        let host_application = context
+            .upgrade()
+            .and_then(|context| context.cast::<dyn IHostApplication>());
+        if let Some(host_application) = host_application {
+            let mut name: String128 = [0; 128];
+            if host_application.get_name(&mut name) == kResultOk {
+                // The name is not guaranteed to be null terminated if it's exactly 128 characters
+                name[127] = 0;
+                *self.inner.host_name.borrow_mut() =
+                    U16CStr::from_ptr_str(name.as_ptr() as *const u16)
+                        .to_string()
+                        .ok()
+                        .filter(|name| !name.is_empty());
+            }
+        }
+
         kResultOk
     }
 
@@ -382,7 +474,13 @@ impl<P: Vst3Plugin> IComponent for Wrapper<P> {
                 let mut init_context = self.inner.make_init_context();
                 let audio_io_layout = self.inner.current_audio_io_layout.load();
                 let mut plugin = self.inner.plugin.lock();
-                if plugin.initialize(&audio_io_layout, &buffer_config, &mut init_context) {
+                if initialize_plugin(
+                    &mut *plugin,
+                    self.inner.last_initialized_config.load(),
+                    &audio_io_layout,
+                    &buffer_config,
+                    &mut init_context,
+                ) {
                     // NOTE: We don't call `Plugin::reset()` here. The call is done in `set_process()`
                     //       instead. Otherwise we would call the function twice, and `set_process()` needs
                     //       to be called after this function before the plugin may process audio again.
@@ -394,9 +492,47 @@ impl<P: Vst3Plugin> IComponent for Wrapper<P> {
                         buffer_config.max_buffer_size as usize,
                         audio_io_layout,
                     );
+                    *self.inner.fixed_block_adapter.borrow_mut() = P::FIXED_BLOCK_SIZE
+                        .map(|block_size| FixedBlockAdapter::new(block_size, &audio_io_layout));
+                    *self.inner.bypass_crossfader.borrow_mut() =
+                        self.inner.bypass_param.map(|_| {
+                            BypassCrossfader::new(
+                                P::BYPASS_CROSSFADE_MS.unwrap_or_default(),
+                                buffer_config.sample_rate,
+                                buffer_config.max_buffer_size as usize,
+                                audio_io_layout
+                                    .main_output_channels
+                                    .map(NonZeroU32::get)
+                                    .unwrap_or(0) as usize,
+                                self.get_latency_samples(),
+                            )
+                        });
+                    *self.inner.analyzer_passthrough.borrow_mut() = P::ANALYZER_MODE.then(|| {
+                        AnalyzerPassthrough::new(
+                            audio_io_layout
+                                .main_output_channels
+                                .map(NonZeroU32::get)
+                                .unwrap_or(0) as usize,
+                            buffer_config.max_buffer_size as usize,
+                        )
+                    });
+                    self.inner
+                        .param_output_limiter
+                        .borrow_mut()
+                        .initialize(buffer_config.sample_rate);
+                    self.inner
+                        .cv_inputs
+                        .initialize(&audio_io_layout, buffer_config.max_buffer_size as usize);
+                    self.inner
+                        .last_initialized_config
+                        .store(Some((audio_io_layout, buffer_config)));
+                    self.inner.panic_guard.reset();
+                    self.inner.process_timings.reset();
 
                     kResultOk
                 } else {
+                    self.inner.last_initialized_config.store(None);
+
                     kResultFalse
                 }
             }
@@ -449,10 +585,23 @@ impl<P: Vst3Plugin> IComponent for Wrapper<P> {
             return kResultFalse;
         }
 
-        match state::deserialize_json(&read_buffer) {
+        // Plugins with `#[persist_streamed]` fields store the regular state with a length prefix,
+        // followed by the streamed fields. See `get_state()` below.
+        let (json_state, streamed_fields) = match Self::split_streamed_state(&read_buffer) {
+            Ok(split) => split,
+            Err(err) => {
+                nih_debug_assert_failure!("Could not read streamed fields: {:#}", err);
+                return kResultFalse;
+            }
+        };
+
+        match state::deserialize_json(json_state) {
             Some(mut state) => {
                 if self.inner.set_state_inner(&mut state) {
                     nih_trace!("Loaded state ({} bytes)", read_buffer.len());
+                    self.inner
+                        .streamed_fields_loader
+                        .load(self.inner.params.clone(), streamed_fields);
                     kResultOk
                 } else {
                     kResultFalse
@@ -470,9 +619,10 @@ impl<P: Vst3Plugin> IComponent for Wrapper<P> {
         let serialized = state::serialize_json::<P>(
             self.inner.params.clone(),
             state::make_params_iter(&self.inner.param_by_hash, &self.inner.param_id_to_hash),
+            Some(&self.inner.midi_learn),
         );
         match serialized {
-            Ok(serialized) => {
+            Ok(serialized) if self.inner.params.streamed_fields().is_empty() => {
                 let mut num_bytes_written = 0;
                 let result = state.write(
                     serialized.as_ptr() as *const c_void,
@@ -487,6 +637,31 @@ impl<P: Vst3Plugin> IComponent for Wrapper<P> {
 
                 kResultOk
             }
+            Ok(serialized) => {
+                // VST3 streams don't have a length prefix like CLAP streams, so the regular state
+                // needs to be prefixed with its length so we can tell where the large
+                // `#[persist_streamed]` fields start. These are written directly to the stream.
+                let mut writer = IBStreamWriter(&state);
+                let result = writer
+                    .write_all(&state::STREAMED_FIELDS_MAGIC)
+                    .and_then(|_| writer.write_all(&(serialized.len() as u64).to_le_bytes()))
+                    .and_then(|_| writer.write_all(&serialized))
+                    .map_err(anyhow::Error::from)
+                    .and_then(|_| {
+                        state::write_streamed_fields(self.inner.params.as_ref(), &mut writer)
+                    });
+
+                match result {
+                    Ok(()) => {
+                        nih_trace!("Saved state ({} bytes)", serialized.len());
+                        kResultOk
+                    }
+                    Err(err) => {
+                        nih_debug_assert_failure!("Could not save streamed fields: {:#}", err);
+                        kResultFalse
+                    }
+                }
+            }
             Err(err) => {
                 nih_debug_assert_failure!("Could not save state: {:#}", err);
                 kResultFalse
@@ -514,11 +689,13 @@ impl<P: Vst3Plugin> IEditController for Wrapper<P> {
     }
 
     unsafe fn get_parameter_count(&self) -> i32 {
+        let num_params = self.inner.param_hashes.len() as i32 + Self::num_program_change_params();
+
         // We need to add a whole bunch of parameters if the plugin accepts MIDI CCs
         if P::MIDI_INPUT >= MidiConfig::MidiCCs {
-            self.inner.param_hashes.len() as i32 + VST3_MIDI_NUM_PARAMS as i32
+            num_params + VST3_MIDI_NUM_PARAMS as i32
         } else {
-            self.inner.param_hashes.len() as i32
+            num_params
         }
     }
 
@@ -536,11 +713,25 @@ impl<P: Vst3Plugin> IEditController for Wrapper<P> {
         *info = std::mem::zeroed();
         let info = &mut *info;
 
-        // If the parameter is a generated MIDI CC/channel pressure/pitch bend then it needs to be
-        // handled separately
+        // If the parameter is the generated program change parameter or a generated MIDI CC/channel
+        // pressure/pitch bend parameter then it needs to be handled separately
         let num_actual_params = self.inner.param_hashes.len() as i32;
-        if P::MIDI_INPUT >= MidiConfig::MidiCCs && param_index >= num_actual_params {
-            let midi_param_relative_idx = (param_index - num_actual_params) as u32;
+        let num_program_change_params = Self::num_program_change_params();
+        if param_index >= num_actual_params
+            && param_index < num_actual_params + num_program_change_params
+        {
+            info.id = VST3_PROGRAM_CHANGE_PARAM_ID;
+            u16strlcpy(&mut info.title, "Program");
+            u16strlcpy(&mut info.short_title, "Program");
+            info.step_count = P::FACTORY_PRESETS.len().saturating_sub(1) as i32;
+            info.default_normalized_value = 0.0;
+            info.unit_id = kRootUnitId;
+            info.flags = ParameterFlags::kCanAutomate as i32;
+            info.flags |= 1 << 3; // kIsList
+            info.flags |= 1 << 15; // kIsProgramChange
+        } else if P::MIDI_INPUT >= MidiConfig::MidiCCs && param_index >= num_actual_params {
+            let midi_param_relative_idx =
+                (param_index - num_actual_params - num_program_change_params) as u32;
             // This goes up to 130 for the 128 CCs followed by channel pressure and pitch bend
             let midi_cc = midi_param_relative_idx % VST3_MIDI_CCS;
             let midi_channel = midi_param_relative_idx / VST3_MIDI_CCS;
@@ -602,13 +793,22 @@ impl<P: Vst3Plugin> IEditController for Wrapper<P> {
 
         let dest = &mut *(string as *mut [TChar; 128]);
 
+        if !P::FACTORY_PRESETS.is_empty() && id == VST3_PROGRAM_CHANGE_PARAM_ID {
+            let preset = &P::FACTORY_PRESETS[Self::factory_preset_idx(value_normalized)];
+            u16strlcpy(dest, preset.name);
+
+            return kResultOk;
+        }
+
         // TODO: We don't implement these methods at all for our generated MIDI CC parameters,
         //       should be fine right? They should be hidden anyways.
         match self.inner.param_by_hash.get(&id) {
             Some(param_ptr) => {
-                u16strlcpy(
-                    dest,
-                    &param_ptr.normalized_value_to_string(value_normalized as f32, false),
+                // Values that don't fit are truncated, so the result can be ignored
+                let _ = param_ptr.normalized_value_to_buf(
+                    value_normalized as f32,
+                    false,
+                    &mut U16CStrWriter::new(dest),
                 );
 
                 kResultOk
@@ -630,6 +830,19 @@ impl<P: Vst3Plugin> IEditController for Wrapper<P> {
             Err(_) => return kInvalidArgument,
         };
 
+        if !P::FACTORY_PRESETS.is_empty() && id == VST3_PROGRAM_CHANGE_PARAM_ID {
+            return match P::FACTORY_PRESETS
+                .iter()
+                .position(|preset| preset.name == string)
+            {
+                Some(preset_idx) => {
+                    *value_normalized = Self::factory_preset_normalized(preset_idx);
+                    kResultOk
+                }
+                None => kResultFalse,
+            };
+        }
+
         match self.inner.param_by_hash.get(&id) {
             Some(param_ptr) => {
                 let value = match param_ptr.string_to_normalized_value(&string) {
@@ -645,6 +858,10 @@ impl<P: Vst3Plugin> IEditController for Wrapper<P> {
     }
 
     unsafe fn normalized_param_to_plain(&self, id: u32, value_normalized: f64) -> f64 {
+        if !P::FACTORY_PRESETS.is_empty() && id == VST3_PROGRAM_CHANGE_PARAM_ID {
+            return Self::factory_preset_idx(value_normalized) as f64;
+        }
+
         match self.inner.param_by_hash.get(&id) {
             Some(param_ptr) => param_ptr.preview_plain(value_normalized as f32) as f64,
             _ => value_normalized,
@@ -652,6 +869,10 @@ impl<P: Vst3Plugin> IEditController for Wrapper<P> {
     }
 
     unsafe fn plain_param_to_normalized(&self, id: u32, plain_value: f64) -> f64 {
+        if !P::FACTORY_PRESETS.is_empty() && id == VST3_PROGRAM_CHANGE_PARAM_ID {
+            return Self::factory_preset_normalized(plain_value.max(0.0).round() as usize);
+        }
+
         match self.inner.param_by_hash.get(&id) {
             Some(param_ptr) => param_ptr.preview_normalized(plain_value as f32) as f64,
             _ => plain_value,
@@ -659,6 +880,10 @@ impl<P: Vst3Plugin> IEditController for Wrapper<P> {
     }
 
     unsafe fn get_param_normalized(&self, id: u32) -> f64 {
+        if !P::FACTORY_PRESETS.is_empty() && id == VST3_PROGRAM_CHANGE_PARAM_ID {
+            return Self::factory_preset_normalized(self.inner.current_factory_preset.load());
+        }
+
         match self.inner.param_by_hash.get(&id) {
             Some(param_ptr) => param_ptr.modulated_normalized_value() as f64,
             _ => 0.5,
@@ -666,6 +891,24 @@ impl<P: Vst3Plugin> IEditController for Wrapper<P> {
     }
 
     unsafe fn set_param_normalized(&self, id: u32, value: f64) -> tresult {
+        // Selecting a program loads the corresponding factory preset. This is always done from the
+        // edit controller since it can't be done on the audio thread. If the plugin is currently
+        // processing audio then the state will be restored at the end of the current process call.
+        if !P::FACTORY_PRESETS.is_empty() && id == VST3_PROGRAM_CHANGE_PARAM_ID {
+            let preset_idx = Self::factory_preset_idx(value);
+            if self.inner.current_factory_preset.swap(preset_idx) == preset_idx {
+                return kResultOk;
+            }
+
+            return match P::FACTORY_PRESETS[preset_idx].state() {
+                Some(state) => {
+                    self.inner.set_state_object_from_gui(state);
+                    kResultOk
+                }
+                None => kResultFalse,
+            };
+        }
+
         // If the plugin is currently processing audio, then this parameter change will also be sent
         // to the process function
         if self.inner.is_processing.load(Ordering::SeqCst) {
@@ -716,6 +959,19 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
             return kInvalidArgument;
         }
 
+        // Ports with an explicit channel layout need to match the speaker arrangement exactly. For
+        // all other ports we completely ignore the speaker arrangements and only look at the
+        // channel counts. This may cause issues at some point, but it works for now.
+        let arrangement_matches =
+            |arrangement: SpeakerArrangement,
+             num_channels: u32,
+             channel_layout: Option<ChannelLayout>| {
+                match channel_layout {
+                    Some(channel_layout) => arrangement == speaker_arrangement(channel_layout),
+                    None => arrangement.count_ones() == num_channels,
+                }
+            };
+
         // NIH-plug no longer supports flexible IO layouts. Instead we'll try to find an audio IO
         // layout that matches the host's requested layout.
         let matching_layout = P::AUDIO_IO_LAYOUTS
@@ -738,37 +994,46 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
                     return false;
                 }
 
-                // NOTE: We completely ignore the speaker arrangements and only look at the channel
-                //       counts here. This may cause issues at some point, but it works for now.
+                let channel_layouts = &layout.channel_layouts;
                 let has_main_input = layout.main_input_channels.is_some();
                 let aux_input_start_idx = if has_main_input { 0 } else { 1 };
                 if has_main_input
-                    && (*inputs).count_ones() != layout.main_input_channels.unwrap().get()
+                    && !arrangement_matches(
+                        *inputs,
+                        layout.main_input_channels.unwrap().get(),
+                        channel_layouts.main_input,
+                    )
                 {
                     return false;
                 }
                 for (aux_input_idx, channel_count) in layout.aux_input_ports.iter().enumerate() {
-                    if (*inputs.add(aux_input_idx + aux_input_start_idx)).count_ones()
-                        != channel_count.get()
-                    {
+                    if !arrangement_matches(
+                        *inputs.add(aux_input_idx + aux_input_start_idx),
+                        channel_count.get(),
+                        channel_layouts.aux_inputs.get(aux_input_idx).copied(),
+                    ) {
                         return false;
                     }
                 }
 
                 let has_main_output = layout.main_output_channels.is_some();
                 let aux_output_start_idx = if has_main_output { 0 } else { 1 };
-                if (*outputs).count_ones()
-                    != layout
+                if !arrangement_matches(
+                    *outputs,
+                    layout
                         .main_output_channels
                         .map(NonZeroU32::get)
-                        .unwrap_or_default()
-                {
+                        .unwrap_or_default(),
+                    channel_layouts.main_output,
+                ) {
                     return false;
                 }
                 for (aux_output_idx, channel_count) in layout.aux_output_ports.iter().enumerate() {
-                    if (*outputs.add(aux_output_idx + aux_output_start_idx)).count_ones()
-                        != channel_count.get()
-                    {
+                    if !arrangement_matches(
+                        *outputs.add(aux_output_idx + aux_output_start_idx),
+                        channel_count.get(),
+                        channel_layouts.aux_outputs.get(aux_output_idx).copied(),
+                    ) {
                         return false;
                     }
                 }
@@ -815,14 +1080,21 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
         };
 
         let current_audio_io_layout = self.inner.current_audio_io_layout.load();
-        let num_channels = if dir == vst3_sys::vst::BusDirections::kInput as i32 {
+        let channel_layouts = &current_audio_io_layout.channel_layouts;
+        let (num_channels, channel_layout) = if dir == vst3_sys::vst::BusDirections::kInput as i32 {
             let has_main_input = current_audio_io_layout.main_input_channels.is_some();
             let aux_input_start_idx = if has_main_input { 1 } else { 0 };
             let aux_input_idx = (index - aux_input_start_idx).max(0) as usize;
             if index == 0 && has_main_input {
-                current_audio_io_layout.main_input_channels.unwrap().get()
+                (
+                    current_audio_io_layout.main_input_channels.unwrap().get(),
+                    channel_layouts.main_input,
+                )
             } else if aux_input_idx < current_audio_io_layout.aux_input_ports.len() {
-                current_audio_io_layout.aux_input_ports[aux_input_idx].get()
+                (
+                    current_audio_io_layout.aux_input_ports[aux_input_idx].get(),
+                    channel_layouts.aux_inputs.get(aux_input_idx).copied(),
+                )
             } else {
                 return kInvalidArgument;
             }
@@ -831,16 +1103,25 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
             let aux_output_start_idx = if has_main_output { 1 } else { 0 };
             let aux_output_idx = (index - aux_output_start_idx).max(0) as usize;
             if index == 0 && has_main_output {
-                current_audio_io_layout.main_output_channels.unwrap().get()
+                (
+                    current_audio_io_layout.main_output_channels.unwrap().get(),
+                    channel_layouts.main_output,
+                )
             } else if aux_output_idx < current_audio_io_layout.aux_output_ports.len() {
-                current_audio_io_layout.aux_output_ports[aux_output_idx].get()
+                (
+                    current_audio_io_layout.aux_output_ports[aux_output_idx].get(),
+                    channel_layouts.aux_outputs.get(aux_output_idx).copied(),
+                )
             } else {
                 return kInvalidArgument;
             }
         } else {
             return kInvalidArgument;
         };
-        let channel_map = channel_count_to_map(num_channels);
+        let channel_map = match channel_layout {
+            Some(channel_layout) => speaker_arrangement(channel_layout),
+            None => channel_count_to_map(num_channels),
+        };
 
         nih_debug_assert_eq!(num_channels, channel_map.count_ones());
         *arr = channel_map;
@@ -857,7 +1138,8 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
     }
 
     unsafe fn get_latency_samples(&self) -> u32 {
-        self.inner.current_latency.load(Ordering::SeqCst)
+        // The buffering for `P::FIXED_BLOCK_SIZE` adds another block of latency
+        self.inner.current_latency.load(Ordering::SeqCst) + P::FIXED_BLOCK_SIZE.unwrap_or(0)
     }
 
     unsafe fn setup_processing(&self, setup: *const vst3_sys::vst::ProcessSetup) -> tresult {
@@ -900,6 +1182,17 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
 
         // Always reset the processing status when the plugin gets activated or deactivated
         self.inner.last_process_status.store(ProcessStatus::Normal);
+        self.inner.transport_tracker.borrow_mut().reset();
+        self.inner.silence_skipper.borrow_mut().reset();
+        if let Some(adapter) = self.inner.fixed_block_adapter.borrow_mut().as_mut() {
+            adapter.reset();
+        }
+        if let (Some(crossfader), Some(bypass_param)) = (
+            self.inner.bypass_crossfader.borrow_mut().as_mut(),
+            self.inner.bypass_param,
+        ) {
+            crossfader.reset(bypass_crossfade::is_bypassed(bypass_param));
+        }
         self.inner.is_processing.store(state, Ordering::SeqCst);
 
         // This function is also used to reset buffers on the plugin, so we should do the same
@@ -921,7 +1214,7 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
                 }
             };
 
-            process_wrapper(|| plugin.reset());
+            process_wrapper("IAudioProcessor::setProcessing()", || plugin.reset());
         }
 
         // We don't have any special handling for suspending and resuming plugins, yet
@@ -935,7 +1228,7 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
 
         // Panic on allocations if the `assert_process_allocs` feature has been enabled, and make
         // sure that FTZ is set up correctly
-        process_wrapper(|| {
+        process_wrapper("IAudioProcessor::process()", || {
             // We need to handle incoming automation first
             let data = &*data;
             let sample_rate = self
@@ -957,6 +1250,10 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
             let current_audio_io_layout = self.inner.current_audio_io_layout.load();
             let has_main_input = current_audio_io_layout.main_input_channels.is_some();
             let has_main_output = current_audio_io_layout.main_output_channels.is_some();
+            let num_main_input_channels = current_audio_io_layout
+                .main_input_channels
+                .map(NonZeroU32::get)
+                .unwrap_or(0) as usize;
             let aux_input_start_idx = if has_main_input { 1 } else { 0 };
             let aux_output_start_idx = if has_main_output { 1 } else { 0 };
 
@@ -995,6 +1292,13 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
                             continue;
                         }
 
+                        // Program changes are handled by the edit controller on the main thread
+                        if !P::FACTORY_PRESETS.is_empty()
+                            && param_hash == VST3_PROGRAM_CHANGE_PARAM_ID
+                        {
+                            continue;
+                        }
+
                         let mut sample_offset = 0i32;
                         let mut value = 0.0f64;
                         for change_idx in 0..num_changes {
@@ -1025,7 +1329,7 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
                                     let midi_cc = (midi_param_relative_idx % VST3_MIDI_CCS) as u8;
                                     let midi_channel =
                                         (midi_param_relative_idx / VST3_MIDI_CCS) as u8;
-                                    process_events.push(ProcessEvent::NoteEvent(match midi_cc {
+                                    let note_event = match midi_cc {
                                         // kAfterTouch
                                         128 => NoteEvent::MidiChannelPressure {
                                             timing,
@@ -1044,7 +1348,31 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
                                             cc: n,
                                             value,
                                         },
-                                    }));
+                                    };
+
+                                    // CCs mapped through MIDI learn are turned into parameter
+                                    // changes instead
+                                    match self.inner.apply_midi_learn(&note_event) {
+                                        Some((hash, normalized_value))
+                                            if P::SAMPLE_ACCURATE_AUTOMATION =>
+                                        {
+                                            process_events.push(ProcessEvent::ParameterChange {
+                                                timing,
+                                                hash,
+                                                normalized_value,
+                                            });
+                                        }
+                                        Some((hash, normalized_value)) => {
+                                            self.inner.set_normalized_value_by_hash(
+                                                hash,
+                                                normalized_value,
+                                                Some(sample_rate),
+                                            );
+                                        }
+                                        None => {
+                                            process_events.push(ProcessEvent::NoteEvent(note_event))
+                                        }
+                                    }
                                 } else if P::SAMPLE_ACCURATE_AUTOMATION {
                                     process_events.push(ProcessEvent::ParameterChange {
                                         timing,
@@ -1339,6 +1667,7 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
                         // https://steinbergmedia.github.io/vst3_doc/vstinterfaces/structSteinberg_1_1Vst_1_1ProcessContext.html
                         transport.playing = context.state & (1 << 1) != 0; // kPlaying
                         transport.recording = context.state & (1 << 3) != 0; // kRecording
+                        transport.loop_active = Some(context.state & (1 << 2) != 0); // kCycleActive
                         if context.state & (1 << 10) != 0 {
                             // kTempoValid
                             transport.tempo = Some(context.tempo);
@@ -1388,6 +1717,13 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
                                 Some((context.cycle_start_music, context.cycle_end_music));
                         }
                     }
+                    self.inner
+                        .transport_tracker
+                        .borrow_mut()
+                        .update(&mut transport, block_len);
+                    self.inner
+                        .last_transport
+                        .store(Some(TransportSnapshot::new(&transport)));
 
                     let result = if buffer_is_valid {
                         // NOTE: `parking_lot`'s mutexes sometimes allocate because of their use of
@@ -1397,10 +1733,134 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
                             inputs: buffers.aux_inputs,
                             outputs: buffers.aux_outputs,
                         };
-                        let mut context = self.inner.make_process_context(transport);
-                        let result = plugin.process(buffers.main_buffer, &mut aux, &mut context);
-                        self.inner.last_process_status.store(result);
-                        result
+                        let mut fixed_block_adapter = self.inner.fixed_block_adapter.borrow_mut();
+                        let mut bypass_crossfader = self.inner.bypass_crossfader.borrow_mut();
+                        if let Some(crossfader) = bypass_crossfader.as_mut() {
+                            crossfader.set_latency(self.get_latency_samples());
+                            crossfader.store_dry(buffers.main_buffer, num_main_input_channels);
+                        }
+                        let mut silence_skipper = self.inner.silence_skipper.borrow_mut();
+                        let skip_block = silence_skipper.should_skip::<P>(
+                            || {
+                                // Audio buffered by the fixed block adapter needs to be sent to
+                                // the host before the plugin can be skipped
+                                silence_skip::input_is_silent(
+                                    buffers.main_buffer,
+                                    num_main_input_channels,
+                                    aux.inputs,
+                                    !self.inner.input_events.borrow().is_empty(),
+                                ) && !fixed_block_adapter
+                                    .as_ref()
+                                    .is_some_and(|adapter| adapter.has_buffered_output())
+                            },
+                            block_len,
+                        );
+                        let result = if self.inner.panic_guard.has_panicked() {
+                            None
+                        } else if skip_block {
+                            panic_guard::silence_outputs(buffers.main_buffer, aux.outputs);
+                            Some(ProcessStatus::Normal)
+                        } else {
+                            let result = self.inner.panic_guard.catch_process::<P, _>(|| {
+                                self.inner.process_timings.time::<P, _>(
+                                    block_len,
+                                    sample_rate,
+                                    || match fixed_block_adapter.as_mut() {
+                                        Some(adapter) => adapter.process(
+                                            buffers.main_buffer,
+                                            &mut aux,
+                                            &self.inner.input_events,
+                                            |buffer, aux, output_offset| {
+                                                let num_output_events =
+                                                    self.inner.output_events.borrow().len();
+                                                self.inner
+                                                    .cv_inputs
+                                                    .copy_inputs(aux.inputs, buffer.samples());
+                                                self.inner.flush_param_output(buffer.samples());
+                                                let mut context = self
+                                                    .inner
+                                                    .make_process_context(transport.clone());
+                                                let result = process_with_passthrough(
+                                                    self.inner
+                                                        .analyzer_passthrough
+                                                        .borrow_mut()
+                                                        .as_mut(),
+                                                    buffer,
+                                                    |buffer| {
+                                                        plugin.process(buffer, aux, &mut context)
+                                                    },
+                                                );
+                                                drop(context);
+
+                                                // This block's output starts at `output_offset`,
+                                                // so its output events need to be moved there
+                                                for event in self
+                                                    .inner
+                                                    .output_events
+                                                    .borrow_mut()
+                                                    .iter_mut()
+                                                    .skip(num_output_events)
+                                                {
+                                                    event.add_timing(output_offset as u32);
+                                                }
+
+                                                result
+                                            },
+                                        ),
+                                        None => {
+                                            self.inner.cv_inputs.copy_inputs(
+                                                aux.inputs,
+                                                buffers.main_buffer.samples(),
+                                            );
+                                            self.inner
+                                                .flush_param_output(buffers.main_buffer.samples());
+                                            let mut context =
+                                                self.inner.make_process_context(transport);
+                                            process_with_passthrough(
+                                                self.inner
+                                                    .analyzer_passthrough
+                                                    .borrow_mut()
+                                                    .as_mut(),
+                                                buffers.main_buffer,
+                                                |buffer| {
+                                                    plugin.process(buffer, &mut aux, &mut context)
+                                                },
+                                            )
+                                        }
+                                    },
+                                )
+                            });
+                            if result.is_none() {
+                                panic_guard::rescue_state(&mut *plugin, || {
+                                    self.inner.get_state_object()
+                                });
+                            }
+
+                            result
+                        };
+
+                        match result {
+                            Some(result) => {
+                                if let (Some(crossfader), Some(bypass_param)) =
+                                    (bypass_crossfader.as_mut(), self.inner.bypass_param)
+                                {
+                                    crossfader.apply(
+                                        buffers.main_buffer,
+                                        bypass_crossfade::is_bypassed(bypass_param),
+                                    );
+                                }
+
+                                self.inner.last_process_status.store(result);
+                                silence_skipper.update(result, || {
+                                    silence_skip::output_is_silent(buffers.main_buffer, aux.outputs)
+                                });
+                                result
+                            }
+                            None => {
+                                panic_guard::silence_outputs(buffers.main_buffer, aux.outputs);
+                                ProcessStatus::Normal
+                            }
+                        }
                     } else {
                         ProcessStatus::Normal
                     };
@@ -1415,6 +1875,33 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
                     }
                 };
 
+                // Apply the parameter changes made by the plugin during this block and send them to
+                // the host
+                while let Some((param_hash, normalized_value)) =
+                    self.inner.output_param_changes.pop()
+                {
+                    self.inner.set_normalized_value_by_hash(
+                        param_hash,
+                        normalized_value,
+                        Some(sample_rate),
+                    );
+
+                    if let Some(param_changes) = data.output_param_changes.upgrade() {
+                        let mut queue_idx = 0;
+                        if let Some(param_queue) = param_changes
+                            .add_parameter_data(&param_hash, &mut queue_idx)
+                            .upgrade()
+                        {
+                            let mut point_idx = 0;
+                            param_queue.add_point(
+                                block_start as i32,
+                                normalized_value as f64,
+                                &mut point_idx,
+                            );
+                        }
+                    }
+                }
+
                 // Send any events output by the plugin during the process cycle
                 if let Some(events) = data.output_events.upgrade() {
                     let mut output_events = self.inner.output_events.borrow_mut();
@@ -1808,7 +2295,11 @@ impl<P: Vst3Plugin> IUnitInfo for Wrapper<P> {
                 info.id = unit_id;
                 info.parent_unit_id = unit_info.parent_id;
                 u16strlcpy(&mut info.name, &unit_info.name);
-                info.program_list_id = kNoProgramListId;
+                info.program_list_id = if unit_id == kRootUnitId && !P::FACTORY_PRESETS.is_empty() {
+                    VST3_FACTORY_PRESETS_PROGRAM_LIST_ID
+                } else {
+                    kNoProgramListId
+                };
 
                 kResultOk
             }
@@ -1817,25 +2308,43 @@ impl<P: Vst3Plugin> IUnitInfo for Wrapper<P> {
     }
 
     unsafe fn get_program_list_count(&self) -> i32 {
-        // TODO: Do we want program lists? Probably not, CLAP doesn't even support them.
-        0
+        // The factory presets are the only program list we expose
+        Self::num_program_change_params()
     }
 
-    unsafe fn get_program_list_info(
-        &self,
-        _list_index: i32,
-        _info: *mut ProgramListInfo,
-    ) -> tresult {
-        kInvalidArgument
+    unsafe fn get_program_list_info(&self, list_index: i32, info: *mut ProgramListInfo) -> tresult {
+        check_null_ptr!(info);
+
+        if P::FACTORY_PRESETS.is_empty() || list_index != 0 {
+            return kInvalidArgument;
+        }
+
+        *info = mem::zeroed();
+
+        let info = &mut *info;
+        info.id = VST3_FACTORY_PRESETS_PROGRAM_LIST_ID;
+        u16strlcpy(&mut info.name, "Factory Presets");
+        info.program_count = P::FACTORY_PRESETS.len() as i32;
+
+        kResultOk
     }
 
-    unsafe fn get_program_name(
-        &self,
-        _list_id: i32,
-        _program_index: i32,
-        _name: *mut u16,
-    ) -> tresult {
-        kInvalidArgument
+    unsafe fn get_program_name(&self, list_id: i32, program_index: i32, name: *mut u16) -> tresult {
+        check_null_ptr!(name);
+
+        if list_id != VST3_FACTORY_PRESETS_PROGRAM_LIST_ID || program_index < 0 {
+            return kInvalidArgument;
+        }
+
+        match P::FACTORY_PRESETS.get(program_index as usize) {
+            Some(preset) => {
+                let dest = &mut *(name as *mut [TChar; 128]);
+                u16strlcpy(dest, preset.name);
+
+                kResultOk
+            }
+            None => kInvalidArgument,
+        }
     }
 
     unsafe fn get_program_info(
@@ -0,0 +1,81 @@
+//! Integration tests that load a compiled `.clap` bundle through NIH-plug's CLAP wrapper. Build a
+//! bundle first, and then point the `NIH_PLUG_TEST_CLAP_BUNDLE` environment variable at it:
+//!
+//! ```shell
+//! cargo xtask bundle gain --release
+//! NIH_PLUG_TEST_CLAP_BUNDLE=target/bundled/gain.clap cargo test --features test_clap_host --test clap_bundle -- --ignored
+//! ```
+//!
+//! These tests are marked as ignored since they need a bundle, and they fail when they are run
+//! without the environment variable. CI runs them against the gain example.
+
+#![cfg(feature = "test_clap_host")]
+
+use nih_plug::test::ClapTestHost;
+
+const BUNDLE_ENV_VAR: &str = "NIH_PLUG_TEST_CLAP_BUNDLE";
+
+fn load_bundle() -> ClapTestHost {
+    let bundle_path = std::env::var_os(BUNDLE_ENV_VAR)
+        .unwrap_or_else(|| panic!("{BUNDLE_ENV_VAR} should point to a compiled .clap bundle"));
+
+    ClapTestHost::load(bundle_path).expect("Could not load the bundle")
+}
+
+#[test]
+#[ignore = "needs NIH_PLUG_TEST_CLAP_BUNDLE"]
+fn params_are_exposed() {
+    let host = load_bundle();
+
+    for param in host.params() {
+        let value = host
+            .param_value(param.id)
+            .unwrap_or_else(|| panic!("Could not get the value for '{}'", param.name));
+        assert!(
+            (param.min_value..=param.max_value).contains(&value),
+            "'{}' is out of range",
+            param.name
+        );
+    }
+}
+
+#[test]
+#[ignore = "needs NIH_PLUG_TEST_CLAP_BUNDLE"]
+fn state_round_trip() {
+    let mut host = load_bundle();
+
+    let params = host.params();
+    host.activate(44100.0, 512).unwrap();
+    for param in &params {
+        host.schedule_parameter_change(0, param.id, (param.min_value + param.max_value) / 2.0);
+    }
+    host.process(&mut [vec![0.0; 512], vec![0.0; 512]]).unwrap();
+
+    let values: Vec<Option<f64>> = params
+        .iter()
+        .map(|param| host.param_value(param.id))
+        .collect();
+    let state = host.save_state().unwrap();
+    host.deactivate();
+
+    let mut other_host = load_bundle();
+    other_host.load_state(&state).unwrap();
+    let loaded_values: Vec<Option<f64>> = params
+        .iter()
+        .map(|param| other_host.param_value(param.id))
+        .collect();
+    assert_eq!(loaded_values, values);
+}
+
+#[test]
+#[ignore = "needs NIH_PLUG_TEST_CLAP_BUNDLE"]
+fn process_produces_finite_output() {
+    let mut host = load_bundle();
+
+    host.activate(48000.0, 256).unwrap();
+    for block_size in [256, 1, 100, 255] {
+        let mut main_io = vec![vec![0.5; block_size]; 2];
+        host.process(&mut main_io).unwrap();
+        assert!(main_io.iter().flatten().all(|sample| sample.is_finite()));
+    }
+}